@@ -0,0 +1,99 @@
+//! How long drawing the board takes, without a window: working out what each square shows and
+//! painting it onto a canvas that only records the calls. Run with `cargo bench`.
+
+use chess_template::{Game, Position};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use vprytz_chess_gui::board::{captures, parse_move};
+use vprytz_chess_gui::render::{self, BoardState, MoveMarkers, Palette, Recorder};
+
+fn render(state: &BoardState) -> Recorder {
+    let mut recorder = Recorder::default();
+    render::draw_board(&mut recorder, &render::squares(state)).unwrap();
+    recorder
+}
+
+fn start_position(c: &mut Criterion) {
+    let state = BoardState {
+        board: Game::new().get_board(),
+        pieces_hidden: false,
+        dots_hidden: false,
+        selected: None,
+        highlighted: &[],
+        attacked: &[],
+        hanging: &[],
+        dots: &[],
+        captures: &[],
+        markers: MoveMarkers::default(),
+        repetition: None,
+        check_pulse: None,
+        flipped: false,
+        cursor: None,
+        palette: Palette::Wood,
+        heat: None,
+    };
+    c.bench_function("start position", |b| b.iter(|| render(black_box(&state))));
+}
+
+fn midgame(c: &mut Criterion) {
+    let mut game = Game::new();
+    for text in [
+        "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "d2d3", "f8c5",
+    ] {
+        let (from, to, _) = parse_move(text).unwrap();
+        game.make_move_pos(from, to).unwrap();
+    }
+    // the knight on f3 picked up, with a premove queued
+    let selected = parse_move("f3g5").unwrap().0;
+    let dots = game.get_possible_moves(selected, 0);
+    let captures = captures(&game.get_board(), selected, &dots);
+    let (from, to, _) = parse_move("e1g1").unwrap();
+    let state = BoardState {
+        board: game.get_board(),
+        pieces_hidden: false,
+        dots_hidden: false,
+        selected: Some(selected),
+        highlighted: &[from, to],
+        attacked: &[],
+        hanging: &[],
+        dots: &dots,
+        captures: &captures,
+        markers: MoveMarkers::default(),
+        repetition: dots.first().copied(),
+        check_pulse: None,
+        flipped: false,
+        cursor: None,
+        palette: Palette::Wood,
+        heat: None,
+    };
+    c.bench_function("midgame with dots", |b| {
+        b.iter(|| render(black_box(&state)))
+    });
+}
+
+fn every_square_highlighted(c: &mut Criterion) {
+    let all = (0..64)
+        .map(|idx| Position::new(idx / 8, idx % 8).unwrap())
+        .collect::<Vec<Position>>();
+    let state = BoardState {
+        board: Game::new().get_board(),
+        pieces_hidden: false,
+        dots_hidden: false,
+        selected: None,
+        highlighted: &all,
+        attacked: &all,
+        hanging: &[],
+        dots: &all,
+        captures: &[],
+        markers: MoveMarkers::default(),
+        repetition: None,
+        check_pulse: None,
+        flipped: false,
+        cursor: None,
+        palette: Palette::Wood,
+        heat: None,
+    };
+    c.bench_function("64 highlights", |b| b.iter(|| render(black_box(&state))));
+}
+
+criterion_group!(benches, start_position, midgame, every_square_highlighted);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+//! Throws arbitrary frames at the message decoder, as if read from the socket. Decoding must
+//! never panic, and anything understood must come out the same after being sent on again.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vprytz_chess_gui::protocol::{decode, encode, Message, MSG_SIZE};
+
+fuzz_target!(|frame: &[u8]| {
+    // the network thread reads exactly one frame at a time
+    let frame = &frame[..frame.len().min(MSG_SIZE)];
+    let text = match decode(frame) {
+        Some(text) => text,
+        None => return,
+    };
+    if let Ok(msg) = Message::parse(&text) {
+        let again = decode(&encode(&msg.to_string())).unwrap();
+        assert!(
+            Message::parse(&again) == Ok(msg),
+            "{:?} became {:?}",
+            text,
+            again
+        );
+    }
+});
@@ -0,0 +1,48 @@
+//! Adds the messages in transcripts written with `--record` to the fuzzing corpus, one frame
+//! per file, so the fuzzer starts from what real games send.
+//!
+//! cargo run --bin seed_corpus -- corpus/decode net.jsonl [more.jsonl ...]
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+use vprytz_chess_gui::protocol::encode;
+use vprytz_chess_gui::transcript::{self, Entry};
+
+fn main() {
+    let args = env::args().skip(1).map(PathBuf::from).collect::<Vec<_>>();
+    let (corpus, transcripts) = match args.split_first() {
+        Some((corpus, transcripts)) if !transcripts.is_empty() => (corpus, transcripts),
+        _ => {
+            eprintln!("usage: seed_corpus <corpus dir> <transcript>...");
+            process::exit(2);
+        }
+    };
+
+    let mut added = 0;
+    for path in transcripts {
+        let (_, entries) = transcript::load(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        for entry in entries {
+            if let Entry::Message { text, .. } = entry {
+                add(corpus, &encode(&text));
+                added += 1;
+            }
+        }
+    }
+    println!("Added {} messages to {}", added, corpus.display());
+}
+
+/// Writes a frame named after its hash, so the same message seen twice is only kept once.
+fn add(corpus: &Path, frame: &[u8]) {
+    let mut hasher = DefaultHasher::new();
+    frame.hash(&mut hasher);
+    let path = corpus.join(format!("{:016x}", hasher.finish()));
+    if let Err(err) = fs::write(&path, frame) {
+        eprintln!("Couldn't write {}: {}", path.display(), err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,415 @@
+/**
+ * A small chess engine for the computer opponent.
+ *
+ * It's a plain alpha-beta search over material and piece-square tables, using iterative
+ * deepening so that it always has a move ready when its time runs out. It finds one best move
+ * per search, so the analysis board's few best moves are found one after another, each search
+ * leaving out the moves found before it.
+ */
+use crate::board::{king_attacked, piece_value};
+use crate::bot::{legal_moves, MovePicker};
+use chess_template::{Colour, Game, PieceType, Position};
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Lowest and highest difficulty. The difficulty is the depth the engine searches to, in plies.
+pub const MIN_DIFFICULTY: u8 = 1;
+pub const MAX_DIFFICULTY: u8 = 8;
+
+/// Difficulty and time limit per move, in seconds, used unless the player picks others.
+pub const DEFAULT_DIFFICULTY: u8 = 3;
+pub const DEFAULT_TIME_LIMIT: u64 = 5;
+
+/// Score of being checkmated, larger than any material difference.
+const MATE: i32 = 100_000;
+
+/// When a search has to stop: once its deadline passes or it's cancelled, if it has either.
+#[derive(Clone, Copy, Default)]
+struct Limit<'a> {
+    deadline: Option<Instant>,
+    cancelled: Option<&'a AtomicBool>,
+}
+
+impl Limit<'_> {
+    fn reached(self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self
+                .cancelled
+                .is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+    }
+}
+
+/// Bonuses for pawns and knights on each square, from White's side of the board. Row 0 is the
+/// eighth rank, the same as on the board itself.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+/// Bishops and queens just like being near the centre.
+#[rustfmt::skip]
+const CENTRE_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+/// The bonuses for a piece of `piece_type` on each square, from White's side of the board, or
+/// None for the pieces that don't mind where they stand.
+pub fn piece_square_table(piece_type: PieceType) -> Option<&'static [i32; 64]> {
+    match piece_type {
+        PieceType::Pawn => Some(&PAWN_TABLE),
+        PieceType::Knight => Some(&KNIGHT_TABLE),
+        PieceType::Bishop | PieceType::Queen => Some(&CENTRE_TABLE),
+        PieceType::Rook | PieceType::King => None,
+    }
+}
+
+/// The bonus for a `colour` piece of `piece_type` on the square at `idx` on the board.
+pub fn square_bonus(piece_type: PieceType, colour: Colour, idx: usize) -> i32 {
+    // the tables are from White's side, so flip them vertically for Black
+    let square = match colour {
+        Colour::White => idx,
+        Colour::Black => (7 - idx / 8) * 8 + idx % 8,
+    };
+    piece_square_table(piece_type).map_or(0, |table| table[square])
+}
+
+/// Turns a time limit in seconds, as asked for or kept in the config file, into the engine's.
+/// 0 means no limit.
+pub fn time_limit(secs: u64) -> Option<Duration> {
+    match secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// The computer opponent.
+pub struct Ai {
+    difficulty: u8,               // search depth in plies
+    time_limit: Option<Duration>, // longest the engine may think about a move
+}
+
+impl Ai {
+    pub fn new(difficulty: u8, time_limit: Option<Duration>) -> Ai {
+        Ai {
+            difficulty: difficulty.clamp(MIN_DIFFICULTY, MAX_DIFFICULTY),
+            time_limit,
+        }
+    }
+}
+
+impl MovePicker for Ai {
+    fn pick(&mut self, game: &Game, progress: &mut dyn FnMut(u32)) -> Option<(Position, Position)> {
+        let limit = Limit {
+            deadline: self.time_limit.map(|limit| Instant::now() + limit),
+            cancelled: None,
+        };
+        let mut moves = ordered_moves(game);
+        let mut best = *moves.first()?;
+
+        // search one ply deeper at a time, keeping the best move of the last finished depth
+        for depth in 1..=self.difficulty as u32 {
+            progress(depth);
+
+            match search_root(game, &moves, depth, limit) {
+                Some((found, _)) => best = found,
+                // out of time
+                None => break,
+            }
+
+            // try the best move first next time, it makes the cut-offs come sooner
+            if let Some(idx) = moves.iter().position(|&m| m == best) {
+                moves[..=idx].rotate_right(1);
+            }
+        }
+
+        Some(best)
+    }
+
+    fn set_difficulty(&mut self, level: u8) {
+        self.difficulty = level.clamp(MIN_DIFFICULTY, MAX_DIFFICULTY);
+    }
+}
+
+/// Finds the best of `moves` at the given depth and its score, or `None` if the search had to
+/// stop first.
+fn search_root(
+    game: &Game,
+    moves: &[(Position, Position)],
+    depth: u32,
+    limit: Limit,
+) -> Option<((Position, Position), i32)> {
+    let mut alpha = -MATE - 1;
+    let mut best = None;
+
+    for &(from, to) in moves {
+        let mut child = game.clone();
+        if child.make_move_pos(from, to).is_err() {
+            continue;
+        }
+
+        let score = -negamax(&child, depth - 1, -MATE - 1, -alpha, 1, limit)?;
+        if score > alpha {
+            alpha = score;
+            best = Some(((from, to), score));
+        }
+    }
+
+    best
+}
+
+/// The `count` best moves at `depth`, best first, each with its score for the side to move.
+/// Each is passed to `found` as soon as it's known, and the search gives up once `cancelled` is
+/// set. Fewer are found if there aren't as many legal moves.
+pub fn best_moves(
+    game: &Game,
+    depth: u32,
+    count: usize,
+    cancelled: &AtomicBool,
+    found: &mut dyn FnMut(Position, Position, i32),
+) {
+    let limit = Limit {
+        deadline: None,
+        cancelled: Some(cancelled),
+    };
+    let mut moves = ordered_moves(game);
+    for _ in 0..count {
+        let ((from, to), score) = match search_root(game, &moves, depth.max(1), limit) {
+            Some(best) => best,
+            None => return,
+        };
+        moves.retain(|&m| m != (from, to));
+        found(from, to, score);
+    }
+}
+
+/// The score of the move from `from` to `to` at `depth`, for the side playing it. None if the
+/// move isn't legal or the search gave up once `cancelled` was set.
+pub fn move_score(
+    game: &Game,
+    from: Position,
+    to: Position,
+    depth: u32,
+    cancelled: &AtomicBool,
+) -> Option<i32> {
+    let limit = Limit {
+        deadline: None,
+        cancelled: Some(cancelled),
+    };
+    search_root(game, &[(from, to)], depth.max(1), limit).map(|(_, score)| score)
+}
+
+/// Whether a score is for a forced mate, one way or the other, rather than material.
+pub fn is_mate(score: i32) -> bool {
+    score.abs() > MATE - 1000
+}
+
+/// Alpha-beta search. Scores are from the point of view of the side to move, and `None` means
+/// the search had to stop.
+fn negamax(
+    game: &Game,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    ply: i32,
+    limit: Limit,
+) -> Option<i32> {
+    if limit.reached() {
+        return None;
+    }
+
+    let moves = ordered_moves(game);
+    if moves.is_empty() {
+        // checkmate or stalemate; prefer mates that come sooner
+        let colour = game.get_active_colour();
+        return Some(if king_attacked(&game.get_board(), colour) {
+            -MATE + ply
+        } else {
+            0
+        });
+    }
+    if depth == 0 {
+        return Some(evaluate(game));
+    }
+
+    for (from, to) in moves {
+        let mut child = game.clone();
+        if child.make_move_pos(from, to).is_err() {
+            continue;
+        }
+
+        let score = -negamax(&child, depth - 1, -beta, -alpha, ply + 1, limit)?;
+        if score >= beta {
+            return Some(score);
+        }
+        alpha = alpha.max(score);
+    }
+
+    Some(alpha)
+}
+
+/// How much a move loses, in centipawns, for the side playing it: the score of the position
+/// before it, against the score after the opponent's best reply and our best answer to that, so
+/// a piece that's traded rather than lost isn't counted. Negative for a move that gains. None if
+/// the move isn't legal.
+pub fn swing(game: &Game, from: Position, to: Position) -> Option<i32> {
+    let mut after = game.clone();
+    after.make_move_pos(from, to).ok()?;
+    let before = evaluate(game);
+    let after = -negamax(&after, 2, -MATE - 1, MATE + 1, 1, Limit::default())?;
+    Some(before - after)
+}
+
+/// Legal moves with captures of the most valuable pieces first.
+fn ordered_moves(game: &Game) -> Vec<(Position, Position)> {
+    let board = game.get_board();
+    let mut moves = legal_moves(game);
+    moves.sort_by_key(|(_, to)| {
+        Reverse(board[to.row * 8 + to.col].map_or(0, |p| piece_value(p.piece_type)))
+    });
+    moves
+}
+
+/// Static evaluation in centipawns, from the point of view of the side to move.
+fn evaluate(game: &Game) -> i32 {
+    let board = game.get_board();
+    let mut score = 0;
+
+    for (idx, piece) in board.iter().enumerate() {
+        let piece = match piece {
+            Some(piece) => piece,
+            None => continue,
+        };
+
+        let bonus = square_bonus(piece.piece_type, piece.colour, idx);
+        let value = piece_value(piece.piece_type) as i32 * 100 + bonus;
+
+        if piece.colour == game.get_active_colour() {
+            score += value;
+        } else {
+            score -= value;
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{parse_move, square_name};
+
+    /// The game after `moves`.
+    fn after(moves: &[&str]) -> Game {
+        let mut game = Game::new();
+        for text in moves {
+            let (from, to, _) = parse_move(text).unwrap();
+            game.make_move_pos(from, to).unwrap();
+        }
+        game
+    }
+
+    /// How much `next` loses after `moves`.
+    fn swing_after(moves: &[&str], next: &str) -> i32 {
+        let (from, to, _) = parse_move(next).unwrap();
+        swing(&after(moves), from, to).unwrap()
+    }
+
+    /// The best `count` moves after `moves`, as text with their scores.
+    fn best_after(moves: &[&str], count: usize, cancelled: bool) -> Vec<(String, i32)> {
+        let mut found = Vec::new();
+        let cancelled = AtomicBool::new(cancelled);
+        best_moves(
+            &after(moves),
+            2,
+            count,
+            &cancelled,
+            &mut |from, to, score| {
+                found.push((format!("{}{}", square_name(from), square_name(to)), score))
+            },
+        );
+        found
+    }
+
+    #[test]
+    fn the_best_moves_come_one_after_another_best_first() {
+        // the queen left on g4 is taken first, and the rest are all worse for Black
+        let best = best_after(&["e2e4", "d7d5", "d1g4"], 3, false);
+        assert_eq!(best.len(), 3);
+        assert_eq!(best[0].0, "c8g4");
+        assert!(best[0].1 > 700 && best[1].1 < best[0].1 && best[2].1 <= best[1].1);
+        assert_ne!(best[1].0, best[2].0);
+
+        // fool's mate is found as a mate
+        let best = best_after(&["f2f3", "e7e5", "g2g4"], 1, false);
+        assert_eq!(best[0].0, "d8h4");
+        assert!(is_mate(best[0].1) && !is_mate(900));
+
+        // a cancelled search finds nothing
+        assert!(best_after(&[], 3, true).is_empty());
+    }
+
+    #[test]
+    fn a_hanging_queen_swings_the_score_and_a_trade_does_not() {
+        // 1.e4 d5 opens the c8 bishop's diagonal to g4, where nothing covers the queen
+        assert!(swing_after(&["e2e4", "d7d5"], "d1g4") > 700);
+        // the knight on f6 takes it on h5, and for Black the knight on f3 takes it on g5
+        assert!(swing_after(&["e2e4", "g8f6"], "d1h5") > 700);
+        assert!(swing_after(&["e2e4", "e7e5", "g1f3"], "d8g5") > 700);
+
+        // taking on d5 is answered by taking back, which is only a trade
+        assert!(swing_after(&["e2e4", "d7d5"], "e4d5").abs() < 100);
+        assert!(swing_after(&["e2e4", "d7d5"], "b1c3") < 100);
+
+        // an illegal move has no swing
+        let (from, to, _) = parse_move("e2e5").unwrap();
+        assert!(swing(&Game::new(), from, to).is_none());
+    }
+
+    #[test]
+    fn knights_like_the_centre_from_either_side() {
+        let idx = |text| {
+            let square = crate::board::parse_square(text).unwrap();
+            square.row * 8 + square.col
+        };
+        let knight = |colour, text| square_bonus(PieceType::Knight, colour, idx(text));
+        assert_eq!(knight(Colour::White, "d4"), 20);
+        assert_eq!(knight(Colour::White, "a1"), -50);
+        assert!(knight(Colour::White, "d4") > knight(Colour::White, "b1"));
+
+        // Black's are White's turned upside down
+        assert_eq!(knight(Colour::Black, "e5"), knight(Colour::White, "e4"));
+        assert_eq!(
+            square_bonus(PieceType::Pawn, Colour::Black, idx("a2")),
+            square_bonus(PieceType::Pawn, Colour::White, idx("a7"))
+        );
+
+        assert!(piece_square_table(PieceType::Rook).is_none());
+        assert_eq!(square_bonus(PieceType::King, Colour::White, idx("e1")), 0);
+    }
+}
@@ -0,0 +1,362 @@
+/**
+ * Exporting a finished game as an animated GIF, one frame per position with the move that led
+ * to it highlighted, written to `games/<date>-<room>.gif`. The positions are painted by the same
+ * `draw_board` as the window, onto a `Pixmap` in memory instead of the screen, so the whole
+ * export runs on a background thread without touching ggez. Pieces are drawn as shapes, since
+ * the images and the glyph font live on the graphics card.
+ */
+use crate::board::BOARD_SIZE;
+use crate::render::{
+    self, shape, shape_colours, BoardState, Canvas, MarkerStyle, MoveMarkers, Palette, Part, FRAME,
+};
+use chess_template::{Piece, Position};
+use ggez::{graphics, GameResult};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+/// Width and height of the GIF, in pixels.
+pub const GIF_SIZE: u16 = 360;
+
+/// The most frames a GIF gets. Longer games skip positions evenly, keeping the first and last.
+pub const MAX_FRAMES: usize = 120;
+
+/// How long each position is shown, and the final one before the GIF starts over, in
+/// hundredths of a second.
+const FRAME_DELAY: u16 = 100;
+const FINAL_DELAY: u16 = 400;
+
+/// How hard the encoder works on picking each frame's colours, from 1 (best) to 30 (fastest).
+const QUANTIZE_SPEED: i32 = 10;
+
+/// Width of outlines, in pixels of the GIF.
+const OUTLINE: f32 = 1.5;
+
+/// One position of the game and the move that led to it, if any.
+pub struct Frame {
+    pub board: [Option<Piece>; 64],
+    pub last_move: Option<(Position, Position)>,
+}
+
+/// How an export is getting on, sent from the thread doing it.
+#[derive(Debug, PartialEq)]
+pub enum Progress {
+    Frame(usize, usize), // frames done, out of how many
+    Done(PathBuf),
+    Failed(String),
+}
+
+/// Starts writing the positions to a GIF in the background, reporting back on the returned
+/// channel until it's `Done` or has `Failed`.
+pub fn spawn(path: PathBuf, frames: Vec<Frame>) -> mpsc::Receiver<Progress> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result = encode(&path, &frames, &sender);
+        let _ = sender.send(match result {
+            Ok(()) => Progress::Done(path),
+            Err(err) => Progress::Failed(err),
+        });
+    });
+    receiver
+}
+
+/// Which of `positions` get a frame: all of them, or for long games at most `max` spread
+/// evenly from the first to the last. `max` has to be at least 2.
+pub fn frame_indices(positions: usize, max: usize) -> Vec<usize> {
+    if positions <= max {
+        return (0..positions).collect();
+    }
+    (0..max).map(|i| i * (positions - 1) / (max - 1)).collect()
+}
+
+fn encode(path: &Path, frames: &[Frame], progress: &mpsc::Sender<Progress>) -> Result<(), String> {
+    let io_error = |err: std::io::Error| format!("Couldn't write {}: {}", path.display(), err);
+    let gif_error = |err: gif::EncodingError| format!("Couldn't encode the GIF: {}", err);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    let file = BufWriter::new(File::create(path).map_err(io_error)?);
+    let mut encoder = gif::Encoder::new(file, GIF_SIZE, GIF_SIZE, &[]).map_err(gif_error)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(gif_error)?;
+
+    let indices = frame_indices(frames.len(), MAX_FRAMES);
+    for (done, &i) in indices.iter().enumerate() {
+        let mut pixmap = Pixmap::new(usize::from(GIF_SIZE));
+        paint(&mut pixmap, &frames[i]).map_err(|err| err.to_string())?;
+        let mut frame =
+            gif::Frame::from_rgb_speed(GIF_SIZE, GIF_SIZE, &pixmap.pixels, QUANTIZE_SPEED);
+        frame.delay = if done + 1 == indices.len() {
+            FINAL_DELAY
+        } else {
+            FRAME_DELAY
+        };
+        encoder.write_frame(&frame).map_err(gif_error)?;
+        let _ = progress.send(Progress::Frame(done + 1, indices.len()));
+    }
+    Ok(())
+}
+
+/// Paints one position onto the pixmap: the frame, the squares with the last move highlighted,
+/// and the pieces, with the king in check marked as on the board.
+fn paint(pixmap: &mut Pixmap, frame: &Frame) -> GameResult {
+    let whole = graphics::Rect::new(0.0, 0.0, BOARD_SIZE.0, BOARD_SIZE.1);
+    pixmap.rectangle(graphics::DrawMode::fill(), whole, FRAME.into())?;
+
+    let highlighted: Vec<Position> = frame
+        .last_move
+        .map_or(Vec::new(), |(from, to)| vec![from, to]);
+    let state = BoardState {
+        board: frame.board,
+        pieces_hidden: false,
+        dots_hidden: true,
+        selected: None,
+        highlighted: &highlighted,
+        attacked: &[],
+        hanging: &[],
+        dots: &[],
+        captures: &[],
+        markers: MoveMarkers::default(),
+        repetition: None,
+        check_pulse: Some(1.0),
+        flipped: false,
+        cursor: None,
+        palette: Palette::Wood,
+        heat: None,
+    };
+    render::draw_board(pixmap, &render::squares(&state))
+}
+
+/// An RGB image in memory that the board can be painted on, in the board's own coordinates
+/// shrunk to fit. Shapes are filled where they cover a pixel's centre, without smoothing.
+pub struct Pixmap {
+    size: usize,
+    scale: f32, // pixels per unit of the board's coordinates
+    pixels: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn new(size: usize) -> Pixmap {
+        Pixmap {
+            size,
+            scale: size as f32 / BOARD_SIZE.0,
+            pixels: vec![0; size * size * 3],
+        }
+    }
+
+    /// The colour of a pixel, as red, green and blue.
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.size + x) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    /// Blends a colour over the pixels inside a shape, given in the board's coordinates by its
+    /// bounds and whether a point in pixels is inside.
+    fn cover(
+        &mut self,
+        (left, top, right, bottom): (f32, f32, f32, f32),
+        colour: graphics::Color,
+        inside: impl Fn(f32, f32) -> bool,
+    ) {
+        let clamp = |v: f32| (v * self.scale).clamp(0.0, self.size as f32) as usize;
+        let (x0, x1) = (clamp(left), (clamp(right) + 1).min(self.size));
+        let (y0, y1) = (clamp(top), (clamp(bottom) + 1).min(self.size));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if inside(x as f32 + 0.5, y as f32 + 0.5) {
+                    let i = (y * self.size + x) * 3;
+                    for (channel, value) in [colour.r, colour.g, colour.b].into_iter().enumerate() {
+                        let old = f32::from(self.pixels[i + channel]) / 255.0;
+                        let new = old + (value - old) * colour.a;
+                        self.pixels[i + channel] = (new * 255.0).round() as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills a polygon given in the board's coordinates, counting crossings so any simple
+    /// outline works.
+    fn fill_polygon(&mut self, points: &[[f32; 2]], colour: graphics::Color) {
+        let scale = self.scale;
+        let pixels: Vec<[f32; 2]> = points.iter().map(|[x, y]| [x * scale, y * scale]).collect();
+        self.cover(bounds(points), colour, |x, y| {
+            let mut inside = false;
+            for (i, [x1, y1]) in pixels.iter().copied().enumerate() {
+                let [x2, y2] = pixels[(i + 1) % pixels.len()];
+                if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+                    inside = !inside;
+                }
+            }
+            inside
+        });
+    }
+
+    /// Draws a polygon's outline, given in the board's coordinates.
+    fn stroke_polygon(&mut self, points: &[[f32; 2]], colour: graphics::Color) {
+        let scale = self.scale;
+        let pixels: Vec<[f32; 2]> = points.iter().map(|[x, y]| [x * scale, y * scale]).collect();
+        let margin = OUTLINE / scale;
+        let (left, top, right, bottom) = bounds(points);
+        let area = (left - margin, top - margin, right + margin, bottom + margin);
+        self.cover(area, colour, |x, y| {
+            (0..pixels.len()).any(|i| {
+                let next = pixels[(i + 1) % pixels.len()];
+                segment_distance([x, y], pixels[i], next) <= OUTLINE / 2.0
+            })
+        });
+    }
+
+    /// Fills a ring between two radii around a centre, all in the board's coordinates. An inner
+    /// radius of 0 fills a disc.
+    fn fill_ring(&mut self, [cx, cy]: [f32; 2], inner: f32, outer: f32, colour: graphics::Color) {
+        let scale = self.scale;
+        let area = (cx - outer, cy - outer, cx + outer, cy + outer);
+        self.cover(area, colour, |x, y| {
+            let distance = (x - cx * scale).hypot(y - cy * scale);
+            distance >= inner * scale && distance <= outer * scale
+        });
+    }
+}
+
+/// The left, top, right and bottom of some points.
+fn bounds(points: &[[f32; 2]]) -> (f32, f32, f32, f32) {
+    points.iter().fold(
+        (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+        |(left, top, right, bottom), &[x, y]| {
+            (left.min(x), top.min(y), right.max(x), bottom.max(y))
+        },
+    )
+}
+
+/// How far a point is from the closest point on a line segment.
+fn segment_distance([px, py]: [f32; 2], [ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((px - ax) * dx + (py - ay) * dy) / length).clamp(0.0, 1.0)
+    };
+    (px - (ax + t * dx)).hypot(py - (ay + t * dy))
+}
+
+impl Canvas for Pixmap {
+    fn rectangle(
+        &mut self,
+        mode: graphics::DrawMode,
+        rect: graphics::Rect,
+        colour: graphics::Color,
+    ) -> GameResult {
+        let corners = [
+            [rect.x, rect.y],
+            [rect.x + rect.w, rect.y],
+            [rect.x + rect.w, rect.y + rect.h],
+            [rect.x, rect.y + rect.h],
+        ];
+        match mode {
+            graphics::DrawMode::Fill(_) => self.fill_polygon(&corners, colour),
+            graphics::DrawMode::Stroke(_) => self.stroke_polygon(&corners, colour),
+        }
+        Ok(())
+    }
+
+    fn circle(&mut self, centre: [f32; 2], radius: f32, colour: graphics::Color) -> GameResult {
+        self.fill_ring(centre, 0.0, radius, colour);
+        Ok(())
+    }
+
+    fn marker(&mut self, style: MarkerStyle, pos: Position, colour: graphics::Color) -> GameResult {
+        let (left, top) = crate::board::square_origin(pos);
+        let size = crate::board::GRID_CELL_SIZE.0 as f32;
+        let centre = [left + size / 2.0, top + size / 2.0];
+        match style {
+            MarkerStyle::Dot => self.fill_ring(centre, 0.0, 10.0, colour),
+            MarkerStyle::Fill => {
+                let square = graphics::Rect::new(left, top, size, size);
+                self.rectangle(graphics::DrawMode::fill(), square, colour)?;
+            }
+            MarkerStyle::Corners => {
+                let corner = size / 4.0;
+                for (x, y, dx, dy) in [
+                    (left, top, corner, corner),
+                    (left + size, top, -corner, corner),
+                    (left, top + size, corner, -corner),
+                    (left + size, top + size, -corner, -corner),
+                ] {
+                    self.fill_polygon(&[[x, y], [x + dx, y], [x, y + dy]], colour);
+                }
+            }
+            MarkerStyle::Ring => {
+                let radius = size / 2.0 - 4.0;
+                self.fill_ring(centre, radius - 3.0, radius + 3.0, colour);
+            }
+        }
+        Ok(())
+    }
+
+    fn piece(&mut self, piece: Piece, pos: Position, tint: Option<graphics::Color>) -> GameResult {
+        let (fill, ink) = shape_colours(piece.colour, tint);
+        let (left, top) = crate::board::square_origin(pos);
+        let size = crate::board::GRID_CELL_SIZE.0 as f32;
+        let place = |[x, y]: [f32; 2]| [left + x * size, top + y * size];
+        for part in shape(piece.piece_type) {
+            match part {
+                Part::Polygon(points) => {
+                    let points: Vec<_> = points.into_iter().map(place).collect();
+                    self.fill_polygon(&points, fill);
+                    self.stroke_polygon(&points, ink);
+                }
+                Part::Circle(centre, radius) => {
+                    let (centre, radius) = (place(centre), radius * size);
+                    self.fill_ring(centre, 0.0, radius, fill);
+                    let outline = OUTLINE / self.scale;
+                    self.fill_ring(centre, radius - outline / 2.0, radius + outline / 2.0, ink);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::square_origin;
+    use chess_template::Game;
+
+    #[test]
+    fn long_games_are_cut_down_evenly_keeping_both_ends() {
+        assert_eq!(frame_indices(3, MAX_FRAMES), [0, 1, 2]);
+        assert_eq!(frame_indices(0, MAX_FRAMES), Vec::<usize>::new());
+
+        let indices = frame_indices(1000, MAX_FRAMES);
+        assert_eq!(indices.len(), MAX_FRAMES);
+        assert_eq!((indices[0], indices[MAX_FRAMES - 1]), (0, 999));
+        assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn positions_are_painted_with_the_board_drawing() {
+        let frame = Frame {
+            board: Game::new().get_board(),
+            last_move: None,
+        };
+        let mut pixmap = Pixmap::new(usize::from(GIF_SIZE));
+        paint(&mut pixmap, &frame).unwrap();
+
+        let at =
+            |x: f32, y: f32| pixmap.pixel((x * pixmap.scale) as usize, (y * pixmap.scale) as usize);
+        let to_u8 = |c: [f32; 4]| [c[0], c[1], c[2]].map(|v| (v * 255.0).round() as u8);
+        // the frame's corner, an empty light square and the middle of the white king
+        assert_eq!(at(2.0, 2.0), to_u8(FRAME));
+        let (left, top) = square_origin(Position::new(4, 4).unwrap());
+        assert_ne!(at(left + 5.0, top + 5.0), to_u8(FRAME));
+        let (left, top) = square_origin(Position::new(7, 4).unwrap());
+        assert_eq!(at(left + 45.0, top + 55.0), [255, 255, 255]);
+    }
+}
@@ -0,0 +1,407 @@
+/**
+ * Move annotations for games replayed from the match history: each move marked !, ?!, ? or ??
+ * by how its score compares with the best move's, as the computer opponent sees them. The whole
+ * game is worked through on a thread of its own, one move after another, while the replay plays.
+ * Only the scores are kept in the match history, so a game opened again isn't analysed again and
+ * changing the thresholds in the config file marks the moves anew.
+ */
+use crate::ai;
+use crate::matches::{self, Match};
+use chess_template::{Game, Position};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// How many plies deep each move is searched. Shallower than the suggestions, as every move of
+/// the game is searched two or three times.
+pub const ANNOTATION_DEPTH: u32 = 2;
+
+/// How a move scored against the others in its position, in centipawns for the side playing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Evaluation {
+    pub best: i32,           // the best move's score
+    pub second: Option<i32>, // the next best's, if there was another move
+    pub played: i32,         // the move played's
+}
+
+impl Evaluation {
+    /// The scores as they're kept in the match history, e.g. "35/10/35", with "-" for a second
+    /// best there wasn't.
+    pub fn to_text(self) -> String {
+        let second = self
+            .second
+            .map_or_else(|| "-".to_string(), |score| score.to_string());
+        format!("{}/{}/{}", self.best, second, self.played)
+    }
+
+    /// The inverse of `to_text`.
+    pub fn parse(text: &str) -> Option<Evaluation> {
+        let mut fields = text.split('/');
+        let best = fields.next()?.parse().ok()?;
+        let second = match fields.next()? {
+            "-" => None,
+            score => Some(score.parse().ok()?),
+        };
+        let played = fields.next()?.parse().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(Evaluation {
+            best,
+            second,
+            played,
+        })
+    }
+}
+
+/// What a move is marked as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Annotation {
+    /// The only move that keeps the position, found.
+    Good,
+    Dubious,
+    Mistake,
+    Blunder,
+}
+
+impl Annotation {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Annotation::Good => "!",
+            Annotation::Dubious => "?!",
+            Annotation::Mistake => "?",
+            Annotation::Blunder => "??",
+        }
+    }
+}
+
+/// How many centipawns a move has to lose against the best to be marked each way, and how much
+/// better than the next best the best move has to be for finding it to be marked good. Set in
+/// the config file's `[annotations]` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Thresholds {
+    pub dubious: i32,
+    pub mistake: i32,
+    pub blunder: i32,
+    pub only_move: i32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Thresholds {
+        Thresholds {
+            dubious: 60,
+            mistake: 120,
+            blunder: 300,
+            only_move: 200,
+        }
+    }
+}
+
+/// What a move is marked as, if anything, given its scores.
+pub fn classify(evaluation: Evaluation, thresholds: &Thresholds) -> Option<Annotation> {
+    let loss = evaluation.best.saturating_sub(evaluation.played);
+    let only_move = evaluation
+        .second
+        .is_some_and(|second| evaluation.best.saturating_sub(second) >= thresholds.only_move);
+    match loss {
+        loss if loss >= thresholds.blunder => Some(Annotation::Blunder),
+        loss if loss >= thresholds.mistake => Some(Annotation::Mistake),
+        loss if loss >= thresholds.dubious => Some(Annotation::Dubious),
+        loss if loss <= 0 && only_move => Some(Annotation::Good),
+        _ => None,
+    }
+}
+
+/// Scores the move from `from` to `to` in `game` against the two best there, searched `depth`
+/// plies deep. None if the move isn't legal or the search was cancelled.
+pub fn evaluate(
+    game: &Game,
+    from: Position,
+    to: Position,
+    depth: u32,
+    cancelled: &AtomicBool,
+) -> Option<Evaluation> {
+    let mut found = Vec::new();
+    ai::best_moves(game, depth, 2, cancelled, &mut |from, to, score| {
+        found.push(((from, to), score))
+    });
+    if cancelled.load(Ordering::Relaxed) {
+        return None;
+    }
+    let best = found.first()?.1;
+    let played = match found.iter().find(|&&(found, _)| found == (from, to)) {
+        Some(&(_, score)) => score,
+        None => ai::move_score(game, from, to, depth, cancelled)?,
+    };
+    Some(Evaluation {
+        best,
+        second: found.get(1).map(|&(_, score)| score),
+        played,
+    })
+}
+
+/// The analysis of a whole game, and the moves it's got through so far.
+pub struct Annotator {
+    total: usize, // moves in the game
+    found: Vec<Evaluation>,
+    done: bool, // the analysis has ended, with every move or up to one it couldn't score
+    results: Receiver<Evaluation>,
+    cancelled: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Annotator {
+    /// Starts analysing the game of `moves` from the starting position, `depth` plies deep.
+    pub fn start(moves: Vec<(Position, Position)>, depth: u32) -> Annotator {
+        let (sender, results) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&cancelled);
+        let total = moves.len();
+        let thread = thread::spawn(move || {
+            let mut game = Game::new();
+            for (from, to) in moves {
+                let evaluation = match evaluate(&game, from, to, depth, &stop) {
+                    Some(evaluation) => evaluation,
+                    None => return,
+                };
+                if sender.send(evaluation).is_err() || game.make_move_pos(from, to).is_err() {
+                    return;
+                }
+            }
+        });
+        Annotator {
+            total,
+            found: Vec::new(),
+            done: false,
+            results,
+            cancelled,
+            thread: Some(thread),
+        }
+    }
+
+    /// Takes in the moves scored since the last call.
+    pub fn poll(&mut self) {
+        loop {
+            match self.results.try_recv() {
+                Ok(evaluation) => self.found.push(evaluation),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The scores of the moves got through so far, in the order they were played.
+    pub fn found(&self) -> &[Evaluation] {
+        &self.found
+    }
+
+    /// Whether the analysis has ended, whether or not it got through every move.
+    pub fn done(&self) -> bool {
+        self.done
+    }
+
+    /// Whether every move of the game was scored.
+    pub fn complete(&self) -> bool {
+        self.found.len() == self.total
+    }
+
+    /// How far it's got, e.g. "analysing move 23/41".
+    pub fn progress_text(&self) -> String {
+        format!(
+            "analysing move {}/{}",
+            (self.found.len() + 1).min(self.total),
+            self.total
+        )
+    }
+}
+
+/// A game replayed from the match history, the analysis of its moves while it's worked out, and
+/// how they're marked.
+pub struct Annotations {
+    pub thresholds: Thresholds, // how much worse than the best a move is marked
+    recorded: Option<Match>,    // the game played back, to keep its scores in
+    annotator: Option<Annotator>, // works out its moves' scores, until they're all kept in it
+}
+
+impl Annotations {
+    /// The annotations for `recorded`, if a game is played back, which is analysed unless that's
+    /// been done before.
+    pub fn new(recorded: Option<Match>, thresholds: Thresholds) -> Annotations {
+        let annotator = recorded
+            .as_ref()
+            .filter(|game| game.evaluations.is_none())
+            .map(|game| Annotator::start(game.moves.clone(), ANNOTATION_DEPTH));
+        Annotations {
+            thresholds,
+            recorded,
+            annotator,
+        }
+    }
+
+    /// Takes in the scores of the moves as they're worked out, and keeps them in the match
+    /// history once every move has one, so the game isn't analysed again.
+    pub fn update(&mut self) {
+        let annotator = match &mut self.annotator {
+            Some(annotator) => annotator,
+            None => return,
+        };
+        annotator.poll();
+        if !annotator.done() || !annotator.complete() {
+            return;
+        }
+        let game = match &mut self.recorded {
+            Some(game) => game,
+            None => return,
+        };
+        game.evaluations = Some(annotator.found().to_vec());
+        self.annotator = None;
+
+        let path = match matches::default_path() {
+            Some(path) => path,
+            None => return,
+        };
+        match matches::replace(&path, game) {
+            Ok(true) => info!(
+                "Kept the move scores in the match history {}",
+                path.display()
+            ),
+            Ok(false) => warn!(
+                "The replayed game is no longer in the match history {}",
+                path.display()
+            ),
+            Err(err) => warn!(
+                "Couldn't keep the move scores in the match history {}: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    /// The scores of the moves, as far as they're known.
+    pub fn evaluations(&self) -> &[Evaluation] {
+        match (&self.annotator, &self.recorded) {
+            (Some(annotator), _) => annotator.found(),
+            (None, Some(game)) => game.evaluations.as_deref().unwrap_or_default(),
+            (None, None) => &[],
+        }
+    }
+
+    /// What the move `entry` is marked as, once it's been scored.
+    pub fn annotation(&self, entry: usize) -> Option<Annotation> {
+        let evaluation = *self.evaluations().get(entry)?;
+        classify(evaluation, &self.thresholds)
+    }
+
+    /// How far the analysis has got, while it's still going.
+    pub fn progress_text(&self) -> Option<String> {
+        self.annotator
+            .as_ref()
+            .filter(|annotator| !annotator.done())
+            .map(Annotator::progress_text)
+    }
+
+    /// Whether the moves are being analysed.
+    pub fn analysing(&self) -> bool {
+        self.annotator.is_some()
+    }
+}
+
+impl Drop for Annotator {
+    /// Stops the analysis, waiting for the search to notice, so closing the replay never leaves
+    /// it running. The search checks for that at every position, so it's not kept waiting.
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_move;
+    use std::time::{Duration, Instant};
+
+    fn evaluation(best: i32, second: Option<i32>, played: i32) -> Evaluation {
+        Evaluation {
+            best,
+            second,
+            played,
+        }
+    }
+
+    #[test]
+    fn moves_are_marked_by_how_much_they_lose() {
+        let thresholds = Thresholds::default();
+        let mark = |best, second, played| classify(evaluation(best, second, played), &thresholds);
+        assert_eq!(mark(40, Some(30), 40), None);
+        assert_eq!(mark(40, Some(30), -10), None);
+        assert_eq!(mark(40, Some(30), -20), Some(Annotation::Dubious));
+        assert_eq!(mark(40, Some(30), -80), Some(Annotation::Mistake));
+        assert_eq!(mark(40, Some(30), -260), Some(Annotation::Blunder));
+        assert_eq!(mark(-99_990, None, -99_990), None);
+        assert_eq!(mark(99_990, Some(0), 0), Some(Annotation::Blunder));
+
+        // finding the only move that doesn't lose a piece
+        assert_eq!(mark(0, Some(-300), 0), Some(Annotation::Good));
+        assert_eq!(mark(0, Some(-300), -10), None);
+
+        let strict = Thresholds {
+            dubious: 10,
+            ..Thresholds::default()
+        };
+        assert_eq!(
+            classify(evaluation(40, Some(30), 20), &strict),
+            Some(Annotation::Dubious)
+        );
+    }
+
+    #[test]
+    fn scores_round_trip_as_text() {
+        for evaluation in [evaluation(35, Some(-10), 35), evaluation(-5, None, -5)] {
+            assert_eq!(Evaluation::parse(&evaluation.to_text()), Some(evaluation));
+        }
+        assert_eq!(evaluation(-5, None, -5).to_text(), "-5/-/-5");
+        assert_eq!(Evaluation::parse("1/2"), None);
+        assert_eq!(Evaluation::parse("1/2/3/4"), None);
+        assert_eq!(Evaluation::parse("1/x/3"), None);
+    }
+
+    #[test]
+    fn a_hung_queen_is_a_blunder_and_closing_stops_the_analysis() {
+        let moves: Vec<(Position, Position)> = ["e2e4", "d7d5", "d1g4"]
+            .iter()
+            .map(|text| {
+                let (from, to, _) = parse_move(text).unwrap();
+                (from, to)
+            })
+            .collect();
+        let mut annotator = Annotator::start(moves.clone(), ANNOTATION_DEPTH);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !annotator.done() {
+            assert!(Instant::now() < deadline, "the analysis never ended");
+            annotator.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(annotator.complete());
+        let queen = annotator.found()[2];
+        assert_eq!(
+            classify(queen, &Thresholds::default()),
+            Some(Annotation::Blunder)
+        );
+        assert_eq!(annotator.progress_text(), "analysing move 3/3");
+
+        // deep enough to take minutes, but closed right away
+        let started = Instant::now();
+        drop(Annotator::start(moves, 8));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,237 @@
+/**
+ * Announcements for playing by ear, e.g. with a screen reader: every move as a sentence like
+ * "White plays knight f3, move 2", and check, the result, draw offers and the connection going.
+ * The config file says where they go: standard output, a file or named pipe a screen reader can
+ * follow, or a speech program run with each one. They're written on a thread of their own, so a
+ * pipe nobody reads yet or a slow voice never holds up the game.
+ */
+use crate::board::{piece_name, square_name};
+use crate::config::Config;
+use crate::history::MoveRecord;
+use chess_template::{Colour, PieceType};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+/// Where announcements go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnnounceTo {
+    /// Nowhere.
+    Off,
+    /// A line each on standard output.
+    Stdout,
+    /// A line each appended to `announce_pipe`, which can be a named pipe.
+    Pipe,
+    /// Spoken by running `announce_command` with the announcement as its argument.
+    Speech,
+}
+
+/// How a game ended, as it's announced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    Checkmate(Colour), // the side that gave mate
+    Stalemate,
+    Resigned(Colour), // the side that gave up
+    DrawAgreed,
+}
+
+/// Said when the connection to the server is lost.
+pub const CONNECTION_LOST: &str = "Connection lost";
+
+/// A colour as it's said, e.g. "White".
+fn colour_name(colour: Colour) -> &'static str {
+    match colour {
+        Colour::White => "White",
+        Colour::Black => "Black",
+    }
+}
+
+/// A move as it's announced, given its place in the game counting from 0, e.g. "Black plays
+/// bishop takes knight f3, check, move 4". Promotion is always to a queen.
+pub fn move_text(record: &MoveRecord, index: usize) -> String {
+    let castles = record.piece == PieceType::King && record.from.col.abs_diff(record.to.col) == 2;
+    let mut text = if castles && record.to.col > record.from.col {
+        "castles kingside".to_string()
+    } else if castles {
+        "castles queenside".to_string()
+    } else {
+        let takes = match record.captured {
+            Some(captured) => format!(" takes {}", piece_name(captured)),
+            None => String::new(),
+        };
+        format!(
+            "plays {}{} {}",
+            piece_name(record.piece),
+            takes,
+            square_name(record.to)
+        )
+    };
+
+    if record.piece == PieceType::Pawn && (record.to.row == 0 || record.to.row == 7) {
+        text.push_str(", promotes to queen");
+    }
+    if record.check {
+        text.push_str(", check");
+    }
+    format!(
+        "{} {}, move {}",
+        colour_name(record.colour),
+        text,
+        index / 2 + 1
+    )
+}
+
+/// How a game ended, e.g. "Checkmate, White wins".
+pub fn end_text(end: End) -> String {
+    let other = |colour| match colour {
+        Colour::White => Colour::Black,
+        Colour::Black => Colour::White,
+    };
+    match end {
+        End::Checkmate(winner) => format!("Checkmate, {} wins", colour_name(winner)),
+        End::Stalemate => "Stalemate, the game is drawn".to_string(),
+        End::Resigned(colour) => format!(
+            "{} resigns, {} wins",
+            colour_name(colour),
+            colour_name(other(colour))
+        ),
+        End::DrawAgreed => "Draw agreed".to_string(),
+    }
+}
+
+/// A draw offer, e.g. "Black offers a draw".
+pub fn draw_offer_text(colour: Colour) -> String {
+    format!("{} offers a draw", colour_name(colour))
+}
+
+/// Where a running announcer sends its lines.
+enum Output {
+    Stdout,
+    Pipe(PathBuf),
+    Speech(String),
+}
+
+/// Hands announcements to the thread that writes or speaks them.
+pub struct Announcer {
+    lines: mpsc::Sender<String>,
+}
+
+impl Announcer {
+    /// Starts announcing as the config says, or returns None if it says not to or can't be
+    /// followed, e.g. a pipe without a path.
+    pub fn start(config: &Config) -> Option<Announcer> {
+        let output = match config.announce {
+            AnnounceTo::Off => return None,
+            AnnounceTo::Stdout => Output::Stdout,
+            AnnounceTo::Pipe => match &config.announce_pipe {
+                Some(path) => Output::Pipe(path.clone()),
+                None => {
+                    warn!("Announcing to a pipe needs announce_pipe in the config file");
+                    return None;
+                }
+            },
+            AnnounceTo::Speech => Output::Speech(config.announce_command.clone()),
+        };
+
+        let (lines, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(err) = write_lines(output, receiver) {
+                warn!("Stopped announcing: {}", err);
+            }
+        });
+        Some(Announcer { lines })
+    }
+
+    /// Announces a line. Fails once the announcements have stopped, e.g. because the pipe was
+    /// closed.
+    pub fn say(&self, text: &str) -> Result<(), mpsc::SendError<String>> {
+        self.lines.send(text.to_string())
+    }
+}
+
+/// Writes or speaks every line until the game drops its announcer, or it fails.
+fn write_lines(output: Output, lines: mpsc::Receiver<String>) -> io::Result<()> {
+    match output {
+        Output::Stdout => {
+            for line in lines {
+                let mut stdout = io::stdout().lock();
+                writeln!(stdout, "{}", line)?;
+                stdout.flush()?;
+            }
+        }
+        Output::Pipe(path) => {
+            // opening a named pipe waits for its reader, which is why this is on its own thread
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            for line in lines {
+                writeln!(file, "{}", line)?;
+                file.flush()?;
+            }
+        }
+        // one at a time, so they don't talk over each other
+        Output::Speech(program) => {
+            for line in lines {
+                Command::new(&program).arg(&line).status()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_move;
+    use crate::history::parse_placement;
+    use std::time::Duration;
+
+    /// The record of a move played in a position, given as the placement part of a FEN.
+    fn record(placement: &str, text: &str) -> MoveRecord {
+        let before = parse_placement(placement).unwrap();
+        let (from, to, _) = parse_move(text).unwrap();
+        let mut after = before;
+        after[to.row * 8 + to.col] = after[from.row * 8 + from.col].take();
+        MoveRecord::new(from, to, &before, &after, Duration::ZERO)
+    }
+
+    #[test]
+    fn moves_are_announced_as_sentences() {
+        let knight = record("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", "g1f3");
+        assert_eq!(move_text(&knight, 6), "White plays knight f3, move 4");
+
+        let capture = record("4k3/8/8/8/8/5n2/4B3/4K3", "e2f3");
+        assert_eq!(
+            move_text(&capture, 3),
+            "White plays bishop takes knight f3, move 2"
+        );
+
+        let check = record("4k3/8/8/8/8/8/8/R3K3", "a1a8");
+        assert_eq!(move_text(&check, 0), "White plays rook a8, check, move 1");
+        let castling = record("r3k3/8/8/8/8/8/8/4K3", "e8c8");
+        assert_eq!(move_text(&castling, 9), "Black castles queenside, move 5");
+        let promotion = record("8/4P2k/8/8/8/8/8/4K3", "e7e8");
+        assert_eq!(
+            move_text(&promotion, 20),
+            "White plays pawn e8, promotes to queen, move 11"
+        );
+    }
+
+    #[test]
+    fn endings_and_offers() {
+        assert_eq!(
+            end_text(End::Checkmate(Colour::Black)),
+            "Checkmate, Black wins"
+        );
+        assert_eq!(
+            end_text(End::Resigned(Colour::Black)),
+            "Black resigns, White wins"
+        );
+        assert_eq!(end_text(End::Stalemate), "Stalemate, the game is drawn");
+        assert_eq!(draw_offer_text(Colour::White), "White offers a draw");
+    }
+}
@@ -0,0 +1,6429 @@
+/**
+ * The GUI: application state, drawing and input handling.
+ */
+use crate::ai::{self, MAX_DIFFICULTY, MIN_DIFFICULTY};
+use crate::animation::{self, Progress};
+use crate::annotate::{Annotation, Annotations};
+use crate::announce::{self, Announcer, End};
+use crate::avatar::{self, Avatar};
+use crate::board::*;
+use crate::chat::{self, Chat, Speaker};
+use crate::config::Config;
+use crate::confirm::Confirm;
+use crate::correspondence::{self, Correspondence, Keeper};
+use crate::crash::{self, SavedGame, Snapshot};
+use crate::desync::{self, DesyncPanel, RECENT_MESSAGES};
+use crate::dialog::{ErrorDialog, Retry};
+use crate::error::AppError;
+use crate::export;
+use crate::handover;
+use crate::history::{self, fen, format_duration, game_stats, heat, replay, MoveRecord};
+use crate::inbound::{self, InboundFilter, Verdict};
+use crate::keys::{self, Action};
+use crate::layout::{self, Layout};
+use crate::matches::{self, Match, Outcome};
+use crate::menu::Menu;
+use crate::movelog::MoveLog;
+use crate::openings;
+use crate::pause::{self, PauseItem, PauseMenu};
+use crate::protocol::{self, Message, Score};
+use crate::rating::Ratings;
+use crate::referee::{Ending, Event, Opponent, Referee, Restart};
+use crate::render;
+use crate::scene::{touch_as_mouse, Pointer, Scene, Shared, Transition};
+use crate::session::Session;
+use crate::settings::SettingsScreen;
+use crate::snapshot::{self, StateSnapshot};
+use crate::spectators::Spectators;
+use crate::suggest::{self, Suggester, SUGGESTIONS};
+use crate::toast::{Bubble, Severity, ToastQueue};
+use crate::traffic::Traffic;
+use crate::transcript::Direction;
+use crate::tutor::{self, Tutor};
+use crate::ui::{self, Button, Buttons, ScrollPanel, TextInput};
+use chess_template::{Colour, Game, Piece, PieceType, Position};
+use ggez::event::winit_event::TouchPhase;
+use ggez::{event, graphics, Context, GameResult};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Width of the move history panel to the right of the board.
+const PANEL_WIDTH: f32 = 200.0;
+
+/// Where the players are listed in the panel, below the opening name, a row each with their
+/// avatar, in the order they sit at the board. Names longer than fit are cut short. The row
+/// under them counts the spectators, if there are any.
+const PLAYERS_TOP: f32 = 92.0;
+const PLAYER_ROW: f32 = 28.0;
+const AVATAR_SIZE: f32 = 24.0;
+const PLAYER_NAME_CHARS: usize = 16;
+
+/// Where the move history is listed in the panel, below the players and above the repetition
+/// warning.
+const HISTORY_RECT: graphics::Rect = graphics::Rect {
+    x: BOARD_SIZE.0,
+    y: PLAYERS_TOP + 3.0 * PLAYER_ROW + 2.0,
+    w: PANEL_WIDTH,
+    h: BOARD_SIZE.1 - 238.0,
+};
+
+/// Where the spectators are counted, under the players.
+const SPECTATORS_RECT: graphics::Rect = graphics::Rect {
+    x: BOARD_SIZE.0,
+    y: PLAYERS_TOP + 2.0 * PLAYER_ROW,
+    w: PANEL_WIDTH,
+    h: PLAYER_ROW,
+};
+
+/// Most spectators named in the list shown over the count, the rest only counted.
+const SPECTATORS_LISTED: usize = 10;
+
+/// Height of a line in the move history, and where each line's white and black moves start
+/// from the panel's left edge. The move number goes before them.
+const HISTORY_LINE: f32 = 22.0;
+const HISTORY_COLUMNS: [f32; 2] = [48.0, 118.0];
+
+/// How big the preview of a position is next to the history panel, compared to the board.
+const PREVIEW_SCALE: f32 = 0.25;
+
+/// Size of the application window.
+pub const SCREEN_SIZE: (f32, f32) = (BOARD_SIZE.0 + PANEL_WIDTH, BOARD_SIZE.1 + 40.0);
+
+/// Most messages from the opponent handled in one frame, so a flood of them can't stall drawing.
+/// Everything that's arrived is normally handled at once, in order, so a reset queued behind a
+/// few moves isn't applied frames late; past this, the rest wait for the next frame.
+const MAX_MESSAGES_PER_FRAME: usize = 64;
+
+/// Messages kept, both ways together, as many as a snapshot for a bug report takes, and how
+/// many of the last are listed in the network info overlay. The desync report takes its last few
+/// each way from the same list.
+const TRAFFIC_LINES: usize = snapshot::MESSAGES;
+const TRAFFIC_SHOWN: usize = 20;
+
+/// Where the chat is typed while it's open, over the bottom of the board, and how many lines of
+/// the log are shown above it.
+const CHAT_INPUT_RECT: graphics::Rect = graphics::Rect {
+    x: BOARD_MARGIN + 10.0,
+    y: BOARD_SIZE.1 - BOARD_MARGIN - 50.0,
+    w: SQUARES_SIZE.0 - 20.0,
+    h: 40.0,
+};
+const CHAT_LINES_SHOWN: usize = 8;
+const CHAT_LINE: f32 = 24.0;
+
+/// How long the peek key reveals the pieces in blindfold mode.
+const PEEK_DURATION: Duration = Duration::from_secs(2);
+
+/// How long the mouse rests on a piece before its tooltip shows, and how wide the tooltip can be.
+const TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+const TOOLTIP_WIDTH: f32 = 320.0;
+
+/// How long a toast stays on screen, and an error's, which is worth a longer look.
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+const ERROR_TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// How long the opponent's quick-chat reaction stays beside their name.
+const REACTION_DURATION: Duration = Duration::from_secs(4);
+
+/// How long closing the window waits for the last messages to reach the server.
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(1);
+
+/// How long after both players agree to an automatic rematch the next game starts.
+const REMATCH_COUNTDOWN: Duration = Duration::from_secs(10);
+
+/// Where the button cancelling the automatic rematch is, under the game over text.
+const REMATCH_CANCEL_RECT: graphics::Rect = graphics::Rect {
+    x: BOARD_SIZE.0 / 2.0 - 120.0,
+    y: BOARD_SIZE.1 / 2.0 + 140.0,
+    w: 240.0,
+    h: 44.0,
+};
+
+/// How long a king in check takes to go red and back.
+const CHECK_PULSE_PERIOD: Duration = Duration::from_millis(1200);
+
+/// How long the board takes to turn round to the side to move on one board.
+const TURN_DURATION: Duration = Duration::from_millis(400);
+
+/// Where the button exporting the game as a GIF is, under the one cancelling the rematch.
+const EXPORT_GIF_RECT: graphics::Rect = graphics::Rect {
+    x: BOARD_SIZE.0 / 2.0 - 120.0,
+    y: BOARD_SIZE.1 / 2.0 + 194.0,
+    w: 240.0,
+    h: 44.0,
+};
+
+/// Where the button taking back a move the tutor flagged is, over the bottom of the move
+/// history.
+const TAKEBACK_RECT: graphics::Rect = graphics::Rect {
+    x: BOARD_SIZE.0 + 10.0,
+    y: HISTORY_RECT.y + HISTORY_RECT.h - 40.0,
+    w: PANEL_WIDTH - 20.0,
+    h: 34.0,
+};
+
+/// Size of the buttons listing the engine's suggested moves on the analysis board, stacked over
+/// the bottom of the move history, and where the first one is.
+const SUGGESTION_SIZE: (f32, f32) = (PANEL_WIDTH - 20.0, 30.0);
+const SUGGESTIONS_TOP: f32 = HISTORY_RECT.y + HISTORY_RECT.h - SUGGESTIONS as f32 * 34.0 - 2.0;
+
+/// Size of the draw and resign buttons, side by side in the corner under the move history,
+/// where the move input box goes while it's open.
+const HUD_SIZE: (f32, f32) = (85.0, 30.0);
+const HUD_TOP: f32 = BOARD_SIZE.1 + 5.0;
+
+/// How long the dots waiting on the opponent's move take to pulse along, and how long without a
+/// word from them before they're shown as maybe disconnected.
+const THINKING_DOTS_PERIOD: Duration = Duration::from_millis(1200);
+const OPPONENT_QUIET: Duration = Duration::from_secs(60);
+
+/// How long the bar naming the players stays up in kiosk mode after a move or the mouse moving,
+/// and how long it then takes to fade out.
+const KIOSK_BAR_SHOWN: Duration = Duration::from_secs(4);
+const KIOSK_BAR_FADE: Duration = Duration::from_millis(500);
+
+/// How long a finger is held still for a long press, which does what a right-click does.
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+/// How far a finger can wander and still be tapping, rather than dragging.
+const TOUCH_SLOP: f32 = 12.0;
+
+/// How much of the board is hidden in blindfold training mode.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Blindfold {
+    /// Everything is shown as usual.
+    Off,
+    /// Piece sprites are hidden, legal-move dots are still shown.
+    Pieces,
+    /// Piece sprites and our own legal-move dots are hidden.
+    PiecesAndDots,
+}
+
+impl Blindfold {
+    /// Next level when cycling through the setting with the B key.
+    pub fn next(self) -> Blindfold {
+        match self {
+            Blindfold::Off => Blindfold::Pieces,
+            Blindfold::Pieces => Blindfold::PiecesAndDots,
+            Blindfold::PiecesAndDots => Blindfold::Off,
+        }
+    }
+
+    /// Previous level, for cycling the other way on the settings screen.
+    pub fn previous(self) -> Blindfold {
+        match self {
+            Blindfold::Off => Blindfold::PiecesAndDots,
+            Blindfold::Pieces => Blindfold::Off,
+            Blindfold::PiecesAndDots => Blindfold::Pieces,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Blindfold::Off => "Off",
+            Blindfold::Pieces => "Pieces hidden",
+            Blindfold::PiecesAndDots => "Pieces and dots hidden",
+        }
+    }
+}
+
+/// When pieces that can be taken for nothing are marked on the board, as a training aid.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HangingPieces {
+    /// Never.
+    Off,
+    /// Against the computer and on one board, but not online.
+    Practice,
+    /// In every game, online once both players allow study aids.
+    Always,
+}
+
+impl HangingPieces {
+    pub fn next(self) -> HangingPieces {
+        match self {
+            HangingPieces::Off => HangingPieces::Practice,
+            HangingPieces::Practice => HangingPieces::Always,
+            HangingPieces::Always => HangingPieces::Off,
+        }
+    }
+
+    pub fn previous(self) -> HangingPieces {
+        match self {
+            HangingPieces::Off => HangingPieces::Always,
+            HangingPieces::Practice => HangingPieces::Off,
+            HangingPieces::Always => HangingPieces::Practice,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            HangingPieces::Off => "Off",
+            HangingPieces::Practice => "Offline games",
+            HangingPieces::Always => "All games",
+        }
+    }
+}
+
+/// Scratch board for exploring "what if" lines once the game is over.
+/// Nothing played here is ever sent to the server.
+struct Analysis {
+    game: Game,                       // scratch game, forked from the final position
+    moves: Vec<(Position, Position)>, // variation played on the scratch game
+}
+
+/// Playing again automatically once an online game is over. Each player agrees as soon as it
+/// ends, and once both have, the next game starts after a countdown with colours swapped.
+/// Either can cancel, which leaves restarting by hand.
+struct AutoRematch {
+    agreed: Vec<Colour>,        // colours that agreed, our own as soon as it's sent
+    starts_at: Option<Instant>, // when the next game starts, once both have agreed
+    cancelled: bool,            // either player cancelled it for this game
+    cancel: Buttons<()>,
+}
+
+impl AutoRematch {
+    fn new() -> AutoRematch {
+        AutoRematch {
+            agreed: Vec::new(),
+            starts_at: None,
+            cancelled: false,
+            cancel: Buttons::new(vec![Button::new(REMATCH_CANCEL_RECT, "Cancel rematch", ())]),
+        }
+    }
+}
+
+/// A finger on the screen.
+struct TouchDown {
+    start: [f32; 2],    // where it went down
+    at: [f32; 2],       // where it is now
+    since: Instant,     // when it went down
+    dragged: bool,      // it's wandered too far to be a tap or a long press
+    long_pressed: bool, // it was held long enough, and the long press is done
+}
+
+/// The buttons in the corner under the move history, doing what the pause menu's do.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum HudAction {
+    OfferDraw,
+    Resign,
+}
+
+/// A button for each suggested move, labelled once it's found.
+fn suggestion_buttons() -> Buttons<usize> {
+    let rects = ui::column(
+        SUGGESTIONS,
+        SUGGESTION_SIZE,
+        4.0,
+        BOARD_SIZE.0 + PANEL_WIDTH / 2.0,
+        SUGGESTIONS_TOP,
+    );
+    Buttons::unfocusable(
+        rects
+            .into_iter()
+            .enumerate()
+            .map(|(i, rect)| Button::new(rect, "", i))
+            .collect(),
+    )
+}
+
+/// The draw and resign buttons, labelled with a half and a white flag.
+fn hud_buttons(layout: Layout) -> Buttons<HudAction> {
+    Buttons::unfocusable(
+        hud_rects(layout)
+            .into_iter()
+            .zip([("½", HudAction::OfferDraw), ("⚑", HudAction::Resign)])
+            .map(|(rect, (label, action))| Button::new(rect, label, action))
+            .collect(),
+    )
+}
+
+/// Where the draw and resign buttons go: under the move history, or at the end of the line under
+/// the board in the mini layout, which has no history.
+fn hud_rects(layout: Layout) -> Vec<graphics::Rect> {
+    let centre = match layout {
+        Layout::Mini => BOARD_SIZE.0 - HUD_SIZE.0 - 15.0,
+        _ => BOARD_SIZE.0 + PANEL_WIDTH / 2.0,
+    };
+    ui::row(2, HUD_SIZE, 10.0, centre, HUD_TOP)
+}
+
+/// Actions that can't be undone, so they're confirmed first unless the config says not to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Guarded {
+    /// Restart the game, for both players.
+    Restart,
+    Resign,
+    /// Leave a game in progress, resigning it.
+    Leave,
+    /// Close the window during a game in progress, resigning it.
+    Quit,
+}
+
+impl Guarded {
+    fn question(self) -> &'static str {
+        match self {
+            Guarded::Restart => "Restart the game? Online, your opponent has to agree.",
+            Guarded::Resign => "Resign the game?",
+            Guarded::Leave => "Leave the game? This resigns it.",
+            Guarded::Quit => "Quit Schack? This resigns the game.",
+        }
+    }
+}
+
+/// How the wait for the opponent's move online is shown.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Waiting {
+    Thinking, // pulsing dots, so it's clear the game isn't stuck
+    Quiet,    // nothing has arrived for `OPPONENT_QUIET`, they may be disconnected
+}
+
+/// Counts of what went through the connection, for the network info overlay.
+#[derive(Default)]
+struct NetStats {
+    received: u64,     // messages from the opponent or server
+    ignored: u64,      // messages that weren't understood, or were for another room
+    echoes: u64,       // our own messages, sent back by the server
+    repeated: u64,     // messages the same as one that came before, or moves we have, dropped
+    flooded: u64,      // messages dropped because too many were arriving
+    unasked: u64,      // resets and rematch messages nothing here asked for, dropped
+    sent: u64,         // messages handed to the network thread
+    waited: u64, // messages that had to wait in the outbox because the network thread was behind
+    deferred: u64, // frames that handled as many messages as they may, leaving the rest for later
+    last_frame: usize, // messages handled on the last frame
+}
+
+/// GUI logic and event implementation structure.
+///
+pub struct AppState {
+    referee: Referee, // the game, its room and our colour, kept in step with the opponent
+    positions: Vec<Position>, // Save the position of each tile
+    captures: Vec<Position>, // which of the positions take a piece, marked in their own style
+    selected_position: Option<Position>, // hold position of the selected piece
+    sender: mpsc::SyncSender<String>, // for sending messages to server
+    to_mainthread_receiver: mpsc::Receiver<String>, // for sending messages from network thread to main thread
+    start_time: SystemTime,                         // time when the game started
+    blindfold: Blindfold,                           // blindfold training level
+    peek_until: Option<Instant>, // pieces are shown until this time while blindfolded
+    moves: Vec<MoveRecord>,      // every move played in the game, in order
+    last_move_at: Instant,       // when the previous move was played, for think times
+    pointer_moved_at: Instant,   // when the mouse last moved, which brings back the kiosk bar
+    analysis: Option<Analysis>,  // post-game analysis board, if open
+    premove: Option<(Position, Position)>, // move queued while the opponent is thinking
+    toasts: ToastQueue,          // transient messages, on screen or waiting for room
+    move_input: Option<String>, // move being typed in coordinate notation, if the input box is open
+    opening: Option<&'static str>, // name of the opening, while the game is still in book
+    difficulty: Option<u8>,     // strength of the computer opponent, when playing one
+    thinking: Option<u32>,      // depth the computer opponent is searching, while it thinks
+    history: Vec<u64>,          // key of every position reached so far, including the start
+    hovered: Option<Position>,  // square under the mouse, if it's on the board
+    hovered_since: Instant,     // when the mouse came onto that square, for the tooltip delay
+    hotseat: bool,              // both colours are played here, so our colour follows the turn
+    leaving: bool,              // the player asked to go back to the main menu
+    quitting: bool,             // the player confirmed closing the window
+    connected: bool,            // false once the network thread has stopped and moves can't be sent
+    server: Option<String>,     // address of the server, when playing online
+    failure: Option<ErrorDialog>, // why the game had to stop, until the scene shows it
+    desync: Option<DesyncPanel>, // what the games were doing when they fell out of sync
+    traffic: Traffic, // the last messages each way, for the network info and the desync report
+    waiting_since: Option<Instant>, // when we started waiting on the opponent's move online
+    chat: Chat,       // messages to and from the opponent online, and notices between them
+    chat_input: Option<TextInput>, // message being typed, while the chat is open
+    reactions: ToastQueue, // the opponent's presets, shown beside their name
+    pause: Option<PauseMenu>, // pause menu drawn over the game, while open
+    settings: Option<SettingsScreen>, // settings opened from the pause menu, shown instead of the board
+    result_sent: bool,                // we told the opponent how the game ended
+    agreed: Option<Score>,            // the result both games have, once the opponent's is checked
+    confirming: Option<(Confirm, Guarded)>, // question asked before an action, while it's open
+    help: bool,                       // the list of keyboard shortcuts is shown over the board
+    outbox: VecDeque<String>, // messages waiting for room in the channel to the network thread
+    instance: u32,            // tagged on what we send, to know it when the server sends it back
+    inbound: InboundFilter,   // drops what a peer over the network repeats, or floods us with
+    net: NetStats,            // what went through the connection so far
+    network_info: bool,       // the connection statistics are shown over the board
+    piece_squares: Option<PieceType>, // the engine's square bonuses shading the board, if any
+    noted_finished: bool,     // what the recent rooms in the config say about this game
+    opponent: Option<String>, // who's played, if the game goes in the match history
+    opponent_avatar: Option<u8>, // the avatar the opponent picked online, if they did
+    spectators: Spectators,   // who's announced they're watching, recently enough
+    correspondence: Keeper,   // the game kept between sessions, and moves the opponent missed
+    spectators_hover: bool,   // the mouse is over their count, so they're listed
+    archived: bool,           // the game is in the match history, or couldn't be added
+    rating: Option<(f64, f64)>, // our rating after the game, and how much it changed
+    movelog: Option<MoveLog>, // file every move is appended to, until writing to it fails
+    announcer: Option<Announcer>, // where moves are announced for playing by ear, if anywhere
+    announced_end: bool,      // how the game ended has been announced
+    history_panel: ScrollPanel, // scrolls the move history, following the newest move
+    threats_held: Option<event::KeyCode>, // key held down to show the threat view
+    threats: Option<(u64, Colour, Vec<Position>)>, // squares attacked, in which position and by whom
+    assists_allowed_by: Vec<Colour>,               // colours that allowed study aids, online
+    hanging: Option<(u64, Vec<Position>)>, // pieces of the side to move left hanging, and where
+    cover: Option<(u64, [[bool; 64]; 2])>, // squares each side attacks, for the piece tooltips
+    snapshots: Vec<[Option<Piece>; 64]>,   // the board after each move, for previewing it
+    history_hover: Option<[f32; 2]>,       // mouse position while it's over the history panel
+    move_list: bool,                       // the history panel is shown beside the board
+    last_click: Option<(Position, Instant)>, // square clicked last and when, for double-clicks
+    reviewing: Option<usize>,              // earlier move whose position is shown on the board
+    heat: [u16; 64],                       // how many moves went to each square this game
+    heatmap: bool,                         // the squares are shaded by `heat`, when allowed
+    replay: bool,                          // a recorded game played back, not one being played
+    annotations: Annotations, // the scores of a game from the match history played back
+    flipped: bool,            // the board is turned round, Black's side at the bottom
+    turned_at: Option<Instant>, // when it started turning round to the side to move
+    hotseat_flip: bool,       // on one board, it turns round to the side to move
+    hotseat_cover: bool,      // on one board, it's hidden between moves
+    handover: Option<Colour>, // the side it's passed to, hidden until they click
+    cursor: Option<Position>, // keyboard cursor on the board, once it's been used
+    stick: [i8; 2],           // which way the gamepad stick is held, across and up
+    touch_down: Option<TouchDown>, // a finger on the screen, for long presses and drags
+    rematch: AutoRematch,     // the next game, once this one's over online
+    export_gif: Buttons<()>,  // on the game over text
+    tutor: Tutor,             // flags moves that might lose material, and offers them back
+    takeback: Buttons<()>,    // takes the flagged move back, while offered
+    suggestions: Suggester,   // the engine's best moves on the analysis board
+    suggested: Buttons<usize>, // plays a suggestion, and shows it as an arrow on hover
+    hud: Buttons<HudAction>,  // offering a draw and resigning, under the history
+    layout: Layout,           // how the game was last drawn, which `hud` is placed for
+    gif_export: Option<(mpsc::Receiver<Progress>, String)>, // a GIF being written, and its toast
+    frame: render::BoardFrame,
+    markers: render::MarkerMeshes,
+}
+
+impl AppState {
+    /// Initialise new application, i.e. initialise new game. The piece images are loaded
+    /// once for every game, see `Shared::load_pieces`.
+    pub fn new(session: Session, start_time: SystemTime, config: &Config) -> AppState {
+        // A cool way to instantiate the board
+        // You can safely delete this if the chess-library already does this
+
+        let correspondence = session.correspondence;
+        let paired_now = correspondence.is_none();
+        let mut state = AppState {
+            referee: Referee::new(&session.room_name, session.color),
+            positions: Vec::new(),
+            captures: Vec::new(),
+            selected_position: None,
+            sender: session.sender, // mpsc::Sender::clone(&sender)
+            to_mainthread_receiver: session.receiver,
+            start_time,
+            blindfold: config.blindfold,
+            peek_until: None,
+            moves: Vec::new(),
+            last_move_at: Instant::now(),
+            pointer_moved_at: Instant::now(),
+            analysis: None,
+            premove: None,
+            toasts: ToastQueue::default(),
+            move_input: None,
+            opening: None,
+            difficulty: session.difficulty,
+            thinking: None,
+            history: vec![position_key(&Game::new())],
+            hovered: None,
+            hovered_since: Instant::now(),
+            hotseat: session.hotseat,
+            leaving: false,
+            quitting: false,
+            connected: true,
+            server: session.server,
+            failure: None,
+            desync: None,
+            traffic: Traffic::new(TRAFFIC_LINES, Instant::now()),
+            waiting_since: None,
+            chat: Chat::new(config.quick_chat.clone()),
+            chat_input: None,
+            reactions: ToastQueue::default(),
+            pause: None,
+            settings: None,
+            result_sent: false,
+            agreed: None,
+            confirming: None,
+            help: false,
+            outbox: VecDeque::new(),
+            instance: rand::random(),
+            inbound: InboundFilter::default(),
+            net: NetStats::default(),
+            network_info: false,
+            piece_squares: None,
+            noted_finished: false,
+            opponent: session.opponent,
+            opponent_avatar: session.opponent_avatar,
+            spectators: Spectators::default(),
+            correspondence: Keeper::default(),
+            spectators_hover: false,
+            archived: false,
+            rating: None,
+            movelog: None,
+            announcer: Announcer::start(config),
+            announced_end: false,
+            history_panel: ScrollPanel::new(HISTORY_RECT, true),
+            threats_held: None,
+            threats: None,
+            assists_allowed_by: Vec::new(),
+            hanging: None,
+            cover: None,
+            snapshots: Vec::new(),
+            history_hover: None,
+            move_list: config.move_list,
+            last_click: None,
+            reviewing: None,
+            heat: [0; 64],
+            heatmap: false,
+            replay: session.replay,
+            annotations: Annotations::new(session.recorded.map(|game| *game), config.annotations),
+            flipped: config.board_flipped,
+            turned_at: None,
+            hotseat_flip: config.hotseat_flip,
+            hotseat_cover: config.hotseat_cover,
+            handover: None,
+            cursor: None,
+            stick: [0, 0],
+            touch_down: None,
+            rematch: AutoRematch::new(),
+            export_gif: Buttons::new(vec![Button::new(EXPORT_GIF_RECT, "Export GIF", ())]),
+            tutor: Tutor::new(config.tutor),
+            takeback: Buttons::new(vec![Button::new(TAKEBACK_RECT, "Take it back", ())]),
+            suggestions: Suggester::default(),
+            suggested: suggestion_buttons(),
+            hud: hud_buttons(Layout::Standard),
+            layout: Layout::Standard,
+            gif_export: None,
+            frame: render::BoardFrame::default(),
+            markers: render::MarkerMeshes::default(),
+        };
+        if let Some(game) = correspondence {
+            state.pick_up(game);
+        }
+        if paired_now {
+            state.chat_notice(format!("{:?} connected", state.their_colour()));
+        }
+        state
+    }
+
+    /// Plays a correspondence game's saved moves back and offers to sync, in case the opponent
+    /// moved while we were away.
+    fn pick_up(&mut self, game: Correspondence) {
+        let saved = SavedGame {
+            room: self.referee.room.clone(),
+            fen: String::new(),
+            moves: game.moves.clone(),
+        };
+        self.correspondence.pick_up(game);
+        if !saved.moves.is_empty() {
+            self.resume(&saved);
+        }
+        self.offer_sync();
+    }
+
+    /// Tells the opponent how many moves we have, and a hash of them.
+    fn offer_sync(&mut self) {
+        let msg = correspondence::offer(&self.referee, &self.move_names());
+        self.send(msg);
+    }
+
+    /// Sends the next batch of moves the opponent missed, once it's time.
+    fn send_missed_moves(&mut self, now: Instant) {
+        for msg in self.correspondence.due(&self.referee, now) {
+            self.send(msg);
+        }
+    }
+
+    /// Keeps a correspondence game's moves after every change, so they're there next time.
+    fn keep_correspondence(&mut self) {
+        let names = self.move_names();
+        self.correspondence.keep(names);
+    }
+
+    /// Every move so far in coordinate notation.
+    fn move_names(&self) -> Vec<String> {
+        self.moves.iter().map(MoveRecord::name).collect()
+    }
+
+    /// Appends every move from now on to a file, starting with a header for this game.
+    pub fn log_moves_to(&mut self, path: &Path) {
+        match MoveLog::open(path) {
+            Ok(movelog) => {
+                self.movelog = Some(movelog);
+                let room = self.referee.room.clone();
+                self.write_movelog(|movelog| movelog.section(&room));
+            }
+            Err(err) => self.report(AppError::MoveLog(err)),
+        }
+    }
+
+    /// Writes to the move log, if there is one. If that fails, the game goes on without it.
+    fn write_movelog(&mut self, write: impl FnOnce(&mut MoveLog) -> io::Result<()>) {
+        if let Some(Err(err)) = self.movelog.as_mut().map(write) {
+            self.movelog = None;
+            self.report(AppError::MoveLog(err));
+        }
+    }
+    /// Stops the game with a dialog explaining why. The moves are logged first, so the game
+    /// isn't lost whatever the player does next.
+    fn fail(&mut self, err: AppError) {
+        let message = match (&err, &self.server) {
+            (AppError::Disconnected, Some(server)) => {
+                format!("Lost connection to server {}", server)
+            }
+            _ => err.to_string(),
+        };
+        error!("{}", message);
+        self.log_moves();
+
+        let retry = self.server.clone().map(|server| Retry {
+            server,
+            room: self.referee.room.clone(),
+        });
+        self.failure = Some(ErrorDialog::new(message, retry));
+    }
+
+    /// Freezes the game on a desync, showing what led up to it until the player abandons it.
+    /// `results` are ours and theirs, when it's the game's result the two disagree on.
+    fn desynced(&mut self, remote: u32, results: Option<(&'static str, &'static str)>) {
+        let report = desync::Report {
+            room: self.referee.room.clone(),
+            local: self.referee.counter(),
+            remote,
+            results,
+            sent: self.traffic.last(Direction::Out, RECENT_MESSAGES),
+            received: self.traffic.last(Direction::In, RECENT_MESSAGES),
+            fen: fen(self.referee.game(), &self.moves),
+        };
+        error!("{}", report.text());
+        self.selected_position = None;
+        self.positions = vec![];
+        self.premove = None;
+        self.desync = Some(DesyncPanel::new(report));
+    }
+
+    /// Once the player gives up on a game that's out of sync, goes on to the error dialog, which
+    /// offers to reconnect.
+    fn abandon_if_asked(&mut self) {
+        if !self.desync.as_ref().is_some_and(DesyncPanel::abandoned) {
+            return;
+        }
+        if let Some(desync) = self.desync.take() {
+            let report = desync.report();
+            let err = match report.results {
+                Some((ours, theirs)) => AppError::ResultMismatch { theirs, ours },
+                None => AppError::Desync {
+                    remote: report.remote,
+                    local: report.local,
+                },
+            };
+            self.fail(err);
+        }
+    }
+
+    /// Writes the moves played so far to the log, so a game that's left or lost can be recovered.
+    fn log_moves(&self) {
+        let moves = self
+            .moves
+            .iter()
+            .map(MoveRecord::name)
+            .collect::<Vec<String>>();
+        if !moves.is_empty() {
+            info!("Moves played: {}", moves.join(" "));
+        }
+    }
+
+    /// Goes back to the main menu. Leaving a game in progress resigns it, so an opponent on
+    /// another computer isn't left waiting for a move that never comes. A correspondence game is
+    /// kept for next time instead.
+    fn leave(&mut self) {
+        if self.in_progress() && !self.hotseat && self.connected && !self.correspondence.active() {
+            let msg = Message::Resign {
+                room: self.referee.room.clone(),
+                colour: self.referee.colour,
+            };
+            self.send(msg);
+        }
+        self.log_moves();
+        self.leaving = true;
+    }
+
+    /// Ends the game as the window closes: a game in progress is resigned, as on leaving it, and
+    /// the network thread gets a moment to send that before the connection is closed.
+    fn close(&mut self, shared: &mut Shared) {
+        if !self.leaving {
+            self.leave();
+            self.note_finished(shared);
+            self.archive(shared);
+        }
+        let deadline = Instant::now() + SHUTDOWN_WAIT;
+
+        // hand over what's still in the outbox, taking what arrives so the threads aren't stuck
+        while !self.outbox.is_empty() && self.connected && Instant::now() < deadline {
+            self.flush();
+            while self.to_mainthread_receiver.try_recv().is_ok() {}
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // dropping the last sender closes the connection once the rest is sent. The panic hook
+        // has a copy of it, which it doesn't need now
+        crash::clear();
+        let (closed, _) = mpsc::sync_channel(0);
+        drop(mem::replace(&mut self.sender, closed));
+        if !self.connected || self.server.is_none() {
+            self.connected = false;
+            return;
+        }
+        self.connected = false;
+
+        // the network threads are done once their end of the incoming channel is dropped
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.to_mainthread_receiver.recv_timeout(left) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Disconnected) => return,
+                Err(RecvTimeoutError::Timeout) => {
+                    warn!("The connection didn't close in time, quitting anyway");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Takes on settings changed on the settings screen, telling the computer opponent about a
+    /// new difficulty.
+    fn apply_config(&mut self, shared: &mut Shared, config: Config) {
+        if self.blindfold != config.blindfold {
+            self.blindfold = config.blindfold;
+            self.peek_until = None;
+        }
+        self.tutor.on = config.tutor;
+        self.annotations.thresholds = config.annotations;
+        self.hotseat_flip = config.hotseat_flip;
+        self.hotseat_cover = config.hotseat_cover;
+        self.move_list = config.move_list;
+        if !self.hotseat_cover {
+            self.handover = None;
+        }
+        self.turn_to_mover(false);
+
+        if let (Some(current), Some(difficulty)) = (self.difficulty, config.ai_difficulty) {
+            if current != difficulty {
+                self.difficulty = Some(difficulty);
+                let msg = Message::Difficulty {
+                    room: self.referee.room.clone(),
+                    level: difficulty,
+                };
+                self.send(msg);
+            }
+        }
+
+        shared.config = config;
+    }
+
+    /// Whether the squares can be shaded by how often they were moved to: in a replay and once
+    /// the game is over, when it can't help anyone find a move.
+    fn heatmap_allowed(&self) -> bool {
+        self.replay || self.game_over() || self.analysis.is_some()
+    }
+
+    /// Whether the game is over, on the board or by resignation or agreement.
+    fn game_over(&self) -> bool {
+        self.referee.game_over()
+    }
+
+    /// Notes in the recent rooms whether an online game got to its end, or was left resigning,
+    /// so the connection screen can tell which rooms have a game to go back to.
+    fn note_finished(&mut self, shared: &mut Shared) {
+        let server = match &self.server {
+            Some(server) => server,
+            None => return,
+        };
+        let resigned = self.leaving && self.in_progress() && !self.correspondence.active();
+        let finished = self.game_over() || resigned;
+        if finished != self.noted_finished {
+            shared.config.finish(server, &self.referee.room, finished);
+            shared.config.save();
+            self.noted_finished = finished;
+        }
+    }
+
+    /// Whether the result is agreed with the opponent before it's recorded: only online, since
+    /// the computer opponents and replays don't send one.
+    fn agrees_result(&self) -> bool {
+        self.server.is_some() && !self.hotseat
+    }
+
+    /// The game's result as we see it, once it's over.
+    fn score(&self) -> Option<Score> {
+        self.outcome()
+            .map(|outcome| outcome.score(self.referee.colour))
+    }
+
+    /// Tells the opponent how the game ended, once it has, unless they've told us first.
+    fn send_result(&mut self) {
+        if !self.agrees_result() || self.result_sent || self.agreed.is_some() || !self.game_over() {
+            return;
+        }
+        if let Some(score) = self.score() {
+            self.result_sent = true;
+            let msg = Message::Result {
+                room: self.referee.room.clone(),
+                score,
+            };
+            self.send(msg);
+        }
+    }
+
+    /// Checks a result sent by either side against our own game, adopting it if they're the
+    /// same. If they aren't, the games have diverged and the desync report comes up.
+    fn check_result(&mut self, theirs: Score) {
+        // one left over from before a restart has no game to check against
+        if self.agreed == Some(theirs) || self.moves.is_empty() {
+            return;
+        }
+        match self.score() {
+            Some(ours) if ours == theirs && self.agreed.is_none() => self.agreed = Some(theirs),
+            ours => {
+                let ours = self.agreed.or(ours).map_or("*", Score::code);
+                self.desynced(self.referee.counter(), Some((ours, theirs.code())));
+            }
+        }
+    }
+
+    /// How the game ended for us as it goes in the match history: as agreed with the opponent
+    /// online, or as we see it when there's nobody to agree with. None until then.
+    fn recorded_outcome(&self) -> Option<Outcome> {
+        if let Some(score) = self.agreed {
+            return Some(Outcome::of(score, self.referee.colour));
+        }
+        if self.agrees_result() && self.connected && !self.leaving && self.game_over() {
+            return None;
+        }
+        self.outcome()
+    }
+
+    /// How the game ended for us, once it's over. Leaving a game in progress resigns it.
+    fn outcome(&self) -> Option<Outcome> {
+        if self.leaving && self.in_progress() && !self.correspondence.active() {
+            return Some(Outcome::Lost);
+        }
+        self.referee.outcome()
+    }
+
+    /// Adds the game to the match history once it's over, if it has an opponent to file it
+    /// under, and works out what it did to our rating. A history that can't be written is only
+    /// logged, it never holds up the next game.
+    fn archive(&mut self, shared: &Shared) {
+        if self.archived || self.hotseat {
+            return;
+        }
+        let (opponent, outcome) = match (&self.opponent, self.recorded_outcome()) {
+            (Some(opponent), Some(outcome)) => (opponent.clone(), outcome),
+            _ => return,
+        };
+        self.archived = true;
+
+        let now = SystemTime::now();
+        let result = outcome.result(self.referee.colour);
+        let (white, black) = self.player_names(shared);
+        let tags = [
+            ("Event", "Schack".to_string()),
+            ("Site", self.referee.room.clone()),
+            ("Date", export::date(now).replace('-', ".")),
+            ("White", white),
+            ("Black", black),
+            ("Result", result.to_string()),
+        ];
+        let game = Match {
+            played_at: matches::timestamp(now),
+            opponent: opponent.clone(),
+            colour: self.referee.colour,
+            outcome,
+            moves: self
+                .moves
+                .iter()
+                .map(|record| (record.from, record.to))
+                .collect(),
+            time_control: matches::NO_TIME_CONTROL.to_string(),
+            pgn: history::pgn(&tags, &self.moves, result),
+            evaluations: None,
+        };
+
+        let path = match matches::default_path() {
+            Some(path) => path,
+            None => {
+                warn!("There's no data directory to keep the match history in");
+                return;
+            }
+        };
+        let mut ratings = Ratings::from_history(&matches::load(&path));
+        let delta = ratings.record(&opponent, outcome);
+        self.rating = Some((ratings.mine, delta));
+        match matches::append(&path, &game) {
+            Ok(()) => info!("Added the game to the match history {}", path.display()),
+            Err(err) => warn!(
+                "Couldn't add the game to the match history {}: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    /// Whether moves have been played and the game isn't over yet.
+    fn in_progress(&self) -> bool {
+        !self.moves.is_empty() && !self.game_over()
+    }
+
+    /// The room the game is played in.
+    pub fn room(&self) -> &str {
+        &self.referee.room
+    }
+
+    /// How the wait for the opponent's move online looks `now`, or None if we're not waiting on
+    /// one: it's our move, the game is over, or the opponent is the computer or across the desk.
+    /// Nor by correspondence, where they're likely not online at all.
+    fn opponent_wait(&self, now: Instant) -> Option<Waiting> {
+        if self.server.is_none()
+            || self.hotseat
+            || self.correspondence.active()
+            || !self.connected
+            || self.desync.is_some()
+            || self.game_over()
+            || self.referee.game().get_active_colour() == self.referee.colour
+        {
+            return None;
+        }
+        let quiet = self
+            .traffic
+            .since_last_in(now)
+            .or_else(|| {
+                self.waiting_since
+                    .map(|since| now.saturating_duration_since(since))
+            })
+            .unwrap_or_default();
+        Some(if quiet >= OPPONENT_QUIET {
+            Waiting::Quiet
+        } else {
+            Waiting::Thinking
+        })
+    }
+
+    /// Starts the dots when the opponent's turn begins, and stops them when it ends. The chat
+    /// notes the opponent going quiet, once each time.
+    fn update_waiting(&mut self, now: Instant) {
+        let wait = self.opponent_wait(now);
+        self.waiting_since = match wait {
+            Some(_) => self.waiting_since.or(Some(now)),
+            None => None,
+        };
+        if self.chat.went_quiet(wait == Some(Waiting::Quiet)) {
+            self.chat_notice(format!("{:?} may have disconnected", self.their_colour()));
+        }
+    }
+
+    /// The colour the opponent plays.
+    fn their_colour(&self) -> Colour {
+        self.referee.their_colour()
+    }
+
+    /// Whether there's someone to chat with: only online, not with the computer or across the
+    /// desk.
+    fn chat_allowed(&self) -> bool {
+        self.server.is_some() && !self.hotseat
+    }
+
+    /// Notes something that happened in the chat log, in games that have one.
+    fn chat_notice(&mut self, text: impl Into<String>) {
+        if self.chat_allowed() {
+            self.chat.notice(text, SystemTime::now());
+        }
+    }
+
+    /// Sends the typed messages the chat's rate limit lets through `now`.
+    fn send_chat(&mut self, now: Instant) {
+        for text in self.chat.send_due(now, SystemTime::now()) {
+            let msg = Message::Chat {
+                room: self.referee.room.clone(),
+                colour: self.referee.colour,
+                text,
+            };
+            self.send(msg);
+        }
+    }
+
+    /// Sends what's typed in the chat, or queues it if too much has been sent lately, and closes
+    /// the box.
+    fn submit_chat_input(&mut self) {
+        if let Some(input) = self.chat_input.take() {
+            self.chat
+                .queue(&chat::fit(&self.referee.room, input.text()));
+            self.send_chat(Instant::now());
+        }
+    }
+
+    /// Sends the quick-chat preset for Alt and a number from 1 to 4, online. Returns whether the
+    /// key was one, even if that number has no preset.
+    fn quick_chat_key(&mut self, key: event::KeyCode, mods: event::KeyMods) -> bool {
+        let index = match key {
+            event::KeyCode::Key1 => 0,
+            event::KeyCode::Key2 => 1,
+            event::KeyCode::Key3 => 2,
+            event::KeyCode::Key4 => 3,
+            _ => return false,
+        };
+        if !mods.contains(event::KeyMods::ALT) || !self.chat_allowed() {
+            return false;
+        }
+        if let Some(preset) = self.chat.preset(index) {
+            let text = chat::fit(&self.referee.room, preset);
+            self.chat.queue(&text);
+            self.send_chat(Instant::now());
+        }
+        true
+    }
+
+    /// Draws the opponent's reactions over the edge of the board, level with their name.
+    fn draw_reactions(&self, ctx: &mut Context) -> GameResult {
+        // Black is listed first, unless the board's turned round
+        let row = if (self.their_colour() == Colour::Black) != self.flipped {
+            0.0
+        } else {
+            1.0
+        };
+        self.reactions.draw_reactions(
+            ctx,
+            Instant::now(),
+            BOARD_SIZE.0 - 6.0,
+            PLAYERS_TOP + row * PLAYER_ROW,
+        )
+    }
+
+    /// Draws the chat over the bottom of the board while a message is typed: the last lines of
+    /// the log, with notices in green, and the message under them.
+    fn draw_chat(&self, ctx: &mut Context, input: &TextInput) -> GameResult {
+        let mut lines: Vec<(String, [f32; 4])> = self
+            .chat
+            .lines()
+            .rev()
+            .take(CHAT_LINES_SHOWN)
+            .rev()
+            .map(|line| {
+                let time = chat::clock(line.at);
+                match line.speaker {
+                    Speaker::Us => (
+                        format!("{} {:?}: {}", time, self.referee.colour, line.text),
+                        [0.1, 0.1, 0.4, 1.0],
+                    ),
+                    Speaker::Them => (
+                        format!("{} {:?}: {}", time, self.their_colour(), line.text),
+                        [0.0, 0.0, 0.0, 1.0],
+                    ),
+                    Speaker::Notice => (format!("{} {}", time, line.text), [0.1, 0.45, 0.2, 1.0]),
+                }
+            })
+            .collect();
+        if self.chat.queued() > 0 {
+            let text = format!("{} waiting to be sent", self.chat.queued());
+            lines.push((text, [0.5, 0.5, 0.5, 1.0]));
+        }
+
+        let top = CHAT_INPUT_RECT.y - lines.len() as f32 * CHAT_LINE - 8.0;
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                CHAT_INPUT_RECT.x - 6.0,
+                top - 6.0,
+                CHAT_INPUT_RECT.w + 12.0,
+                CHAT_INPUT_RECT.y + CHAT_INPUT_RECT.h - top + 12.0,
+            ),
+            [1.0, 1.0, 1.0, 0.85].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+        for (i, (line, colour)) in lines.into_iter().enumerate() {
+            let text = graphics::Text::new(
+                graphics::TextFragment::from(line).scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color(colour.into())
+                    .dest([CHAT_INPUT_RECT.x, top + i as f32 * CHAT_LINE]),
+            )?;
+        }
+        input.draw(ctx, CHAT_INPUT_RECT, true, "Say something, Enter sends")
+    }
+
+    /// Draws the wait for the opponent's move after the status text at `x`, as three dots
+    /// pulsing in turn, or with a warning in orange once they've gone quiet.
+    fn draw_waiting(&self, ctx: &mut Context, x: f32) -> GameResult {
+        let now = Instant::now();
+        let (since, wait) = match (self.waiting_since, self.opponent_wait(now)) {
+            (Some(since), Some(wait)) => (since, wait),
+            _ => return Ok(()),
+        };
+        let text = match wait {
+            Waiting::Thinking => {
+                let dot = |alpha| {
+                    graphics::TextFragment::from("•")
+                        .color(graphics::Color::new(0.1, 0.1, 0.4, alpha))
+                        .scale(graphics::PxScale { x: 30.0, y: 30.0 })
+                };
+                let [first, second, third] = thinking_dots(now.saturating_duration_since(since));
+                let mut text = graphics::Text::new(dot(first));
+                text.add(dot(second));
+                text.add(dot(third));
+                text
+            }
+            Waiting::Quiet => graphics::Text::new(
+                graphics::TextFragment::from("••• opponent may be disconnected")
+                    .color(graphics::Color::new(0.8, 0.4, 0.0, 1.0))
+                    .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            ),
+        };
+        let height = text.dimensions(ctx).h;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default().dest([x, SCREEN_SIZE.1 - (40.0 + height) / 2.0]),
+        )
+    }
+
+    /// Who plays White and who plays Black: us by our name, and the opponent as the match history
+    /// has them.
+    fn player_names(&self, shared: &Shared) -> (String, String) {
+        let me = shared.name().unwrap_or_else(|| "You".to_string());
+        let opponent = self
+            .opponent
+            .clone()
+            .unwrap_or_else(|| "Opponent".to_string());
+        match self.referee.colour {
+            Colour::White => (me, opponent),
+            Colour::Black => (opponent, me),
+        }
+    }
+
+    /// The players as they're listed in the panel, with their avatars: the one playing from the
+    /// top of the board first. Players sharing this computer aren't named, so there are none.
+    fn player_rows(&self, shared: &Shared) -> Vec<(String, &'static Avatar)> {
+        if self.hotseat {
+            return Vec::new();
+        }
+        let (white, black) = self.player_names(shared);
+        let ours = avatar::avatar(shared.config.avatar);
+        let theirs = avatar::avatar(self.opponent_avatar);
+        let (white_avatar, black_avatar) = match self.referee.colour {
+            Colour::White => (ours, theirs),
+            Colour::Black => (theirs, ours),
+        };
+        let mut rows = vec![(black, black_avatar), (white, white_avatar)];
+        if self.flipped {
+            rows.reverse();
+        }
+        rows
+    }
+
+    /// Lists the players in the panel with their avatars.
+    fn draw_players(&self, ctx: &mut Context, shared: &Shared) -> GameResult {
+        for (i, (name, avatar)) in self.player_rows(shared).iter().enumerate() {
+            let rect = graphics::Rect::new(
+                BOARD_SIZE.0 + 10.0,
+                PLAYERS_TOP + i as f32 * PLAYER_ROW,
+                AVATAR_SIZE,
+                AVATAR_SIZE,
+            );
+            avatar::draw(ctx, avatar, shared.avatar_image(avatar), rect)?;
+
+            let name = if name.chars().count() > PLAYER_NAME_CHARS {
+                name.chars().take(PLAYER_NAME_CHARS - 1).collect::<String>() + "…"
+            } else {
+                name.clone()
+            };
+            let text = graphics::Text::new(
+                graphics::TextFragment::from(name).scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+            );
+            let height = text.dimensions(ctx).h;
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([rect.right() + 8.0, rect.y + (AVATAR_SIZE - height) / 2.0]),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Counts the spectators under the players, as an eye and a number, if there are any.
+    fn draw_spectators(&self, ctx: &mut Context) -> GameResult {
+        if self.spectators.count() == 0 {
+            return Ok(());
+        }
+        let centre = [
+            SPECTATORS_RECT.x + 10.0 + AVATAR_SIZE / 2.0,
+            SPECTATORS_RECT.y + AVATAR_SIZE / 2.0,
+        ];
+        let (w, h) = (AVATAR_SIZE / 2.0, AVATAR_SIZE / 4.0);
+        let outline: Vec<[f32; 2]> = (0..24)
+            .map(|i| {
+                let angle = i as f32 / 24.0 * std::f32::consts::TAU;
+                // pointed at the corners, like an eye, rather than an ellipse
+                let y = angle.sin() * angle.sin().abs().sqrt();
+                [centre[0] + w * angle.cos(), centre[1] + h * y]
+            })
+            .collect();
+        let colour: graphics::Color = [0.1, 0.1, 0.4, 1.0].into();
+        let eye = graphics::MeshBuilder::new()
+            .polygon(graphics::DrawMode::stroke(2.0), &outline, colour)?
+            .circle(graphics::DrawMode::fill(), centre, h * 0.8, 0.1, colour)?
+            .build(ctx)?;
+        graphics::draw(ctx, &eye, graphics::DrawParam::default())?;
+
+        let text = graphics::Text::new(
+            graphics::TextFragment::from(self.spectators.count().to_string())
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        let height = text.dimensions(ctx).h;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default().color(colour).dest([
+                SPECTATORS_RECT.x + 18.0 + AVATAR_SIZE,
+                SPECTATORS_RECT.y + (AVATAR_SIZE - height) / 2.0,
+            ]),
+        )?;
+        if self.spectators_hover {
+            self.draw_spectator_list(ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Lists the spectators by name, over the move history under their count.
+    fn draw_spectator_list(&self, ctx: &mut Context) -> GameResult {
+        let names = self.spectators.names();
+        let mut lines: Vec<String> = names.iter().take(SPECTATORS_LISTED).cloned().collect();
+        if names.len() > SPECTATORS_LISTED {
+            lines.push(format!("and {} more", names.len() - SPECTATORS_LISTED));
+        }
+        let mut text = graphics::Text::new(
+            graphics::TextFragment::from(format!("Watching:\n{}", lines.join("\n")))
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        text.set_bounds([PANEL_WIDTH - 40.0, f32::INFINITY], graphics::Align::Left);
+        let size = text.dimensions(ctx);
+        let area = graphics::Rect::new(
+            SPECTATORS_RECT.x + 10.0,
+            SPECTATORS_RECT.bottom(),
+            PANEL_WIDTH - 20.0,
+            size.h + 20.0,
+        );
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            area,
+            [1.0, 1.0, 1.0, 0.95].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([area.x + 10.0, area.y + 10.0]),
+        )
+    }
+
+    /// How long the game has gone on, e.g. "1:43". A clock set backwards since the game started
+    /// shows 0:0 rather than failing.
+    fn elapsed_text(&self) -> String {
+        let elapsed = self.start_time.elapsed().unwrap_or_default().as_secs();
+        elapsed.div_euclid(60).to_string() + ":" + &elapsed.rem_euclid(60).to_string()
+    }
+
+    /// How the game ended, e.g. "White resigned".
+    fn ending_text(&self) -> String {
+        match self.referee.ending() {
+            Some(Ending::Resigned(colour)) => format!("{:?} resigned", colour),
+            Some(Ending::DrawAgreed) => "Draw agreed".to_string(),
+            None => "Game Over".to_string(),
+        }
+    }
+
+    /// What the kiosk bar says: the players, unless they share this computer, the time, and
+    /// whose move it is or how the game ended.
+    fn kiosk_bar_text(&self, shared: &Shared) -> String {
+        let players = if self.hotseat {
+            String::new()
+        } else {
+            let (white, black) = self.player_names(shared);
+            format!("White: {}   Black: {}   ", white, black)
+        };
+        let state = if self.game_over() {
+            self.ending_text()
+        } else {
+            format!("{:?} to move", self.referee.game().get_active_colour())
+        };
+        format!("{}{}   {}", players, self.elapsed_text(), state)
+    }
+
+    /// Draws the kiosk bar over the top of the frame, where it hides no squares. It fades out a
+    /// while after the last move or the mouse moving, and stays up once the game is over.
+    fn draw_kiosk_bar(&self, ctx: &mut Context, shared: &Shared) -> GameResult {
+        let since = self.last_move_at.max(self.pointer_moved_at).elapsed();
+        let opacity = if self.game_over() {
+            1.0
+        } else {
+            kiosk_bar_opacity(since)
+        };
+        if opacity <= 0.0 {
+            return Ok(());
+        }
+
+        let bar = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, BOARD_SIZE.0, BOARD_MARGIN),
+            [0.0, 0.0, 0.0, 0.85 * opacity].into(),
+        )?;
+        graphics::draw(ctx, &bar, graphics::DrawParam::default())?;
+
+        let text = graphics::Text::new(
+            graphics::TextFragment::from(self.kiosk_bar_text(shared))
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        let size = text.dimensions(ctx);
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color([1.0, 1.0, 1.0, opacity].into())
+                .dest([(BOARD_SIZE.0 - size.w) / 2.0, (BOARD_MARGIN - size.h) / 2.0]),
+        )
+    }
+
+    /// Whether the opponent is waiting for us to move.
+    pub fn awaiting_move(&self) -> bool {
+        !self.hotseat
+            && !self.game_over()
+            && self.referee.game().get_active_colour() == self.referee.colour
+    }
+
+    /// The window title while the game is shown, saying whose move it is for when the window is
+    /// only a button in the taskbar, e.g. "Schack — fredagsschack — Your move (White) — move 14".
+    pub fn title(&self) -> String {
+        let turn = self.referee.game().get_active_colour();
+        let state = if self.game_over() {
+            "Game over".to_string()
+        } else if self.hotseat {
+            format!("{:?} to move", turn)
+        } else if turn == self.referee.colour {
+            format!("Your move ({:?})", turn)
+        } else {
+            format!("Opponent's move ({:?})", turn)
+        };
+        let mut title = format!(
+            "Schack — {} — {} — move {}",
+            self.referee.room,
+            state,
+            self.moves.len() / 2 + 1
+        );
+        if !self.connected {
+            title.push_str(" (disconnected)");
+        }
+        title
+    }
+
+    /// Whether the player has left the game, going back to the main menu.
+    pub fn left(&self) -> bool {
+        self.leaving
+    }
+
+    /// Whether the settings screen is open, covering the whole window.
+    pub fn showing_settings(&self) -> bool {
+        self.settings.is_some()
+    }
+
+    /// Shows a message in a toast for a moment.
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.push_toast(text, Severity::Info, TOAST_DURATION);
+    }
+
+    /// Whether the opponent has offered a draw we could accept.
+    fn draw_offered_to_us(&self) -> bool {
+        self.referee
+            .draw_offered_by()
+            .is_some_and(|colour| colour != self.referee.colour)
+    }
+
+    /// Offers a draw, or accepts the opponent's offer.
+    fn offer_draw(&mut self) {
+        if self.game_over() {
+            self.push_toast("The game is already over", Severity::Warn, TOAST_DURATION);
+            return;
+        }
+        if self.referee.draw_offered_by() == Some(self.referee.colour) {
+            self.push_toast(
+                "You've already offered a draw",
+                Severity::Warn,
+                TOAST_DURATION,
+            );
+            return;
+        }
+
+        if let Some((msg, event)) = self.referee.offer_draw() {
+            self.send(msg);
+            self.react(event);
+        }
+    }
+
+    /// Whether study aids like the threat view may be used. They're mild help, so online both
+    /// players have to allow them; against the computer or on one board, they're always allowed.
+    fn assists_allowed(&self) -> bool {
+        self.server.is_none()
+            || [Colour::White, Colour::Black]
+                .iter()
+                .all(|colour| self.assists_allowed_by.contains(colour))
+    }
+
+    /// Allows study aids on our side, asking the opponent to allow them too.
+    fn allow_assists(&mut self) {
+        if self.assists_allowed_by.contains(&self.referee.colour) {
+            return;
+        }
+        let msg = Message::AllowAssists {
+            room: self.referee.room.clone(),
+            colour: self.referee.colour,
+        };
+        self.send(msg);
+        self.assists_allowed_by.push(self.referee.colour);
+        self.push_toast(
+            "Asked your opponent to allow the threat view",
+            Severity::Info,
+            TOAST_DURATION,
+        );
+    }
+
+    /// Squares attacked by the opponent of whoever is looking at the board: the player, or the
+    /// side to move on the analysis board. Worked out once for each position.
+    fn threatened_squares(&mut self) -> Vec<Position> {
+        let shown = self.shown_game();
+        let viewer = match self.analysis {
+            Some(_) => shown.get_active_colour(),
+            None => self.referee.colour,
+        };
+        let opponent = match viewer {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        };
+        let key = position_key(shown);
+        if let Some((cached, by, squares)) = &self.threats {
+            if (*cached, *by) == (key, opponent) {
+                return squares.clone();
+            }
+        }
+        let squares = attacked_squares(&shown.get_board(), opponent);
+        self.threats = Some((key, opponent, squares.clone()));
+        squares
+    }
+
+    /// Whether pieces left hanging are marked in this game. Practice games are the ones against
+    /// the computer or on one board; online, marking them is a study aid both players allow.
+    fn marks_hanging_pieces(&self, setting: HangingPieces) -> bool {
+        match setting {
+            HangingPieces::Off => false,
+            HangingPieces::Practice => {
+                self.hotseat || self.difficulty.is_some() || self.server.is_none()
+            }
+            HangingPieces::Always => self.assists_allowed(),
+        }
+    }
+
+    /// Pieces of the side to move on the board shown that are attacked and undefended. Worked
+    /// out once for each position.
+    fn hanging_squares(&mut self) -> Vec<Position> {
+        let shown = self.shown_game();
+        let key = position_key(shown);
+        if let Some((cached, squares)) = &self.hanging {
+            if *cached == key {
+                return squares.clone();
+            }
+        }
+        let squares = hanging_pieces(&shown.get_board(), shown.get_active_colour());
+        self.hanging = Some((key, squares.clone()));
+        squares
+    }
+
+    /// The squares each side attacks on the board shown. Worked out once for each position, so
+    /// hovering over the pieces doesn't work it out again every frame.
+    fn cover(&mut self) -> [[bool; 64]; 2] {
+        let shown = self.shown_game();
+        let key = position_key(shown);
+        if let Some((cached, cover)) = &self.cover {
+            if *cached == key {
+                return *cover;
+            }
+        }
+        let cover = cover(&shown.get_board());
+        self.cover = Some((key, cover));
+        cover
+    }
+
+    /// What the tooltip over the hovered piece says, once the mouse has rested on it long
+    /// enough: the piece, and whether it's attacked or defended where study aids are allowed.
+    /// Nothing while the pieces are hidden or an earlier position is shown.
+    fn tooltip(&mut self, now: Instant) -> Option<String> {
+        let pos = self.hovered?;
+        if now.saturating_duration_since(self.hovered_since) < TOOLTIP_DELAY
+            || self.pieces_hidden()
+            || self.reviewing.is_some()
+        {
+            return None;
+        }
+        let board = self.shown_game().get_board();
+        let piece = board[pos.row * 8 + pos.col]?;
+        let safety = match self.assists_allowed() {
+            true => safety(&board, &self.cover(), pos),
+            false => None,
+        };
+        Some(piece_tooltip(piece, safety))
+    }
+
+    /// The selected piece and how many legal moves it has, e.g. "Knight — 5 legal moves", for
+    /// the status line. Only for a piece of the side to move, whose moves are shown.
+    fn selection_text(&self) -> Option<String> {
+        let pos = self.selected_position?;
+        if self.pieces_hidden() || self.reviewing.is_some() {
+            return None;
+        }
+        let shown = self.shown_game();
+        let piece = shown.get_board()[pos.row * 8 + pos.col]
+            .filter(|piece| piece.colour == shown.get_active_colour())?;
+        let name = piece_name(piece.piece_type);
+        let moves = match self.positions.len() {
+            1 => "1 legal move".to_string(),
+            count => format!("{} legal moves", count),
+        };
+        Some(format!(
+            "{}{} — {}",
+            name[..1].to_uppercase(),
+            &name[1..],
+            moves
+        ))
+    }
+
+    /// Draws the tooltip by the hovered piece, under its square or over it at the bottom of the
+    /// board.
+    fn draw_tooltip(&mut self, ctx: &mut Context) -> GameResult {
+        let (pos, text) = match (self.hovered, self.tooltip(Instant::now())) {
+            (Some(pos), Some(text)) => (pos, text),
+            _ => return Ok(()),
+        };
+        let bubble = Bubble::new(ctx, &text, TOOLTIP_WIDTH, true);
+        let (w, h) = bubble.size();
+        let (x, y) = square_origin(turned(pos, self.flipped));
+        let below = y + GRID_CELL_SIZE.1 as f32 - 6.0;
+        let y = if below + h <= BOARD_SIZE.1 {
+            below
+        } else {
+            y - h + 6.0
+        };
+        let x = (x + 6.0).min(SCREEN_SIZE.0 - w - 4.0);
+        bubble.draw(ctx, [x, y], Severity::Info.colour(), 1.0)
+    }
+
+    /// Greys out the draw and resign buttons when they'd do nothing: once the game is over, or
+    /// the opponent can't hear about it, or a draw offer is waiting for an answer.
+    fn update_hud(&mut self) {
+        let live = self.connected && !self.game_over();
+        let offered = self.referee.draw_offered_by() == Some(self.referee.colour);
+        self.hud.set_enabled(HudAction::OfferDraw, live && !offered);
+        self.hud.set_enabled(HudAction::Resign, live);
+    }
+
+    /// Gives up the game.
+    fn resign(&mut self) {
+        if self.game_over() {
+            self.push_toast("The game is already over", Severity::Warn, TOAST_DURATION);
+            return;
+        }
+
+        if let Some(msg) = self.referee.resign() {
+            self.send(msg);
+        }
+    }
+
+    /// Turns the board round, and starts the next game the same way round.
+    fn flip_board(&mut self, shared: &mut Shared) {
+        self.flipped = !self.flipped;
+        shared.config.board_flipped = self.flipped;
+        shared.config.save();
+    }
+
+    /// Shows or hides the history panel, for this game and the next.
+    fn toggle_move_list(&mut self, shared: &mut Shared) {
+        self.move_list = !self.move_list;
+        self.history_hover = None;
+        shared.config.move_list = self.move_list;
+        shared.config.save();
+    }
+
+    /// Acts on an item picked from the pause menu.
+    fn choose_pause(&mut self, shared: &mut Shared, item: PauseItem) {
+        match item {
+            PauseItem::Resume => self.pause = None,
+            // the pause menu stays open underneath, so leaving the settings comes back to it
+            PauseItem::Settings => {
+                self.settings = Some(SettingsScreen::new(shared.config.clone(), true));
+            }
+            PauseItem::OfferDraw => {
+                self.pause = None;
+                self.offer_draw();
+            }
+            // there's nothing to confirm once the game is over, resign just says so
+            PauseItem::Resign if self.game_over() => {
+                self.pause = None;
+                self.resign();
+            }
+            PauseItem::Resign => self.ask(shared, Guarded::Resign),
+            PauseItem::Quit if self.in_progress() && !self.hotseat => {
+                self.ask(shared, Guarded::Leave)
+            }
+            PauseItem::Quit => self.leave(),
+            // the View submenu stays open, to see what changed underneath
+            PauseItem::MoveList => self.toggle_move_list(shared),
+            PauseItem::FlipBoard => self.flip_board(shared),
+            // the menu opens and leaves the View submenu itself
+            PauseItem::View | PauseItem::Back => (),
+        }
+    }
+
+    /// Asks before doing something that can't be undone, unless confirmations are turned off.
+    /// Whatever was open underneath, like the pause menu, is back if the answer is no.
+    fn ask(&mut self, shared: &Shared, action: Guarded) {
+        if shared.config.confirm_actions {
+            self.confirming = Some((Confirm::new(action.question()), action));
+        } else {
+            self.perform(action);
+        }
+    }
+
+    fn perform(&mut self, action: Guarded) {
+        match action {
+            Guarded::Restart => self.restart(),
+            Guarded::Resign => {
+                self.pause = None;
+                self.resign();
+            }
+            Guarded::Leave => self.leave(),
+            Guarded::Quit => self.quitting = true,
+        }
+    }
+
+    /// Starts a new game on both boards. Online, once moves have been played, the opponent has
+    /// to agree by restarting too: until they have, the reset sent only asks them.
+    fn restart(&mut self) {
+        match self.referee.restart(self.opponent()) {
+            Restart::Asked(msg) => {
+                if let Some(msg) = msg {
+                    self.send(msg);
+                }
+                self.push_toast(
+                    format!("Asked {:?} to restart the game", self.their_colour()),
+                    Severity::Info,
+                    TOAST_DURATION,
+                );
+            }
+            Restart::Restarted(msg) => {
+                self.reset();
+                self.send(msg);
+            }
+        }
+    }
+
+    /// Whether the game is played online against someone, who can be asked for a rematch.
+    fn rematch_possible(&self) -> bool {
+        self.opponent() == Opponent::Online
+    }
+
+    /// Who the game is against, which decides what their messages can do.
+    fn opponent(&self) -> Opponent {
+        if self.hotseat {
+            Opponent::Hotseat
+        } else if self.server.is_some() {
+            Opponent::Online
+        } else {
+            Opponent::Local
+        }
+    }
+
+    /// Agrees to a rematch once the game is over, if automatic rematches are `enabled`, and
+    /// starts the next game once the countdown has run out.
+    fn update_rematch(&mut self, enabled: bool) {
+        if !self.rematch_possible() || !self.game_over() || self.rematch.cancelled {
+            return;
+        }
+
+        if enabled && self.connected && !self.rematch.agreed.contains(&self.referee.colour) {
+            let msg = Message::RematchOk {
+                room: self.referee.room.clone(),
+                colour: self.referee.colour,
+            };
+            self.send(msg);
+            self.rematch.agreed.push(self.referee.colour);
+        }
+
+        match self.rematch.starts_at {
+            Some(at) if Instant::now() >= at => {
+                self.reset();
+                self.referee.colour = match self.referee.colour {
+                    Colour::White => Colour::Black,
+                    Colour::Black => Colour::White,
+                };
+                self.push_toast(
+                    format!("Rematch, you're {:?}", self.referee.colour),
+                    Severity::Info,
+                    TOAST_DURATION,
+                );
+            }
+            Some(_) => (),
+            None if self.rematch.agreed.len() == 2 => {
+                self.rematch.starts_at = Some(Instant::now() + REMATCH_COUNTDOWN);
+            }
+            None => (),
+        }
+    }
+
+    /// Stops the automatic rematch for both players.
+    fn cancel_rematch(&mut self) {
+        self.rematch.cancelled = true;
+        self.rematch.starts_at = None;
+        let msg = Message::RematchCancel {
+            room: self.referee.room.clone(),
+            colour: self.referee.colour,
+        };
+        self.send(msg);
+    }
+
+    /// Writes the game to `games/` as an animated GIF, one frame per position. It's drawn on a
+    /// background thread, with how far it has got in a toast.
+    fn export_gif(&mut self) {
+        if self.gif_export.is_some() {
+            self.push_toast("Already exporting a GIF", Severity::Warn, TOAST_DURATION);
+            return;
+        }
+        let start = animation::Frame {
+            board: Game::new().get_board(),
+            last_move: None,
+        };
+        let played = self
+            .moves
+            .iter()
+            .zip(&self.snapshots)
+            .map(|(record, board)| animation::Frame {
+                board: *board,
+                last_move: Some((record.from, record.to)),
+            });
+        let frames = std::iter::once(start).chain(played).collect();
+        let path = export::game_path(&self.referee.room, SystemTime::now(), ".gif");
+        info!("Exporting the game to {}", path.display());
+
+        let toast = "Exporting GIF…".to_string();
+        self.push_toast(toast.clone(), Severity::Info, TOAST_DURATION);
+        self.gif_export = Some((animation::spawn(path, frames), toast));
+    }
+
+    /// Shows how the GIF export is getting on, and where it was saved once it's done.
+    fn poll_gif_export(&mut self) {
+        let (receiver, toast) = match &mut self.gif_export {
+            Some(export) => export,
+            None => return,
+        };
+        loop {
+            let (text, severity, duration) = match receiver.try_recv() {
+                Ok(Progress::Frame(done, total)) => (
+                    format!("Exporting GIF, {}%", done * 100 / total),
+                    Severity::Info,
+                    TOAST_DURATION,
+                ),
+                // long enough to read where it went
+                Ok(Progress::Done(path)) => (
+                    format!("GIF saved to {}", path.display()),
+                    Severity::Info,
+                    TOAST_DURATION * 2,
+                ),
+                Ok(Progress::Failed(err)) => {
+                    warn!("{}", err);
+                    (err, Severity::Error, ERROR_TOAST_DURATION)
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.gif_export = None;
+                    return;
+                }
+            };
+            self.toasts
+                .retext(toast, text.clone(), severity, duration, Instant::now());
+            *toast = text;
+        }
+    }
+
+    /// Writes the position on the board to `games/` as an SVG diagram: the one being reviewed, or
+    /// on the analysis board, with the move that led to it highlighted.
+    fn export_svg(&mut self, config: &Config) {
+        let (board, last_move, played) = match (self.reviewing, &self.analysis) {
+            (Some(entry), _) => {
+                let record = &self.moves[entry];
+                (
+                    self.snapshots[entry],
+                    Some((record.from, record.to)),
+                    entry + 1,
+                )
+            }
+            (None, Some(analysis)) if !analysis.moves.is_empty() => (
+                analysis.game.get_board(),
+                analysis.moves.last().copied(),
+                self.moves.len() + analysis.moves.len(),
+            ),
+            (None, _) => (
+                self.shown_game().get_board(),
+                self.moves.last().map(|record| (record.from, record.to)),
+                self.moves.len(),
+            ),
+        };
+        let svg = export::svg(&board, last_move, &render::diagram_style(config));
+
+        let suffix = format!("-{}.svg", played);
+        let path = export::game_path(&self.referee.room, SystemTime::now(), &suffix);
+        let written = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir).and_then(|()| fs::write(&path, svg)),
+            None => fs::write(&path, svg),
+        };
+        match written {
+            Ok(()) => {
+                info!("Saved the board to {}", path.display());
+                let text = format!("Diagram saved to {}", path.display());
+                self.push_toast(text, Severity::Info, TOAST_DURATION * 2);
+            }
+            Err(err) => {
+                warn!("Couldn't write {}: {}", path.display(), err);
+                let text = format!("Couldn't save the diagram: {}", err);
+                self.push_toast(text, Severity::Error, ERROR_TOAST_DURATION);
+            }
+        }
+    }
+
+    /// Whether the button cancelling the automatic rematch is shown: from when we agreed until
+    /// the next game starts.
+    fn rematch_cancellable(&self) -> bool {
+        self.rematch_possible()
+            && self.game_over()
+            && !self.rematch.cancelled
+            && self.rematch.agreed.contains(&self.referee.colour)
+    }
+
+    /// Whether piece sprites should be hidden right now. Pieces are shown while peeking
+    /// and once the game is over, so the final position can always be seen.
+    fn pieces_hidden(&self) -> bool {
+        let peeking = self.peek_until.is_some_and(|until| Instant::now() < until);
+
+        self.blindfold != Blindfold::Off && !peeking && !self.game_over()
+    }
+
+    /// The game currently shown on the board: the analysis board if it's open, otherwise the real game.
+    fn shown_game(&self) -> &Game {
+        match &self.analysis {
+            Some(analysis) => &analysis.game,
+            None => self.referee.game(),
+        }
+    }
+
+    /// Starts a new game, discarding the move log and any analysis.
+    fn reset(&mut self) {
+        self.referee.reset();
+        self.positions = vec![];
+        self.selected_position = None;
+        self.moves = vec![];
+        self.snapshots = vec![];
+        self.reviewing = None;
+        self.heat = [0; 64];
+        self.rematch = AutoRematch::new();
+        self.correspondence.stop();
+        self.last_move_at = Instant::now();
+        self.analysis = None;
+        self.premove = None;
+        self.opening = None;
+        self.thinking = None;
+        self.history = vec![position_key(self.referee.game())];
+        self.history_panel.scroll_to(f32::MAX);
+        self.result_sent = false;
+        self.agreed = None;
+        self.archived = false;
+        self.announced_end = false;
+        self.rating = None;
+        self.tutor.reset();
+        self.handover = None;
+        if self.hotseat {
+            self.referee.colour = Colour::White;
+        }
+        self.turn_to_mover(true);
+        let room = self.referee.room.clone();
+        self.write_movelog(|movelog| movelog.section(&room));
+        self.leave_snapshot();
+        self.keep_correspondence();
+    }
+
+    /// Everything about the game worth a look in a bug report, with the settings it's played
+    /// with, secrets left out.
+    fn state_snapshot(&self, config: &Config, now: SystemTime) -> StateSnapshot {
+        let colour_name = |colour| protocol::colour_name(colour).to_string();
+        let mut snapshot = StateSnapshot {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            taken: snapshot::seconds(now),
+            config: config.clone(),
+            game: snapshot::GameState {
+                room: self.referee.room.clone(),
+                colour: colour_name(self.referee.colour),
+                hotseat: self.hotseat,
+                counter: self.referee.counter(),
+                moves: self.moves.iter().map(MoveRecord::name).collect(),
+                fen: fen(self.referee.game(), &self.moves),
+                position_keys: self.history.clone(),
+                ending: self.referee.ending().map(|ending| format!("{:?}", ending)),
+                draw_offered_by: self.referee.draw_offered_by().map(colour_name),
+            },
+            connection: snapshot::Connection {
+                server: self.server.clone(),
+                connected: self.connected,
+                correspondence: self.correspondence.active(),
+                desync: self.desync.is_some(),
+                received: self.net.received,
+                ignored: self.net.ignored,
+                echoes: self.net.echoes,
+                repeated: self.net.repeated,
+                flooded: self.net.flooded,
+                unasked: self.net.unasked,
+                sent: self.net.sent,
+                waited: self.net.waited,
+                deferred: self.net.deferred,
+                outbox: self.outbox.len(),
+            },
+            messages: self
+                .traffic
+                .iter()
+                .map(|logged| {
+                    snapshot::LoggedMessage::new(logged.ms, logged.direction, &logged.text)
+                })
+                .collect(),
+        };
+        snapshot.redact();
+        snapshot
+    }
+
+    /// Saves a snapshot of the game for a bug report in the working directory, and says where.
+    fn save_snapshot(&mut self, config: &Config) {
+        match self
+            .state_snapshot(config, SystemTime::now())
+            .save(Path::new("."))
+        {
+            Ok(path) => {
+                info!("Saved a snapshot of the game to {}", path.display());
+                let text = format!("Snapshot saved to {}", path.display());
+                self.push_toast(text, Severity::Info, TOAST_DURATION * 2);
+            }
+            Err(err) => {
+                warn!("Couldn't save a snapshot: {}", err);
+                let text = format!("Couldn't save the snapshot: {}", err);
+                self.push_toast(text, Severity::Error, ERROR_TOAST_DURATION);
+            }
+        }
+    }
+
+    /// Plays on a game saved when the program crashed, from the position it stopped at. Moves
+    /// aren't sent, there's no one to send them to.
+    pub fn resume(&mut self, saved: &SavedGame) {
+        // the moves were announced the first time they were played
+        let announcer = self.announcer.take();
+        for (from, to) in saved.positions() {
+            let before = self.referee.game().get_board();
+            if let Err(err) = self.referee.play_on(from, to) {
+                warn!(
+                    "Stopped resuming the saved game at an illegal move: {}",
+                    err
+                );
+                self.push_toast(
+                    format!(
+                        "The saved game stops after {} moves, the next one is illegal",
+                        self.moves.len()
+                    ),
+                    Severity::Warn,
+                    ERROR_TOAST_DURATION,
+                );
+                break;
+            }
+            self.record_move(from, to, &before);
+        }
+        self.announcer = announcer;
+        if self.hotseat {
+            self.referee.colour = self.referee.game().get_active_colour();
+        }
+        self.turn_to_mover(false);
+        info!(
+            "Resumed the game from room {} after {} moves",
+            saved.room,
+            self.moves.len()
+        );
+        self.push_toast(
+            format!("Playing on after {} moves", self.moves.len()),
+            Severity::Info,
+            TOAST_DURATION,
+        );
+    }
+
+    /// Leaves the game as it is now for the panic hook to save, with the message resigning it
+    /// online, so it isn't lost if the program crashes.
+    fn leave_snapshot(&self) {
+        let online = self.server.is_some() && !self.hotseat && !self.correspondence.active();
+        let leave = (online && self.connected).then(|| {
+            let msg = Message::Resign {
+                room: self.referee.room.clone(),
+                colour: self.referee.colour,
+            };
+            (self.sender.clone(), msg.to_string())
+        });
+        crash::update(Snapshot {
+            game: SavedGame {
+                room: self.referee.room.clone(),
+                fen: fen(self.referee.game(), &self.moves),
+                moves: self.moves.iter().map(MoveRecord::name).collect(),
+            },
+            leave,
+        });
+    }
+
+    /// Whether playing a move would reach a position for the third time, letting either player
+    /// claim a draw. Applies the move to a copy of the game, so it's only worth doing for one move.
+    fn allows_repetition_claim(&self, from: Position, to: Position) -> bool {
+        let mut game = self.referee.game().clone();
+        if game.make_move_pos(from, to).is_err() {
+            return false;
+        }
+
+        let key = position_key(&game);
+        self.history.iter().filter(|&&seen| seen == key).count() >= 2
+    }
+
+    /// Adds a move that has just been applied to the log, and looks the game up in the opening book.
+    /// Both our own and the opponent's moves go through here.
+    fn record_move(&mut self, from: Position, to: Position, before: &[Option<Piece>; 64]) {
+        let now = Instant::now();
+        let record = MoveRecord::new(
+            from,
+            to,
+            before,
+            &self.referee.game().get_board(),
+            now - self.last_move_at,
+        );
+        self.moves.push(record);
+        self.snapshots.push(self.referee.game().get_board());
+        let square = &mut self.heat[to.row * 8 + to.col];
+        *square = square.saturating_add(1);
+        self.last_move_at = now;
+        self.history.push(position_key(self.referee.game()));
+
+        let (counter, fen) = (
+            self.referee.counter(),
+            fen(self.referee.game(), &self.moves),
+        );
+        self.write_movelog(|movelog| movelog.record(counter, record.colour, &record.name(), &fen));
+        self.announce(&announce::move_text(&record, self.moves.len() - 1));
+        self.leave_snapshot();
+        self.keep_correspondence();
+
+        let moves = self
+            .moves
+            .iter()
+            .map(MoveRecord::name)
+            .collect::<Vec<String>>();
+        self.opening = openings::opening_name(&moves);
+    }
+
+    /// Announces something for playing by ear, if announcements are on. If they've stopped, e.g.
+    /// because the pipe was closed, they're turned off for the rest of the game.
+    fn announce(&mut self, text: &str) {
+        if let Some(Err(_)) = self.announcer.as_ref().map(|announcer| announcer.say(text)) {
+            self.announcer = None;
+        }
+    }
+
+    /// Announces how the game ended, once it has.
+    fn announce_end(&mut self) {
+        if self.announced_end || self.announcer.is_none() || !self.game_over() {
+            return;
+        }
+        self.announced_end = true;
+        let stuck = self.referee.game().get_active_colour();
+        let end = match self.referee.ending() {
+            Some(Ending::Resigned(colour)) => End::Resigned(colour),
+            Some(Ending::DrawAgreed) => End::DrawAgreed,
+            None if king_attacked(&self.referee.game().get_board(), stuck) => {
+                End::Checkmate(match stuck {
+                    Colour::White => Colour::Black,
+                    Colour::Black => Colour::White,
+                })
+            }
+            None => End::Stalemate,
+        };
+        self.announce(&announce::end_text(end));
+    }
+
+    /// Shows a short message in the corner of the window, after any that are waiting.
+    fn push_toast(&mut self, text: impl Into<String>, severity: Severity, duration: Duration) {
+        self.toasts.push(text, severity, duration, Instant::now());
+    }
+
+    /// Shows a recoverable error in a toast. Losing the connection is only reported once.
+    fn report(&mut self, err: AppError) {
+        if let AppError::Disconnected = err {
+            if !self.connected {
+                return;
+            }
+            self.connected = false;
+            self.announce(announce::CONNECTION_LOST);
+            self.chat_notice("Connection to the server lost");
+        }
+        warn!("{}", err);
+        self.push_toast(err.to_string(), Severity::Error, ERROR_TOAST_DURATION);
+    }
+
+    /// Sends a message to the server, noting that the connection is gone if it can't be sent.
+    /// Sends a message to the opponent. If the network thread is behind, the message waits in
+    /// the outbox and goes out in order on a later frame; meanwhile the status bar says the
+    /// connection is congested.
+    fn send(&mut self, msg: Message) {
+        let msg = protocol::tag(&msg.to_string(), self.instance);
+        self.traffic.push(Direction::Out, &msg);
+        self.outbox.push_back(msg);
+        self.flush();
+        if !self.outbox.is_empty() {
+            self.net.waited += 1;
+        }
+    }
+
+    /// Hands as much of the outbox to the network thread as it has room for.
+    fn flush(&mut self) {
+        while let Some(msg) = self.outbox.pop_front() {
+            match self.sender.try_send(msg) {
+                Ok(()) => self.net.sent += 1,
+                Err(TrySendError::Full(msg)) => {
+                    self.outbox.push_front(msg);
+                    return;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    self.outbox.clear();
+                    self.report(AppError::Disconnected);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Plays one of our own moves and sends it to the opponent.
+    fn play_move(&mut self, from: Position, to: Position) -> Result<(), String> {
+        // the opponent would never see the move
+        if !self.connected {
+            return Err(AppError::Disconnected.to_string());
+        }
+
+        let before = self.referee.game().get_board();
+        let position = self.tutoring().then(|| self.referee.game().clone());
+        let msg = self.referee.play(from, to)?;
+        self.record_move(from, to, &before);
+
+        self.tutor
+            .played(position, from, to, self.referee.counter());
+
+        // send move to server
+        self.send(msg);
+
+        self.selected_position = None;
+        self.positions = vec![];
+
+        // when hotseat, the next move is made from the same computer
+        if self.hotseat {
+            self.referee.colour = self.referee.game().get_active_colour();
+            self.hand_over();
+        }
+
+        Ok(())
+    }
+
+    /// Handles a click on the board while it's the opponent's turn: the first click picks one of
+    /// our pieces, the second picks where it should go once the opponent has moved.
+    fn queue_premove(&mut self, pos: Position) {
+        let own_piece = self.referee.game().get_board()[pos.row * 8 + pos.col]
+            .is_some_and(|piece| piece.colour == self.referee.colour);
+
+        match self.selected_position {
+            // clicking another of our pieces picks that one instead
+            _ if own_piece => {
+                self.selected_position = Some(pos);
+                self.premove = None;
+            }
+            Some(from) => {
+                self.premove = Some((from, pos));
+                self.selected_position = None;
+            }
+            None => self.explain(Ignored::NotYourTurn(
+                self.referee.game().get_active_colour(),
+            )),
+        }
+    }
+
+    /// Says why a click did nothing, rather than leaving the player to wonder.
+    fn explain(&mut self, ignored: Ignored) {
+        self.push_toast(ignored.message(), Severity::Warn, TOAST_DURATION);
+    }
+
+    /// Handles a click on a square, selecting pieces and playing moves. Typed moves go through here too,
+    /// so they behave exactly like a pair of clicks.
+    fn click_square(&mut self, clicked: Position) {
+        // while the opponent is thinking, clicks queue up a premove instead
+        if self.analysis.is_none()
+            && !self.game_over()
+            && self.referee.game().get_active_colour() != self.referee.colour
+        {
+            self.queue_premove(clicked);
+            return;
+        }
+
+        // in analysis mode either colour may move, since nothing is sent to the opponent
+        let analysing = self.analysis.is_some();
+        if !analysing && self.game_over() {
+            self.explain(Ignored::GameOver);
+            return;
+        }
+        let game = self.shown_game();
+        let turn = game.get_active_colour();
+
+        // convert row, col to idx
+        let idx = clicked.row * 8 + clicked.col;
+
+        // check if the selected position has a piece and that it's the player's turn; it's ours
+        // unless analysing, as the opponent's turn went to the premove above
+        let ignored = match game.get_board()[idx] {
+            Some(piece) if piece.colour == turn && (analysing || turn == self.referee.colour) => {
+                // get possible moves for the selected piece
+                let available_moves = game.get_possible_moves(clicked, 0);
+
+                // set available moves to App State, and which of them capture for their markers
+                self.captures = captures(&game.get_board(), clicked, &available_moves);
+                self.positions = available_moves;
+
+                // set selected position to App State
+                self.selected_position = Some(clicked);
+                None
+            }
+            Some(_) if analysing || self.hotseat => Some(Ignored::NotToMove(turn)),
+            Some(_) => Some(Ignored::NotYourPiece(self.referee.colour)),
+            None => Some(Ignored::EmptySquare(clicked)),
+        };
+
+        // only a selected piece can have destinations, so there's nothing more to do without one
+        let selected = match (self.selected_position, ignored) {
+            (Some(selected), _) => selected,
+            (None, Some(ignored)) => return self.explain(ignored),
+            (None, None) => return,
+        };
+
+        // moves on the analysis board stay local
+        if let Some(analysis) = &mut self.analysis {
+            if self.positions.contains(&clicked)
+                && analysis.game.make_move_pos(selected, clicked).is_ok()
+            {
+                analysis.moves.push((selected, clicked));
+                self.selected_position = None;
+                self.positions = vec![];
+            }
+            return;
+        }
+
+        // check if clicked position is in self.positions
+        if self.positions.contains(&clicked) {
+            // if the move was successful, play_move clears the selected position
+            if let Err(err) = self.play_move(selected, clicked) {
+                self.push_toast(err, Severity::Warn, TOAST_DURATION);
+            }
+        } else if selected != clicked {
+            // explain why nothing happened, so beginners aren't left guessing
+            self.push_toast(
+                rejection_reason(self.referee.game(), selected, clicked),
+                Severity::Warn,
+                TOAST_DURATION,
+            );
+        } else if self.positions.is_empty() {
+            self.push_toast(
+                stuck_reason(self.referee.game(), selected),
+                Severity::Warn,
+                TOAST_DURATION,
+            );
+        }
+    }
+
+    /// Handles a left click on the board. With double-clicks turned on, a second click on a piece
+    /// that has only one legal move plays it; otherwise it's an ordinary click.
+    fn click_board(&mut self, clicked: Position, config: &Config) {
+        let now = Instant::now();
+        let threshold = Duration::from_millis(config.double_click_ms);
+        let double = self.last_click.is_some_and(|(square, at)| {
+            square == clicked && now.saturating_duration_since(at) <= threshold
+        });
+        // a third click starts over, rather than being a second double-click
+        self.last_click = if double { None } else { Some((clicked, now)) };
+
+        if double && config.double_click_move {
+            if let Some(to) = self.only_move(clicked) {
+                self.click_square(clicked);
+                self.click_square(to);
+                return;
+            }
+        }
+        self.click_square(clicked);
+    }
+
+    /// Moves the keyboard cursor with the arrow keys, and presses the square it's on with Space,
+    /// or Enter once it's showing. Pressing a square does what clicking it would. Keys bound to
+    /// a shortcut are left to the shortcut. Returns whether the key was used.
+    fn cursor_key(&mut self, key: event::KeyCode, action: Option<Action>) -> bool {
+        // rows and columns on the screen, so Up is always up however the board is turned
+        let step = match key {
+            event::KeyCode::Up => Some((-1, 0)),
+            event::KeyCode::Down => Some((1, 0)),
+            event::KeyCode::Left => Some((0, -1)),
+            event::KeyCode::Right => Some((0, 1)),
+            _ => None,
+        };
+        let pressed = match key {
+            event::KeyCode::Space => action.is_none(),
+            event::KeyCode::Return | event::KeyCode::NumpadEnter => self.cursor.is_some(),
+            _ => false,
+        };
+        if !pressed && (action.is_some() || step.is_none()) {
+            return false;
+        }
+
+        // the first key only shows the cursor, in front of our king or on the piece picked up
+        let cursor = match self.cursor {
+            Some(cursor) => cursor,
+            None => {
+                let home = match self.referee.colour {
+                    Colour::White => Position::new(6, 4),
+                    Colour::Black => Position::new(1, 4),
+                };
+                self.cursor = self.selected_position.or(home.ok());
+                return true;
+            }
+        };
+
+        if let Some((rows, cols)) = step {
+            let screen = turned(cursor, self.flipped);
+            let row = (screen.row as isize + rows).clamp(0, 7) as usize;
+            let col = (screen.col as isize + cols).clamp(0, 7) as usize;
+            if let Ok(screen) = Position::new(row, col) {
+                self.cursor = Some(turned(screen, self.flipped));
+            }
+        } else if self.reviewing.is_some() {
+            // as with a click on the board, only goes back to the game
+            self.reviewing = None;
+        } else {
+            self.click_square(cursor);
+        }
+        true
+    }
+
+    /// Where the piece on a square can go, if it's one that may move now and it has exactly one
+    /// legal move.
+    fn only_move(&self, from: Position) -> Option<Position> {
+        let analysing = self.analysis.is_some();
+        let game = self.shown_game();
+        let turn = game.get_active_colour();
+        let movable = game.get_board()[from.row * 8 + from.col]
+            .is_some_and(|piece| piece.colour == turn)
+            && (analysing || turn == self.referee.colour);
+        if !movable {
+            return None;
+        }
+        match game.get_possible_moves(from, 0)[..] {
+            [to] => Some(to),
+            _ => None,
+        }
+    }
+
+    /// Plays a move typed into the input box, the same way as clicking both squares would.
+    fn submit_move_input(&mut self) {
+        let text = self.move_input.take().unwrap_or_default();
+        let (from, to, promotion) = match parse_move(&text) {
+            Some(parsed) => parsed,
+            None => {
+                self.push_toast(
+                    "Type moves like e2e4, or e7e8q to promote",
+                    Severity::Warn,
+                    TOAST_DURATION,
+                );
+                return;
+            }
+        };
+
+        // the chess library always promotes to a queen
+        if let Some(piece) = promotion {
+            let promotes = self.shown_game().get_board()[from.row * 8 + from.col]
+                .is_some_and(|p| p.piece_type == PieceType::Pawn)
+                && (to.row == 0 || to.row == 7);
+            if !promotes {
+                self.push_toast(
+                    "Only pawns reaching the last rank can promote",
+                    Severity::Warn,
+                    TOAST_DURATION,
+                );
+                return;
+            }
+            if piece != PieceType::Queen {
+                self.push_toast(
+                    "Pawns can only be promoted to queens",
+                    Severity::Warn,
+                    TOAST_DURATION,
+                );
+                return;
+            }
+        }
+
+        // start from a clean selection, so the first square can't be mistaken for a destination
+        self.selected_position = None;
+        self.positions = vec![];
+        self.click_square(from);
+        if self.selected_position != Some(from) {
+            self.push_toast(
+                format!("You have no piece to move on {}", square_name(from)),
+                Severity::Warn,
+                TOAST_DURATION,
+            );
+            return;
+        }
+        self.click_square(to);
+    }
+
+    /// Takes back our move that the server refused, the one that took the counter to `counter`,
+    /// picking its piece up again so another move can be played in its place. Anything queued
+    /// after it goes too: it was never going to be accepted. A refusal of a move the opponent has
+    /// since answered means the games have diverged.
+    fn rejected(&mut self, counter: u32, reason: &str) {
+        let index = match (counter as usize).checked_sub(2) {
+            Some(index) if index < self.moves.len() => index,
+            _ => {
+                self.net.ignored += 1;
+                return;
+            }
+        };
+        let record = self.moves[index];
+        if !self.hotseat && record.colour != self.referee.colour {
+            self.net.ignored += 1;
+            return;
+        }
+        if counter != self.referee.counter() {
+            self.desynced(counter, None);
+            return;
+        }
+        warn!("The server refused {}: {}", record.name(), reason);
+
+        // the move and everything sent after it is dead, rather than going out on a later frame
+        self.outbox.retain(|msg| match Message::parse(msg) {
+            Ok(Message::Move { counter: later, .. }) => later < counter,
+            Ok(Message::Result { .. }) => false,
+            _ => true,
+        });
+        self.result_sent = false;
+        self.agreed = None;
+
+        self.rewind(counter - 1);
+
+        // the premove was planned for after the opponent's answer, which won't come now
+        self.premove = None;
+        let destinations = self.referee.game().get_possible_moves(record.from, 0);
+        self.captures = captures(&self.referee.game().get_board(), record.from, &destinations);
+        self.positions = destinations;
+        self.selected_position = Some(record.from);
+
+        let reason = match reason.trim() {
+            "" => "no reason given",
+            reason => reason,
+        };
+        self.push_toast(
+            format!("The server refused {}: {}", record.name(), reason),
+            Severity::Warn,
+            ERROR_TOAST_DURATION,
+        );
+    }
+
+    /// Takes the game back to how it was when the move counter was `counter`.
+    fn rewind(&mut self, counter: u32) {
+        self.referee.rewind(counter);
+        self.rewound();
+    }
+
+    /// Cuts the move log, and everything kept with it, back to the moves the referee has left.
+    fn rewound(&mut self) {
+        let kept = self.referee.moves().len();
+        self.moves.truncate(kept);
+        self.snapshots.truncate(kept);
+        self.history.truncate(kept + 1);
+        self.heat = heat(&self.moves);
+        self.reviewing = None;
+        self.opening = openings::opening_name(&self.move_names());
+        if self.hotseat {
+            self.referee.colour = self.referee.game().get_active_colour();
+        }
+        self.turn_to_mover(true);
+        self.leave_snapshot();
+        self.keep_correspondence();
+    }
+
+    /// Whether the board is handed from one player to the other between moves: only in a game
+    /// on one board that's still going, never online, where each player has a board of their own.
+    fn handing_over(&self) -> bool {
+        self.hotseat && self.server.is_none() && !self.game_over()
+    }
+
+    /// Turns the board round to the side to move, if the setting's on. It spins round unless
+    /// `animated` is false.
+    fn turn_to_mover(&mut self, animated: bool) {
+        let flipped = self.referee.colour == Colour::Black;
+        if self.hotseat_flip && self.handing_over() && self.flipped != flipped {
+            self.flipped = flipped;
+            self.turned_at = animated.then(Instant::now);
+        }
+    }
+
+    /// Passes the board to the side to move after a move on it, as the settings say: turns it
+    /// round to them, and hides it until they click. While it's hidden it turns without spinning,
+    /// as there's nothing to see.
+    fn hand_over(&mut self) {
+        if self.hotseat_cover && self.handing_over() {
+            self.handover = Some(self.referee.colour);
+        }
+        self.turn_to_mover(self.handover.is_none());
+    }
+
+    /// Whether the tutor checks our moves: if it's turned on, and only against the computer or
+    /// across the desk, never online.
+    fn tutoring(&self) -> bool {
+        self.tutor.on && self.server.is_none() && (self.hotseat || self.difficulty.is_some())
+    }
+
+    /// Offers our last move back once the tutor finds it might lose material, and withdraws the
+    /// offer once its time is up.
+    fn update_tutor(&mut self, now: Instant) {
+        let game_over = self.game_over();
+        if let Some(swing) = self.tutor.update(now, self.referee.moves(), game_over) {
+            info!("The tutor flagged a move losing {} centipawns", swing);
+            self.push_toast(
+                "That might lose material — take it back?",
+                Severity::Warn,
+                tutor::TAKEBACK_OFFER,
+            );
+        }
+    }
+
+    /// What one of the replayed game's moves is marked as, once it's been scored.
+    fn annotation(&self, entry: usize) -> Option<Annotation> {
+        self.annotations.annotation(entry)
+    }
+
+    /// Keeps the suggested moves to the position on the analysis board, and the buttons labelled
+    /// as they're found.
+    fn update_suggestions(&mut self) {
+        let shown = self.analysis.as_ref().map(|analysis| &analysis.game);
+        let suggestions = match self.suggestions.follow(shown) {
+            Some(suggestions) => suggestions,
+            None => return,
+        };
+        for i in 0..SUGGESTIONS {
+            let label = match suggestions.found().get(i) {
+                Some(found) => format!(
+                    "{}{} {}",
+                    square_name(found.from),
+                    square_name(found.to),
+                    suggest::score_text(found.score)
+                ),
+                None if suggestions.done() => String::new(),
+                None => "Thinking…".to_string(),
+            };
+            self.suggested.set_label(i, &label);
+            self.suggested.set_enabled(i, i < suggestions.found().len());
+        }
+    }
+
+    /// The suggested move numbered `i`, if it's been found for the position on the analysis board.
+    fn suggestion(&self, i: usize) -> Option<(Position, Position)> {
+        let analysis = self.analysis.as_ref()?;
+        self.suggestions.get(&analysis.game, i)
+    }
+
+    /// Plays a suggested move on the analysis board.
+    fn play_suggestion(&mut self, i: usize) {
+        let (from, to) = match self.suggestion(i) {
+            Some(found) => found,
+            None => return,
+        };
+        if let Some(analysis) = &mut self.analysis {
+            if analysis.game.make_move_pos(from, to).is_ok() {
+                analysis.moves.push((from, to));
+                self.selected_position = None;
+                self.positions = vec![];
+                self.captures = vec![];
+            }
+        }
+    }
+
+    /// Takes back the move the tutor flagged, along with the computer's reply if it's come, and
+    /// tells the computer opponent to do the same. Its moves are ignored until it has.
+    fn take_back(&mut self) {
+        let counter = match self.tutor.take_offer() {
+            Some(counter) => counter,
+            None => return,
+        };
+        let msg = self.referee.take_back(counter);
+        self.rewound();
+        self.selected_position = None;
+        self.positions = vec![];
+        self.captures = vec![];
+        self.premove = None;
+        self.thinking = None;
+        self.last_move_at = Instant::now();
+        self.send(msg);
+    }
+
+    /// Plays the queued premove if it's legal in the new position, otherwise drops it silently.
+    fn play_premove(&mut self) {
+        if let Some((from, to)) = self.premove.take() {
+            // the piece may have been captured, or the move may no longer be legal, e.g. due to a check
+            let own_piece = self.referee.game().get_board()[from.row * 8 + from.col]
+                .is_some_and(|piece| piece.colour == self.referee.colour);
+
+            if own_piece
+                && self
+                    .referee
+                    .game()
+                    .get_possible_moves(from, 0)
+                    .contains(&to)
+            {
+                let _ = self.play_move(from, to);
+            }
+        }
+    }
+
+    /// Lines shown in the history panel: the game's moves, followed by the analysis variation
+    /// indented. Each line is a list of texts and how far from the panel's left edge they start,
+    /// so the moves line up in columns that can be pointed at.
+    fn history_lines(&self) -> Vec<Vec<(f32, String)>> {
+        let mut lines =
+            self.moves
+                .chunks(2)
+                .enumerate()
+                .map(|(i, pair)| {
+                    let mut line = vec![(10.0, format!("{}.", i + 1))];
+                    line.extend(HISTORY_COLUMNS.iter().zip(pair).enumerate().map(
+                        |(j, (&x, m))| {
+                            let symbol = self.annotation(i * 2 + j).map_or("", Annotation::symbol);
+                            (x, format!("{}{}", m.name(), symbol))
+                        },
+                    ));
+                    line
+                })
+                .collect::<Vec<_>>();
+
+        if let Some(analysis) = &self.analysis {
+            lines.push(vec![(10.0, "  Analysis:".to_string())]);
+            for (i, (from, to)) in analysis.moves.iter().enumerate() {
+                // continue the numbering from where the game ended
+                let ply = self.moves.len() + i;
+                let dots = match ply % 2 {
+                    0 => ".",
+                    _ => "...",
+                };
+                lines.push(vec![(
+                    10.0,
+                    format!(
+                        "    {}{} {}{}",
+                        ply / 2 + 1,
+                        dots,
+                        square_name(*from),
+                        square_name(*to)
+                    ),
+                )]);
+            }
+        }
+
+        lines
+    }
+
+    /// Draws the board as it was after one of the game's moves, a quarter of its size, to the left
+    /// of the history panel at the height of the mouse. The board was saved when the move was
+    /// played, so nothing is replayed.
+    fn draw_preview(
+        &mut self,
+        ctx: &mut Context,
+        shared: &Shared,
+        entry: usize,
+        mouse_y: f32,
+    ) -> GameResult {
+        let size = SQUARES_SIZE.0 * PREVIEW_SCALE;
+        let x = HISTORY_RECT.x - size - 12.0;
+        let y = (mouse_y - size / 2.0).clamp(4.0, BOARD_SIZE.1 - size - 4.0);
+        let frame = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(x - 3.0, y - 3.0, size + 6.0, size + 6.0),
+            [0.1, 0.1, 0.1, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &frame, graphics::DrawParam::default())?;
+
+        let record = &self.moves[entry];
+        let highlighted = [record.from, record.to];
+        let state = render::BoardState {
+            board: self.snapshots[entry],
+            pieces_hidden: self.pieces_hidden(),
+            dots_hidden: true,
+            selected: None,
+            highlighted: &highlighted,
+            attacked: &[],
+            hanging: &[],
+            dots: &[],
+            captures: &[],
+            markers: shared.config.markers,
+            repetition: None,
+            check_pulse: Some(1.0),
+            flipped: self.flipped,
+            cursor: None,
+            palette: palette(shared),
+            heat: None,
+        };
+
+        // the squares are laid out at full size, so the screen is stretched to shrink them into
+        // place, on top of whatever the tabs did to it. The frame is left out
+        let screen = graphics::screen_coordinates(ctx);
+        graphics::set_screen_coordinates(
+            ctx,
+            graphics::Rect::new(
+                BOARD_ORIGIN.0 + (screen.x - x) / PREVIEW_SCALE,
+                BOARD_ORIGIN.1 + (screen.y - y) / PREVIEW_SCALE,
+                screen.w / PREVIEW_SCALE,
+                screen.h / PREVIEW_SCALE,
+            ),
+        )?;
+        let mut canvas = render::Screen {
+            ctx,
+            pieces: shared,
+            markers: &mut self.markers,
+        };
+        let drawn = render::draw_board(&mut canvas, &render::squares(&state));
+        graphics::set_screen_coordinates(ctx, screen)?;
+        drawn
+    }
+
+    /// Shows the position after the move clicked in the history panel, if one was. Clicking the
+    /// latest move goes back to the game as it is.
+    fn review_clicked_move(&mut self, x: f32, y: f32) -> bool {
+        if !self.move_list {
+            return false;
+        }
+        let offset = self.history_panel.offset();
+        match history_entry_at(self.moves.len(), offset, x, y) {
+            Some(entry) => {
+                self.reviewing = (entry + 1 < self.moves.len()).then_some(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Shows the position a move before or after the one on the board, starting from the game
+    /// as it is. Stepping past the latest move goes back to the game, and there's nothing before
+    /// the first, so however fast it's done it never leaves the moves played.
+    fn step_review(&mut self, back: bool) {
+        let latest = match self.moves.len().checked_sub(1) {
+            Some(latest) => latest,
+            None => return,
+        };
+        let shown = self.reviewing.map_or(latest, |entry| entry.min(latest));
+        let entry = match back {
+            true => shown.saturating_sub(1),
+            false => (shown + 1).min(latest),
+        };
+        self.reviewing = (entry < latest).then_some(entry);
+
+        // keep its line in the history panel in view
+        let top = (entry / 2) as f32 * HISTORY_LINE;
+        let offset = self.history_panel.offset();
+        if top < offset {
+            self.history_panel.scroll_to(top);
+        } else if top + HISTORY_LINE > offset + HISTORY_RECT.h {
+            self.history_panel
+                .scroll_to(top + HISTORY_LINE - HISTORY_RECT.h);
+        }
+    }
+
+    /// What the network info overlay says, given the frame rate and the time now.
+    fn network_info_text(&self, fps: f64, now: Instant) -> String {
+        let state = if !self.connected {
+            "disconnected"
+        } else if !self.outbox.is_empty() {
+            "congested"
+        } else {
+            "ok"
+        };
+        let quiet = match self.traffic.since_last_in(now) {
+            Some(since) => format!("{:.1}s ago", since.as_secs_f32()),
+            None => "never".to_string(),
+        };
+        let mut text = format!(
+            "Connection: {}\n\
+             Move counter: {}\n\
+             Frames per second: {:.0}\n\
+             Last received: {}\n\
+             Received: {} ({} ignored, {} our own, {} last frame)\n\
+             Dropped: {} repeated, {} in a flood, {} not asked for\n\
+             Sent: {} ({} waiting now)\n\
+             Had to wait: {}\n\
+             Busy frames: {}\n\
+             Square bonuses: {}\n",
+            state,
+            self.referee.counter(),
+            fps,
+            quiet,
+            self.net.received,
+            self.net.ignored,
+            self.net.echoes,
+            self.net.last_frame,
+            self.net.repeated,
+            self.net.flooded,
+            self.net.unasked,
+            self.net.sent,
+            self.outbox.len(),
+            self.net.waited,
+            self.net.deferred,
+            self.piece_squares.map_or("off", piece_name)
+        );
+        let traffic = self.traffic.iter();
+        let skipped = traffic.len().saturating_sub(TRAFFIC_SHOWN);
+        for logged in traffic.skip(skipped) {
+            let arrow = match logged.direction {
+                Direction::In => "<-",
+                Direction::Out => "->",
+            };
+            text.push_str(&format!(
+                "\n{:>8.3}s {} {}",
+                logged.ms as f64 / 1000.0,
+                arrow,
+                logged.text
+            ));
+        }
+        text
+    }
+
+    /// The square under a point on the screen, as the board is drawn.
+    fn square_under(&self, x: f32, y: f32) -> Option<Position> {
+        square_at(x, y).map(|pos| turned(pos, self.flipped))
+    }
+
+    /// Draws the connection statistics and the last messages each way in the top-left corner of
+    /// the board. Nothing is formatted unless the overlay is open.
+    fn draw_network_info(&self, ctx: &mut Context) -> GameResult {
+        let text = graphics::Text::new(
+            graphics::TextFragment::from(
+                self.network_info_text(ggez::timer::fps(ctx), Instant::now()),
+            )
+            .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        let dimensions = text.dimensions(ctx);
+
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(4.0, 4.0, dimensions.w + 12.0, dimensions.h + 12.0),
+            [0.1, 0.1, 0.1, 0.85].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color([1.0, 1.0, 1.0, 1.0].into())
+                .dest([10.0, 10.0]),
+        )
+    }
+
+    /// Acts on the messages from the network thread that have arrived since the last frame.
+    fn receive(&mut self) {
+        self.net.last_frame = 0;
+        for _ in 0..MAX_MESSAGES_PER_FRAME {
+            // stop once the game has failed, e.g. on a desync, rather than read past it
+            if self.failure.is_some() || self.desync.is_some() {
+                return;
+            }
+            match self.to_mainthread_receiver.try_recv() {
+                Ok(msg) => {
+                    self.net.received += 1;
+                    self.net.last_frame += 1;
+                    self.traffic.push(Direction::In, &msg);
+                    self.handle_message(msg);
+                }
+                // no message in channel
+                Err(TryRecvError::Empty) => return,
+                // channel has been disconnected (main thread has terminated)
+                Err(TryRecvError::Disconnected) => {
+                    self.fail(AppError::Disconnected);
+                    return;
+                }
+            }
+        }
+        self.net.deferred += 1;
+    }
+
+    /// Acts on one message from the opponent or server.
+    fn handle_message(&mut self, msg: String) {
+        // the server sends what we send back to us too, so anything tagged as ours is dropped
+        // here, whatever kind of message it is
+        if protocol::untag(&msg).0 == Some(self.instance) {
+            self.net.echoes += 1;
+            return;
+        }
+        // a peer over the network may repeat itself or flood the room, where the bots and
+        // hotseat games on this computer don't
+        if self.server.is_some() {
+            match self.inbound.check(&msg, Instant::now()) {
+                Verdict::Take => (),
+                Verdict::Repeated => {
+                    self.net.repeated += 1;
+                    return;
+                }
+                Verdict::Flooded { started } => {
+                    self.net.flooded += 1;
+                    if started {
+                        warn!("Too many messages are arriving, ignoring them for a while");
+                        self.push_toast(
+                            format!(
+                                "Too many messages from the network, ignoring them for {}s",
+                                inbound::IGNORE_FOR.as_secs()
+                            ),
+                            Severity::Warn,
+                            ERROR_TOAST_DURATION,
+                        );
+                    }
+                    return;
+                }
+            }
+        }
+
+        // ignore messages we don't understand, and messages for other rooms
+        let msg = match Message::parse(&msg) {
+            Ok(parsed) if parsed.room() == self.referee.room => parsed,
+            Ok(_) => {
+                self.net.ignored += 1;
+                return;
+            }
+            Err(err) => {
+                self.net.ignored += 1;
+                self.report(AppError::BadMessage(msg, err));
+                return;
+            }
+        };
+
+        // the moves, offers and restarts are the referee's, syncing a correspondence game is
+        // its keeper's, the opponent's chat is the chat's, and what's left is ours
+        let msg = match self.referee.handle(msg, self.opponent()) {
+            Event::Other(msg) => msg,
+            event => {
+                self.react(event);
+                return;
+            }
+        };
+        let names = self.move_names();
+        let msg = match self.correspondence.handle(msg, &self.referee, &names) {
+            correspondence::Event::Other(msg) => msg,
+            correspondence::Event::Reply(msgs) => {
+                msgs.into_iter().for_each(|msg| self.send(msg));
+                return;
+            }
+            correspondence::Event::Rejoined(offer) => {
+                self.chat_notice(format!("{:?} connected", self.their_colour()));
+                self.send(offer);
+                return;
+            }
+            correspondence::Event::Diverged(counter) => {
+                self.desynced(counter, None);
+                return;
+            }
+        };
+        let msg = if self.chat_allowed() {
+            match self
+                .chat
+                .handle(msg, self.referee.colour, SystemTime::now())
+            {
+                chat::Event::Other(msg) => msg,
+                chat::Event::Reaction(text) => {
+                    self.reactions.push(
+                        text,
+                        Severity::Reaction,
+                        REACTION_DURATION,
+                        Instant::now(),
+                    );
+                    return;
+                }
+                chat::Event::Said(colour, text) => {
+                    if self.chat_input.is_none() {
+                        self.push_toast(
+                            format!("{:?}: {}", colour, text),
+                            Severity::Info,
+                            TOAST_DURATION,
+                        );
+                    }
+                    return;
+                }
+            }
+        } else {
+            msg
+        };
+
+        match msg {
+            Message::Result { score, .. } if !self.hotseat => {
+                self.check_result(score);
+            }
+            Message::Reject {
+                counter, reason, ..
+            } => {
+                self.rejected(counter, &reason);
+            }
+            // the computer opponent reports how deep it's searching
+            Message::Thinking { depth, .. } => {
+                self.thinking = Some(depth);
+            }
+            Message::Spectate { token, name, .. } => {
+                self.spectators.saw(&token, &name, Instant::now());
+            }
+            Message::AllowAssists { colour, .. }
+                if !self.hotseat
+                    && colour != self.referee.colour
+                    && !self.assists_allowed_by.contains(&colour) =>
+            {
+                self.assists_allowed_by.push(colour);
+                let text = if self.assists_allowed() {
+                    "The threat view is allowed for both players".to_string()
+                } else {
+                    format!(
+                        "{:?} asks to allow the threat view, use its key to agree",
+                        colour
+                    )
+                };
+                self.push_toast(text, Severity::Info, TOAST_DURATION);
+            }
+            Message::RematchOk { colour, .. }
+                if self.rematch_possible()
+                    && self.game_over()
+                    && colour != self.referee.colour
+                    && !self.rematch.agreed.contains(&colour) =>
+            {
+                self.rematch.agreed.push(colour);
+            }
+            Message::RematchCancel { colour, .. }
+                if self.rematch_possible()
+                    && colour != self.referee.colour
+                    && self.game_over()
+                    && !self.rematch.cancelled =>
+            {
+                self.rematch.cancelled = true;
+                self.rematch.starts_at = None;
+                self.chat_notice(format!("{:?} cancelled the rematch", colour));
+                self.push_toast(
+                    format!("{:?} cancelled the rematch", colour),
+                    Severity::Info,
+                    TOAST_DURATION,
+                );
+            }
+            // asked again, or with no game over to play again after
+            Message::RematchOk { .. } | Message::RematchCancel { .. } => self.net.unasked += 1,
+            _ => (),
+        }
+    }
+
+    /// Shows what a message from the opponent, or an offer of ours, did to the game.
+    fn react(&mut self, event: Event) {
+        match event {
+            Event::Nothing | Event::Other(_) => (),
+            Event::Stale => self.net.ignored += 1,
+            Event::Repeated => self.net.repeated += 1,
+            Event::Unasked => self.net.unasked += 1,
+            Event::Moved { from, to, before } => {
+                self.selected_position = None;
+                self.positions = vec![];
+                self.thinking = None;
+                self.record_move(from, to, &before);
+
+                // it's our turn now, so play the premove if there is one
+                self.play_premove();
+            }
+            Event::OutOfSync { counter } => self.desynced(counter, None),
+            Event::Restarted => self.reset(),
+            Event::RestartAsked(colour) => {
+                self.chat_notice(format!("{:?} asked to restart the game", colour));
+                self.push_toast(
+                    format!(
+                        "{:?} asks to restart the game, restart too to agree",
+                        colour
+                    ),
+                    Severity::Info,
+                    TOAST_DURATION,
+                );
+            }
+            Event::DrawOffered(colour) => {
+                self.announce(&announce::draw_offer_text(colour));
+                if colour == self.referee.colour {
+                    self.push_toast("Draw offered", Severity::Info, TOAST_DURATION);
+                } else {
+                    self.chat_notice(format!("{:?} offered a draw", colour));
+                    self.push_toast(
+                        format!("{:?} offers a draw, accept from the Escape menu", colour),
+                        Severity::Info,
+                        TOAST_DURATION,
+                    );
+                }
+            }
+            Event::DrawAgreed => self.push_toast("Draw agreed", Severity::Info, TOAST_DURATION),
+            Event::Resigned(colour) => {
+                self.chat_notice(format!("{:?} resigned", colour));
+                self.push_toast(
+                    format!("{:?} resigned", colour),
+                    Severity::Info,
+                    TOAST_DURATION,
+                );
+            }
+        }
+    }
+
+    /// Draws the game and whatever is open over it, without presenting the frame, so the tabs
+    /// can draw theirs above it.
+    pub fn draw_game(&mut self, ctx: &mut Context, shared: &Shared, layout: Layout) -> GameResult {
+        // the draw and resign buttons move with the layout
+        if layout != self.layout {
+            self.layout = layout;
+            self.hud = hud_buttons(layout);
+            self.update_hud();
+        }
+
+        // clear interface with gray background colour, or black around the board alone
+        let kiosk = layout == Layout::Kiosk;
+        if kiosk {
+            graphics::clear(ctx, [0.0, 0.0, 0.0, 1.0].into());
+        } else {
+            graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+        }
+
+        let splash_text: String;
+
+        // if game state is GameOver, draw game over screen
+        if self.analysis.is_some() {
+            splash_text = format!(
+                "Analysis, it's {:?} turn. Press A to go back",
+                self.shown_game().get_active_colour()
+            );
+        } else if self.game_over() {
+            let ending = self.ending_text();
+            let stats = game_stats(&self.moves, &self.referee.game().get_board());
+            let longest_think = match stats.longest_think {
+                Some((colour, time)) => format!("{:?}, {}", colour, format_duration(time)),
+                None => "-".to_string(),
+            };
+            let rating = match self.rating {
+                Some((rating, delta)) => format!("\nRating: {:.0} ({:+.0})", rating, delta),
+                None => String::new(),
+            };
+            let next = match self.rematch.starts_at {
+                Some(at) if !self.rematch.cancelled => {
+                    let left = at.saturating_duration_since(Instant::now());
+                    format!("rematch starting in {}…", left.as_secs_f32().ceil())
+                }
+                _ => "press R to restart or A to analyse!".to_string(),
+            };
+            splash_text = format!(
+                "{}, {}\n\n\
+                 Moves: {}\n\
+                 Captures: White {}, Black {}\n\
+                 Checks: White {}, Black {}\n\
+                 Longest think: {}\n\
+                 Material: White {}, Black {}{}",
+                ending,
+                next,
+                stats.moves,
+                stats.captures.0,
+                stats.captures.1,
+                stats.checks.0,
+                stats.checks.1,
+                longest_think,
+                stats.material.0,
+                stats.material.1,
+                rating
+            );
+        } else {
+            splash_text = format!(
+                "{:?}, it's {:?} turn. You're {:?}",
+                self.referee.game().get_game_state(),
+                self.referee.game().get_active_colour(),
+                self.referee.colour
+            );
+        }
+
+        // create text representation
+        let state_text = graphics::Text::new(
+            graphics::TextFragment::from(splash_text).scale(graphics::PxScale { x: 30.0, y: 30.0 }),
+        );
+
+        // get size of text
+        let text_dimensions = state_text.dimensions(ctx);
+        // create background rectangle with white coulouring
+        let background_box = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                (BOARD_SIZE.0 - text_dimensions.w) / 2.0 - 8.0,
+                (BOARD_SIZE.1 - text_dimensions.h) / 2.0,
+                text_dimensions.w + 16.0,
+                text_dimensions.h,
+            ),
+            [1.0, 1.0, 1.0, 1.0].into(),
+        )?;
+
+        // draw background
+        if !kiosk {
+            graphics::draw(ctx, &background_box, graphics::DrawParam::default())?;
+        }
+
+        // readable minutes and seconds since the game started, like "1:43"
+        let time = self.elapsed_text();
+
+        // show the blindfold level next to the other status info
+        let blindfold_text = match self.blindfold {
+            Blindfold::Off => "",
+            Blindfold::Pieces => "  Blindfold",
+            Blindfold::PiecesAndDots => "  Blindfold+",
+        };
+
+        // show the computer opponent's strength, and how far it's got while it thinks, or how
+        // far the analysis of a replayed game has got
+        let ai_text = match (
+            self.difficulty,
+            self.thinking,
+            self.annotations.progress_text(),
+        ) {
+            (Some(_), Some(depth), _) => format!("  thinking… depth {}", depth),
+            (Some(difficulty), None, _) => format!("  AI level {}", difficulty),
+            (None, _, Some(progress)) => format!("  {}", progress),
+            (None, _, None) => String::new(),
+        };
+
+        let connection_text = if !self.connected {
+            "  Disconnected"
+        } else if !self.outbox.is_empty() {
+            "  Congested"
+        } else {
+            ""
+        };
+
+        // the selected piece's moves take the room's place while one's picked up
+        let lead = match self.selection_text() {
+            Some(selection) => selection,
+            None => format!("Room: {}", self.referee.room),
+        };
+
+        // draw text at bottom  of screen
+        let bottom_text = graphics::Text::new(
+            graphics::TextFragment::from(format!(
+                "{}  Turn: {}     Time: {}{}{}{}",
+                lead,
+                self.referee.counter(),
+                time,
+                blindfold_text,
+                ai_text,
+                connection_text
+            ))
+            .scale(graphics::PxScale { x: 30.0, y: 30.0 }),
+        );
+
+        // get dimensions of bottom status text
+        let bottom_text_dimensions = bottom_text.dimensions(ctx);
+
+        let pieces_hidden = self.pieces_hidden();
+        let board = match self.reviewing {
+            Some(entry) => self.snapshots[entry],
+            None => self.shown_game().get_board(),
+        };
+
+        // check the hovered destination for a repetition, but no other dots
+        let repetition_square = match (self.selected_position, self.hovered) {
+            (Some(from), Some(to))
+                if self.analysis.is_none()
+                    && self.reviewing.is_none()
+                    && self.positions.contains(&to)
+                    && self.allows_repetition_claim(from, to) =>
+            {
+                Some(to)
+            }
+            _ => None,
+        };
+
+        // squares of the move being reviewed, or of the queued premove, or the piece picked for one
+        let highlighted = match (self.reviewing, self.premove, self.selected_position) {
+            (Some(entry), _, _) => vec![self.moves[entry].from, self.moves[entry].to],
+            _ if self.analysis.is_some() => vec![],
+            (None, Some((from, to)), _) => vec![from, to],
+            (None, None, Some(selected))
+                if self.referee.game().get_active_colour() != self.referee.colour =>
+            {
+                vec![selected]
+            }
+            _ => vec![],
+        };
+
+        // wash the squares the opponent attacks while the threat view key is held
+        let reviewing = self.reviewing.is_some();
+        let attacked = if self.threats_held.is_some() && self.assists_allowed() && !reviewing {
+            self.threatened_squares()
+        } else {
+            vec![]
+        };
+
+        // mark the pieces the side to move would lose for nothing
+        let hanging = if self.marks_hanging_pieces(shared.config.hanging_pieces) && !reviewing {
+            self.hanging_squares()
+        } else {
+            vec![]
+        };
+
+        // while the board turns round to the side to move, it's squeezed edge-on and opened out
+        // again, showing the side it turned from until it's halfway
+        let (flipped, squeeze) = match self.turned_at.and_then(|at| turning(at.elapsed())) {
+            Some((scale, before)) => (self.flipped != before, Some(scale)),
+            None => (self.flipped, None),
+        };
+
+        // the engine's square bonuses for the side at the bottom, over the heatmap
+        let bottom = if flipped {
+            Colour::Black
+        } else {
+            Colour::White
+        };
+        let bonuses = self
+            .piece_squares
+            .map(|piece_type| piece_square_heat(piece_type, bottom));
+
+        // draw grid
+        let state = render::BoardState {
+            board,
+            pieces_hidden,
+            dots_hidden: self.blindfold == Blindfold::PiecesAndDots,
+            selected: self.selected_position.filter(|_| !reviewing),
+            highlighted: &highlighted,
+            attacked: &attacked,
+            hanging: &hanging,
+            dots: if reviewing { &[] } else { &self.positions },
+            captures: &self.captures,
+            markers: shared.config.markers,
+            repetition: repetition_square,
+            check_pulse: Some(check_pulse(self.last_move_at.elapsed())),
+            flipped,
+            cursor: self.cursor,
+            palette: palette(shared),
+            heat: bonuses
+                .as_ref()
+                .or((self.heatmap && self.heatmap_allowed()).then_some(&self.heat)),
+        };
+        let screen = graphics::screen_coordinates(ctx);
+        if let Some(scale) = squeeze {
+            let squeezed = layout::squeezed(screen, BOARD_SIZE.0 / 2.0, scale);
+            graphics::set_screen_coordinates(ctx, squeezed)?;
+        }
+        self.frame.draw(ctx, flipped)?;
+        let mut canvas = render::Screen {
+            ctx,
+            pieces: shared,
+            markers: &mut self.markers,
+        };
+        let drawn = render::draw_board(&mut canvas, &render::squares(&state));
+        graphics::set_screen_coordinates(ctx, screen)?;
+        drawn?;
+
+        // the suggested move under the mouse, as an arrow over the pieces
+        if let Some((from, to)) = self.suggested.hovered().and_then(|i| self.suggestion(i)) {
+            render::draw_arrow(ctx, &render::arrow(from, to, self.flipped))?;
+        }
+
+        // with the board alone in the window, the players and time are all that goes over it
+        if kiosk {
+            return self.draw_kiosk_bar(ctx, shared);
+        }
+
+        // draw text with dark gray colouring and center position
+        graphics::draw(
+            ctx,
+            &state_text,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: (BOARD_SIZE.0 - text_dimensions.w) / 2.0,
+                    y: (BOARD_SIZE.1 - text_dimensions.h) / 2.0,
+                }),
+        )?;
+        if self.rematch_cancellable() {
+            self.rematch.cancel.draw(ctx)?;
+        }
+        if self.game_over() {
+            self.export_gif.draw(ctx)?;
+        }
+        if self.tutor.offering() {
+            self.takeback.draw(ctx)?;
+        }
+        if self.analysis.is_some() {
+            self.suggested.draw(ctx)?;
+        }
+        if self.move_input.is_none() {
+            self.hud.draw(ctx)?;
+        }
+
+        // a small window has the board, with the status and the buttons on one line under it
+        if layout == Layout::Mini {
+            let status = graphics::Text::new(
+                graphics::TextFragment::from(format!(
+                    "Turn: {}  Time: {}{}{}",
+                    self.referee.counter(),
+                    time,
+                    ai_text,
+                    connection_text
+                ))
+                .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            let size = status.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &status,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([5.0, SCREEN_SIZE.1 - (40.0 + size.h) / 2.0]),
+            )?;
+            self.draw_waiting(ctx, size.w + 12.0)?;
+            return self.toasts.draw(ctx, Instant::now(), BOARD_SIZE.0);
+        }
+
+        // draw the opening name at the top of the history panel, wrapped to fit
+        let mut opening_text = graphics::Text::new(
+            graphics::TextFragment::from(self.opening.unwrap_or(""))
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        opening_text.set_bounds([PANEL_WIDTH - 20.0, f32::INFINITY], graphics::Align::Left);
+        graphics::draw(
+            ctx,
+            &opening_text,
+            graphics::DrawParam::default()
+                .color([0.1, 0.1, 0.4, 1.0].into())
+                .dest([BOARD_SIZE.0 + 10.0, 10.0]),
+        )?;
+        self.draw_players(ctx, shared)?;
+
+        // warn at the bottom of the panel when the hovered move allows a draw claim
+        if repetition_square.is_some() {
+            let mut repetition_text = graphics::Text::new(
+                graphics::TextFragment::from("This move allows a draw claim by repetition")
+                    .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+            );
+            repetition_text.set_bounds([PANEL_WIDTH - 20.0, f32::INFINITY], graphics::Align::Left);
+            graphics::draw(
+                ctx,
+                &repetition_text,
+                graphics::DrawParam::default()
+                    .color([0.8, 0.4, 0.0, 1.0].into())
+                    .dest([BOARD_SIZE.0 + 10.0, BOARD_SIZE.1 - 50.0]),
+            )?;
+        }
+
+        // in the same place, say which earlier move the board shows
+        if let Some(entry) = self.reviewing {
+            let dots = match entry % 2 {
+                0 => ".",
+                _ => "...",
+            };
+            let mut review_text = graphics::Text::new(
+                graphics::TextFragment::from(format!(
+                    "After {}{} {}, click the board to go back",
+                    entry / 2 + 1,
+                    dots,
+                    self.moves[entry].name()
+                ))
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+            );
+            review_text.set_bounds([PANEL_WIDTH - 20.0, f32::INFINITY], graphics::Align::Left);
+            graphics::draw(
+                ctx,
+                &review_text,
+                graphics::DrawParam::default()
+                    .color([0.1, 0.1, 0.4, 1.0].into())
+                    .dest([BOARD_SIZE.0 + 10.0, BOARD_SIZE.1 - 50.0]),
+            )?;
+        }
+
+        // draw move history panel to the right of the board, scrolled to the latest lines
+        let lines = if self.move_list {
+            self.history_lines()
+        } else {
+            vec![]
+        };
+        self.history_panel
+            .set_content_height(lines.len() as f32 * HISTORY_LINE);
+        self.history_panel.draw(ctx, |ctx| {
+            for (i, line) in lines.iter().enumerate() {
+                for (x, text) in line {
+                    let text = graphics::Text::new(
+                        graphics::TextFragment::from(text.as_str())
+                            .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+                    );
+                    graphics::draw(
+                        ctx,
+                        &text,
+                        graphics::DrawParam::default()
+                            .color([0.0, 0.0, 0.0, 1.0].into())
+                            .dest([HISTORY_RECT.x + x, HISTORY_RECT.y + i as f32 * HISTORY_LINE]),
+                    )?;
+                }
+            }
+            Ok(())
+        })?;
+
+        // after the history, so the list of spectators goes over it
+        self.draw_spectators(ctx)?;
+
+        // the position after the move under the mouse, beside the panel
+        if let Some([x, y]) = self.history_hover {
+            let offset = self.history_panel.offset();
+            if let Some(entry) = history_entry_at(self.moves.len(), offset, x, y) {
+                self.draw_preview(ctx, shared, entry, y)?;
+            }
+        }
+
+        // draw status text at bottom
+        graphics::draw(
+            ctx,
+            &bottom_text,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest(ggez::mint::Point2 {
+                    x: 5.0,
+                    y: SCREEN_SIZE.1 - bottom_text_dimensions.h,
+                }),
+        )?;
+        self.draw_waiting(ctx, bottom_text_dimensions.w + 17.0)?;
+
+        if let Some(input) = &self.chat_input {
+            self.draw_chat(ctx, input)?;
+        }
+
+        // draw the move input box under the history panel
+        if let Some(input) = &self.move_input {
+            let input_box = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(
+                    BOARD_SIZE.0 + 10.0,
+                    BOARD_SIZE.1 + 5.0,
+                    PANEL_WIDTH - 20.0,
+                    30.0,
+                ),
+                [1.0, 1.0, 1.0, 1.0].into(),
+            )?;
+            graphics::draw(ctx, &input_box, graphics::DrawParam::default())?;
+
+            let input_text = graphics::Text::new(
+                graphics::TextFragment::from(format!("Move: {}_", input))
+                    .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            graphics::draw(
+                ctx,
+                &input_text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([BOARD_SIZE.0 + 15.0, BOARD_SIZE.1 + 8.0]),
+            )?;
+        }
+
+        self.draw_tooltip(ctx)?;
+
+        // draw toasts on top of everything else, and the opponent's reactions by their name
+        self.toasts.draw(ctx, Instant::now(), SCREEN_SIZE.0)?;
+        self.draw_reactions(ctx)?;
+
+        // passing the board across the desk hides all of it, toasts too
+        if let Some(colour) = self.handover {
+            handover::draw(ctx, colour)?;
+        }
+
+        if self.network_info {
+            self.draw_network_info(ctx)?;
+        }
+        if self.help {
+            keys::draw_help(ctx, &shared.config.keys)?;
+        }
+
+        // the pause menu goes over everything, while the game carries on underneath
+        if let Some(pause) = &self.pause {
+            let labels = pause::Labels {
+                draw_offered: self.draw_offered_to_us(),
+                move_list: self.move_list,
+            };
+            pause.draw(ctx, labels)?;
+        }
+        if let Some((confirm, _)) = &self.confirming {
+            confirm.draw(ctx)?;
+        }
+        if let Some(desync) = &self.desync {
+            desync.draw(ctx)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Which of the game's `moves` is listed at a point in the history panel, when it's scrolled
+/// down by `offset`. The number in front of a line counts as its white move.
+fn history_entry_at(moves: usize, offset: f32, x: f32, y: f32) -> Option<usize> {
+    if !HISTORY_RECT.contains([x, y]) {
+        return None;
+    }
+    let line = ((y - HISTORY_RECT.y + offset) / HISTORY_LINE) as usize;
+    let column = usize::from(x - HISTORY_RECT.x >= HISTORY_COLUMNS[1]);
+    let entry = line * 2 + column;
+    (entry < moves).then_some(entry)
+}
+
+/// How red a king in check is `since` the move that checked it, starting from its own colour and
+/// easing in and out once every `CHECK_PULSE_PERIOD`.
+fn check_pulse(since: Duration) -> f32 {
+    let phase = since.as_secs_f32() / CHECK_PULSE_PERIOD.as_secs_f32();
+    0.5 - 0.5 * (phase * std::f32::consts::TAU).cos()
+}
+
+/// How the board is drawn `since` it started turning round to the side to move: how much of its
+/// width shows, and whether it still shows the side it turned from. None once it's done.
+fn turning(since: Duration) -> Option<(f32, bool)> {
+    let progress = since.as_secs_f32() / TURN_DURATION.as_secs_f32();
+    let width = (progress * std::f32::consts::PI).cos().abs().max(0.01);
+    (progress < 1.0).then_some((width, progress < 0.5))
+}
+
+/// The pieces the engine has square bonuses for, in the order the network info steps through.
+const PIECE_SQUARE_TYPES: [PieceType; 4] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Queen,
+];
+
+/// The piece whose square bonuses are shown after `current`'s, and after the last none.
+fn next_piece_squares(current: Option<PieceType>) -> Option<PieceType> {
+    match current {
+        None => Some(PIECE_SQUARE_TYPES[0]),
+        Some(piece_type) => PIECE_SQUARE_TYPES
+            .iter()
+            .skip_while(|&&shown| shown != piece_type)
+            .nth(1)
+            .copied(),
+    }
+}
+
+/// The engine's bonuses for a `colour` piece of `piece_type` as heat for the heatmap's ramp: the
+/// square it likes least is the coolest and the one it likes most the hottest.
+fn piece_square_heat(piece_type: PieceType, colour: Colour) -> [u16; 64] {
+    let bonuses: Vec<i32> = (0..64)
+        .map(|idx| ai::square_bonus(piece_type, colour, idx))
+        .collect();
+    let least = bonuses.iter().copied().min().unwrap_or(0);
+    let mut heat = [0; 64];
+    for (square, bonus) in heat.iter_mut().zip(bonuses) {
+        *square = u16::try_from(bonus - least + 1).unwrap_or(u16::MAX);
+    }
+    heat
+}
+
+/// How strongly the kiosk bar is drawn `since` the last move or the mouse moving, from 1 while
+/// it's up to 0 once it's faded out.
+fn kiosk_bar_opacity(since: Duration) -> f32 {
+    let fading = since.saturating_sub(KIOSK_BAR_SHOWN);
+    1.0 - (fading.as_secs_f32() / KIOSK_BAR_FADE.as_secs_f32()).min(1.0)
+}
+
+/// The squares' colours: in kiosk mode they're made to stand out from across a room.
+fn palette(shared: &Shared) -> render::Palette {
+    if shared.args.kiosk {
+        render::Palette::HighContrast
+    } else {
+        render::Palette::Wood
+    }
+}
+
+/// How strongly each of the three dots waiting on the opponent is drawn `since` the wait began:
+/// a pulse runs along them once every `THINKING_DOTS_PERIOD`, never leaving one invisible.
+fn thinking_dots(since: Duration) -> [f32; 3] {
+    let phase = since.as_secs_f32() / THINKING_DOTS_PERIOD.as_secs_f32();
+    [0.0, 1.0, 2.0].map(|i| {
+        let pulse = 0.5 + 0.5 * ((phase - i / 3.0) * std::f32::consts::TAU).cos();
+        0.25 + 0.75 * pulse
+    })
+}
+
+// This is where we implement the functions that ggez requires to function
+/// Once the game is closed, there's nothing for the panic hook to save.
+impl Drop for AppState {
+    fn drop(&mut self) {
+        crash::clear();
+    }
+}
+
+impl Scene for AppState {
+    /// For updating game logic, which front-end doesn't handle.
+    /// It won't be necessary to touch this unless you are implementing something that's not triggered by the user, like a clock
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        // drop toasts that have been shown long enough, making room for waiting ones
+        self.toasts.update(Instant::now());
+        self.reactions.update(Instant::now());
+
+        if let Some([x, y]) = self.long_press(Instant::now()) {
+            self.mouse_button_down(ctx, shared, event::MouseButton::Right, x, y);
+            self.mouse_button_up(ctx, shared, event::MouseButton::Right, x, y);
+        }
+
+        // act on the confirmation, pause menu and settings screen, if they're open
+        if let Some((confirm, action)) = &mut self.confirming {
+            if let Some(yes) = confirm.take_answer() {
+                let action = *action;
+                self.confirming = None;
+                if yes {
+                    self.perform(action);
+                }
+            }
+        }
+        self.abandon_if_asked();
+        if let Some(item) = self.pause.as_mut().and_then(PauseMenu::take_choice) {
+            self.choose_pause(shared, item);
+        }
+        if self.settings.as_ref().is_some_and(SettingsScreen::leaving) {
+            if let Some(screen) = self.settings.take() {
+                self.apply_config(shared, screen.config().clone());
+            }
+        }
+
+        self.flush();
+        self.receive();
+        self.spectators.expire(Instant::now());
+        self.send_result();
+        self.update_waiting(Instant::now());
+        self.send_chat(Instant::now());
+        self.send_missed_moves(Instant::now());
+        self.update_tutor(Instant::now());
+        self.note_finished(shared);
+        if self.game_over() {
+            self.correspondence.finish();
+        }
+        self.archive(shared);
+        self.announce_end();
+        if self.rematch.cancel.take_choice().is_some() {
+            self.cancel_rematch();
+        }
+        if self.export_gif.take_choice().is_some() {
+            self.export_gif();
+        }
+        if self.takeback.take_choice().is_some() {
+            self.take_back();
+        }
+        self.update_suggestions();
+        self.annotations.update();
+        if let Some(i) = self.suggested.take_choice() {
+            self.play_suggestion(i);
+        }
+        match self.hud.take_choice() {
+            Some(HudAction::OfferDraw) => self.offer_draw(),
+            Some(HudAction::Resign) => self.ask(shared, Guarded::Resign),
+            None => (),
+        }
+        self.update_hud();
+        self.poll_gif_export();
+        self.update_rematch(shared.config.auto_rematch);
+
+        // the settings changed during the game are already shared, so nothing is lost here
+        if let Some(dialog) = self.failure.take() {
+            return Ok(Transition::Replace(Box::new(dialog)));
+        }
+        if self.quitting {
+            return Ok(Transition::Quit);
+        }
+        if self.leaving {
+            return Ok(Transition::Replace(Box::new(Menu::new())));
+        }
+        Ok(Transition::None)
+    }
+
+    /// Asks before closing the window on a game in progress, like leaving it from the pause
+    /// menu. Closing it again while asked doesn't ask twice.
+    fn quit_requested(&mut self, _ctx: &mut Context, shared: &mut Shared) -> bool {
+        let asked = matches!(self.confirming, Some((_, Guarded::Quit)));
+        if asked || self.quitting || !self.in_progress() || self.hotseat {
+            return false;
+        }
+        if !shared.config.confirm_actions {
+            return false;
+        }
+        self.pause = None;
+        self.ask(shared, Guarded::Quit);
+        true
+    }
+
+    fn shut_down(&mut self, shared: &mut Shared) {
+        self.close(shared);
+    }
+
+    /// In kiosk mode the board fills the window alone, and in a small window it has one line
+    /// under it, unless something's open that needs the room around it.
+    fn layout(&self, shared: &Shared, window: (f32, f32)) -> Layout {
+        let covered = self.settings.is_some()
+            || self.pause.is_some()
+            || self.confirming.is_some()
+            || self.desync.is_some()
+            || self.help
+            || self.network_info
+            || self.move_input.is_some()
+            || self.chat_input.is_some()
+            || self.handover.is_some();
+        if covered {
+            Layout::Standard
+        } else if shared.args.kiosk {
+            Layout::Kiosk
+        } else if layout::cramped(window) {
+            Layout::Mini
+        } else {
+            Layout::Standard
+        }
+    }
+
+    /// Draw interface, i.e. draw game board
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult {
+        // the settings screen covers the whole window
+        if let Some(settings) = &self.settings {
+            return settings.draw(ctx);
+        }
+        let layout = self.layout(shared, graphics::drawable_size(ctx));
+        self.draw_game(ctx, shared, layout)?;
+
+        // render updated graphics
+        graphics::present(ctx)
+    }
+
+    /// Keeps track of the hovered square, for the repetition warning. A finger doesn't hover, so
+    /// nothing that's only for hovering is shown while one's used.
+    fn mouse_motion(&mut self, _ctx: &mut Context, shared: &mut Shared, x: f32, y: f32) {
+        self.pointer_moved_at = Instant::now();
+        let touch = shared.pointer == Pointer::Touch;
+        self.rematch.cancel.set_touch(touch);
+        self.export_gif.set_touch(touch);
+        self.takeback.set_touch(touch);
+        self.suggested.set_touch(touch);
+        self.hud.set_touch(touch);
+
+        if let Some(desync) = &mut self.desync {
+            desync.mouse_motion_event(x, y);
+        } else if let Some((confirm, _)) = &mut self.confirming {
+            confirm.mouse_motion_event(x, y);
+        } else if let Some(settings) = &mut self.settings {
+            settings.mouse_motion_event(x, y);
+        } else if let Some(pause) = &mut self.pause {
+            pause.mouse_motion_event(x, y);
+        } else {
+            let hovered = self.square_under(x, y).filter(|_| !touch);
+            if hovered != self.hovered {
+                self.hovered_since = Instant::now();
+            }
+            self.hovered = hovered;
+            self.history_panel.mouse_motion_event(x, y);
+            self.history_hover =
+                (self.move_list && HISTORY_RECT.contains([x, y]) && !touch).then_some([x, y]);
+            self.spectators_hover = SPECTATORS_RECT.contains([x, y]) && !touch;
+            self.rematch.cancel.mouse_motion_event(x, y);
+            self.export_gif.mouse_motion_event(x, y);
+            self.takeback.mouse_motion_event(x, y);
+            self.suggested.mouse_motion_event(x, y);
+            self.hud.mouse_motion_event(x, y);
+        }
+    }
+
+    /// Grabs the history panel's scrollbar.
+    fn mouse_button_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: event::MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        if let Some(desync) = &mut self.desync {
+            desync.mouse_button_down_event(button, x, y);
+        } else if self.confirming.is_none() && self.settings.is_none() && self.pause.is_none() {
+            self.history_panel.mouse_button_down_event(button, x, y);
+            if self.rematch_cancellable() {
+                self.rematch.cancel.mouse_button_down_event(button, x, y);
+            }
+            if self.game_over() {
+                self.export_gif.mouse_button_down_event(button, x, y);
+            }
+            if self.tutor.offering() {
+                self.takeback.mouse_button_down_event(button, x, y);
+            }
+            if self.analysis.is_some() {
+                self.suggested.mouse_button_down_event(button, x, y);
+            }
+            if self.move_input.is_none() {
+                self.hud.mouse_button_down_event(button, x, y);
+            }
+        }
+    }
+
+    /// Steps through the moves played while the mouse is over the board, and scrolls the move
+    /// history anywhere else.
+    fn mouse_wheel(&mut self, _ctx: &mut Context, shared: &mut Shared, _x: f32, y: f32) {
+        if self.desync.is_some()
+            || self.confirming.is_some()
+            || self.settings.is_some()
+            || self.pause.is_some()
+        {
+            return;
+        }
+        if shared.config.wheel_steps_moves && self.hovered.is_some() {
+            // rolling it away, like scrolling up, goes back
+            if y != 0.0 {
+                self.step_review(y > 0.0);
+            }
+        } else {
+            self.history_panel.mouse_wheel_event(y);
+        }
+    }
+
+    /// Update game on mouse click
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        shared: &mut Shared,
+        button: event::MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        // while a menu is open, clicks only reach the menu
+        if let Some(desync) = &mut self.desync {
+            desync.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if let Some((confirm, _)) = &mut self.confirming {
+            confirm.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if let Some(settings) = &mut self.settings {
+            settings.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if let Some(pause) = &mut self.pause {
+            pause.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if self.help {
+            self.help = false;
+            return;
+        }
+        // the board's passed over once the next player clicks
+        if self.handover.is_some() {
+            self.handover = None;
+            return;
+        }
+        if self.history_panel.mouse_button_up_event(button) {
+            return;
+        }
+        // the middle button flips the board wherever it's clicked, even while reviewing
+        if button == event::MouseButton::Middle && shared.config.middle_click_flip {
+            self.flip_board(shared);
+            return;
+        }
+        // the takeback is over the move history, so it's taken before a move there
+        if self.tutor.offering() && self.takeback.contains(x, y) {
+            self.takeback.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if self.analysis.is_some() && self.suggested.contains(x, y) {
+            self.suggested.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if button == event::MouseButton::Left && self.review_clicked_move(x, y) {
+            return;
+        }
+        if self.rematch_cancellable() && self.rematch.cancel.contains(x, y) {
+            self.rematch.cancel.mouse_button_up_event(button, x, y);
+            return;
+        }
+        if self.game_over() && self.export_gif.contains(x, y) {
+            self.export_gif.mouse_button_up_event(button, x, y);
+            return;
+        }
+        // the buttons are taken first, whatever's under them, and greyed out ones take the
+        // click too, so nothing else gets it
+        if self.move_input.is_none() && self.hud.contains(x, y) {
+            self.hud.mouse_button_up_event(button, x, y);
+            return;
+        }
+        // a click on the board while reviewing only goes back to the game
+        if self.reviewing.is_some() && self.square_under(x, y).is_some() {
+            self.reviewing = None;
+            return;
+        }
+
+        // right-click cancels a queued premove
+        if button == event::MouseButton::Right {
+            if self.premove.is_some()
+                || self.referee.game().get_active_colour() != self.referee.colour
+            {
+                self.premove = None;
+                self.selected_position = None;
+            }
+            return;
+        }
+
+        if button == event::MouseButton::Left {
+            /* check click position and update board accordingly */
+            // ignore clicks outside the board, e.g. on the history panel or the status strip
+            if let Some(pos) = self.square_under(x, y) {
+                // the keyboard cursor follows the mouse, so the two can be mixed
+                if self.cursor.is_some() {
+                    self.cursor = Some(pos);
+                }
+                self.click_board(pos, &shared.config);
+            }
+        }
+    }
+
+    /// Taps work like clicks. A finger held still for a moment is a right-click, see `update`,
+    /// and dragging one over the move history scrolls it; neither clicks where it's lifted.
+    fn touch(&mut self, ctx: &mut Context, shared: &mut Shared, phase: TouchPhase, x: f32, y: f32) {
+        match phase {
+            TouchPhase::Started => {
+                self.touch_down = Some(TouchDown {
+                    start: [x, y],
+                    at: [x, y],
+                    since: Instant::now(),
+                    dragged: false,
+                    long_pressed: false,
+                });
+                touch_as_mouse(self, ctx, shared, phase, x, y);
+            }
+            TouchPhase::Moved => {
+                if let Some(touch) = &mut self.touch_down {
+                    let [start_x, start_y] = touch.start;
+                    if (x - start_x).hypot(y - start_y) > TOUCH_SLOP {
+                        touch.dragged = true;
+                    }
+                    if touch.dragged
+                        && HISTORY_RECT.contains(touch.start)
+                        && !self.history_panel.dragging()
+                    {
+                        self.history_panel.scroll_by(touch.at[1] - y);
+                    }
+                    touch.at = [x, y];
+                }
+                touch_as_mouse(self, ctx, shared, phase, x, y);
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                let done = self.touch_down.take().is_some_and(|touch| {
+                    touch.long_pressed || touch.dragged && HISTORY_RECT.contains(touch.start)
+                });
+                let phase = if done { TouchPhase::Cancelled } else { phase };
+                touch_as_mouse(self, ctx, shared, phase, x, y);
+            }
+        }
+    }
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        shared: &mut Shared,
+        key: event::KeyCode,
+        mods: event::KeyMods,
+    ) {
+        // every shortcut goes through the keymap, so a rebound key works in the overlays too
+        let action = shared.config.keys.action(key, mods);
+        self.press(shared, key, mods, action);
+    }
+
+    /// Gamepad buttons. The d-pad, A and B work the board cursor, or whatever's open over the
+    /// board, like the arrow keys, Space and Escape. The rest trigger the actions they're bound
+    /// to in the keymap's table.
+    fn gamepad_button(&mut self, _ctx: &mut Context, shared: &mut Shared, button: event::Button) {
+        let key = match button {
+            event::Button::DPadUp => event::KeyCode::Up,
+            event::Button::DPadDown => event::KeyCode::Down,
+            event::Button::DPadLeft => event::KeyCode::Left,
+            event::Button::DPadRight => event::KeyCode::Right,
+            event::Button::South => event::KeyCode::Space,
+            // backs out of what's open or puts a piece down, but never opens the pause menu
+            event::Button::East => {
+                let open = self.desync.is_some()
+                    || self.confirming.is_some()
+                    || self.settings.is_some()
+                    || self.pause.is_some()
+                    || self.help
+                    || self.handover.is_some()
+                    || self.move_input.is_some()
+                    || self.chat_input.is_some()
+                    || self.selected_position.is_some();
+                if open {
+                    self.press(
+                        shared,
+                        event::KeyCode::Escape,
+                        event::KeyMods::NONE,
+                        Some(Action::Pause),
+                    );
+                }
+                return;
+            }
+            _ => {
+                if let Some(action) = keys::pad_action(button) {
+                    self.pad_shortcut(shared, action);
+                }
+                return;
+            }
+        };
+        self.press(shared, key, event::KeyMods::NONE, None);
+    }
+
+    /// The left stick, and the d-pad on gamepads that report it as an axis, step the board
+    /// cursor once each time they're pushed.
+    fn gamepad_axis(
+        &mut self,
+        _ctx: &mut Context,
+        shared: &mut Shared,
+        axis: event::Axis,
+        value: f32,
+    ) {
+        let (i, [negative, positive]) = match axis {
+            event::Axis::LeftStickX | event::Axis::DPadX => {
+                (0, [event::KeyCode::Left, event::KeyCode::Right])
+            }
+            event::Axis::LeftStickY | event::Axis::DPadY => {
+                (1, [event::KeyCode::Down, event::KeyCode::Up])
+            }
+            _ => return,
+        };
+        let held = keys::stick_direction(self.stick[i], value);
+        let pushed = held != 0 && held != self.stick[i];
+        self.stick[i] = held;
+        if pushed {
+            let key = if held > 0 { positive } else { negative };
+            self.press(shared, key, event::KeyMods::NONE, None);
+        }
+    }
+
+    /// Hides the threat view once its key is let go.
+    fn key_up(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: event::KeyCode) {
+        if self.threats_held == Some(key) {
+            self.threats_held = None;
+        }
+    }
+
+    /// Characters typed while the move input box or the chat is open.
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) {
+        if self.pause.is_some() || self.confirming.is_some() || self.desync.is_some() || self.help {
+            return;
+        }
+        if let Some(input) = &mut self.move_input {
+            let character = character.to_ascii_lowercase();
+            // "e7e8q" is the longest move there is
+            if character.is_ascii_alphanumeric() && input.len() < 5 {
+                input.push(character);
+            }
+        } else if let Some(input) = &mut self.chat_input {
+            input.text_input_event(character);
+        }
+    }
+}
+
+impl AppState {
+    /// A key press, or a gamepad button standing in for one, with the action it triggers. It
+    /// goes to whatever's open over the board first.
+    fn press(
+        &mut self,
+        shared: &mut Shared,
+        key: event::KeyCode,
+        mods: event::KeyMods,
+        action: Option<Action>,
+    ) {
+        if let Some(desync) = &mut self.desync {
+            desync.key_down_event(key, mods, action);
+            return;
+        }
+        if let Some((confirm, _)) = &mut self.confirming {
+            confirm.key_down_event(key, action);
+            return;
+        }
+        if let Some(settings) = &mut self.settings {
+            settings.key_down_event(key, mods);
+            return;
+        }
+        if let Some(pause) = &mut self.pause {
+            pause.key_down_event(key, action);
+            return;
+        }
+        if self.help || self.handover.is_some() {
+            self.help = false;
+            self.handover = None;
+            return;
+        }
+
+        // while a move is being typed, keys edit it instead of triggering shortcuts
+        if let Some(input) = &mut self.move_input {
+            match key {
+                // the pause key closes the input box rather than pausing
+                _ if action == Some(Action::Pause) => self.move_input = None,
+                event::KeyCode::Back => {
+                    input.pop();
+                }
+                event::KeyCode::Return | event::KeyCode::NumpadEnter => self.submit_move_input(),
+                _ => (),
+            }
+            return;
+        }
+        // and while a chat message is, it takes them all
+        if let Some(input) = &mut self.chat_input {
+            match key {
+                _ if action == Some(Action::Pause) => self.chat_input = None,
+                event::KeyCode::Return | event::KeyCode::NumpadEnter => self.submit_chat_input(),
+                _ => {
+                    input.key_down_event(key, mods);
+                }
+            }
+            return;
+        }
+
+        if self.quick_chat_key(key, mods) {
+            return;
+        }
+        if self.cursor_key(key, action) {
+            return;
+        }
+
+        let action = match action {
+            Some(action) => action,
+            None => return,
+        };
+        self.act(shared, action, Some(key));
+    }
+
+    /// Where a finger's been held still long enough for a long press, once it has. It's only
+    /// pressed once, however long it's held.
+    fn long_press(&mut self, now: Instant) -> Option<[f32; 2]> {
+        let touch = self.touch_down.as_mut()?;
+        if touch.dragged || touch.long_pressed || now < touch.since + LONG_PRESS {
+            return None;
+        }
+        touch.long_pressed = true;
+        Some(touch.at)
+    }
+
+    /// A gamepad button bound to an action. Only the pause menu opens over what's already open;
+    /// the rest wait until there's nothing in the way, though one closes the help like any key.
+    fn pad_shortcut(&mut self, shared: &mut Shared, action: Action) {
+        if action == Action::Pause {
+            self.press(
+                shared,
+                event::KeyCode::Escape,
+                event::KeyMods::NONE,
+                Some(action),
+            );
+        } else if self.help || self.handover.is_some() {
+            self.help = false;
+            self.handover = None;
+        } else if self.desync.is_none()
+            && self.confirming.is_none()
+            && self.settings.is_none()
+            && self.pause.is_none()
+            && self.move_input.is_none()
+            && self.chat_input.is_none()
+        {
+            self.act(shared, action, None);
+        }
+    }
+
+    /// Does what a shortcut's for, once nothing open over the board has taken it. `key` is the
+    /// key that was pressed, and None for a gamepad button.
+    fn act(&mut self, shared: &mut Shared, action: Action, key: Option<event::KeyCode>) {
+        match action {
+            // any key closes the help again
+            Action::Help => self.help = true,
+            // put a picked up piece down, or open the pause menu, which closes itself on the
+            // next Escape
+            Action::Pause if self.selected_position.is_some() => {
+                self.selected_position = None;
+                self.positions = vec![];
+            }
+            Action::Pause => self.pause = Some(PauseMenu::new()),
+            // open the input box for typing a move, e.g. e2e4
+            Action::TypeMove => self.move_input = Some(String::new()),
+            // open the chat, with room for as much as fits in a message in this room
+            Action::Chat if self.chat_allowed() => {
+                let length = chat::room_for_text(&self.referee.room);
+                self.chat_input = Some(TextInput::new("", length, ui::printable));
+            }
+            Action::Chat => (),
+            // restarting throws the game away, so ask first once there's something to lose
+            Action::Restart if !self.moves.is_empty() => self.ask(shared, Guarded::Restart),
+            Action::Restart => self.restart(),
+            // cycle blindfold training level
+            Action::Blindfold => {
+                self.blindfold = self.blindfold.next();
+                self.peek_until = None;
+
+                shared.config.blindfold = self.blindfold;
+                shared.config.save();
+            }
+            // open the analysis board once the game is over, or go back to the game
+            Action::Analysis => {
+                if self.analysis.is_some() {
+                    self.analysis = None;
+                } else if self.game_over() {
+                    // from the move being reviewed, if any, or else the end of the game
+                    let played = self.reviewing.map_or(self.moves.len(), |entry| entry + 1);
+                    self.analysis = Some(Analysis {
+                        game: replay(&self.moves[..played]),
+                        moves: vec![],
+                    });
+                    self.reviewing = None;
+                } else {
+                    return;
+                }
+                self.selected_position = None;
+                self.positions = vec![];
+            }
+            // make the computer opponent stronger or weaker, from its next move on
+            Action::Stronger | Action::Weaker => {
+                if let Some(difficulty) = self.difficulty {
+                    let difficulty = if action == Action::Weaker {
+                        difficulty.saturating_sub(1)
+                    } else {
+                        difficulty.saturating_add(1)
+                    }
+                    .clamp(MIN_DIFFICULTY, MAX_DIFFICULTY);
+
+                    self.difficulty = Some(difficulty);
+                    shared.config.ai_difficulty = Some(difficulty);
+                    shared.config.save();
+                    let msg = Message::Difficulty {
+                        room: self.referee.room.clone(),
+                        level: difficulty,
+                    };
+                    self.send(msg);
+                }
+            }
+            Action::NetworkInfo => self.network_info = !self.network_info,
+            // a developer's view, so only stepped through with the statistics open
+            Action::PieceSquares if self.network_info => {
+                self.piece_squares = next_piece_squares(self.piece_squares);
+            }
+            Action::PieceSquares => (),
+            Action::FlipBoard => self.flip_board(shared),
+            Action::MoveList => self.toggle_move_list(shared),
+            Action::Heatmap => self.heatmap = !self.heatmap,
+            // the same as the wheel over the board
+            Action::PreviousMove => self.step_review(true),
+            Action::NextMove => self.step_review(false),
+            Action::ExportSvg => self.export_svg(&shared.config),
+            Action::Snapshot => self.save_snapshot(&shared.config),
+            // the tabs take these before the game sees them
+            Action::NextGame | Action::AnotherGame => (),
+            // peek at the pieces while blindfolded
+            Action::Peek if self.blindfold != Blindfold::Off => {
+                self.peek_until = Some(Instant::now() + PEEK_DURATION);
+            }
+            Action::Peek => (),
+            // shown for as long as the key is held
+            Action::Threats if self.assists_allowed() => self.threats_held = key,
+            Action::Threats => self.allow_assists(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotate::{Evaluation, Thresholds};
+    use crate::chaos::{self, Chaos};
+    use crate::cli::Args;
+    use crate::net::CHANNEL_CAPACITY;
+    use crate::spectators;
+    use crate::transcript::Entry;
+    use clap::Parser;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// A hotseat game, with the channel its messages arrive on. The receiver of what it sends is
+    /// returned too, so sending doesn't fail.
+    fn game_with_inbox() -> (AppState, mpsc::SyncSender<String>, mpsc::Receiver<String>) {
+        let (sender, sent) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (inbox, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let session = Session {
+            sender,
+            receiver,
+            room_name: "room".to_string(),
+            color: Colour::White,
+            difficulty: None,
+            hotseat: true,
+            server: None,
+            opponent: None,
+            opponent_avatar: None,
+            correspondence: None,
+            replay: false,
+            recorded: None,
+        };
+        let state = AppState::new(session, SystemTime::now(), &Config::default());
+        (state, inbox, sent)
+    }
+
+    /// Ends the game by resignation or agreement, as the messages between the players would.
+    fn end_game(state: &mut AppState, ending: Ending) {
+        let referee = &mut state.referee;
+        let room = referee.room.clone();
+        let msg = match ending {
+            Ending::Resigned(colour) if colour == referee.colour => referee.resign(),
+            Ending::Resigned(colour) => Some(Message::Resign { room, colour }),
+            Ending::DrawAgreed => {
+                referee.offer_draw();
+                let colour = referee.their_colour();
+                Some(Message::DrawOffer { room, colour })
+            }
+        };
+        referee.handle(msg.unwrap(), Opponent::Online);
+        assert_eq!(referee.ending(), Some(ending));
+    }
+
+    fn move_message(counter: u32, text: &str) -> String {
+        let (from, to, _) = parse_move(text).unwrap();
+        let msg = Message::Move {
+            room: "room".to_string(),
+            counter,
+            from,
+            to,
+        };
+        msg.to_string()
+    }
+
+    const OPENING: [&str; 10] = [
+        "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "d2d3", "f8c5", "b1c3", "d7d6",
+    ];
+
+    #[test]
+    fn queued_messages_are_all_applied_in_one_tick() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        for (i, text) in OPENING.iter().enumerate() {
+            inbox.send(move_message(i as u32 + 2, text)).unwrap();
+        }
+
+        state.receive();
+
+        let mut expected = Game::new();
+        for text in OPENING {
+            let (from, to, _) = parse_move(text).unwrap();
+            expected.make_move_pos(from, to).unwrap();
+        }
+        assert_eq!(state.referee.counter(), 11);
+        assert_eq!(state.moves.len(), OPENING.len());
+        assert_eq!(position_key(state.referee.game()), position_key(&expected));
+    }
+
+    /// The quickest stalemate there is, Black being stalemated after White's tenth move.
+    const STALEMATE: [&str; 19] = [
+        "e2e3", "a7a5", "d1h5", "a8a6", "h5a5", "h7h5", "h2h4", "a6h6", "a5c7", "f7f6", "c7d7",
+        "e8f7", "d7b7", "d8d3", "b7b8", "d3h7", "b8c8", "f7g6", "c8e6",
+    ];
+
+    #[test]
+    fn nothing_is_played_or_sent_once_the_game_is_over() {
+        let fools_mate = ["f2f3", "e7e5", "g2g4", "d8h4"];
+        let endings: [(&[&str], Option<Ending>); 4] = [
+            (&fools_mate, None),
+            (&STALEMATE, None),
+            (&OPENING[..2], Some(Ending::Resigned(Colour::White))),
+            (&OPENING[..2], Some(Ending::DrawAgreed)),
+        ];
+        for (moves, ending) in endings {
+            let (mut state, inbox, sent) = game_with_inbox();
+            for text in moves {
+                let (from, to, _) = parse_move(text).unwrap();
+                state.play_move(from, to).unwrap();
+            }
+            if let Some(ending) = ending {
+                end_game(&mut state, ending);
+            }
+            assert!(state.game_over());
+            while sent.try_recv().is_ok() {}
+
+            // clicks, a move played directly and one from the opponent
+            let (from, to, _) = parse_move("b1c3").unwrap();
+            state.click_square(from);
+            state.click_square(to);
+            assert!(state.play_move(from, to).is_err());
+            inbox
+                .send(move_message(state.referee.counter() + 1, "g8f6"))
+                .unwrap();
+            state.receive();
+
+            assert_eq!(state.moves.len(), moves.len());
+            assert!(sent.try_recv().is_err());
+        }
+    }
+
+    #[test]
+    fn a_reset_behind_moves_is_applied_in_the_same_tick() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        for (i, text) in OPENING[..3].iter().enumerate() {
+            inbox.send(move_message(i as u32 + 2, text)).unwrap();
+        }
+        let reset = Message::Reset {
+            room: "room".to_string(),
+        };
+        inbox.send(reset.to_string()).unwrap();
+
+        state.receive();
+
+        assert_eq!(state.referee.counter(), 1);
+        assert!(state.moves.is_empty());
+        assert_eq!(
+            position_key(state.referee.game()),
+            position_key(&Game::new())
+        );
+    }
+
+    #[test]
+    fn the_network_info_lists_the_last_messages_both_ways() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        let quiet = state.network_info_text(60.0, Instant::now());
+        assert!(quiet.contains("Move counter: 1\nFrames per second: 60\nLast received: never\n"));
+
+        inbox.send(move_message(2, "e2e4")).unwrap();
+        state.receive();
+        let (from, to, _) = parse_move("e7e5").unwrap();
+        state.play_move(from, to).unwrap();
+
+        let text = state.network_info_text(60.0, Instant::now());
+        assert!(text.contains("Move counter: 3\n"));
+        assert!(text.contains("Received: 1 (0 ignored, 0 our own, 1 last frame)\n"));
+        let lines: Vec<&str> = text.lines().rev().take(2).collect();
+        assert!(lines[1].ends_with(&format!("<- {}", move_message(2, "e2e4").trim_end())));
+        assert!(lines[0].contains(&format!("-> {} ~", move_message(3, "e7e5").trim_end())));
+    }
+
+    #[test]
+    fn the_engines_square_bonuses_are_stepped_through_from_the_network_info() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        state.act(&mut shared, Action::PieceSquares, None);
+        assert_eq!(state.piece_squares, None);
+
+        state.act(&mut shared, Action::NetworkInfo, None);
+        let mut shown = vec![];
+        for _ in 0..5 {
+            state.act(&mut shared, Action::PieceSquares, None);
+            shown.push(state.piece_squares);
+        }
+        assert_eq!(
+            shown,
+            [
+                Some(PieceType::Pawn),
+                Some(PieceType::Knight),
+                Some(PieceType::Bishop),
+                Some(PieceType::Queen),
+                None
+            ]
+        );
+        state.act(&mut shared, Action::PieceSquares, None);
+        let text = state.network_info_text(60.0, Instant::now());
+        assert!(text.contains("Square bonuses: pawn\n"));
+
+        // the knight's favourite squares are the hottest, the corners the coolest, and Black's
+        // are White's turned upside down
+        let heat = piece_square_heat(PieceType::Knight, Colour::White);
+        let idx = |text| {
+            let square = parse_square(text).unwrap();
+            square.row * 8 + square.col
+        };
+        let hottest = *heat.iter().max().unwrap();
+        assert_eq!(heat[idx("d4")], hottest);
+        assert_eq!(heat[idx("a1")], 1);
+        assert!(heat.iter().all(|&count| count > 0));
+        let black = piece_square_heat(PieceType::Knight, Colour::Black);
+        assert_eq!(black[idx("b8")], heat[idx("b1")]);
+    }
+
+    #[test]
+    fn the_dots_show_while_the_opponent_has_the_move() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        let now = Instant::now();
+        assert_eq!(state.opponent_wait(now), None);
+
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        state.update_waiting(now);
+        assert_eq!(state.waiting_since, Some(now));
+        assert_eq!(state.opponent_wait(now), Some(Waiting::Thinking));
+        assert_eq!(
+            state.opponent_wait(now + OPPONENT_QUIET),
+            Some(Waiting::Quiet)
+        );
+
+        // their move stops the dots the moment it's applied
+        inbox.send(move_message(3, "e7e5")).unwrap();
+        state.receive();
+        assert_eq!(state.opponent_wait(Instant::now()), None);
+        state.update_waiting(Instant::now());
+        assert_eq!(state.waiting_since, None);
+
+        // the first dot is brightest as the pulse starts, and none goes out
+        let dots = thinking_dots(Duration::ZERO);
+        assert!(dots[0] > dots[1] && dots[0] > dots[2]);
+        let third = thinking_dots(THINKING_DOTS_PERIOD * 2 / 3);
+        assert!(third[2] > third[0] && third[2] > third[1]);
+        assert!(dots.iter().chain(&third).all(|&alpha| alpha >= 0.25));
+    }
+
+    #[test]
+    fn chat_goes_through_the_room_a_few_messages_at_a_time() {
+        let (mut state, inbox, sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        // no one to chat with across the desk
+        state.act(&mut shared, Action::Chat, None);
+        assert!(state.chat_input.is_none());
+
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        state.referee.room = "lobby".to_string();
+        while sent.try_recv().is_ok() {}
+        state.act(&mut shared, Action::Chat, None);
+        state.chat_input.as_mut().unwrap().set_text("  hej  då ");
+        state.press(
+            &mut shared,
+            event::KeyCode::Return,
+            event::KeyMods::NONE,
+            None,
+        );
+        assert!(state.chat_input.is_none());
+        let chat = sent.try_recv().unwrap();
+        assert_eq!(protocol::untag(&chat).1, "lobby chat white hej då");
+
+        // our own message coming back from the relay isn't logged twice
+        inbox.send(chat).unwrap();
+        inbox.send("lobby chat black hej hej ".to_string()).unwrap();
+        inbox.send("lobby draw black ".to_string()).unwrap();
+        state.receive();
+        let log: Vec<(Speaker, &str)> = state
+            .chat
+            .lines()
+            .map(|line| (line.speaker, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            log,
+            [
+                (Speaker::Us, "hej då"),
+                (Speaker::Them, "hej hej"),
+                (Speaker::Notice, "Black offered a draw"),
+            ]
+        );
+        assert!(state
+            .toasts
+            .shown()
+            .iter()
+            .any(|toast| toast.text == "Black: hej hej"));
+
+        for text in ["ett", "två", "tre"] {
+            state.chat.queue(text);
+        }
+        state.send_chat(Instant::now());
+        let chats = sent
+            .try_iter()
+            .filter(|msg| msg.starts_with("lobby chat"))
+            .count();
+        assert_eq!(chats, 2);
+        assert_eq!(state.chat.queued(), 1);
+    }
+
+    #[test]
+    fn our_own_messages_are_dropped_whatever_they_say() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        state.referee.room = "lobby".to_string();
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        let room = || "lobby".to_string();
+        let (from, to, _) = parse_move("e7e5").unwrap();
+
+        // every kind there is, as the opponent would send it, so only the tag gives them away
+        let every = [
+            Message::Join {
+                room: room(),
+                id: 7,
+                avatar: Some(1),
+            },
+            Message::Move {
+                room: room(),
+                counter: 3,
+                from,
+                to,
+            },
+            Message::Reset { room: room() },
+            Message::Thinking {
+                room: room(),
+                depth: 2,
+            },
+            Message::Difficulty {
+                room: room(),
+                level: 5,
+            },
+            Message::DrawOffer {
+                room: room(),
+                colour: Colour::Black,
+            },
+            Message::Resign {
+                room: room(),
+                colour: Colour::Black,
+            },
+            Message::AllowAssists {
+                room: room(),
+                colour: Colour::Black,
+            },
+            Message::RematchOk {
+                room: room(),
+                colour: Colour::Black,
+            },
+            Message::RematchCancel {
+                room: room(),
+                colour: Colour::Black,
+            },
+            Message::Result {
+                room: room(),
+                score: Score::BlackWins,
+            },
+            Message::Reject {
+                room: room(),
+                counter: 2,
+                reason: "illegal move".to_string(),
+            },
+            Message::SyncOffer {
+                room: room(),
+                counter: 9,
+                hash: 42,
+            },
+            Message::SyncRequest {
+                room: room(),
+                counter: 1,
+            },
+            Message::Takeback {
+                room: room(),
+                counter: 1,
+            },
+            Message::Spectate {
+                room: room(),
+                token: "s3cr3t".to_string(),
+                name: "Bob".to_string(),
+            },
+            Message::Chat {
+                room: room(),
+                colour: Colour::Black,
+                text: "hej".to_string(),
+            },
+        ];
+        for msg in &every {
+            let echo = protocol::tag(&msg.to_string(), state.instance);
+            assert_eq!(protocol::untag(&echo).0, Some(state.instance));
+            inbox.send(echo).unwrap();
+        }
+        state.receive();
+        assert_eq!(state.net.echoes, every.len() as u64);
+        assert_eq!(state.net.ignored, 0);
+        assert_eq!(state.move_names(), ["e2e4"]);
+        assert!(state.referee.draw_offered_by().is_none() && state.referee.ending().is_none());
+        assert!(state.thinking.is_none() && state.desync.is_none());
+        assert!(state.assists_allowed_by.is_empty());
+        assert_eq!(state.spectators.count(), 0);
+        assert_eq!(state.chat.lines().len(), 0);
+        assert!(state.toasts.shown().is_empty());
+
+        // the same from another client is acted on
+        let other = state.instance.wrapping_add(1);
+        inbox
+            .send(protocol::tag(&every[1].to_string(), other))
+            .unwrap();
+        inbox
+            .send(protocol::tag(&every[5].to_string(), other))
+            .unwrap();
+        state.receive();
+        assert_eq!(state.move_names(), ["e2e4", "e7e5"]);
+        assert_eq!(state.referee.draw_offered_by(), Some(Colour::Black));
+        assert_eq!(state.net.echoes, every.len() as u64);
+    }
+
+    #[test]
+    fn a_peer_spamming_resets_and_moves_cant_thrash_the_game() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        state.referee.room = "lobby".to_string();
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+
+        // as recorded from a client stuck resending everything, with a few tags of its own
+        let transcript = r#"{"ms":0,"dir":"session","room":"lobby","colour":"black","hotseat":false}
+{"ms":812,"dir":"in","msg":"lobby mv 2 6 4 4 4 ~0badf00d "}
+{"ms":1530,"dir":"in","msg":"lobby reset ~deadbeef "}
+{"ms":1531,"dir":"in","msg":"lobby reset ~deadbeef "}
+{"ms":1531,"dir":"in","msg":"lobby reset ~0badf00d "}
+{"ms":1532,"dir":"in","msg":"lobby reset"}
+{"ms":1540,"dir":"in","msg":"lobby mv 3 1 4 3 4 ~deadbeef "}
+{"ms":1541,"dir":"in","msg":"lobby mv 3 1 4 3 4 ~deadbeef "}
+{"ms":1550,"dir":"in","msg":"lobby rematch_auto_ok black ~deadbeef "}
+{"ms":1551,"dir":"in","msg":"lobby rematch_auto_cancel black ~deadbeef "}"#;
+        for line in transcript.lines() {
+            if let Entry::Message { text, .. } = Entry::parse(line).unwrap() {
+                inbox.send(text).unwrap();
+            }
+        }
+        state.receive();
+        // the first move, passed on again, is one we've played already
+        assert_eq!(state.move_names(), ["e2e4", "e7e5"]);
+        assert_eq!(state.referee.restart_asked(), Some(Colour::Black));
+        assert_eq!(state.net.repeated, 4);
+        assert_eq!(state.net.unasked, 2);
+        assert!(!state.game_over() && !state.rematch.cancelled);
+
+        // then a flood up to the limit, counting the messages above, after which even a good
+        // move is ignored for a while
+        for depth in state.net.received as u32..inbound::RATE_LIMIT as u32 {
+            inbox.send(format!("lobby thinking {}", depth)).unwrap();
+            if depth % 32 == 0 {
+                state.receive();
+            }
+        }
+        state.receive();
+        assert_eq!(state.net.flooded, 0);
+        inbox.send("lobby thinking 1000".to_string()).unwrap();
+        inbox.send(move_message(5, "g1f3")).unwrap();
+        state.receive();
+        assert_eq!(state.net.flooded, 2);
+        assert!(state.inbound.flooded(Instant::now()));
+        assert_eq!(state.move_names(), ["e2e4", "e7e5"]);
+        assert!(state
+            .toasts
+            .shown()
+            .iter()
+            .any(|toast| toast.severity == Severity::Warn));
+
+        // the same over a hotseat game's channel is all acted on
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.referee.room = "lobby".to_string();
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        inbox.send("lobby reset".to_string()).unwrap();
+        state.receive();
+        assert!(state.moves.is_empty() && state.referee.restart_asked().is_none());
+    }
+
+    #[test]
+    fn a_long_game_is_resent_without_passing_the_flood_limit() {
+        // knights out and back, for more moves than the limit takes at once
+        let moves: Vec<String> = ["g1f3", "g8f6", "f3g1", "f6g8"]
+            .iter()
+            .cycle()
+            .take(240)
+            .map(|text| text.to_string())
+            .collect();
+        assert!(moves.len() > inbound::RATE_LIMIT);
+        let (mut ahead, _inbox, sent) = game_with_inbox();
+        ahead.resume(&SavedGame {
+            room: "room".to_string(),
+            fen: String::new(),
+            moves,
+        });
+        ahead.hotseat = false;
+        let (mut behind, inbox, _sent) = game_with_inbox();
+        behind.hotseat = false;
+        behind.referee.colour = Colour::Black;
+
+        // what the side behind would take, as it arrives a tenth of a second at a time
+        ahead.correspondence.resend(&ahead.referee, 1);
+        let start = Instant::now();
+        let mut filter = InboundFilter::default();
+        let mut batches = 0;
+        for tick in 0..60 {
+            let now = start + Duration::from_millis(100 * tick);
+            ahead.send_missed_moves(now);
+            let mut batch = 0;
+            while let Ok(msg) = sent.try_recv() {
+                assert_eq!(filter.check(&msg, now), Verdict::Take);
+                inbox.send(msg).unwrap();
+                batch += 1;
+            }
+            assert!(batch <= correspondence::RESEND_BATCH);
+            batches += usize::from(batch > 0);
+            behind.receive();
+        }
+        assert_eq!(batches, 5);
+        assert_eq!(behind.referee.counter(), ahead.referee.counter());
+        assert!(behind.desync.is_none());
+    }
+
+    #[test]
+    fn a_snapshot_plays_back_to_the_same_position() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        state.server = Some("alice:hunter2@example.com:6000".to_string());
+        for (counter, text) in [(1, "e2e4"), (3, "g1f3")] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.play_move(from, to).unwrap();
+            let reply = if counter == 1 { "e7e5" } else { "b8c6" };
+            inbox.send(move_message(counter + 2, reply)).unwrap();
+            state.receive();
+        }
+        inbox.send("room spectate s3cr3t Bob ".to_string()).unwrap();
+        state.receive();
+
+        let taken = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let snapshot = state.state_snapshot(&Config::default(), taken);
+        assert_eq!(snapshot.taken, 1_700_000_000);
+        assert_eq!(snapshot.game.moves, ["e2e4", "e7e5", "g1f3", "b8c6"]);
+        assert_eq!(snapshot.game.position_keys, state.history);
+        assert_eq!(snapshot.connection.received, state.net.received);
+        let text = serde_json::to_string(&snapshot).unwrap();
+        assert!(!text.contains("hunter2") && !text.contains("s3cr3t"));
+        assert_eq!(
+            snapshot.messages.last().unwrap().text,
+            "room spectate redacted Bob"
+        );
+
+        let read: StateSnapshot = serde_json::from_str(&text).unwrap();
+        assert_eq!(read, snapshot);
+        let mut played_back = AppState::new(
+            crate::session::offline_session(),
+            SystemTime::now(),
+            &Config::default(),
+        );
+        played_back.resume(&read.saved_game());
+        assert_eq!(played_back.history, state.history);
+        assert_eq!(played_back.move_names(), state.move_names());
+    }
+
+    #[test]
+    fn presets_are_sent_with_alt_and_shown_as_reactions() {
+        let (mut state, inbox, sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        let alt = event::KeyMods::ALT;
+        state.press(&mut shared, event::KeyCode::Key2, alt, None);
+        assert_eq!(state.chat.lines().len(), 0);
+
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        state.referee.room = "lobby".to_string();
+        state.chat = Chat::new(chat::default_presets()[..3].to_vec());
+        while sent.try_recv().is_ok() {}
+        state.press(&mut shared, event::KeyCode::Key2, alt, None);
+        state.press(&mut shared, event::KeyCode::Key4, alt, None);
+        state.press(
+            &mut shared,
+            event::KeyCode::Key2,
+            event::KeyMods::NONE,
+            None,
+        );
+        let sent: Vec<String> = sent.try_iter().collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(protocol::untag(&sent[0]).1, "lobby chat white Nice move");
+
+        // theirs go beside their name, not with the other toasts
+        inbox
+            .send("lobby chat black Good game ".to_string())
+            .unwrap();
+        inbox.send("lobby chat black gg wp ".to_string()).unwrap();
+        state.receive();
+        let reactions = state.reactions.shown();
+        assert_eq!(reactions.len(), 1);
+        assert_eq!(reactions[0].text, "Good game");
+        assert_eq!(reactions[0].severity, Severity::Reaction);
+        assert_eq!(state.toasts.shown()[0].text, "Black: gg wp");
+        assert_eq!(state.chat.lines().len(), 3);
+    }
+
+    #[test]
+    fn players_are_listed_with_their_avatars_as_they_sit() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut config = Config::default();
+        config.name = Some("Alice".to_string());
+        config.avatar = Some(1);
+        let shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), config);
+        assert!(state.player_rows(&shared).is_empty());
+
+        // online as Black, against someone with an avatar this version doesn't have
+        state.hotseat = false;
+        state.referee.colour = Colour::Black;
+        state.opponent = Some("study".to_string());
+        state.opponent_avatar = Some(99);
+        let rows = state.player_rows(&shared);
+        let names: Vec<&str> = rows.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Alice", "study"]);
+        assert_eq!(rows[0].1.name, "Knight");
+        assert_eq!(*rows[1].1, avatar::AVATARS[avatar::DEFAULT]);
+
+        state.flipped = true;
+        assert_eq!(state.player_rows(&shared)[0].0, "study");
+    }
+
+    #[test]
+    fn kiosk_mode_shows_the_board_alone_until_something_opens() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        assert_eq!(state.layout(&shared, SCREEN_SIZE), Layout::Standard);
+        assert_eq!(state.layout(&shared, layout::MINI_WINDOW), Layout::Mini);
+
+        let args = Args::try_parse_from(["schack", "--kiosk"]).unwrap();
+        let kiosk = Shared::new(args, Config::default());
+        assert_eq!(state.layout(&kiosk, SCREEN_SIZE), Layout::Kiosk);
+        assert_eq!(palette(&kiosk), render::Palette::HighContrast);
+        state.help = true;
+        assert_eq!(state.layout(&kiosk, SCREEN_SIZE), Layout::Standard);
+
+        // players sharing the computer aren't named
+        assert!(state.kiosk_bar_text(&kiosk).ends_with("   White to move"));
+        assert!(!state.kiosk_bar_text(&kiosk).contains("Black:"));
+        state.hotseat = false;
+        state.opponent = Some("study".to_string());
+        assert!(state
+            .kiosk_bar_text(&kiosk)
+            .starts_with("White: You   Black: study   "));
+
+        assert_eq!(kiosk_bar_opacity(Duration::ZERO), 1.0);
+        assert_eq!(kiosk_bar_opacity(KIOSK_BAR_SHOWN + KIOSK_BAR_FADE / 2), 0.5);
+        assert_eq!(kiosk_bar_opacity(KIOSK_BAR_SHOWN * 2), 0.0);
+    }
+
+    #[test]
+    fn a_desync_freezes_the_game_until_it_is_abandoned() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        inbox.send(move_message(2, "e2e4")).unwrap();
+        inbox.send(move_message(5, "e7e5")).unwrap();
+        state.receive();
+
+        let report = state.desync.as_ref().unwrap().report().clone();
+        assert!(state.failure.is_none());
+        assert_eq!((report.local, report.remote), (2, 5));
+        assert_eq!(
+            report.received,
+            [move_message(2, "e2e4"), move_message(5, "e7e5")]
+                .map(|msg| msg.trim_end().to_string())
+        );
+        assert!(report.fen.starts_with("rnbqkbnr/pppppppp/8/8/4P3/"));
+
+        // nothing more is applied, and Escape leaves the panel up
+        inbox.send(move_message(3, "e7e5")).unwrap();
+        state.receive();
+        assert_eq!(state.moves.len(), 1);
+        let panel = state.desync.as_mut().unwrap();
+        panel.key_down_event(
+            event::KeyCode::Escape,
+            event::KeyMods::NONE,
+            Some(Action::Pause),
+        );
+        state.abandon_if_asked();
+        assert!(state.desync.is_some());
+
+        let panel = state.desync.as_mut().unwrap();
+        panel.key_down_event(event::KeyCode::Right, event::KeyMods::NONE, None);
+        panel.key_down_event(event::KeyCode::Return, event::KeyMods::NONE, None);
+        state.abandon_if_asked();
+        assert!(state.desync.is_none());
+        assert!(state.failure.is_some());
+    }
+
+    #[test]
+    fn a_refused_move_is_taken_back_along_with_the_premove_after_it() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        state.referee.room = "study".to_string();
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        let (premove_from, premove_to, _) = parse_move("d2d4").unwrap();
+        state.click_square(premove_from);
+        state.click_square(premove_to);
+        assert!(state.premove.is_some());
+        // as if the network thread hadn't taken a later move yet
+        state.outbox.push_back(move_message(4, "d2d4"));
+
+        let reject = Message::Reject {
+            room: "study".to_string(),
+            counter: 2,
+            reason: "not your turn".to_string(),
+        };
+        inbox.send(reject.to_string()).unwrap();
+        state.receive();
+
+        assert!(state.desync.is_none());
+        assert_eq!(state.referee.counter(), 1);
+        assert!(state.moves.is_empty() && state.snapshots.is_empty());
+        assert_eq!(
+            position_key(state.referee.game()),
+            position_key(&Game::new())
+        );
+        assert!(state.premove.is_none() && state.outbox.is_empty());
+        assert!(state.selected_position == Some(from));
+        assert!(state.positions.contains(&to));
+        let shown = mem::take(&mut state.toasts).shown()[0].text.clone();
+        assert_eq!(shown, "The server refused e2e4: not your turn");
+
+        // another move can go in its place, with the same counter
+        let (from, to, _) = parse_move("d2d4").unwrap();
+        state.play_move(from, to).unwrap();
+        assert_eq!(state.referee.counter(), 2);
+    }
+
+    #[test]
+    fn the_layout_chosen_is_kept_for_the_next_game() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        let first_move = [HISTORY_RECT.x + 60.0, HISTORY_RECT.y + 5.0];
+
+        state.act(&mut shared, Action::MoveList, None);
+        assert!(!state.move_list);
+        assert!(!shared.config.move_list);
+        // the moves can't be clicked while they aren't shown
+        assert!(!state.review_clicked_move(first_move[0], first_move[1]));
+        state.choose_pause(&mut shared, PauseItem::MoveList);
+        assert!(state.review_clicked_move(first_move[0], first_move[1]));
+        state.act(&mut shared, Action::MoveList, None);
+
+        state.choose_pause(&mut shared, PauseItem::FlipBoard);
+        assert!(state.flipped);
+        assert!(shared.config.board_flipped);
+
+        let next = AppState::new(
+            crate::session::offline_session(),
+            SystemTime::now(),
+            &shared.config,
+        );
+        assert!(next.flipped);
+        assert!(!next.move_list);
+    }
+
+    #[test]
+    fn the_heatmap_follows_the_moves_but_waits_for_the_game_to_end() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        state.referee.room = "study".to_string();
+        for text in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.play_move(from, to).unwrap();
+        }
+        let f3 = parse_move("f3f3").unwrap().0;
+        assert_eq!(state.heat[f3.row * 8 + f3.col], 2);
+        assert_eq!(state.heat.iter().sum::<u16>(), 5);
+
+        // no help while playing, but a replay can show it all along
+        assert!(!state.heatmap_allowed());
+        state.replay = true;
+        assert!(state.heatmap_allowed());
+
+        // taking moves back takes their squares off, and a new game starts cold
+        state.rewind(3);
+        assert_eq!(state.heat.iter().sum::<u16>(), 2);
+        assert_eq!(state.heat[f3.row * 8 + f3.col], 1);
+        state.reset();
+        assert_eq!(state.heat, [0; 64]);
+    }
+
+    #[test]
+    fn suggested_moves_fill_in_and_follow_the_analysis_board() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        state.referee.room = "study".to_string();
+        for text in ["e2e4", "d7d5", "d1g4"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.play_move(from, to).unwrap();
+        }
+        state.update_suggestions();
+        assert!(state.suggestions.key().is_none());
+
+        end_game(&mut state, Ending::Resigned(Colour::Black));
+        state.analysis = Some(Analysis {
+            game: replay(&state.moves),
+            moves: vec![],
+        });
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while state.suggestion(SUGGESTIONS - 1).is_none() {
+            assert!(Instant::now() < deadline, "the suggestions never came");
+            state.update_suggestions();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (c8, g4) = (parse_move("c8g4").unwrap().0, parse_move("g4g4").unwrap().0);
+        assert!(state.suggestion(0) == Some((c8, g4)));
+
+        // playing one moves on, and the next frame searches the new position
+        let searched = state.suggestions.key();
+        state.play_suggestion(0);
+        assert!(state.analysis.as_ref().unwrap().moves == [(c8, g4)]);
+        assert!(state.suggestion(0).is_none());
+        state.update_suggestions();
+        assert_ne!(state.suggestions.key(), searched);
+
+        // going back to the game drops the search
+        state.analysis = None;
+        state.update_suggestions();
+        assert!(state.suggestions.key().is_none());
+    }
+
+    #[test]
+    fn a_piece_is_named_with_its_moves_and_how_safe_it_is() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        state.referee.room = "study".to_string();
+        for text in ["e2e4", "d7d5"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.play_move(from, to).unwrap();
+        }
+
+        // the pawn on d5 attacks e4, which nothing defends
+        let e4 = parse_square("e4").unwrap();
+        state.hovered = Some(e4);
+        state.hovered_since = Instant::now();
+        assert!(state.tooltip(Instant::now()).is_none());
+        let later = Instant::now() + TOOLTIP_DELAY;
+        assert_eq!(
+            state.tooltip(later).unwrap(),
+            "White pawn, attacked and undefended"
+        );
+
+        // worked out once for the position, and not again while it's hovered
+        let key = position_key(state.referee.game());
+        assert!(state.cover.is_some_and(|(cached, _)| cached == key));
+        state.cover = Some((key, [[false; 64]; 2]));
+        assert_eq!(
+            state.tooltip(later).unwrap(),
+            "White pawn, not attacked or defended"
+        );
+
+        // online, without study aids, the piece is only named
+        state.server = Some("example.com:6000".to_string());
+        assert_eq!(state.tooltip(later).unwrap(), "White pawn");
+        state.server = None;
+
+        assert!(state.selection_text().is_none());
+        state.click_square(parse_square("g1").unwrap());
+        assert_eq!(state.selection_text().unwrap(), "Knight — 3 legal moves");
+        state.click_square(parse_square("d2").unwrap());
+        assert_eq!(state.selection_text().unwrap(), "Pawn — 2 legal moves");
+        state.click_square(parse_square("e1").unwrap());
+        assert_eq!(state.selection_text().unwrap(), "King — 1 legal move");
+    }
+
+    #[test]
+    fn replayed_moves_are_marked_from_the_scores_kept_for_them() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let moves = ["e2e4", "d7d5", "d1g4"].map(|text| parse_move(text).unwrap());
+        let scores = ["30/25/30", "-10/-20/-140", "20/0/-600"];
+        let recorded = Match {
+            played_at: 0,
+            opponent: "Computer, level 3".to_string(),
+            colour: Colour::White,
+            outcome: Outcome::Lost,
+            moves: moves.iter().map(|&(from, to, _)| (from, to)).collect(),
+            time_control: matches::NO_TIME_CONTROL.to_string(),
+            pgn: String::new(),
+            evaluations: scores.iter().map(|text| Evaluation::parse(text)).collect(),
+        };
+        state.annotations = Annotations::new(Some(recorded), Thresholds::default());
+        for (from, to, _) in moves {
+            state.play_move(from, to).unwrap();
+        }
+        let names: Vec<String> = state.history_lines()[..2]
+            .iter()
+            .flat_map(|line| line[1..].iter().map(|(_, name)| name.clone()))
+            .collect();
+        assert_eq!(names, ["e2e4", "d7d5?", "d1g4??"]);
+
+        // stricter thresholds mark them anew, without analysing the game again
+        state.annotations.thresholds.dubious = 0;
+        assert_eq!(state.annotation(0), Some(Annotation::Dubious));
+        assert!(!state.annotations.analysing());
+    }
+
+    #[test]
+    fn the_board_is_handed_over_on_one_board_but_never_online() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        state.hotseat_flip = true;
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        assert!(state.flipped && state.turned_at.is_some() && state.handover.is_none());
+
+        // halfway round, it's edge-on and about to show Black's side
+        let (width, before) = turning(TURN_DURATION / 2 - Duration::from_millis(1)).unwrap();
+        assert!(before && width < 0.05);
+        assert!(!turning(TURN_DURATION / 2).unwrap().1);
+        assert!(turning(TURN_DURATION).is_none());
+
+        // hidden, it turns without spinning until a key shows it again
+        state.hotseat_cover = true;
+        state.turned_at = None;
+        let (from, to, _) = parse_move("e7e5").unwrap();
+        state.play_move(from, to).unwrap();
+        assert!(!state.flipped && state.turned_at.is_none());
+        assert_eq!(state.handover, Some(Colour::White));
+        state.press(&mut shared, event::KeyCode::F, event::KeyMods::NONE, None);
+        assert!(state.handover.is_none() && !state.flipped);
+
+        // online, each player has a board of their own
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        state.referee.colour = Colour::White;
+        let (from, to, _) = parse_move("g1f3").unwrap();
+        state.play_move(from, to).unwrap();
+        assert!(!state.flipped && state.handover.is_none());
+    }
+
+    #[test]
+    fn the_tutor_offers_back_a_hanging_queen_but_never_online() {
+        let (mut state, inbox, sent) = game_with_inbox();
+        state.referee.room = "study".to_string();
+        state.tutor.on = true;
+        for text in ["e2e4", "d7d5", "d1g4"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.play_move(from, to).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !state.tutor.offering() {
+            assert!(Instant::now() < deadline, "the queen was never flagged");
+            state.update_tutor(Instant::now());
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            state.toasts.shown()[0].text,
+            "That might lose material — take it back?"
+        );
+
+        // the bishop took the queen before the takeback reached the other side
+        state.take_back();
+        assert_eq!(state.referee.counter(), 3);
+        assert_eq!(state.move_names(), ["e2e4", "d7d5"]);
+        assert!(!state.tutor.offering());
+        let sent: Vec<String> = sent.try_iter().collect();
+        assert_eq!(protocol::untag(sent.last().unwrap()).1, "study takeback 3");
+        inbox
+            .send(move_message(5, "c8g4").replace("room", "study"))
+            .unwrap();
+        inbox.send("study takeback 3 ".to_string()).unwrap();
+        state.receive();
+        assert!(state.desync.is_none() && state.referee.taking_back().is_none());
+        assert_eq!(state.referee.counter(), 3);
+
+        // an online game isn't checked at all
+        state.server = Some("example.com:6000".to_string());
+        state.hotseat = false;
+        let (from, to, _) = parse_move("d1g4").unwrap();
+        state.play_move(from, to).unwrap();
+        assert!(!state.tutor.checking());
+    }
+
+    #[test]
+    fn both_players_record_the_result_they_agree_on() {
+        // a room called "room" would read as a lobby announcement
+        let (mut white, mut black) = two_players(None);
+        for player in [&mut white, &mut black] {
+            player.server = Some("example.com:6000".to_string());
+            player.referee.room = "study".to_string();
+        }
+        for (i, text) in ["f2f3", "e7e5", "g2g4", "d8h4"].iter().enumerate() {
+            match i % 2 {
+                0 => play_and_deliver(&mut white, &mut black, text),
+                _ => play_and_deliver(&mut black, &mut white, text),
+            }
+        }
+        // nothing goes in the history until the result is agreed
+        assert!(white.game_over() && black.game_over());
+        assert!(white.recorded_outcome().is_none());
+
+        // both see the mate at once, so both send it
+        white.send_result();
+        black.send_result();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while white.agreed.is_none() || black.agreed.is_none() {
+            assert!(Instant::now() < deadline, "the result never arrived");
+            white.receive();
+            black.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(white.desync.is_none() && black.desync.is_none());
+        assert_eq!(white.agreed, Some(Score::BlackWins));
+        assert_eq!(white.recorded_outcome(), Some(Outcome::Lost));
+        assert_eq!(black.recorded_outcome(), Some(Outcome::Won));
+    }
+
+    #[test]
+    fn spectators_are_counted_without_disturbing_the_game() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        state.referee.room = "study".to_string();
+        let spectate = |token: &str, name: &str| {
+            Message::Spectate {
+                room: "study".to_string(),
+                token: token.to_string(),
+                name: name.to_string(),
+            }
+            .to_string()
+        };
+        inbox.send(spectate("a1", "Klubbens skärm")).unwrap();
+        inbox.send(spectate("b2", "Bertil")).unwrap();
+        inbox.send(spectate("a1", "Klubbens skärm")).unwrap();
+        state.receive();
+        assert_eq!(state.spectators.names(), ["Bertil", "Klubbens skärm"]);
+        assert!(state.desync.is_none() && state.failure.is_none());
+        assert_eq!(state.referee.counter(), 1);
+        assert_eq!(state.net.ignored, 0);
+
+        // gone once they stop announcing themselves
+        let later = Instant::now() + spectators::SPECTATOR_TIMEOUT;
+        state.spectators.expire(later);
+        assert_eq!(state.spectators.count(), 0);
+    }
+
+    #[test]
+    fn a_result_that_differs_from_ours_is_a_desync() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        state.referee.room = "study".to_string();
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+
+        // the opponent thinks we resigned, but here the game goes on
+        let result = Message::Result {
+            room: "study".to_string(),
+            score: Score::BlackWins,
+        };
+        inbox.send(result.to_string()).unwrap();
+        state.receive();
+
+        let report = state.desync.as_ref().unwrap().report().clone();
+        assert_eq!(report.results, Some(("*", "0-1")));
+        assert!(state.agreed.is_none());
+        state.desync.as_mut().unwrap().key_down_event(
+            event::KeyCode::Right,
+            event::KeyMods::NONE,
+            None,
+        );
+        state.desync.as_mut().unwrap().key_down_event(
+            event::KeyCode::Return,
+            event::KeyMods::NONE,
+            None,
+        );
+        state.abandon_if_asked();
+        assert!(state.failure.is_some());
+    }
+
+    /// Two players in the same room, connected to a relay that, like the server, echoes every
+    /// message to both. With `chaos`, each is behind a bad connection to it.
+    fn two_players(chaos: Option<Chaos>) -> (AppState, AppState) {
+        let (to_relay, from_players) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let (to_white, white_inbox) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (to_black, black_inbox) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        thread::spawn(move || {
+            for msg in from_players {
+                if to_white.send(msg.clone()).is_err() || to_black.send(msg).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let player = |color, receiver, seed| {
+            let session = Session {
+                sender: to_relay.clone(),
+                receiver,
+                room_name: "room".to_string(),
+                color,
+                difficulty: None,
+                hotseat: false,
+                server: None,
+                opponent: None,
+                opponent_avatar: None,
+                correspondence: None,
+                replay: false,
+                recorded: None,
+            };
+            let session = match chaos {
+                Some(chaos) => chaos::wrap(session, chaos, &mut StdRng::seed_from_u64(seed)),
+                None => session,
+            };
+            AppState::new(session, SystemTime::now(), &Config::default())
+        };
+        let white = player(Colour::White, white_inbox, 1);
+        let black = player(Colour::Black, black_inbox, 2);
+        (white, black)
+    }
+
+    /// Plays a move for `mover`, then ticks both players until `waiter` has it too, the way the
+    /// other player can't move until the move has got through.
+    fn play_and_deliver(mover: &mut AppState, waiter: &mut AppState, text: &str) {
+        let (from, to, _) = parse_move(text).unwrap();
+        mover.play_move(from, to).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while waiter.referee.counter() != mover.referee.counter() {
+            assert!(Instant::now() < deadline, "move {} never arrived", text);
+            waiter.receive();
+            mover.receive();
+            assert!(waiter.failure.is_none() && mover.failure.is_none());
+            assert!(waiter.desync.is_none() && mover.desync.is_none());
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn a_correspondence_move_reaches_an_opponent_who_connects_later() {
+        use crate::correspondence::Correspondence;
+        use crate::session::Connection;
+        use crate::waiting::WaitingScreen;
+
+        let dir = std::env::temp_dir().join(format!("correspondence-game-{}", std::process::id()));
+        let (path_a, path_b) = (dir.join("a.txt"), dir.join("b.txt"));
+
+        // a relay pumped by hand, passing everything sent to whoever is in the room
+        let (to_relay, from_players) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let mut room: Vec<mpsc::SyncSender<String>> = Vec::new();
+        let pump = |room: &mut Vec<mpsc::SyncSender<String>>| {
+            while let Ok(msg) = from_players.try_recv() {
+                room.retain(|inbox| inbox.send(msg.clone()).is_ok());
+            }
+        };
+        let connect = |room: &mut Vec<mpsc::SyncSender<String>>| {
+            let (inbox, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+            room.push(inbox);
+            Connection {
+                sender: to_relay.clone(),
+                receiver,
+                server: "127.0.0.1:6000".to_string(),
+                room_name: "study".to_string(),
+            }
+        };
+
+        // A starts the game, plays e2e4 with nobody there to see it, and leaves
+        let session = connect(&mut room)
+            .into_correspondence(Correspondence::new(path_a.clone(), Colour::White));
+        let mut a = AppState::new(session, SystemTime::now(), &Config::default());
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        a.play_move(from, to).unwrap();
+        pump(&mut room);
+        a.receive();
+        a.leave();
+        pump(&mut room);
+        assert!(a.desync.is_none());
+        assert!(a.outcome().is_none());
+        drop(a);
+        room.clear();
+
+        // B connects afterwards and waits in the room, seeing nothing of the move
+        let connection = connect(&mut room);
+        let mut screen = WaitingScreen::new(
+            connection,
+            None,
+            Some(path_b.clone()),
+            &mut StdRng::seed_from_u64(1),
+        );
+        pump(&mut room);
+        assert!(screen.update().unwrap().is_none());
+
+        // A comes back to the game kept for the room, and B joins it on A's sync offer
+        let kept = Correspondence::load(&path_a).unwrap();
+        assert_eq!(kept.moves, ["e2e4"]);
+        let session = connect(&mut room).into_correspondence(kept);
+        let mut a = AppState::new(session, SystemTime::now(), &Config::default());
+        assert_eq!(a.referee.counter(), 2);
+        pump(&mut room);
+        let session = screen.update().unwrap().unwrap();
+        assert_eq!(session.color, Colour::Black);
+        let mut b = AppState::new(session, SystemTime::now(), &Config::default());
+
+        // B is behind, so it asks for the move and A sends it again
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while b.referee.counter() != a.referee.counter() {
+            assert!(Instant::now() < deadline, "the move never arrived");
+            pump(&mut room);
+            a.receive();
+            b.receive();
+            assert!(a.desync.is_none() && b.desync.is_none());
+        }
+        assert_eq!(b.move_names(), ["e2e4"]);
+        assert_eq!(Correspondence::load(&path_b).unwrap().moves, ["e2e4"]);
+
+        // and the game goes on as usual, with both kept
+        let (from, to, _) = parse_move("e7e5").unwrap();
+        b.play_move(from, to).unwrap();
+        while a.referee.counter() != b.referee.counter() {
+            assert!(Instant::now() < deadline, "the answer never arrived");
+            pump(&mut room);
+            a.receive();
+            b.receive();
+            assert!(a.desync.is_none() && b.desync.is_none());
+        }
+        assert_eq!(
+            Correspondence::load(&path_a).unwrap().moves,
+            ["e2e4", "e7e5"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn both_players_see_the_same_game_after_every_move() {
+        // a capture, a promotion by capturing, castling short, and mate with the new queen
+        const GAME: [&str; 17] = [
+            "e2e4", "f7f5", "e4f5", "g7g6", "f5g6", "g8f6", "g6h7", "h8g8", "h7g8", "a7a6", //
+            "g1f3", "a6a5", "f1c4", "a5a4", "e1g1", "b7b6", "g8f7",
+        ];
+        let (mut white, mut black) = two_players(None);
+
+        for (i, text) in GAME.iter().enumerate() {
+            if i % 2 == 0 {
+                play_and_deliver(&mut white, &mut black, text);
+            } else {
+                play_and_deliver(&mut black, &mut white, text);
+            }
+
+            // there's no SAN here, so the move log is compared as what SAN would be made from
+            let names = |state: &AppState| {
+                let moves = state.moves.iter();
+                let moves = moves.map(|m| (m.name(), m.captured, m.check));
+                moves.collect::<Vec<_>>()
+            };
+            assert_eq!(
+                white.referee.counter(),
+                black.referee.counter(),
+                "after {}",
+                text
+            );
+            assert_eq!(
+                position_key(white.referee.game()),
+                position_key(black.referee.game())
+            );
+            assert_eq!(names(&white), names(&black));
+            assert_eq!(
+                white.referee.game().get_game_state(),
+                black.referee.game().get_game_state()
+            );
+        }
+
+        assert_eq!(white.referee.counter(), GAME.len() as u32 + 1);
+        assert_eq!(
+            black.referee.game().get_game_state(),
+            chess_template::GameState::GameOver
+        );
+        assert_eq!(black.moves[2].captured, Some(PieceType::Pawn));
+        assert_eq!(black.moves[8].captured, Some(PieceType::Rook));
+        assert!(black.moves[16].check);
+
+        // the pawn came back as a queen, and the king and rook castled
+        let board = black.referee.game().get_board();
+        let piece = |square: &str| {
+            let pos = parse_square(square).unwrap();
+            board[pos.row * 8 + pos.col].map(|p| (p.colour, p.piece_type))
+        };
+        assert_eq!(piece("f7"), Some((Colour::White, PieceType::Queen)));
+        assert_eq!(piece("g1"), Some((Colour::White, PieceType::King)));
+        assert_eq!(piece("f1"), Some((Colour::White, PieceType::Rook)));
+        assert_eq!(piece("h1"), None);
+    }
+
+    #[test]
+    fn a_game_over_a_slow_connection_ends_the_same_for_both_players() {
+        const GAME: [&str; 20] = [
+            "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "d2d3", "f8c5", "b1c3", "d7d6", //
+            "c1g5", "h7h6", "g5h4", "g7g5", "h4g3", "c8g4", "h2h3", "g4h5", "a2a3", "a7a6",
+        ];
+        let chaos = Chaos::parse("delay=5ms,jitter=5ms").unwrap();
+        let (mut white, mut black) = two_players(Some(chaos));
+
+        for (i, text) in GAME.iter().enumerate() {
+            if i % 2 == 0 {
+                play_and_deliver(&mut white, &mut black, text);
+            } else {
+                play_and_deliver(&mut black, &mut white, text);
+            }
+        }
+
+        assert_eq!(white.referee.counter(), GAME.len() as u32 + 1);
+        assert_eq!(
+            position_key(white.referee.game()),
+            position_key(black.referee.game())
+        );
+        assert_eq!(white.moves.len(), black.moves.len());
+    }
+
+    #[test]
+    fn moves_delivered_twice_or_late_are_dropped_by_their_counter() {
+        let (mut state, inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        inbox.send(move_message(3, "e7e5")).unwrap();
+        inbox.send(move_message(3, "e7e5")).unwrap();
+        state.receive();
+        let (from, to, _) = parse_move("g1f3").unwrap();
+        state.play_move(from, to).unwrap();
+        // a copy arriving after our answer, then the move that follows it
+        inbox.send(move_message(3, "e7e5")).unwrap();
+        inbox.send(move_message(5, "b8c6")).unwrap();
+        state.receive();
+        assert_eq!(state.referee.counter(), 5);
+        assert!(state.desync.is_none());
+        assert_eq!(state.net.repeated, 1);
+
+        // an earlier move that isn't the one played is a real divergence
+        inbox.send(move_message(3, "d7d5")).unwrap();
+        state.receive();
+        assert!(state.desync.is_some());
+    }
+
+    #[test]
+    fn a_game_over_a_connection_that_duplicates_and_reorders_ends_the_same_for_both() {
+        // loss is left out: there's no retransmission in the protocol, so a lost move can't be
+        // made up for and is meant to end in a desync
+        const GAME: [&str; 20] = [
+            "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "d2d3", "f8c5", "b1c3", "d7d6", //
+            "c1g5", "h7h6", "g5h4", "g7g5", "h4g3", "c8g4", "h2h3", "g4h5", "a2a3", "a7a6",
+        ];
+        let chaos =
+            Chaos::parse("delay=5ms,jitter=5ms,duplicate=30%,reorder=30%,seed=141").unwrap();
+        let (mut white, mut black) = two_players(Some(chaos));
+
+        for (i, text) in GAME.iter().enumerate() {
+            if i % 2 == 0 {
+                play_and_deliver(&mut white, &mut black, text);
+            } else {
+                play_and_deliver(&mut black, &mut white, text);
+            }
+        }
+        // the last copies still in flight
+        let deadline = Instant::now() + Duration::from_millis(50);
+        while Instant::now() < deadline {
+            white.receive();
+            black.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(white.desync.is_none() && black.desync.is_none());
+        assert_eq!(white.referee.counter(), GAME.len() as u32 + 1);
+        assert_eq!(black.referee.counter(), white.referee.counter());
+        assert_eq!(
+            position_key(white.referee.game()),
+            position_key(black.referee.game())
+        );
+    }
+
+    #[test]
+    fn history_entries_are_found_by_line_and_column() {
+        let (x, y) = (HISTORY_RECT.x, HISTORY_RECT.y);
+        let white = x + HISTORY_COLUMNS[0];
+        let black = x + HISTORY_COLUMNS[1] + 5.0;
+        assert_eq!(history_entry_at(3, 0.0, white, y + 5.0), Some(0));
+        assert_eq!(history_entry_at(3, 0.0, black, y + 5.0), Some(1));
+        assert_eq!(
+            history_entry_at(3, 0.0, x + 5.0, y + HISTORY_LINE + 5.0),
+            Some(2)
+        );
+        // no move there yet, and outside the panel
+        assert_eq!(
+            history_entry_at(3, 0.0, black, y + HISTORY_LINE + 5.0),
+            None
+        );
+        assert_eq!(history_entry_at(3, 0.0, white, y - 5.0), None);
+        // scrolled down a line, the second line is at the top
+        assert_eq!(history_entry_at(3, HISTORY_LINE, white, y + 5.0), Some(2));
+    }
+
+    #[test]
+    fn clicking_an_earlier_move_shows_its_position() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let saved = SavedGame {
+            room: "room".to_string(),
+            fen: String::new(),
+            moves: ["e2e4", "e7e5", "g1f3"].map(String::from).to_vec(),
+        };
+        state.resume(&saved);
+        assert_eq!(state.snapshots.len(), 3);
+        let placement = crate::history::placement;
+        assert_eq!(
+            placement(&state.snapshots[2]),
+            placement(&state.referee.game().get_board())
+        );
+
+        let (x, y) = (
+            HISTORY_RECT.x + HISTORY_COLUMNS[1] + 5.0,
+            HISTORY_RECT.y + 5.0,
+        );
+        assert!(state.review_clicked_move(x, y));
+        assert_eq!(state.reviewing, Some(1));
+        let after_e5 = state.snapshots[1];
+        assert!(after_e5[3 * 8 + 4].is_some() && after_e5[5 * 8 + 5].is_none());
+
+        // the latest move is the game as it is
+        let (x, y) = (HISTORY_RECT.x + 20.0, HISTORY_RECT.y + HISTORY_LINE + 5.0);
+        assert!(state.review_clicked_move(x, y));
+        assert_eq!(state.reviewing, None);
+        assert!(!state.review_clicked_move(BOARD_SIZE.0 / 2.0, 20.0));
+    }
+
+    #[test]
+    fn the_wheel_steps_through_the_moves_and_back_to_the_game() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        state.step_review(true);
+        assert_eq!(state.reviewing, None);
+
+        let saved = SavedGame {
+            room: "room".to_string(),
+            fen: String::new(),
+            moves: ["e2e4", "e7e5", "g1f3"].map(String::from).to_vec(),
+        };
+        state.resume(&saved);
+        let mut shown = Vec::new();
+        for back in [true, true, true, false, false, false] {
+            state.step_review(back);
+            shown.push(state.reviewing);
+        }
+        assert_eq!(shown, [Some(1), Some(0), Some(0), Some(1), None, None]);
+
+        // flipped, h1 is in the top left corner
+        let corner = (BOARD_ORIGIN.0 + 1.0, BOARD_ORIGIN.1 + 1.0);
+        assert!(state.square_under(corner.0, corner.1) == parse_square("a8"));
+        state.flipped = true;
+        assert!(state.square_under(corner.0, corner.1) == parse_square("h1"));
+    }
+
+    #[test]
+    fn analysis_starts_from_the_move_being_reviewed() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        let saved = SavedGame {
+            room: "room".to_string(),
+            fen: String::new(),
+            moves: ["e2e4", "e7e5", "g1f3"].map(String::from).to_vec(),
+        };
+        state.resume(&saved);
+        end_game(&mut state, Ending::Resigned(Colour::Black));
+        let placement = crate::history::placement;
+
+        state.step_review(true);
+        state.act(&mut shared, Action::Analysis, None);
+        let analysis = state.analysis.as_ref().unwrap();
+        assert_eq!(
+            placement(&analysis.game.get_board()),
+            placement(&state.snapshots[1])
+        );
+        assert_eq!(state.reviewing, None);
+
+        // and from the end of the game when nothing is
+        state.act(&mut shared, Action::Analysis, None);
+        assert!(state.analysis.is_none());
+        state.act(&mut shared, Action::Analysis, None);
+        let analysis = state.analysis.as_ref().unwrap();
+        assert_eq!(
+            placement(&analysis.game.get_board()),
+            placement(&state.referee.game().get_board())
+        );
+    }
+
+    #[test]
+    fn hanging_pieces_are_marked_in_practice_games() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        for text in ["e2e4", "d7d5"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.referee.play_on(from, to).unwrap();
+        }
+        // the pawn on e4 is attacked by the one on d5, and nothing defends it
+        assert!(state.hanging_squares() == vec![Position::new(4, 4).unwrap()]);
+
+        assert!(state.marks_hanging_pieces(HangingPieces::Practice));
+        assert!(!state.marks_hanging_pieces(HangingPieces::Off));
+
+        state.hotseat = false;
+        state.server = Some("example.com:6000".to_string());
+        assert!(!state.marks_hanging_pieces(HangingPieces::Practice));
+        assert!(!state.marks_hanging_pieces(HangingPieces::Always));
+        state.assists_allowed_by = vec![Colour::White, Colour::Black];
+        assert!(state.marks_hanging_pieces(HangingPieces::Always));
+    }
+
+    /// Two players online in a room called "study", with the game just resigned by White.
+    fn finished_online_game() -> (AppState, AppState) {
+        let (mut white, mut black) = two_players(None);
+        for player in [&mut white, &mut black] {
+            player.server = Some("example.com:6000".to_string());
+            player.referee.room = "study".to_string();
+            end_game(player, Ending::Resigned(Colour::White));
+        }
+        (white, black)
+    }
+
+    /// Lets both players agree to a rematch until each has heard from the other.
+    fn exchange_rematch(white: &mut AppState, black: &mut AppState) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while white.rematch.agreed.len() < 2 || black.rematch.agreed.len() < 2 {
+            assert!(Instant::now() < deadline, "the agreement never arrived");
+            white.update_rematch(true);
+            black.update_rematch(true);
+            white.receive();
+            black.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn automatic_rematch_starts_after_both_agree_with_colours_swapped() {
+        let (mut white, mut black) = finished_online_game();
+        white.update_rematch(true);
+        assert!(white.rematch_cancellable() && white.rematch.starts_at.is_none());
+
+        exchange_rematch(&mut white, &mut black);
+        white.update_rematch(true);
+        let starts_at = white.rematch.starts_at.unwrap();
+        assert!(starts_at > Instant::now() + REMATCH_COUNTDOWN - Duration::from_secs(1));
+
+        // nothing happens until the countdown has run out
+        white.update_rematch(true);
+        assert!(white.game_over());
+        white.rematch.starts_at = Some(Instant::now());
+        white.update_rematch(true);
+        assert!(!white.game_over() && !white.rematch_cancellable());
+        assert_eq!(white.referee.colour, Colour::Black);
+        assert_eq!(white.referee.counter(), 1);
+    }
+
+    #[test]
+    fn the_headless_bot_agrees_to_a_restart() {
+        use crate::headless::Bot;
+
+        // black's channels are driven by the bot instead of a second window
+        let (mut white, black) = two_players(None);
+        white.server = Some("example.com:6000".to_string());
+        let mut bot = Bot::new("room", Colour::Black);
+        let pump = |bot: &mut Bot| {
+            while let Ok(msg) = black.to_mainthread_receiver.try_recv() {
+                if let Some(reply) = bot.handle(&msg).unwrap() {
+                    black.sender.send(reply.to_string()).unwrap();
+                }
+            }
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        white.play_move(from, to).unwrap();
+        while !bot.referee().our_move() {
+            assert!(Instant::now() < deadline, "the move never arrived");
+            pump(&mut bot);
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (from, to, _) = parse_move("e7e5").unwrap();
+        black
+            .sender
+            .send(bot.play(from, to).unwrap().to_string())
+            .unwrap();
+
+        white.restart();
+        while !white.moves.is_empty() || white.referee.restart_asked().is_some() {
+            assert!(Instant::now() < deadline, "the restart was never agreed to");
+            pump(&mut bot);
+            white.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(white.desync.is_none() && white.failure.is_none());
+        assert!(!bot.referee().our_move());
+
+        // and the new game goes on in step
+        let (from, to, _) = parse_move("d2d4").unwrap();
+        white.play_move(from, to).unwrap();
+        while !bot.referee().our_move() {
+            assert!(
+                Instant::now() < deadline,
+                "the new game's move never arrived"
+            );
+            pump(&mut bot);
+            thread::sleep(Duration::from_millis(1));
+        }
+        white.receive();
+        assert!(white.desync.is_none() && white.failure.is_none());
+        assert_eq!(white.move_names(), ["d2d4"]);
+    }
+
+    #[test]
+    fn restarting_online_waits_for_the_opponent_to_agree() {
+        let (mut white, mut black) = two_players(None);
+        for player in [&mut white, &mut black] {
+            player.server = Some("example.com:6000".to_string());
+            player.referee.room = "study".to_string();
+        }
+        play_and_deliver(&mut white, &mut black, "e2e4");
+
+        white.restart();
+        assert_eq!(white.move_names(), ["e2e4"]);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while black.referee.restart_asked().is_none() {
+            assert!(Instant::now() < deadline, "the request never arrived");
+            black.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(black.referee.restart_asked(), Some(Colour::White));
+        assert_eq!(black.move_names(), ["e2e4"]);
+
+        // asking again sends nothing more
+        white.restart();
+        assert_eq!(white.referee.restart_asked(), Some(Colour::White));
+
+        black.restart();
+        assert!(black.moves.is_empty() && black.referee.restart_asked().is_none());
+        while !white.moves.is_empty() {
+            assert!(Instant::now() < deadline, "the agreement never arrived");
+            white.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(white.referee.restart_asked().is_none());
+        assert_eq!(white.net.unasked, 0);
+    }
+
+    #[test]
+    fn cancelling_the_rematch_stops_it_for_both() {
+        let (mut white, mut black) = finished_online_game();
+        exchange_rematch(&mut white, &mut black);
+        white.update_rematch(true);
+        black.update_rematch(true);
+
+        black.cancel_rematch();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !white.rematch.cancelled {
+            assert!(Instant::now() < deadline, "the cancellation never arrived");
+            white.receive();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(white.rematch.starts_at.is_none() && !white.rematch_cancellable());
+
+        // and restarting by hand is left
+        white.rematch.starts_at = Some(Instant::now());
+        white.update_rematch(true);
+        assert!(white.game_over());
+
+        // with it turned off, nobody agrees in the first place
+        let (mut white, _black) = finished_online_game();
+        white.update_rematch(false);
+        assert!(white.rematch.agreed.is_empty());
+    }
+
+    #[test]
+    fn the_threat_view_is_only_allowed_online_once_both_players_agree() {
+        let (mut white, mut black) = two_players(None);
+        assert!(white.assists_allowed());
+        // a room called "room" would be taken for the lobby by three-word messages
+        for player in [&mut white, &mut black] {
+            player.server = Some("example.com:6000".to_string());
+            player.referee.room = "study".to_string();
+        }
+        assert!(!white.assists_allowed());
+
+        // each waits for the other's agreement to arrive, and their own comes back too
+        let deliver = |white: &mut AppState, black: &mut AppState, agreed: usize| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while white.assists_allowed_by.len() < agreed || black.assists_allowed_by.len() < agreed
+            {
+                assert!(Instant::now() < deadline, "the agreement never arrived");
+                white.receive();
+                black.receive();
+                thread::sleep(Duration::from_millis(1));
+            }
+        };
+        white.allow_assists();
+        deliver(&mut white, &mut black, 1);
+        assert!(!white.assists_allowed() && !black.assists_allowed());
+
+        black.allow_assists();
+        deliver(&mut white, &mut black, 2);
+        assert!(white.assists_allowed() && black.assists_allowed());
+    }
+
+    #[test]
+    fn closing_an_online_game_resigns_it_before_the_connection_closes() {
+        // stands in for the network threads: whatever is sent is kept, and the incoming channel
+        // is dropped once the outgoing one closes
+        let (sender, outgoing) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let (incoming, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let network = thread::spawn(move || {
+            let sent: Vec<String> = outgoing.iter().collect();
+            drop(incoming);
+            sent
+        });
+
+        let session = Session {
+            sender,
+            receiver,
+            room_name: "room".to_string(),
+            color: Colour::White,
+            difficulty: None,
+            hotseat: false,
+            server: Some("example.com:6000".to_string()),
+            opponent: None,
+            opponent_avatar: None,
+            correspondence: None,
+            replay: false,
+            recorded: None,
+        };
+        let mut state = AppState::new(session, SystemTime::now(), &Config::default());
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        let started = Instant::now();
+        state.close(&mut shared);
+
+        assert!(started.elapsed() < SHUTDOWN_WAIT);
+        assert!(!state.connected);
+        let sent = network.join().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(protocol::untag(&sent[1]).1, "room resign white");
+    }
+
+    #[test]
+    fn the_title_says_whose_move_it_is() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        assert_eq!(state.title(), "Schack — room — White to move — move 1");
+
+        state.hotseat = false;
+        assert_eq!(state.title(), "Schack — room — Your move (White) — move 1");
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        state.play_move(from, to).unwrap();
+        assert_eq!(
+            state.title(),
+            "Schack — room — Opponent's move (Black) — move 1"
+        );
+
+        state.connected = false;
+        assert!(state.title().ends_with("move 1 (disconnected)"));
+    }
+
+    #[test]
+    fn double_clicking_a_piece_with_one_move_plays_it() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut config = Config::default();
+        for text in ["e2e4", "e7e5"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            state.play_move(from, to).unwrap();
+        }
+        let square = |text: &str| parse_square(text).unwrap();
+
+        // the knight has three moves, so a double-click only picks it up
+        state.click_board(square("g1"), &config);
+        state.click_board(square("g1"), &config);
+        assert!(state.selected_position == Some(square("g1")));
+        assert_eq!(state.moves.len(), 2);
+
+        // the king's only move is to e2, unless double-clicks are turned off
+        config.double_click_move = false;
+        state.click_board(square("e1"), &config);
+        state.click_board(square("e1"), &config);
+        assert_eq!(state.moves.len(), 2);
+        config.double_click_move = true;
+        state.click_board(square("e1"), &config);
+        state.click_board(square("e1"), &config);
+        assert_eq!(state.moves.len(), 3);
+        let king = state.referee.game().get_board()[square("e2").row * 8 + square("e2").col];
+        assert!(king.is_some_and(|piece| piece.piece_type == PieceType::King));
+    }
+
+    #[test]
+    fn the_keyboard_cursor_picks_up_and_plays_like_clicks() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let square = |text: &str| parse_square(text).unwrap();
+        let press = |state: &mut AppState, key| state.cursor_key(key, None);
+
+        // Enter types a move until the cursor is showing; the first arrow only shows it
+        assert!(!state.cursor_key(event::KeyCode::Return, Some(Action::TypeMove)));
+        assert!(press(&mut state, event::KeyCode::Up));
+        assert!(state.cursor == Some(square("e2")));
+
+        assert!(press(&mut state, event::KeyCode::Space));
+        assert!(state.selected_position == Some(square("e2")));
+        assert_eq!(state.positions.len(), 2);
+        press(&mut state, event::KeyCode::Up);
+        press(&mut state, event::KeyCode::Up);
+        assert!(state.cursor_key(event::KeyCode::Return, Some(Action::TypeMove)));
+        assert_eq!(state.moves.len(), 1);
+        assert!(state.selected_position.is_none());
+
+        // Up is up on the screen, which is towards White when flipped
+        state.flipped = true;
+        press(&mut state, event::KeyCode::Up);
+        press(&mut state, event::KeyCode::Left);
+        assert!(state.cursor == Some(square("f3")));
+        // and it stops at the edge
+        for _ in 0..10 {
+            press(&mut state, event::KeyCode::Right);
+        }
+        assert!(state.cursor == Some(square("a3")));
+
+        // keys bound to a shortcut are left alone
+        assert!(!state.cursor_key(event::KeyCode::Up, Some(Action::Help)));
+    }
+
+    #[test]
+    fn a_finger_held_still_presses_once() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let since = Instant::now();
+        let touch = |dragged| TouchDown {
+            start: [100.0, 100.0],
+            at: [104.0, 100.0],
+            since,
+            dragged,
+            long_pressed: false,
+        };
+
+        state.touch_down = Some(touch(false));
+        assert!(state.long_press(since + LONG_PRESS / 2).is_none());
+        assert_eq!(state.long_press(since + LONG_PRESS), Some([104.0, 100.0]));
+        assert!(state.long_press(since + LONG_PRESS * 2).is_none());
+
+        state.touch_down = Some(touch(true));
+        assert!(state.long_press(since + LONG_PRESS).is_none());
+    }
+
+    #[test]
+    fn clicks_that_pick_nothing_up_say_why() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let square = |text: &str| parse_square(text).unwrap();
+        // only a few toasts are shown at once, so each is taken away once it's checked
+        let said = |state: &mut AppState| mem::take(&mut state.toasts).shown()[0].text.clone();
+        state.hotseat = false;
+
+        state.click_square(square("e7"));
+        assert_eq!(
+            said(&mut state),
+            "You are playing White — that's Black's piece"
+        );
+        state.click_square(square("e4"));
+        assert_eq!(said(&mut state), "There's no piece on e4");
+
+        state.click_square(square("e2"));
+        state.click_square(square("e4"));
+        state.click_square(square("d4"));
+        assert_eq!(said(&mut state), "Waiting for Black to move");
+        assert!(state.premove.is_none());
+
+        end_game(&mut state, Ending::DrawAgreed);
+        state.click_square(square("d2"));
+        assert_eq!(said(&mut state), "The game is over");
+        assert!(state.selected_position.is_none());
+    }
+
+    #[test]
+    fn the_draw_and_resign_buttons_grey_out_when_they_would_do_nothing() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        state.hotseat = false;
+        let rects = hud_rects(Layout::Standard);
+        let click = |state: &mut AppState, rect: graphics::Rect| {
+            let (x, y) = (rect.x + 5.0, rect.y + 5.0);
+            state
+                .hud
+                .mouse_button_down_event(event::MouseButton::Left, x, y);
+            state
+                .hud
+                .mouse_button_up_event(event::MouseButton::Left, x, y);
+            state.hud.take_choice()
+        };
+
+        state.update_hud();
+        assert_eq!(click(&mut state, rects[0]), Some(HudAction::OfferDraw));
+        state.offer_draw();
+        state.update_hud();
+        assert_eq!(click(&mut state, rects[0]), None);
+        assert_eq!(click(&mut state, rects[1]), Some(HudAction::Resign));
+
+        state.resign();
+        state.update_hud();
+        assert_eq!(click(&mut state, rects[1]), None);
+        // nor does a click there reach the board
+        assert!(state.square_under(rects[0].x, rects[0].y).is_none());
+    }
+
+    #[test]
+    fn the_mini_layout_keeps_the_buttons_on_the_line_under_the_board() {
+        let area = Layout::Mini.area();
+        let rects = hud_rects(Layout::Mini);
+        assert!(rects.iter().all(|rect| {
+            rect.x >= area.x && rect.right() <= area.right() && rect.bottom() <= area.bottom()
+        }));
+        assert!(rects.iter().all(|rect| rect.y >= BOARD_SIZE.1));
+        // room for the status at the start of the line
+        assert!(rects[0].x > BOARD_SIZE.0 / 2.0);
+    }
+
+    #[test]
+    fn gamepad_shortcuts_wait_for_what_is_open() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+
+        state.pad_shortcut(&mut shared, Action::FlipBoard);
+        assert!(state.flipped);
+
+        // the first button only closes the help
+        state.help = true;
+        state.pad_shortcut(&mut shared, Action::FlipBoard);
+        assert!(!state.help && state.flipped);
+
+        // Start opens the pause menu, and nothing else gets past it
+        state.pad_shortcut(&mut shared, Action::Pause);
+        assert!(state.pause.is_some());
+        state.pad_shortcut(&mut shared, Action::FlipBoard);
+        assert!(state.flipped);
+    }
+
+    #[test]
+    fn the_computer_gets_no_stronger_than_the_strongest() {
+        let (mut state, _inbox, _sent) = game_with_inbox();
+        let mut shared = Shared::new(Args::try_parse_from(["schack"]).unwrap(), Config::default());
+        state.difficulty = Some(MAX_DIFFICULTY);
+        state.act(&mut shared, Action::Stronger, None);
+        assert_eq!(state.difficulty, Some(MAX_DIFFICULTY));
+        assert_eq!(shared.config.ai_difficulty, Some(MAX_DIFFICULTY));
+
+        // even from a level past the top, which can't go one higher
+        state.difficulty = Some(u8::MAX);
+        state.act(&mut shared, Action::Stronger, None);
+        assert_eq!(state.difficulty, Some(MAX_DIFFICULTY));
+        state.act(&mut shared, Action::Weaker, None);
+        assert_eq!(state.difficulty, Some(MAX_DIFFICULTY - 1));
+    }
+}
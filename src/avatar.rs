@@ -0,0 +1,128 @@
+/**
+ * Avatars shown next to the players' names: one of a few bundled images in
+ * `resources/avatars/`, or a plain colour swatch. Each player picks theirs in the settings, and
+ * it's sent to the opponent by its place in the list in the lobby announcement. A player who
+ * hasn't picked one, an older client, or a place this version doesn't know all get the default.
+ */
+use ggez::{graphics, Context, GameResult};
+
+/// How an avatar is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Look {
+    /// An image in `resources/avatars/`, e.g. "/avatars/knight.png".
+    Image(&'static str),
+    /// A square of one colour, as red, green and blue.
+    Swatch([f32; 3]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Avatar {
+    pub name: &'static str,
+    pub look: Look,
+}
+
+/// Every avatar, in the order they're numbered over the network. New ones go at the end, so
+/// the numbers older clients send keep meaning the same avatar.
+pub const AVATARS: [Avatar; 8] = [
+    Avatar {
+        name: "Grey",
+        look: Look::Swatch([0.55, 0.55, 0.55]),
+    },
+    Avatar {
+        name: "Knight",
+        look: Look::Image("/avatars/knight.png"),
+    },
+    Avatar {
+        name: "Rook",
+        look: Look::Image("/avatars/rook.png"),
+    },
+    Avatar {
+        name: "Bishop",
+        look: Look::Image("/avatars/bishop.png"),
+    },
+    Avatar {
+        name: "Queen",
+        look: Look::Image("/avatars/queen.png"),
+    },
+    Avatar {
+        name: "Red",
+        look: Look::Swatch([0.75, 0.2, 0.2]),
+    },
+    Avatar {
+        name: "Green",
+        look: Look::Swatch([0.2, 0.6, 0.25]),
+    },
+    Avatar {
+        name: "Blue",
+        look: Look::Swatch([0.2, 0.35, 0.75]),
+    },
+];
+
+/// The avatar of players who haven't picked one.
+pub const DEFAULT: usize = 0;
+
+/// The avatar numbered `index`, or the default if there's none or it's not one we know.
+pub fn avatar(index: Option<u8>) -> &'static Avatar {
+    index
+        .and_then(|index| AVATARS.get(usize::from(index)))
+        .unwrap_or(&AVATARS[DEFAULT])
+}
+
+/// The images of the avatars that have one.
+pub fn image_paths() -> impl Iterator<Item = &'static str> {
+    AVATARS.iter().filter_map(|avatar| match avatar.look {
+        Look::Image(path) => Some(path),
+        Look::Swatch(_) => None,
+    })
+}
+
+/// Draws an avatar filling `rect`. One whose image couldn't be loaded is drawn as the default.
+pub fn draw(
+    ctx: &mut Context,
+    avatar: &Avatar,
+    image: Option<&graphics::Image>,
+    rect: graphics::Rect,
+) -> GameResult {
+    let colour = match (avatar.look, image) {
+        (Look::Image(_), Some(image)) => {
+            let scale = [
+                rect.w / f32::from(image.width()),
+                rect.h / f32::from(image.height()),
+            ];
+            return graphics::draw(
+                ctx,
+                image,
+                graphics::DrawParam::default()
+                    .dest([rect.x, rect.y])
+                    .scale(scale),
+            );
+        }
+        (Look::Swatch(colour), _) => colour,
+        (Look::Image(_), None) => match AVATARS[DEFAULT].look {
+            Look::Swatch(colour) => colour,
+            Look::Image(_) => [0.5, 0.5, 0.5],
+        },
+    };
+    let swatch = graphics::Mesh::new_rounded_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        rect,
+        rect.h / 4.0,
+        [colour[0], colour[1], colour[2], 1.0].into(),
+    )?;
+    graphics::draw(ctx, &swatch, graphics::DrawParam::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_avatars_fall_back_to_the_default() {
+        assert_eq!(avatar(Some(1)).name, "Knight");
+        assert_eq!(avatar(Some(7)).name, "Blue");
+        assert_eq!(*avatar(None), AVATARS[DEFAULT]);
+        assert_eq!(*avatar(Some(AVATARS.len() as u8)), AVATARS[DEFAULT]);
+        assert_eq!(*avatar(Some(255)), AVATARS[DEFAULT]);
+    }
+}
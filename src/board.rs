@@ -0,0 +1,720 @@
+/**
+ * Board geometry and chess helpers that don't need the GUI: mapping between screen
+ * coordinates and squares, coordinate notation, and the rule checks used to explain moves.
+ */
+use chess_template::{Colour, Game, GameState, Piece, PieceType, Position};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A chess board is 8x8 tiles.
+pub const GRID_SIZE: i16 = 8;
+/// Sutible size of each tile.
+pub const GRID_CELL_SIZE: (i16, i16) = (90, 90);
+
+/// Width of the frame around the squares, where their coordinates are written.
+pub const BOARD_MARGIN: f32 = 24.0;
+
+/// Size of the squares together, inside the frame.
+pub const SQUARES_SIZE: (f32, f32) = (
+    GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32,
+    GRID_SIZE as f32 * GRID_CELL_SIZE.1 as f32,
+);
+
+/// Size of the board itself, frame included.
+pub const BOARD_SIZE: (f32, f32) = (
+    SQUARES_SIZE.0 + 2.0 * BOARD_MARGIN,
+    SQUARES_SIZE.1 + 2.0 * BOARD_MARGIN,
+);
+
+/// Top left corner of the squares, that of a8 unless the board is flipped, on the screen. Points
+/// and squares are only ever converted with `square_at` and `square_origin`, which are the two
+/// places that use it.
+pub const BOARD_ORIGIN: (f32, f32) = (BOARD_MARGIN, BOARD_MARGIN);
+
+/// The square under a point on the screen, or `None` if the point is outside the squares,
+/// including on the frame.
+pub fn square_at(x: f32, y: f32) -> Option<Position> {
+    let (x, y) = (x - BOARD_ORIGIN.0, y - BOARD_ORIGIN.1);
+    if x < 0.0 || y < 0.0 {
+        return None;
+    }
+    // each tile is 90x90 pixels, so we can divide the position by 90 to get the tile
+    let row = (y / GRID_CELL_SIZE.1 as f32) as usize;
+    let col = (x / GRID_CELL_SIZE.0 as f32) as usize;
+    // checked here, so a point past the last row or column, like one in the status strip under
+    // the board, is never made into a square, whatever the window's size
+    let size = GRID_SIZE as usize;
+    if row >= size || col >= size {
+        return None;
+    }
+    Position::new(row, col).ok()
+}
+
+/// Top left corner of a square on the screen.
+pub fn square_origin(pos: Position) -> (f32, f32) {
+    (
+        BOARD_ORIGIN.0 + pos.col as f32 * GRID_CELL_SIZE.0 as f32,
+        BOARD_ORIGIN.1 + pos.row as f32 * GRID_CELL_SIZE.1 as f32,
+    )
+}
+
+/// The square drawn where `pos` would be, with the board turned round if it's flipped, so that
+/// h1 is in the top left corner. Turning it twice gives the square back.
+pub fn turned(pos: Position, flipped: bool) -> Position {
+    match flipped {
+        true => Position::new(7 - pos.row, 7 - pos.col).unwrap_or(pos),
+        false => pos,
+    }
+}
+
+/// Name of a square in coordinate notation, e.g. "e4". Row 0 is drawn at the top of the
+/// board, which is the eighth rank from White's point of view.
+pub fn square_name(pos: Position) -> String {
+    format!("{}{}", (b'a' + pos.col as u8) as char, 8 - pos.row)
+}
+
+/// Parses a square in coordinate notation, e.g. "e4". The inverse of `square_name`.
+pub fn parse_square(text: &str) -> Option<Position> {
+    let mut chars = text.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?.to_digit(10)? as usize;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !(1..=8).contains(&rank) {
+        return None;
+    }
+    Position::new(8 - rank, file as usize - 'a' as usize).ok()
+}
+
+/// Parses a move in coordinate notation, e.g. "e2e4", with an optional promotion suffix as in "e7e8q".
+pub fn parse_move(text: &str) -> Option<(Position, Position, Option<PieceType>)> {
+    if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+        return None;
+    }
+    let promotion = match text.get(4..) {
+        Some("") => None,
+        Some("q") => Some(PieceType::Queen),
+        Some("r") => Some(PieceType::Rook),
+        Some("b") => Some(PieceType::Bishop),
+        Some("n") => Some(PieceType::Knight),
+        _ => return None,
+    };
+    Some((
+        parse_square(&text[0..2])?,
+        parse_square(&text[2..4])?,
+        promotion,
+    ))
+}
+
+/// Squares the piece on `from` could move to if checks were ignored. Castling and en passant
+/// are left out, since this is only used to explain why a click was rejected.
+pub fn pseudo_destinations(board: &[Option<Piece>; 64], from: Position) -> Vec<Position> {
+    let piece = match board[from.row * 8 + from.col] {
+        Some(piece) => piece,
+        None => return vec![],
+    };
+    let holds = |pos: &Position, colour: Colour| {
+        board[pos.row * 8 + pos.col].is_some_and(|p| p.colour == colour)
+    };
+    let enemy = match piece.colour {
+        Colour::White => Colour::Black,
+        Colour::Black => Colour::White,
+    };
+
+    // pieces capture where they attack, except that pawns only capture diagonally
+    let mut squares: Vec<Position> = attacks(board, from)
+        .into_iter()
+        .filter(|pos| match piece.piece_type {
+            PieceType::Pawn => holds(pos, enemy),
+            _ => !holds(pos, piece.colour),
+        })
+        .collect();
+
+    if piece.piece_type == PieceType::Pawn {
+        // white pawns move up the board, towards row 0
+        let (dir, start) = match piece.colour {
+            Colour::White => (-1, 6),
+            Colour::Black => (1, 1),
+        };
+        let empty =
+            |row: i32| (0..8).contains(&row) && board[row as usize * 8 + from.col].is_none();
+        let row = from.row as i32;
+        if empty(row + dir) {
+            squares.push(Position::new((row + dir) as usize, from.col).unwrap());
+            if row == start && empty(row + 2 * dir) {
+                squares.push(Position::new((row + 2 * dir) as usize, from.col).unwrap());
+            }
+        }
+    }
+    squares
+}
+
+/// Squares the piece on `from` attacks, i.e. where it could capture an enemy piece. That
+/// includes squares held by its own side, which it defends, and both of a pawn's diagonals
+/// even when they're empty, but not the squares ahead of a pawn, which it can't capture on.
+pub fn attacks(board: &[Option<Piece>; 64], from: Position) -> Vec<Position> {
+    let piece = match board[from.row * 8 + from.col] {
+        Some(piece) => piece,
+        None => return vec![],
+    };
+    let (row, col) = (from.row as i32, from.col as i32);
+    let on_board = |r: i32, c: i32| (0..8).contains(&r) && (0..8).contains(&c);
+
+    let mut squares = vec![];
+    match piece.piece_type {
+        PieceType::Pawn => {
+            // white pawns move up the board, towards row 0
+            let dir = match piece.colour {
+                Colour::White => -1,
+                Colour::Black => 1,
+            };
+            for dc in [-1, 1] {
+                squares.push((row + dir, col + dc));
+            }
+        }
+        PieceType::Knight | PieceType::King => {
+            let offsets: &[(i32, i32)] = if piece.piece_type == PieceType::Knight {
+                &[
+                    (1, 2),
+                    (2, 1),
+                    (-1, 2),
+                    (-2, 1),
+                    (1, -2),
+                    (2, -1),
+                    (-1, -2),
+                    (-2, -1),
+                ]
+            } else {
+                &[
+                    (1, 0),
+                    (-1, 0),
+                    (0, 1),
+                    (0, -1),
+                    (1, 1),
+                    (1, -1),
+                    (-1, 1),
+                    (-1, -1),
+                ]
+            };
+            for (dr, dc) in offsets {
+                squares.push((row + dr, col + dc));
+            }
+        }
+        PieceType::Rook | PieceType::Bishop | PieceType::Queen => {
+            let straight = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+            let diagonal = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+            let directions = match piece.piece_type {
+                PieceType::Rook => straight.to_vec(),
+                PieceType::Bishop => diagonal.to_vec(),
+                _ => [straight, diagonal].concat(),
+            };
+            // each ray stops at the first piece, whichever side it's on
+            for (dr, dc) in directions {
+                let (mut r, mut c) = (row + dr, col + dc);
+                while on_board(r, c) {
+                    squares.push((r, c));
+                    if board[(r * 8 + c) as usize].is_some() {
+                        break;
+                    }
+                    r += dr;
+                    c += dc;
+                }
+            }
+        }
+    }
+
+    squares
+        .into_iter()
+        .filter(|&(r, c)| on_board(r, c))
+        .filter_map(|(r, c)| Position::new(r as usize, c as usize).ok())
+        .collect()
+}
+
+/// Whether each square is attacked by a piece of the given colour, in reading order from a8.
+pub fn attack_map(board: &[Option<Piece>; 64], colour: Colour) -> [bool; 64] {
+    let mut attacked = [false; 64];
+    for idx in 0..64 {
+        if board[idx].is_some_and(|p| p.colour == colour) {
+            for pos in attacks(board, Position::new(idx / 8, idx % 8).unwrap()) {
+                attacked[pos.row * 8 + pos.col] = true;
+            }
+        }
+    }
+    attacked
+}
+
+/// Every square attacked by a piece of the given colour, each once, in reading order from a8.
+pub fn attacked_squares(board: &[Option<Piece>; 64], colour: Colour) -> Vec<Position> {
+    let attacked = attack_map(board, colour);
+    (0..64)
+        .filter(|&idx| attacked[idx])
+        .filter_map(|idx| Position::new(idx / 8, idx % 8).ok())
+        .collect()
+}
+
+/// The squares each side attacks, White's first, which is all it takes to tell how safe any
+/// piece in the position is.
+pub fn cover(board: &[Option<Piece>; 64]) -> [[bool; 64]; 2] {
+    [
+        attack_map(board, Colour::White),
+        attack_map(board, Colour::Black),
+    ]
+}
+
+/// Whether the other side attacks a piece, and whether its own side defends it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Safety {
+    pub attacked: bool,
+    pub defended: bool,
+}
+
+/// How safe the piece on `pos` is, given each side's `cover`, or None for an empty square. A
+/// piece is defended when one of its side attacks its square, which move lists don't say, since
+/// a piece can't move onto its own side.
+pub fn safety(
+    board: &[Option<Piece>; 64],
+    cover: &[[bool; 64]; 2],
+    pos: Position,
+) -> Option<Safety> {
+    let idx = pos.row * 8 + pos.col;
+    let (own, enemy) = match board[idx]?.colour {
+        Colour::White => (0, 1),
+        Colour::Black => (1, 0),
+    };
+    Some(Safety {
+        attacked: cover[enemy][idx],
+        defended: cover[own][idx],
+    })
+}
+
+/// Pieces of the given colour that the other side attacks and none of their own pieces defend,
+/// so they can be taken for nothing. The king is left out, being attacked is check.
+pub fn hanging_pieces(board: &[Option<Piece>; 64], colour: Colour) -> Vec<Position> {
+    let cover = cover(board);
+    (0..64)
+        .filter_map(|idx| Position::new(idx / 8, idx % 8).ok())
+        .filter(|pos| {
+            board[pos.row * 8 + pos.col]
+                .is_some_and(|p| p.colour == colour && p.piece_type != PieceType::King)
+        })
+        .filter(|&pos| {
+            safety(board, &cover, pos).is_some_and(|safety| safety.attacked && !safety.defended)
+        })
+        .collect()
+}
+
+/// What a piece's tooltip says: the piece, and how safe it is if that's to be shown, e.g.
+/// "White knight, attacked and undefended". A king is only ever in check or not.
+pub fn piece_tooltip(piece: Piece, safety: Option<Safety>) -> String {
+    let colour = match piece.colour {
+        Colour::White => "White",
+        Colour::Black => "Black",
+    };
+    let name = format!("{} {}", colour, piece_name(piece.piece_type));
+    let safety = match safety {
+        Some(safety) => safety,
+        None => return name,
+    };
+    let state = match (piece.piece_type, safety.attacked, safety.defended) {
+        (PieceType::King, true, _) => "in check",
+        (PieceType::King, false, _) => "not in check",
+        (_, true, true) => "attacked and defended",
+        (_, true, false) => "attacked and undefended",
+        (_, false, true) => "defended",
+        (_, false, false) => "not attacked or defended",
+    };
+    format!("{}, {}", name, state)
+}
+
+/// Which of the piece on `from`'s destinations take a piece: the squares with a piece on them,
+/// since only the other side's are destinations, and a pawn's diagonal step, which onto an empty
+/// square is en passant.
+pub fn captures(
+    board: &[Option<Piece>; 64],
+    from: Position,
+    destinations: &[Position],
+) -> Vec<Position> {
+    let pawn = board[from.row * 8 + from.col].is_some_and(|p| p.piece_type == PieceType::Pawn);
+    destinations
+        .iter()
+        .copied()
+        .filter(|to| board[to.row * 8 + to.col].is_some() || (pawn && to.col != from.col))
+        .collect()
+}
+
+/// Explains why the piece on `from` can't move to `to`, for a beginner who just tried it.
+pub fn rejection_reason(game: &Game, from: Position, to: Position) -> &'static str {
+    if !pseudo_destinations(&game.get_board(), from).contains(&to) {
+        "That square isn't a legal destination"
+    } else if game.get_possible_moves(from, 0).is_empty() {
+        stuck_reason(game, from)
+    } else if game.get_game_state() == GameState::Check {
+        "That doesn't get you out of check"
+    } else {
+        "That would leave your king in check"
+    }
+}
+
+/// Explains why the piece on `from` has no legal moves at all.
+pub fn stuck_reason(game: &Game, from: Position) -> &'static str {
+    let board = game.get_board();
+    let is_king = board[from.row * 8 + from.col].is_some_and(|p| p.piece_type == PieceType::King);
+
+    if game.get_game_state() == GameState::Check {
+        "You're in check"
+    } else if pseudo_destinations(&board, from).is_empty() {
+        "That piece can't move"
+    } else if is_king {
+        "Every square your king can reach is attacked"
+    } else {
+        "That piece is pinned"
+    }
+}
+
+/// Why a click picked nothing up, for a beginner who thinks the board isn't working.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ignored {
+    /// The opponent is still to move, and it wasn't one of our pieces to premove.
+    NotYourTurn(Colour), // the side to move
+    /// A piece of the side we aren't playing.
+    NotYourPiece(Colour), // the side we play
+    /// A piece of the side that isn't to move, on a board either side plays from.
+    NotToMove(Colour), // the side to move
+    EmptySquare(Position),
+    GameOver,
+}
+
+impl Ignored {
+    pub fn message(self) -> String {
+        let other = |colour| match colour {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        };
+        match self {
+            Ignored::NotYourTurn(turn) => format!("Waiting for {:?} to move", turn),
+            Ignored::NotYourPiece(ours) => {
+                format!(
+                    "You are playing {:?} — that's {:?}'s piece",
+                    ours,
+                    other(ours)
+                )
+            }
+            Ignored::NotToMove(turn) => {
+                format!("It's {:?}'s move — that's {:?}'s piece", turn, other(turn))
+            }
+            Ignored::EmptySquare(square) => format!("There's no piece on {}", square_name(square)),
+            Ignored::GameOver => "The game is over".to_string(),
+        }
+    }
+}
+
+/// Whether the king of the given colour is attacked on this board.
+pub fn king_attacked(board: &[Option<Piece>; 64], colour: Colour) -> bool {
+    let king = match king_square(board, colour) {
+        Some(king) => king,
+        None => return false,
+    };
+
+    (0..64).any(|idx| {
+        board[idx].is_some_and(|p| p.colour != colour)
+            && pseudo_destinations(board, Position::new(idx / 8, idx % 8).unwrap()).contains(&king)
+    })
+}
+
+/// Where a side's king stands, if it's on the board.
+fn king_square(board: &[Option<Piece>; 64], colour: Colour) -> Option<Position> {
+    let idx = board
+        .iter()
+        .position(|p| p.is_some_and(|p| p.colour == colour && p.piece_type == PieceType::King))?;
+    Position::new(idx / 8, idx % 8).ok()
+}
+
+/// The square of whichever king is in check on this board, if either is.
+pub fn checked_king(board: &[Option<Piece>; 64]) -> Option<Position> {
+    [Colour::White, Colour::Black]
+        .into_iter()
+        .find(|&colour| king_attacked(board, colour))
+        .and_then(|colour| king_square(board, colour))
+}
+
+/// Material value of a piece, in pawns. Kings aren't counted.
+pub fn piece_value(piece_type: PieceType) -> u32 {
+    match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight | PieceType::Bishop => 3,
+        PieceType::Rook => 5,
+        PieceType::Queen => 9,
+        PieceType::King => 0,
+    }
+}
+
+/// A kind of piece as it's written in a sentence, e.g. "knight".
+pub fn piece_name(piece: PieceType) -> &'static str {
+    match piece {
+        PieceType::Pawn => "pawn",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    }
+}
+
+/// The filled and outlined chess symbols for a kind of piece.
+pub fn glyphs(piece_type: PieceType) -> (char, char) {
+    match piece_type {
+        PieceType::King => ('♚', '♔'),
+        PieceType::Queen => ('♛', '♕'),
+        PieceType::Rook => ('♜', '♖'),
+        PieceType::Bishop => ('♝', '♗'),
+        PieceType::Knight => ('♞', '♘'),
+        PieceType::Pawn => ('♟', '♙'),
+    }
+}
+
+/// Identifies a position by its pieces and the side to move, for spotting repetitions.
+/// The library doesn't expose castling or en passant rights, so those aren't included.
+pub fn position_key(game: &Game) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for (idx, piece) in game.get_board().iter().enumerate() {
+        if let Some(piece) = piece {
+            (idx, piece.colour, piece.piece_type).hash(&mut hasher);
+        }
+    }
+    game.get_active_colour().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::parse_placement;
+
+    fn pos(row: usize, col: usize) -> Position {
+        Position::new(row, col).unwrap()
+    }
+
+    /// A point on the screen, given from the top left corner of the squares.
+    fn at(x: f32, y: f32) -> Option<Position> {
+        square_at(BOARD_ORIGIN.0 + x, BOARD_ORIGIN.1 + y)
+    }
+
+    #[test]
+    fn screen_points_map_to_squares() {
+        assert!(at(0.0, 0.0) == Some(pos(0, 0)));
+        assert!(at(89.9, 89.9) == Some(pos(0, 0)));
+        assert!(at(90.0, 0.0) == Some(pos(0, 1)));
+        assert!(at(5.0 * 90.0 + 45.0, 2.0 * 90.0 + 45.0) == Some(pos(2, 5)));
+        assert!(at(SQUARES_SIZE.0 - 1.0, SQUARES_SIZE.1 - 1.0) == Some(pos(7, 7)));
+    }
+
+    #[test]
+    fn points_outside_the_squares_have_no_square() {
+        assert!(at(-1.0, 10.0).is_none());
+        assert!(at(10.0, -1.0).is_none());
+        assert!(at(SQUARES_SIZE.0, 10.0).is_none());
+        assert!(at(10.0, SQUARES_SIZE.1 + 20.0).is_none());
+        // the frame is part of the board, but not of any square
+        assert!(square_at(BOARD_MARGIN / 2.0, BOARD_SIZE.1 / 2.0).is_none());
+        assert!(square_at(BOARD_SIZE.0 / 2.0, BOARD_SIZE.1 - BOARD_MARGIN / 2.0).is_none());
+        assert!(square_at(BOARD_SIZE.0 - 1.0, BOARD_SIZE.1 - 1.0).is_none());
+    }
+
+    #[test]
+    fn every_edge_and_corner_of_the_squares() {
+        let (right, bottom) = SQUARES_SIZE;
+        let inside = 0.5;
+        let edges = [
+            // top, bottom, left and right edges, half way along
+            ((right / 2.0, inside), (right / 2.0, -inside), pos(0, 4)),
+            (
+                (right / 2.0, bottom - inside),
+                (right / 2.0, bottom),
+                pos(7, 4),
+            ),
+            ((inside, bottom / 2.0), (-inside, bottom / 2.0), pos(4, 0)),
+            (
+                (right - inside, bottom / 2.0),
+                (right, bottom / 2.0),
+                pos(4, 7),
+            ),
+            // the corners, stepping out across one edge or the other
+            ((inside, inside), (-inside, inside), pos(0, 0)),
+            (
+                (right - inside, inside),
+                (right - inside, -inside),
+                pos(0, 7),
+            ),
+            ((inside, bottom - inside), (inside, bottom), pos(7, 0)),
+            (
+                (right - inside, bottom - inside),
+                (right, bottom),
+                pos(7, 7),
+            ),
+        ];
+        for ((x, y), (outside_x, outside_y), square) in edges {
+            assert!(at(x, y) == Some(square));
+            assert!(at(outside_x, outside_y).is_none());
+        }
+
+        // a column past the last isn't taken for a square in the next row, as a wider window
+        // could otherwise make it
+        assert!(at(9.0 * 90.0 + 10.0, 10.0).is_none());
+        // nor is the status strip under the board
+        assert!(square_at(10.0, BOARD_SIZE.1 + 20.0).is_none());
+    }
+
+    #[test]
+    fn square_origins_map_back_to_their_square() {
+        for row in 0..8 {
+            for col in 0..8 {
+                let (x, y) = square_origin(pos(row, col));
+                assert!(square_at(x, y) == Some(pos(row, col)));
+                // and the corner diagonally across is the next square's
+                let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+                let next = Position::new(row + 1, col + 1).ok();
+                assert!(square_at(x + width, y + height) == next);
+            }
+        }
+    }
+
+    #[test]
+    fn flipping_turns_the_board_round() {
+        assert!(turned(pos(0, 0), true) == pos(7, 7));
+        assert!(turned(pos(6, 4), true) == pos(1, 3));
+        assert!(turned(pos(6, 4), false) == pos(6, 4));
+        for row in 0..8 {
+            for col in 0..8 {
+                assert!(turned(turned(pos(row, col), true), true) == pos(row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn square_names_round_trip() {
+        assert_eq!(square_name(pos(0, 0)), "a8");
+        assert_eq!(square_name(pos(7, 7)), "h1");
+        assert_eq!(square_name(pos(4, 4)), "e4");
+        for row in 0..8 {
+            for col in 0..8 {
+                assert!(parse_square(&square_name(pos(row, col))) == Some(pos(row, col)));
+            }
+        }
+        assert!(parse_square("i1").is_none());
+        assert!(parse_square("a9").is_none());
+        assert!(parse_square("e").is_none());
+        assert!(parse_square("e44").is_none());
+    }
+
+    #[test]
+    fn parses_coordinate_moves() {
+        let (from, to, promotion) = parse_move("e2e4").unwrap();
+        assert!(from == pos(6, 4) && to == pos(4, 4) && promotion.is_none());
+
+        let (_, _, promotion) = parse_move("e7e8q").unwrap();
+        assert_eq!(promotion, Some(PieceType::Queen));
+
+        assert!(parse_move("e2").is_none());
+        assert!(parse_move("e2e4k").is_none());
+        assert!(parse_move("e2e9").is_none());
+    }
+
+    #[test]
+    fn pieces_in_the_starting_position() {
+        let board = Game::new().get_board();
+
+        // a knight can jump out, a bishop is blocked in
+        let knight = pseudo_destinations(&board, pos(7, 6));
+        assert_eq!(knight.len(), 2);
+        assert!(knight.contains(&pos(5, 5)) && knight.contains(&pos(5, 7)));
+        assert!(pseudo_destinations(&board, pos(7, 5)).is_empty());
+        assert_eq!(pseudo_destinations(&board, pos(6, 4)).len(), 2);
+        assert!(pseudo_destinations(&board, pos(4, 4)).is_empty());
+
+        assert!(!king_attacked(&board, Colour::White));
+        assert!(!king_attacked(&board, Colour::Black));
+    }
+
+    #[test]
+    fn position_keys_match_after_knights_return() {
+        let mut game = Game::new();
+        let start = position_key(&game);
+
+        for (from, to) in [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")] {
+            let from = parse_square(from).unwrap();
+            let to = parse_square(to).unwrap();
+            game.make_move_pos(from, to).unwrap();
+        }
+
+        assert_eq!(position_key(&game), start);
+    }
+
+    #[test]
+    fn attacks_include_defended_squares_and_pawn_diagonals() {
+        let mut game = Game::new();
+        for text in ["e2e4", "d7d5"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            game.make_move_pos(from, to).unwrap();
+        }
+        let board = game.get_board();
+
+        // the e4 pawn attacks both diagonals, one empty, but not the square in front of it
+        let pawn = attacks(&board, pos(4, 4));
+        assert_eq!(pawn.len(), 2);
+        assert!(pawn.contains(&pos(3, 3)) && pawn.contains(&pos(3, 5)));
+        // a pawn on the edge only has one diagonal
+        assert!(attacks(&board, pos(6, 0)) == vec![pos(5, 1)]);
+
+        // the queen defends the pieces around her, and sees along the opened diagonal
+        let queen = attacks(&board, pos(7, 3));
+        assert!(queen.contains(&pos(7, 4)) && queen.contains(&pos(6, 3)));
+        assert!(queen.contains(&pos(3, 7)) && !queen.contains(&pos(2, 7)));
+
+        // White attacks the whole third rank, and d5 with the pawn, but not e5 in front of it
+        let attacked = attacked_squares(&board, Colour::White);
+        assert!((0..8).all(|col| attacked.contains(&pos(5, col))));
+        assert!(attacked.contains(&pos(3, 3)) && !attacked.contains(&pos(3, 4)));
+        // moves still leave out squares held by their own side
+        assert!(!pseudo_destinations(&board, pos(7, 3)).contains(&pos(7, 4)));
+        assert!(pseudo_destinations(&board, pos(4, 4)).contains(&pos(3, 3)));
+    }
+
+    #[test]
+    fn hanging_pieces_are_attacked_and_undefended() {
+        // the rooks attack the bishop on a4 and the knight on d5, which only the pawn defends
+        let board = parse_placement("r2rk3/8/8/3N4/B3P3/8/8/4K3").unwrap();
+        assert!(hanging_pieces(&board, Colour::White) == vec![pos(4, 0)]);
+        assert!(hanging_pieces(&board, Colour::Black).is_empty());
+
+        // the rook defends the knight on d3 until its own pawn stands in the way
+        let board = parse_placement("4k3/8/8/1b6/8/3N4/8/3RK3").unwrap();
+        assert!(hanging_pieces(&board, Colour::White).is_empty());
+        let board = parse_placement("4k3/8/8/1b6/8/3N4/3P4/3RK3").unwrap();
+        assert!(hanging_pieces(&board, Colour::White) == vec![pos(5, 3)]);
+
+        // the king defends the pieces next to it, but is never hanging itself
+        let board = parse_placement("4k3/8/8/8/8/8/3q4/4K3").unwrap();
+        assert!(hanging_pieces(&board, Colour::White).is_empty());
+        assert!(hanging_pieces(&board, Colour::Black) == vec![pos(6, 3)]);
+        let board = parse_placement("4k3/8/8/3q4/8/8/8/4K2R").unwrap();
+        assert!(hanging_pieces(&board, Colour::White) == vec![pos(7, 7)]);
+    }
+
+    #[test]
+    fn tooltips_say_how_safe_a_piece_is() {
+        let board = parse_placement("r2rk3/8/8/3N4/B3P3/8/8/4K3").unwrap();
+        let cover = cover(&board);
+        let tooltip = |at: Position| {
+            let piece = board[at.row * 8 + at.col].unwrap();
+            piece_tooltip(piece, safety(&board, &cover, at))
+        };
+        assert_eq!(tooltip(pos(4, 0)), "White bishop, attacked and undefended");
+        assert_eq!(tooltip(pos(3, 3)), "White knight, attacked and defended");
+        assert_eq!(tooltip(pos(0, 0)), "Black rook, defended");
+        assert_eq!(tooltip(pos(4, 4)), "White pawn, not attacked or defended");
+        assert_eq!(tooltip(pos(7, 4)), "White king, not in check");
+        assert!(safety(&board, &cover, pos(5, 5)).is_none());
+
+        // without study aids, only the piece is named
+        let knight = board[3 * 8 + 3].unwrap();
+        assert_eq!(piece_tooltip(knight, None), "White knight");
+    }
+}
@@ -0,0 +1,200 @@
+/**
+ * Local computer opponents.
+ *
+ * An opponent runs on its own thread and talks to the GUI over the same pair of channels as the
+ * network thread, sending and receiving the same messages a server would relay. That way the GUI
+ * doesn't need to know whether it's playing someone online or a bot; only the function that
+ * picks the bot's moves differs.
+ */
+use crate::net::CHANNEL_CAPACITY;
+use crate::protocol::Message;
+use chess_template::{Colour, Game, GameState, Position};
+use rand::prelude::*;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the bot waits before moving, so its replies don't appear instantly.
+const MOVE_DELAY: Duration = Duration::from_millis(600);
+
+/// Something that picks the bot's moves.
+pub trait MovePicker: Send {
+    /// Picks a move for the side to move, or `None` if there are none. Pickers that search
+    /// call `progress` with each depth they start on, so the GUI can show how far they've got.
+    fn pick(&mut self, game: &Game, progress: &mut dyn FnMut(u32)) -> Option<(Position, Position)>;
+
+    /// Changes how strongly the picker plays, from the next move on. Ignored by pickers that
+    /// don't have a strength.
+    fn set_difficulty(&mut self, _level: u8) {}
+}
+
+/// Plays a uniformly random legal move, for beginners learning how the pieces move.
+pub struct RandomMover {
+    rng: StdRng, // picks the moves, so a seed replays the same game
+}
+
+impl RandomMover {
+    pub fn new(rng: StdRng) -> RandomMover {
+        RandomMover { rng }
+    }
+}
+
+impl MovePicker for RandomMover {
+    fn pick(
+        &mut self,
+        game: &Game,
+        _progress: &mut dyn FnMut(u32),
+    ) -> Option<(Position, Position)> {
+        random_move(game, &mut self.rng)
+    }
+}
+
+/// Every legal move for the side to move.
+pub fn legal_moves(game: &Game) -> Vec<(Position, Position)> {
+    let colour = game.get_active_colour();
+    let board = game.get_board();
+
+    (0..64)
+        .filter(|&idx| board[idx].is_some_and(|p| p.colour == colour))
+        .flat_map(|idx| {
+            let from = Position::new(idx / 8, idx % 8).unwrap();
+            game.get_possible_moves(from, 0)
+                .into_iter()
+                .map(move |to| (from, to))
+        })
+        .collect()
+}
+
+/// Picks a uniformly random legal move, or `None` if there are none.
+pub fn random_move(game: &Game, rng: &mut StdRng) -> Option<(Position, Position)> {
+    legal_moves(game).choose(rng).copied()
+}
+
+/// Starts a bot playing `colour` in the given room, choosing its moves with `picker`.
+/// Returns the channels to talk to it with, just like `online_setup`.
+///
+/// Besides the usual moves and resets, the bot understands `{room} difficulty {level}` and
+/// `{room} takeback {counter}` from the GUI, answering the takeback with the same once it has
+/// taken the moves back, and sends `{room} thinking {depth}` while it searches.
+pub fn bot_setup<P>(
+    room_name: &str,
+    colour: Colour,
+    mut picker: P,
+) -> (SyncSender<String>, Receiver<String>)
+where
+    P: MovePicker + 'static,
+{
+    // create channel for communication between threads, from main thread to bot thread
+    let (sender, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+
+    // create channel for communication between threads, from bot thread to main thread
+    let (to_mainthread_sender, to_mainthread_receiver) =
+        mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+
+    let room_name = room_name.to_string();
+
+    thread::spawn(move || {
+        let mut game = Game::new();
+        let mut counter: u32 = 1;
+        // every move so far, for taking some back
+        let mut moves: Vec<(Position, Position)> = Vec::new();
+        // when it became the bot's turn, so it can wait a while before replying
+        let mut thinking_since: Option<Instant> = None;
+
+        loop {
+            /* Apply whatever the player has sent. */
+            loop {
+                match receiver.try_recv() {
+                    Ok(msg) => match Message::parse(&msg) {
+                        Ok(msg) if msg.room() != room_name => (),
+                        Ok(Message::Reset { .. }) => {
+                            game = Game::new();
+                            counter = 1;
+                            moves.clear();
+                            thinking_since = None;
+                        }
+                        // replay the moves up to the counter, and say so once done
+                        Ok(Message::Takeback { counter: back, .. }) if back >= 1 => {
+                            moves.truncate(back as usize - 1);
+                            game = Game::new();
+                            for &(from, to) in &moves {
+                                let _ = game.make_move_pos(from, to);
+                            }
+                            counter = moves.len() as u32 + 1;
+                            thinking_since = None;
+                            let msg = Message::Takeback {
+                                room: room_name.clone(),
+                                counter,
+                            };
+                            if to_mainthread_sender.send(msg.to_string()).is_err() {
+                                return;
+                            }
+                        }
+                        Ok(Message::Move {
+                            counter: turn,
+                            from,
+                            to,
+                            ..
+                        }) if turn == counter + 1 => {
+                            let applied = game.make_move_pos(from, to).is_ok();
+                            if applied {
+                                counter = turn;
+                                moves.push((from, to));
+                            }
+                        }
+                        Ok(Message::Difficulty { level, .. }) => picker.set_difficulty(level),
+                        _ => (),
+                    },
+                    // nothing more from the player
+                    Err(TryRecvError::Empty) => break,
+                    // channel has been disconnected (main thread has terminated)
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+
+            /* Move if it's our turn and we've waited long enough. */
+            let our_turn =
+                game.get_active_colour() == colour && game.get_game_state() != GameState::GameOver;
+
+            if !our_turn {
+                thinking_since = None;
+            } else {
+                let started = *thinking_since.get_or_insert_with(Instant::now);
+
+                if started.elapsed() >= MOVE_DELAY {
+                    thinking_since = None;
+
+                    // with no legal moves the game is over, so just keep waiting for a reset
+                    let mut progress = |depth: u32| {
+                        let msg = Message::Thinking {
+                            room: room_name.clone(),
+                            depth,
+                        };
+                        let _ = to_mainthread_sender.send(msg.to_string());
+                    };
+
+                    if let Some((from, to)) = picker.pick(&game, &mut progress) {
+                        if game.make_move_pos(from, to).is_ok() {
+                            counter += 1;
+                            moves.push((from, to));
+
+                            let msg = Message::Move {
+                                room: room_name.clone(),
+                                counter,
+                                from,
+                                to,
+                            };
+                            if to_mainthread_sender.send(msg.to_string()).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+
+            thread::sleep(Duration::from_millis(30));
+        }
+    });
+
+    (sender, to_mainthread_receiver)
+}
@@ -0,0 +1,258 @@
+/**
+ * A bad network on purpose, for testing: `--net-chaos delay=200ms,jitter=100ms,loss=5%` puts a
+ * layer between the GUI and the opponent that delays, reorders, duplicates and drops messages.
+ *
+ * Like the transcript recorder, it sits on the channels every session talks through, so it works
+ * the same against the server, the bots and hotseat. A move that comes twice or late is dropped by
+ * its counter, but the game has no retransmission, so a lost one shows up as a desync, which is
+ * what this is for finding. With `seed=` in the settings, or `--seed`, the same messages are
+ * treated the same way every run.
+ */
+use crate::net::CHANNEL_CAPACITY;
+use crate::session::Session;
+use log::{debug, info};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How badly to treat messages, in both directions.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Chaos {
+    pub delay: Duration,   // added to every message
+    pub jitter: Duration,  // up to this much more or less delay, picked for each message
+    pub loss: f64,         // chance that a message is dropped, from 0 to 1
+    pub duplicate: f64,    // chance that a message is delivered twice
+    pub reorder: f64,      // chance that a message may overtake earlier ones
+    pub seed: Option<u64>, // makes the choices above the same every run
+}
+
+impl Chaos {
+    /// Parses settings like `delay=200ms,jitter=100ms,loss=5%,duplicate=1%,reorder=2%,seed=7`.
+    /// Settings left out are off.
+    pub fn parse(text: &str) -> Result<Chaos, String> {
+        let mut chaos = Chaos::default();
+        for setting in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value but got {:?}", setting))?;
+            match key.trim() {
+                "delay" => chaos.delay = parse_duration(value)?,
+                "jitter" => chaos.jitter = parse_duration(value)?,
+                "loss" => chaos.loss = parse_percentage(value)?,
+                "duplicate" => chaos.duplicate = parse_percentage(value)?,
+                "reorder" => chaos.reorder = parse_percentage(value)?,
+                "seed" => {
+                    let seed = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("bad seed {:?}", value))?;
+                    chaos.seed = Some(seed);
+                }
+                key => return Err(format!("unknown setting {:?}", key)),
+            }
+        }
+        Ok(chaos)
+    }
+
+    /// How long after it was sent each copy of a message arrives: none if it's lost, two if
+    /// it's duplicated.
+    fn delays(&self, rng: &mut StdRng) -> Vec<Duration> {
+        if rng.gen::<f64>() < self.loss {
+            return vec![];
+        }
+        let copies = if rng.gen::<f64>() < self.duplicate {
+            2
+        } else {
+            1
+        };
+        (0..copies)
+            .map(|_| {
+                let jitter = self.jitter.as_secs_f64();
+                let offset = if jitter > 0.0 {
+                    rng.gen_range(-jitter..=jitter)
+                } else {
+                    0.0
+                };
+                Duration::from_secs_f64((self.delay.as_secs_f64() + offset).max(0.0))
+            })
+            .collect()
+    }
+}
+
+/// Parses a duration like `200ms` or `1s`.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let (number, scale) = if let Some(ms) = text.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = text.strip_suffix('s') {
+        (s, 1.0)
+    } else {
+        return Err(format!("expected a duration like 200ms but got {:?}", text));
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("bad duration {:?}", text))?;
+    if number < 0.0 {
+        return Err(format!("negative duration {:?}", text));
+    }
+    Ok(Duration::from_secs_f64(number * scale))
+}
+
+/// Parses a percentage like `5%`, as a fraction.
+fn parse_percentage(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let number: f64 = text
+        .strip_suffix('%')
+        .and_then(|number| number.trim().parse().ok())
+        .ok_or_else(|| format!("expected a percentage like 5% but got {:?}", text))?;
+    if !(0.0..=100.0).contains(&number) {
+        return Err(format!("{:?} isn't between 0% and 100%", text));
+    }
+    Ok(number / 100.0)
+}
+
+/// Treats a session's messages badly in both directions. Returns a session for the GUI that
+/// talks to the same opponent. The seed comes from `rng` unless the settings give one.
+pub fn wrap(session: Session, chaos: Chaos, rng: &mut StdRng) -> Session {
+    let seed = chaos.seed.unwrap_or_else(|| rng.gen());
+    info!("Network chaos {:?}, seed {}", chaos, seed);
+
+    let (sender, from_gui) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+    let outgoing = StdRng::seed_from_u64(seed);
+    let to_opponent = session.sender;
+    thread::spawn(move || relay(from_gui, to_opponent, chaos, outgoing));
+
+    // the other direction gets its own choices, so they don't depend on how many messages were sent
+    let (to_gui, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+    let incoming = StdRng::seed_from_u64(seed.wrapping_add(1));
+    let from_opponent = session.receiver;
+    thread::spawn(move || relay(from_opponent, to_gui, chaos, incoming));
+
+    Session {
+        sender,
+        receiver,
+        ..session
+    }
+}
+
+/// Passes messages from `source` to `sink` late, or twice, or not at all. Messages that aren't
+/// picked to be reordered keep their order, as they would on a TCP connection. Stops once both
+/// ends are gone and everything still in flight has been delivered.
+fn relay(source: Receiver<String>, sink: SyncSender<String>, chaos: Chaos, mut rng: StdRng) {
+    // messages in flight, soonest first. The number keeps messages due at once in order
+    let mut in_flight = BinaryHeap::<Reverse<(Instant, u64, String)>>::new();
+    let mut sent: u64 = 0;
+    let mut last_due = Instant::now();
+    let mut open = true;
+
+    loop {
+        let now = Instant::now();
+        while in_flight
+            .peek()
+            .is_some_and(|Reverse((due, _, _))| *due <= now)
+        {
+            if let Some(Reverse((_, _, msg))) = in_flight.pop() {
+                if sink.send(msg).is_err() {
+                    return;
+                }
+            }
+        }
+
+        let wait = in_flight
+            .peek()
+            .map(|Reverse((due, _, _))| due.saturating_duration_since(now));
+        let received = match (open, wait) {
+            (true, Some(wait)) => source.recv_timeout(wait),
+            (true, None) => source.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            (false, Some(wait)) => {
+                thread::sleep(wait);
+                continue;
+            }
+            (false, None) => return,
+        };
+
+        match received {
+            Ok(msg) => {
+                let delays = chaos.delays(&mut rng);
+                if delays.is_empty() {
+                    debug!("chaos dropped {:?}", msg.trim_end());
+                }
+                let reorder = rng.gen::<f64>() < chaos.reorder;
+                for delay in delays {
+                    let mut due = Instant::now() + delay;
+                    if !reorder {
+                        due = due.max(last_due);
+                    }
+                    last_due = last_due.max(due);
+                    in_flight.push(Reverse((due, sent, msg.clone())));
+                    sent += 1;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => open = false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_parse() {
+        let chaos = Chaos::parse("delay=200ms, jitter=0.1s,loss=5%,duplicate=1%,seed=7").unwrap();
+        assert_eq!(chaos.delay, Duration::from_millis(200));
+        assert_eq!(chaos.jitter, Duration::from_millis(100));
+        assert_eq!(chaos.loss, 0.05);
+        assert_eq!(chaos.duplicate, 0.01);
+        assert_eq!(chaos.reorder, 0.0);
+        assert_eq!(chaos.seed, Some(7));
+        assert_eq!(Chaos::parse("").unwrap(), Chaos::default());
+
+        assert!(Chaos::parse("delay=200").is_err());
+        assert!(Chaos::parse("loss=150%").is_err());
+        assert!(Chaos::parse("speed=fast").is_err());
+        assert!(Chaos::parse("loss").is_err());
+    }
+
+    #[test]
+    fn the_same_seed_treats_messages_the_same() {
+        let chaos = Chaos::parse("delay=50ms,jitter=40ms,loss=30%,duplicate=30%").unwrap();
+        let plan = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..50).map(|_| chaos.delays(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(plan(1), plan(1));
+        assert_ne!(plan(1), plan(2));
+
+        let plan = plan(1);
+        assert!(plan.iter().any(Vec::is_empty));
+        assert!(plan.iter().any(|copies| copies.len() == 2));
+        for delay in plan.iter().flatten() {
+            assert!(*delay >= Duration::from_millis(10) && *delay <= Duration::from_millis(90));
+        }
+    }
+
+    #[test]
+    fn messages_keep_their_order_unless_reordered() {
+        let (sender, source) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (sink, received) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let chaos = Chaos::parse("delay=5ms,jitter=5ms").unwrap();
+        thread::spawn(move || relay(source, sink, chaos, StdRng::seed_from_u64(3)));
+
+        for i in 0..20 {
+            sender.send(i.to_string()).unwrap();
+        }
+        drop(sender);
+
+        // everything in flight still arrives after the sender is gone
+        let order = received.iter().collect::<Vec<String>>();
+        let expected = (0..20).map(|i| i.to_string()).collect::<Vec<String>>();
+        assert_eq!(order, expected);
+    }
+}
@@ -0,0 +1,322 @@
+/**
+ * Chat with the opponent in an online game. Messages go through the server like everything
+ * else, so each has to fit in a frame along with the room and who sent it, which leaves room for
+ * a few words. The log keeps them with the time they were sent or arrived, and notices of what
+ * happened in between, like a draw offer or the opponent going quiet. What's typed is sent a few
+ * messages every few seconds at most, so it can't flood the relay; the rest wait their turn
+ * rather than being dropped.
+ *
+ * Alt+1 to Alt+4 send a preset instead, for when there's no time to type. They go as any other
+ * message, so older clients see them in the log; a client that knows the text shows it as a
+ * reaction beside the opponent's name.
+ */
+use crate::protocol::{Message, INSTANCE_TAG_SIZE, MSG_SIZE};
+use chess_template::Colour;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Most messages sent within `RATE_WINDOW` of each other.
+pub const RATE_LIMIT: usize = 3;
+pub const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Lines kept in the log, the oldest going first.
+const KEPT_LINES: usize = 100;
+
+/// The quick-chat presets until the config file says otherwise.
+pub const DEFAULT_PRESETS: [&str; 4] = ["Good game", "Nice move", "Oops", "Rematch?"];
+
+/// Who a line of the log is from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Speaker {
+    Us,
+    Them,
+    /// Something that happened in the game, not a message.
+    Notice,
+}
+
+/// A line of the log.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Line {
+    pub speaker: Speaker,
+    pub text: String,
+    pub at: SystemTime, // when it was sent or arrived, by this computer's clock
+}
+
+/// The log, what's waiting to be sent, and the presets to send instead.
+#[derive(Default)]
+pub struct Chat {
+    lines: VecDeque<Line>,
+    queued: VecDeque<String>, // typed, but held back by the rate limit
+    sent: VecDeque<Instant>,  // when the last few messages went out
+    presets: Vec<String>,     // sent with Alt and a number, from the config
+    quiet: bool,              // noted the opponent going quiet, until they're heard from
+}
+
+/// What a message from the opponent was to the chat.
+pub enum Event {
+    /// Not a chat message from the opponent.
+    Other(Message),
+    /// One of the presets, to show beside their name.
+    Reaction(String),
+    /// Anything else they typed.
+    Said(Colour, String),
+}
+
+impl Chat {
+    /// An empty log, with the presets from the config.
+    pub fn new(presets: Vec<String>) -> Chat {
+        Chat {
+            presets,
+            ..Chat::default()
+        }
+    }
+
+    /// Logs a chat message from the opponent, arriving `at`, when we play `ours`. Our own come
+    /// back from the server, and were logged when they were sent.
+    pub fn handle(&mut self, msg: Message, ours: Colour, at: SystemTime) -> Event {
+        match msg {
+            Message::Chat { colour, text, .. } if colour != ours => {
+                self.received(&text, at);
+                if is_preset(&text, &self.presets) {
+                    Event::Reaction(text)
+                } else {
+                    Event::Said(colour, text)
+                }
+            }
+            msg => Event::Other(msg),
+        }
+    }
+
+    /// The preset for Alt and the number after `index`, if there's one.
+    pub fn preset(&self, index: usize) -> Option<&str> {
+        self.presets.get(index).map(String::as_str)
+    }
+
+    /// Whether the opponent has just gone quiet, given whether they're quiet now, so it's noted
+    /// once each time.
+    pub fn went_quiet(&mut self, quiet: bool) -> bool {
+        let news = quiet && !self.quiet;
+        self.quiet = quiet;
+        news
+    }
+
+    /// Logs a message from the opponent, arriving `at`.
+    pub fn received(&mut self, text: &str, at: SystemTime) {
+        self.log(Speaker::Them, text.to_string(), at);
+    }
+
+    /// Logs something that happened in the game `at`, e.g. "Black offered a draw".
+    pub fn notice(&mut self, text: impl Into<String>, at: SystemTime) {
+        self.log(Speaker::Notice, text.into(), at);
+    }
+
+    fn log(&mut self, speaker: Speaker, text: String, at: SystemTime) {
+        if self.lines.len() == KEPT_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(Line { speaker, text, at });
+    }
+
+    /// Queues a message we typed, to go out once the rate limit allows. A blank one is dropped.
+    pub fn queue(&mut self, text: &str) {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+            self.queued.push_back(text);
+        }
+    }
+
+    /// How many messages are waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Takes the queued messages the rate limit lets through `now`, oldest first, and logs them
+    /// as sent `at`.
+    pub fn send_due(&mut self, now: Instant, at: SystemTime) -> Vec<String> {
+        while self
+            .sent
+            .front()
+            .is_some_and(|&sent| now.saturating_duration_since(sent) >= RATE_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+        let mut due = Vec::new();
+        while self.sent.len() < RATE_LIMIT {
+            let text = match self.queued.pop_front() {
+                Some(text) => text,
+                None => break,
+            };
+            self.sent.push_back(now);
+            self.log(Speaker::Us, text.clone(), at);
+            due.push(text);
+        }
+        due
+    }
+
+    /// The log, oldest first.
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &Line> + ExactSizeIterator {
+        self.lines.iter()
+    }
+}
+
+/// The presets as they're kept in the config file.
+pub fn default_presets() -> Vec<String> {
+    DEFAULT_PRESETS
+        .iter()
+        .map(|preset| preset.to_string())
+        .collect()
+}
+
+/// Whether a message from the opponent is a preset: one of `presets` or one of the defaults, which
+/// they may not have changed. Case and spacing don't matter.
+pub fn is_preset(text: &str, presets: &[String]) -> bool {
+    let same = |preset: &str| preset.trim().eq_ignore_ascii_case(text.trim());
+    presets.iter().any(|preset| same(preset)) || DEFAULT_PRESETS.into_iter().any(same)
+}
+
+/// The time of day of `at` in UTC, e.g. "14:05".
+pub fn clock(at: SystemTime) -> String {
+    let secs = at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    format!("{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60)
+}
+
+/// How many bytes of text fit in a chat message in `room`, after the room, the kind, the colour,
+/// the spaces between them and at the end, and the tag naming the sender.
+pub fn room_for_text(room: &str) -> usize {
+    MSG_SIZE.saturating_sub(format!("{} chat white  ", room).len() + INSTANCE_TAG_SIZE)
+}
+
+/// `text` cut short to fit in a chat message in `room`, without splitting a character.
+pub fn fit(room: &str, text: &str) -> String {
+    let room = room_for_text(room);
+    let mut end = text.len().min(room);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{decode, encode, tag, untag, MAX_ROOM_LENGTH};
+
+    #[test]
+    fn messages_past_the_limit_wait_their_turn() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut chat = Chat::default();
+        for text in ["hej", "  ", "lycka  till", "gg", "igen?"] {
+            chat.queue(text);
+        }
+        assert_eq!(chat.queued(), 4);
+        assert_eq!(
+            chat.send_due(at(0), UNIX_EPOCH),
+            ["hej", "lycka till", "gg"]
+        );
+        assert!(chat.send_due(at(4), UNIX_EPOCH).is_empty());
+        assert_eq!(chat.queued(), 1);
+        assert_eq!(chat.send_due(at(5), UNIX_EPOCH), ["igen?"]);
+
+        // two more fit in the window of the last one
+        chat.queue("a");
+        chat.queue("b");
+        chat.queue("c");
+        assert_eq!(chat.send_due(at(6), UNIX_EPOCH), ["a", "b"]);
+        assert_eq!(chat.send_due(at(10), UNIX_EPOCH), ["c"]);
+
+        let sent: Vec<&str> = chat.lines().map(|line| line.text.as_str()).collect();
+        assert_eq!(sent, ["hej", "lycka till", "gg", "igen?", "a", "b", "c"]);
+        assert!(chat.lines().all(|line| line.speaker == Speaker::Us));
+    }
+
+    #[test]
+    fn notices_go_in_the_log_with_the_time() {
+        let at = UNIX_EPOCH + Duration::from_secs(20_000 * 86_400 + 14 * 3600 + 5 * 60 + 59);
+        assert_eq!(clock(at), "14:05");
+        assert_eq!(clock(UNIX_EPOCH), "00:00");
+
+        let mut chat = Chat::default();
+        chat.notice("Black connected", at);
+        chat.received("hej!", at);
+        chat.notice("Black offered a draw", at);
+        let speakers: Vec<Speaker> = chat.lines().map(|line| line.speaker).collect();
+        assert_eq!(speakers, [Speaker::Notice, Speaker::Them, Speaker::Notice]);
+        assert_eq!(chat.lines().next().unwrap().at, at);
+
+        for i in 0..KEPT_LINES {
+            chat.received(&i.to_string(), at);
+        }
+        assert_eq!(chat.lines().len(), KEPT_LINES);
+        assert_eq!(chat.lines().next().unwrap().text, "0");
+    }
+
+    #[test]
+    fn long_messages_are_cut_to_fit_the_frame() {
+        let room = "fredagsschack";
+        let long = "ä".repeat(40);
+        let text = fit(room, &long);
+        assert!(text.len() <= room_for_text(room));
+        assert!(long.starts_with(&text));
+        let msg = Message::Chat {
+            room: room.to_string(),
+            colour: Colour::White,
+            text: text.clone(),
+        }
+        .to_string();
+        // with room left for the tag
+        let tagged = tag(&msg, u32::MAX);
+        assert!(tagged.len() <= MSG_SIZE);
+        let received = decode(&encode(&tagged)).unwrap();
+        assert_eq!(untag(&received).0, Some(u32::MAX));
+        assert_eq!(Message::parse(&received).unwrap().to_string(), msg);
+        assert_eq!(fit(room, "hej då"), "hej då");
+        assert_eq!(fit(&"x".repeat(70), "hej"), "");
+    }
+
+    #[test]
+    fn only_the_opponents_messages_are_logged_on_arrival() {
+        let mut chat = Chat::new(vec!["Bra drag!".to_string()]);
+        let said = |colour, text: &str| Message::Chat {
+            room: "lobby".to_string(),
+            colour,
+            text: text.to_string(),
+        };
+        assert!(matches!(
+            chat.handle(said(Colour::White, "hej"), Colour::White, UNIX_EPOCH),
+            Event::Other(_)
+        ));
+        assert!(matches!(
+            chat.handle(said(Colour::Black, "bra drag!"), Colour::White, UNIX_EPOCH),
+            Event::Reaction(text) if text == "bra drag!"
+        ));
+        assert!(matches!(
+            chat.handle(said(Colour::Black, "hej"), Colour::White, UNIX_EPOCH),
+            Event::Said(Colour::Black, text) if text == "hej"
+        ));
+        assert_eq!(chat.lines().len(), 2);
+        assert_eq!(chat.preset(0), Some("Bra drag!"));
+        assert_eq!(chat.preset(1), None);
+
+        assert!(chat.went_quiet(true));
+        assert!(!chat.went_quiet(true));
+        assert!(!chat.went_quiet(false));
+        assert!(chat.went_quiet(true));
+    }
+
+    #[test]
+    fn presets_are_known_by_their_text() {
+        let ours = vec!["Bra drag!".to_string()];
+        assert!(is_preset("bra drag!", &ours));
+        assert!(is_preset("Good game ", &ours));
+        assert!(!is_preset("Good game, again?", &ours));
+        assert!(!is_preset("", &default_presets()));
+        // each fits in a message even in the longest room name
+        let room = "x".repeat(MAX_ROOM_LENGTH);
+        assert!(default_presets()
+            .iter()
+            .all(|preset| fit(&room, preset) == *preset));
+    }
+}
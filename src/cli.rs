@@ -0,0 +1,194 @@
+/**
+ * Command-line arguments, and prompts for anything they leave out.
+ */
+use crate::chaos::Chaos;
+use crate::headless::BotKind;
+use crate::invite::Invite;
+use crate::protocol;
+use clap::Parser;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+/// A bad chess GUI. Play online through a relay server, against the computer, or hotseat.
+#[derive(Parser, Clone, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Invite to a game, like schack://192.168.1.10:6000/room, as copied from the waiting
+    /// screen. Connects to its server and room right away
+    #[arg(value_name = "INVITE", value_parser = Invite::parse, conflicts_with_all = ["server", "room", "offline", "replay_net"])]
+    pub invite: Option<Invite>,
+
+    /// Server IP and port, e.g. 127.0.0.1:6000. Use "bot" to practice against a random mover,
+    /// or "ai" to play the computer
+    #[arg(long)]
+    pub server: Option<String>,
+
+    /// Room to join on the server; anything without spaces, up to 20 characters
+    #[arg(long, value_parser = parse_room)]
+    pub room: Option<String>,
+
+    /// Display name, shown in the window title
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Play without a window, as a bot for testing the server: "random" for random moves, or
+    /// "ai:LEVEL" for the computer at that difficulty. Plays one game in --room, then exits
+    /// with 0 if it won, 3 on a draw, 4 if it lost, or 1 if the game couldn't be played
+    #[arg(long, value_name = "KIND", value_parser = BotKind::parse, requires = "room", conflicts_with_all = ["offline", "replay_net"])]
+    pub bot: Option<BotKind>,
+
+    /// Open the window without a title bar or border, for this run. Also a setting
+    #[arg(long)]
+    pub borderless: bool,
+
+    /// Keep the window above other windows, for this run. Also a setting
+    #[arg(long)]
+    pub always_on_top: bool,
+
+    /// Show the game full screen as the board alone, for streaming or a big screen: high
+    /// contrast squares, and the players and time in a bar that hides itself
+    #[arg(long)]
+    pub kiosk: bool,
+
+    /// Play both colours on this computer, without a server
+    #[arg(long, conflicts_with_all = ["server", "room"])]
+    pub offline: bool,
+
+    /// Ask for missing connection details on the terminal instead of showing the connection
+    /// screen
+    #[arg(long)]
+    pub headless_prompt: bool,
+
+    /// Settings file to use instead of config.toml in the platform's config directory
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Also append log messages to this file, e.g. to compare what two players' games saw.
+    /// Log levels are set with RUST_LOG
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Start from a position in FEN (not supported by the chess library yet)
+    #[arg(long)]
+    pub fen: Option<String>,
+
+    /// Open the window with its top-left corner here, e.g. 0,0, instead of where it was last
+    /// closed. Handy for placing two clients side by side
+    #[arg(long, value_name = "X,Y", value_parser = parse_window_pos)]
+    pub window_pos: Option<(i32, i32)>,
+
+    /// Append every message sent and received during games to this file, for reproducing
+    /// problems with --replay-net
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Append every move to this file as it's played, with the position after it in FEN, for
+    /// overlays and bots to follow along
+    #[arg(long, value_name = "FILE")]
+    pub movelog: Option<PathBuf>,
+
+    /// Instead of connecting anywhere, play back the messages received in a file written with
+    /// --record, at the pace they arrived
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["server", "room", "offline"])]
+    pub replay_net: Option<PathBuf>,
+
+    /// With --replay-net, play the messages back as fast as possible
+    #[arg(long, requires = "replay_net")]
+    pub fast: bool,
+
+    /// Instead of connecting anywhere, play on from a snapshot saved with F12 for a bug report,
+    /// with both colours played on this computer. Its settings aren't used
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["server", "room", "offline", "replay_net"])]
+    pub load_snapshot: Option<PathBuf>,
+
+    /// Simulate a bad connection for testing, e.g. delay=200ms,jitter=100ms,loss=5%. Also takes
+    /// duplicate=, reorder= and seed=
+    #[arg(long, value_name = "SPEC", value_parser = Chaos::parse)]
+    pub net_chaos: Option<Chaos>,
+
+    /// Seed for every random choice, so a run can be repeated: the number that decides colours
+    /// online, the colour against the computer, the random mover's moves and --net-chaos. Give
+    /// two clients in the same room different seeds
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+/// Checks a room name is one that can be played in.
+fn parse_room(text: &str) -> Result<String, String> {
+    protocol::check_room(text)?;
+    Ok(text.to_string())
+}
+
+/// Parses a window position given as "x,y".
+fn parse_window_pos(text: &str) -> Result<(i32, i32), String> {
+    let (x, y) = text
+        .split_once(',')
+        .ok_or_else(|| format!("expected x,y but got {:?}", text))?;
+    let coordinate = |value: &str| {
+        value
+            .trim()
+            .parse::<i32>()
+            .map_err(|err| format!("bad coordinate {:?}: {}", value, err))
+    };
+    Ok((coordinate(x)?, coordinate(y)?))
+}
+
+/// Asks a question on the terminal and returns the trimmed answer, or `None` if there's no
+/// terminal to ask on, e.g. when started from a desktop launcher, or it can't be read.
+pub fn prompt(question: &str) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+
+    println!("{}", question);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    Some(answer.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_invite_is_the_first_argument() {
+        let args = Args::try_parse_from(["schack", "schack://10.0.0.1:6000/lunch"]).unwrap();
+        assert_eq!(
+            args.invite.map(|invite| invite.room).as_deref(),
+            Some("lunch")
+        );
+
+        assert!(Args::try_parse_from(["schack", "lunch"]).is_err());
+        assert!(
+            Args::try_parse_from(["schack", "schack://10.0.0.1:6000/lunch", "--room", "x"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn a_bot_needs_a_room() {
+        let args =
+            Args::try_parse_from(["schack", "--bot", "ai:4", "--server", "x", "--room", "y"])
+                .unwrap();
+        assert_eq!(args.bot, Some(BotKind::Ai(4)));
+        assert!(Args::try_parse_from(["schack", "--bot", "random"]).is_err());
+        assert!(Args::try_parse_from(["schack", "--bot", "ai:0", "--room", "y"]).is_err());
+    }
+
+    #[test]
+    fn rooms_have_room_for_the_tag() {
+        let longest = "x".repeat(protocol::MAX_ROOM_LENGTH);
+        let args = Args::try_parse_from(["schack", "--room", &longest]).unwrap();
+        assert_eq!(args.room, Some(longest.clone()));
+        assert!(Args::try_parse_from(["schack", "--room", &format!("{}x", longest)]).is_err());
+        assert!(Args::try_parse_from(["schack", "--room", "two words"]).is_err());
+    }
+
+    #[test]
+    fn window_positions_parse() {
+        assert_eq!(parse_window_pos("100,200"), Ok((100, 200)));
+        assert_eq!(parse_window_pos("-1920, 0"), Ok((-1920, 0)));
+        assert!(parse_window_pos("100").is_err());
+        assert!(parse_window_pos("100,top").is_err());
+    }
+}
@@ -0,0 +1,499 @@
+/**
+ * Settings kept between runs, in `config.toml` in the platform's config directory.
+ *
+ * Values given on the command line win over the file, and anything missing from both falls
+ * back to a built-in default (or a prompt, for the connection details).
+ */
+use crate::ai;
+use crate::annotate::Thresholds;
+use crate::announce::AnnounceTo;
+use crate::app::{Blindfold, HangingPieces};
+use crate::chat;
+use crate::keys::Keymap;
+use crate::protocol;
+use crate::render::{MoveMarkers, PieceTheme};
+use directories::ProjectDirs;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How many servers are kept in the list of recent ones.
+pub const RECENT_SERVERS: usize = 5;
+
+/// How many rooms are kept in the list of recent ones, to rejoin from the connection screen.
+pub const RECENT_ROOMS: usize = 3;
+
+/// A room an online game was played in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecentRoom {
+    pub server: String,
+    pub room: String,
+    pub played: u64,    // when the game started, in seconds since the Unix epoch
+    pub finished: bool, // whether it was played to the end, or left by resigning
+}
+
+impl RecentRoom {
+    /// What the connection screen shows, e.g. "Rejoin 'fredagsschack' (yesterday, unfinished)".
+    pub fn label(&self, now: SystemTime) -> String {
+        let age = seconds(now).saturating_sub(self.played);
+        let when = match age / 3600 {
+            0 => "just now".to_string(),
+            1 => "an hour ago".to_string(),
+            hours @ 2..=23 => format!("{} hours ago", hours),
+            24..=47 => "yesterday".to_string(),
+            hours => format!("{} days ago", hours / 24),
+        };
+        let state = if self.finished {
+            "finished"
+        } else {
+            "unfinished"
+        };
+        format!("Rejoin '{}' ({}, {})", self.room, when, state)
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub server: Option<String>,          // server IP and port, or "bot"/"ai"
+    pub room: Option<String>,            // room to join on the server
+    pub name: Option<String>,            // display name
+    pub avatar: Option<u8>,              // shown next to our name, by its place in avatar::AVATARS
+    pub blindfold: Blindfold,            // blindfold training level
+    pub hanging_pieces: HangingPieces,   // in which games undefended pieces are marked
+    pub tutor: bool,                     // flag moves that might lose material, played offline
+    pub ai_difficulty: Option<u8>,       // strength of the computer opponent
+    pub ai_time_limit: Option<u64>,      // seconds the computer may think per move, 0 for no limit
+    pub confirm_actions: bool, // ask before restarting, resigning, leaving a game or closing the window on one
+    pub auto_rematch: bool,    // play again online once both players agree, after a countdown
+    pub quick_chat: Vec<String>, // chat messages sent with Alt+1 to Alt+4 in online games
+    pub keys: Keymap,          // keyboard shortcuts during a game
+    pub window_pos: Option<(i32, i32)>, // where the window was when it was last closed
+    pub window_size: Option<(u32, u32)>, // and how big it was, in pixels
+    pub borderless: bool,      // the window has no title bar or border
+    pub always_on_top: bool,   // the window is kept above other windows
+    pub pieces: PieceTheme,    // how pieces are drawn
+    pub white_glyphs: [u8; 3], // red, green and blue of white's pieces in the glyph theme
+    pub black_glyphs: [u8; 3], // the same for black's
+    pub markers: MoveMarkers,  // how legal moves are marked, quiet ones and captures
+    pub annotations: Thresholds, // how much worse than the best a replayed move is marked ?!, ? or ??
+    pub diagram_coordinates: bool, // whether exported SVG diagrams have the coordinates around them
+    pub double_click_move: bool, // double-clicking a piece with one legal move plays it
+    pub double_click_ms: u64,    // most milliseconds between the clicks of a double-click
+    pub middle_click_flip: bool, // the middle mouse button flips the board
+    pub wheel_steps_moves: bool, // the mouse wheel over the board steps through the moves played
+    pub move_list: bool,         // the moves played are listed beside the board
+    pub board_flipped: bool, // games start with the board turned round, Black's side at the bottom
+    pub hotseat_flip: bool,  // in hotseat games the board turns round to the side to move
+    pub hotseat_cover: bool, // in hotseat games the board is hidden until the next player clicks
+    pub announce: AnnounceTo, // where moves and events are announced, for playing by ear
+    pub announce_pipe: Option<PathBuf>, // file or named pipe they're written to with "pipe"
+    pub announce_command: String, // program that speaks each one with "speech"
+    pub last_server: Option<String>, // server of the last online game, to fill in next time
+    pub last_room: Option<String>, // and its room
+    pub recent_servers: Vec<String>, // servers played on, most recent first
+    pub recent_rooms: Vec<RecentRoom>, // rooms played in, most recent first
+    pub recent_room_days: u64, // days a recent room is kept for
+
+    #[serde(skip)]
+    path: Option<PathBuf>, // where the settings are written back to
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            server: None,
+            room: None,
+            name: None,
+            avatar: None,
+            blindfold: Blindfold::Off,
+            hanging_pieces: HangingPieces::Practice,
+            tutor: false,
+            ai_difficulty: None,
+            ai_time_limit: None,
+            confirm_actions: true,
+            auto_rematch: true,
+            quick_chat: chat::default_presets(),
+            keys: Keymap::default(),
+            window_pos: None,
+            window_size: None,
+            borderless: false,
+            always_on_top: false,
+            pieces: PieceTheme::Images,
+            white_glyphs: [255, 255, 255],
+            black_glyphs: [0, 0, 0],
+            markers: MoveMarkers::default(),
+            annotations: Thresholds::default(),
+            diagram_coordinates: true,
+            double_click_move: true,
+            double_click_ms: 400,
+            middle_click_flip: true,
+            wheel_steps_moves: true,
+            move_list: true,
+            board_flipped: false,
+            hotseat_flip: false,
+            hotseat_cover: false,
+            announce: AnnounceTo::Off,
+            announce_pipe: None,
+            announce_command: if cfg!(target_os = "macos") {
+                "say".to_string()
+            } else {
+                "espeak".to_string()
+            },
+            last_server: None,
+            last_room: None,
+            recent_servers: Vec::new(),
+            recent_rooms: Vec::new(),
+            recent_room_days: 7,
+            path: None,
+        }
+    }
+}
+
+impl Config {
+    /// `config.toml` in the platform's config directory, if there is one.
+    pub fn default_path() -> Option<PathBuf> {
+        ProjectDirs::from("se", "prytznet", "schack")
+            .map(|dirs| dirs.config_dir().join("config.toml"))
+    }
+
+    /// Parses settings from TOML. Settings left out get their defaults.
+    pub fn parse(text: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Loads the settings from a file, falling back to the defaults with a warning if it can't
+    /// be read or parsed. Changes are written back to the same file, unless it couldn't be
+    /// parsed: that's left as it is for the user to fix, rather than overwritten with defaults.
+    pub fn load(path: &Path) -> Config {
+        let (config, save_to) = match fs::read_to_string(path) {
+            Ok(text) => match Config::parse(&text) {
+                Ok(config) => (config, Some(path.to_path_buf())),
+                Err(err) => {
+                    warn!(
+                        "Ignoring malformed config file {}, changes won't be saved: {}",
+                        path.display(),
+                        err
+                    );
+                    (Config::default(), None)
+                }
+            },
+            Err(err) => {
+                warn!(
+                    "Couldn't read config file {} ({}), using defaults",
+                    path.display(),
+                    err
+                );
+                (Config::default(), Some(path.to_path_buf()))
+            }
+        };
+
+        for (key, first, second) in config.keys.conflicts() {
+            warn!(
+                "{} is bound to both {:?} and {:?} in {}, it only does {:?}",
+                key,
+                first,
+                second,
+                path.display(),
+                first
+            );
+        }
+
+        let mut config = Config {
+            path: save_to,
+            ..config
+        };
+        config.prune(SystemTime::now());
+        config.keep_in_range();
+        config
+    }
+
+    /// Notes where and when an online game was started, and by whom, so the connection screen
+    /// can be filled in with it next time. Isn't saved until `save` is called.
+    pub fn remember(&mut self, server: &str, room: &str, name: Option<&str>, now: SystemTime) {
+        self.last_server = Some(server.to_string());
+        self.last_room = Some(room.to_string());
+        if let Some(name) = name {
+            self.name = Some(name.to_string());
+        }
+
+        self.recent_servers.retain(|recent| recent != server);
+        self.recent_servers.insert(0, server.to_string());
+        self.recent_servers.truncate(RECENT_SERVERS);
+
+        self.recent_rooms
+            .retain(|recent| recent.server != server || recent.room != room);
+        let recent = RecentRoom {
+            server: server.to_string(),
+            room: room.to_string(),
+            played: seconds(now),
+            finished: false,
+        };
+        self.recent_rooms.insert(0, recent);
+        self.prune(now);
+    }
+
+    /// Notes whether the game in a recent room is over. Isn't saved until `save` is called.
+    pub fn finish(&mut self, server: &str, room: &str, finished: bool) {
+        let recent = self
+            .recent_rooms
+            .iter_mut()
+            .find(|recent| recent.server == server && recent.room == room);
+        if let Some(recent) = recent {
+            recent.finished = finished;
+        }
+    }
+
+    /// Brings values set out of range in the file back into it, once, so nothing using them has
+    /// to. Rooms that can't be played in, e.g. too long to leave room for the tags on messages,
+    /// are forgotten.
+    fn keep_in_range(&mut self) {
+        self.ai_difficulty = self
+            .ai_difficulty
+            .map(|level| level.clamp(ai::MIN_DIFFICULTY, ai::MAX_DIFFICULTY));
+        for room in [&mut self.room, &mut self.last_room] {
+            if let Some(Err(err)) = room.as_deref().map(protocol::check_room) {
+                warn!("Ignoring a room in the config file: {}", err);
+                *room = None;
+            }
+        }
+        self.recent_rooms
+            .retain(|recent| protocol::check_room(&recent.room).is_ok());
+    }
+
+    /// Forgets rooms played in longer ago than `recent_room_days`, and all but the latest few.
+    fn prune(&mut self, now: SystemTime) {
+        // a number of days too large to count in seconds keeps everything
+        let max_age = Duration::from_secs(self.recent_room_days.saturating_mul(24 * 60 * 60));
+        let oldest = seconds(now).saturating_sub(max_age.as_secs());
+        self.recent_rooms.retain(|recent| recent.played >= oldest);
+        self.recent_rooms.truncate(RECENT_ROOMS);
+    }
+
+    /// The server to fill in: one picked in the file, or else the last one played on.
+    pub fn server(&self) -> Option<String> {
+        self.server.clone().or(self.last_server.clone())
+    }
+
+    /// The same for the room.
+    pub fn room(&self) -> Option<String> {
+        self.room.clone().or(self.last_room.clone())
+    }
+
+    /// Writes the settings back to the file they were loaded from. Failing to save isn't fatal,
+    /// so it's only reported on the console.
+    pub fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let result = toml::to_string_pretty(self)
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+                }
+                fs::write(path, text).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save config file {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::MarkerStyle;
+
+    #[test]
+    fn missing_settings_get_defaults() {
+        let config = Config::parse("server = \"example.com:6000\"\n").unwrap();
+        assert_eq!(config.server.as_deref(), Some("example.com:6000"));
+        assert_eq!(config.room, None);
+        assert_eq!(config.blindfold, Blindfold::Off);
+        assert_eq!(config.hanging_pieces, HangingPieces::Practice);
+        assert!(config.confirm_actions);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        assert!(Config::parse("server = ").is_err());
+        assert!(Config::parse("ai_difficulty = \"hard\"").is_err());
+        assert!(Config::parse("blindfold = \"sometimes\"").is_err());
+        assert!(Config::parse("white_glyphs = [255, 255]").is_err());
+        assert!(Config::parse("[markers]\ncapture = \"cross\"").is_err());
+    }
+
+    #[test]
+    fn a_malformed_file_is_not_overwritten() {
+        let path = std::env::temp_dir().join(format!("config-{}.toml", std::process::id()));
+        let text = "name = \"Alice\"\nai_difficulty = \"hard\"\n";
+        fs::write(&path, text).unwrap();
+
+        let mut config = Config::load(&path);
+        assert_eq!(config.name, None);
+        config.name = Some("Bob".to_string());
+        config.save();
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+
+        // a missing one is created
+        fs::remove_file(&path).unwrap();
+        Config::load(&path).save();
+        assert!(Config::parse(&fs::read_to_string(&path).unwrap()).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn values_out_of_range_are_brought_back_in() {
+        let mut config = Config::parse("ai_difficulty = 255").unwrap();
+        config.keep_in_range();
+        assert_eq!(config.ai_difficulty, Some(ai::MAX_DIFFICULTY));
+        let mut config = Config::parse("ai_difficulty = 0").unwrap();
+        config.keep_in_range();
+        assert_eq!(config.ai_difficulty, Some(ai::MIN_DIFFICULTY));
+        let mut config = Config::default();
+        config.keep_in_range();
+        assert_eq!(config, Config::default());
+
+        let long = "x".repeat(protocol::MAX_ROOM_LENGTH + 1);
+        let mut config = Config::parse(&format!(
+            "room = \"{}\"\nlast_room = \"lunch\"\n\
+             [[recent_rooms]]\nserver = \"a:1\"\nroom = \"{}\"\nplayed = 1\nfinished = false\n\
+             [[recent_rooms]]\nserver = \"a:1\"\nroom = \"lunch\"\nplayed = 1\nfinished = false\n",
+            long, long
+        ))
+        .unwrap();
+        config.keep_in_range();
+        assert_eq!(config.room, None);
+        assert_eq!(config.last_room.as_deref(), Some("lunch"));
+        let rooms: Vec<&str> = config
+            .recent_rooms
+            .iter()
+            .map(|r| r.room.as_str())
+            .collect();
+        assert_eq!(rooms, ["lunch"]);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let config = Config {
+            name: Some("Alice".to_string()),
+            blindfold: Blindfold::PiecesAndDots,
+            hanging_pieces: HangingPieces::Always,
+            ai_difficulty: Some(5),
+            window_pos: Some((-1280, 40)),
+            window_size: Some((1456, 1212)),
+            always_on_top: true,
+            avatar: Some(3),
+            quick_chat: vec!["Bra drag!".to_string(), "Hoppsan".to_string()],
+            pieces: PieceTheme::Glyphs,
+            black_glyphs: [40, 20, 120],
+            markers: MoveMarkers {
+                quiet: MarkerStyle::Corners,
+                capture: MarkerStyle::Ring,
+            },
+            diagram_coordinates: false,
+            double_click_move: false,
+            double_click_ms: 250,
+            annotations: Thresholds {
+                blunder: 500,
+                ..Thresholds::default()
+            },
+            middle_click_flip: false,
+            wheel_steps_moves: false,
+            move_list: false,
+            board_flipped: true,
+            hotseat_flip: true,
+            hotseat_cover: true,
+            announce: AnnounceTo::Pipe,
+            announce_pipe: Some(PathBuf::from("/tmp/schack-moves")),
+            ..Config::default()
+        };
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(Config::parse(&text).unwrap(), config);
+    }
+
+    #[test]
+    fn recent_servers_are_most_recent_first_without_repeats() {
+        let mut config = Config::default();
+        for i in 0..7 {
+            config.remember(
+                &format!("10.0.0.{}:6000", i),
+                "room",
+                None,
+                SystemTime::now(),
+            );
+        }
+        config.remember(
+            "10.0.0.4:6000",
+            "fredagsschack",
+            Some("Alice"),
+            SystemTime::now(),
+        );
+
+        assert_eq!(
+            config.recent_servers,
+            [
+                "10.0.0.4:6000",
+                "10.0.0.6:6000",
+                "10.0.0.5:6000",
+                "10.0.0.3:6000",
+                "10.0.0.2:6000"
+            ]
+        );
+        assert_eq!(config.server().as_deref(), Some("10.0.0.4:6000"));
+        assert_eq!(config.room().as_deref(), Some("fredagsschack"));
+        assert_eq!(config.name.as_deref(), Some("Alice"));
+
+        // a server picked in the file still wins
+        config.server = Some("example.com:6000".to_string());
+        assert_eq!(config.server().as_deref(), Some("example.com:6000"));
+    }
+
+    #[test]
+    fn recent_rooms_are_labelled_and_pruned() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = UNIX_EPOCH + 100 * day;
+        let mut config = Config::default();
+        config.remember("10.0.0.1:6000", "old", None, now - 8 * day);
+        config.remember("10.0.0.1:6000", "fredagsschack", None, now - day);
+        config.remember("10.0.0.2:6000", "fredagsschack", None, now - 3 * day);
+        config.finish("10.0.0.2:6000", "fredagsschack", true);
+        config.remember("10.0.0.1:6000", "lunch", None, now);
+
+        config.prune(now);
+        let labels: Vec<_> = config.recent_rooms.iter().map(|r| r.label(now)).collect();
+        assert_eq!(
+            labels,
+            [
+                "Rejoin 'lunch' (just now, unfinished)",
+                "Rejoin 'fredagsschack' (3 days ago, finished)",
+                "Rejoin 'fredagsschack' (yesterday, unfinished)",
+            ]
+        );
+
+        // rejoining moves a room back to the top, unfinished again
+        config.remember("10.0.0.2:6000", "fredagsschack", None, now);
+        assert_eq!(config.recent_rooms[0].server, "10.0.0.2:6000");
+        assert!(!config.recent_rooms[0].finished);
+        assert_eq!(config.recent_rooms.len(), 3);
+
+        // as many days as the file can hold, without overflowing
+        let mut forever = Config::parse("recent_room_days = 9223372036854775807").unwrap();
+        forever.remember("10.0.0.1:6000", "old", None, UNIX_EPOCH);
+        forever.prune(now);
+        assert_eq!(forever.recent_rooms.len(), 1);
+    }
+}
@@ -0,0 +1,166 @@
+/**
+ * A Yes/No question drawn over the screen, for actions that can't be undone. While it's open it
+ * takes all input; the screen underneath gets it back once the question is answered.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::keys::Action;
+use ggez::{event, graphics, Context, GameResult};
+
+/// Size of a button, and the gap between the two.
+const BUTTON_SIZE: (f32, f32) = (140.0, 50.0);
+const BUTTON_GAP: f32 = 40.0;
+
+/// Size of the box holding the question and buttons, centred on the screen.
+const BOX_SIZE: (f32, f32) = (520.0, 200.0);
+
+pub struct Confirm {
+    question: String,
+    yes_selected: bool,   // which button Enter picks, Yes by default
+    answer: Option<bool>, // the answer, until whoever asked acts on it
+}
+
+impl Confirm {
+    pub fn new(question: impl Into<String>) -> Confirm {
+        Confirm {
+            question: question.into(),
+            yes_selected: true,
+            answer: None,
+        }
+    }
+
+    /// The answer, once one has been given: true for Yes.
+    pub fn take_answer(&mut self) -> Option<bool> {
+        self.answer.take()
+    }
+
+    /// Draws the question over whatever is on screen.
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        let shade = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1),
+            [0.0, 0.0, 0.0, 0.5].into(),
+        )?;
+        graphics::draw(ctx, &shade, graphics::DrawParam::default())?;
+
+        let area = box_rect();
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            area,
+            [1.0, 1.0, 1.0, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+        let mut question = graphics::Text::new(
+            graphics::TextFragment::from(self.question.as_str())
+                .scale(graphics::PxScale { x: 26.0, y: 26.0 }),
+        );
+        question.set_bounds([area.w - 40.0, f32::INFINITY], graphics::Align::Center);
+        graphics::draw(
+            ctx,
+            &question,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([area.x + 20.0, area.y + 30.0]),
+        )?;
+
+        for (yes, label) in [(true, "Yes (Y)"), (false, "No (N)")] {
+            let rect = button_rect(yes);
+            let colour = if yes == self.yes_selected {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let button = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &button, graphics::DrawParam::default())?;
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(label).scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            let label_size = label.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([
+                        rect.x + (rect.w - label_size.w) / 2.0,
+                        rect.y + (rect.h - label_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        if let Some(yes) = button_at(x, y) {
+            self.yes_selected = yes;
+        }
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        if button != event::MouseButton::Left {
+            return;
+        }
+        if let Some(yes) = button_at(x, y) {
+            self.answer = Some(yes);
+        }
+    }
+
+    /// `action` is the shortcut the key is bound to, if any. The pause key backs out, like No.
+    pub fn key_down_event(&mut self, key: event::KeyCode, action: Option<Action>) {
+        match key {
+            event::KeyCode::Y => self.answer = Some(true),
+            event::KeyCode::N | event::KeyCode::Escape => self.answer = Some(false),
+            _ if action == Some(Action::Pause) => self.answer = Some(false),
+            event::KeyCode::Left | event::KeyCode::Right | event::KeyCode::Tab => {
+                self.yes_selected = !self.yes_selected
+            }
+            event::KeyCode::Return | event::KeyCode::NumpadEnter | event::KeyCode::Space => {
+                self.answer = Some(self.yes_selected)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Where the box is on the screen.
+fn box_rect() -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - BOX_SIZE.0) / 2.0,
+        (SCREEN_SIZE.1 - BOX_SIZE.1) / 2.0,
+        BOX_SIZE.0,
+        BOX_SIZE.1,
+    )
+}
+
+/// Where the Yes or No button is on the screen.
+fn button_rect(yes: bool) -> graphics::Rect {
+    let area = box_rect();
+    let left = area.x + (area.w - 2.0 * BUTTON_SIZE.0 - BUTTON_GAP) / 2.0;
+    let x = if yes {
+        left
+    } else {
+        left + BUTTON_SIZE.0 + BUTTON_GAP
+    };
+    graphics::Rect::new(
+        x,
+        area.y + area.h - BUTTON_SIZE.1 - 20.0,
+        BUTTON_SIZE.0,
+        BUTTON_SIZE.1,
+    )
+}
+
+/// Which button is at a point on the screen, if either: true for Yes.
+fn button_at(x: f32, y: f32) -> Option<bool> {
+    [true, false]
+        .into_iter()
+        .find(|&yes| button_rect(yes).contains([x, y]))
+}
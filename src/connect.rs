@@ -0,0 +1,590 @@
+/**
+ * The connection screen shown before a game: server address, room and name fields, and a
+ * Connect button. The server field has a list of the servers played on recently, and rooms
+ * played in recently are listed under the button to rejoin with one click. Connecting happens on a worker thread, so the window stays responsive and
+ * failures can be shown inline. Waiting for an opponent is left to the waiting screen.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::config::Config;
+use crate::matches;
+use crate::net::SERVER_ADDR;
+use crate::protocol::{self, MAX_ROOM_LENGTH};
+use crate::rating::Ratings;
+use crate::session::{self, Connection, Session, Setup};
+use crate::ui::{self, TextInput};
+use ggez::{event, graphics, Context, GameResult};
+use rand::rngs::StdRng;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::SystemTime;
+
+/// Labels of the text fields, in tab order.
+const FIELDS: [&str; 3] = ["Server", "Room", "Name"];
+const SERVER: usize = 0;
+const ROOM: usize = 1;
+const NAME: usize = 2;
+
+/// Longest text a field accepts.
+const FIELD_LENGTH: usize = 32;
+
+/// Size of a text field, and where the first one is drawn. The rest follow below it.
+const FIELD_SIZE: (f32, f32) = (400.0, 40.0);
+const FIELD_TOP: f32 = 240.0;
+const FIELD_SPACING: f32 = 90.0;
+
+/// Size and top edge of the Connect button.
+const BUTTON_SIZE: (f32, f32) = (200.0, 50.0);
+const BUTTON_TOP: f32 = 510.0;
+
+/// Size of a recent room's row, and where the first one is drawn. The rest follow below it.
+const REJOIN_SIZE: (f32, f32) = (400.0, 30.0);
+const REJOIN_TOP: f32 = 620.0;
+const REJOIN_SPACING: f32 = 34.0;
+
+/// How connecting went, reported by the worker thread.
+enum ConnectEvent {
+    /// Connected to the server, ready to wait for an opponent to join the room.
+    Connected(Connection),
+    /// Connecting failed.
+    Failed(String),
+}
+
+pub struct ConnectScreen {
+    fields: [TextInput; 3],                  // server, room and name, as typed
+    focus: usize,                            // field receiving typed characters, or recent room
+    status: Option<String>,                  // progress of the connection attempt
+    error: Option<String>,                   // why the last attempt failed
+    pending: Option<Receiver<ConnectEvent>>, // progress from the worker thread, while connecting
+    ready: Option<Session>,                  // session to start, once there is one
+    leaving: bool,                           // Escape was pressed, go back to the menu
+    config: Config,                          // for the computer opponent's settings
+    rng: StdRng,                             // for the colour against the computer, and its moves
+    recent: Option<usize>,                   // highlighted entry while the recent servers are open
+    ratings: Ratings,                        // from the match history, for opponents played before
+}
+
+impl ConnectScreen {
+    /// Creates the screen with the fields filled in from the command line or config file.
+    pub fn new(
+        server: Option<String>,
+        room: Option<String>,
+        name: Option<String>,
+        config: Config,
+        rng: StdRng,
+    ) -> ConnectScreen {
+        ConnectScreen {
+            // the protocol separates words with spaces, so only names may contain them
+            fields: [
+                TextInput::new(server.unwrap_or_default(), FIELD_LENGTH, ui::no_spaces),
+                TextInput::new(room.unwrap_or_default(), MAX_ROOM_LENGTH, ui::no_spaces),
+                TextInput::new(name.unwrap_or_default(), FIELD_LENGTH, ui::any_char),
+            ],
+            focus: SERVER,
+            status: None,
+            error: None,
+            pending: None,
+            ready: None,
+            leaving: false,
+            config,
+            rng,
+            recent: None,
+            ratings: Ratings::from_history(&matches::load_default()),
+        }
+    }
+
+    /// Name typed into the name field, if any.
+    pub fn name(&self) -> Option<&str> {
+        Some(self.fields[NAME].text().trim()).filter(|name| !name.is_empty())
+    }
+
+    /// Whether the player asked to go back to the main menu. An attempt still connecting is
+    /// abandoned.
+    pub fn leaving(&self) -> bool {
+        self.leaving
+    }
+
+    /// Our rating and the opponent's, if the room typed is one we've played in before. Online
+    /// opponents go by the room's name, since the server doesn't say who joined.
+    fn rating_line(&self) -> Option<String> {
+        let room = self.fields[ROOM].text().trim();
+        let opponent = self.ratings.opponent(room)?;
+        let games = match opponent.games {
+            1 => "1 game".to_string(),
+            n => format!("{} games", n),
+        };
+        Some(format!(
+            "You're rated {:.0}, {} {:.0} after {}",
+            self.ratings.mine, room, opponent.rating, games
+        ))
+    }
+
+    /// Starts connecting with what's in the fields. Games against the computer start right away.
+    pub fn connect(&mut self) {
+        if self.pending.is_some() {
+            return;
+        }
+        self.error = None;
+
+        let server_addr = match self.fields[SERVER].text().trim() {
+            "" => SERVER_ADDR.to_string(),
+            addr => addr.to_string(),
+        };
+
+        match server_addr.as_str() {
+            "bot" => {
+                self.ready = Some(session::random_mover_session(&mut self.rng));
+                return;
+            }
+            "ai" => {
+                self.ready = Some(session::configured_ai_session(&self.config, &mut self.rng));
+                return;
+            }
+            _ => (),
+        }
+
+        let room_name = self.fields[ROOM].text().trim().to_string();
+        if room_name.is_empty() {
+            self.error = Some("Enter a room name to meet your opponent in".to_string());
+            self.focus = ROOM;
+            return;
+        }
+        // the field stops at as many characters, but some take more than a byte
+        if protocol::check_room(&room_name).is_err() {
+            self.error = Some(format!(
+                "The room name is too long, it can have at most {} letters",
+                MAX_ROOM_LENGTH
+            ));
+            self.focus = ROOM;
+            return;
+        }
+
+        let (events, pending) = mpsc::channel();
+        self.pending = Some(pending);
+        self.status = Some(format!("Connecting to {}...", server_addr));
+
+        thread::spawn(move || {
+            let event = match session::connect(&server_addr, &room_name) {
+                Ok(connection) => ConnectEvent::Connected(connection),
+                Err(err) => ConnectEvent::Failed(err),
+            };
+            let _ = events.send(event);
+        });
+    }
+
+    /// Checks on the connection attempt. Returns the game once it can start, or the connection
+    /// once there is one to wait for an opponent on.
+    pub fn update(&mut self) -> Option<Setup> {
+        if let Some(session) = self.ready.take() {
+            return Some(Setup::Ready(session));
+        }
+
+        let event = match &self.pending {
+            Some(pending) => pending.try_recv(),
+            None => return None,
+        };
+
+        match event {
+            Ok(ConnectEvent::Connected(connection)) => {
+                self.pending = None;
+                self.status = None;
+                Some(Setup::Waiting(connection))
+            }
+            Ok(ConnectEvent::Failed(err)) => {
+                self.fail(err);
+                None
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.fail("Connecting stopped unexpectedly".to_string());
+                None
+            }
+        }
+    }
+
+    /// Number of fields and recent rooms that can be moved between with Tab.
+    fn focusable(&self) -> usize {
+        FIELDS.len() + self.config.recent_rooms.len()
+    }
+
+    /// Connects to a recent room again.
+    fn rejoin(&mut self, i: usize) {
+        if let Some(recent) = self.config.recent_rooms.get(i) {
+            self.fields[SERVER].set_text(&recent.server);
+            self.fields[ROOM].set_text(&recent.room);
+            self.connect();
+        }
+    }
+
+    /// Opens the list of recent servers, if there are any.
+    fn open_recent(&mut self) {
+        if !self.config.recent_servers.is_empty() {
+            self.focus = SERVER;
+            self.recent = Some(0);
+        }
+    }
+
+    /// Fills in the server field with a recent server and closes the list.
+    fn pick_recent(&mut self, i: usize) {
+        if let Some(server) = self.config.recent_servers.get(i) {
+            self.fields[SERVER].set_text(server);
+            self.focus = ROOM;
+        }
+        self.recent = None;
+    }
+
+    fn fail(&mut self, err: String) {
+        self.pending = None;
+        self.status = None;
+        self.error = Some(err);
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        let title = graphics::Text::new(
+            graphics::TextFragment::from("Schack").scale(graphics::PxScale { x: 60.0, y: 60.0 }),
+        );
+        let title_width = title.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - title_width) / 2.0, 100.0]),
+        )?;
+
+        for (i, label) in FIELDS.iter().enumerate() {
+            let rect = field_rect(i);
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(*label).scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([rect.x, rect.y - 24.0]),
+            )?;
+
+            // the default server is shown in grey while the field is empty
+            let focused = i == self.focus && self.pending.is_none();
+            let placeholder = if i == SERVER { SERVER_ADDR } else { "" };
+            self.fields[i].draw(ctx, rect, focused, placeholder)?;
+        }
+
+        if let Some(line) = self.rating_line() {
+            let rect = field_rect(ROOM);
+            let text = graphics::Text::new(
+                graphics::TextFragment::from(line).scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+            );
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.1, 0.1, 0.3, 1.0].into())
+                    .dest([rect.x, rect.y + rect.h + 4.0]),
+            )?;
+        }
+
+        // a little triangle opening the recent servers
+        if !self.config.recent_servers.is_empty() {
+            let arrow = arrow_rect();
+            let triangle = graphics::Mesh::new_polygon(
+                ctx,
+                graphics::DrawMode::fill(),
+                &[
+                    [arrow.x + 10.0, arrow.y + 15.0],
+                    [arrow.x + arrow.w - 10.0, arrow.y + 15.0],
+                    [arrow.x + arrow.w / 2.0, arrow.y + arrow.h - 13.0],
+                ],
+                [0.3, 0.3, 0.3, 1.0].into(),
+            )?;
+            graphics::draw(ctx, &triangle, graphics::DrawParam::default())?;
+        }
+
+        // greyed out while connecting
+        let button = button_rect();
+        let button_colour = match self.pending {
+            Some(_) => [0.7, 0.7, 0.7, 1.0],
+            None => [0.3, 0.6, 0.3, 1.0],
+        };
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            button,
+            button_colour.into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+        let label = graphics::Text::new(
+            graphics::TextFragment::from("Connect").scale(graphics::PxScale { x: 28.0, y: 28.0 }),
+        );
+        let label_size = label.dimensions(ctx);
+        graphics::draw(
+            ctx,
+            &label,
+            graphics::DrawParam::default()
+                .color([1.0, 1.0, 1.0, 1.0].into())
+                .dest([
+                    button.x + (button.w - label_size.w) / 2.0,
+                    button.y + (button.h - label_size.h) / 2.0,
+                ]),
+        )?;
+
+        // progress or the last error, under the button
+        let message = match (&self.error, &self.status) {
+            (Some(err), _) => Some((err.as_str(), [0.7, 0.0, 0.0, 1.0])),
+            (None, Some(status)) => Some((status.as_str(), [0.0, 0.0, 0.0, 1.0])),
+            (None, None) => None,
+        };
+        if let Some((message, colour)) = message {
+            let mut text = graphics::Text::new(
+                graphics::TextFragment::from(message).scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+            );
+            text.set_bounds(
+                [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+                graphics::Align::Center,
+            );
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color(colour.into())
+                    .dest([20.0, BUTTON_TOP + BUTTON_SIZE.1 + 20.0]),
+            )?;
+        }
+
+        let now = SystemTime::now();
+        for (i, recent) in self.config.recent_rooms.iter().enumerate() {
+            let rect = rejoin_rect(i);
+            let focused = self.focus == FIELDS.len() + i && self.pending.is_none();
+            let colour = if focused {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.4, 0.4, 0.4, 1.0]
+            };
+            let background = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(recent.label(now))
+                    .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            let label_size = label.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([
+                        rect.x + (rect.w - label_size.w) / 2.0,
+                        rect.y + (rect.h - label_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+
+        // drawn last, over the fields below it
+        if let Some(highlighted) = self.recent {
+            for (i, server) in self.config.recent_servers.iter().enumerate() {
+                let rect = recent_rect(i);
+                let colour = if i == highlighted {
+                    [0.8, 0.88, 1.0, 1.0]
+                } else {
+                    [0.95, 0.95, 0.95, 1.0]
+                };
+                let background = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    rect,
+                    colour.into(),
+                )?;
+                graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+                let outline = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::stroke(1.0),
+                    rect,
+                    [0.6, 0.6, 0.6, 1.0].into(),
+                )?;
+                graphics::draw(ctx, &outline, graphics::DrawParam::default())?;
+
+                let text = graphics::Text::new(
+                    graphics::TextFragment::from(server.as_str())
+                        .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+                );
+                graphics::draw(
+                    ctx,
+                    &text,
+                    graphics::DrawParam::default()
+                        .color([0.0, 0.0, 0.0, 1.0].into())
+                        .dest([rect.x + 8.0, rect.y + 8.0]),
+                )?;
+            }
+        }
+
+        let mut hint = graphics::Text::new(
+            graphics::TextFragment::from(
+                "Tab moves between fields, Enter connects, Escape goes back, Alt+Down lists recent servers. Type bot or ai as the server to play on this computer.",
+            )
+            .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        hint.set_bounds(
+            [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+            graphics::Align::Center,
+        );
+        graphics::draw(
+            ctx,
+            &hint,
+            graphics::DrawParam::default()
+                .color([0.2, 0.2, 0.2, 1.0].into())
+                .dest([20.0, SCREEN_SIZE.1 - 40.0]),
+        )?;
+
+        graphics::present(ctx)
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        if self.recent.is_some() {
+            if let Some(i) = recent_at(self.config.recent_servers.len(), x, y) {
+                self.recent = Some(i);
+            }
+        }
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        if button != event::MouseButton::Left || self.pending.is_some() {
+            return;
+        }
+
+        // a click anywhere else closes the list
+        if self.recent.is_some() {
+            match recent_at(self.config.recent_servers.len(), x, y) {
+                Some(i) => self.pick_recent(i),
+                None => self.recent = None,
+            }
+            return;
+        }
+
+        if !self.config.recent_servers.is_empty() && arrow_rect().contains([x, y]) {
+            self.open_recent();
+        } else if let Some(field) = (0..FIELDS.len()).find(|&i| field_rect(i).contains([x, y])) {
+            self.focus = field;
+        } else if button_rect().contains([x, y]) {
+            self.connect();
+        } else if let Some(i) =
+            (0..self.config.recent_rooms.len()).find(|&i| rejoin_rect(i).contains([x, y]))
+        {
+            self.focus = FIELDS.len() + i;
+            self.rejoin(i);
+        }
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        if let Some(highlighted) = self.recent {
+            let count = self.config.recent_servers.len();
+            match key {
+                event::KeyCode::Up => self.recent = Some((highlighted + count - 1) % count),
+                event::KeyCode::Down => self.recent = Some((highlighted + 1) % count),
+                event::KeyCode::Return | event::KeyCode::NumpadEnter => {
+                    self.pick_recent(highlighted)
+                }
+                event::KeyCode::Escape | event::KeyCode::Tab => self.recent = None,
+                _ => (),
+            }
+            return;
+        }
+
+        match key {
+            event::KeyCode::Escape => self.leaving = true,
+            // fields can't be edited while connecting
+            _ if self.pending.is_some() => (),
+            // the field with the focus moves its caret, deletes and pastes
+            _ if self
+                .fields
+                .get_mut(self.focus)
+                .is_some_and(|field| field.key_down_event(key, mods)) => {}
+            event::KeyCode::Down if mods.contains(event::KeyMods::ALT) => self.open_recent(),
+            event::KeyCode::Tab if mods.contains(event::KeyMods::SHIFT) => {
+                self.focus = (self.focus + self.focusable() - 1) % self.focusable();
+            }
+            event::KeyCode::Tab | event::KeyCode::Down => {
+                self.focus = (self.focus + 1) % self.focusable();
+            }
+            event::KeyCode::Up => {
+                self.focus = (self.focus + self.focusable() - 1) % self.focusable();
+            }
+            event::KeyCode::Return | event::KeyCode::NumpadEnter if self.focus >= FIELDS.len() => {
+                self.rejoin(self.focus - FIELDS.len())
+            }
+            event::KeyCode::Return | event::KeyCode::NumpadEnter => self.connect(),
+            _ => (),
+        }
+    }
+
+    pub fn text_input_event(&mut self, character: char) {
+        if self.pending.is_some() || self.recent.is_some() {
+            return;
+        }
+        // unless a recent room has the focus
+        if let Some(field) = self.fields.get_mut(self.focus) {
+            field.text_input_event(character);
+        }
+    }
+}
+
+/// Where a text field is on the screen.
+fn field_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - FIELD_SIZE.0) / 2.0,
+        FIELD_TOP + i as f32 * FIELD_SPACING,
+        FIELD_SIZE.0,
+        FIELD_SIZE.1,
+    )
+}
+
+/// Where the button opening the recent servers is, at the right end of the server field.
+fn arrow_rect() -> graphics::Rect {
+    let field = field_rect(SERVER);
+    graphics::Rect::new(field.x + field.w - field.h, field.y, field.h, field.h)
+}
+
+/// Where a recent server is listed, under the server field.
+fn recent_rect(i: usize) -> graphics::Rect {
+    let field = field_rect(SERVER);
+    graphics::Rect::new(
+        field.x,
+        field.y + (i + 1) as f32 * field.h,
+        field.w,
+        field.h,
+    )
+}
+
+/// Where a recent room's row is on the screen.
+fn rejoin_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - REJOIN_SIZE.0) / 2.0,
+        REJOIN_TOP + i as f32 * REJOIN_SPACING,
+        REJOIN_SIZE.0,
+        REJOIN_SIZE.1,
+    )
+}
+
+/// Index of the recent server at a point on the screen, if there is one.
+fn recent_at(count: usize, x: f32, y: f32) -> Option<usize> {
+    (0..count).find(|&i| recent_rect(i).contains([x, y]))
+}
+
+/// Where the Connect button is on the screen.
+fn button_rect() -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - BUTTON_SIZE.0) / 2.0,
+        BUTTON_TOP,
+        BUTTON_SIZE.0,
+        BUTTON_SIZE.1,
+    )
+}
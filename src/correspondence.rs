@@ -0,0 +1,440 @@
+/**
+ * Correspondence games: slow games where the players don't have to be online at the same time.
+ * The server keeps nothing, so each player's moves are kept in a file of their own in the data
+ * directory, one per server and room, saved after every move. Connecting to the room again plays
+ * the saved moves back and offers to sync, saying how many moves we have and a hash of them.
+ * Whichever side is behind asks for the moves it's missing, and the other sends them again, so a
+ * move played while the opponent was away reaches them the next time both are online.
+ *
+ * The player who starts a correspondence game from the waiting screen plays White. Someone
+ * waiting in the room when a sync offer comes joins the game as Black.
+ */
+use crate::board::parse_move;
+use crate::inbound;
+use crate::protocol::{self, Message};
+use crate::referee::Referee;
+use chess_template::{Colour, Position};
+use directories::ProjectDirs;
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Most moves sent again per `inbound::RATE_WINDOW`. A quarter of what the other side takes, so
+/// a long game's moves never trip its flood limit, even with two batches arriving together and
+/// the game's other messages besides.
+pub const RESEND_BATCH: usize = inbound::RATE_LIMIT / 4;
+
+/// A correspondence game, as it's kept between sessions.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Correspondence {
+    pub path: PathBuf,      // the file it's kept in
+    pub colour: Colour,     // the colour we play
+    pub moves: Vec<String>, // every move so far in coordinate notation
+}
+
+/// What to do about a sync offer from the opponent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sync {
+    /// They're ahead, so ask for the moves after our counter.
+    Request,
+    /// They're behind, so offer ours back for them to ask.
+    Offer,
+    /// Both have the same moves.
+    InStep,
+    /// Both have as many moves, but not the same ones.
+    Diverged,
+}
+
+impl Correspondence {
+    /// A game with no moves yet, kept in `path`.
+    pub fn new(path: PathBuf, colour: Colour) -> Correspondence {
+        Correspondence {
+            path,
+            colour,
+            moves: Vec::new(),
+        }
+    }
+
+    /// The file's contents, one field per line like the crash saves:
+    ///
+    /// ```text
+    /// colour white
+    /// moves e2e4 e7e5
+    /// ```
+    pub fn to_text(&self) -> String {
+        format!(
+            "colour {}\nmoves {}\n",
+            protocol::colour_name(self.colour),
+            self.moves.join(" ")
+        )
+    }
+
+    /// Reads a game kept in `path` back from its contents, if it's one.
+    pub fn parse(path: PathBuf, text: &str) -> Option<Correspondence> {
+        let (mut colour, mut moves) = (None, None);
+        for line in text.lines() {
+            match line.split_once(' ').unwrap_or((line, "")) {
+                ("colour", value) => colour = protocol::parse_colour(value).ok(),
+                ("moves", value) => {
+                    moves = Some(value.split_whitespace().map(String::from).collect())
+                }
+                _ => (),
+            }
+        }
+        Some(Correspondence {
+            path,
+            colour: colour?,
+            moves: moves?,
+        })
+    }
+
+    /// The game kept in `path`, if there is one. A file that can't be read is left alone, with a
+    /// warning.
+    pub fn load(path: &Path) -> Option<Correspondence> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return None,
+            Err(err) => {
+                warn!(
+                    "Couldn't read the correspondence game {}: {}",
+                    path.display(),
+                    err
+                );
+                return None;
+            }
+        };
+        let game = Correspondence::parse(path.to_path_buf(), &text);
+        if game.is_none() {
+            warn!("{} isn't a correspondence game", path.display());
+        }
+        game
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, self.to_text())
+    }
+
+    /// Forgets the game once it's over, so the room starts a new one next time.
+    pub fn remove(&self) {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => warn!(
+                "Couldn't remove the finished correspondence game {}: {}",
+                self.path.display(),
+                err
+            ),
+            _ => (),
+        }
+    }
+
+    /// The moves as squares, up to the first that can't be read.
+    pub fn positions(&self) -> Vec<(Position, Position)> {
+        self.moves
+            .iter()
+            .map_while(|text| parse_move(text).map(|(from, to, _)| (from, to)))
+            .collect()
+    }
+}
+
+/// Moves the opponent asked for, waiting to be sent again `RESEND_BATCH` at a time.
+#[derive(Default)]
+pub struct Resend {
+    moves: VecDeque<(u32, Position, Position)>, // each with the counter it takes the game to
+    next_batch: Option<Instant>,                // when another batch may go, once one has
+}
+
+impl Resend {
+    /// Sends `moves` again, the first taking the game to `counter`, in place of any still waiting.
+    pub fn start(&mut self, counter: u32, moves: &[(Position, Position)]) {
+        self.moves = (counter..)
+            .zip(moves)
+            .map(|(counter, &(from, to))| (counter, from, to))
+            .collect();
+    }
+
+    /// Drops the moves still waiting, e.g. when the game they're from is over.
+    pub fn stop(&mut self) {
+        self.moves.clear();
+    }
+
+    /// The moves to send `now`, if it's time for the next batch.
+    pub fn due(&mut self, now: Instant) -> Vec<(u32, Position, Position)> {
+        if self.moves.is_empty() || self.next_batch.is_some_and(|next| now < next) {
+            return Vec::new();
+        }
+        self.next_batch = Some(now + inbound::RATE_WINDOW);
+        let batch = self.moves.len().min(RESEND_BATCH);
+        self.moves.drain(..batch).collect()
+    }
+}
+
+/// The correspondence side of a game in a room: the game kept between sessions, if it's one, and
+/// the moves the opponent missed.
+#[derive(Default)]
+pub struct Keeper {
+    game: Option<Correspondence>, // until it's over
+    resend: Resend,
+}
+
+/// What a message did to a correspondence game.
+pub enum Event {
+    /// Nothing to do with it.
+    Other(Message),
+    /// Answered, with what to send back, if anything.
+    Reply(Vec<Message>),
+    /// The opponent is back in the room, and is offered a sync.
+    Rejoined(Message),
+    /// They have as many moves as we do, but not the same ones, at this counter.
+    Diverged(u32),
+}
+
+impl Keeper {
+    /// Keeps `game` from now on.
+    pub fn pick_up(&mut self, game: Correspondence) {
+        self.game = Some(game);
+    }
+
+    /// Whether the game is kept between sessions, until it's over.
+    pub fn active(&self) -> bool {
+        self.game.is_some()
+    }
+
+    /// Acts on a message about syncing, given the game as the referee has it and its moves in
+    /// coordinate notation. Our own offer comes back in step.
+    pub fn handle(&mut self, msg: Message, referee: &Referee, names: &[String]) -> Event {
+        if self.game.is_none() {
+            return Event::Other(msg);
+        }
+        match msg {
+            Message::SyncOffer {
+                counter,
+                hash: theirs,
+                ..
+            } => match sync((referee.counter(), hash(names)), (counter, theirs)) {
+                Sync::Request => Event::Reply(vec![Message::SyncRequest {
+                    room: referee.room.clone(),
+                    counter: referee.counter(),
+                }]),
+                Sync::Offer => Event::Reply(vec![offer(referee, names)]),
+                Sync::InStep => Event::Reply(Vec::new()),
+                Sync::Diverged => Event::Diverged(counter),
+            },
+            Message::SyncRequest { counter, .. } if counter < referee.counter() => {
+                self.resend(referee, counter);
+                Event::Reply(self.due(referee, Instant::now()))
+            }
+            // they may have missed moves while they were away
+            Message::Join { .. } => Event::Rejoined(offer(referee, names)),
+            msg => Event::Other(msg),
+        }
+    }
+
+    /// Sends the moves after `counter` again, for an opponent who was away when they were
+    /// played. They go out a batch at a time, see `due`, so a long game doesn't look like a
+    /// flood.
+    pub fn resend(&mut self, referee: &Referee, counter: u32) {
+        // the move that took the counter to n is the (n - 1)th
+        let first = (counter as usize).saturating_sub(1);
+        let missing = referee.moves().get(first..).unwrap_or_default();
+        info!(
+            "Sending the opponent the {} moves they missed",
+            missing.len()
+        );
+        self.resend.start(first as u32 + 2, missing);
+    }
+
+    /// The next batch of moves the opponent missed, once it's time.
+    pub fn due(&mut self, referee: &Referee, now: Instant) -> Vec<Message> {
+        self.resend
+            .due(now)
+            .into_iter()
+            .map(|(counter, from, to)| Message::Move {
+                room: referee.room.clone(),
+                counter,
+                from,
+                to,
+            })
+            .collect()
+    }
+
+    /// Drops the moves still waiting to be sent again.
+    pub fn stop(&mut self) {
+        self.resend.stop();
+    }
+
+    /// Keeps the game's moves after every change, so they're there next time.
+    pub fn keep(&mut self, names: Vec<String>) {
+        if let Some(game) = &mut self.game {
+            game.moves = names;
+            if let Err(err) = game.save() {
+                warn!(
+                    "Couldn't keep the correspondence game in {}: {}",
+                    game.path.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Forgets the game once it's over, so the room starts a new one next time. What follows,
+    /// e.g. a rematch with both players online, is played as usual.
+    pub fn finish(&mut self) {
+        if let Some(game) = self.game.take() {
+            game.remove();
+        }
+    }
+}
+
+/// Tells the opponent how many moves we have, and a hash of them.
+pub fn offer(referee: &Referee, names: &[String]) -> Message {
+    Message::SyncOffer {
+        room: referee.room.clone(),
+        counter: referee.counter(),
+        hash: hash(names),
+    }
+}
+
+/// Where the game in a room on a server is kept, in `dir`. Characters that can't go in a file
+/// name are replaced.
+pub fn path(dir: &Path, server: &str, room: &str) -> PathBuf {
+    let safe = |text: &str| -> String {
+        text.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+                _ => '_',
+            })
+            .collect()
+    };
+    dir.join(format!("{}_{}.txt", safe(server), safe(room)))
+}
+
+/// Where the game in a room on a server is kept in the platform's data directory, if there is
+/// one.
+pub fn default_path(server: &str, room: &str) -> Option<PathBuf> {
+    ProjectDirs::from("se", "prytznet", "schack")
+        .map(|dirs| path(&dirs.data_dir().join("correspondence"), server, room))
+}
+
+/// A hash of the moves, to tell whether two games with as many moves have the same ones.
+/// FNV-1a, so it's the same on every platform and version.
+pub fn hash(moves: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in moves.join(" ").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// What to do about a sync offer, given our move counter and hash and theirs.
+pub fn sync(ours: (u32, u64), theirs: (u32, u64)) -> Sync {
+    match theirs.0.cmp(&ours.0) {
+        std::cmp::Ordering::Greater => Sync::Request,
+        std::cmp::Ordering::Less => Sync::Offer,
+        std::cmp::Ordering::Equal if theirs.1 == ours.1 => Sync::InStep,
+        std::cmp::Ordering::Equal => Sync::Diverged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_round_trip_and_are_kept_apart_by_room() {
+        let dir = std::env::temp_dir().join(format!("correspondence-{}", std::process::id()));
+        let path = path(&dir, "10.0.0.2:6000", "fredags/schack");
+        assert_eq!(
+            path.file_name().unwrap(),
+            "10.0.0.2_6000_fredags_schack.txt"
+        );
+        assert!(Correspondence::load(&path).is_none());
+
+        let mut game = Correspondence::new(path.clone(), Colour::Black);
+        game.moves = vec!["e2e4".to_string(), "e7e5".to_string()];
+        game.save().unwrap();
+        assert_eq!(Correspondence::load(&path), Some(game.clone()));
+        assert_eq!(game.positions().len(), 2);
+
+        game.remove();
+        assert!(Correspondence::load(&path).is_none());
+        assert!(Correspondence::parse(path, "moves e2e4\n").is_none());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_side_behind_asks() {
+        let moves =
+            |text: &str| -> Vec<String> { text.split_whitespace().map(String::from).collect() };
+        let (one, two) = (hash(&moves("e2e4")), hash(&moves("e2e4 e7e5")));
+        assert_ne!(one, two);
+        assert_eq!(sync((2, one), (3, two)), Sync::Request);
+        assert_eq!(sync((3, two), (2, one)), Sync::Offer);
+        assert_eq!(sync((3, two), (3, two)), Sync::InStep);
+        assert_eq!(sync((2, one), (2, hash(&moves("d2d4")))), Sync::Diverged);
+    }
+
+    #[test]
+    fn only_a_kept_game_answers_sync_offers() {
+        let mut referee = Referee::new("study", Colour::White);
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        referee.play_on(from, to).unwrap();
+        let names = ["e2e4".to_string()];
+        let ahead = || Message::SyncOffer {
+            room: "study".to_string(),
+            counter: 3,
+            hash: 1,
+        };
+
+        let mut keeper = Keeper::default();
+        assert!(matches!(
+            keeper.handle(ahead(), &referee, &names),
+            Event::Other(Message::SyncOffer { .. })
+        ));
+        keeper.pick_up(Correspondence::new(
+            PathBuf::from("study.txt"),
+            Colour::White,
+        ));
+        assert!(matches!(
+            &keeper.handle(ahead(), &referee, &names),
+            Event::Reply(msgs) if matches!(msgs[..], [Message::SyncRequest { counter: 2, .. }])
+        ));
+        assert!(matches!(
+            keeper.handle(offer(&referee, &names), &referee, &names),
+            Event::Reply(msgs) if msgs.is_empty()
+        ));
+        let behind = Message::SyncRequest {
+            room: "study".to_string(),
+            counter: 1,
+        };
+        assert!(matches!(
+            &keeper.handle(behind, &referee, &names),
+            Event::Reply(msgs) if matches!(msgs[..], [Message::Move { counter: 2, .. }])
+        ));
+    }
+
+    #[test]
+    fn missed_moves_go_a_batch_per_window() {
+        let knight = parse_move("g1f3").map(|(from, to, _)| (from, to)).unwrap();
+        let start = Instant::now();
+        let mut resend = Resend::default();
+        resend.start(2, &[knight; RESEND_BATCH + 10]);
+
+        let first = resend.due(start);
+        assert_eq!(first.len(), RESEND_BATCH);
+        assert_eq!(
+            (first[0].0, first[RESEND_BATCH - 1].0),
+            (2, RESEND_BATCH as u32 + 1)
+        );
+        assert!(resend.due(start + inbound::RATE_WINDOW / 2).is_empty());
+        let second = resend.due(start + inbound::RATE_WINDOW);
+        assert_eq!(second.len(), 10);
+        assert_eq!(second[0].0, RESEND_BATCH as u32 + 2);
+        assert!(resend.due(start + inbound::RATE_WINDOW * 3).is_empty());
+    }
+}
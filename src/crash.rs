@@ -0,0 +1,292 @@
+/**
+ * Keeping the game when the program panics. The game on screen leaves a snapshot here after
+ * every move, and the panic hook writes it to `crash-<seconds>.txt` next to a `crash-<seconds>.log`
+ * with the panic message and backtrace, both in the config directory. Online, it also tries to
+ * resign, so the opponent isn't left waiting. On the next start, the game offers to play the
+ * saved game on from where it stopped.
+ */
+use crate::board::parse_move;
+use crate::confirm::Confirm;
+use crate::keys::Action;
+use chess_template::Position;
+use ggez::{event, graphics, Context, GameResult};
+use log::{error, info, warn};
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Mutex, TryLockError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The game being played, as the panic hook should save it.
+pub struct Snapshot {
+    pub game: SavedGame,
+    pub leave: Option<(SyncSender<String>, String)>, // channel to the server and the message resigning
+}
+
+/// The snapshot of the game on screen, if there is one.
+static CURRENT: Mutex<Option<Snapshot>> = Mutex::new(None);
+
+/// Replaces the snapshot the panic hook saves.
+pub fn update(snapshot: Snapshot) {
+    *CURRENT.lock().unwrap_or_else(|err| err.into_inner()) = Some(snapshot);
+}
+
+/// Forgets the snapshot, once there's no game on screen.
+pub fn clear() {
+    *CURRENT.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// A game written down when the program crashed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SavedGame {
+    pub room: String,
+    pub fen: String,        // the last position, for other programs to load
+    pub moves: Vec<String>, // every move in coordinate notation, for playing on
+}
+
+impl SavedGame {
+    /// The file the game is written to, one field per line:
+    ///
+    /// ```text
+    /// room myroom
+    /// fen rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+    /// moves e2e4
+    /// ```
+    pub fn to_text(&self) -> String {
+        format!(
+            "room {}\nfen {}\nmoves {}\n",
+            self.room,
+            self.fen,
+            self.moves.join(" ")
+        )
+    }
+
+    /// Reads a game back from its file, if it's one.
+    pub fn parse(text: &str) -> Option<SavedGame> {
+        let mut game = SavedGame {
+            room: String::new(),
+            fen: String::new(),
+            moves: Vec::new(),
+        };
+        let mut fields = 0;
+        for line in text.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "room" => game.room = value.to_string(),
+                "fen" => game.fen = value.to_string(),
+                "moves" => game.moves = value.split_whitespace().map(String::from).collect(),
+                _ => continue,
+            }
+            fields += 1;
+        }
+        (fields == 3).then_some(game)
+    }
+
+    /// The moves as squares, up to the first that can't be read. Pawns always promote to
+    /// queens, so promotions don't need saying.
+    pub fn positions(&self) -> Vec<(Position, Position)> {
+        self.moves
+            .iter()
+            .map_while(|text| parse_move(text).map(|(from, to, _)| (from, to)))
+            .collect()
+    }
+}
+
+/// The time now, in whole seconds since the Unix epoch, for naming crash files.
+fn seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Saves the game and a crash log in `dir` whenever the program panics, after the default hook
+/// has printed the panic.
+pub fn install(dir: PathBuf) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+
+        let stamp = seconds();
+        if let Err(err) = fs::create_dir_all(&dir) {
+            error!(
+                "Couldn't create {} for the crash log: {}",
+                dir.display(),
+                err
+            );
+        }
+        let log = dir.join(format!("crash-{}.log", stamp));
+        let report = format!("{}\n\n{}\n", info, Backtrace::force_capture());
+        match fs::write(&log, report) {
+            Ok(()) => error!("Crash log written to {}", log.display()),
+            Err(err) => error!("Couldn't write the crash log {}: {}", log.display(), err),
+        }
+
+        // the panic may have happened while the snapshot was being replaced. A poisoned
+        // snapshot is still whole, but one that's locked can't be waited for
+        let current = match CURRENT.try_lock() {
+            Ok(current) => current,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        let snapshot = match current.as_ref() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        let path = dir.join(format!("crash-{}.txt", stamp));
+        match fs::write(&path, snapshot.game.to_text()) {
+            Ok(()) => error!("The game was saved to {}", path.display()),
+            Err(err) => error!("Couldn't save the game to {}: {}", path.display(), err),
+        }
+
+        // the network thread may still be running to pass it on
+        if let Some((sender, msg)) = &snapshot.leave {
+            let _ = sender.try_send(msg.clone());
+        }
+    }));
+}
+
+/// A game saved by a crash that hasn't been offered yet.
+pub struct Crash {
+    pub path: PathBuf,
+    pub game: SavedGame,
+}
+
+impl Crash {
+    /// The newest saved game in `dir`, if there is one. Games that have been offered are
+    /// renamed, so each is only offered once.
+    pub fn find(dir: &Path) -> Option<Crash> {
+        let newest = fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let stamp = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix("crash-")?
+                    .strip_suffix(".txt")?
+                    .parse::<u64>()
+                    .ok()?;
+                Some((stamp, path))
+            })
+            .max_by_key(|(stamp, _)| *stamp)?;
+
+        let path = newest.1;
+        let text = fs::read_to_string(&path).ok()?;
+        match SavedGame::parse(&text) {
+            Some(game) => Some(Crash { path, game }),
+            None => {
+                warn!("Ignoring {}, it isn't a saved game", path.display());
+                None
+            }
+        }
+    }
+
+    /// Marks the game as offered, so it isn't offered again.
+    pub fn dismiss(&self) {
+        let mut seen = self.path.clone().into_os_string();
+        seen.push(".seen");
+        match fs::rename(&self.path, &seen) {
+            Ok(()) => info!("Moved the saved game to {}", PathBuf::from(seen).display()),
+            Err(err) => warn!("Couldn't move {}: {}", self.path.display(), err),
+        }
+    }
+}
+
+/// Asks on startup whether to play on a game saved by a crash.
+pub struct ResumeOffer {
+    crash: Crash,
+    confirm: Confirm,
+}
+
+impl ResumeOffer {
+    pub fn new(crash: Crash) -> ResumeOffer {
+        let moves = crash.game.moves.len();
+        let question = format!(
+            "The game in room {} stopped after {} move{} when Schack crashed. Play it on here?",
+            crash.game.room,
+            moves,
+            if moves == 1 { "" } else { "s" }
+        );
+        ResumeOffer {
+            crash,
+            confirm: Confirm::new(question),
+        }
+    }
+
+    /// The answer once given, with the game to play on if it's yes. Either way, the game isn't
+    /// offered again.
+    pub fn take_answer(&mut self) -> Option<Option<SavedGame>> {
+        let yes = self.confirm.take_answer()?;
+        self.crash.dismiss();
+        Some(yes.then(|| self.crash.game.clone()))
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.2, 0.2, 0.2, 1.0].into());
+        self.confirm.draw(ctx)?;
+        graphics::present(ctx)
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.confirm.mouse_motion_event(x, y);
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.confirm.mouse_button_up_event(button, x, y);
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode, action: Option<Action>) {
+        self.confirm.key_down_event(key, action);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game() -> SavedGame {
+        SavedGame {
+            room: "myroom".to_string(),
+            fen: "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string(),
+            moves: vec!["e2e4".to_string()],
+        }
+    }
+
+    #[test]
+    fn saved_games_round_trip() {
+        let game = game();
+        assert_eq!(SavedGame::parse(&game.to_text()), Some(game));
+
+        let empty = SavedGame {
+            moves: Vec::new(),
+            ..self::game()
+        };
+        assert_eq!(SavedGame::parse(&empty.to_text()), Some(empty));
+        assert_eq!(SavedGame::parse("room myroom\n"), None);
+    }
+
+    #[test]
+    fn the_newest_crash_is_found_once() {
+        let dir = std::env::temp_dir().join(format!("crashes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let older = SavedGame {
+            room: "older".to_string(),
+            ..game()
+        };
+        fs::write(dir.join("crash-100.txt"), older.to_text()).unwrap();
+        fs::write(dir.join("crash-200.txt"), game().to_text()).unwrap();
+        fs::write(dir.join("crash-300.log"), "panicked").unwrap();
+
+        let crash = Crash::find(&dir).unwrap();
+        assert_eq!(crash.game, game());
+        crash.dismiss();
+        assert_eq!(Crash::find(&dir).unwrap().game, older);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
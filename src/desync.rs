@@ -0,0 +1,269 @@
+/**
+ * What's shown when the two games fall out of sync, over the frozen board: both move counters,
+ * the last few messages each way and our position as a FEN, for a screenshot or a bug report.
+ * The report can be copied to the clipboard, and abandoning the game goes on to the error
+ * dialog, which offers to reconnect.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::keys::Action;
+use crate::ui::{self, Button, Buttons};
+use ggez::{event, graphics, Context, GameResult};
+use log::{info, warn};
+
+/// How many messages each way go in the report.
+pub const RECENT_MESSAGES: usize = 5;
+
+/// Size of the box holding the report, centred on the screen, and of its buttons.
+const BOX_SIZE: (f32, f32) = (860.0, 640.0);
+const BUTTON_SIZE: (f32, f32) = (260.0, 50.0);
+
+/// Where the two games were when they fell out of sync.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub room: String,
+    pub local: u32,                                    // our move counter
+    pub remote: u32, // the counter on the opponent's move that didn't follow on from it
+    pub results: Option<(&'static str, &'static str)>, // ours and theirs, when those differ
+    pub sent: Vec<String>,
+    pub received: Vec<String>, // the last being the move that was out of sync
+    pub fen: String,           // our position
+}
+
+impl Report {
+    /// The report as plain text, for pasting into a bug report.
+    pub fn text(&self) -> String {
+        let messages = |list: &[String]| match list {
+            [] => "  (none)\n".to_string(),
+            _ => list.iter().map(|msg| format!("  {}\n", msg)).collect(),
+        };
+        let results = match self.results {
+            Some((ours, theirs)) => format!("Our result: {}\nTheir result: {}\n", ours, theirs),
+            None => String::new(),
+        };
+        format!(
+            "Out of sync in room {}\nOur move counter: {}\nTheir move counter: {}\n{}Our position: {}\nLast sent, oldest first:\n{}Last received, oldest first:\n{}",
+            self.room,
+            self.local,
+            self.remote,
+            results,
+            self.fen,
+            messages(&self.sent),
+            messages(&self.received)
+        )
+    }
+}
+
+/// The panel's buttons.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DesyncAction {
+    Copy,
+    Abandon,
+}
+
+pub struct DesyncPanel {
+    report: Report,
+    buttons: Buttons<DesyncAction>,
+    notice: Option<String>, // whether the report was copied
+    abandoned: bool,        // the player gave up on the game, until the scene acts on it
+}
+
+impl DesyncPanel {
+    pub fn new(report: Report) -> DesyncPanel {
+        let area = box_rect();
+        let rects = ui::row(
+            2,
+            BUTTON_SIZE,
+            20.0,
+            SCREEN_SIZE.0 / 2.0,
+            area.y + area.h - BUTTON_SIZE.1 - 20.0,
+        );
+        let buttons = vec![
+            Button::new(rects[0], "Copy report", DesyncAction::Copy),
+            Button::new(rects[1], "Abandon game", DesyncAction::Abandon),
+        ];
+        DesyncPanel {
+            report,
+            buttons: Buttons::new(buttons),
+            notice: None,
+            abandoned: false,
+        }
+    }
+
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Whether the player gave up on the game.
+    pub fn abandoned(&self) -> bool {
+        self.abandoned
+    }
+
+    /// Does what the button picked since the last input is for.
+    fn act(&mut self) {
+        match self.buttons.take_choice() {
+            Some(DesyncAction::Copy) => {
+                let text = self.report.text();
+                let copied = arboard::Clipboard::new().and_then(|mut c| c.set_text(text.clone()));
+                self.notice = Some(match copied {
+                    Ok(()) => "Copied, paste it into a bug report".to_string(),
+                    Err(err) => {
+                        warn!("Couldn't copy the desync report: {}", err);
+                        info!("{}", text);
+                        "Couldn't copy it, the report is in the log".to_string()
+                    }
+                });
+            }
+            Some(DesyncAction::Abandon) => self.abandoned = true,
+            None => (),
+        }
+    }
+
+    /// Draws the report over the game.
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        let shade = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1),
+            [0.0, 0.0, 0.0, 0.5].into(),
+        )?;
+        graphics::draw(ctx, &shade, graphics::DrawParam::default())?;
+
+        let area = box_rect();
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            area,
+            [1.0, 1.0, 1.0, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+        let mut title = graphics::Text::new(
+            graphics::TextFragment::from("The games are out of sync")
+                .scale(graphics::PxScale { x: 30.0, y: 30.0 }),
+        );
+        title.set_bounds([area.w - 40.0, f32::INFINITY], graphics::Align::Center);
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::DrawParam::default()
+                .color([0.7, 0.0, 0.0, 1.0].into())
+                .dest([area.x + 20.0, area.y + 20.0]),
+        )?;
+
+        let mut report = graphics::Text::new(
+            graphics::TextFragment::from(self.report.text())
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        report.set_bounds([area.w - 40.0, f32::INFINITY], graphics::Align::Left);
+        graphics::draw(
+            ctx,
+            &report,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([area.x + 20.0, area.y + 70.0]),
+        )?;
+
+        if let Some(notice) = &self.notice {
+            let mut text = graphics::Text::new(
+                graphics::TextFragment::from(notice.as_str())
+                    .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            text.set_bounds([area.w - 40.0, f32::INFINITY], graphics::Align::Center);
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.1, 0.4, 0.1, 1.0].into())
+                    .dest([area.x + 20.0, area.y + area.h - BUTTON_SIZE.1 - 55.0]),
+            )?;
+        }
+
+        self.buttons.draw(ctx)
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.buttons.mouse_motion_event(x, y);
+    }
+
+    pub fn mouse_button_down_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.buttons.mouse_button_down_event(button, x, y);
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.buttons.mouse_button_up_event(button, x, y);
+        self.act();
+    }
+
+    /// Left, Right and Tab move between the buttons and Enter picks one. Nothing backs out of the
+    /// panel, since there's no game left to go back to.
+    pub fn key_down_event(
+        &mut self,
+        key: event::KeyCode,
+        mods: event::KeyMods,
+        action: Option<Action>,
+    ) {
+        match key {
+            event::KeyCode::Left => self.buttons.focus_previous(),
+            event::KeyCode::Right => self.buttons.focus_next(),
+            _ if action == Some(Action::Pause) => (),
+            _ => {
+                self.buttons.key_down_event(key, mods);
+            }
+        }
+        self.act();
+    }
+}
+
+/// Where the box is on the screen.
+fn box_rect() -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - BOX_SIZE.0) / 2.0,
+        (SCREEN_SIZE.1 - BOX_SIZE.1) / 2.0,
+        BOX_SIZE.0,
+        BOX_SIZE.1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_report_lists_both_ways() {
+        let report = Report {
+            room: "room".to_string(),
+            local: 4,
+            remote: 7,
+            results: None,
+            sent: Vec::new(),
+            received: vec!["mv room 3 e2 e4".to_string(), "mv room 7 e2 e4".to_string()],
+            fen: "8/8/8/8/8/8/8/8 w - - 0 1".to_string(),
+        };
+        let text = report.text();
+        assert!(text.contains("Our move counter: 4\nTheir move counter: 7\n"));
+        assert!(text.contains("Last sent, oldest first:\n  (none)\n"));
+        assert!(text.ends_with("  mv room 7 e2 e4\n"));
+    }
+
+    #[test]
+    fn escape_doesnt_close_it_but_enter_abandons() {
+        let mut panel = DesyncPanel::new(Report {
+            room: "room".to_string(),
+            local: 1,
+            remote: 3,
+            results: Some(("1-0", "*")),
+            sent: Vec::new(),
+            received: Vec::new(),
+            fen: String::new(),
+        });
+        panel.key_down_event(
+            event::KeyCode::Escape,
+            event::KeyMods::NONE,
+            Some(Action::Pause),
+        );
+        panel.key_down_event(event::KeyCode::Right, event::KeyMods::NONE, None);
+        assert!(!panel.abandoned());
+        panel.key_down_event(event::KeyCode::Return, event::KeyMods::NONE, None);
+        assert!(panel.abandoned());
+    }
+}
@@ -0,0 +1,201 @@
+/**
+ * A dialog explaining why a game had to stop, with what can be done about it, shown instead of
+ * crashing or closing the window.
+ */
+use crate::app::SCREEN_SIZE;
+use ggez::{event, graphics, Context, GameResult};
+
+/// What the player can do from the dialog.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DialogAction {
+    Retry,
+    Menu,
+    Quit,
+}
+
+impl DialogAction {
+    fn label(self) -> &'static str {
+        match self {
+            DialogAction::Retry => "Retry",
+            DialogAction::Menu => "Main menu",
+            DialogAction::Quit => "Quit",
+        }
+    }
+}
+
+/// Server and room to connect to again when retrying.
+#[derive(Clone, Debug)]
+pub struct Retry {
+    pub server: String,
+    pub room: String,
+}
+
+/// Size of a button, and the gap between them.
+const BUTTON_SIZE: (f32, f32) = (180.0, 50.0);
+const BUTTON_GAP: f32 = 20.0;
+
+pub struct ErrorDialog {
+    message: String,
+    retry: Option<Retry>, // where to connect again, if retrying makes sense
+    actions: Vec<DialogAction>, // buttons, left to right
+    selected: usize,      // highlighted button
+    chosen: Option<DialogAction>, // button picked, until the scene acts on it
+}
+
+impl ErrorDialog {
+    /// A dialog with the message, offering to connect again if there's somewhere to retry.
+    pub fn new(message: impl Into<String>, retry: Option<Retry>) -> ErrorDialog {
+        let actions = match retry {
+            Some(_) => vec![DialogAction::Retry, DialogAction::Menu, DialogAction::Quit],
+            None => vec![DialogAction::Menu, DialogAction::Quit],
+        };
+        ErrorDialog {
+            message: message.into(),
+            retry,
+            actions,
+            selected: 0,
+            chosen: None,
+        }
+    }
+
+    /// Where to connect again, if the dialog offers to retry.
+    pub fn retry(&self) -> Option<&Retry> {
+        self.retry.as_ref()
+    }
+
+    /// The action picked since the last call, if any.
+    pub fn take_choice(&mut self) -> Option<DialogAction> {
+        self.chosen.take()
+    }
+
+    fn message_text(&self) -> graphics::Text {
+        let mut text = graphics::Text::new(
+            graphics::TextFragment::from(format!("Something went wrong\n\n{}", self.message))
+                .scale(graphics::PxScale { x: 26.0, y: 26.0 }),
+        );
+        text.set_bounds(
+            [SCREEN_SIZE.0 - 80.0, f32::INFINITY],
+            graphics::Align::Center,
+        );
+        text
+    }
+
+    /// Height of the message, and where it starts so the message and buttons are centred.
+    fn layout(ctx: &mut Context, text: &graphics::Text) -> (f32, f32) {
+        let height = text.dimensions(ctx).h;
+        (
+            height,
+            (SCREEN_SIZE.1 - height - BUTTON_SIZE.1) / 2.0 - 40.0,
+        )
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        let text = self.message_text();
+        let (height, top) = ErrorDialog::layout(ctx, &text);
+
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                20.0,
+                top - 20.0,
+                SCREEN_SIZE.0 - 40.0,
+                height + BUTTON_SIZE.1 + 80.0,
+            ),
+            [1.0, 1.0, 1.0, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color([0.7, 0.0, 0.0, 1.0].into())
+                .dest([40.0, top]),
+        )?;
+
+        for (i, action) in self.actions.iter().enumerate() {
+            let rect = self.button_rect(i, top + height + 30.0);
+            let colour = if i == self.selected {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let button = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &button, graphics::DrawParam::default())?;
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(action.label())
+                    .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            let label_size = label.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([
+                        rect.x + (rect.w - label_size.w) / 2.0,
+                        rect.y + (rect.h - label_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+
+        graphics::present(ctx)
+    }
+
+    /// Where a button is on the screen, given the top edge of the row of buttons.
+    fn button_rect(&self, i: usize, top: f32) -> graphics::Rect {
+        let count = self.actions.len() as f32;
+        let width = count * BUTTON_SIZE.0 + (count - 1.0) * BUTTON_GAP;
+        graphics::Rect::new(
+            (SCREEN_SIZE.0 - width) / 2.0 + i as f32 * (BUTTON_SIZE.0 + BUTTON_GAP),
+            top,
+            BUTTON_SIZE.0,
+            BUTTON_SIZE.1,
+        )
+    }
+
+    pub fn mouse_button_up_event(
+        &mut self,
+        ctx: &mut Context,
+        button: event::MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        if button != event::MouseButton::Left {
+            return;
+        }
+
+        // the buttons sit under the message, so lay it out the same way draw does
+        let (height, top) = ErrorDialog::layout(ctx, &self.message_text());
+
+        if let Some(i) = (0..self.actions.len())
+            .find(|&i| self.button_rect(i, top + height + 30.0).contains([x, y]))
+        {
+            self.selected = i;
+            self.chosen = Some(self.actions[i]);
+        }
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode) {
+        let count = self.actions.len();
+        match key {
+            event::KeyCode::Left => self.selected = (self.selected + count - 1) % count,
+            event::KeyCode::Right | event::KeyCode::Tab => {
+                self.selected = (self.selected + 1) % count
+            }
+            event::KeyCode::Return | event::KeyCode::NumpadEnter | event::KeyCode::Space => {
+                self.chosen = Some(self.actions[self.selected])
+            }
+            event::KeyCode::Escape => self.chosen = Some(DialogAction::Menu),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,494 @@
+/**
+ * Setting up a position by hand. A piece picked from the palette beside the board is placed on
+ * every square clicked, and right-clicking a square empties it. Ctrl+V pastes a position in FEN.
+ *
+ * The chess library can only start a game from the initial position, so a position set up here
+ * can't be played. It's checked and copied to the clipboard in FEN instead, for another program
+ * to load.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::board::{king_attacked, square_at, BOARD_ORIGIN, BOARD_SIZE, GRID_CELL_SIZE};
+use crate::history::{parse_placement, placement};
+use crate::layout;
+use crate::render::{self, BoardFrame, BoardState, MarkerMeshes, MoveMarkers, PieceRenderer};
+use crate::ui::{self, Button, Buttons};
+use chess_template::{Colour, Game, Piece, PieceType, Position};
+use ggez::{event, graphics, Context, GameResult};
+use log::{info, warn};
+
+/// Size of a square in the palette, and where the palette starts. White's pieces are in the
+/// first column and Black's in the second, in the order of `PALETTE`.
+const PALETTE_CELL: f32 = 60.0;
+const PALETTE_ORIGIN: (f32, f32) = (BOARD_SIZE.0 + 40.0, 20.0);
+const PALETTE: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+/// Size of a button, and where the first one is drawn, under the palette.
+const BUTTON_SIZE: (f32, f32) = (180.0, 40.0);
+const BUTTON_TOP: f32 = 400.0;
+const BUTTON_GAP: f32 = 8.0;
+
+/// Shown under the board until something else needs saying.
+const HINT: &str = "Pick a piece on the right, then click squares to place it. Right-click empties a square, Ctrl+V pastes a FEN";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum EditorAction {
+    SideToMove,
+    Clear,
+    StartPosition,
+    CopyFen,
+    Back,
+}
+
+/// Buttons in the order they're shown.
+const ACTIONS: [EditorAction; 5] = [
+    EditorAction::SideToMove,
+    EditorAction::Clear,
+    EditorAction::StartPosition,
+    EditorAction::CopyFen,
+    EditorAction::Back,
+];
+
+impl EditorAction {
+    fn label(self) -> &'static str {
+        match self {
+            EditorAction::SideToMove => side_label(Colour::White),
+            EditorAction::Clear => "Clear",
+            EditorAction::StartPosition => "Start Position",
+            EditorAction::CopyFen => "Copy FEN",
+            EditorAction::Back => "Back",
+        }
+    }
+}
+
+fn side_label(colour: Colour) -> &'static str {
+    match colour {
+        Colour::White => "White to move",
+        Colour::Black => "Black to move",
+    }
+}
+
+pub struct BoardEditor {
+    board: [Option<Piece>; 64],
+    active: Colour,       // side to move
+    brush: Option<Piece>, // picked from the palette, placed on the squares clicked
+    buttons: Buttons<EditorAction>,
+    notice: Option<String>, // shown under the board instead of the hint, e.g. why a copy failed
+    leaving: bool,          // back to the main menu
+    frame: BoardFrame,
+    markers: MarkerMeshes,
+}
+
+impl Default for BoardEditor {
+    fn default() -> BoardEditor {
+        BoardEditor::new()
+    }
+}
+
+impl BoardEditor {
+    /// The editor with the starting position set up, White to move.
+    pub fn new() -> BoardEditor {
+        let rects = ui::column(
+            ACTIONS.len(),
+            BUTTON_SIZE,
+            BUTTON_GAP,
+            (BOARD_SIZE.0 + SCREEN_SIZE.0) / 2.0,
+            BUTTON_TOP,
+        );
+        let buttons = ACTIONS
+            .iter()
+            .zip(rects)
+            .map(|(&action, rect)| Button::new(rect, action.label(), action))
+            .collect();
+        BoardEditor {
+            board: Game::new().get_board(),
+            active: Colour::White,
+            brush: None,
+            buttons: Buttons::new(buttons),
+            notice: None,
+            leaving: false,
+            frame: BoardFrame::default(),
+            markers: MarkerMeshes::default(),
+        }
+    }
+
+    pub fn leaving(&self) -> bool {
+        self.leaving
+    }
+
+    fn set_active(&mut self, colour: Colour) {
+        self.active = colour;
+        self.buttons
+            .set_label(EditorAction::SideToMove, side_label(colour));
+    }
+
+    /// Acts on the button picked since the last call, if any.
+    pub fn update(&mut self) {
+        match self.buttons.take_choice() {
+            Some(EditorAction::SideToMove) => self.set_active(match self.active {
+                Colour::White => Colour::Black,
+                Colour::Black => Colour::White,
+            }),
+            Some(EditorAction::Clear) => self.board = [None; 64],
+            Some(EditorAction::StartPosition) => {
+                self.board = Game::new().get_board();
+                self.set_active(Colour::White);
+            }
+            Some(EditorAction::CopyFen) => self.copy_fen(),
+            Some(EditorAction::Back) => self.leaving = true,
+            None => (),
+        }
+    }
+
+    /// Checks the position, and copies it to the clipboard in FEN if it could be played.
+    fn copy_fen(&mut self) {
+        if let Err(reason) = check_position(&self.board, self.active) {
+            self.notice = Some(reason.to_string());
+            return;
+        }
+
+        let fen = position_fen(&self.board, self.active);
+        let copied = arboard::Clipboard::new().and_then(|mut c| c.set_text(fen.clone()));
+        self.notice = Some(match copied {
+            Ok(()) => "The position was copied in FEN".to_string(),
+            Err(err) => {
+                warn!("Couldn't copy the position: {}", err);
+                info!("{}", fen);
+                "Couldn't copy the position, its FEN is in the log".to_string()
+            }
+        });
+    }
+
+    /// Sets up the position from a FEN on the clipboard.
+    fn paste(&mut self) {
+        let text = match arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("Couldn't paste from the clipboard: {}", err);
+                self.notice = Some("Couldn't paste from the clipboard".to_string());
+                return;
+            }
+        };
+        match parse_placement(&text) {
+            Some(board) => {
+                self.board = board;
+                let black = text.split_whitespace().nth(1) == Some("b");
+                self.set_active(if black { Colour::Black } else { Colour::White });
+                self.notice = None;
+            }
+            None => self.notice = Some("The clipboard doesn't hold a FEN position".to_string()),
+        }
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, pieces: &dyn PieceRenderer) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+        self.frame.draw(ctx, false)?;
+
+        let state = BoardState {
+            board: self.board,
+            pieces_hidden: false,
+            dots_hidden: false,
+            selected: None,
+            highlighted: &[],
+            attacked: &[],
+            hanging: &[],
+            dots: &[],
+            captures: &[],
+            markers: MoveMarkers::default(),
+            repetition: None,
+            check_pulse: None,
+            flipped: false,
+            cursor: None,
+            palette: render::Palette::Wood,
+            heat: None,
+        };
+        let mut screen = render::Screen {
+            ctx,
+            pieces,
+            markers: &mut self.markers,
+        };
+        render::draw_board(&mut screen, &render::squares(&state))?;
+
+        self.draw_palette(ctx, pieces)?;
+        self.buttons.draw(ctx)?;
+
+        let mut text = graphics::Text::new(
+            graphics::TextFragment::from(self.notice.as_deref().unwrap_or(HINT))
+                .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        text.set_bounds([BOARD_SIZE.0 - 10.0, f32::INFINITY], graphics::Align::Left);
+        let colour = if self.notice.is_some() {
+            [0.7, 0.0, 0.0, 1.0]
+        } else {
+            [0.0, 0.0, 0.0, 1.0]
+        };
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color(colour.into())
+                .dest([5.0, BOARD_SIZE.1 + 2.0]),
+        )?;
+
+        graphics::present(ctx)
+    }
+
+    fn draw_palette(&self, ctx: &mut Context, pieces: &dyn PieceRenderer) -> GameResult {
+        for (piece, rect) in palette() {
+            let picked = self.brush.is_some_and(|brush| {
+                brush.colour == piece.colour && brush.piece_type == piece.piece_type
+            });
+            let colour = if picked {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.6, 0.6, 0.6, 1.0]
+            };
+            let cell = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &cell, graphics::DrawParam::default())?;
+        }
+
+        // pieces are drawn on board squares, so the palette is drawn as a smaller board whose
+        // squares line up with its cells
+        let scale = PALETTE_CELL / GRID_CELL_SIZE.0 as f32;
+        let screen = graphics::screen_coordinates(ctx);
+        graphics::set_screen_coordinates(
+            ctx,
+            layout::within(
+                screen,
+                graphics::Rect::new(
+                    BOARD_ORIGIN.0 - PALETTE_ORIGIN.0 / scale,
+                    BOARD_ORIGIN.1 - PALETTE_ORIGIN.1 / scale,
+                    SCREEN_SIZE.0 / scale,
+                    SCREEN_SIZE.1 / scale,
+                ),
+            ),
+        )?;
+        let drawn = palette().try_for_each(|(piece, rect)| {
+            let row = ((rect.y - PALETTE_ORIGIN.1) / PALETTE_CELL) as usize;
+            let col = ((rect.x - PALETTE_ORIGIN.0) / PALETTE_CELL) as usize;
+            match Position::new(row, col) {
+                Ok(pos) => pieces.draw_piece(ctx, piece, pos, None),
+                Err(_) => Ok(()),
+            }
+        });
+        graphics::set_screen_coordinates(ctx, screen)?;
+        drawn
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.buttons.mouse_motion_event(x, y);
+    }
+
+    pub fn mouse_button_down_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.buttons.mouse_button_down_event(button, x, y);
+    }
+
+    /// Picks a piece from the palette, or places or removes one on the board.
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.buttons.mouse_button_up_event(button, x, y);
+
+        if let Some(piece) = palette_at(x, y) {
+            if button == event::MouseButton::Left {
+                self.brush = Some(piece);
+            }
+            return;
+        }
+        let pos = match square_at(x, y) {
+            Some(pos) => pos,
+            None => return,
+        };
+        let square = &mut self.board[pos.row * 8 + pos.col];
+        match button {
+            event::MouseButton::Left => match self.brush {
+                Some(piece) => *square = Some(piece),
+                None => self.notice = Some("Pick a piece on the right first".to_string()),
+            },
+            event::MouseButton::Right => *square = None,
+            _ => (),
+        }
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        match key {
+            event::KeyCode::V if mods.contains(event::KeyMods::CTRL) => self.paste(),
+            event::KeyCode::Escape => self.buttons.pick(EditorAction::Back),
+            _ => {
+                self.buttons.key_down_event(key, mods);
+            }
+        }
+    }
+}
+
+/// Every piece in the palette, with its cell.
+fn palette() -> impl Iterator<Item = (Piece, graphics::Rect)> {
+    [Colour::White, Colour::Black]
+        .into_iter()
+        .enumerate()
+        .flat_map(|(col, colour)| {
+            PALETTE.iter().enumerate().map(move |(row, &piece_type)| {
+                let rect = graphics::Rect::new(
+                    PALETTE_ORIGIN.0 + col as f32 * PALETTE_CELL,
+                    PALETTE_ORIGIN.1 + row as f32 * PALETTE_CELL,
+                    PALETTE_CELL,
+                    PALETTE_CELL,
+                );
+                (Piece { piece_type, colour }, rect)
+            })
+        })
+}
+
+/// The palette piece at a point, if any.
+fn palette_at(x: f32, y: f32) -> Option<Piece> {
+    palette()
+        .find(|(_, rect)| rect.contains([x, y]))
+        .map(|(piece, _)| piece)
+}
+
+/// Why a position can't be played, if it can't: each side needs one king, pawns can't stand
+/// on the first or last rank, and the side that just moved can't have left its king in check.
+pub fn check_position(board: &[Option<Piece>; 64], active: Colour) -> Result<(), &'static str> {
+    for colour in [Colour::White, Colour::Black] {
+        let kings = board
+            .iter()
+            .filter(|p| p.is_some_and(|p| p.colour == colour && p.piece_type == PieceType::King))
+            .count();
+        if kings != 1 {
+            return Err("Each side needs exactly one king");
+        }
+    }
+
+    let back_ranks = board[..8].iter().chain(&board[56..]);
+    if back_ranks
+        .clone()
+        .any(|p| p.is_some_and(|p| p.piece_type == PieceType::Pawn))
+    {
+        return Err("Pawns can't stand on the first or last rank");
+    }
+
+    let waiting = match active {
+        Colour::White => Colour::Black,
+        Colour::Black => Colour::White,
+    };
+    if king_attacked(board, active) && king_attacked(board, waiting) {
+        return Err("Both kings can't be in check");
+    }
+    if king_attacked(board, waiting) {
+        return Err("The side that isn't moving can't be in check");
+    }
+    Ok(())
+}
+
+/// The position in FEN. Castling is allowed wherever the king and rook are still on their
+/// starting squares, and there's no en passant square, as no move has been played.
+pub fn position_fen(board: &[Option<Piece>; 64], active: Colour) -> String {
+    let on = |row: usize, col: usize, colour: Colour, piece_type: PieceType| {
+        board[row * 8 + col].is_some_and(|p| p.colour == colour && p.piece_type == piece_type)
+    };
+    let castling: String = [
+        ('K', Colour::White, 7, 7),
+        ('Q', Colour::White, 7, 0),
+        ('k', Colour::Black, 0, 7),
+        ('q', Colour::Black, 0, 0),
+    ]
+    .iter()
+    .filter(|&&(_, colour, row, rook)| {
+        on(row, 4, colour, PieceType::King) && on(row, rook, colour, PieceType::Rook)
+    })
+    .map(|(letter, ..)| *letter)
+    .collect();
+    let castling = if castling.is_empty() {
+        "-".to_string()
+    } else {
+        castling
+    };
+    let active = match active {
+        Colour::White => "w",
+        Colour::Black => "b",
+    };
+    format!("{} {} {} - 0 1", placement(board), active, castling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> [Option<Piece>; 64] {
+        parse_placement(fen).unwrap()
+    }
+
+    #[test]
+    fn positions_are_checked_before_copying() {
+        let start = Game::new().get_board();
+        assert_eq!(check_position(&start, Colour::White), Ok(()));
+
+        let no_black_king = board("8/8/8/8/8/8/8/4K3");
+        assert!(check_position(&no_black_king, Colour::White).is_err());
+        let two_white_kings = board("4k3/8/8/8/8/8/8/3KK3");
+        assert!(check_position(&two_white_kings, Colour::White).is_err());
+
+        let pawn_on_the_last_rank = board("3Pk3/8/8/8/8/8/8/4K3");
+        assert_eq!(
+            check_position(&pawn_on_the_last_rank, Colour::White),
+            Err("Pawns can't stand on the first or last rank")
+        );
+
+        // Black in check from the rook is fine with Black to move, but not White
+        let rook_check = board("4k3/8/8/8/4R3/8/8/K7");
+        assert_eq!(check_position(&rook_check, Colour::Black), Ok(()));
+        assert!(check_position(&rook_check, Colour::White).is_err());
+
+        let both_in_check = board("4k3/8/8/4R3/4r3/8/8/4K3");
+        assert_eq!(
+            check_position(&both_in_check, Colour::White),
+            Err("Both kings can't be in check")
+        );
+    }
+
+    #[test]
+    fn fen_of_a_set_up_position() {
+        let start = Game::new().get_board();
+        assert_eq!(
+            position_fen(&start, Colour::White),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        assert_eq!(
+            position_fen(&board("r3k3/8/8/8/8/8/8/4K2R"), Colour::Black),
+            "r3k3/8/8/8/8/8/8/4K2R b Kq - 0 1"
+        );
+        assert_eq!(
+            position_fen(&board("4k3/8/8/8/8/8/8/3K3R"), Colour::White),
+            "4k3/8/8/8/8/8/8/3K3R w - - 0 1"
+        );
+    }
+
+    #[test]
+    fn palette_cells_hold_each_piece_once() {
+        let piece = palette_at(PALETTE_ORIGIN.0 + 1.0, PALETTE_ORIGIN.1 + 1.0).unwrap();
+        assert!(piece.colour == Colour::White && piece.piece_type == PieceType::King);
+        let piece = palette_at(
+            PALETTE_ORIGIN.0 + PALETTE_CELL + 1.0,
+            PALETTE_ORIGIN.1 + 5.0 * PALETTE_CELL + 1.0,
+        )
+        .unwrap();
+        assert!(piece.colour == Colour::Black && piece.piece_type == PieceType::Pawn);
+        assert!(palette_at(PALETTE_ORIGIN.0 - 1.0, PALETTE_ORIGIN.1 + 1.0).is_none());
+
+        // the palette stays beside the board and above the buttons
+        let cells: Vec<_> = palette().map(|(_, rect)| rect).collect();
+        assert_eq!(cells.len(), 12);
+        assert!(cells.iter().all(|rect| rect.x >= BOARD_SIZE.0
+            && rect.right() <= SCREEN_SIZE.0
+            && rect.bottom() <= BUTTON_TOP));
+    }
+}
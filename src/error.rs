@@ -0,0 +1,58 @@
+/**
+ * Things that can go wrong while playing. Losing the connection and falling out of sync stop the
+ * game with an error dialog; anything else is shown in a toast and play goes on. Resources that
+ * can't be loaded are only logged, and pieces without an image are drawn as shapes.
+ */
+use crate::protocol::DecodeError;
+use ggez::GameError;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum AppError {
+    /// A file like a piece sprite couldn't be loaded.
+    MissingResource(String, GameError),
+    /// The network thread has stopped, so nothing more can be sent or received.
+    Disconnected,
+    /// The opponent's move counter doesn't follow on from ours, so the games have diverged.
+    Desync { remote: u32, local: u32 },
+    /// The opponent's game ended differently from ours, given as results like "1-0".
+    ResultMismatch {
+        theirs: &'static str,
+        ours: &'static str,
+    },
+    /// A message from the server couldn't be understood.
+    BadMessage(String, DecodeError),
+    /// The `--movelog` file couldn't be written, so no more moves are logged to it.
+    MoveLog(io::Error),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::MissingResource(path, err) => {
+                write!(f, "Couldn't load {}: {}", path, err)
+            }
+            AppError::Disconnected => write!(f, "Lost connection with the server"),
+            AppError::Desync { remote, local } => write!(
+                f,
+                "Out of sync with the opponent: their game is on move {}, ours is on move {}",
+                remote, local
+            ),
+            AppError::ResultMismatch { theirs, ours } => write!(
+                f,
+                "Out of sync with the opponent: their game ended {}, ours ended {}",
+                theirs, ours
+            ),
+            AppError::BadMessage(msg, err) => write!(
+                f,
+                "Ignored a message the game didn't understand ({}): {}",
+                err,
+                msg.trim_end()
+            ),
+            AppError::MoveLog(err) => write!(f, "Stopped writing the move log: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
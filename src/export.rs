@@ -0,0 +1,358 @@
+/**
+ * Positions written out as SVG diagrams, for putting in documents: the squares in the board's
+ * colours, the pieces as chess symbols, and if asked for the coordinates around the squares and
+ * the last move. It's only text put together in a string, so none of it needs ggez.
+ * Exported games and diagrams are saved under `games/`, named after the day and the room.
+ */
+use crate::board::{glyphs, square_name};
+use chess_template::{Colour, Piece, Position};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Size of a square in the diagram, and of the border the coordinates are written on. The same
+/// proportions as the window's board.
+const SQUARE: f32 = 45.0;
+const MARGIN: f32 = 12.0;
+
+/// Size of the symbols, as a fraction of a square, and of the coordinates.
+const GLYPH_SCALE: f32 = 0.9;
+const LABEL_SIZE: f32 = 9.0;
+
+/// Fonts with the chess symbols in them, tried in order by whatever shows the diagram.
+const FONTS: &str = "DejaVu Sans, Segoe UI Symbol, Noto Sans Symbols2, serif";
+
+/// How a diagram looks. Colours are red, green, blue and alpha from 0 to 1, like the window's.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub white_squares: [f32; 4], // the squares drawn in the WHITE colour, a8 among them
+    pub black_squares: [f32; 4],
+    pub white_pieces: [f32; 4], // each side's symbols are edged in the other's colour
+    pub black_pieces: [f32; 4],
+    pub highlight: [f32; 4], // over the squares of the last move
+    pub frame: [f32; 4],     // the border around the squares
+    pub ink: [f32; 4],       // the coordinates, and the line between the border and the squares
+    pub coordinates: bool,   // whether there's a border with the coordinates written on it
+}
+
+/// A standalone SVG of `board`, with the squares of `last_move` highlighted if there is one.
+pub fn svg(
+    board: &[Option<Piece>; 64],
+    last_move: Option<(Position, Position)>,
+    style: &Style,
+) -> String {
+    let margin = if style.coordinates { MARGIN } else { 0.0 };
+    let size = 8.0 * SQUARE + 2.0 * margin;
+    let corner = |pos: Position| {
+        (
+            margin + pos.col as f32 * SQUARE,
+            margin + pos.row as f32 * SQUARE,
+        )
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">\n",
+        size
+    );
+    if style.coordinates {
+        svg.push_str(&format!(
+            "  <rect class=\"frame\" width=\"{0}\" height=\"{0}\"{1}/>\n",
+            size,
+            fill(style.frame)
+        ));
+    }
+
+    svg.push_str("  <g class=\"squares\">\n");
+    for pos in positions() {
+        let (x, y) = corner(pos);
+        let colour = if (pos.row + pos.col) % 2 == 0 {
+            style.white_squares
+        } else {
+            style.black_squares
+        };
+        svg.push_str(&format!(
+            "    <rect x=\"{}\" y=\"{}\" width=\"{3}\" height=\"{3}\"{}/>\n",
+            x,
+            y,
+            fill(colour),
+            SQUARE
+        ));
+    }
+    svg.push_str("  </g>\n");
+
+    if let Some((from, to)) = last_move {
+        svg.push_str("  <g class=\"last-move\">\n");
+        for pos in [from, to] {
+            let (x, y) = corner(pos);
+            svg.push_str(&format!(
+                "    <rect x=\"{}\" y=\"{}\" width=\"{3}\" height=\"{3}\"{}/>\n",
+                x,
+                y,
+                fill(style.highlight),
+                SQUARE
+            ));
+        }
+        svg.push_str("  </g>\n");
+    }
+
+    if style.coordinates {
+        svg.push_str(&format!(
+            "  <rect x=\"{0}\" y=\"{0}\" width=\"{1}\" height=\"{1}\" fill=\"none\" stroke=\"{2}\"/>\n",
+            margin,
+            8.0 * SQUARE,
+            hex(style.ink)
+        ));
+        svg.push_str(&format!(
+            "  <g class=\"coordinates\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\"{}>\n",
+            LABEL_SIZE,
+            fill(style.ink)
+        ));
+        for i in 0..8 {
+            // a file under each column, and a rank left of each row
+            let file = (b'a' + i as u8) as char;
+            let x = margin + (i as f32 + 0.5) * SQUARE;
+            svg.push_str(&format!(
+                "    <text x=\"{}\" y=\"{}\">{}</text>\n",
+                x,
+                size - margin / 2.0,
+                file
+            ));
+            let y = margin + (i as f32 + 0.5) * SQUARE;
+            svg.push_str(&format!(
+                "    <text x=\"{}\" y=\"{}\">{}</text>\n",
+                margin / 2.0,
+                y,
+                8 - i
+            ));
+        }
+        svg.push_str("  </g>\n");
+    }
+
+    svg.push_str(&format!(
+        "  <g class=\"pieces\" font-family=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">\n",
+        FONTS,
+        SQUARE * GLYPH_SCALE
+    ));
+    for pos in positions() {
+        let piece = match board[pos.row * 8 + pos.col] {
+            Some(piece) => piece,
+            None => continue,
+        };
+        let (colour, edge) = match piece.colour {
+            Colour::White => (style.white_pieces, style.black_pieces),
+            Colour::Black => (style.black_pieces, style.white_pieces),
+        };
+        let (filled, outline) = glyphs(piece.piece_type);
+        let (x, y) = corner(pos);
+        let (x, y) = (x + SQUARE / 2.0, y + SQUARE / 2.0);
+
+        // the filled symbol in the piece's colour, edged with the outline symbol in the other's
+        svg.push_str(&format!(
+            "    <g class=\"piece\" data-square=\"{}\">\n",
+            square_name(pos)
+        ));
+        for (symbol, colour) in [(filled, colour), (outline, edge)] {
+            svg.push_str(&format!(
+                "      <text x=\"{}\" y=\"{}\"{}>{}</text>\n",
+                x,
+                y,
+                fill(colour),
+                symbol
+            ));
+        }
+        svg.push_str("    </g>\n");
+    }
+    svg.push_str("  </g>\n</svg>\n");
+    svg
+}
+
+/// Every square, in reading order from a8.
+fn positions() -> impl Iterator<Item = Position> {
+    (0..64).filter_map(|i| Position::new(i / 8, i % 8).ok())
+}
+
+/// A colour as `#rrggbb`, leaving out its alpha.
+fn hex([r, g, b, _]: [f32; 4]) -> String {
+    let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b))
+}
+
+/// The `fill` attribute for a colour, with its opacity if it's see-through.
+fn fill(colour: [f32; 4]) -> String {
+    if colour[3] < 1.0 {
+        format!(" fill=\"{}\" fill-opacity=\"{}\"", hex(colour), colour[3])
+    } else {
+        format!(" fill=\"{}\"", hex(colour))
+    }
+}
+
+/// Where something exported from a game in `room` at `now` is written, the day and room followed
+/// by `suffix`, e.g. `games/2026-10-16-fredagsschack.gif`. Characters that don't belong in a
+/// file name are left out of the room.
+pub fn game_path(room: &str, now: SystemTime, suffix: &str) -> PathBuf {
+    let room: String = room
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    PathBuf::from("games").join(format!("{}-{}{}", date(now), room, suffix))
+}
+
+/// The day of `now` in UTC, e.g. "2026-10-16".
+pub fn date(now: SystemTime) -> String {
+    let days = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400);
+    let (year, month, day) = civil_date(days as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The year, month and day some days after 1970-01-01, in the proleptic Gregorian calendar.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    // counted in 400-year eras from 0000-03-01, so leap days fall at the end of each year
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_square;
+    use crate::history::parse_placement;
+    use chess_template::Game;
+    use std::path::Path;
+
+    const STYLE: Style = Style {
+        white_squares: [1.0, 1.0, 1.0, 1.0],
+        black_squares: [0.0, 0.5, 0.0, 1.0],
+        white_pieces: [1.0, 1.0, 1.0, 1.0],
+        black_pieces: [0.0, 0.0, 0.0, 1.0],
+        highlight: [0.2, 0.4, 1.0, 0.4],
+        frame: [0.4, 0.2, 0.0, 1.0],
+        ink: [1.0, 0.0, 0.0, 1.0],
+        coordinates: false,
+    };
+
+    /// The contents of the group with `class`, which must be there.
+    fn group<'a>(svg: &'a str, class: &str) -> &'a str {
+        let start = svg
+            .find(&format!("<g class=\"{}\"", class))
+            .unwrap_or_else(|| panic!("no {} in {}", class, svg));
+        let inner = &svg[start..];
+        // groups only nest inside the pieces, whose own groups close on the same lines
+        let end = inner.find("\n  </g>").unwrap();
+        &inner[..end]
+    }
+
+    /// The piece group on a square, e.g. `<g class="piece" data-square="e1">` and its symbols.
+    fn piece<'a>(svg: &'a str, square: &str) -> Option<&'a str> {
+        let start = svg.find(&format!("data-square=\"{}\"", square))?;
+        let inner = &svg[start..];
+        Some(&inner[..inner.find("</g>").unwrap()])
+    }
+
+    #[test]
+    fn the_start_position_has_every_square_and_piece() {
+        let svg = svg(&Game::new().get_board(), None, &STYLE);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"360\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g").count(), svg.matches("</g>").count());
+
+        // a8 is drawn in the WHITE colour, like on the screen, and the squares alternate
+        let squares = group(&svg, "squares");
+        assert_eq!(squares.matches("<rect").count(), 64);
+        assert!(
+            squares.contains("<rect x=\"0\" y=\"0\" width=\"45\" height=\"45\" fill=\"#ffffff\"/>")
+        );
+        assert!(squares
+            .contains("<rect x=\"45\" y=\"0\" width=\"45\" height=\"45\" fill=\"#008000\"/>"));
+
+        assert_eq!(group(&svg, "pieces").matches("class=\"piece\"").count(), 32);
+        let king = piece(&svg, "e1").unwrap();
+        assert!(king.contains("<text x=\"202.5\" y=\"337.5\" fill=\"#ffffff\">♚</text>"));
+        assert!(king.contains("fill=\"#000000\">♔</text>"));
+        let pawn = piece(&svg, "d7").unwrap();
+        assert!(pawn.contains("fill=\"#000000\">♟</text>"));
+        assert!(piece(&svg, "e4").is_none());
+
+        // nothing that wasn't asked for
+        assert!(!svg.contains("last-move") && !svg.contains("coordinates"));
+    }
+
+    #[test]
+    fn a_promoted_piece_is_drawn_with_the_move_that_made_it() {
+        let board = parse_placement("k2Q4/8/1K6/8/8/8/8/3Q4").unwrap();
+        let promotion = (parse_square("d7").unwrap(), parse_square("d8").unwrap());
+        let svg = svg(&board, Some(promotion), &STYLE);
+
+        assert_eq!(group(&svg, "pieces").matches("class=\"piece\"").count(), 4);
+        assert_eq!(svg.matches("♛").count(), 2);
+        assert!(piece(&svg, "d8")
+            .unwrap()
+            .contains("fill=\"#ffffff\">♛</text>"));
+
+        // the move's squares are washed see-through, over the squares and under the pieces
+        let highlight = group(&svg, "last-move");
+        assert_eq!(highlight.matches("<rect").count(), 2);
+        assert!(highlight.contains(
+            "<rect x=\"135\" y=\"45\" width=\"45\" height=\"45\" fill=\"#3366ff\" fill-opacity=\"0.4\"/>"
+        ));
+        let order = [
+            "class=\"squares\"",
+            "class=\"last-move\"",
+            "class=\"pieces\"",
+        ]
+        .map(|class| svg.find(class).unwrap());
+        assert!(order[0] < order[1] && order[1] < order[2]);
+    }
+
+    #[test]
+    fn coordinates_are_written_on_a_border() {
+        let style = Style {
+            coordinates: true,
+            ..STYLE
+        };
+        let svg = svg(&Game::new().get_board(), None, &style);
+        assert!(svg.contains("width=\"384\" height=\"384\" viewBox=\"0 0 384 384\""));
+        assert!(
+            svg.contains("<rect class=\"frame\" width=\"384\" height=\"384\" fill=\"#663300\"/>")
+        );
+
+        // the squares move in to make room
+        assert!(group(&svg, "squares").contains("<rect x=\"12\" y=\"12\""));
+        let labels = group(&svg, "coordinates");
+        assert_eq!(labels.matches("<text").count(), 16);
+        assert!(labels.contains("fill=\"#ff0000\""));
+        assert!(labels.contains("<text x=\"34.5\" y=\"378\">a</text>"));
+        assert!(labels.contains("<text x=\"6\" y=\"34.5\">8</text>"));
+        assert!(labels.contains("<text x=\"6\" y=\"349.5\">1</text>"));
+    }
+
+    #[test]
+    fn exports_are_named_after_the_day_and_room() {
+        // 2026-10-16, a little after noon
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(1_792_152_000);
+        assert_eq!(
+            game_path("fredags schack/../", now, ".gif"),
+            Path::new("games/2026-10-16-fredagsschack.gif")
+        );
+        assert_eq!(
+            game_path("room", now, "-12.svg"),
+            Path::new("games/2026-10-16-room-12.svg")
+        );
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,51 @@
+/**
+ * The screen between moves of a hotseat game, when the players pass the device across the desk.
+ * It hides the position until the next player clicks or presses a key, so the one handing it over
+ * doesn't see the other's first look at it. It's never shown online, where each player has a
+ * board of their own.
+ */
+use crate::app::SCREEN_SIZE;
+use chess_template::Colour;
+use ggez::{graphics, Context, GameResult};
+
+/// What the screen says, for the side about to move.
+fn title(colour: Colour) -> &'static str {
+    match colour {
+        Colour::White => "White to move",
+        Colour::Black => "Black to move",
+    }
+}
+
+/// Draws the screen over the whole game, hiding it, for `colour` to take over.
+pub fn draw(ctx: &mut Context, colour: Colour) -> GameResult {
+    let cover = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1),
+        [0.15, 0.15, 0.15, 1.0].into(),
+    )?;
+    graphics::draw(ctx, &cover, graphics::DrawParam::default())?;
+
+    let lines = [
+        (title(colour), 50.0, SCREEN_SIZE.1 / 2.0 - 60.0),
+        (
+            "Pass the device, then click or press a key to show the board",
+            24.0,
+            SCREEN_SIZE.1 / 2.0 + 10.0,
+        ),
+    ];
+    for (line, size, y) in lines {
+        let text = graphics::Text::new(
+            graphics::TextFragment::from(line).scale(graphics::PxScale { x: size, y: size }),
+        );
+        let width = text.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color([1.0, 1.0, 1.0, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - width) / 2.0, y]),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,382 @@
+/**
+ * Playing online without a window, as a bot for testing the server: `--bot random` or
+ * `--bot ai:4` joins the room like the GUI would, plays until the game is over and exits with a
+ * code saying how it went, so a script can run dozens of games at once.
+ *
+ * The game is followed by the same `Referee` as in the GUI, which knows the protocol and the
+ * rules but nothing of ggez, and a plain loop feeds it what the server sends.
+ */
+use crate::ai::{self, Ai, MAX_DIFFICULTY, MIN_DIFFICULTY};
+use crate::bot::{MovePicker, RandomMover};
+use crate::config::Config;
+use crate::matches::Outcome;
+use crate::net::Handshake;
+use crate::protocol::Message;
+use crate::referee::{Event, Opponent, Referee, Restart};
+use crate::session::{self, split_rng, Connection};
+use chess_template::{Colour, Position};
+use log::{error, info, warn};
+use rand::rngs::StdRng;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit codes, for scripts running the bots. 2 is left to clap, for bad arguments.
+pub const EXIT_WON: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_DRAWN: i32 = 3;
+pub const EXIT_LOST: i32 = 4;
+
+/// How long to wait for a message before looking again, e.g. for the opponent joining.
+const POLL: Duration = Duration::from_millis(50);
+
+/// How long the connection gets to send the last messages once the game is over.
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(1);
+
+/// Moves after which the bot offers a draw. The library doesn't know the fifty-move rule or
+/// insufficient material, so two random movers might never finish otherwise.
+const DRAW_AFTER: u32 = 400;
+
+/// What picks the bot's moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotKind {
+    Random,
+    Ai(u8), // the computer opponent, at this difficulty
+}
+
+impl BotKind {
+    /// Parses "random", "ai" for the default difficulty, or "ai:4".
+    pub fn parse(text: &str) -> Result<BotKind, String> {
+        let level = match text.split_once(':') {
+            None if text == "random" => return Ok(BotKind::Random),
+            None if text == "ai" => ai::DEFAULT_DIFFICULTY,
+            Some(("ai", level)) => level
+                .parse()
+                .map_err(|_| format!("{:?} isn't a difficulty", level))?,
+            _ => {
+                return Err(format!(
+                    "expected random, ai or ai:LEVEL but got {:?}",
+                    text
+                ))
+            }
+        };
+        if !(MIN_DIFFICULTY..=MAX_DIFFICULTY).contains(&level) {
+            return Err(format!(
+                "the difficulty goes from {} to {}",
+                MIN_DIFFICULTY, MAX_DIFFICULTY
+            ));
+        }
+        Ok(BotKind::Ai(level))
+    }
+
+    /// The computer thinks for as long as the config file allows, as it does against a player.
+    fn picker(self, config: &Config, rng: &mut StdRng) -> Box<dyn MovePicker> {
+        match self {
+            BotKind::Random => Box::new(RandomMover::new(split_rng(rng))),
+            BotKind::Ai(level) => {
+                let time_limit = config.ai_time_limit.unwrap_or(ai::DEFAULT_TIME_LIMIT);
+                Box::new(Ai::new(level, ai::time_limit(time_limit)))
+            }
+        }
+    }
+}
+
+impl Outcome {
+    /// The code the bot exits with after a game ending this way.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Won => EXIT_WON,
+            Outcome::Lost => EXIT_LOST,
+            Outcome::Drawn => EXIT_DRAWN,
+        }
+    }
+}
+
+/// The bot's side of a game: the referee following it, and what the bot answers. It takes any
+/// draw and restart it's offered, and offers a draw itself once the game has gone on long enough.
+pub struct Bot {
+    referee: Referee,
+    offered_draw: bool, // once is enough, as the opponent may never agree
+}
+
+impl Bot {
+    pub fn new(room: &str, colour: Colour) -> Bot {
+        Bot {
+            referee: Referee::new(room, colour),
+            offered_draw: false,
+        }
+    }
+
+    pub fn referee(&self) -> &Referee {
+        &self.referee
+    }
+
+    /// Acts on one message from the server. Returns what to send back, if anything, or why the
+    /// game can't go on.
+    pub fn handle(&mut self, text: &str) -> Result<Option<Message>, String> {
+        let msg = match Message::parse(text) {
+            Ok(msg) if msg.room() == self.referee.room => msg,
+            Ok(_) => return Ok(None),
+            Err(err) => {
+                warn!("Ignored {:?}: {}", text.trim_end(), err);
+                return Ok(None);
+            }
+        };
+
+        match self.referee.handle(msg, Opponent::Online) {
+            Event::OutOfSync { counter } => Err(format!(
+                "Out of sync with the opponent, who is at move {} while we're at {}",
+                counter,
+                self.referee.counter()
+            )),
+            Event::RestartAsked(_) => match self.referee.restart(Opponent::Online) {
+                Restart::Asked(msg) => Ok(msg),
+                Restart::Restarted(msg) => {
+                    self.offered_draw = false;
+                    Ok(Some(msg))
+                }
+            },
+            Event::Restarted => {
+                self.offered_draw = false;
+                Ok(None)
+            }
+            Event::DrawOffered(_) => Ok(self.referee.offer_draw().map(|(msg, _)| msg)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Plays our move, returning the message telling the opponent.
+    pub fn play(&mut self, from: Position, to: Position) -> Result<Message, String> {
+        self.referee.play(from, to)
+    }
+
+    /// A draw offer, once the game has gone on for long enough without ending.
+    pub fn draw_offer(&mut self) -> Option<Message> {
+        if self.offered_draw || self.referee.counter() <= DRAW_AFTER {
+            return None;
+        }
+        let (msg, _) = self.referee.offer_draw()?;
+        self.offered_draw = true;
+        Some(msg)
+    }
+}
+
+/// Plays one game in `room` on `server` and returns the code to exit with.
+pub fn run(kind: BotKind, server: &str, room: &str, config: &Config, rng: &mut StdRng) -> i32 {
+    match play(kind, server, room, config, rng) {
+        Ok(outcome) => {
+            info!("The game is over, {:?}", outcome);
+            outcome.exit_code()
+        }
+        Err(err) => {
+            error!("{}", err);
+            EXIT_FAILED
+        }
+    }
+}
+
+fn play(
+    kind: BotKind,
+    server: &str,
+    room: &str,
+    config: &Config,
+    rng: &mut StdRng,
+) -> Result<Outcome, String> {
+    let connection = session::connect(server, room)?;
+    let handshake = Handshake::start(room, None, &connection.sender, rng)?;
+    let colour = loop {
+        if let Some(paired) = handshake.poll(&connection.sender, &connection.receiver)? {
+            break paired.colour;
+        }
+        thread::sleep(POLL);
+    };
+    info!("Playing {:?} as a {:?} bot", colour, kind);
+
+    let mut picker = kind.picker(config, rng);
+    let mut bot = Bot::new(room, colour);
+    let send = |msg: Message| {
+        connection
+            .sender
+            .send(msg.to_string())
+            .map_err(|_| "Lost the connection to the server".to_string())
+    };
+
+    let outcome = loop {
+        if let Some(outcome) = bot.referee().outcome() {
+            break outcome;
+        }
+
+        // what has arrived is taken first, e.g. a resignation, but on our move nothing is waited for
+        let wait = if bot.referee().our_move() {
+            Duration::ZERO
+        } else {
+            POLL
+        };
+        match connection.receiver.recv_timeout(wait) {
+            Ok(msg) => {
+                if let Some(reply) = bot.handle(&msg)? {
+                    send(reply)?;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("Lost the connection to the server".to_string())
+            }
+        }
+
+        if bot.referee().our_move() {
+            let (from, to) = picker
+                .pick(bot.referee().game(), &mut |_| ())
+                .ok_or("No move to play in a game that isn't over")?;
+            send(bot.play(from, to)?)?;
+            if let Some(offer) = bot.draw_offer() {
+                send(offer)?;
+            }
+        }
+    };
+
+    close(connection);
+    Ok(outcome)
+}
+
+/// Closes the connection once what's been sent has gone, waiting at most `SHUTDOWN_WAIT`.
+fn close(connection: Connection) {
+    let Connection {
+        sender, receiver, ..
+    } = connection;
+    drop(sender);
+
+    // the network threads are done once their end of the incoming channel is dropped
+    let deadline = Instant::now() + SHUTDOWN_WAIT;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(left) {
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {
+                warn!("The connection didn't close in time, quitting anyway");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_square;
+    use crate::bot::random_move;
+    use rand::SeedableRng;
+
+    fn move_message(counter: u32, text: &str) -> String {
+        let msg = Message::Move {
+            room: "lunch".to_string(),
+            counter,
+            from: parse_square(&text[..2]).unwrap(),
+            to: parse_square(&text[2..]).unwrap(),
+        };
+        msg.to_string()
+    }
+
+    #[test]
+    fn bot_kinds_parse() {
+        assert_eq!(BotKind::parse("random"), Ok(BotKind::Random));
+        assert_eq!(
+            BotKind::parse("ai"),
+            Ok(BotKind::Ai(ai::DEFAULT_DIFFICULTY))
+        );
+        assert_eq!(BotKind::parse("ai:4"), Ok(BotKind::Ai(4)));
+        assert!(BotKind::parse("ai:9").is_err());
+        assert!(BotKind::parse("ai:hard").is_err());
+        assert!(BotKind::parse("stockfish").is_err());
+    }
+
+    #[test]
+    fn two_bots_play_a_game_to_the_same_end() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut white = Bot::new("lunch", Colour::White);
+        let mut black = Bot::new("lunch", Colour::Black);
+
+        // the server relays everything to both, the sender included
+        let mut relayed: Vec<String> = vec![];
+        for _ in 0..2 * DRAW_AFTER {
+            if white.referee().outcome().is_some() && black.referee().outcome().is_some() {
+                break;
+            }
+            for msg in std::mem::take(&mut relayed) {
+                for bot in [&mut white, &mut black] {
+                    if let Some(reply) = bot.handle(&msg).unwrap() {
+                        relayed.push(reply.to_string());
+                    }
+                }
+            }
+            for bot in [&mut white, &mut black] {
+                if bot.referee().our_move() {
+                    let (from, to) = random_move(bot.referee().game(), &mut rng).unwrap();
+                    relayed.push(bot.play(from, to).unwrap().to_string());
+                    relayed.extend(bot.draw_offer().map(|offer| offer.to_string()));
+                }
+            }
+        }
+
+        let ends = (
+            white.referee().outcome().unwrap(),
+            black.referee().outcome().unwrap(),
+        );
+        assert!(matches!(
+            ends,
+            (Outcome::Won, Outcome::Lost)
+                | (Outcome::Lost, Outcome::Won)
+                | (Outcome::Drawn, Outcome::Drawn)
+        ));
+    }
+
+    #[test]
+    fn a_resignation_wins_and_a_draw_offer_is_taken() {
+        let mut bot = Bot::new("lunch", Colour::White);
+        // our own resignation coming back doesn't count
+        assert!(bot.handle("lunch resign white ").unwrap().is_none());
+        assert_eq!(bot.referee().outcome(), None);
+        assert!(bot.handle("lunch resign black ").unwrap().is_none());
+        assert_eq!(bot.referee().outcome(), Some(Outcome::Won));
+        assert_eq!(Outcome::Won.exit_code(), EXIT_WON);
+
+        let mut bot = Bot::new("lunch", Colour::Black);
+        let accept = bot.handle("lunch draw white ").unwrap();
+        assert!(accept.is_some_and(|msg| msg.to_string() == "lunch draw black "));
+        assert_eq!(bot.referee().outcome(), Some(Outcome::Drawn));
+        // offered again, it isn't answered again
+        assert!(bot.handle("lunch draw white ").unwrap().is_none());
+    }
+
+    #[test]
+    fn a_move_that_cannot_follow_ends_the_game() {
+        let mut bot = Bot::new("lunch", Colour::Black);
+        bot.handle(&move_message(2, "e2e4")).unwrap();
+        assert!(bot.referee().our_move());
+        bot.play(parse_square("e7").unwrap(), parse_square("e5").unwrap())
+            .unwrap();
+        // our move and the opponent's sent again, and moves for another room, change nothing
+        bot.handle(&move_message(3, "e7e5")).unwrap();
+        bot.handle(&move_message(2, "e2e4")).unwrap();
+        bot.handle("dinner mv 4 6 3 4 3 ").unwrap();
+        assert!(!bot.referee().our_move());
+
+        assert!(bot.handle(&move_message(6, "d2d4")).is_err());
+        assert!(bot.handle(&move_message(4, "e4e5")).is_err());
+    }
+
+    #[test]
+    fn a_restart_is_agreed_to_once() {
+        let mut bot = Bot::new("lunch", Colour::Black);
+        bot.handle(&move_message(2, "e2e4")).unwrap();
+        let agreed = bot.handle("lunch reset ~0000002a ").unwrap();
+        assert!(agreed.is_some_and(|msg| msg.to_string() == "lunch reset "));
+        assert!(!bot.referee().our_move());
+        // our agreement coming back isn't answered again
+        assert!(bot.handle("lunch reset ").unwrap().is_none());
+        bot.handle(&move_message(2, "d2d4")).unwrap();
+        assert!(bot.referee().our_move());
+    }
+}
@@ -0,0 +1,524 @@
+/**
+ * The log of moves played in a game, and the statistics shown once it's over.
+ */
+use crate::board::{king_attacked, piece_value, square_name};
+use chess_template::{Colour, Game, GameState, Piece, PieceType, Position};
+use std::time::Duration;
+
+/// A move that has been played in the game.
+#[derive(Clone, Copy)]
+pub struct MoveRecord {
+    pub from: Position,
+    pub to: Position,
+    pub colour: Colour,              // colour of the player who made the move
+    pub piece: PieceType,            // the piece that moved
+    pub captured: Option<PieceType>, // piece taken by the move, if any
+    pub check: bool,                 // whether the move put the opponent in check
+    pub think_time: Duration,        // time between the previous move and this one
+}
+
+impl MoveRecord {
+    /// Describes a move, given the board before and after it was played.
+    pub fn new(
+        from: Position,
+        to: Position,
+        before: &[Option<Piece>; 64],
+        after: &[Option<Piece>; 64],
+        think_time: Duration,
+    ) -> MoveRecord {
+        let mover = before[from.row * 8 + from.col];
+        let colour = mover.map_or(Colour::White, |p| p.colour);
+        let piece = mover.map_or(PieceType::Pawn, |p| p.piece_type);
+
+        // a pawn moving diagonally onto an empty square captures en passant
+        let en_passant =
+            mover.is_some_and(|p| p.piece_type == PieceType::Pawn) && from.col != to.col;
+        let captured = match before[to.row * 8 + to.col] {
+            Some(piece) => Some(piece.piece_type),
+            None if en_passant => Some(PieceType::Pawn),
+            None => None,
+        };
+
+        let opponent = match colour {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        };
+
+        MoveRecord {
+            from,
+            to,
+            colour,
+            piece,
+            captured,
+            check: king_attacked(after, opponent),
+            think_time,
+        }
+    }
+
+    /// The move in coordinate notation, e.g. "e2e4".
+    pub fn name(&self) -> String {
+        format!("{}{}", square_name(self.from), square_name(self.to))
+    }
+}
+
+/// Summary of a finished game, shown on the game-over screen. Pairs are (White, Black).
+pub struct GameStats {
+    pub moves: usize,             // full moves, i.e. one move by each side
+    pub captures: (usize, usize), // pieces captured by each side
+    pub checks: (usize, usize),   // checks given by each side
+    pub longest_think: Option<(Colour, Duration)>, // the single slowest move of the game
+    pub material: (u32, u32),     // material left on the board for each side
+}
+
+/// Computes the statistics of a game from its move log and final board.
+pub fn game_stats(moves: &[MoveRecord], board: &[Option<Piece>; 64]) -> GameStats {
+    // count the moves matching a condition, separately for each side
+    let per_side = |condition: &dyn Fn(&MoveRecord) -> bool| {
+        let count = |colour: Colour| {
+            moves
+                .iter()
+                .filter(|m| m.colour == colour && condition(m))
+                .count()
+        };
+        (count(Colour::White), count(Colour::Black))
+    };
+    let material = |colour: Colour| {
+        board
+            .iter()
+            .flatten()
+            .filter(|p| p.colour == colour)
+            .map(|p| piece_value(p.piece_type))
+            .sum()
+    };
+
+    GameStats {
+        moves: moves.len().div_ceil(2),
+        captures: per_side(&|m| m.captured.is_some()),
+        checks: per_side(&|m| m.check),
+        longest_think: moves
+            .iter()
+            .max_by_key(|m| m.think_time)
+            .map(|m| (m.colour, m.think_time)),
+        material: (material(Colour::White), material(Colour::Black)),
+    }
+}
+
+/// Formats a duration as minutes and seconds, e.g. "1:05".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// The position in Forsyth-Edwards Notation, e.g. for pasting into an analysis board. The
+/// library doesn't expose castling and en passant rights, so they're worked out from the moves
+/// played, which always start from the initial position.
+pub fn fen(game: &Game, moves: &[MoveRecord]) -> String {
+    let placement = placement(&game.get_board());
+
+    let active = match game.get_active_colour() {
+        Colour::White => "w",
+        Colour::Black => "b",
+    };
+
+    // a right is lost once the king or that rook has moved, or the rook was taken
+    let untouched = |squares: &[(usize, usize)]| {
+        !moves.iter().any(|m| {
+            squares
+                .iter()
+                .any(|&square| square == (m.from.row, m.from.col) || square == (m.to.row, m.to.col))
+        })
+    };
+    let castling: String = [
+        ('K', [(7, 4), (7, 7)]),
+        ('Q', [(7, 4), (7, 0)]),
+        ('k', [(0, 4), (0, 7)]),
+        ('q', [(0, 4), (0, 0)]),
+    ]
+    .iter()
+    .filter(|(_, squares)| untouched(squares))
+    .map(|(letter, _)| *letter)
+    .collect();
+    let castling = if castling.is_empty() {
+        "-".to_string()
+    } else {
+        castling
+    };
+
+    // the square a pawn that just moved two squares skipped over
+    let en_passant = match moves.last() {
+        Some(m) if m.piece == PieceType::Pawn && m.from.row.abs_diff(m.to.row) == 2 => {
+            Position::new((m.from.row + m.to.row) / 2, m.from.col)
+                .map_or("-".to_string(), square_name)
+        }
+        _ => "-".to_string(),
+    };
+
+    let halfmove_clock = moves
+        .iter()
+        .rev()
+        .take_while(|m| m.piece != PieceType::Pawn && m.captured.is_none())
+        .count();
+    let fullmove = moves.len() / 2 + 1;
+
+    format!(
+        "{} {} {} {} {} {}",
+        placement, active, castling, en_passant, halfmove_clock, fullmove
+    )
+}
+
+/// The pieces in FEN, rank 8 first, e.g. "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR".
+pub fn placement(board: &[Option<Piece>; 64]) -> String {
+    let mut placement = String::new();
+    for row in 0..8 {
+        let mut empty = 0;
+        for col in 0..8 {
+            match board[row * 8 + col] {
+                Some(piece) => {
+                    if empty > 0 {
+                        placement.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    placement.push(fen_letter(piece));
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if row < 7 {
+            placement.push('/');
+        }
+    }
+    placement
+}
+
+/// Reads the pieces back from FEN, the inverse of `placement`. Anything after the placement,
+/// like the side to move, is ignored.
+pub fn parse_placement(fen: &str) -> Option<[Option<Piece>; 64]> {
+    let ranks: Vec<&str> = fen.split_whitespace().next()?.split('/').collect();
+    if ranks.len() != 8 {
+        return None;
+    }
+    let mut board = [None; 64];
+    for (row, rank) in ranks.iter().enumerate() {
+        let mut col = 0;
+        for letter in rank.chars() {
+            match letter.to_digit(10) {
+                Some(empty @ 1..=8) => col += empty as usize,
+                Some(_) => return None,
+                None => {
+                    if col >= 8 {
+                        return None;
+                    }
+                    board[row * 8 + col] = Some(fen_piece(letter)?);
+                    col += 1;
+                }
+            }
+        }
+        if col != 8 {
+            return None;
+        }
+    }
+    Some(board)
+}
+
+/// A piece's letter in FEN: upper case for White, lower case for Black.
+fn fen_letter(piece: Piece) -> char {
+    let letter = match piece.piece_type {
+        PieceType::King => 'k',
+        PieceType::Queen => 'q',
+        PieceType::Rook => 'r',
+        PieceType::Bishop => 'b',
+        PieceType::Knight => 'n',
+        PieceType::Pawn => 'p',
+    };
+    match piece.colour {
+        Colour::White => letter.to_ascii_uppercase(),
+        Colour::Black => letter,
+    }
+}
+
+/// The piece a letter stands for in FEN, the inverse of `fen_letter`.
+fn fen_piece(letter: char) -> Option<Piece> {
+    let piece_type = match letter.to_ascii_lowercase() {
+        'k' => PieceType::King,
+        'q' => PieceType::Queen,
+        'r' => PieceType::Rook,
+        'b' => PieceType::Bishop,
+        'n' => PieceType::Knight,
+        'p' => PieceType::Pawn,
+        _ => return None,
+    };
+    let colour = if letter.is_ascii_uppercase() {
+        Colour::White
+    } else {
+        Colour::Black
+    };
+    Some(Piece { piece_type, colour })
+}
+
+/// The move in standard algebraic notation, e.g. "Nbd2", "exd5" or "O-O+", given the game
+/// before it was played. Pawns always promote to a queen, as the library plays them.
+pub fn san(game: &Game, record: &MoveRecord) -> String {
+    let mut text = String::new();
+    let file = |pos: Position| (b'a' + pos.col as u8) as char;
+
+    if record.piece == PieceType::King && record.from.col.abs_diff(record.to.col) == 2 {
+        text.push_str(if record.to.col > record.from.col {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else if record.piece == PieceType::Pawn {
+        if record.captured.is_some() {
+            text.push(file(record.from));
+            text.push('x');
+        }
+        text.push_str(&square_name(record.to));
+        if record.to.row == 0 || record.to.row == 7 {
+            text.push_str("=Q");
+        }
+    } else {
+        text.push(fen_letter(Piece {
+            piece_type: record.piece,
+            colour: Colour::White,
+        }));
+
+        // another piece of the same kind that could have gone there is told apart by file, by
+        // rank if they share the file, or by both
+        let board = game.get_board();
+        let rivals: Vec<Position> = (0..64)
+            .filter(|&i| {
+                board[i].is_some_and(|piece| {
+                    piece.piece_type == record.piece && piece.colour == record.colour
+                })
+            })
+            .filter_map(|i| Position::new(i / 8, i % 8).ok())
+            .filter(|&pos| {
+                pos != record.from && game.get_possible_moves(pos, 0).contains(&record.to)
+            })
+            .collect();
+        if !rivals.is_empty() {
+            let square = square_name(record.from);
+            if rivals.iter().all(|pos| pos.col != record.from.col) {
+                text.push(file(record.from));
+            } else if rivals.iter().all(|pos| pos.row != record.from.row) {
+                text.push_str(&square[1..]);
+            } else {
+                text.push_str(&square);
+            }
+        }
+
+        if record.captured.is_some() {
+            text.push('x');
+        }
+        text.push_str(&square_name(record.to));
+    }
+
+    if record.check {
+        let mut after = game.clone();
+        let _ = after.make_move_pos(record.from, record.to);
+        text.push(if after.get_game_state() == GameState::GameOver {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    text
+}
+
+/// The game in PGN: the tags in the order given, then the moves wrapped at 80 columns, ending
+/// with the result, e.g. "1-0".
+pub fn pgn(tags: &[(&str, String)], moves: &[MoveRecord], result: &str) -> String {
+    let mut text = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        text.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    text.push('\n');
+
+    let mut game = Game::new();
+    let mut tokens = Vec::new();
+    for (i, record) in moves.iter().enumerate() {
+        if record.colour == Colour::White || i == 0 {
+            let dots = if record.colour == Colour::White {
+                "."
+            } else {
+                "..."
+            };
+            tokens.push(format!("{}{}", i / 2 + 1, dots));
+        }
+        tokens.push(san(&game, record));
+        let _ = game.make_move_pos(record.from, record.to);
+    }
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+/// Replays a move log from the starting position.
+pub fn replay(moves: &[MoveRecord]) -> Game {
+    let mut game = Game::new();
+    for record in moves {
+        // every logged move was legal when it was played, so replaying it can't fail
+        let _ = game.make_move_pos(record.from, record.to);
+    }
+    game
+}
+
+/// How many of the moves went to each square, in reading order from a8, for the heatmap.
+pub fn heat(moves: &[MoveRecord]) -> [u16; 64] {
+    let mut heat = [0u16; 64];
+    for record in moves {
+        let square = &mut heat[record.to.row * 8 + record.to.col];
+        *square = square.saturating_add(1);
+    }
+    heat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_move;
+
+    /// Plays moves in coordinate notation, logging them the way the game does.
+    fn play(moves: &[&str]) -> (Game, Vec<MoveRecord>) {
+        let mut game = Game::new();
+        let mut log = Vec::new();
+        for text in moves {
+            let (from, to, _) = parse_move(text).unwrap();
+            let before = game.get_board();
+            game.make_move_pos(from, to).unwrap();
+            log.push(MoveRecord::new(
+                from,
+                to,
+                &before,
+                &game.get_board(),
+                Duration::ZERO,
+            ));
+        }
+        (game, log)
+    }
+
+    #[test]
+    fn heat_counts_every_move_to_a_square() {
+        let (_, log) = play(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"]);
+        let heat = heat(&log);
+        let at = |name: &str| {
+            let (_, to, _) = parse_move(&format!("a1{}", name)).unwrap();
+            heat[to.row * 8 + to.col]
+        };
+        assert_eq!((at("f3"), at("g1"), at("f6"), at("g8")), (2, 1, 1, 1));
+        assert_eq!(heat.iter().sum::<u16>(), 5);
+    }
+
+    #[test]
+    fn stats_count_each_sides_captures_and_checks() {
+        // 1. e4 d5 2. exd5 c5 3. dxc6 e.p. bxc6 4. Qf3 Qd4 5. Qxf7+ Kxf7 6. Nf3 Qxf2+ 7. Kxf2
+        let (game, mut log) = play(&[
+            "e2e4", "d7d5", "e4d5", "c7c5", "d5c6", "b7c6", "d1f3", "d8d4", "f3f7", "e8f7", "g1f3",
+            "d4f2", "e1f2",
+        ]);
+        assert_eq!(log[4].captured, Some(PieceType::Pawn));
+        for (record, secs) in log
+            .iter_mut()
+            .zip([2, 3, 1, 40, 5, 5, 2, 7, 1, 0, 3, 12, 1])
+        {
+            record.think_time = Duration::from_secs(secs);
+        }
+
+        let stats = game_stats(&log, &game.get_board());
+        assert_eq!(stats.moves, 7);
+        assert_eq!(stats.captures, (4, 3));
+        assert_eq!(stats.checks, (1, 1));
+        assert_eq!(
+            stats.longest_think,
+            Some((Colour::Black, Duration::from_secs(40)))
+        );
+        // White is a queen and two pawns down, Black a queen and three pawns
+        assert_eq!(stats.material, (28, 27));
+    }
+
+    #[test]
+    fn stats_of_a_game_without_moves() {
+        let (game, log) = play(&[]);
+        let stats = game_stats(&log, &game.get_board());
+        assert_eq!(stats.moves, 0);
+        assert_eq!((stats.captures, stats.checks), ((0, 0), (0, 0)));
+        assert_eq!(stats.longest_think, None);
+        assert_eq!(stats.material, (39, 39));
+
+        // one move is a full move begun
+        let (game, log) = play(&["e2e4"]);
+        assert_eq!(game_stats(&log, &game.get_board()).moves, 1);
+    }
+
+    #[test]
+    fn fen_of_the_start_and_after_moves() {
+        let (game, log) = play(&[]);
+        assert_eq!(
+            fen(&game, &log),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        let (game, log) = play(&["e2e4"]);
+        assert_eq!(
+            fen(&game, &log),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+
+        let (game, log) = play(&["g1f3", "g8f6", "h1g1", "b8c6"]);
+        assert_eq!(
+            fen(&game, &log),
+            "r1bqkb1r/pppppppp/2n2n2/8/8/5N2/PPPPPPPP/RNBQKBR1 w Qkq - 4 3"
+        );
+    }
+
+    #[test]
+    fn moves_in_algebraic_notation() {
+        let (_, log) = play(&[
+            "e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5c6", "d7c6", "e1g1",
+        ]);
+        let tags = [("White", "Ann \"the rook\"".to_string())];
+        assert_eq!(
+            pgn(&tags, &log, "*"),
+            "[White \"Ann \\\"the rook\\\"\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6 dxc6 5. O-O *\n"
+        );
+
+        // both knights could go to d2, and the queen mates
+        let (_, log) = play(&["g1f3", "a7a6", "d2d4", "a6a5", "b1d2"]);
+        assert!(pgn(&[], &log, "*").ends_with("3. Nbd2 *\n"));
+        let (_, log) = play(&["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(pgn(&[], &log, "0-1"), "\n1. f3 e5 2. g4 Qh4# 0-1\n");
+    }
+
+    #[test]
+    fn placements_round_trip() {
+        let (game, _) = play(&["e2e4", "c7c5", "g1f3"]);
+        let text = placement(&game.get_board());
+        assert_eq!(text, "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R");
+        let board = parse_placement(&format!("{} b KQkq - 1 2", text)).unwrap();
+        assert_eq!(placement(&board), text);
+
+        // a rank too short or too long, a letter that isn't a piece, and a missing rank
+        assert!(parse_placement("8/8/8/8/8/8/8/7").is_none());
+        assert!(parse_placement("8/8/8/8/8/8/8/k8").is_none());
+        assert!(parse_placement("8/8/8/8/8/8/8/7x").is_none());
+        assert!(parse_placement("8/8/8/8/8/8/8").is_none());
+    }
+}
@@ -0,0 +1,187 @@
+/**
+ * A filter in front of everything that arrives over the network, so a buggy or hostile peer
+ * can't make the game thrash. A message the same as one that arrived within `REPEAT_WINDOW` is
+ * dropped, as acting on it again would at best do nothing. More than `RATE_LIMIT` messages within
+ * `RATE_WINDOW` and everything is ignored for `IGNORE_FOR`, so the board stays usable while
+ * someone floods the room.
+ *
+ * Messages are compared without the tag naming their sender, so one sent again under another tag
+ * is still the same. The bots and hotseat games talk over channels on this computer and aren't
+ * filtered.
+ */
+use crate::protocol;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long a message is remembered, to drop it if it comes again.
+pub const REPEAT_WINDOW: Duration = Duration::from_secs(2);
+
+/// Most messages taken within `RATE_WINDOW`. Moves sent again for a correspondence game go out
+/// in batches well under it, however long the game.
+pub const RATE_LIMIT: usize = 200;
+pub const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// How long everything is ignored once the limit is passed.
+pub const IGNORE_FOR: Duration = Duration::from_secs(5);
+
+/// What to do with a message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Take,
+    /// The same as one that came a moment ago.
+    Repeated,
+    /// Too many are arriving; `started` on the one that passed the limit.
+    Flooded {
+        started: bool,
+    },
+}
+
+/// The messages seen lately, and whether they're being ignored.
+#[derive(Default)]
+pub struct InboundFilter {
+    recent: VecDeque<(Instant, String)>, // taken within `REPEAT_WINDOW`, oldest first
+    arrivals: VecDeque<Instant>,         // of every message within `RATE_WINDOW`
+    ignoring_until: Option<Instant>,
+}
+
+impl InboundFilter {
+    /// Whether to act on `text`, arriving `now`. Every message counts towards the limit, even
+    /// one that's dropped, or flooding with the same message would never pass it.
+    pub fn check(&mut self, text: &str, now: Instant) -> Verdict {
+        let expired = |at: Instant, window| now.saturating_duration_since(at) >= window;
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|&at| expired(at, RATE_WINDOW))
+        {
+            self.arrivals.pop_front();
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|&(at, _)| expired(at, REPEAT_WINDOW))
+        {
+            self.recent.pop_front();
+        }
+
+        if let Some(until) = self.ignoring_until {
+            if now < until {
+                return Verdict::Flooded { started: false };
+            }
+            self.ignoring_until = None;
+            self.arrivals.clear();
+        }
+        self.arrivals.push_back(now);
+        if self.arrivals.len() > RATE_LIMIT {
+            self.ignoring_until = Some(now + IGNORE_FOR);
+            return Verdict::Flooded { started: true };
+        }
+
+        let text = protocol::untag(text).1.trim();
+        if self.recent.iter().any(|(_, seen)| seen == text) {
+            return Verdict::Repeated;
+        }
+        self.recent.push_back((now, text.to_string()));
+        Verdict::Take
+    }
+
+    /// Whether messages are being ignored `now`.
+    pub fn flooded(&self, now: Instant) -> bool {
+        self.ignoring_until.is_some_and(|until| now < until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{Direction, Entry};
+
+    /// Plays the incoming messages of a transcript through a filter, at the times they were
+    /// recorded, and says what became of each.
+    fn replay(transcript: &str) -> Vec<Verdict> {
+        let start = Instant::now();
+        let mut filter = InboundFilter::default();
+        transcript
+            .lines()
+            .filter_map(|line| match Entry::parse(line).unwrap() {
+                Entry::Message {
+                    ms,
+                    direction: Direction::In,
+                    text,
+                } => Some(filter.check(&text, start + Duration::from_millis(ms))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_peer_repeating_itself_is_heard_once() {
+        // a reset sent over and over, and the same move under two tags
+        let verdicts = replay(
+            r#"{"ms":0,"dir":"session","room":"lobby","colour":"white","hotseat":false}
+{"ms":1000,"dir":"out","msg":"lobby mv 2 6 4 4 4 ~0000002a "}
+{"ms":1200,"dir":"in","msg":"lobby mv 3 1 4 3 4 ~deadbeef "}
+{"ms":1210,"dir":"in","msg":"lobby mv 3 1 4 3 4 ~0badf00d "}
+{"ms":1300,"dir":"in","msg":"lobby reset ~deadbeef "}
+{"ms":1301,"dir":"in","msg":"lobby reset ~deadbeef "}
+{"ms":1302,"dir":"in","msg":"lobby reset"}
+{"ms":1303,"dir":"in","msg":"lobby mv 3 1 3 3 3 ~deadbeef "}
+{"ms":3300,"dir":"in","msg":"lobby reset ~deadbeef "}"#,
+        );
+        use Verdict::*;
+        assert_eq!(
+            verdicts,
+            [Take, Repeated, Take, Repeated, Repeated, Take, Take]
+        );
+    }
+
+    #[test]
+    fn a_flood_is_ignored_for_a_while() {
+        // conflicting moves as fast as the relay passes them on
+        let mut transcript =
+            r#"{"ms":0,"dir":"session","room":"lobby","colour":"white","hotseat":false}"#
+                .to_string();
+        for i in 0..RATE_LIMIT + 10 {
+            transcript.push_str(&format!(
+                "\n{{\"ms\":{},\"dir\":\"in\",\"msg\":\"lobby mv {} 1 4 3 4\"}}",
+                i / 25,
+                i + 2
+            ));
+        }
+        for ms in [5007, 5008, 5009] {
+            transcript.push_str(&format!(
+                "\n{{\"ms\":{},\"dir\":\"in\",\"msg\":\"lobby chat black hej\"}}",
+                ms
+            ));
+        }
+        let verdicts = replay(&transcript);
+
+        assert!(verdicts[..RATE_LIMIT]
+            .iter()
+            .all(|&verdict| verdict == Verdict::Take));
+        assert_eq!(verdicts[RATE_LIMIT], Verdict::Flooded { started: true });
+        assert!(verdicts[RATE_LIMIT + 1..RATE_LIMIT + 11]
+            .iter()
+            .all(|&verdict| verdict == Verdict::Flooded { started: false }));
+        // it passed the limit 8 ms in, so it listens again from 5008 ms
+        assert_eq!(
+            verdicts[RATE_LIMIT + 11..],
+            [Verdict::Take, Verdict::Repeated]
+        );
+    }
+
+    #[test]
+    fn the_flood_ends_on_time() {
+        let start = Instant::now();
+        let mut filter = InboundFilter::default();
+        for i in 0..=RATE_LIMIT {
+            filter.check(&format!("lobby thinking {}", i), start);
+        }
+        assert!(filter.flooded(start + IGNORE_FOR - Duration::from_millis(1)));
+        assert!(!filter.flooded(start + IGNORE_FOR));
+        assert_eq!(
+            filter.check("lobby thinking 1", start + IGNORE_FOR),
+            Verdict::Take
+        );
+    }
+}
@@ -0,0 +1,127 @@
+/**
+ * Invites like `schack://192.168.1.10:6000/fredagsschack`: the server and room of a game in one
+ * string, copied from the waiting screen and given back to the game as its first argument, or
+ * opened by the desktop if the game is registered for the `schack` scheme.
+ */
+use crate::protocol::MAX_ROOM_LENGTH;
+use std::fmt;
+
+/// The scheme invites start with.
+pub const SCHEME: &str = "schack";
+
+/// Where to find an opponent waiting for a game.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Invite {
+    pub server: String, // IP or host name, and port
+    pub room: String,
+}
+
+impl Invite {
+    /// Parses an invite, checking that it has a server with a port and a room the protocol can
+    /// carry. A slash after the room is allowed, as some chat programs add one.
+    pub fn parse(text: &str) -> Result<Invite, String> {
+        let text = text.trim();
+        let rest = match text.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case(SCHEME) => rest,
+            Some((scheme, _)) => {
+                return Err(format!(
+                    "{:?} isn't an invite, those start with {}:// rather than {}://",
+                    text, SCHEME, scheme
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "{:?} isn't an invite, those look like {}://192.168.1.10:6000/room",
+                    text, SCHEME
+                ))
+            }
+        };
+
+        let (server, room) = rest
+            .split_once('/')
+            .ok_or_else(|| format!("the invite {:?} has no room after the server", text))?;
+        let room = room.strip_suffix('/').unwrap_or(room);
+
+        let port = match server.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() => port,
+            _ => {
+                return Err(format!(
+                    "the invite {:?} needs a server with a port, like 192.168.1.10:6000",
+                    text
+                ))
+            }
+        };
+        port.parse::<u16>()
+            .map_err(|_| format!("{:?} in the invite {:?} isn't a port", port, text))?;
+
+        if room.is_empty() {
+            return Err(format!(
+                "the invite {:?} has no room after the server",
+                text
+            ));
+        }
+        if room.contains(|c: char| c.is_whitespace() || c.is_control() || c == '/') {
+            return Err(format!(
+                "{:?} in the invite {:?} isn't a room name",
+                room, text
+            ));
+        }
+        if room.len() > MAX_ROOM_LENGTH {
+            return Err(format!(
+                "the room {:?} in the invite {:?} is longer than {} characters",
+                room, text, MAX_ROOM_LENGTH
+            ));
+        }
+
+        Ok(Invite {
+            server: server.to_string(),
+            room: room.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}/{}", SCHEME, self.server, self.room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invites_round_trip() {
+        let invite = Invite::parse("schack://192.168.1.10:6000/fredagsschack").unwrap();
+        assert_eq!(invite.server, "192.168.1.10:6000");
+        assert_eq!(invite.room, "fredagsschack");
+        assert_eq!(
+            invite.to_string(),
+            "schack://192.168.1.10:6000/fredagsschack"
+        );
+
+        let invite = Invite::parse(" SCHACK://chess.example.com:6000/lunch/ ").unwrap();
+        assert_eq!(invite.server, "chess.example.com:6000");
+        assert_eq!(invite.room, "lunch");
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        for text in [
+            "",
+            "fredagsschack",
+            "http://192.168.1.10:6000/fredagsschack",
+            "schack://192.168.1.10:6000",
+            "schack://192.168.1.10:6000/",
+            "schack://192.168.1.10/fredagsschack",
+            "schack://:6000/fredagsschack",
+            "schack://192.168.1.10:port/fredagsschack",
+            "schack://192.168.1.10:99999/fredagsschack",
+            "schack://192.168.1.10:6000/two words",
+            "schack://192.168.1.10:6000/a/b",
+            "schack://192.168.1.10:6000/fredagsschack_klockan_sex",
+        ] {
+            assert!(Invite::parse(text).is_err(), "{:?} was accepted", text);
+        }
+    }
+}
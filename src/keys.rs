@@ -0,0 +1,725 @@
+/**
+ * Keyboard shortcuts during a game. Every shortcut is listed in one keymap, which the key
+ * handler, the help overlay and the settings screen all read, so the help can't fall out of date
+ * and a rebound key works everywhere.
+ *
+ * The keymap is kept in the config file as a `[keys]` table of action names to key names, e.g.
+ * `restart = ["Ctrl+R"]`. Actions left out keep their default keys.
+ *
+ * A gamepad can trigger some of the actions too, with the buttons in `PAD_BUTTONS`, which the help
+ * lists next to the keys. Those can't be changed. The d-pad, left stick, A and B work the board
+ * cursor instead, like the arrow keys, Space and Escape.
+ */
+use crate::app::SCREEN_SIZE;
+use ggez::event::{Button, KeyCode, KeyMods};
+use ggez::{graphics, Context, GameResult};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Something a shortcut does.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Help,
+    Pause,
+    TypeMove,
+    Restart,
+    Blindfold,
+    Peek,
+    Threats,
+    Analysis,
+    FlipBoard,
+    Heatmap,
+    MoveList,
+    PreviousMove,
+    NextMove,
+    Stronger,
+    Weaker,
+    NetworkInfo,
+    PieceSquares,
+    NextGame,
+    AnotherGame,
+    Chat,
+    ExportSvg,
+    Snapshot,
+}
+
+/// Actions in the order they're listed in the help and the settings.
+pub const ACTIONS: [Action; 22] = [
+    Action::Help,
+    Action::Pause,
+    Action::TypeMove,
+    Action::Restart,
+    Action::Blindfold,
+    Action::Peek,
+    Action::Threats,
+    Action::Analysis,
+    Action::FlipBoard,
+    Action::Heatmap,
+    Action::MoveList,
+    Action::PreviousMove,
+    Action::NextMove,
+    Action::Stronger,
+    Action::Weaker,
+    Action::NetworkInfo,
+    Action::PieceSquares,
+    Action::NextGame,
+    Action::AnotherGame,
+    Action::Chat,
+    Action::ExportSvg,
+    Action::Snapshot,
+];
+
+impl Action {
+    /// Short name, for the settings screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Help => "Help",
+            Action::Pause => "Pause menu",
+            Action::TypeMove => "Type a move",
+            Action::Restart => "Restart",
+            Action::Blindfold => "Blindfold mode",
+            Action::Peek => "Peek",
+            Action::Threats => "Threat view",
+            Action::Analysis => "Analysis board",
+            Action::FlipBoard => "Flip board",
+            Action::Heatmap => "Square heatmap",
+            Action::MoveList => "Move list",
+            Action::PreviousMove => "Previous move",
+            Action::NextMove => "Next move",
+            Action::Stronger => "Stronger computer",
+            Action::Weaker => "Weaker computer",
+            Action::NetworkInfo => "Network info",
+            Action::PieceSquares => "Piece-square table",
+            Action::NextGame => "Next game",
+            Action::AnotherGame => "Another game",
+            Action::Chat => "Chat",
+            Action::ExportSvg => "Export SVG",
+            Action::Snapshot => "Debug snapshot",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Help => "Show or hide this help",
+            Action::Pause => "Pause menu, to offer a draw, resign or leave",
+            Action::TypeMove => "Type a move, e.g. e2e4",
+            Action::Restart => "Restart the game",
+            Action::Blindfold => "Cycle blindfold training mode",
+            Action::Peek => "Peek at the pieces while blindfolded",
+            Action::Threats => "Hold to show the squares your opponent attacks",
+            Action::Analysis => "Analyse the finished game, or go back to it",
+            Action::FlipBoard => "Turn the board round",
+            Action::Heatmap => "Shade the squares moved to most, after the game",
+            Action::MoveList => "Show or hide the moves beside the board",
+            Action::PreviousMove => "Show the position a move earlier",
+            Action::NextMove => "Show the position a move later, up to the game",
+            Action::Stronger => "Make the computer opponent stronger",
+            Action::Weaker => "Make the computer opponent weaker",
+            Action::NetworkInfo => "Show or hide connection statistics",
+            Action::PieceSquares => "Cycle the engine's square bonuses, with network info",
+            Action::NextGame => "Switch to the next open game",
+            Action::AnotherGame => "Open another online game in a new tab",
+            Action::Chat => "Chat with the opponent, online",
+            Action::ExportSvg => "Save the board as an SVG diagram",
+            Action::Snapshot => "Save the game's state for a bug report",
+        }
+    }
+}
+
+/// A key, with the modifiers that must be held.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyChord {
+    key: KeyCode,
+    mods: KeyMods,
+}
+
+/// Names of the keys that can be bound, as written in the config file and the help.
+const KEY_NAMES: &[(KeyCode, &str)] = &[
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+    (KeyCode::Escape, "Esc"),
+    (KeyCode::Return, "Enter"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Back, "Backspace"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Plus, "+"),
+    (KeyCode::Equals, "="),
+    (KeyCode::Minus, "-"),
+    (KeyCode::Slash, "/"),
+    (KeyCode::Backslash, "\\"),
+    (KeyCode::Comma, ","),
+    (KeyCode::Period, "."),
+    (KeyCode::Semicolon, ";"),
+    (KeyCode::Apostrophe, "'"),
+    (KeyCode::Grave, "`"),
+    (KeyCode::LBracket, "["),
+    (KeyCode::RBracket, "]"),
+    (KeyCode::Numpad0, "Numpad0"),
+    (KeyCode::Numpad1, "Numpad1"),
+    (KeyCode::Numpad2, "Numpad2"),
+    (KeyCode::Numpad3, "Numpad3"),
+    (KeyCode::Numpad4, "Numpad4"),
+    (KeyCode::Numpad5, "Numpad5"),
+    (KeyCode::Numpad6, "Numpad6"),
+    (KeyCode::Numpad7, "Numpad7"),
+    (KeyCode::Numpad8, "Numpad8"),
+    (KeyCode::Numpad9, "Numpad9"),
+    (KeyCode::NumpadAdd, "NumpadPlus"),
+    (KeyCode::NumpadSubtract, "NumpadMinus"),
+    (KeyCode::NumpadEnter, "NumpadEnter"),
+];
+
+/// Modifiers in the order they're written, e.g. "Ctrl+Shift+R".
+const MOD_NAMES: [(KeyMods, &str); 4] = [
+    (KeyMods::CTRL, "Ctrl"),
+    (KeyMods::ALT, "Alt"),
+    (KeyMods::LOGO, "Super"),
+    (KeyMods::SHIFT, "Shift"),
+];
+
+const fn chord(key: KeyCode, mods: KeyMods) -> KeyChord {
+    KeyChord { key, mods }
+}
+
+/// The keys each action starts out with. Several keys may do the same thing.
+const DEFAULTS: &[(Action, KeyChord)] = &[
+    (Action::Help, chord(KeyCode::F1, KeyMods::NONE)),
+    (Action::Help, chord(KeyCode::Slash, KeyMods::SHIFT)),
+    (Action::Pause, chord(KeyCode::Escape, KeyMods::NONE)),
+    (Action::TypeMove, chord(KeyCode::Return, KeyMods::NONE)),
+    (Action::TypeMove, chord(KeyCode::NumpadEnter, KeyMods::NONE)),
+    (Action::Restart, chord(KeyCode::R, KeyMods::NONE)),
+    (Action::Blindfold, chord(KeyCode::B, KeyMods::NONE)),
+    (Action::Peek, chord(KeyCode::P, KeyMods::NONE)),
+    (Action::Threats, chord(KeyCode::X, KeyMods::NONE)),
+    (Action::Analysis, chord(KeyCode::A, KeyMods::NONE)),
+    (Action::FlipBoard, chord(KeyCode::F, KeyMods::NONE)),
+    (Action::Heatmap, chord(KeyCode::H, KeyMods::NONE)),
+    (Action::MoveList, chord(KeyCode::Key1, KeyMods::CTRL)),
+    (Action::PreviousMove, chord(KeyCode::PageUp, KeyMods::NONE)),
+    (Action::NextMove, chord(KeyCode::PageDown, KeyMods::NONE)),
+    (Action::Stronger, chord(KeyCode::Plus, KeyMods::NONE)),
+    (Action::Stronger, chord(KeyCode::Equals, KeyMods::NONE)),
+    (Action::Stronger, chord(KeyCode::NumpadAdd, KeyMods::NONE)),
+    (Action::Weaker, chord(KeyCode::Minus, KeyMods::NONE)),
+    (
+        Action::Weaker,
+        chord(KeyCode::NumpadSubtract, KeyMods::NONE),
+    ),
+    (Action::NetworkInfo, chord(KeyCode::F3, KeyMods::NONE)),
+    (Action::PieceSquares, chord(KeyCode::F4, KeyMods::NONE)),
+    (Action::NextGame, chord(KeyCode::Tab, KeyMods::CTRL)),
+    (Action::AnotherGame, chord(KeyCode::T, KeyMods::CTRL)),
+    (Action::Chat, chord(KeyCode::F2, KeyMods::NONE)),
+    (Action::ExportSvg, chord(KeyCode::S, KeyMods::CTRL)),
+    (Action::Snapshot, chord(KeyCode::F12, KeyMods::NONE)),
+];
+
+/// Gamepad buttons for actions, named as on an Xbox controller.
+const PAD_BUTTONS: [(Action, Button, &str); 5] = [
+    (Action::Help, Button::Select, "Back"),
+    (Action::Pause, Button::Start, "Start"),
+    (Action::FlipBoard, Button::West, "X"),
+    (Action::PreviousMove, Button::LeftTrigger, "LB"),
+    (Action::NextMove, Button::RightTrigger, "RB"),
+];
+
+/// The action a gamepad button triggers, if any.
+pub fn pad_action(button: Button) -> Option<Action> {
+    PAD_BUTTONS
+        .iter()
+        .find(|&&(_, bound, _)| bound == button)
+        .map(|&(action, _, _)| action)
+}
+
+/// The gamepad button for an action, as shown in the help.
+pub fn pad_name(action: Action) -> Option<&'static str> {
+    PAD_BUTTONS
+        .iter()
+        .find(|&&(bound, _, _)| bound == action)
+        .map(|&(_, _, name)| name)
+}
+
+/// How far a stick has to be pushed to count, and how far back it has to come before it can
+/// count again, so a stick resting near the line doesn't step the cursor over and over.
+const STICK_PUSHED: f32 = 0.6;
+const STICK_RELEASED: f32 = 0.3;
+
+/// Which way a stick axis is pushed, -1, 0 or 1, given where it's moved to and which way it was
+/// pushed before.
+pub fn stick_direction(held: i8, value: f32) -> i8 {
+    if value >= STICK_PUSHED {
+        1
+    } else if value <= -STICK_PUSHED {
+        -1
+    } else if value.abs() <= STICK_RELEASED {
+        0
+    } else {
+        held
+    }
+}
+
+impl KeyChord {
+    /// A key pressed with some modifiers, if it's one that can be bound. Modifier keys on their
+    /// own can't be.
+    pub fn new(key: KeyCode, mods: KeyMods) -> Option<KeyChord> {
+        KEY_NAMES
+            .iter()
+            .any(|&(known, _)| known == key)
+            .then_some(KeyChord { key, mods })
+    }
+
+    /// Whether a key press triggers this chord. Shift is ignored for chords without modifiers,
+    /// since some layouts need it to type the key at all, e.g. + on a US keyboard.
+    fn matches(&self, key: KeyCode, mods: KeyMods) -> bool {
+        if key != self.key {
+            return false;
+        }
+        if self.mods == KeyMods::NONE {
+            !mods.intersects(KeyMods::CTRL | KeyMods::ALT | KeyMods::LOGO)
+        } else {
+            mods == self.mods
+        }
+    }
+}
+
+/// Written like "Ctrl+Shift+R", the way it's stored in the config file.
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in MOD_NAMES {
+            if self.mods.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        let name = KEY_NAMES
+            .iter()
+            .find(|&&(key, _)| key == self.key)
+            .map_or("?", |&(_, name)| name);
+        write!(f, "{}", name)
+    }
+}
+
+/// Parses key names like "R", "Ctrl+R" or "Shift++". Names are case-insensitive.
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<KeyChord, String> {
+        // the key itself may be "+", so it can't just be split on every "+"
+        let (prefix, key_name) = match text.strip_suffix('+') {
+            Some(prefix) => (prefix, "+"),
+            None => text.rsplit_once('+').unwrap_or(("", text)),
+        };
+
+        let mut mods = KeyMods::NONE;
+        for part in prefix.split('+').filter(|part| !part.is_empty()) {
+            let modifier = MOD_NAMES
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(part))
+                .map(|&(modifier, _)| modifier)
+                .ok_or_else(|| format!("unknown modifier {:?} in {:?}", part, text))?;
+            mods = mods | modifier;
+        }
+
+        let key = KEY_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(key_name))
+            .map(|&(key, _)| key)
+            .ok_or_else(|| format!("unknown key {:?}", text))?;
+
+        Ok(KeyChord { key, mods })
+    }
+}
+
+impl Serialize for KeyChord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyChord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<KeyChord, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Which keys trigger which actions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<Action, Vec<KeyChord>>",
+    into = "BTreeMap<Action, Vec<KeyChord>>"
+)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<KeyChord>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::from(BTreeMap::new())
+    }
+}
+
+/// Keys read from the config file. Actions that aren't mentioned keep their defaults.
+impl From<BTreeMap<Action, Vec<KeyChord>>> for Keymap {
+    fn from(mut bindings: BTreeMap<Action, Vec<KeyChord>>) -> Keymap {
+        for action in ACTIONS {
+            bindings.entry(action).or_insert_with(|| {
+                DEFAULTS
+                    .iter()
+                    .filter(|(default, _)| *default == action)
+                    .map(|&(_, chord)| chord)
+                    .collect()
+            });
+        }
+        Keymap { bindings }
+    }
+}
+
+impl From<Keymap> for BTreeMap<Action, Vec<KeyChord>> {
+    fn from(keymap: Keymap) -> BTreeMap<Action, Vec<KeyChord>> {
+        keymap.bindings
+    }
+}
+
+impl Keymap {
+    /// The action a key press triggers, if any.
+    pub fn action(&self, key: KeyCode, mods: KeyMods) -> Option<Action> {
+        ACTIONS.into_iter().find(|action| {
+            self.keys(*action)
+                .iter()
+                .any(|chord| chord.matches(key, mods))
+        })
+    }
+
+    /// The keys bound to an action.
+    pub fn keys(&self, action: Action) -> &[KeyChord] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The keys bound to an action, as shown to the player, e.g. "F1, Shift+/".
+    pub fn names(&self, action: Action) -> String {
+        match self.keys(action) {
+            [] => "Unbound".to_string(),
+            keys => keys
+                .iter()
+                .map(KeyChord::to_string)
+                .collect::<Vec<String>>()
+                .join(", "),
+        }
+    }
+
+    /// Makes a key the only one for an action. If another action had the key, it's taken from
+    /// that action, which is returned so the player can be told.
+    pub fn bind(&mut self, action: Action, chord: KeyChord) -> Option<Action> {
+        let mut taken_from = None;
+        for (other, keys) in self.bindings.iter_mut() {
+            if *other != action && keys.contains(&chord) {
+                keys.retain(|key| *key != chord);
+                taken_from = Some(*other);
+            }
+        }
+        self.bindings.insert(action, vec![chord]);
+        taken_from
+    }
+
+    /// Keys bound to more than one action, which can only happen by editing the config file.
+    /// Only the first of the actions, in the order of `ACTIONS`, is triggered by the key.
+    pub fn conflicts(&self) -> Vec<(KeyChord, Action, Action)> {
+        let mut conflicts = Vec::new();
+        for (i, &first) in ACTIONS.iter().enumerate() {
+            for &second in &ACTIONS[i + 1..] {
+                for chord in self.keys(first) {
+                    if self.keys(second).contains(chord) {
+                        conflicts.push((*chord, first, second));
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+/// Draws the list of shortcuts over whatever is on screen.
+pub fn draw_help(ctx: &mut Context, keymap: &Keymap) -> GameResult {
+    let shade = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1),
+        [0.0, 0.0, 0.0, 0.8].into(),
+    )?;
+    graphics::draw(ctx, &shade, graphics::DrawParam::default())?;
+
+    let title = graphics::Text::new(
+        graphics::TextFragment::from("Keyboard shortcuts")
+            .scale(graphics::PxScale { x: 40.0, y: 40.0 }),
+    );
+    let title_width = title.dimensions(ctx).w;
+    graphics::draw(
+        ctx,
+        &title,
+        graphics::DrawParam::default()
+            .color([1.0, 1.0, 1.0, 1.0].into())
+            .dest([(SCREEN_SIZE.0 - title_width) / 2.0, 60.0]),
+    )?;
+
+    for (i, action) in ACTIONS.iter().enumerate() {
+        let y = 115.0 + i as f32 * 28.0;
+        let keys = graphics::Text::new(
+            graphics::TextFragment::from(keymap.names(*action))
+                .scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+        );
+        graphics::draw(
+            ctx,
+            &keys,
+            graphics::DrawParam::default()
+                .color([1.0, 0.85, 0.4, 1.0].into())
+                .dest([60.0, y]),
+        )?;
+
+        if let Some(button) = pad_name(*action) {
+            let button = graphics::Text::new(
+                graphics::TextFragment::from(button).scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+            );
+            graphics::draw(
+                ctx,
+                &button,
+                graphics::DrawParam::default()
+                    .color([0.6, 0.85, 1.0, 1.0].into())
+                    .dest([270.0, y]),
+            )?;
+        }
+
+        let description = graphics::Text::new(
+            graphics::TextFragment::from(action.description())
+                .scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+        );
+        graphics::draw(
+            ctx,
+            &description,
+            graphics::DrawParam::default()
+                .color([1.0, 1.0, 1.0, 1.0].into())
+                .dest([340.0, y]),
+        )?;
+    }
+
+    let mut hint = graphics::Text::new(
+        graphics::TextFragment::from(
+            "Arrow keys, the d-pad or the left stick move a cursor on the board. Space, Enter or A presses \
+             its square, and Escape or B puts a piece down.\n\
+             Press any key or click to close. Keys can be changed in Settings, gamepad buttons can't.",
+        )
+        .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+    );
+    hint.set_bounds(
+        [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+        graphics::Align::Center,
+    );
+    graphics::draw(
+        ctx,
+        &hint,
+        graphics::DrawParam::default()
+            .color([0.8, 0.8, 0.8, 1.0].into())
+            .dest([20.0, SCREEN_SIZE.1 - 70.0]),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The keymap as the `[keys]` table of a config file.
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Section {
+        keys: Keymap,
+    }
+
+    #[test]
+    fn keys_dispatch_to_their_action() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            keymap.action(KeyCode::R, KeyMods::NONE),
+            Some(Action::Restart)
+        );
+        assert_eq!(
+            keymap.action(KeyCode::F1, KeyMods::NONE),
+            Some(Action::Help)
+        );
+        assert_eq!(
+            keymap.action(KeyCode::Slash, KeyMods::SHIFT),
+            Some(Action::Help)
+        );
+        assert_eq!(keymap.action(KeyCode::Slash, KeyMods::NONE), None);
+        // shift is needed to type + on some layouts
+        assert_eq!(
+            keymap.action(KeyCode::Equals, KeyMods::SHIFT),
+            Some(Action::Stronger)
+        );
+        // but Ctrl+R isn't R
+        assert_eq!(keymap.action(KeyCode::R, KeyMods::CTRL), None);
+    }
+
+    #[test]
+    fn gamepad_buttons_share_the_actions() {
+        assert_eq!(pad_action(Button::Start), Some(Action::Pause));
+        assert_eq!(pad_action(Button::South), None);
+        assert_eq!(pad_name(Action::FlipBoard), Some("X"));
+        assert_eq!(pad_name(Action::Restart), None);
+        for (action, button, _) in PAD_BUTTONS {
+            assert_eq!(pad_action(button), Some(action));
+        }
+    }
+
+    #[test]
+    fn sticks_step_once_per_push() {
+        assert_eq!(stick_direction(0, 0.2), 0);
+        assert_eq!(stick_direction(0, 0.5), 0);
+        assert_eq!(stick_direction(0, 0.8), 1);
+        // still held while it drifts back towards the middle, until it's close
+        assert_eq!(stick_direction(1, 0.5), 1);
+        assert_eq!(stick_direction(1, 0.1), 0);
+        assert_eq!(stick_direction(1, -0.9), -1);
+    }
+
+    #[test]
+    fn key_names_round_trip() {
+        for text in [
+            "R",
+            "Ctrl+R",
+            "Ctrl+Shift+F5",
+            "+",
+            "Shift++",
+            "NumpadMinus",
+            "Esc",
+        ] {
+            assert_eq!(text.parse::<KeyChord>().unwrap().to_string(), text);
+        }
+        assert_eq!(
+            "ctrl+r".parse::<KeyChord>(),
+            Ok(chord(KeyCode::R, KeyMods::CTRL))
+        );
+        assert!("Hyper+R".parse::<KeyChord>().is_err());
+        assert!("Banana".parse::<KeyChord>().is_err());
+        assert!(KeyChord::new(KeyCode::LShift, KeyMods::SHIFT).is_none());
+    }
+
+    #[test]
+    fn keymap_section_round_trips() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Restart, chord(KeyCode::R, KeyMods::CTRL));
+        let section = Section { keys: keymap };
+
+        let text = toml::to_string(&section).unwrap();
+        assert!(text.contains("restart = [\"Ctrl+R\"]"));
+        assert_eq!(toml::from_str::<Section>(&text).unwrap(), section);
+    }
+
+    #[test]
+    fn missing_actions_keep_their_defaults() {
+        let section: Section = toml::from_str("[keys]\nrestart = [\"Ctrl+R\"]\n").unwrap();
+        assert_eq!(
+            section.keys.action(KeyCode::R, KeyMods::CTRL),
+            Some(Action::Restart)
+        );
+        assert_eq!(section.keys.action(KeyCode::R, KeyMods::NONE), None);
+        assert_eq!(
+            section.keys.action(KeyCode::B, KeyMods::NONE),
+            Some(Action::Blindfold)
+        );
+
+        assert!(toml::from_str::<Section>("[keys]\nrestart = [\"Banana\"]\n").is_err());
+        assert!(toml::from_str::<Section>("[keys]\nfly = [\"F\"]\n").is_err());
+    }
+
+    #[test]
+    fn binding_a_taken_key_moves_it() {
+        let mut keymap = Keymap::default();
+        let taken = keymap.bind(Action::Restart, chord(KeyCode::B, KeyMods::NONE));
+        assert_eq!(taken, Some(Action::Blindfold));
+        assert_eq!(keymap.names(Action::Blindfold), "Unbound");
+        assert_eq!(
+            keymap.action(KeyCode::B, KeyMods::NONE),
+            Some(Action::Restart)
+        );
+        assert!(keymap.conflicts().is_empty());
+    }
+
+    #[test]
+    fn conflicts_in_the_config_file_are_found() {
+        let section: Section = toml::from_str("[keys]\nrestart = [\"B\"]\n").unwrap();
+        assert_eq!(
+            section.keys.conflicts(),
+            vec![(
+                chord(KeyCode::B, KeyMods::NONE),
+                Action::Restart,
+                Action::Blindfold
+            )]
+        );
+        // the action listed first wins
+        assert_eq!(
+            section.keys.action(KeyCode::B, KeyMods::NONE),
+            Some(Action::Restart)
+        );
+    }
+}
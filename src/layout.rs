@@ -0,0 +1,186 @@
+/**
+ * How the screens are fitted into the window, which can be resized. Whatever's shown is scaled
+ * to fit it, centred without being stretched, so a window the size the screens are drawn for
+ * shows them as they are. In kiosk mode (--kiosk) the window fills the monitor, e.g. for
+ * streaming or a big screen at a club, and a game is shown as the board alone. A window made
+ * small, e.g. to keep a game pinned over other work, shows a game as the board with one line
+ * under it.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::board::BOARD_SIZE;
+use ggez::graphics::Rect;
+
+/// The smallest the window can be made, which still fits the mini layout at a playable size.
+pub const MINI_WINDOW: (f32, f32) = (360.0, 400.0);
+
+/// How much smaller than they're drawn the screens would be shown before a game switches to the
+/// mini layout.
+const MINI_SCALE: f32 = 0.5;
+
+/// The part of a screen's coordinates that's fitted into the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// The whole screen, with the panels and status text around the board.
+    Standard,
+    /// The board and its frame, without anything around them.
+    Kiosk,
+    /// The board, with the status and the draw and resign buttons on one line under it.
+    Mini,
+}
+
+impl Layout {
+    /// The area shown, in the screen's coordinates.
+    pub fn area(self) -> Rect {
+        match self {
+            Layout::Standard => Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1),
+            Layout::Kiosk => Rect::new(0.0, 0.0, BOARD_SIZE.0, BOARD_SIZE.1),
+            Layout::Mini => Rect::new(0.0, 0.0, BOARD_SIZE.0, SCREEN_SIZE.1),
+        }
+    }
+
+    /// How many window pixels the area's coordinates are drawn at, fitted into a window of
+    /// `window` size.
+    pub fn scale(self, window: (f32, f32)) -> f32 {
+        let area = self.area();
+        (window.0 / area.w).min(window.1 / area.h)
+    }
+
+    /// Screen coordinates that show the area as large as it fits in a window of `window` size,
+    /// centred, with the same scale both ways.
+    pub fn fit(self, window: (f32, f32)) -> Rect {
+        let area = self.area();
+        if window.0 <= 0.0 || window.1 <= 0.0 {
+            return area;
+        }
+        let scale = self.scale(window);
+        let (w, h) = (window.0 / scale, window.1 / scale);
+        Rect::new(
+            area.x - (w - area.w) / 2.0,
+            area.y - (h - area.h) / 2.0,
+            w,
+            h,
+        )
+    }
+}
+
+/// Whether a window is too small for a game to be shown as usual, so it's shown in the mini
+/// layout.
+pub fn cramped(window: (f32, f32)) -> bool {
+    window.0 > 0.0 && window.1 > 0.0 && Layout::Standard.scale(window) < MINI_SCALE
+}
+
+/// A point in a window of `window` size, in the coordinates `screen` shows it in.
+pub fn to_screen(screen: Rect, window: (f32, f32), x: f32, y: f32) -> (f32, f32) {
+    if window.0 <= 0.0 || window.1 <= 0.0 {
+        return (x, y);
+    }
+    (
+        screen.x + x * screen.w / window.0,
+        screen.y + y * screen.h / window.1,
+    )
+}
+
+/// Screen coordinates that show `inner` where the usual screen would be, given the screen
+/// coordinates set now, which may be fitted into a bigger window. With the usual screen coordinates
+/// set, that's just `inner`.
+pub fn within(screen: Rect, inner: Rect) -> Rect {
+    let area = Layout::Standard.area();
+    let (sx, sy) = (inner.w / area.w, inner.h / area.h);
+    Rect::new(
+        inner.x + (screen.x - area.x) * sx,
+        inner.y + (screen.y - area.y) * sy,
+        screen.w * sx,
+        screen.h * sy,
+    )
+}
+
+/// Screen coordinates that draw everything squeezed across by `scale` towards `centre`, a point
+/// across the screen that stays where it is, given the screen coordinates set now.
+pub fn squeezed(screen: Rect, centre: f32, scale: f32) -> Rect {
+    Rect::new(
+        centre - (centre - screen.x) / scale,
+        screen.y,
+        screen.w / scale,
+        screen.h,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{square_at, square_name, square_origin, GRID_CELL_SIZE};
+    use chess_template::Position;
+
+    #[test]
+    fn the_board_fills_a_wide_window_without_stretching() {
+        // exactly the screen's size changes nothing
+        assert_eq!(Layout::Standard.fit(SCREEN_SIZE), Layout::Standard.area());
+
+        // a 1920x1080 monitor is wider than the board, so it's centred between black bars
+        let screen = Layout::Kiosk.fit((1920.0, 1080.0));
+        assert!((screen.h - BOARD_SIZE.1).abs() < 0.01);
+        assert!((screen.w / screen.h - 1920.0 / 1080.0).abs() < 0.001);
+        assert!((screen.x + screen.w / 2.0 - BOARD_SIZE.0 / 2.0).abs() < 0.01);
+
+        // the middle of the window is the middle of the board, and its corners are the board's
+        let (x, y) = to_screen(screen, (1920.0, 1080.0), 960.0, 540.0);
+        assert!((x - BOARD_SIZE.0 / 2.0).abs() < 0.01 && (y - BOARD_SIZE.1 / 2.0).abs() < 0.01);
+        let (x, y) = to_screen(screen, (1920.0, 1080.0), 420.0, 1080.0);
+        assert!(x.abs() < 0.01 && (y - BOARD_SIZE.1).abs() < 0.01);
+    }
+
+    #[test]
+    fn clicks_in_a_mini_window_land_on_the_squares_drawn_there() {
+        assert!(cramped(MINI_WINDOW));
+        assert!(!cramped(SCREEN_SIZE) && !cramped((0.0, 0.0)));
+
+        // the whole board and the line under it fit, over half as big as in the standard layout
+        let screen = Layout::Mini.fit(MINI_WINDOW);
+        let area = Layout::Mini.area();
+        assert!(screen.x <= area.x && screen.y <= area.y);
+        assert!(screen.right() >= area.right() && screen.bottom() >= area.bottom());
+        assert!(Layout::Mini.scale(MINI_WINDOW) > 1.2 * Layout::Standard.scale(MINI_WINDOW));
+
+        // each square's middle, as drawn in the window, is clicked back on that square
+        for (row, col) in [(0, 0), (6, 4), (7, 7), (3, 3)] {
+            let pos = Position::new(row, col).unwrap();
+            let (x, y) = square_origin(pos);
+            let middle = (
+                x + GRID_CELL_SIZE.0 as f32 / 2.0,
+                y + GRID_CELL_SIZE.1 as f32 / 2.0,
+            );
+            let drawn = (
+                (middle.0 - screen.x) * MINI_WINDOW.0 / screen.w,
+                (middle.1 - screen.y) * MINI_WINDOW.1 / screen.h,
+            );
+            let (x, y) = to_screen(screen, MINI_WINDOW, drawn.0, drawn.1);
+            assert!(square_at(x, y) == Some(pos), "{} missed", square_name(pos));
+        }
+    }
+
+    #[test]
+    fn nested_coordinates_follow_the_fitted_screen() {
+        let inner = Rect::new(0.0, -30.0, SCREEN_SIZE.0 * 1.1, SCREEN_SIZE.1 + 30.0);
+        assert_eq!(within(Layout::Standard.area(), inner), inner);
+
+        // a point drawn at the inner rect's corner lands where the usual screen's corner is
+        let screen = Layout::Standard.fit((2000.0, 1000.0));
+        let nested = within(screen, inner);
+        let fraction = |rect: Rect, x: f32| (x - rect.x) / rect.w;
+        assert!((fraction(nested, inner.x) - fraction(screen, 0.0)).abs() < 0.0001);
+        assert!((fraction(nested, inner.right()) - fraction(screen, SCREEN_SIZE.0)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn a_squeezed_board_keeps_its_middle() {
+        let screen = Layout::Standard.fit((2000.0, 1000.0));
+        assert_eq!(squeezed(screen, 400.0, 1.0), screen);
+
+        // a point drawn 100 right of the middle shows where one 25 right of it did before
+        let fraction = |rect: Rect, x: f32| (x - rect.x) / rect.w;
+        let squeezed = squeezed(screen, 400.0, 0.25);
+        assert!((fraction(squeezed, 400.0) - fraction(screen, 400.0)).abs() < 0.0001);
+        assert!((fraction(squeezed, 500.0) - fraction(screen, 425.0)).abs() < 0.0001);
+        assert_eq!((squeezed.y, squeezed.h), (screen.y, screen.h));
+    }
+}
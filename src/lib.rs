@@ -0,0 +1,60 @@
+/**
+ * Chess GUI, as a library so the benchmarks can reach the drawing code. The game itself is the
+ * binary in main.rs.
+ * Author: Vilhelm Prytz <vilhelm@prytznet.se> / <vprytz@kth.se>
+ */
+pub mod ai;
+pub mod animation;
+pub mod annotate;
+pub mod announce;
+pub mod app;
+pub mod avatar;
+pub mod board;
+pub mod bot;
+pub mod chaos;
+pub mod chat;
+pub mod cli;
+pub mod config;
+pub mod confirm;
+pub mod connect;
+pub mod correspondence;
+pub mod crash;
+pub mod desync;
+pub mod dialog;
+pub mod editor;
+pub mod error;
+pub mod export;
+pub mod handover;
+pub mod headless;
+pub mod history;
+pub mod inbound;
+pub mod invite;
+pub mod keys;
+pub mod layout;
+pub mod logging;
+pub mod matches;
+pub mod matchlist;
+pub mod menu;
+pub mod movelog;
+pub mod net;
+pub mod openings;
+pub mod pause;
+pub mod protocol;
+pub mod rating;
+pub mod referee;
+pub mod render;
+pub mod resources;
+pub mod scene;
+pub mod session;
+pub mod settings;
+pub mod snapshot;
+pub mod spectators;
+pub mod suggest;
+pub mod tabs;
+pub mod toast;
+pub mod traffic;
+pub mod transcript;
+pub mod tutor;
+pub mod ui;
+pub mod waiting;
+pub mod window;
@@ -0,0 +1,52 @@
+/**
+ * Diagnostics, on stderr and optionally in a file. Levels are set per module with `RUST_LOG`,
+ * e.g. `RUST_LOG=vprytz_chess_gui::net=debug` to see every message sent and received.
+ */
+use env_logger::{Builder, Env, Target};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Used when `RUST_LOG` isn't set: our own info messages, and only warnings from libraries.
+const DEFAULT_FILTER: &str = "warn,vprytz_chess_gui=info";
+
+/// Writes everything to stderr and to a file.
+struct Tee {
+    file: File,
+}
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        self.file.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.file.flush()
+    }
+}
+
+/// Starts logging. With a log file, everything is also appended to it; if it can't be opened,
+/// logs only go to stderr and a warning says why.
+pub fn init(log_file: Option<&Path>) {
+    let mut builder = Builder::from_env(Env::default().default_filter_or(DEFAULT_FILTER));
+
+    let file = log_file.map(|path| {
+        let file = OpenOptions::new().create(true).append(true).open(path);
+        (path, file)
+    });
+
+    match file {
+        Some((_, Ok(file))) => {
+            builder.target(Target::Pipe(Box::new(Tee { file })));
+            builder.init();
+        }
+        Some((path, Err(err))) => {
+            builder.init();
+            log::warn!("Couldn't open log file {}: {}", path.display(), err);
+        }
+        None => builder.init(),
+    }
+}
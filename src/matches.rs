@@ -0,0 +1,420 @@
+/**
+ * The match history: every finished game against an opponent, one JSON line each in
+ * `matches.jsonl` in the data directory, written like the network transcripts. Games are
+ * appended as they end, and only rewritten to keep the scores of their moves once a replay has
+ * analysed them. A line that can't be read is skipped with a warning, so a damaged file loses
+ * those games and nothing else.
+ */
+use crate::annotate::Evaluation;
+use crate::board::{king_attacked, parse_move, square_name};
+use crate::export;
+use crate::protocol::{self, Score};
+use chess_template::{Colour, Game, GameState, Position};
+use directories::ProjectDirs;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Written as the time control until the game has a clock.
+pub const NO_TIME_CONTROL: &str = "none";
+
+/// How a game ended for us.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Won,
+    Lost,
+    Drawn,
+}
+
+impl Outcome {
+    /// How a game that's over on the board ended for `colour`: the side left without a move
+    /// loses if it's in check, and draws if it isn't. None while the game goes on.
+    pub fn on_board(game: &Game, colour: Colour) -> Option<Outcome> {
+        if game.get_game_state() != GameState::GameOver {
+            return None;
+        }
+        let stuck = game.get_active_colour();
+        Some(if !king_attacked(&game.get_board(), stuck) {
+            Outcome::Drawn
+        } else if stuck == colour {
+            Outcome::Lost
+        } else {
+            Outcome::Won
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Won => "won",
+            Outcome::Lost => "lost",
+            Outcome::Drawn => "drawn",
+        }
+    }
+
+    /// The result in PGN for a game we played as `colour`, e.g. "0-1".
+    pub fn result(self, colour: Colour) -> &'static str {
+        self.score(colour).code()
+    }
+
+    /// The result of a game we played as `colour`, as both players see it.
+    pub fn score(self, colour: Colour) -> Score {
+        match (self, colour) {
+            (Outcome::Drawn, _) => Score::Drawn,
+            (Outcome::Won, Colour::White) | (Outcome::Lost, Colour::Black) => Score::WhiteWins,
+            (Outcome::Won, Colour::Black) | (Outcome::Lost, Colour::White) => Score::BlackWins,
+        }
+    }
+
+    /// How a game with this result went for `colour`.
+    pub fn of(score: Score, colour: Colour) -> Outcome {
+        match (score, colour) {
+            (Score::Drawn, _) => Outcome::Drawn,
+            (Score::WhiteWins, Colour::White) | (Score::BlackWins, Colour::Black) => Outcome::Won,
+            (Score::WhiteWins, Colour::Black) | (Score::BlackWins, Colour::White) => Outcome::Lost,
+        }
+    }
+}
+
+/// A finished game.
+#[derive(Clone, PartialEq)]
+pub struct Match {
+    pub played_at: u64,   // when it ended, in seconds since the Unix epoch
+    pub opponent: String, // the room online, or who the computer was
+    pub colour: Colour,   // the colour we played
+    pub outcome: Outcome, // how it went for us
+    pub moves: Vec<(Position, Position)>, // every move in order, for replaying it
+    pub time_control: String, // NO_TIME_CONTROL, the game has no clock yet
+    pub pgn: String,      // the whole game, for pasting elsewhere
+    pub evaluations: Option<Vec<Evaluation>>, // each move's scores, once a replay analysed them
+}
+
+impl Match {
+    /// The day it was played, e.g. "2026-10-16".
+    pub fn date(&self) -> String {
+        export::date(UNIX_EPOCH + Duration::from_secs(self.played_at))
+    }
+
+    /// Full moves played, counting a move by White alone as one.
+    pub fn move_count(&self) -> usize {
+        self.moves.len().div_ceil(2)
+    }
+
+    /// Whether `other` is the same game, whether or not either has been analysed.
+    fn same_game(&self, other: &Match) -> bool {
+        self.played_at == other.played_at
+            && self.opponent == other.opponent
+            && self.moves == other.moves
+    }
+
+    /// The game as a line of JSON, without the newline.
+    pub fn to_line(&self) -> String {
+        let line = Line {
+            played_at: self.played_at,
+            opponent: self.opponent.clone(),
+            colour: protocol::colour_name(self.colour).to_string(),
+            result: self.outcome,
+            moves: self.move_count(),
+            time_control: self.time_control.clone(),
+            line: self
+                .moves
+                .iter()
+                .map(|&(from, to)| format!("{}{}", square_name(from), square_name(to)))
+                .collect::<Vec<String>>()
+                .join(" "),
+            pgn: self.pgn.clone(),
+            evaluations: self.evaluations.as_ref().map(|evaluations| {
+                evaluations
+                    .iter()
+                    .map(|evaluation| evaluation.to_text())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            }),
+        };
+        serde_json::to_string(&line).expect("a line is only strings and numbers")
+    }
+
+    /// Parses a line written by `to_line`. The move count is worked out from the moves.
+    pub fn parse(line: &str) -> Result<Match, String> {
+        let line: Line = serde_json::from_str(line).map_err(|err| err.to_string())?;
+        let colour = protocol::parse_colour(&line.colour).map_err(|err| err.to_string())?;
+        let moves = line
+            .line
+            .split_whitespace()
+            .map(|word| {
+                parse_move(word)
+                    .map(|(from, to, _)| (from, to))
+                    .ok_or_else(|| format!("{:?} isn't a move", word))
+            })
+            .collect::<Result<Vec<(Position, Position)>, String>>()?;
+
+        // games that were never analysed have no scores, and scores that don't fit the moves
+        // are dropped, so the game's analysed again
+        let evaluations = line.evaluations.and_then(|text| {
+            text.split_whitespace()
+                .map(Evaluation::parse)
+                .collect::<Option<Vec<Evaluation>>>()
+                .filter(|evaluations| evaluations.len() == moves.len())
+        });
+
+        Ok(Match {
+            played_at: line.played_at,
+            opponent: line.opponent,
+            colour,
+            outcome: line.result,
+            moves,
+            time_control: line.time_control,
+            pgn: line.pgn,
+            evaluations,
+        })
+    }
+}
+
+/// A game as it's written to the file. The moves are in coordinate notation, and their count is
+/// only there for whoever reads the file.
+#[derive(Serialize, Deserialize)]
+struct Line {
+    played_at: u64,
+    opponent: String,
+    colour: String,
+    result: Outcome,
+    #[serde(default)]
+    moves: usize,
+    time_control: String,
+    line: String,
+    pgn: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    evaluations: Option<String>, // each move's scores, e.g. "35/10/35 -20/-/-35"
+}
+
+/// Seconds since the Unix epoch, for `Match::played_at`.
+pub fn timestamp(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// `matches.jsonl` in the platform's data directory, if there is one.
+pub fn default_path() -> Option<PathBuf> {
+    ProjectDirs::from("se", "prytznet", "schack").map(|dirs| dirs.data_dir().join("matches.jsonl"))
+}
+
+/// The history from its usual place, which is empty if there's no data directory.
+pub fn load_default() -> Vec<Match> {
+    default_path().map_or_else(Vec::new, |path| load(&path))
+}
+
+/// Adds a game to the end of the history file, creating it if need be. If the file was cut off
+/// in the middle of a line, the game starts on a line of its own, so it can still be read.
+pub fn append(path: &Path, game: &Match) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    let mut line = String::new();
+    if file.metadata()?.len() > 0 {
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.push('\n');
+        }
+    }
+    line.push_str(&game.to_line());
+    line.push('\n');
+    file.write_all(line.as_bytes())
+}
+
+/// Puts `game` in place of the same game in the history file, e.g. once its moves are scored.
+/// The file is written anew next to the old one and moved over it, so it's never left half
+/// written. Returns whether the game was found.
+pub fn replace(path: &Path, game: &Match) -> io::Result<bool> {
+    let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    let mut found = false;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let same = !found && Match::parse(line).is_ok_and(|old| old.same_game(game));
+        found |= same;
+        lines.push(if same {
+            game.to_line()
+        } else {
+            line.to_string()
+        });
+    }
+    if !found {
+        return Ok(false);
+    }
+
+    let rewritten = path.with_extension("jsonl.tmp");
+    fs::write(&rewritten, lines.join("\n") + "\n")?;
+    fs::rename(&rewritten, path)?;
+    Ok(true)
+}
+
+/// Every game in the history file, the most recent first. A missing file is an empty history,
+/// and lines that can't be read are left out with a warning.
+pub fn load(path: &Path) -> Vec<Match> {
+    let text = match fs::read(path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Vec::new(),
+        Err(err) => {
+            warn!(
+                "Couldn't read the match history {}: {}",
+                path.display(),
+                err
+            );
+            return Vec::new();
+        }
+    };
+
+    let mut games = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Match::parse(line) {
+            Ok(game) => games.push(game),
+            Err(err) => warn!(
+                "Skipping line {} of the match history {}: {}",
+                i + 1,
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    // later lines first among games ending in the same second
+    games.reverse();
+    games.sort_by_key(|game| Reverse(game.played_at));
+    games
+}
+
+/// The games against opponents whose name contains `filter`, ignoring case. An empty filter
+/// matches every game.
+pub fn against<'a>(games: &'a [Match], filter: &str) -> Vec<&'a Match> {
+    let filter = filter.trim().to_lowercase();
+    games
+        .iter()
+        .filter(|game| game.opponent.to_lowercase().contains(&filter))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(played_at: u64, opponent: &str, moves: &[&str]) -> Match {
+        Match {
+            played_at,
+            opponent: opponent.to_string(),
+            colour: Colour::Black,
+            outcome: Outcome::Lost,
+            moves: moves
+                .iter()
+                .map(|text| {
+                    let (from, to, _) = parse_move(text).unwrap();
+                    (from, to)
+                })
+                .collect(),
+            time_control: NO_TIME_CONTROL.to_string(),
+            pgn: "[Result \"1-0\"]\n\n1. e4 1-0\n".to_string(),
+            evaluations: None,
+        }
+    }
+
+    #[test]
+    fn matches_round_trip() {
+        let original = game(
+            1_792_108_800,
+            "fredags\"schack\"",
+            &["e2e4", "e7e5", "g1f3"],
+        );
+        let line = original.to_line();
+        assert!(line.contains("\"moves\":2"));
+        assert!(Match::parse(&line) == Ok(original.clone()));
+        assert_eq!(original.date(), "2026-10-16");
+
+        assert!(Match::parse("{\"played_at\":1}").is_err());
+        let bad_move = line.replace("g1f3", "g1f9");
+        assert!(Match::parse(&bad_move).is_err());
+    }
+
+    #[test]
+    fn analysed_games_keep_their_scores() {
+        let path = std::env::temp_dir().join(format!("analysed-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        append(&path, &game(100, "Computer", &["e2e4"])).unwrap();
+        append(&path, &game(200, "lobby", &["d2d4", "d7d5"])).unwrap();
+
+        let mut analysed = game(200, "lobby", &["d2d4", "d7d5"]);
+        let scores = ["20/15/20", "-20/-/-35"];
+        analysed.evaluations = scores.iter().map(|text| Evaluation::parse(text)).collect();
+        assert!(replace(&path, &analysed).unwrap());
+        assert!(load(&path) == vec![analysed.clone(), game(100, "Computer", &["e2e4"])]);
+        assert!(!replace(&path, &game(300, "lobby", &[])).unwrap());
+
+        // scores that don't fit the moves are dropped
+        let line = analysed.to_line().replace(" -20/-/-35", "");
+        assert!(Match::parse(&line).unwrap().evaluations.is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_damaged_file_keeps_the_games_it_can() {
+        let path = std::env::temp_dir().join(format!("matches-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(load(&path).is_empty());
+
+        append(&path, &game(100, "Computer", &["e2e4"])).unwrap();
+        append(&path, &game(300, "lobby", &["d2d4"])).unwrap();
+        // a line cut off half way, as if the program died while writing it
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"played_at\":200,\"oppo").unwrap();
+        drop(file);
+        append(&path, &game(200, "Random mover", &[])).unwrap();
+
+        let opponents: Vec<String> = load(&path).into_iter().map(|game| game.opponent).collect();
+        assert_eq!(opponents, ["lobby", "Random mover", "Computer"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn filtered_by_opponent() {
+        let games = [
+            game(3, "Fredagsschack", &[]),
+            game(2, "Computer", &[]),
+            game(1, "fredag", &[]),
+        ];
+        let names = |filter: &str| -> Vec<&str> {
+            against(&games, filter)
+                .iter()
+                .map(|game| game.opponent.as_str())
+                .collect()
+        };
+        assert_eq!(names("FREDAG "), ["Fredagsschack", "fredag"]);
+        assert_eq!(names("").len(), 3);
+        assert!(names("lobby").is_empty());
+    }
+
+    #[test]
+    fn results_for_each_side() {
+        assert_eq!(Outcome::Won.result(Colour::Black), "0-1");
+        assert_eq!(Outcome::Lost.result(Colour::Black), "1-0");
+        assert_eq!(Outcome::Drawn.result(Colour::White), "1/2-1/2");
+        for outcome in [Outcome::Won, Outcome::Lost, Outcome::Drawn] {
+            let written = serde_json::to_string(&outcome).unwrap();
+            assert_eq!(written, format!("{:?}", outcome.name()));
+            for colour in [Colour::White, Colour::Black] {
+                assert_eq!(Outcome::of(outcome.score(colour), colour), outcome);
+            }
+        }
+    }
+}
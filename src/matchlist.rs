@@ -0,0 +1,350 @@
+/**
+ * The History screen, opened from the main menu: games from the match history, the most recent
+ * first, narrowed down by typing part of an opponent's name. Picking one replays it on the board,
+ * a move a second.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::matches::{self, Match, Outcome};
+use crate::protocol::Message;
+use crate::session::Session;
+use crate::transcript;
+use crate::ui::{self, ScrollPanel, TextInput};
+use ggez::{event, graphics, Context, GameResult};
+
+/// Longest filter that can be typed.
+const FILTER_LENGTH: usize = 32;
+
+/// Where the filter field is, and the list of games under it.
+const FILTER_RECT: graphics::Rect = graphics::Rect {
+    x: (SCREEN_SIZE.0 - LIST_WIDTH) / 2.0,
+    y: 150.0,
+    w: LIST_WIDTH,
+    h: 40.0,
+};
+const LIST_RECT: graphics::Rect = graphics::Rect {
+    x: (SCREEN_SIZE.0 - LIST_WIDTH) / 2.0,
+    y: 210.0,
+    w: LIST_WIDTH,
+    h: 520.0,
+};
+const LIST_WIDTH: f32 = 700.0;
+const ROW_HEIGHT: f32 = 40.0;
+
+/// Time between the moves of a game being replayed.
+const REPLAY_STEP_MS: u64 = 1000;
+
+/// The room a replayed game is shown in. Messages are split at spaces, so it can't be the
+/// opponent's name.
+const REPLAY_ROOM: &str = "history";
+
+pub struct MatchList {
+    games: Vec<Match>,      // the whole history, most recent first
+    shown: Vec<Match>,      // the games matching the filter
+    filter: TextInput,      // part of an opponent's name, always taking what's typed
+    selected: usize,        // row picked with the arrow keys, in `shown`
+    hovered: Option<usize>, // row under the mouse, in `shown`
+    panel: ScrollPanel,     // scrolls the list once it's longer than the screen
+    chosen: Option<Match>,  // game to replay, until the scene takes it
+    leaving: bool,          // Escape was pressed, go back to the menu
+}
+
+impl MatchList {
+    pub fn new(games: Vec<Match>) -> MatchList {
+        let mut list = MatchList {
+            shown: Vec::new(),
+            games,
+            filter: TextInput::new("", FILTER_LENGTH, ui::any_char),
+            selected: 0,
+            hovered: None,
+            panel: ScrollPanel::new(LIST_RECT, false),
+            chosen: None,
+            leaving: false,
+        };
+        list.refilter();
+        list
+    }
+
+    /// The history from its usual place.
+    pub fn load() -> MatchList {
+        MatchList::new(matches::load_default())
+    }
+
+    /// Whether the player asked to go back to the main menu.
+    pub fn leaving(&self) -> bool {
+        self.leaving
+    }
+
+    /// The game picked since the last call, if any.
+    pub fn take_choice(&mut self) -> Option<Match> {
+        self.chosen.take()
+    }
+
+    /// Lists the games matching the filter again, from the top.
+    fn refilter(&mut self) {
+        self.shown = matches::against(&self.games, self.filter.text())
+            .into_iter()
+            .cloned()
+            .collect();
+        self.selected = 0;
+        self.hovered = None;
+        self.panel
+            .set_content_height(self.shown.len() as f32 * ROW_HEIGHT);
+        self.panel.scroll_to(0.0);
+    }
+
+    /// Moves the selection by `step` rows, scrolling it into view.
+    fn select_by(&mut self, step: isize) {
+        if self.shown.is_empty() {
+            return;
+        }
+        let last = self.shown.len() as isize - 1;
+        self.selected = (self.selected as isize + step).clamp(0, last) as usize;
+
+        let top = self.selected as f32 * ROW_HEIGHT;
+        if top < self.panel.offset() {
+            self.panel.scroll_to(top);
+        } else if top + ROW_HEIGHT > self.panel.offset() + LIST_RECT.h {
+            self.panel.scroll_to(top + ROW_HEIGHT - LIST_RECT.h);
+        }
+    }
+
+    /// The row at a point on the screen, if there is one.
+    fn row_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !LIST_RECT.contains([x, y]) {
+            return None;
+        }
+        let row = ((y - LIST_RECT.y + self.panel.offset()) / ROW_HEIGHT) as usize;
+        (row < self.shown.len()).then_some(row)
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        let title = graphics::Text::new(
+            graphics::TextFragment::from("History").scale(graphics::PxScale { x: 48.0, y: 48.0 }),
+        );
+        let title_width = title.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - title_width) / 2.0, 60.0]),
+        )?;
+
+        self.filter
+            .draw(ctx, FILTER_RECT, true, "Filter by opponent")?;
+
+        if self.shown.is_empty() {
+            let message = if self.games.is_empty() {
+                "No games yet. Games against the computer or online are kept here once they end."
+                    .to_string()
+            } else {
+                format!("No games against {:?}", self.filter.text().trim())
+            };
+            let mut text = graphics::Text::new(
+                graphics::TextFragment::from(message).scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+            );
+            text.set_bounds([LIST_RECT.w, f32::INFINITY], graphics::Align::Center);
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([LIST_RECT.x, LIST_RECT.y + 20.0]),
+            )?;
+        }
+
+        let (shown, selected, hovered) = (&self.shown, self.selected, self.hovered);
+        self.panel.draw(ctx, |ctx| {
+            for (i, game) in shown.iter().enumerate() {
+                let rect = graphics::Rect::new(
+                    LIST_RECT.x,
+                    LIST_RECT.y + i as f32 * ROW_HEIGHT,
+                    LIST_RECT.w,
+                    ROW_HEIGHT - 4.0,
+                );
+                let colour = if i == selected {
+                    [0.3, 0.6, 0.3, 1.0]
+                } else if hovered == Some(i) {
+                    [0.45, 0.45, 0.45, 1.0]
+                } else {
+                    [0.4, 0.4, 0.4, 1.0]
+                };
+                let background = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    rect,
+                    colour.into(),
+                )?;
+                graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+                let scale = graphics::PxScale { x: 20.0, y: 20.0 };
+                let result_colour = match game.outcome {
+                    Outcome::Won => [0.7, 1.0, 0.7, 1.0],
+                    Outcome::Lost => [1.0, 0.7, 0.7, 1.0],
+                    Outcome::Drawn => [1.0, 1.0, 1.0, 1.0],
+                };
+                let mut label = graphics::Text::new(
+                    graphics::TextFragment::from(format!(
+                        "{}   {}   {:?}   ",
+                        game.date(),
+                        game.opponent,
+                        game.colour
+                    ))
+                    .scale(scale),
+                );
+                label.add(
+                    graphics::TextFragment::from(game.outcome.name())
+                        .color(graphics::Color::from(result_colour))
+                        .scale(scale),
+                );
+                label.add(
+                    graphics::TextFragment::from(format!("   {} moves", game.move_count()))
+                        .scale(scale),
+                );
+                let height = label.dimensions(ctx).h;
+                graphics::draw(
+                    ctx,
+                    &label,
+                    graphics::DrawParam::default()
+                        .color([1.0, 1.0, 1.0, 1.0].into())
+                        .dest([rect.x + 12.0, rect.y + (rect.h - height) / 2.0]),
+                )?;
+            }
+            Ok(())
+        })?;
+
+        let mut hint = graphics::Text::new(
+            graphics::TextFragment::from(
+                "Type to filter by opponent, Up and Down pick a game, Enter replays it, Escape goes back.",
+            )
+            .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        hint.set_bounds(
+            [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+            graphics::Align::Center,
+        );
+        graphics::draw(
+            ctx,
+            &hint,
+            graphics::DrawParam::default()
+                .color([0.2, 0.2, 0.2, 1.0].into())
+                .dest([20.0, SCREEN_SIZE.1 - 40.0]),
+        )?;
+
+        graphics::present(ctx)
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.panel.mouse_motion_event(x, y);
+        self.hovered = self.row_at(x, y);
+    }
+
+    pub fn mouse_button_down_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.panel.mouse_button_down_event(button, x, y);
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        if self.panel.mouse_button_up_event(button) || button != event::MouseButton::Left {
+            return;
+        }
+        if let Some(row) = self.row_at(x, y) {
+            self.selected = row;
+            self.chosen = Some(self.shown[row].clone());
+        }
+    }
+
+    pub fn mouse_wheel_event(&mut self, y: f32) {
+        self.panel.mouse_wheel_event(y);
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        match key {
+            event::KeyCode::Escape => self.leaving = true,
+            event::KeyCode::Up => self.select_by(-1),
+            event::KeyCode::Down => self.select_by(1),
+            event::KeyCode::PageUp => self.select_by(-((LIST_RECT.h / ROW_HEIGHT) as isize)),
+            event::KeyCode::PageDown => self.select_by((LIST_RECT.h / ROW_HEIGHT) as isize),
+            event::KeyCode::Return | event::KeyCode::NumpadEnter => {
+                self.chosen = self.shown.get(self.selected).cloned();
+            }
+            _ => self.edit_filter(|filter| {
+                filter.key_down_event(key, mods);
+            }),
+        }
+    }
+
+    pub fn text_input_event(&mut self, character: char) {
+        self.edit_filter(|filter| filter.text_input_event(character));
+    }
+
+    /// Changes the filter, listing the games again if that changed what it says.
+    fn edit_filter(&mut self, change: impl FnOnce(&mut TextInput)) {
+        let before = self.filter.text().to_string();
+        change(&mut self.filter);
+        if self.filter.text() != before {
+            self.refilter();
+        }
+    }
+}
+
+/// A game from the history played back on the board from our side, a move at a time, with
+/// nothing sent anywhere.
+pub fn replay_session(game: &Match) -> Session {
+    let incoming = game
+        .moves
+        .iter()
+        .enumerate()
+        .map(|(i, &(from, to))| {
+            let msg = Message::Move {
+                room: REPLAY_ROOM.to_string(),
+                counter: i as u32 + 2,
+                from,
+                to,
+            };
+            ((i as u64 + 1) * REPLAY_STEP_MS, msg.to_string())
+        })
+        .collect();
+    let mut session =
+        transcript::playback(REPLAY_ROOM.to_string(), game.colour, false, incoming, false);
+    session.recorded = Some(Box::new(game.clone()));
+    session
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_move;
+    use chess_template::Colour;
+
+    fn game(opponent: &str) -> Match {
+        let (from, to, _) = parse_move("e2e4").unwrap();
+        Match {
+            played_at: 0,
+            opponent: opponent.to_string(),
+            colour: Colour::White,
+            outcome: Outcome::Won,
+            moves: vec![(from, to)],
+            time_control: matches::NO_TIME_CONTROL.to_string(),
+            pgn: String::new(),
+            evaluations: None,
+        }
+    }
+
+    #[test]
+    fn typing_filters_and_enter_picks() {
+        let mut list = MatchList::new(vec![game("lobby"), game("Computer, level 4"), game("bot")]);
+        list.key_down_event(event::KeyCode::Down, event::KeyMods::NONE);
+        list.key_down_event(event::KeyCode::Return, event::KeyMods::NONE);
+        assert_eq!(list.take_choice().unwrap().opponent, "Computer, level 4");
+
+        for c in "LOB".chars() {
+            list.text_input_event(c);
+        }
+        assert_eq!(list.shown.len(), 1);
+        list.key_down_event(event::KeyCode::Return, event::KeyMods::NONE);
+        assert_eq!(list.take_choice().unwrap().opponent, "lobby");
+        assert!(list.take_choice().is_none());
+    }
+}
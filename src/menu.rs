@@ -0,0 +1,156 @@
+/**
+ * The main menu, shown when the game starts and when leaving a game.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::ui::{self, Button, Buttons};
+use ggez::{event, graphics, Context, GameResult};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuItem {
+    PlayOnline,
+    PlayLocal,
+    PlayComputer,
+    SetUpPosition,
+    ReplayPgn,
+    History,
+    Settings,
+    Quit,
+}
+
+/// Items in the order they're shown.
+const ITEMS: [MenuItem; 8] = [
+    MenuItem::PlayOnline,
+    MenuItem::PlayLocal,
+    MenuItem::PlayComputer,
+    MenuItem::SetUpPosition,
+    MenuItem::ReplayPgn,
+    MenuItem::History,
+    MenuItem::Settings,
+    MenuItem::Quit,
+];
+
+/// Size of a button, and where the first one is drawn. The rest follow below it.
+const BUTTON_SIZE: (f32, f32) = (320.0, 50.0);
+const BUTTON_TOP: f32 = 180.0;
+const BUTTON_GAP: f32 = 20.0;
+
+impl MenuItem {
+    fn label(self) -> &'static str {
+        match self {
+            MenuItem::PlayOnline => "Play Online",
+            MenuItem::PlayLocal => "Play Local (hotseat)",
+            MenuItem::PlayComputer => "Play vs Computer",
+            MenuItem::SetUpPosition => "Set Up Position",
+            MenuItem::ReplayPgn => "Replay PGN",
+            MenuItem::History => "History",
+            MenuItem::Settings => "Settings",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
+pub struct Menu {
+    buttons: Buttons<MenuItem>, // one for each item, focused with the arrow keys or Tab
+    notice: Option<String>,     // shown under the buttons, e.g. why an item isn't available
+}
+
+impl Default for Menu {
+    fn default() -> Menu {
+        Menu::new()
+    }
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        let rects = ui::column(
+            ITEMS.len(),
+            BUTTON_SIZE,
+            BUTTON_GAP,
+            SCREEN_SIZE.0 / 2.0,
+            BUTTON_TOP,
+        );
+        let buttons = ITEMS
+            .iter()
+            .zip(rects)
+            .map(|(&item, rect)| Button::new(rect, item.label(), item))
+            .collect();
+        Menu {
+            buttons: Buttons::new(buttons),
+            notice: None,
+        }
+    }
+
+    /// The item picked since the last call, if any. Picking one clears the notice.
+    pub fn take_choice(&mut self) -> Option<MenuItem> {
+        let item = self.buttons.take_choice()?;
+        self.notice = None;
+        Some(item)
+    }
+
+    /// Shows a message under the buttons until the next item is picked.
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.notice = Some(text.into());
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        let title = graphics::Text::new(
+            graphics::TextFragment::from("Schack").scale(graphics::PxScale { x: 60.0, y: 60.0 }),
+        );
+        let title_width = title.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - title_width) / 2.0, 100.0]),
+        )?;
+
+        self.buttons.draw(ctx)?;
+
+        if let Some(notice) = &self.notice {
+            let mut text = graphics::Text::new(
+                graphics::TextFragment::from(notice.as_str())
+                    .scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+            );
+            text.set_bounds(
+                [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+                graphics::Align::Center,
+            );
+            let below = BUTTON_TOP + ITEMS.len() as f32 * (BUTTON_SIZE.1 + BUTTON_GAP);
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.7, 0.0, 0.0, 1.0].into())
+                    .dest([20.0, below + 10.0]),
+            )?;
+        }
+
+        graphics::present(ctx)
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.buttons.mouse_motion_event(x, y);
+    }
+
+    pub fn mouse_button_down_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.buttons.mouse_button_down_event(button, x, y);
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        self.buttons.mouse_button_up_event(button, x, y);
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        match key {
+            event::KeyCode::Up => self.buttons.focus_previous(),
+            event::KeyCode::Down => self.buttons.focus_next(),
+            event::KeyCode::Escape => self.buttons.pick(MenuItem::Quit),
+            _ => {
+                self.buttons.key_down_event(key, mods);
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+/**
+ * A live log of the moves played, for overlays and bots that tail it. With `--movelog`, every
+ * move is appended to the file as soon as it's played, one tab-separated line each:
+ *
+ * ```text
+ * # 1760608800000 new game in room myroom
+ * 1760608805230  2  white  e2e4  rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+ * ```
+ *
+ * with tabs between the fields: milliseconds since the Unix epoch, the move counter, the colour
+ * that moved, the move in coordinate notation and the position after it in FEN. Each new game,
+ * including a restart, starts with a line beginning with `#`.
+ */
+use chess_template::Colour;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct MoveLog {
+    file: File,
+}
+
+impl MoveLog {
+    /// Opens the file for appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<MoveLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(MoveLog { file })
+    }
+
+    /// Marks the start of a new game.
+    pub fn section(&mut self, room: &str) -> io::Result<()> {
+        writeln!(self.file, "# {} new game in room {}", millis(), room)?;
+        self.file.flush()
+    }
+
+    /// Appends a move, flushing it right away so anything tailing the file sees it.
+    pub fn record(
+        &mut self,
+        counter: u32,
+        colour: Colour,
+        notation: &str,
+        fen: &str,
+    ) -> io::Result<()> {
+        let colour = match colour {
+            Colour::White => "white",
+            Colour::Black => "black",
+        };
+        writeln!(
+            self.file,
+            "{}\t{}\t{}\t{}\t{}",
+            millis(),
+            counter,
+            colour,
+            notation,
+            fen
+        )?;
+        self.file.flush()
+    }
+}
+
+/// The time now, in milliseconds since the Unix epoch.
+fn millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn moves_are_appended_under_a_header() {
+        let path = std::env::temp_dir().join(format!("movelog-{}.tsv", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut log = MoveLog::open(&path).unwrap();
+        log.section("myroom").unwrap();
+        log.record(2, Colour::White, "e2e4", "fen after").unwrap();
+        // what's written is on disk before the log is closed
+        let text = fs::read_to_string(&path).unwrap();
+        drop(log);
+
+        MoveLog::open(&path).unwrap().section("myroom").unwrap();
+        let reopened = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<_> = text.lines().collect();
+        assert!(lines[0].starts_with("# ") && lines[0].ends_with(" new game in room myroom"));
+        let fields: Vec<_> = lines[1].split('\t').collect();
+        assert_eq!(fields[1..], ["2", "white", "e2e4", "fen after"]);
+        assert!(fields[0].parse::<u128>().is_ok());
+        assert_eq!(reopened.lines().count(), 3);
+    }
+}
@@ -0,0 +1,352 @@
+/**
+ * Connection to the relay server, and the lobby handshake that pairs two players in a room.
+ */
+use crate::protocol::{self, Message, MSG_SIZE};
+use chess_template::Colour;
+use log::{debug, info, warn};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+
+/* address to server. */
+pub const SERVER_ADDR: &str = "127.0.0.1:6000"; // default
+
+/// How many messages can wait in each direction between the GUI and the thread talking to the
+/// opponent. Past that, the network thread stops reading from the socket until the GUI catches
+/// up, and the GUI keeps its own queue of messages to send (see `AppState::send`).
+pub const CHANNEL_CAPACITY: usize = 64;
+
+/// Connects to the server and starts the network threads, one reading and one writing. Messages
+/// sent on the returned sender go to the server, and messages from the server arrive on the
+/// returned receiver. Dropping the sender closes the connection.
+pub fn online_setup(server_addr: &str) -> Result<(SyncSender<String>, Receiver<String>), String> {
+    // Copied mostly from https://github.com/IndaPlus22/AssignmentInstructions-BlueNote/blob/main/task-14/rust-example/client/src/main.rs
+    // Original Author: Tensor-Programming, Viola Söderlund <violaso@kth.se>
+
+    // connect to server
+    let mut client = match TcpStream::connect(server_addr) {
+        Ok(_client) => {
+            info!("Connected to server at {}", server_addr);
+            _client
+        }
+        Err(err) => {
+            return Err(format!(
+                "Failed to connect to server at {}: {}",
+                server_addr, err
+            ))
+        }
+    };
+    // send moves as soon as they're written, rather than waiting to fill a packet
+    client
+        .set_nodelay(true)
+        .map_err(|err| format!("Failed to set up the connection: {}", err))?;
+    let mut writer = client
+        .try_clone()
+        .map_err(|err| format!("Failed to set up the connection: {}", err))?;
+
+    // create channel for communication between threads, from main thread to network thread
+    let (sender, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+
+    // create channel for communication between threads, from network thread to main thread
+    let (to_mainthread_sender, to_mainthread_receiver) =
+        mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+
+    /* Start thread that listens to server. It blocks until a whole message has arrived. Once it
+     * stops, the main thread sees its channel disconnect. */
+    thread::spawn(move || {
+        let mut msg_buffer = vec![0; MSG_SIZE];
+        loop {
+            /* Read message from server. */
+            if let Err(err) = client.read_exact(&mut msg_buffer) {
+                warn!("Lost connection with server: {}", err);
+                break;
+            }
+
+            // received message, send it to main thread
+            match protocol::decode(&msg_buffer) {
+                Some(msg) => {
+                    debug!("received {:?}", msg.trim_end());
+                    // waits while the main thread is behind, leaving the rest on the socket.
+                    // Fails if it has stopped listening, so there's nobody left to talk to
+                    if to_mainthread_sender.send(msg).is_err() {
+                        break;
+                    }
+                }
+                None => warn!("Ignored a message that isn't valid UTF-8"),
+            }
+        }
+
+        // make the writer's next write fail, in case the main thread is still sending
+        let _ = client.shutdown(Shutdown::Both);
+    });
+
+    /* Start thread that sends messages to server, as soon as the main thread hands them over. */
+    thread::spawn(move || {
+        // the channel closes when the main thread drops its sender, e.g. on leaving the game
+        for msg in receiver {
+            debug!("sending {:?}", msg.trim_end());
+            if let Err(err) = writer.write_all(&protocol::encode(&msg)) {
+                warn!("Failed to send message: {}", err);
+                break;
+            }
+        }
+
+        // unblock the reader, which is waiting for a message that won't come now
+        info!("Closing connection to server");
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    Ok((sender, to_mainthread_receiver))
+}
+
+/// Stands in for the server when playing offline. Every message is echoed straight back, the
+/// same way the server relays messages to everyone in the room.
+pub fn offline_setup() -> (SyncSender<String>, Receiver<String>) {
+    // create channel for communication between threads, from main thread to loopback thread
+    let (sender, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+
+    // create channel for communication between threads, from loopback thread to main thread
+    let (to_mainthread_sender, to_mainthread_receiver) =
+        mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+
+    // runs until the main thread has terminated
+    thread::spawn(move || {
+        for msg in receiver {
+            if to_mainthread_sender.send(msg).is_err() {
+                break;
+            }
+        }
+    });
+
+    (sender, to_mainthread_receiver)
+}
+
+/// The lobby handshake that pairs two players in a room. Each announces itself with a random
+/// number, and the lower number plays white, along with its avatar. It's polled from the GUI each
+/// frame, so the window stays responsive while waiting for an opponent.
+///
+/// The number comes from the seed, so known seeds always get the same colours. The announcement
+/// is tagged with a number picked apart from the seed, to tell the server's echo of it from an
+/// opponent who drew the same number; on such a tie the lower tag plays white.
+pub struct Handshake {
+    join: Message, // our announcement
+    id: u8,        // the random number in it
+    instance: u32, // the tag it's sent with
+}
+
+/// How the handshake came out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Paired {
+    pub colour: Colour,       // the colour we play
+    pub avatar: Option<u8>,   // the opponent's avatar, if they picked one
+    pub correspondence: bool, // joining a correspondence game the opponent started
+}
+
+impl Handshake {
+    /// Announces us in the room, with our avatar if we picked one.
+    pub fn start(
+        room_name: &str,
+        avatar: Option<u8>,
+        sender: &SyncSender<String>,
+        rng: &mut StdRng,
+    ) -> Result<Handshake, String> {
+        // generate random  number
+        let id: u8 = rng.gen();
+
+        // send room name to server, along with random number as identifier
+        let join = Message::Join {
+            room: room_name.to_string(),
+            id,
+            avatar,
+        };
+        let instance = rand::random();
+        sender
+            .send(protocol::tag(&join.to_string(), instance))
+            .map_err(lost)?;
+
+        // wait for oponnent to join
+        info!("Joined room {}, waiting for opponent", room_name);
+        Ok(Handshake { join, id, instance })
+    }
+
+    /// Acts on the messages that have arrived since the last call. Returns the colour we play and
+    /// the opponent's avatar once they've joined, or an error if the connection is lost.
+    pub fn poll(
+        &self,
+        sender: &SyncSender<String>,
+        receiver: &Receiver<String>,
+    ) -> Result<Option<Paired>, String> {
+        loop {
+            let msg = match receiver.try_recv() {
+                Ok(msg) => msg,
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(lost(())),
+            };
+            if let Some(paired) = self.handle(&msg, sender)? {
+                return Ok(Some(paired));
+            }
+        }
+    }
+
+    /// Acts on one message from the server. Returns the colour we play and the opponent's avatar
+    /// if it's the opponent joining, or offering to sync a correspondence game they started.
+    fn handle(&self, msg: &str, sender: &SyncSender<String>) -> Result<Option<Paired>, String> {
+        // the server echoes our own announcement back too, so skip anything with our tag
+        let tag = protocol::untag(msg).0;
+        if tag == Some(self.instance) {
+            return Ok(None);
+        }
+        let (id, avatar) = match Message::parse(msg) {
+            Ok(Message::Join { room, id, avatar }) if room == self.join.room() => (id, avatar),
+            // whoever starts a correspondence game plays white
+            Ok(Message::SyncOffer { room, .. }) if room == self.join.room() => {
+                info!(
+                    "Joining the correspondence game in room {}, playing black",
+                    room
+                );
+                return Ok(Some(Paired {
+                    colour: Colour::Black,
+                    avatar: None,
+                    correspondence: true,
+                }));
+            }
+            _ => return Ok(None),
+        };
+
+        // send message to other player that we have joined
+        sender
+            .send(protocol::tag(&self.join.to_string(), self.instance))
+            .map_err(lost)?;
+        info!("Opponent joined room {}", self.join.room());
+
+        // if our random_number is lower than the other player's random_number, we are white. An
+        // older client sends no tag, and as it would take our announcement for its own echo on a
+        // tie, it doesn't matter which we pick then
+        let colour = if (self.id, Some(self.instance)) < (id, tag) {
+            info!("Playing white");
+            Colour::White
+        } else {
+            info!("Playing black");
+            Colour::Black
+        };
+        Ok(Some(Paired {
+            colour,
+            avatar,
+            correspondence: false,
+        }))
+    }
+}
+
+/// The network thread has stopped, which it only does when the connection is gone.
+fn lost<E>(_: E) -> String {
+    "Lost connection with server!".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    #[test]
+    fn messages_are_relayed_and_dropping_the_sender_disconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        // a server that echoes one message back, then reports whether the client hung up
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = vec![0; MSG_SIZE];
+            stream.read_exact(&mut buffer).unwrap();
+            stream.write_all(&buffer).unwrap();
+            stream.read(&mut buffer).unwrap() == 0
+        });
+
+        let (sender, receiver) = online_setup(&addr).unwrap();
+        sender.send("room reset ".to_string()).unwrap();
+        let echoed = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(echoed.trim_end(), "room reset");
+
+        drop(sender);
+        assert!(server.join().unwrap());
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_err());
+    }
+
+    /// How the handshake comes out for two players seeded with `first` and `second`, the first
+    /// with the Rook avatar and the second without one, meeting in a room on a relay that echoes
+    /// every message to both, like the server.
+    fn negotiate(first: u64, second: u64) -> (Paired, Paired) {
+        let (to_relay, from_players) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let (to_first, first_inbox) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (to_second, second_inbox) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        thread::spawn(move || {
+            // a player that has joined stops listening, so keep relaying to the other
+            for msg in from_players {
+                let _ = to_first.send(msg.clone());
+                let _ = to_second.send(msg);
+            }
+        });
+
+        let player = |inbox: Receiver<String>, seed, avatar| {
+            let sender = to_relay.clone();
+            thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let handshake = Handshake::start("room", avatar, &sender, &mut rng).unwrap();
+                loop {
+                    if let Some(paired) = handshake.poll(&sender, &inbox).unwrap() {
+                        return paired;
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+        };
+        let first = player(first_inbox, first, Some(2));
+        let second = player(second_inbox, second, None);
+        (first.join().unwrap(), second.join().unwrap())
+    }
+
+    #[test]
+    fn fixed_seeds_always_get_the_same_colours() {
+        use Colour::{Black, White};
+        let colours = |(first, second): (Paired, Paired)| (first.colour, second.colour);
+        for _ in 0..3 {
+            assert_eq!(colours(negotiate(1, 2)), (Black, White));
+            assert_eq!(colours(negotiate(2, 1)), (White, Black));
+            assert_eq!(colours(negotiate(3, 4)), (Black, White));
+        }
+    }
+
+    #[test]
+    fn players_with_the_same_seed_still_pair() {
+        for _ in 0..3 {
+            let (first, second) = negotiate(7, 7);
+            assert_ne!(first.colour, second.colour);
+        }
+    }
+
+    #[test]
+    fn only_our_own_announcement_is_taken_for_the_echo() {
+        let (sender, sent) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let handshake =
+            Handshake::start("room", None, &sender, &mut StdRng::seed_from_u64(1)).unwrap();
+        let echo = sent.recv().unwrap();
+        assert_eq!(handshake.handle(&echo, &sender).unwrap(), None);
+
+        // the same announcement from another client, or an older one without a tag
+        let (_, join) = protocol::untag(&echo);
+        let other = protocol::tag(join, handshake.instance.wrapping_add(1));
+        assert!(handshake.handle(&other, &sender).unwrap().is_some());
+        assert!(handshake.handle(join, &sender).unwrap().is_some());
+    }
+
+    #[test]
+    fn each_player_gets_the_others_avatar() {
+        let (first, second) = negotiate(1, 2);
+        assert_eq!(first.avatar, None);
+        assert_eq!(second.avatar, Some(2));
+        assert!(!first.correspondence && !second.correspondence);
+    }
+}
@@ -0,0 +1,581 @@
+//! A small opening book, used to name the opening while the game is still in book.
+//!
+//! Openings are only recognised by their exact move order: a game that transposes into a known
+//! opening through a different order of moves isn't named, and drops out of book instead.
+
+/// Known openings, keyed by their moves in coordinate notation separated by spaces.
+/// Kept sorted by key, so the table can be binary searched and every line that continues
+/// a given sequence of moves is found right after it.
+const OPENINGS: &[(&str, &str)] = &[
+    ("a2a3", "Anderssen's Opening"),
+    ("b1c3", "Van Geet Opening"),
+    ("b2b3", "Larsen's Opening"),
+    ("b2b4", "Polish Opening"),
+    ("c2c3", "Saragossa Opening"),
+    ("c2c4", "English Opening"),
+    ("c2c4 c7c5", "English Opening: Symmetrical Variation"),
+    ("c2c4 d7d5", "English Opening: Anglo-Scandinavian Defence"),
+    ("c2c4 e7e5", "English Opening: King's English Variation"),
+    (
+        "c2c4 e7e5 b1c3 g8f6 g1f3 b8c6",
+        "English Opening: King's English, Four Knights",
+    ),
+    ("c2c4 g8f6", "English Opening: Anglo-Indian Defence"),
+    ("d2d3", "Mieses Opening"),
+    ("d2d4", "Queen's Pawn Game"),
+    ("d2d4 d7d5", "Queen's Pawn Game"),
+    ("d2d4 d7d5 b1c3 g8f6 c1g5", "Richter-Veresov Attack"),
+    ("d2d4 d7d5 c1f4", "Queen's Pawn Game: London System"),
+    ("d2d4 d7d5 c2c4", "Queen's Gambit"),
+    (
+        "d2d4 d7d5 c2c4 b8c6",
+        "Queen's Gambit Declined: Chigorin Defence",
+    ),
+    ("d2d4 d7d5 c2c4 c7c6", "Slav Defence"),
+    (
+        "d2d4 d7d5 c2c4 c7c6 c4d5 c6d5",
+        "Slav Defence: Exchange Variation",
+    ),
+    (
+        "d2d4 d7d5 c2c4 c7c6 g1f3 g8f6 b1c3 d5c4",
+        "Slav Defence: Main Line",
+    ),
+    (
+        "d2d4 d7d5 c2c4 c7c6 g1f3 g8f6 b1c3 e7e6",
+        "Semi-Slav Defence",
+    ),
+    (
+        "d2d4 d7d5 c2c4 c7c6 g1f3 g8f6 b1c3 e7e6 e2e3 b8d7 f1d3 d5c4 d3c4 b7b5",
+        "Semi-Slav Defence: Meran Variation",
+    ),
+    ("d2d4 d7d5 c2c4 d5c4", "Queen's Gambit Accepted"),
+    (
+        "d2d4 d7d5 c2c4 d5c4 g1f3 g8f6 e2e3",
+        "Queen's Gambit Accepted: Main Line",
+    ),
+    (
+        "d2d4 d7d5 c2c4 e7e5",
+        "Queen's Gambit Declined: Albin Countergambit",
+    ),
+    ("d2d4 d7d5 c2c4 e7e6", "Queen's Gambit Declined"),
+    (
+        "d2d4 d7d5 c2c4 e7e6 b1c3 c7c5",
+        "Queen's Gambit Declined: Tarrasch Defence",
+    ),
+    ("d2d4 d7d5 c2c4 e7e6 b1c3 g8f6", "Queen's Gambit Declined"),
+    (
+        "d2d4 d7d5 c2c4 e7e6 b1c3 g8f6 c1g5 f8e7",
+        "Queen's Gambit Declined: Orthodox Defence",
+    ),
+    (
+        "d2d4 d7d5 c2c4 e7e6 b1c3 g8f6 c4d5",
+        "Queen's Gambit Declined: Exchange Variation",
+    ),
+    (
+        "d2d4 d7d5 c2c4 e7e6 b1c3 g8f6 g1f3 f8b4",
+        "Queen's Gambit Declined: Ragozin Defence",
+    ),
+    ("d2d4 d7d5 e2e4", "Blackmar-Diemer Gambit"),
+    (
+        "d2d4 d7d5 g1f3 g8f6 e2e3",
+        "Queen's Pawn Game: Colle System",
+    ),
+    ("d2d4 e7e5", "Englund Gambit"),
+    ("d2d4 e7e6 c2c4 b7b6", "English Defence"),
+    ("d2d4 f7f5", "Dutch Defence"),
+    (
+        "d2d4 f7f5 c2c4 g8f6 g2g3 e7e6 f1g2 d7d5",
+        "Dutch Defence: Stonewall Variation",
+    ),
+    (
+        "d2d4 f7f5 c2c4 g8f6 g2g3 g7g6",
+        "Dutch Defence: Leningrad Variation",
+    ),
+    ("d2d4 f7f5 e2e4", "Dutch Defence: Staunton Gambit"),
+    ("d2d4 g8f6", "Indian Defence"),
+    ("d2d4 g8f6 c1f4", "Indian Defence: London System"),
+    ("d2d4 g8f6 c1g5", "Trompowsky Attack"),
+    ("d2d4 g8f6 c2c4", "Indian Defence"),
+    ("d2d4 g8f6 c2c4 c7c5", "Benoni Defence"),
+    ("d2d4 g8f6 c2c4 c7c5 d4d5 b7b5", "Benko Gambit"),
+    (
+        "d2d4 g8f6 c2c4 c7c5 d4d5 e7e6",
+        "Benoni Defence: Modern Variation",
+    ),
+    ("d2d4 g8f6 c2c4 d7d6", "Old Indian Defence"),
+    ("d2d4 g8f6 c2c4 e7e5", "Budapest Gambit"),
+    ("d2d4 g8f6 c2c4 e7e6", "Indian Defence"),
+    ("d2d4 g8f6 c2c4 e7e6 b1c3 f8b4", "Nimzo-Indian Defence"),
+    (
+        "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4 a2a3",
+        "Nimzo-Indian Defence: Sämisch Variation",
+    ),
+    (
+        "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4 d1c2",
+        "Nimzo-Indian Defence: Classical Variation",
+    ),
+    (
+        "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4 e2e3",
+        "Nimzo-Indian Defence: Rubinstein Variation",
+    ),
+    ("d2d4 g8f6 c2c4 e7e6 g1f3 b7b6", "Queen's Indian Defence"),
+    ("d2d4 g8f6 c2c4 e7e6 g1f3 f8b4", "Bogo-Indian Defence"),
+    ("d2d4 g8f6 c2c4 e7e6 g2g3", "Catalan Opening"),
+    (
+        "d2d4 g8f6 c2c4 e7e6 g2g3 d7d5 f1g2 d5c4",
+        "Catalan Opening: Open Defence",
+    ),
+    (
+        "d2d4 g8f6 c2c4 e7e6 g2g3 d7d5 f1g2 f8e7",
+        "Catalan Opening: Closed",
+    ),
+    ("d2d4 g8f6 c2c4 g7g6", "Indian Defence: East Indian"),
+    ("d2d4 g8f6 c2c4 g7g6 b1c3 d7d5", "Grünfeld Defence"),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 d7d5 c4d5 f6d5 e2e4 d5c3 b2c3",
+        "Grünfeld Defence: Exchange Variation",
+    ),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 d7d5 g1f3 f8g7 d1b3",
+        "Grünfeld Defence: Russian Variation",
+    ),
+    ("d2d4 g8f6 c2c4 g7g6 b1c3 f8g7", "King's Indian Defence"),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6",
+        "King's Indian Defence: Normal Variation",
+    ),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6 f1e2 e8g8 c1g5",
+        "King's Indian Defence: Averbakh Variation",
+    ),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6 f2f3",
+        "King's Indian Defence: Sämisch Variation",
+    ),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6 f2f4",
+        "King's Indian Defence: Four Pawns Attack",
+    ),
+    (
+        "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7 e2e4 d7d6 g1f3 e8g8 f1e2 e7e5",
+        "King's Indian Defence: Classical Variation",
+    ),
+    ("e2e3", "Van 't Kruijs Opening"),
+    ("e2e4", "King's Pawn Game"),
+    ("e2e4 b7b6", "Owen's Defence"),
+    ("e2e4 b8c6", "Nimzowitsch Defence"),
+    ("e2e4 c7c5", "Sicilian Defence"),
+    ("e2e4 c7c5 b1c3", "Sicilian Defence: Closed"),
+    (
+        "e2e4 c7c5 b1c3 b8c6 f2f4",
+        "Sicilian Defence: Grand Prix Attack",
+    ),
+    ("e2e4 c7c5 b2b4", "Sicilian Defence: Wing Gambit"),
+    ("e2e4 c7c5 c2c3", "Sicilian Defence: Alapin Variation"),
+    (
+        "e2e4 c7c5 d2d4 c5d4 c2c3",
+        "Sicilian Defence: Smith-Morra Gambit",
+    ),
+    ("e2e4 c7c5 f1c4", "Sicilian Defence: Bowdler Attack"),
+    ("e2e4 c7c5 g1f3", "Sicilian Defence"),
+    ("e2e4 c7c5 g1f3 b8c6", "Sicilian Defence: Old Sicilian"),
+    (
+        "e2e4 c7c5 g1f3 b8c6 d2d4 c5d4 f3d4",
+        "Sicilian Defence: Open",
+    ),
+    (
+        "e2e4 c7c5 g1f3 b8c6 d2d4 c5d4 f3d4 g7g6",
+        "Sicilian Defence: Accelerated Dragon",
+    ),
+    (
+        "e2e4 c7c5 g1f3 b8c6 d2d4 c5d4 f3d4 g8f6 b1c3 e7e5",
+        "Sicilian Defence: Sveshnikov Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 b8c6 f1b5",
+        "Sicilian Defence: Rossolimo Variation",
+    ),
+    ("e2e4 c7c5 g1f3 d7d6", "Sicilian Defence: Modern Variations"),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4",
+        "Sicilian Defence: Open",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3",
+        "Sicilian Defence: Open",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6",
+        "Sicilian Defence: Najdorf Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6 c1e3",
+        "Sicilian Defence: Najdorf Variation, English Attack",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6 c1g5",
+        "Sicilian Defence: Najdorf Variation, Main Line",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6 f1e2",
+        "Sicilian Defence: Najdorf Variation, Opocensky Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 b8c6",
+        "Sicilian Defence: Classical Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 b8c6 c1g5",
+        "Sicilian Defence: Richter-Rauzer Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 e7e6",
+        "Sicilian Defence: Scheveningen Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 g7g6",
+        "Sicilian Defence: Dragon Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 g7g6 c1e3 f8g7 f2f3",
+        "Sicilian Defence: Dragon Variation, Yugoslav Attack",
+    ),
+    (
+        "e2e4 c7c5 g1f3 d7d6 f1b5",
+        "Sicilian Defence: Moscow Variation",
+    ),
+    ("e2e4 c7c5 g1f3 e7e6", "Sicilian Defence: French Variation"),
+    (
+        "e2e4 c7c5 g1f3 e7e6 d2d4 c5d4 f3d4 a7a6",
+        "Sicilian Defence: Kan Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 e7e6 d2d4 c5d4 f3d4 b8c6",
+        "Sicilian Defence: Taimanov Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 e7e6 d2d4 c5d4 f3d4 g8f6 b1c3 b8c6",
+        "Sicilian Defence: Four Knights Variation",
+    ),
+    (
+        "e2e4 c7c5 g1f3 g7g6",
+        "Sicilian Defence: Hyperaccelerated Dragon",
+    ),
+    ("e2e4 c7c6", "Caro-Kann Defence"),
+    (
+        "e2e4 c7c6 b1c3 d7d5 g1f3",
+        "Caro-Kann Defence: Two Knights Attack",
+    ),
+    ("e2e4 c7c6 d2d4 d7d5", "Caro-Kann Defence"),
+    (
+        "e2e4 c7c6 d2d4 d7d5 b1c3 d5e4 c3e4 b8d7",
+        "Caro-Kann Defence: Karpov Variation",
+    ),
+    (
+        "e2e4 c7c6 d2d4 d7d5 b1c3 d5e4 c3e4 c8f5",
+        "Caro-Kann Defence: Classical Variation",
+    ),
+    (
+        "e2e4 c7c6 d2d4 d7d5 e4d5 c6d5",
+        "Caro-Kann Defence: Exchange Variation",
+    ),
+    (
+        "e2e4 c7c6 d2d4 d7d5 e4d5 c6d5 c2c4",
+        "Caro-Kann Defence: Panov Attack",
+    ),
+    (
+        "e2e4 c7c6 d2d4 d7d5 e4e5",
+        "Caro-Kann Defence: Advance Variation",
+    ),
+    ("e2e4 d7d5", "Scandinavian Defence"),
+    (
+        "e2e4 d7d5 e4d5 d8d5 b1c3 d5a5",
+        "Scandinavian Defence: Main Line",
+    ),
+    (
+        "e2e4 d7d5 e4d5 g8f6",
+        "Scandinavian Defence: Modern Variation",
+    ),
+    ("e2e4 d7d6 d2d4 g8f6 b1c3 g7g6", "Pirc Defence"),
+    (
+        "e2e4 d7d6 d2d4 g8f6 b1c3 g7g6 f2f4",
+        "Pirc Defence: Austrian Attack",
+    ),
+    ("e2e4 e7e5", "King's Pawn Game"),
+    ("e2e4 e7e5 b1c3", "Vienna Game"),
+    ("e2e4 e7e5 b1c3 g8f6 f2f4", "Vienna Game: Vienna Gambit"),
+    ("e2e4 e7e5 d1h5", "King's Pawn Game: Wayward Queen Attack"),
+    ("e2e4 e7e5 d2d4 e5d4 c2c3", "Danish Gambit"),
+    ("e2e4 e7e5 d2d4 e5d4 d1d4", "Centre Game"),
+    ("e2e4 e7e5 f1c4", "Bishop's Opening"),
+    ("e2e4 e7e5 f2f4", "King's Gambit"),
+    (
+        "e2e4 e7e5 f2f4 d7d5",
+        "King's Gambit Declined: Falkbeer Countergambit",
+    ),
+    ("e2e4 e7e5 f2f4 e5f4", "King's Gambit Accepted"),
+    (
+        "e2e4 e7e5 f2f4 f8c5",
+        "King's Gambit Declined: Classical Variation",
+    ),
+    ("e2e4 e7e5 g1f3", "King's Knight Opening"),
+    (
+        "e2e4 e7e5 g1f3 b8c6",
+        "King's Knight Opening: Normal Variation",
+    ),
+    ("e2e4 e7e5 g1f3 b8c6 b1c3", "Three Knights Opening"),
+    ("e2e4 e7e5 g1f3 b8c6 b1c3 g8f6", "Four Knights Game"),
+    (
+        "e2e4 e7e5 g1f3 b8c6 b1c3 g8f6 d2d4",
+        "Four Knights Game: Scotch Variation",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 b1c3 g8f6 f1b5",
+        "Four Knights Game: Spanish Variation",
+    ),
+    ("e2e4 e7e5 g1f3 b8c6 c2c3", "Ponziani Opening"),
+    ("e2e4 e7e5 g1f3 b8c6 d2d4", "Scotch Game"),
+    ("e2e4 e7e5 g1f3 b8c6 d2d4 e5d4 f1c4", "Scotch Gambit"),
+    ("e2e4 e7e5 g1f3 b8c6 d2d4 e5d4 f3d4", "Scotch Game"),
+    ("e2e4 e7e5 g1f3 b8c6 f1b5", "Ruy Lopez"),
+    ("e2e4 e7e5 g1f3 b8c6 f1b5 a7a6", "Ruy Lopez: Morphy Defence"),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4",
+        "Ruy Lopez: Morphy Defence",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6",
+        "Ruy Lopez: Morphy Defence",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f6e4",
+        "Ruy Lopez: Open Variation",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7",
+        "Ruy Lopez: Closed",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7 f1e1 b7b5 a4b3 d7d6 c2c3 e8g8",
+        "Ruy Lopez: Closed",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6 e1g1 f8e7 f1e1 b7b5 a4b3 e8g8 c2c3 d7d5",
+        "Ruy Lopez: Marshall Attack",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5c6",
+        "Ruy Lopez: Exchange Variation",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 d7d6",
+        "Ruy Lopez: Steinitz Defence",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 f7f5",
+        "Ruy Lopez: Schliemann Defence",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 f8c5",
+        "Ruy Lopez: Classical Variation",
+    ),
+    ("e2e4 e7e5 g1f3 b8c6 f1b5 g8f6", "Ruy Lopez: Berlin Defence"),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 g8f6 e1g1 f6e4",
+        "Ruy Lopez: Berlin Defence, Rio Gambit Accepted",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1b5 g8f6 e1g1 f6e4 d2d4 e4d6 b5c6 d7c6 d4e5 d6f5 d1d8 e8d8",
+        "Ruy Lopez: Berlin Defence, Berlin Wall",
+    ),
+    ("e2e4 e7e5 g1f3 b8c6 f1c4", "Italian Game"),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5",
+        "Italian Game: Giuoco Piano",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 b2b4",
+        "Italian Game: Evans Gambit",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 c2c3",
+        "Italian Game: Classical Variation",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8c5 d2d3",
+        "Italian Game: Giuoco Pianissimo",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 f8e7",
+        "Italian Game: Hungarian Defence",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6",
+        "Italian Game: Two Knights Defence",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 d2d3",
+        "Italian Game: Two Knights Defence, Modern Bishop's Opening",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 f3g5 d7d5 e4d5 f6d5 g5f7",
+        "Italian Game: Two Knights Defence, Fried Liver Attack",
+    ),
+    (
+        "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 f3g5 f8c5",
+        "Italian Game: Two Knights Defence, Traxler Counterattack",
+    ),
+    ("e2e4 e7e5 g1f3 d7d5", "Elephant Gambit"),
+    ("e2e4 e7e5 g1f3 d7d6", "Philidor Defence"),
+    ("e2e4 e7e5 g1f3 f7f5", "Latvian Gambit"),
+    ("e2e4 e7e5 g1f3 g8f6", "Petrov's Defence"),
+    (
+        "e2e4 e7e5 g1f3 g8f6 f3e5 d7d6 e5f3 f6e4 d2d4",
+        "Petrov's Defence: Classical Attack",
+    ),
+    ("e2e4 e7e6", "French Defence"),
+    ("e2e4 e7e6 d2d3", "French Defence: King's Indian Attack"),
+    ("e2e4 e7e6 d2d4 d7d5", "French Defence: Normal Variation"),
+    (
+        "e2e4 e7e6 d2d4 d7d5 b1c3",
+        "French Defence: Paulsen Variation",
+    ),
+    (
+        "e2e4 e7e6 d2d4 d7d5 b1c3 d5e4",
+        "French Defence: Rubinstein Variation",
+    ),
+    (
+        "e2e4 e7e6 d2d4 d7d5 b1c3 f8b4",
+        "French Defence: Winawer Variation",
+    ),
+    (
+        "e2e4 e7e6 d2d4 d7d5 b1c3 g8f6",
+        "French Defence: Classical Variation",
+    ),
+    (
+        "e2e4 e7e6 d2d4 d7d5 b1d2",
+        "French Defence: Tarrasch Variation",
+    ),
+    (
+        "e2e4 e7e6 d2d4 d7d5 e4d5",
+        "French Defence: Exchange Variation",
+    ),
+    (
+        "e2e4 e7e6 d2d4 d7d5 e4e5",
+        "French Defence: Advance Variation",
+    ),
+    ("e2e4 g7g6", "Modern Defence"),
+    ("e2e4 g8f6", "Alekhine's Defence"),
+    (
+        "e2e4 g8f6 e4e5 f6d5 d2d4 d7d6 c2c4 d5b6 f2f4",
+        "Alekhine's Defence: Four Pawns Attack",
+    ),
+    (
+        "e2e4 g8f6 e4e5 f6d5 d2d4 d7d6 g1f3",
+        "Alekhine's Defence: Modern Variation",
+    ),
+    ("f2f4", "Bird's Opening"),
+    ("f2f4 e7e5", "Bird's Opening: From's Gambit"),
+    ("g1f3", "Zukertort Opening"),
+    ("g1f3 d7d5 c2c4", "Réti Opening"),
+    ("g1f3 d7d5 g2g3", "King's Indian Attack"),
+    ("g1f3 g8f6 c2c4", "Zukertort Opening: Anglo-Indian"),
+    ("g1h3", "Amar Opening"),
+    ("g2g3", "Hungarian Opening"),
+    ("g2g4", "Grob Opening"),
+];
+
+/// Name of the opening played so far, given the moves in coordinate notation (e.g. "e2e4").
+/// The longest book line that the game starts with is used, and `None` is returned once the
+/// game has left the book.
+pub fn opening_name(moves: &[String]) -> Option<&'static str> {
+    if moves.is_empty() {
+        return None;
+    }
+
+    // the game is still in book if some line starts with every move played so far
+    let played = moves.join(" ");
+    let next = OPENINGS.partition_point(|(line, _)| *line < played.as_str());
+    let in_book = OPENINGS.get(next).is_some_and(|(line, _)| {
+        line.strip_prefix(played.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    });
+    if !in_book {
+        return None;
+    }
+
+    // name the longest prefix of the game that the book knows about
+    (1..=moves.len()).rev().find_map(|n| {
+        let key = moves[..n].join(" ");
+        OPENINGS
+            .binary_search_by(|(line, _)| line.cmp(&key.as_str()))
+            .ok()
+            .map(|i| OPENINGS[i].1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(moves: &str) -> Option<&'static str> {
+        let moves: Vec<String> = moves.split_whitespace().map(str::to_string).collect();
+        opening_name(&moves)
+    }
+
+    #[test]
+    fn the_book_is_sorted_without_duplicates() {
+        // strictly increasing, which the binary searches rely on
+        for pair in OPENINGS.windows(2) {
+            assert!(
+                pair[0].0 < pair[1].0,
+                "{:?} before {:?}",
+                pair[0].0,
+                pair[1].0
+            );
+        }
+        for (line, _) in OPENINGS {
+            assert!(
+                line.split(' ').all(|mv| mv.len() == 4),
+                "{:?} isn't a line of moves",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn the_longest_known_line_names_the_game() {
+        assert_eq!(name("e2e4 c7c5"), Some("Sicilian Defence"));
+        assert_eq!(
+            name("e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6"),
+            Some("Sicilian Defence: Najdorf Variation")
+        );
+        assert_eq!(
+            name("e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6 c1g5"),
+            Some("Sicilian Defence: Najdorf Variation, Main Line")
+        );
+        // on the way to a longer line, the game keeps the name of the last one it passed
+        assert_eq!(
+            name("e2e4 c7c5 g1f3 d7d6 d2d4"),
+            Some("Sicilian Defence: Modern Variations")
+        );
+    }
+
+    #[test]
+    fn out_of_book_has_no_name() {
+        assert_eq!(name(""), None);
+        assert_eq!(name("h2h4"), None);
+        // a Najdorf going on with a move the book doesn't have isn't named after it any longer
+        assert_eq!(
+            name("e2e4 c7c5 g1f3 d7d6 d2d4 c5d4 f3d4 g8f6 b1c3 a7a6 h2h3"),
+            None
+        );
+    }
+
+    #[test]
+    fn transpositions_are_not_recognised() {
+        // the Réti's position, reached with the moves in another order
+        assert_eq!(name("g1f3 d7d5 c2c4"), Some("Réti Opening"));
+        assert_eq!(name("c2c4 d7d5 g1f3"), None);
+    }
+}
@@ -0,0 +1,227 @@
+/**
+ * The pause menu, drawn over the game when Escape is pressed. The game keeps running underneath,
+ * so moves from the opponent and the clock still come in, but clicks only reach the menu. Its
+ * View submenu changes how the game is laid out, which is kept for the next game.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::keys::Action;
+use ggez::{event, graphics, Context, GameResult};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PauseItem {
+    Resume,
+    Settings,
+    View,
+    OfferDraw,
+    Resign,
+    Quit,
+    MoveList,
+    FlipBoard,
+    Back,
+}
+
+/// Items in the order they're shown.
+const ITEMS: [PauseItem; 6] = [
+    PauseItem::Resume,
+    PauseItem::Settings,
+    PauseItem::View,
+    PauseItem::OfferDraw,
+    PauseItem::Resign,
+    PauseItem::Quit,
+];
+
+/// The same for the View submenu.
+const VIEW_ITEMS: [PauseItem; 3] = [PauseItem::MoveList, PauseItem::FlipBoard, PauseItem::Back];
+
+/// Size of a button, and where the first one is drawn. The rest follow below it.
+const BUTTON_SIZE: (f32, f32) = (320.0, 50.0);
+const BUTTON_TOP: f32 = 200.0;
+const BUTTON_SPACING: f32 = 70.0;
+
+/// What the items say depends on.
+#[derive(Clone, Copy, Default)]
+pub struct Labels {
+    pub draw_offered: bool, // the opponent has offered a draw, so offering one back accepts it
+    pub move_list: bool,    // the moves played are listed beside the board
+}
+
+impl PauseItem {
+    fn label(self, labels: Labels) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Settings => "Settings",
+            PauseItem::View => "View",
+            PauseItem::OfferDraw if labels.draw_offered => "Accept Draw",
+            PauseItem::OfferDraw => "Offer Draw",
+            PauseItem::Resign => "Resign",
+            PauseItem::Quit => "Quit to Menu",
+            PauseItem::MoveList if labels.move_list => "Hide Move List",
+            PauseItem::MoveList => "Show Move List",
+            PauseItem::FlipBoard => "Flip Board",
+            PauseItem::Back => "Back",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PauseMenu {
+    selected: usize,           // highlighted item, moved with the arrow keys or the mouse
+    chosen: Option<PauseItem>, // item picked, until the game acts on it
+    view: bool,                // the View submenu is open
+}
+
+impl PauseMenu {
+    pub fn new() -> PauseMenu {
+        PauseMenu {
+            selected: 0,
+            chosen: None,
+            view: false,
+        }
+    }
+
+    /// The item picked since the last call, if any. Opening and leaving the View submenu is
+    /// done by the menu itself, so those never are.
+    pub fn take_choice(&mut self) -> Option<PauseItem> {
+        self.chosen.take()
+    }
+
+    /// The items shown now.
+    fn items(&self) -> &'static [PauseItem] {
+        if self.view {
+            &VIEW_ITEMS
+        } else {
+            &ITEMS
+        }
+    }
+
+    fn choose(&mut self, item: usize) {
+        match self.items()[item] {
+            PauseItem::View => {
+                self.view = true;
+                self.selected = 0;
+            }
+            PauseItem::Back => self.leave_view(),
+            chosen => {
+                self.selected = item;
+                self.chosen = Some(chosen);
+            }
+        }
+    }
+
+    /// Goes back from the View submenu to the menu, with View highlighted.
+    fn leave_view(&mut self) {
+        self.view = false;
+        self.selected = ITEMS
+            .iter()
+            .position(|&item| item == PauseItem::View)
+            .unwrap_or(0);
+    }
+
+    /// Draws the menu over whatever is on screen.
+    pub fn draw(&self, ctx: &mut Context, labels: Labels) -> GameResult {
+        // darken the game underneath
+        let shade = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1),
+            [0.0, 0.0, 0.0, 0.6].into(),
+        )?;
+        graphics::draw(ctx, &shade, graphics::DrawParam::default())?;
+
+        let title = graphics::Text::new(
+            graphics::TextFragment::from(if self.view { "View" } else { "Paused" })
+                .scale(graphics::PxScale { x: 50.0, y: 50.0 }),
+        );
+        let title_width = title.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::DrawParam::default()
+                .color([1.0, 1.0, 1.0, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - title_width) / 2.0, 110.0]),
+        )?;
+
+        for (i, item) in self.items().iter().enumerate() {
+            let rect = button_rect(i);
+            let colour = if i == self.selected {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let background = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(item.label(labels))
+                    .scale(graphics::PxScale { x: 26.0, y: 26.0 }),
+            );
+            let label_size = label.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([
+                        rect.x + (rect.w - label_size.w) / 2.0,
+                        rect.y + (rect.h - label_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        if let Some(item) = item_at(self.items().len(), x, y) {
+            self.selected = item;
+        }
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        if button != event::MouseButton::Left {
+            return;
+        }
+        if let Some(item) = item_at(self.items().len(), x, y) {
+            self.choose(item);
+        }
+    }
+
+    /// `action` is the shortcut the key is bound to, if any.
+    pub fn key_down_event(&mut self, key: event::KeyCode, action: Option<Action>) {
+        let count = self.items().len();
+        match key {
+            // a second press of the pause key closes the menu again, or goes back from the View
+            // submenu
+            _ if action == Some(Action::Pause) && self.view => self.leave_view(),
+            _ if action == Some(Action::Pause) => self.chosen = Some(PauseItem::Resume),
+            event::KeyCode::Up => self.selected = (self.selected + count - 1) % count,
+            event::KeyCode::Down | event::KeyCode::Tab => {
+                self.selected = (self.selected + 1) % count
+            }
+            event::KeyCode::Return | event::KeyCode::NumpadEnter | event::KeyCode::Space => {
+                self.choose(self.selected)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Where a button is on the screen.
+fn button_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - BUTTON_SIZE.0) / 2.0,
+        BUTTON_TOP + i as f32 * BUTTON_SPACING,
+        BUTTON_SIZE.0,
+        BUTTON_SIZE.1,
+    )
+}
+
+/// Index of the button at a point on the screen, if there is one, of `count` shown.
+fn item_at(count: usize, x: f32, y: f32) -> Option<usize> {
+    (0..count).find(|&i| button_rect(i).contains([x, y]))
+}
@@ -0,0 +1,904 @@
+/**
+ * Messages exchanged through the server, and how they're framed on the socket.
+ *
+ * The server relays every message to every client in the same session, including the one that
+ * sent it, so clients see their own messages too. Apart from the lobby announcement, every
+ * message starts with the room name so clients can ignore other rooms.
+ *
+ * To know their own when they come back, clients tag what they send during a game with a random
+ * number of their own as the last field, e.g. `myroom draw white ~1a2b3c4d`. Older clients don't,
+ * and a message that only just fits in the frame goes without, so each kind of message still
+ * guards against its echo by the colour or counter in it too.
+ */
+use chess_template::{Colour, Position};
+use std::fmt;
+use std::str::FromStr;
+
+/* max message size in characters. */
+pub const MSG_SIZE: usize = 64;
+
+/// What starts the tag naming the client that sent a message, and how many bytes the tag adds,
+/// with the space before it.
+const INSTANCE_MARK: char = '~';
+pub const INSTANCE_TAG_SIZE: usize = 10;
+
+/// Longest room name, in bytes. Every message of a fixed size still fits in the frame with its
+/// tag then, up to move 9999, and a chat message has room for a few words.
+pub const MAX_ROOM_LENGTH: usize = 20;
+
+/// A message as sent over the wire.
+#[derive(Clone, PartialEq)]
+pub enum Message {
+    /// Announces a player in a room's lobby, with a random number to tell the players apart, and
+    /// the avatar they picked, if they did. Without one it's sent the way older clients send it,
+    /// as they can't read it with one.
+    Join {
+        room: String,
+        id: u8,
+        avatar: Option<u8>,
+    },
+    /// A move, along with the move counter after it was played.
+    Move {
+        room: String,
+        counter: u32,
+        from: Position,
+        to: Position,
+    },
+    /// Restarts the game.
+    Reset { room: String },
+    /// Sent by the computer opponent each time it starts searching a depth.
+    Thinking { room: String, depth: u32 },
+    /// Asks the computer opponent to play at another difficulty.
+    Difficulty { room: String, level: u8 },
+    /// Offers a draw. Once both colours have offered, the game is drawn.
+    DrawOffer { room: String, colour: Colour },
+    /// The player of this colour gives up.
+    Resign { room: String, colour: Colour },
+    /// The player of this colour allows study aids like the threat view. Once both colours have,
+    /// they're on for both. Older clients don't know this message, so they never agree.
+    AllowAssists { room: String, colour: Colour },
+    /// The player of this colour will play again once the game is over. Once both colours have
+    /// agreed, the next game starts after a countdown, with colours swapped.
+    RematchOk { room: String, colour: Colour },
+    /// The player of this colour stopped the automatic rematch, for both players.
+    RematchCancel { room: String, colour: Colour },
+    /// How the game ended, sent by whichever client sees it end first. The other checks it
+    /// against its own game, so both record the same result.
+    Result { room: String, score: Score },
+    /// The server refused the move that took the counter to `counter`, e.g. as illegal. The
+    /// reason is for showing to the player, and may be several words.
+    Reject {
+        room: String,
+        counter: u32,
+        reason: String,
+    },
+    /// Sent on coming back to a correspondence game: how many moves we have, as the move counter
+    /// after the last, and a hash of them. The side that's behind asks for the rest.
+    SyncOffer {
+        room: String,
+        counter: u32,
+        hash: u64,
+    },
+    /// Asks for the moves after the one that took the counter to `counter`, which the other side
+    /// sends again.
+    SyncRequest { room: String, counter: u32 },
+    /// Takes the game back to how it was when the move counter was `counter`, against the
+    /// computer or across the desk. The computer opponent sends it back once it has.
+    Takeback { room: String, counter: u32 },
+    /// Someone watching the game, sent again every few seconds while they do. The token tells
+    /// spectators apart, and the name, which may be several words or none, is for showing.
+    Spectate {
+        room: String,
+        token: String,
+        name: String,
+    },
+    /// A chat message from the player of `colour`, which may be several words, as many as fit
+    /// in the frame.
+    Chat {
+        room: String,
+        colour: Colour,
+        text: String,
+    },
+}
+
+/// A finished game's result, as PGN writes it: "1-0", "0-1" or "1/2-1/2".
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Score {
+    WhiteWins,
+    BlackWins,
+    Drawn,
+}
+
+impl Score {
+    pub fn code(self) -> &'static str {
+        match self {
+            Score::WhiteWins => "1-0",
+            Score::BlackWins => "0-1",
+            Score::Drawn => "1/2-1/2",
+        }
+    }
+
+    /// The inverse of `code`.
+    pub fn parse(field: &str) -> Result<Score, DecodeError> {
+        match field {
+            "1-0" => Ok(Score::WhiteWins),
+            "0-1" => Ok(Score::BlackWins),
+            "1/2-1/2" => Ok(Score::Drawn),
+            _ => Err(DecodeError::BadScore(field.to_string())),
+        }
+    }
+}
+
+/// Why a message couldn't be understood.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// Nothing but whitespace and padding.
+    Empty,
+    /// Not a kind of message we know.
+    UnknownKind(String),
+    /// A kind we know, with too few or too many fields, e.g. a move cut off halfway.
+    WrongLength(String),
+    /// A field that should be a number isn't one, or is too large.
+    BadNumber(String),
+    /// A square off the board, like row 8.
+    OffBoard(usize, usize),
+    /// A field that should be "white" or "black" isn't.
+    BadColour(String),
+    /// A field that should be a result like "1-0" isn't.
+    BadScore(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "empty message"),
+            DecodeError::UnknownKind(kind) => write!(f, "unknown message kind {:?}", kind),
+            DecodeError::WrongLength(kind) => {
+                write!(f, "wrong number of fields for a {:?} message", kind)
+            }
+            DecodeError::BadNumber(field) => write!(f, "{:?} isn't a valid number", field),
+            DecodeError::OffBoard(row, col) => {
+                write!(f, "row {} column {} is off the board", row, col)
+            }
+            DecodeError::BadColour(field) => write!(f, "{:?} isn't a colour", field),
+            DecodeError::BadScore(field) => write!(f, "{:?} isn't a result", field),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Message {
+    /// Parses a message, or says why it isn't one we understand.
+    pub fn parse(text: &str) -> Result<Message, DecodeError> {
+        // older clients wrapped messages in quotes, and frames are padded with zeros
+        let text = text.trim_matches(|c: char| c == '"' || c == '\0' || c.is_whitespace());
+        let (_, text) = untag(text);
+        let parts: Vec<&str> = text.split_whitespace().collect();
+
+        let message = match parts.as_slice() {
+            [] => return Err(DecodeError::Empty),
+            ["room", room, id] => Message::Join {
+                room: room.to_string(),
+                id: parse_number(id)?,
+                avatar: None,
+            },
+            ["room", room, id, avatar] => Message::Join {
+                room: room.to_string(),
+                id: parse_number(id)?,
+                avatar: Some(parse_number(avatar)?),
+            },
+            [room, "mv", counter, from_row, from_col, to_row, to_col] => Message::Move {
+                room: room.to_string(),
+                counter: parse_number(counter)?,
+                from: parse_position(from_row, from_col)?,
+                to: parse_position(to_row, to_col)?,
+            },
+            [room, "reset"] => Message::Reset {
+                room: room.to_string(),
+            },
+            [room, "thinking", depth] => Message::Thinking {
+                room: room.to_string(),
+                depth: parse_number(depth)?,
+            },
+            [room, "difficulty", level] => Message::Difficulty {
+                room: room.to_string(),
+                level: parse_number(level)?,
+            },
+            [room, "draw", colour] => Message::DrawOffer {
+                room: room.to_string(),
+                colour: parse_colour(colour)?,
+            },
+            [room, "resign", colour] => Message::Resign {
+                room: room.to_string(),
+                colour: parse_colour(colour)?,
+            },
+            [room, "assist", colour] => Message::AllowAssists {
+                room: room.to_string(),
+                colour: parse_colour(colour)?,
+            },
+            [room, "rematch_auto_ok", colour] => Message::RematchOk {
+                room: room.to_string(),
+                colour: parse_colour(colour)?,
+            },
+            [room, "rematch_auto_cancel", colour] => Message::RematchCancel {
+                room: room.to_string(),
+                colour: parse_colour(colour)?,
+            },
+            [room, "result", score] => Message::Result {
+                room: room.to_string(),
+                score: Score::parse(score)?,
+            },
+            [room, "reject", counter, reason @ ..] => Message::Reject {
+                room: room.to_string(),
+                counter: parse_number(counter)?,
+                reason: reason.join(" "),
+            },
+            [room, "sync_offer", counter, hash] => Message::SyncOffer {
+                room: room.to_string(),
+                counter: parse_number(counter)?,
+                hash: u64::from_str_radix(hash, 16)
+                    .map_err(|_| DecodeError::BadNumber(hash.to_string()))?,
+            },
+            [room, "sync_request", counter] => Message::SyncRequest {
+                room: room.to_string(),
+                counter: parse_number(counter)?,
+            },
+            [room, "takeback", counter] => Message::Takeback {
+                room: room.to_string(),
+                counter: parse_number(counter)?,
+            },
+            [room, "spectate", token, name @ ..] => Message::Spectate {
+                room: room.to_string(),
+                token: token.to_string(),
+                name: name.join(" "),
+            },
+            [room, "chat", colour, text @ ..] => Message::Chat {
+                room: room.to_string(),
+                colour: parse_colour(colour)?,
+                text: text.join(" "),
+            },
+            ["room", ..] => return Err(DecodeError::WrongLength("room".to_string())),
+            [_, kind @ ("mv"
+            | "reset"
+            | "thinking"
+            | "difficulty"
+            | "draw"
+            | "resign"
+            | "assist"
+            | "rematch_auto_ok"
+            | "rematch_auto_cancel"
+            | "result"
+            | "reject"
+            | "sync_offer"
+            | "sync_request"
+            | "takeback"
+            | "spectate"
+            | "chat"), ..] => return Err(DecodeError::WrongLength(kind.to_string())),
+            [_, kind, ..] | [kind] => return Err(DecodeError::UnknownKind(kind.to_string())),
+        };
+
+        Ok(message)
+    }
+
+    /// The room the message is meant for.
+    pub fn room(&self) -> &str {
+        match self {
+            Message::Join { room, .. }
+            | Message::Move { room, .. }
+            | Message::Reset { room }
+            | Message::Thinking { room, .. }
+            | Message::Difficulty { room, .. }
+            | Message::DrawOffer { room, .. }
+            | Message::Resign { room, .. }
+            | Message::AllowAssists { room, .. }
+            | Message::RematchOk { room, .. }
+            | Message::RematchCancel { room, .. }
+            | Message::Result { room, .. }
+            | Message::Reject { room, .. }
+            | Message::SyncOffer { room, .. }
+            | Message::SyncRequest { room, .. }
+            | Message::Takeback { room, .. }
+            | Message::Spectate { room, .. }
+            | Message::Chat { room, .. } => room,
+        }
+    }
+}
+
+/// Formats the message the way it's sent over the wire, e.g. "myroom mv 2 6 4 4 4 ".
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Join {
+                room,
+                id,
+                avatar: None,
+            } => write!(f, "room {} {} ", room, id),
+            Message::Join {
+                room,
+                id,
+                avatar: Some(avatar),
+            } => write!(f, "room {} {} {} ", room, id, avatar),
+            Message::Move {
+                room,
+                counter,
+                from,
+                to,
+            } => write!(
+                f,
+                "{} mv {} {} {} {} {} ",
+                room, counter, from.row, from.col, to.row, to.col
+            ),
+            Message::Reset { room } => write!(f, "{} reset ", room),
+            Message::Thinking { room, depth } => write!(f, "{} thinking {} ", room, depth),
+            Message::Difficulty { room, level } => write!(f, "{} difficulty {} ", room, level),
+            Message::DrawOffer { room, colour } => {
+                write!(f, "{} draw {} ", room, colour_name(*colour))
+            }
+            Message::Resign { room, colour } => {
+                write!(f, "{} resign {} ", room, colour_name(*colour))
+            }
+            Message::AllowAssists { room, colour } => {
+                write!(f, "{} assist {} ", room, colour_name(*colour))
+            }
+            Message::RematchOk { room, colour } => {
+                write!(f, "{} rematch_auto_ok {} ", room, colour_name(*colour))
+            }
+            Message::RematchCancel { room, colour } => {
+                write!(f, "{} rematch_auto_cancel {} ", room, colour_name(*colour))
+            }
+            Message::Result { room, score } => write!(f, "{} result {} ", room, score.code()),
+            Message::Reject {
+                room,
+                counter,
+                reason,
+            } => write!(f, "{} reject {} {} ", room, counter, reason),
+            Message::SyncOffer {
+                room,
+                counter,
+                hash,
+            } => write!(f, "{} sync_offer {} {:016x} ", room, counter, hash),
+            Message::SyncRequest { room, counter } => {
+                write!(f, "{} sync_request {} ", room, counter)
+            }
+            Message::Takeback { room, counter } => write!(f, "{} takeback {} ", room, counter),
+            Message::Spectate { room, token, name } => {
+                write!(f, "{} spectate {} {} ", room, token, name)
+            }
+            Message::Chat { room, colour, text } => {
+                write!(f, "{} chat {} {} ", room, colour_name(*colour), text)
+            }
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(field: &str) -> Result<T, DecodeError> {
+    field
+        .parse()
+        .map_err(|_| DecodeError::BadNumber(field.to_string()))
+}
+
+/// Parses a row and column into a position on the board.
+fn parse_position(row: &str, col: &str) -> Result<Position, DecodeError> {
+    let (row, col) = (parse_number(row)?, parse_number(col)?);
+    Position::new(row, col).map_err(|_| DecodeError::OffBoard(row, col))
+}
+
+pub fn parse_colour(field: &str) -> Result<Colour, DecodeError> {
+    match field {
+        "white" => Ok(Colour::White),
+        "black" => Ok(Colour::Black),
+        _ => Err(DecodeError::BadColour(field.to_string())),
+    }
+}
+
+pub fn colour_name(colour: Colour) -> &'static str {
+    match colour {
+        Colour::White => "white",
+        Colour::Black => "black",
+    }
+}
+
+/// Whether `room` can be played in: not empty, without spaces, and no longer than
+/// `MAX_ROOM_LENGTH`.
+pub fn check_room(room: &str) -> Result<(), String> {
+    if room.is_empty() || room.contains(char::is_whitespace) {
+        return Err(format!(
+            "{:?} isn't a room name, those have no spaces",
+            room
+        ));
+    }
+    if room.len() > MAX_ROOM_LENGTH {
+        return Err(format!(
+            "the room name {:?} is too long, it can have at most {} characters",
+            room, MAX_ROOM_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// `msg` tagged with the client `instance` sending it, if the tag fits in the frame.
+pub fn tag(msg: &str, instance: u32) -> String {
+    let tagged = format!("{} {}{:08x} ", msg.trim_end(), INSTANCE_MARK, instance);
+    if tagged.len() <= MSG_SIZE {
+        tagged
+    } else {
+        msg.to_string()
+    }
+}
+
+/// The instance of the client that sent a message, if it's tagged, and the message without the
+/// tag.
+pub fn untag(text: &str) -> (Option<u32>, &str) {
+    let trimmed = text.trim_end_matches(|c: char| c == '\0' || c.is_whitespace());
+    let tagged = trimmed.rsplit_once(' ').and_then(|(rest, last)| {
+        let hex = last.strip_prefix(INSTANCE_MARK)?;
+        let instance = u32::from_str_radix(hex, 16).ok()?;
+        (hex.len() == 8).then_some((instance, rest))
+    });
+    match tagged {
+        Some((instance, rest)) => (Some(instance), rest),
+        None => (None, text),
+    }
+}
+
+/// Pads a message with zeros to `MSG_SIZE` bytes, ready to be written to the socket.
+/// Longer messages are cut off.
+pub fn encode(msg: &str) -> Vec<u8> {
+    let mut msg_buffer = msg.as_bytes().to_vec();
+    // add zero character to mark end of message
+    msg_buffer.resize(MSG_SIZE, 0);
+    msg_buffer
+}
+
+/// Reads a message from a buffer read from the socket, up to the first zero.
+/// Returns `None` if it isn't valid UTF-8.
+pub fn decode(msg_buffer: &[u8]) -> Option<String> {
+    let msg = msg_buffer
+        .iter()
+        .copied()
+        .take_while(|&x| x != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8(msg).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn pos(row: usize, col: usize) -> Position {
+        Position::new(row, col).unwrap()
+    }
+
+    #[test]
+    fn parses_every_message() {
+        assert!(
+            Message::parse("room lobby 42 ")
+                == Ok(Message::Join {
+                    room: "lobby".to_string(),
+                    id: 42,
+                    avatar: None
+                })
+        );
+        assert!(
+            Message::parse("room lobby 42 3 ")
+                == Ok(Message::Join {
+                    room: "lobby".to_string(),
+                    id: 42,
+                    avatar: Some(3)
+                })
+        );
+        assert!(
+            Message::parse("lobby mv 2 6 4 4 4 ")
+                == Ok(Message::Move {
+                    room: "lobby".to_string(),
+                    counter: 2,
+                    from: pos(6, 4),
+                    to: pos(4, 4)
+                })
+        );
+        assert!(
+            Message::parse("lobby reset ")
+                == Ok(Message::Reset {
+                    room: "lobby".to_string()
+                })
+        );
+        assert!(
+            Message::parse("bot thinking 3 ")
+                == Ok(Message::Thinking {
+                    room: "bot".to_string(),
+                    depth: 3
+                })
+        );
+        assert!(
+            Message::parse("bot difficulty 5 ")
+                == Ok(Message::Difficulty {
+                    room: "bot".to_string(),
+                    level: 5
+                })
+        );
+        assert!(
+            Message::parse("lobby draw white ")
+                == Ok(Message::DrawOffer {
+                    room: "lobby".to_string(),
+                    colour: Colour::White
+                })
+        );
+        assert!(
+            Message::parse("lobby resign black ")
+                == Ok(Message::Resign {
+                    room: "lobby".to_string(),
+                    colour: Colour::Black
+                })
+        );
+        assert!(
+            Message::parse("lobby assist white ")
+                == Ok(Message::AllowAssists {
+                    room: "lobby".to_string(),
+                    colour: Colour::White
+                })
+        );
+        assert!(
+            Message::parse("lobby rematch_auto_ok white ")
+                == Ok(Message::RematchOk {
+                    room: "lobby".to_string(),
+                    colour: Colour::White
+                })
+        );
+        assert!(
+            Message::parse("lobby rematch_auto_cancel black ")
+                == Ok(Message::RematchCancel {
+                    room: "lobby".to_string(),
+                    colour: Colour::Black
+                })
+        );
+        assert!(
+            Message::parse("lobby result 1/2-1/2 ")
+                == Ok(Message::Result {
+                    room: "lobby".to_string(),
+                    score: Score::Drawn
+                })
+        );
+        assert!(
+            Message::parse("lobby reject 7 illegal move ")
+                == Ok(Message::Reject {
+                    room: "lobby".to_string(),
+                    counter: 7,
+                    reason: "illegal move".to_string()
+                })
+        );
+    }
+
+    #[test]
+    fn formatting_round_trips() {
+        let messages = [
+            "room lobby 42 ",
+            "room lobby 42 200 ",
+            "lobby mv 2 6 4 4 4 ",
+            "lobby reset ",
+            "bot thinking 3 ",
+            "bot difficulty 5 ",
+            "lobby draw white ",
+            "lobby resign black ",
+            "lobby assist white ",
+            "lobby rematch_auto_ok white ",
+            "lobby rematch_auto_cancel black ",
+            "lobby result 1-0 ",
+            "lobby result 0-1 ",
+            "lobby reject 12 not your turn ",
+            "lobby sync_offer 3 00c0ffee12345678 ",
+            "lobby sync_request 2 ",
+            "bot takeback 5 ",
+            "lobby spectate 5f3a Klubbens skärm ",
+            "lobby chat black bra drag! ",
+        ];
+        for text in messages {
+            assert_eq!(Message::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        assert_eq!(Message::parse("").err(), Some(DecodeError::Empty));
+        assert_eq!(
+            Message::parse("lobby castle").err(),
+            Some(DecodeError::UnknownKind("castle".to_string()))
+        );
+        assert_eq!(
+            Message::parse("lobby").err(),
+            Some(DecodeError::UnknownKind("lobby".to_string()))
+        );
+        assert_eq!(
+            Message::parse("lobby mv two 6 4 4 4").err(),
+            Some(DecodeError::BadNumber("two".to_string()))
+        );
+        assert_eq!(
+            Message::parse("room lobby 256").err(),
+            Some(DecodeError::BadNumber("256".to_string()))
+        );
+        assert_eq!(
+            Message::parse("lobby mv -1 6 4 4 4").err(),
+            Some(DecodeError::BadNumber("-1".to_string()))
+        );
+        assert_eq!(
+            Message::parse("lobby resign red").err(),
+            Some(DecodeError::BadColour("red".to_string()))
+        );
+        assert_eq!(
+            Message::parse("lobby result 2-0").err(),
+            Some(DecodeError::BadScore("2-0".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        for text in [
+            "lobby mv 2 6 4 4",
+            "lobby mv",
+            "lobby thinking",
+            "lobby rematch_auto_ok",
+            "lobby result",
+            "lobby reject",
+            "lobby spectate",
+            "lobby chat",
+            "lobby sync_offer 3",
+            "bot takeback",
+            "room lobby",
+        ] {
+            assert!(matches!(
+                Message::parse(text),
+                Err(DecodeError::WrongLength(_))
+            ));
+        }
+        // a frame cut off partway through a move
+        let encoded = encode("lobby mv 12 6 4 4 4 ");
+        let truncated = decode(&encoded[..14]).unwrap();
+        assert_eq!(
+            Message::parse(&truncated).err(),
+            Some(DecodeError::WrongLength("mv".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_squares_off_the_board() {
+        assert_eq!(
+            Message::parse("lobby mv 2 8 4 4 4").err(),
+            Some(DecodeError::OffBoard(8, 4))
+        );
+        assert_eq!(
+            Message::parse("lobby mv 2 6 4 4 8").err(),
+            Some(DecodeError::OffBoard(4, 8))
+        );
+    }
+
+    #[test]
+    fn accepts_corner_squares() {
+        for (from, to) in [((0, 0), (7, 7)), ((7, 0), (0, 7))] {
+            let msg = Message::Move {
+                room: "lobby".to_string(),
+                counter: 1,
+                from: pos(from.0, from.1),
+                to: pos(to.0, to.1),
+            };
+            assert!(Message::parse(&msg.to_string()) == Ok(msg));
+        }
+    }
+
+    #[test]
+    fn accepts_legacy_quoting_and_padding() {
+        let reset = Ok(Message::Reset {
+            room: "lobby".to_string(),
+        });
+        // older clients passed messages on wrapped in quotes
+        assert!(Message::parse("\"lobby reset \"") == reset);
+        // padding left over when a frame isn't cut at the first zero
+        assert!(Message::parse("lobby reset \0\0\0") == reset);
+        assert!(Message::parse("  lobby   reset") == reset);
+    }
+
+    #[test]
+    fn room_is_taken_from_every_message() {
+        assert_eq!(Message::parse("room a 1").unwrap().room(), "a");
+        assert_eq!(Message::parse("b mv 2 6 4 4 4").unwrap().room(), "b");
+        assert_eq!(Message::parse("c reset").unwrap().room(), "c");
+        assert_eq!(Message::parse("d thinking 2").unwrap().room(), "d");
+        assert_eq!(Message::parse("e difficulty 2").unwrap().room(), "e");
+    }
+
+    #[test]
+    fn messages_for_other_rooms_still_parse() {
+        // filtering by room is up to the receiver, so it can tell them apart from garbage
+        let msg = Message::parse("other mv 2 6 4 4 4").unwrap();
+        assert_ne!(msg.room(), "lobby");
+        // a room called "room" isn't mistaken for a lobby announcement
+        assert_eq!(Message::parse("room reset").unwrap().room(), "room");
+    }
+
+    #[test]
+    fn encoding_pads_and_decoding_strips() {
+        let encoded = encode("lobby reset ");
+        assert_eq!(encoded.len(), MSG_SIZE);
+        assert_eq!(decode(&encoded).unwrap(), "lobby reset ");
+
+        assert_eq!(encode(&"x".repeat(100)).len(), MSG_SIZE);
+        assert!(decode(&[0xff, 0xfe, 0]).is_none());
+    }
+
+    #[test]
+    fn tags_name_the_sender_and_are_left_off_by_parsing() {
+        let tagged = tag("lobby draw white ", 0x1a2b_3c4d);
+        assert_eq!(tagged, "lobby draw white ~1a2b3c4d ");
+        assert_eq!(tagged.len(), "lobby draw white ".len() + INSTANCE_TAG_SIZE);
+        assert_eq!(untag(&tagged), (Some(0x1a2b_3c4d), "lobby draw white"));
+        assert_eq!(
+            Message::parse(&tagged).unwrap().to_string(),
+            "lobby draw white "
+        );
+        let chat = tag("lobby chat black hej då ", 7);
+        assert_eq!(untag(&chat).0, Some(7));
+        assert!(
+            Message::parse(&decode(&encode(&chat)).unwrap())
+                == Message::parse("lobby chat black hej då ")
+        );
+
+        // untagged, or only looking like it
+        assert_eq!(untag("lobby reset "), (None, "lobby reset "));
+        assert_eq!(untag("lobby chat white ~abc ").0, None);
+        assert_eq!(untag("~1a2b3c4d").0, None);
+        // a message that only just fits goes without
+        let full = format!("{} chat white {}", "x".repeat(40), "y".repeat(12));
+        assert_eq!(tag(&full, 7), full);
+    }
+
+    #[test]
+    fn rooms_leave_room_for_the_tag() {
+        let room = "x".repeat(MAX_ROOM_LENGTH);
+        assert_eq!(check_room(&room), Ok(()));
+        assert!(check_room(&format!("{}y", room)).is_err());
+        assert!(check_room("").is_err());
+        assert!(check_room("fredags schack").is_err());
+        // more than MAX_ROOM_LENGTH bytes, though fewer characters
+        assert!(check_room(&"ä".repeat(MAX_ROOM_LENGTH / 2 + 1)).is_err());
+
+        // the longest of each kind there is without free text, in the longest room
+        let room = || room.clone();
+        let pos = Position::new(7, 7).unwrap();
+        let longest = [
+            Message::Move {
+                room: room(),
+                counter: 9999,
+                from: pos,
+                to: pos,
+            },
+            Message::RematchCancel {
+                room: room(),
+                colour: Colour::White,
+            },
+            Message::SyncOffer {
+                room: room(),
+                counter: 9999,
+                hash: u64::MAX,
+            },
+            Message::SyncRequest {
+                room: room(),
+                counter: 9999,
+            },
+            Message::Result {
+                room: room(),
+                score: Score::Drawn,
+            },
+        ];
+        for msg in longest {
+            let tagged = tag(&msg.to_string(), u32::MAX);
+            assert_eq!(untag(&tagged).0, Some(u32::MAX), "{} went untagged", msg);
+        }
+    }
+
+    /// One of every kind of message, as seen on the wire.
+    const VALID: [&str; 10] = [
+        "room lobby 42 ",
+        "lobby mv 2 6 4 4 4 ",
+        "lobby reset ",
+        "bot thinking 3 ",
+        "bot difficulty 5 ",
+        "lobby draw white ",
+        "lobby resign black ",
+        "lobby assist white ",
+        "lobby rematch_auto_ok white ",
+        "lobby rematch_auto_cancel black ",
+    ];
+
+    /// Reads a frame the way the network thread does, and checks that anything understood is
+    /// sent on again as the same message. Must never panic, whatever the frame holds.
+    fn check_frame(frame: &[u8]) -> Result<(), TestCaseError> {
+        let text = match decode(&frame[..frame.len().min(MSG_SIZE)]) {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+        if let Ok(msg) = Message::parse(&text) {
+            let again = decode(&encode(&msg.to_string())).unwrap();
+            prop_assert!(
+                Message::parse(&again) == Ok(msg),
+                "{:?} became {:?}",
+                text,
+                again
+            );
+        }
+        Ok(())
+    }
+
+    /// A change to one of the words of a message.
+    #[derive(Clone, Debug)]
+    enum Edit {
+        Replace(usize, String),
+        Remove(usize),
+        Insert(usize, String),
+    }
+
+    fn edit() -> impl Strategy<Value = Edit> {
+        // words that are meaningful somewhere in a message, or almost are
+        let word = prop_oneof![
+            prop::sample::select(vec![
+                "room",
+                "mv",
+                "reset",
+                "thinking",
+                "difficulty",
+                "draw",
+                "resign",
+                "white",
+                "black",
+                "\"",
+                "-1",
+                "+7",
+                "8",
+                "255",
+                "256",
+                "4294967296",
+            ])
+            .prop_map(String::from),
+            any::<u32>().prop_map(|n| n.to_string()),
+            "\\PC{0,8}",
+        ];
+        prop_oneof![
+            (0..8usize, word.clone()).prop_map(|(i, w)| Edit::Replace(i, w)),
+            (0..8usize).prop_map(Edit::Remove),
+            (0..8usize, word).prop_map(|(i, w)| Edit::Insert(i, w)),
+        ]
+    }
+
+    /// A valid message with a few of its words changed.
+    fn mutated() -> impl Strategy<Value = String> {
+        let edits = prop::collection::vec(edit(), 1..4);
+        (prop::sample::select(VALID.to_vec()), edits).prop_map(|(text, edits)| {
+            let mut words = text
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            for edit in edits {
+                match edit {
+                    Edit::Replace(i, word) if i < words.len() => words[i] = word,
+                    Edit::Remove(i) if i < words.len() => {
+                        words.remove(i);
+                    }
+                    Edit::Insert(i, word) => words.insert(i.min(words.len()), word),
+                    _ => (),
+                }
+            }
+            words.join(" ")
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_frames_never_panic(frame in prop::collection::vec(any::<u8>(), 0..80)) {
+            check_frame(&frame)?;
+        }
+
+        #[test]
+        fn mutated_messages_never_panic(text in mutated()) {
+            check_frame(text.as_bytes())?;
+        }
+    }
+}
@@ -0,0 +1,144 @@
+/**
+ * Informal Elo ratings, for fun: ours and a provisional one for each opponent, worked out by
+ * going through the match history from the first game. Nothing is stored and nothing is sent,
+ * so they're only as good as the games played on this computer.
+ */
+use crate::matches::{Match, Outcome};
+use std::collections::BTreeMap;
+
+/// What everyone starts on.
+pub const INITIAL: f64 = 1500.0;
+
+/// How far one game moves a rating at most.
+pub const K: f64 = 32.0;
+
+/// The score we're expected to get against an opponent, from 0 to 1.
+pub fn expected(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// What a game scores: 1 for a win, a half for a draw.
+pub fn score(outcome: Outcome) -> f64 {
+    match outcome {
+        Outcome::Won => 1.0,
+        Outcome::Drawn => 0.5,
+        Outcome::Lost => 0.0,
+    }
+}
+
+/// How much our rating changes with a game against an opponent; theirs changes as much the
+/// other way.
+pub fn change(rating: f64, opponent: f64, outcome: Outcome) -> f64 {
+    K * (score(outcome) - expected(rating, opponent))
+}
+
+/// An opponent's provisional rating, and how many games it's based on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Opponent {
+    pub rating: f64,
+    pub games: u32,
+}
+
+/// Our rating and every opponent's, as of the last game recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ratings {
+    pub mine: f64,
+    opponents: BTreeMap<String, Opponent>,
+}
+
+impl Default for Ratings {
+    fn default() -> Ratings {
+        Ratings {
+            mine: INITIAL,
+            opponents: BTreeMap::new(),
+        }
+    }
+}
+
+impl Ratings {
+    /// The ratings after every game in a history that's listed most recent first, as
+    /// `matches::load` gives it.
+    pub fn from_history(games: &[Match]) -> Ratings {
+        let mut ratings = Ratings::default();
+        for game in games.iter().rev() {
+            ratings.record(&game.opponent, game.outcome);
+        }
+        ratings
+    }
+
+    /// An opponent we've played before.
+    pub fn opponent(&self, name: &str) -> Option<Opponent> {
+        self.opponents.get(name).copied()
+    }
+
+    /// Updates both ratings with a game. Returns how much ours changed.
+    pub fn record(&mut self, opponent: &str, outcome: Outcome) -> f64 {
+        let theirs = self
+            .opponents
+            .entry(opponent.to_string())
+            .or_insert(Opponent {
+                rating: INITIAL,
+                games: 0,
+            });
+        let delta = change(self.mine, theirs.rating, outcome);
+        self.mine += delta;
+        theirs.rating -= delta;
+        theirs.games += 1;
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matches;
+    use chess_template::Colour;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn changes_worked_out_by_hand() {
+        // equal ratings expect half a point each, so a win is worth K / 2
+        assert!(close(expected(1500.0, 1500.0), 0.5));
+        assert!(close(change(1500.0, 1500.0, Outcome::Won), 16.0));
+        assert!(close(change(1500.0, 1500.0, Outcome::Drawn), 0.0));
+
+        // 200 points ahead expects 1 / (1 + 10^-0.5) = 0.7597
+        assert!(close(expected(1600.0, 1400.0), 0.7597));
+        assert!(close(change(1600.0, 1400.0, Outcome::Won), 7.69));
+        assert!(close(change(1600.0, 1400.0, Outcome::Lost), -24.31));
+        assert!(close(change(1400.0, 1600.0, Outcome::Drawn), 8.31));
+    }
+
+    #[test]
+    fn games_are_counted_oldest_first() {
+        let mut ratings = Ratings::default();
+        assert!(close(ratings.record("lobby", Outcome::Won), 16.0));
+        assert!(close(ratings.mine, 1516.0));
+        let lobby = ratings.opponent("lobby").unwrap();
+        assert!(close(lobby.rating, 1484.0));
+        assert_eq!(lobby.games, 1);
+
+        // 32 points ahead expects 1 / (1 + 10^-0.08) = 0.5460, so losing costs 17.47
+        assert!(close(ratings.record("lobby", Outcome::Lost), -17.47));
+        assert!(close(ratings.mine, 1498.53));
+        assert!(close(ratings.opponent("lobby").unwrap().rating, 1501.47));
+        assert!(ratings.opponent("Computer, level 3").is_none());
+
+        // the history lists the loss first, being the most recent
+        let game = |outcome| Match {
+            played_at: 0,
+            opponent: "lobby".to_string(),
+            colour: Colour::White,
+            outcome,
+            moves: Vec::new(),
+            time_control: matches::NO_TIME_CONTROL.to_string(),
+            pgn: String::new(),
+            evaluations: None,
+        };
+        let history = [game(Outcome::Lost), game(Outcome::Won)];
+        assert_eq!(Ratings::from_history(&history), ratings);
+    }
+}
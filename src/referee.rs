@@ -0,0 +1,516 @@
+/**
+ * The game as both ends of the protocol keep it: the position, the move counter the players keep
+ * in step, and what offers, resignations, restarts and takebacks do to it. The GUI and the
+ * headless bots follow their games with a `Referee` each, feeding it the messages that arrive and
+ * playing their own moves through it, so the two can't drift apart. What to show, and what to
+ * answer, is left to them.
+ */
+use crate::matches::Outcome;
+use crate::protocol::Message;
+use chess_template::{Colour, Game, GameState, Piece, Position};
+
+/// How a game ended other than on the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ending {
+    /// The player of this colour gave up.
+    Resigned(Colour),
+    /// Both players offered a draw.
+    DrawAgreed,
+}
+
+/// Who the game is played against, which decides what their messages can do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opponent {
+    /// No one: both sides are played on this board, so there's no one to offer or resign.
+    Hotseat,
+    /// The computer, or a bot on this computer, which does as it's asked.
+    Local,
+    /// Someone over the network, who has to agree to a restart once moves have been played.
+    Online,
+}
+
+/// What a message from the opponent did to the game.
+pub enum Event {
+    /// Nothing, e.g. our own move or offer sent back to us.
+    Nothing,
+    /// A move that came while one was being taken back, or after the end, and was dropped.
+    Stale,
+    /// A move we already have, delivered twice or late, or sent again.
+    Repeated,
+    /// The opponent's move, played on the board `before`.
+    Moved {
+        from: Position,
+        to: Position,
+        before: [Option<Piece>; 64],
+    },
+    /// A move that can't be played after ours, numbered `counter`: the games are out of sync.
+    OutOfSync {
+        counter: u32,
+    },
+    /// The opponent asks to start again, which `restart` agrees to.
+    RestartAsked(Colour),
+    /// The game started again.
+    Restarted,
+    /// A restart asked for again, while waiting for an answer.
+    Unasked,
+    /// The player of this colour offered a draw, which an offer from the other side agrees to.
+    DrawOffered(Colour),
+    DrawAgreed,
+    /// The player of this colour gave up.
+    Resigned(Colour),
+    /// A message about something besides the game, e.g. chat, left to the caller.
+    Other(Message),
+}
+
+/// What asking to restart did.
+pub enum Restart {
+    /// The opponent has to agree first, and is asked with the message if they weren't already.
+    Asked(Option<Message>),
+    /// The game started again, and the message tells the opponent.
+    Restarted(Message),
+}
+
+/// One side's view of a game.
+pub struct Referee {
+    pub room: String,
+    pub colour: Colour, // the colour played here, which follows the turn on one board
+    game: Game,
+    counter: u32,                     // moves played, plus one, as in the move messages
+    moves: Vec<(Position, Position)>, // every move played, in order
+    ending: Option<Ending>,           // ended by resignation or agreement
+    draw_offered_by: Option<Colour>,  // until the other side moves
+    restart_asked: Option<Colour>,    // who asked to restart, until the other agrees
+    taking_back: Option<u32>,         // sent a takeback to this counter, until it's back
+}
+
+impl Referee {
+    pub fn new(room: &str, colour: Colour) -> Referee {
+        Referee {
+            room: room.to_string(),
+            colour,
+            game: Game::new(),
+            counter: 1,
+            moves: Vec::new(),
+            ending: None,
+            draw_offered_by: None,
+            restart_asked: None,
+            taking_back: None,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    pub fn moves(&self) -> &[(Position, Position)] {
+        &self.moves
+    }
+
+    pub fn ending(&self) -> Option<Ending> {
+        self.ending
+    }
+
+    pub fn draw_offered_by(&self) -> Option<Colour> {
+        self.draw_offered_by
+    }
+
+    pub fn restart_asked(&self) -> Option<Colour> {
+        self.restart_asked
+    }
+
+    pub fn taking_back(&self) -> Option<u32> {
+        self.taking_back
+    }
+
+    /// The colour the opponent plays.
+    pub fn their_colour(&self) -> Colour {
+        match self.colour {
+            Colour::White => Colour::Black,
+            Colour::Black => Colour::White,
+        }
+    }
+
+    /// Whether the game is over, on the board or by resignation or agreement, whatever the chess
+    /// library would still allow.
+    pub fn game_over(&self) -> bool {
+        self.ending.is_some() || self.game.get_game_state() == GameState::GameOver
+    }
+
+    /// How the game ended for us, if it has: by resignation or agreement, or on the board, where
+    /// the side to move with no moves left has lost if it's in check and drawn if it isn't.
+    pub fn outcome(&self) -> Option<Outcome> {
+        match self.ending {
+            Some(Ending::Resigned(colour)) if colour == self.colour => Some(Outcome::Lost),
+            Some(Ending::Resigned(_)) => Some(Outcome::Won),
+            Some(Ending::DrawAgreed) => Some(Outcome::Drawn),
+            None => Outcome::on_board(&self.game, self.colour),
+        }
+    }
+
+    /// Whether it's our move in a game that isn't over.
+    pub fn our_move(&self) -> bool {
+        !self.game_over() && self.game.get_active_colour() == self.colour
+    }
+
+    /// Acts on a message for this room from the opponent, or the server.
+    pub fn handle(&mut self, msg: Message, opponent: Opponent) -> Event {
+        let online = opponent == Opponent::Online;
+        let ours = self.colour;
+        let theirs = move |colour| opponent != Opponent::Hotseat && colour != ours;
+        match msg {
+            Message::Move {
+                counter, from, to, ..
+            } => self.opponent_moved(counter, from, to),
+            // online, a restart has to be agreed to: the first reset asks, and one in answer to
+            // ours agrees
+            Message::Reset { .. }
+                if !online || self.moves.is_empty() || self.restart_asked == Some(self.colour) =>
+            {
+                self.reset();
+                Event::Restarted
+            }
+            Message::Reset { .. } if self.restart_asked.is_none() => {
+                let colour = self.their_colour();
+                self.restart_asked = Some(colour);
+                Event::RestartAsked(colour)
+            }
+            Message::Reset { .. } => Event::Unasked,
+            // our own offers and resignations were applied when they were sent
+            Message::DrawOffer { colour, .. } if theirs(colour) && !self.game_over() => {
+                self.draw_offer_from(colour)
+            }
+            Message::Resign { colour, .. } if theirs(colour) && !self.game_over() => {
+                self.ending = Some(Ending::Resigned(colour));
+                Event::Resigned(colour)
+            }
+            Message::DrawOffer { .. } | Message::Resign { .. } => Event::Nothing,
+            Message::Takeback { counter, .. } => {
+                if self.taking_back == Some(counter) {
+                    self.taking_back = None;
+                }
+                Event::Nothing
+            }
+            msg => Event::Other(msg),
+        }
+    }
+
+    fn opponent_moved(&mut self, counter: u32, from: Position, to: Position) -> Event {
+        // a move from before a takeback got through, or one after the end, can't be applied
+        if self.taking_back.is_some() || self.game_over() {
+            return Event::Stale;
+        }
+
+        // our own move, sent back
+        if counter == self.counter {
+            return Event::Nothing;
+        }
+
+        // a move we already have: delivered twice or late by the network, or sent again for a
+        // correspondence game. The move that took the counter to n is the (n - 1)th
+        if counter >= 2 && counter < self.counter {
+            let index = counter as usize - 2;
+            if self.moves.get(index) == Some(&(from, to)) {
+                return Event::Repeated;
+            }
+        }
+
+        // anything but the next move, or one that can't be played, means the games differ
+        let before = self.game.get_board();
+        if counter != self.counter + 1 || self.play_on(from, to).is_err() {
+            return Event::OutOfSync { counter };
+        }
+        Event::Moved { from, to, before }
+    }
+
+    /// Plays a move on the board without telling anyone: the opponent's, or one played before,
+    /// e.g. in a saved game.
+    pub fn play_on(&mut self, from: Position, to: Position) -> Result<(), String> {
+        self.game.make_move_pos(from, to)?;
+        self.counter += 1;
+        self.moves.push((from, to));
+
+        // playing on turns down the other side's draw offer, so once it's back with the side that
+        // offered, the offer is gone
+        let to_move = self.game.get_active_colour();
+        if self.draw_offered_by == Some(to_move) {
+            self.draw_offered_by = None;
+        }
+        Ok(())
+    }
+
+    /// Plays our move, returning the message telling the opponent.
+    pub fn play(&mut self, from: Position, to: Position) -> Result<Message, String> {
+        if self.game_over() {
+            return Err("The game is over".to_string());
+        }
+        self.play_on(from, to)?;
+        Ok(Message::Move {
+            room: self.room.clone(),
+            counter: self.counter,
+            from,
+            to,
+        })
+    }
+
+    /// Notes a draw offer from either side. An offer from each side draws the game.
+    fn draw_offer_from(&mut self, colour: Colour) -> Event {
+        match self.draw_offered_by {
+            Some(offered_by) if offered_by != colour => {
+                self.draw_offered_by = None;
+                self.ending = Some(Ending::DrawAgreed);
+                Event::DrawAgreed
+            }
+            _ => {
+                self.draw_offered_by = Some(colour);
+                Event::DrawOffered(colour)
+            }
+        }
+    }
+
+    /// Offers a draw, or accepts the opponent's offer. Returns the message telling them, and
+    /// what the offer did, or None if the game is over or we've already offered.
+    pub fn offer_draw(&mut self) -> Option<(Message, Event)> {
+        if self.game_over() || self.draw_offered_by == Some(self.colour) {
+            return None;
+        }
+        let msg = Message::DrawOffer {
+            room: self.room.clone(),
+            colour: self.colour,
+        };
+        Some((msg, self.draw_offer_from(self.colour)))
+    }
+
+    /// Gives up the game, returning the message telling the opponent. None if it's already over.
+    pub fn resign(&mut self) -> Option<Message> {
+        if self.game_over() {
+            return None;
+        }
+        self.ending = Some(Ending::Resigned(self.colour));
+        Some(Message::Resign {
+            room: self.room.clone(),
+            colour: self.colour,
+        })
+    }
+
+    /// Starts the game again, or asks the opponent to agree to first.
+    pub fn restart(&mut self, opponent: Opponent) -> Restart {
+        let msg = Message::Reset {
+            room: self.room.clone(),
+        };
+        if opponent == Opponent::Online
+            && !self.moves.is_empty()
+            && self.restart_asked != Some(self.their_colour())
+        {
+            if self.restart_asked.is_some() {
+                return Restart::Asked(None);
+            }
+            self.restart_asked = Some(self.colour);
+            return Restart::Asked(Some(msg));
+        }
+        self.reset();
+        Restart::Restarted(msg)
+    }
+
+    /// Takes the game back to the move counter `counter`, and tells the opponent to do the same.
+    /// Their moves are dropped until they have.
+    pub fn take_back(&mut self, counter: u32) -> Message {
+        self.rewind(counter);
+        self.draw_offered_by = None;
+        self.taking_back = Some(counter);
+        Message::Takeback {
+            room: self.room.clone(),
+            counter,
+        }
+    }
+
+    /// Takes the game back to how it was when the move counter was `counter`.
+    pub fn rewind(&mut self, counter: u32) {
+        self.moves.truncate(counter.saturating_sub(1) as usize);
+        self.game = Game::new();
+        for &(from, to) in &self.moves {
+            // every move was legal when it was played, so playing it again can't fail
+            let _ = self.game.make_move_pos(from, to);
+        }
+        self.counter = self.moves.len() as u32 + 1;
+    }
+
+    /// Starts from the beginning, with nothing offered or asked.
+    pub fn reset(&mut self) {
+        *self = Referee::new(&self.room, self.colour);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_square;
+    use crate::bot::random_move;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn move_message(counter: u32, text: &str) -> Message {
+        Message::Move {
+            room: "lunch".to_string(),
+            counter,
+            from: parse_square(&text[..2]).unwrap(),
+            to: parse_square(&text[2..]).unwrap(),
+        }
+    }
+
+    fn message(text: &str) -> Message {
+        Message::parse(text).unwrap()
+    }
+
+    #[test]
+    fn two_referees_follow_a_game_to_the_same_end() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut white = Referee::new("lunch", Colour::White);
+        let mut black = Referee::new("lunch", Colour::Black);
+
+        // the server relays everything to both, the sender included
+        let mut relayed: Vec<Message> = vec![];
+        for _ in 0..1000 {
+            if white.game_over() && black.game_over() {
+                break;
+            }
+            for msg in std::mem::take(&mut relayed) {
+                for referee in [&mut white, &mut black] {
+                    let event = referee.handle(msg.clone(), Opponent::Online);
+                    assert!(!matches!(event, Event::OutOfSync { .. }));
+                }
+            }
+            for referee in [&mut white, &mut black] {
+                if referee.our_move() {
+                    let (from, to) = random_move(referee.game(), &mut rng).unwrap();
+                    relayed.push(referee.play(from, to).unwrap());
+                }
+            }
+        }
+        assert!(white.counter() == black.counter() && white.moves() == black.moves());
+    }
+
+    #[test]
+    fn moves_are_kept_in_step_with_the_opponent() {
+        let mut referee = Referee::new("lunch", Colour::Black);
+        assert!(!referee.our_move());
+        let event = referee.handle(move_message(2, "e2e4"), Opponent::Online);
+        assert!(matches!(event, Event::Moved { .. }));
+        assert!(referee.our_move());
+
+        let reply = referee.play(parse_square("e7").unwrap(), parse_square("e5").unwrap());
+        assert!(reply.unwrap() == move_message(3, "e7e5"));
+        // our move sent back, and the opponent's again, change nothing
+        let echo = referee.handle(move_message(3, "e7e5"), Opponent::Online);
+        assert!(matches!(echo, Event::Nothing));
+        let again = referee.handle(move_message(2, "e2e4"), Opponent::Online);
+        assert!(matches!(again, Event::Repeated));
+        assert!(!referee.our_move());
+
+        for (counter, text) in [(6, "d2d4"), (2, "d2d4"), (4, "e4e6")] {
+            let event = referee.handle(move_message(counter, text), Opponent::Online);
+            assert!(matches!(event, Event::OutOfSync { counter: at } if at == counter));
+        }
+        assert_eq!(referee.counter(), 3);
+    }
+
+    #[test]
+    fn offers_and_resignations_come_from_the_other_side() {
+        let mut referee = Referee::new("lunch", Colour::White);
+        // our own resignation sent back doesn't count, and on one board nobody else resigns
+        let ours = referee.handle(message("lunch resign white "), Opponent::Online);
+        assert!(matches!(ours, Event::Nothing));
+        let alone = referee.handle(message("lunch resign black "), Opponent::Hotseat);
+        assert!(matches!(alone, Event::Nothing));
+        assert_eq!(referee.outcome(), None);
+        let theirs = referee.handle(message("lunch resign black "), Opponent::Online);
+        assert!(matches!(theirs, Event::Resigned(Colour::Black)));
+        assert_eq!(referee.outcome(), Some(Outcome::Won));
+
+        let mut referee = Referee::new("lunch", Colour::Black);
+        let offered = referee.handle(message("lunch draw white "), Opponent::Online);
+        assert!(matches!(offered, Event::DrawOffered(Colour::White)));
+        let (accept, agreed) = referee.offer_draw().unwrap();
+        assert_eq!(accept.to_string(), "lunch draw black ");
+        assert!(matches!(agreed, Event::DrawAgreed));
+        assert_eq!(referee.outcome(), Some(Outcome::Drawn));
+        // offered again, once it's over, it does nothing
+        let late = referee.handle(message("lunch draw white "), Opponent::Online);
+        assert!(matches!(late, Event::Nothing));
+        assert!(referee.offer_draw().is_none() && referee.resign().is_none());
+    }
+
+    #[test]
+    fn playing_on_turns_a_draw_offer_down() {
+        let mut referee = Referee::new("lunch", Colour::Black);
+        referee.handle(move_message(2, "e2e4"), Opponent::Online);
+        referee.handle(message("lunch draw white "), Opponent::Online);
+        assert_eq!(referee.draw_offered_by(), Some(Colour::White));
+        referee
+            .play(parse_square("e7").unwrap(), parse_square("e5").unwrap())
+            .unwrap();
+        assert_eq!(referee.draw_offered_by(), None);
+    }
+
+    #[test]
+    fn a_restart_online_is_agreed_to_once() {
+        let mut referee = Referee::new("lunch", Colour::Black);
+        referee.handle(move_message(2, "e2e4"), Opponent::Online);
+        let asked = referee.handle(message("lunch reset ~0000002a "), Opponent::Online);
+        assert!(matches!(asked, Event::RestartAsked(Colour::White)));
+        let again = referee.handle(message("lunch reset "), Opponent::Online);
+        assert!(matches!(again, Event::Unasked));
+
+        let agreed = referee.restart(Opponent::Online);
+        assert!(matches!(agreed, Restart::Restarted(msg) if msg.to_string() == "lunch reset "));
+        assert_eq!(referee.counter(), 1);
+        // our agreement sent back starts nothing again
+        let echo = referee.handle(message("lunch reset "), Opponent::Online);
+        assert!(matches!(echo, Event::Restarted));
+        referee.handle(move_message(2, "d2d4"), Opponent::Online);
+        assert!(referee.our_move());
+
+        // asking first waits for the opponent, who agrees with a reset of their own
+        assert!(matches!(
+            referee.restart(Opponent::Online),
+            Restart::Asked(Some(_))
+        ));
+        assert!(matches!(
+            referee.restart(Opponent::Online),
+            Restart::Asked(None)
+        ));
+        let answer = referee.handle(message("lunch reset "), Opponent::Online);
+        assert!(matches!(answer, Event::Restarted));
+        assert!(referee.moves().is_empty() && referee.restart_asked().is_none());
+
+        // nobody has to agree against the computer
+        referee.handle(move_message(2, "d2d4"), Opponent::Local);
+        assert!(matches!(
+            referee.restart(Opponent::Local),
+            Restart::Restarted(_)
+        ));
+    }
+
+    #[test]
+    fn moves_from_before_a_takeback_are_dropped() {
+        let mut referee = Referee::new("lunch", Colour::White);
+        referee
+            .play(parse_square("e2").unwrap(), parse_square("e4").unwrap())
+            .unwrap();
+        referee.handle(move_message(3, "e7e5"), Opponent::Local);
+        let msg = referee.take_back(2);
+        assert_eq!(msg.to_string(), "lunch takeback 2 ");
+        assert_eq!((referee.counter(), referee.moves().len()), (2, 1));
+
+        // the computer's reply from before it heard, then its agreement
+        let stale = referee.handle(move_message(3, "e7e5"), Opponent::Local);
+        assert!(matches!(stale, Event::Stale));
+        referee.handle(message("lunch takeback 2 "), Opponent::Local);
+        assert_eq!(referee.taking_back(), None);
+        let reply = referee.handle(move_message(3, "d7d5"), Opponent::Local);
+        assert!(matches!(reply, Event::Moved { .. }));
+    }
+}
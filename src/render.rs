@@ -0,0 +1,1309 @@
+/**
+ * Drawing the board, split in two so it can be measured and tested without a window: first what
+ * each square shows is worked out from the game, then the squares are painted onto a `Canvas`.
+ * Where the selected piece can go is marked in the styles picked in the settings, one for quiet
+ * moves and one for captures.
+ * The window is one canvas; a `Recorder` that just notes the calls is another. Pieces are drawn
+ * by a `PieceRenderer`: from their images where those loaded and as shapes where they didn't, or
+ * as chess symbols from a font if the player picked that theme.
+ */
+use crate::board::{
+    checked_king, glyphs, square_name, square_origin, turned, BOARD_MARGIN, BOARD_ORIGIN,
+    BOARD_SIZE, GRID_CELL_SIZE, SQUARES_SIZE,
+};
+use crate::config::Config;
+use crate::export;
+use crate::scene::Shared;
+use chess_template::{Colour, Piece, PieceType, Position};
+use ggez::{graphics, Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+// GUI Color representations
+const BLACK: graphics::Color =
+    graphics::Color::new(228.0 / 255.0, 196.0 / 255.0, 108.0 / 255.0, 1.0);
+const WHITE: graphics::Color =
+    graphics::Color::new(188.0 / 255.0, 140.0 / 255.0, 76.0 / 255.0, 1.0);
+
+/// Square colours in kiosk mode, near white and a deep blue, which both kinds of piece stand out
+/// on from across a room or in a small stream.
+const CONTRAST_WHITE: graphics::Color = graphics::Color::new(0.97, 0.97, 0.95, 1.0);
+const CONTRAST_BLACK: graphics::Color = graphics::Color::new(0.16, 0.38, 0.62, 1.0);
+
+/// Colour of squares highlighted for a premove.
+const HIGHLIGHT: [f32; 4] = [0.2, 0.4, 1.0, 0.4];
+
+/// Outline of the square the keyboard cursor is on, unlike anything else drawn on the board.
+const CURSOR: [f32; 4] = [0.1, 0.85, 0.3, 1.0];
+
+/// Light red wash over squares the opponent attacks, in the threat view.
+const THREAT: [f32; 4] = [1.0, 0.1, 0.1, 0.25];
+
+/// Ends of the heatmap's ramp, from the squares moved to least to those moved to most, and how
+/// see-through each is.
+const HEAT_COOL: [f32; 4] = [1.0, 0.9, 0.2, 0.15];
+const HEAT_HOT: [f32; 4] = [0.9, 0.1, 0.0, 0.6];
+
+/// Colour and width of the arrow showing a suggested move, and how long its head is.
+const ARROW: [f32; 4] = [0.1, 0.45, 0.9, 0.75];
+const ARROW_WIDTH: f32 = 14.0;
+const ARROW_HEAD: f32 = 36.0;
+
+/// Colour of the warning mark in the corner of a hanging piece's square.
+const WARNING: [f32; 4] = [1.0, 0.55, 0.0, 1.0];
+
+/// How far the green and blue are taken out of a king in check at the height of its pulse,
+/// leaving it red.
+const CHECK_FADE: f32 = 0.65;
+
+/// Colours of the wooden frame around the squares, and of the line and coordinates on it.
+pub const FRAME: [f32; 4] = [0.42, 0.26, 0.13, 1.0];
+const FRAME_INK: [f32; 4] = [0.93, 0.86, 0.72, 1.0];
+
+/// What the board should show this frame, gathered from the game.
+pub struct BoardState<'a> {
+    pub board: [Option<Piece>; 64],
+    pub pieces_hidden: bool,          // blindfold is on
+    pub dots_hidden: bool,            // blindfold hides the move dots too
+    pub selected: Option<Position>,   // square clicked on
+    pub highlighted: &'a [Position],  // squares of the queued premove
+    pub attacked: &'a [Position],     // squares washed red by the threat view
+    pub hanging: &'a [Position],      // pieces that can be taken for nothing
+    pub dots: &'a [Position],         // where the selected piece can go
+    pub captures: &'a [Position],     // the dots that take a piece, worked out with them
+    pub markers: MoveMarkers,         // how the dots are drawn
+    pub repetition: Option<Position>, // a dot whose move would allow a draw claim
+    pub check_pulse: Option<f32>,     // how red a king in check is, from 0 to 1; None leaves it
+    pub flipped: bool,                // turned round, with Black's side at the bottom
+    pub cursor: Option<Position>,     // square the keyboard cursor is on, once it's been used
+    pub palette: Palette,             // colours of the squares
+    pub heat: Option<&'a [u16; 64]>,  // how often each square was moved to, for the heatmap
+}
+
+/// The colours the squares are painted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Wood,
+    HighContrast,
+}
+
+impl Palette {
+    /// The colour of a square, by whether it's one of the white ones.
+    fn tile(self, white: bool) -> graphics::Color {
+        match (self, white) {
+            (Palette::Wood, true) => WHITE,
+            (Palette::Wood, false) => BLACK,
+            (Palette::HighContrast, true) => CONTRAST_WHITE,
+            (Palette::HighContrast, false) => CONTRAST_BLACK,
+        }
+    }
+}
+
+/// A dot on a square the selected piece can move to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dot {
+    Move,
+    Repetition, // the move would allow a draw claim by repetition
+}
+
+/// Everything drawn on one square, bottom layer first.
+#[derive(Clone, Copy)]
+pub struct Square {
+    pub pos: Position, // where it's drawn, which isn't its own place if flipped
+    pub white: bool,   // drawn in the palette's white colour rather than its black
+    pub colour: graphics::Color, // the tile's colour, from the palette
+    pub heat: Option<graphics::Color>, // heatmap wash over the tile, under everything else
+    pub highlighted: bool, // part of the queued premove
+    pub attacked: bool, // attacked by the opponent, while the threat view is on
+    pub hanging: bool, // its piece is attacked and undefended, so it gets a warning mark
+    pub piece: Option<Piece>, // left out when blindfolded
+    pub tint: Option<graphics::Color>, // the piece's colour is multiplied by this, e.g. in check
+    pub outlined: bool, // selected while its piece is hidden, so the click still shows
+    pub dot: Option<Dot>,
+    pub marker: MarkerStyle, // how the dot is drawn, depending on whether the move captures
+    pub cursor: bool,        // the keyboard cursor is on it, so it's outlined over everything
+}
+
+/// Works out what each square shows, in reading order from a8. On a flipped board each one is
+/// moved to the square it's drawn in.
+/// The king in check is found on the board being drawn, so the tint follows whatever position
+/// is shown, e.g. an earlier one from the history.
+pub fn squares(state: &BoardState) -> Vec<Square> {
+    let checked = state
+        .check_pulse
+        .and_then(|pulse| Some((checked_king(&state.board)?, check_tint(pulse))));
+    let hottest = state
+        .heat
+        .and_then(|heat| heat.iter().copied().max())
+        .unwrap_or(0);
+    let mut squares = Vec::with_capacity(64);
+    for row in 0..8 {
+        for col in 0..8 {
+            let pos = match Position::new(row, col) {
+                Ok(pos) => pos,
+                Err(_) => continue,
+            };
+            let dot = if state.dots_hidden || !state.dots.contains(&pos) {
+                None
+            } else if state.repetition == Some(pos) {
+                Some(Dot::Repetition)
+            } else {
+                Some(Dot::Move)
+            };
+            squares.push(Square {
+                pos: turned(pos, state.flipped),
+                white: (row + col) % 2 == 0,
+                colour: state.palette.tile((row + col) % 2 == 0),
+                heat: state
+                    .heat
+                    .and_then(|heat| heat_colour(heat[row * 8 + col], hottest)),
+                highlighted: state.highlighted.contains(&pos),
+                attacked: state.attacked.contains(&pos),
+                hanging: state.hanging.contains(&pos) && !state.pieces_hidden,
+                piece: state.board[row * 8 + col].filter(|_| !state.pieces_hidden),
+                tint: checked
+                    .filter(|(king, _)| *king == pos)
+                    .map(|(_, tint)| tint),
+                outlined: state.pieces_hidden && state.selected == Some(pos),
+                dot,
+                marker: state.markers.style(state.captures.contains(&pos)),
+                cursor: state.cursor == Some(pos),
+            });
+        }
+    }
+    squares
+}
+
+/// The heatmap's colour for a square moved to `count` times, when the square moved to most was
+/// moved to `hottest` times: along the ramp from cool to hot, by how far `count` is to `hottest`.
+/// Squares never moved to get no colour at all.
+pub fn heat_colour(count: u16, hottest: u16) -> Option<graphics::Color> {
+    if count == 0 || hottest == 0 {
+        return None;
+    }
+    let t = (f32::from(count) / f32::from(hottest)).min(1.0);
+    let channel = |i: usize| HEAT_COOL[i] * (1.0 - t) + HEAT_HOT[i] * t;
+    Some(graphics::Color::new(
+        channel(0),
+        channel(1),
+        channel(2),
+        channel(3),
+    ))
+}
+
+/// The colour a king in check is multiplied by, `pulse` of the way from its own colour to red.
+pub fn check_tint(pulse: f32) -> graphics::Color {
+    let fade = CHECK_FADE * pulse.clamp(0.0, 1.0);
+    graphics::Color::new(1.0, 1.0 - fade, 1.0 - fade, 1.0)
+}
+
+/// A colour multiplied by a tint, channel by channel, the way `DrawParam::color` does it.
+fn modulate(colour: graphics::Color, tint: Option<graphics::Color>) -> graphics::Color {
+    match tint {
+        Some(tint) => graphics::Color::new(
+            colour.r * tint.r,
+            colour.g * tint.g,
+            colour.b * tint.b,
+            colour.a * tint.a,
+        ),
+        None => colour,
+    }
+}
+
+/// Something the board can be painted on.
+pub trait Canvas {
+    fn rectangle(
+        &mut self,
+        mode: graphics::DrawMode,
+        rect: graphics::Rect,
+        colour: graphics::Color,
+    ) -> GameResult;
+
+    fn circle(&mut self, centre: [f32; 2], radius: f32, colour: graphics::Color) -> GameResult;
+
+    /// Marks a square as a legal destination, in one of the marker styles.
+    fn marker(&mut self, style: MarkerStyle, pos: Position, colour: graphics::Color) -> GameResult;
+
+    /// Draws a piece filling the square, its colours multiplied by the tint if there is one.
+    fn piece(&mut self, piece: Piece, pos: Position, tint: Option<graphics::Color>) -> GameResult;
+}
+
+/// Paints the squares, each one's layers in order.
+pub fn draw_board(canvas: &mut impl Canvas, squares: &[Square]) -> GameResult {
+    for square in squares {
+        let (x, y) = square_origin(square.pos);
+        let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+        let tile = graphics::Rect::new(x, y, width, height);
+
+        canvas.rectangle(graphics::DrawMode::fill(), tile, square.colour)?;
+
+        if let Some(heat) = square.heat {
+            canvas.rectangle(graphics::DrawMode::fill(), tile, heat)?;
+        }
+
+        if square.highlighted {
+            canvas.rectangle(graphics::DrawMode::fill(), tile, HIGHLIGHT.into())?;
+        }
+
+        if square.attacked {
+            canvas.rectangle(graphics::DrawMode::fill(), tile, THREAT.into())?;
+        }
+
+        if let Some(piece) = square.piece {
+            canvas.piece(piece, square.pos, square.tint)?;
+        }
+
+        if square.outlined {
+            let inside = graphics::Rect::new(x + 2.0, y + 2.0, width - 4.0, height - 4.0);
+            canvas.rectangle(
+                graphics::DrawMode::stroke(4.0),
+                inside,
+                [1.0, 0.0, 0.0, 1.0].into(),
+            )?;
+        }
+
+        // an exclamation mark on an orange disc in the top right corner
+        if square.hanging {
+            let (right, top) = (x + width, y);
+            canvas.circle([right - 14.0, top + 14.0], 10.0, WARNING.into())?;
+            let mark = graphics::Color::new(0.2, 0.1, 0.0, 1.0);
+            let bar = graphics::Rect::new(right - 15.5, top + 7.0, 3.0, 9.0);
+            canvas.rectangle(graphics::DrawMode::fill(), bar, mark)?;
+            let point = graphics::Rect::new(right - 15.5, top + 18.0, 3.0, 3.0);
+            canvas.rectangle(graphics::DrawMode::fill(), point, mark)?;
+        }
+
+        if let Some(dot) = square.dot {
+            draw_move_marker(canvas, square.pos, dot, square.marker)?;
+        }
+
+        if square.cursor {
+            let inside = graphics::Rect::new(x + 5.0, y + 5.0, width - 10.0, height - 10.0);
+            canvas.rectangle(graphics::DrawMode::stroke(4.0), inside, CURSOR.into())?;
+        }
+    }
+    Ok(())
+}
+
+/// Marks a legal destination, red or orange if the move would allow a draw claim. A filled square
+/// is see-through, so the piece it would take still shows.
+fn draw_move_marker(
+    canvas: &mut impl Canvas,
+    pos: Position,
+    dot: Dot,
+    style: MarkerStyle,
+) -> GameResult {
+    let [r, g, b] = match dot {
+        Dot::Move => [1.0, 0.0, 0.0],
+        Dot::Repetition => [1.0, 0.6, 0.0],
+    };
+    let alpha = match style {
+        MarkerStyle::Fill => 0.35,
+        MarkerStyle::Dot | MarkerStyle::Corners | MarkerStyle::Ring => 1.0,
+    };
+    canvas.marker(style, pos, graphics::Color::new(r, g, b, alpha))
+}
+
+/// An arrow from the middle of one square to the middle of another, e.g. a suggested move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arrow {
+    pub shaft: [[f32; 2]; 2], // where the line starts and where it meets the head
+    pub head: [[f32; 2]; 3],  // the point, then the two back corners
+}
+
+/// The arrow for a move from `from` to `to`, on a board that may be turned round.
+pub fn arrow(from: Position, to: Position, flipped: bool) -> Arrow {
+    let middle = |pos: Position| {
+        let (x, y) = square_origin(turned(pos, flipped));
+        [
+            x + GRID_CELL_SIZE.0 as f32 / 2.0,
+            y + GRID_CELL_SIZE.1 as f32 / 2.0,
+        ]
+    };
+    let (start, point) = (middle(from), middle(to));
+    let (dx, dy) = (point[0] - start[0], point[1] - start[1]);
+    let length = (dx * dx + dy * dy).sqrt().max(1.0);
+    let (ux, uy) = (dx / length, dy / length);
+    let base = [point[0] - ux * ARROW_HEAD, point[1] - uy * ARROW_HEAD];
+    let half = ARROW_HEAD * 0.6;
+    Arrow {
+        shaft: [start, base],
+        head: [
+            point,
+            [base[0] - uy * half, base[1] + ux * half],
+            [base[0] + uy * half, base[1] - ux * half],
+        ],
+    }
+}
+
+/// Draws an arrow over the board.
+pub fn draw_arrow(ctx: &mut Context, arrow: &Arrow) -> GameResult {
+    let mesh = graphics::MeshBuilder::new()
+        .line(&arrow.shaft, ARROW_WIDTH, ARROW.into())?
+        .polygon(graphics::DrawMode::fill(), &arrow.head, ARROW.into())?
+        .build(ctx)?;
+    graphics::draw(ctx, &mesh, graphics::DrawParam::default())
+}
+
+/// How a legal destination is marked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MarkerStyle {
+    /// A small dot in the middle of the square.
+    Dot,
+    /// The whole square, washed over.
+    Fill,
+    /// A triangle in each corner.
+    Corners,
+    /// A ring just inside the square's edges.
+    Ring,
+}
+
+/// Marker styles in the order the setting cycles through them.
+const MARKER_STYLES: [MarkerStyle; 4] = [
+    MarkerStyle::Dot,
+    MarkerStyle::Fill,
+    MarkerStyle::Corners,
+    MarkerStyle::Ring,
+];
+
+impl MarkerStyle {
+    pub fn next(self) -> MarkerStyle {
+        MARKER_STYLES[(self.index() + 1) % MARKER_STYLES.len()]
+    }
+
+    pub fn previous(self) -> MarkerStyle {
+        MARKER_STYLES[(self.index() + MARKER_STYLES.len() - 1) % MARKER_STYLES.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MarkerStyle::Dot => "Dot",
+            MarkerStyle::Fill => "Filled square",
+            MarkerStyle::Corners => "Corners",
+            MarkerStyle::Ring => "Ring",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The marker as a white mesh covering the square from its top left corner, to be moved into
+    /// place and coloured when it's drawn.
+    fn build(self, ctx: &mut Context) -> GameResult<graphics::Mesh> {
+        let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+        let centre = [width / 2.0, height / 2.0];
+        let mut mesh = graphics::MeshBuilder::new();
+        match self {
+            MarkerStyle::Dot => {
+                mesh.circle(
+                    graphics::DrawMode::fill(),
+                    centre,
+                    10.0,
+                    0.1,
+                    graphics::Color::WHITE,
+                )?;
+            }
+            MarkerStyle::Fill => {
+                mesh.rectangle(
+                    graphics::DrawMode::fill(),
+                    graphics::Rect::new(0.0, 0.0, width, height),
+                    graphics::Color::WHITE,
+                )?;
+            }
+            MarkerStyle::Corners => {
+                let size = width / 4.0;
+                for (x, y, dx, dy) in [
+                    (0.0, 0.0, size, size),
+                    (width, 0.0, -size, size),
+                    (0.0, height, size, -size),
+                    (width, height, -size, -size),
+                ] {
+                    mesh.polygon(
+                        graphics::DrawMode::fill(),
+                        &[[x, y], [x + dx, y], [x, y + dy]],
+                        graphics::Color::WHITE,
+                    )?;
+                }
+            }
+            MarkerStyle::Ring => {
+                mesh.circle(
+                    graphics::DrawMode::stroke(6.0),
+                    centre,
+                    width / 2.0 - 4.0,
+                    0.1,
+                    graphics::Color::WHITE,
+                )?;
+            }
+        }
+        mesh.build(ctx)
+    }
+}
+
+/// The marker styles for quiet moves and for captures, picked on the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MoveMarkers {
+    pub quiet: MarkerStyle,
+    pub capture: MarkerStyle,
+}
+
+impl Default for MoveMarkers {
+    fn default() -> MoveMarkers {
+        MoveMarkers {
+            quiet: MarkerStyle::Dot,
+            capture: MarkerStyle::Dot,
+        }
+    }
+}
+
+impl MoveMarkers {
+    /// The style for a move, depending on whether it captures.
+    pub fn style(self, capture: bool) -> MarkerStyle {
+        if capture {
+            self.capture
+        } else {
+            self.quiet
+        }
+    }
+}
+
+/// The marker meshes, each built the first time it's drawn and kept, since they never change.
+#[derive(Default)]
+pub struct MarkerMeshes {
+    meshes: [Option<graphics::Mesh>; 4], // in the order of `MARKER_STYLES`
+}
+
+impl MarkerMeshes {
+    fn draw(
+        &mut self,
+        ctx: &mut Context,
+        style: MarkerStyle,
+        pos: Position,
+        colour: graphics::Color,
+    ) -> GameResult {
+        let mesh = match &mut self.meshes[style.index()] {
+            Some(mesh) => mesh,
+            slot => slot.insert(style.build(ctx)?),
+        };
+        let (x, y) = square_origin(pos);
+        graphics::draw(
+            ctx,
+            mesh,
+            graphics::DrawParam::default().dest([x, y]).color(colour),
+        )
+    }
+}
+
+/// The frame around the squares, with the files written under them and the ranks to their
+/// left. It's built the first time it's drawn, and only the coordinates change after that.
+#[derive(Default)]
+pub struct BoardFrame {
+    mesh: Option<graphics::Mesh>,
+    labels: Vec<(graphics::Text, [f32; 2])>, // each coordinate, and where it's drawn
+    flipped: bool,                           // the labels are for a flipped board
+}
+
+impl BoardFrame {
+    /// Draws the frame, under the squares. The coordinates are written again when the board is
+    /// flipped.
+    pub fn draw(&mut self, ctx: &mut Context, flipped: bool) -> GameResult {
+        if self.mesh.is_none() {
+            // a line just outside the squares sets them off from the wood
+            let squares = graphics::Rect::new(
+                BOARD_ORIGIN.0 - 1.0,
+                BOARD_ORIGIN.1 - 1.0,
+                SQUARES_SIZE.0 + 2.0,
+                SQUARES_SIZE.1 + 2.0,
+            );
+            let mesh = graphics::MeshBuilder::new()
+                .rectangle(
+                    graphics::DrawMode::fill(),
+                    graphics::Rect::new(0.0, 0.0, BOARD_SIZE.0, BOARD_SIZE.1),
+                    FRAME.into(),
+                )?
+                .rectangle(graphics::DrawMode::stroke(2.0), squares, FRAME_INK.into())?
+                .build(ctx)?;
+            self.mesh = Some(mesh);
+        }
+
+        if self.labels.is_empty() || self.flipped != flipped {
+            self.flipped = flipped;
+            self.labels = frame_labels(flipped)
+                .into_iter()
+                .map(|(label, [x, y])| {
+                    let text = graphics::Text::new(
+                        graphics::TextFragment::from(label)
+                            .scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+                    );
+                    let size = text.dimensions(ctx);
+                    (text, [x - size.w / 2.0, y - size.h / 2.0])
+                })
+                .collect();
+        }
+
+        if let Some(mesh) = &self.mesh {
+            graphics::draw(ctx, mesh, graphics::DrawParam::default())?;
+        }
+        for (text, dest) in &self.labels {
+            graphics::draw(
+                ctx,
+                text,
+                graphics::DrawParam::default()
+                    .color(FRAME_INK.into())
+                    .dest(*dest),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The coordinates written on the frame, each with the point it's centred on: a file under
+/// each column, and a rank left of each row, as they are on a flipped board if it is.
+fn frame_labels(flipped: bool) -> Vec<(String, [f32; 2])> {
+    let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+    let mut labels = Vec::with_capacity(16);
+    for i in 0..8 {
+        if let Ok(pos) = Position::new(7, i) {
+            let (x, y) = square_origin(pos);
+            let file = square_name(turned(pos, flipped))[..1].to_string();
+            labels.push((file, [x + width / 2.0, y + height + BOARD_MARGIN / 2.0]));
+        }
+        if let Ok(pos) = Position::new(i, 0) {
+            let (x, y) = square_origin(pos);
+            let rank = square_name(turned(pos, flipped))[1..].to_string();
+            labels.push((rank, [x - BOARD_MARGIN / 2.0, y + height / 2.0]));
+        }
+    }
+    labels
+}
+
+/// How pieces are drawn, picked on the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PieceTheme {
+    /// The piece images.
+    Images,
+    /// Chess symbols from a font, in the colours from the config file.
+    Glyphs,
+}
+
+impl PieceTheme {
+    /// The other theme, for cycling through the setting.
+    pub fn next(self) -> PieceTheme {
+        match self {
+            PieceTheme::Images => PieceTheme::Glyphs,
+            PieceTheme::Glyphs => PieceTheme::Images,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PieceTheme::Images => "Images",
+            PieceTheme::Glyphs => "Symbols",
+        }
+    }
+}
+
+/// Draws pieces onto the window.
+pub trait PieceRenderer {
+    fn draw_piece(
+        &self,
+        ctx: &mut Context,
+        piece: Piece,
+        pos: Position,
+        tint: Option<graphics::Color>,
+    ) -> GameResult;
+}
+
+/// Pieces in the chosen theme. Pieces without an image, or every piece if the glyph font
+/// couldn't be loaded, are drawn as shapes.
+impl PieceRenderer for Shared {
+    fn draw_piece(
+        &self,
+        ctx: &mut Context,
+        piece: Piece,
+        pos: Position,
+        tint: Option<graphics::Color>,
+    ) -> GameResult {
+        match (self.config.pieces, self.glyph_font()) {
+            (PieceTheme::Glyphs, Some(font)) => {
+                let [white, black] = [self.config.white_glyphs, self.config.black_glyphs]
+                    .map(|[r, g, b]| graphics::Color::from_rgb(r, g, b));
+                Glyphs { font, white, black }.draw_piece(ctx, piece, pos, tint)
+            }
+            _ => match self.sprite(piece.colour, piece.piece_type) {
+                Some(sprite) => graphics::draw(
+                    ctx,
+                    sprite,
+                    graphics::DrawParam::default()
+                        .color(tint.unwrap_or(graphics::Color::WHITE))
+                        .scale([2.0, 2.0]) // Tile size is 90 pixels, while image sizes are 45 pixels.
+                        .dest({
+                            let (x, y) = square_origin(pos);
+                            [x, y]
+                        }),
+                ),
+                None => Shapes.draw_piece(ctx, piece, pos, tint),
+            },
+        }
+    }
+}
+
+/// How the board looks in an SVG diagram: its own colours, with the pieces in the colours of the
+/// glyph theme since the diagram draws them as symbols.
+pub fn diagram_style(config: &Config) -> export::Style {
+    let tile = |colour: graphics::Color| [colour.r, colour.g, colour.b, colour.a];
+    let glyph = |rgb: [u8; 3]| {
+        let [r, g, b] = rgb.map(|v| f32::from(v) / 255.0);
+        [r, g, b, 1.0]
+    };
+    export::Style {
+        white_squares: tile(WHITE),
+        black_squares: tile(BLACK),
+        white_pieces: glyph(config.white_glyphs),
+        black_pieces: glyph(config.black_glyphs),
+        highlight: HIGHLIGHT,
+        frame: FRAME,
+        ink: FRAME_INK,
+        coordinates: config.diagram_coordinates,
+    }
+}
+
+/// Size of a glyph, as a fraction of the square's height.
+const GLYPH_SCALE: f32 = 0.9;
+
+/// How far the symbols sit above the middle of their line, as a fraction of the font size. They
+/// stand on the baseline, with more room above them than below.
+const GLYPH_RAISE: f32 = 0.04;
+
+/// Pieces drawn as chess symbols, which scale to any square size.
+pub struct Glyphs {
+    pub font: graphics::Font,
+    pub white: graphics::Color,
+    pub black: graphics::Color,
+}
+
+impl PieceRenderer for Glyphs {
+    fn draw_piece(
+        &self,
+        ctx: &mut Context,
+        piece: Piece,
+        pos: Position,
+        tint: Option<graphics::Color>,
+    ) -> GameResult {
+        let (colour, edge) = match piece.colour {
+            Colour::White => (self.white, self.black),
+            Colour::Black => (self.black, self.white),
+        };
+        let scale = GRID_CELL_SIZE.1 as f32 * GLYPH_SCALE;
+        let (filled, outline) = glyphs(piece.piece_type);
+
+        // the filled symbol in the piece's colour, edged with the outline symbol in the other's
+        for (symbol, colour) in [(filled, modulate(colour, tint)), (outline, edge)] {
+            let text = graphics::Text::new(
+                graphics::TextFragment::new(symbol)
+                    .font(self.font)
+                    .scale(graphics::PxScale { x: scale, y: scale }),
+            );
+            let size = text.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color(colour)
+                    .dest(glyph_dest(pos, (size.w, size.h), scale)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Where to draw a glyph of the given size so it's centred in its square.
+fn glyph_dest(pos: Position, (width, height): (f32, f32), scale: f32) -> [f32; 2] {
+    let (cell_width, cell_height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+    let (x, y) = square_origin(pos);
+    [
+        x + (cell_width - width) / 2.0,
+        y + (cell_height - height) / 2.0 - GLYPH_RAISE * scale,
+    ]
+}
+
+/// Pieces drawn as filled outlines, needing no images.
+pub struct Shapes;
+
+impl PieceRenderer for Shapes {
+    fn draw_piece(
+        &self,
+        ctx: &mut Context,
+        piece: Piece,
+        pos: Position,
+        tint: Option<graphics::Color>,
+    ) -> GameResult {
+        let (fill, ink) = shape_colours(piece.colour, tint);
+        let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+        let (left, top) = square_origin(pos);
+        let to_screen = |[x, y]: [f32; 2]| [left + x * width, top + y * height];
+
+        let mut mesh = graphics::MeshBuilder::new();
+        for part in shape(piece.piece_type) {
+            // filled first, then outlined so overlapping parts stay apart
+            for (mode, colour) in [
+                (graphics::DrawMode::fill(), fill),
+                (graphics::DrawMode::stroke(2.0), ink),
+            ] {
+                match &part {
+                    Part::Polygon(points) => {
+                        let points: Vec<_> = points.iter().copied().map(to_screen).collect();
+                        mesh.polygon(mode, &points, colour)?;
+                    }
+                    Part::Circle(centre, radius) => {
+                        mesh.circle(mode, to_screen(*centre), radius * width, 0.1, colour)?;
+                    }
+                }
+            }
+        }
+        let mesh = mesh.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::default())
+    }
+}
+
+/// The fill and outline colours of a side's shapes, the fill multiplied by the tint if there is
+/// one.
+pub fn shape_colours(
+    colour: Colour,
+    tint: Option<graphics::Color>,
+) -> (graphics::Color, graphics::Color) {
+    let (fill, ink) = match colour {
+        Colour::White => ([1.0, 1.0, 1.0, 1.0], [0.0, 0.0, 0.0, 1.0]),
+        Colour::Black => ([0.1, 0.1, 0.1, 1.0], [0.8, 0.8, 0.8, 1.0]),
+    };
+    (modulate(fill.into(), tint), ink.into())
+}
+
+/// A piece of a piece's shape, in fractions of a square from its top left corner.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Part {
+    Polygon(Vec<[f32; 2]>),
+    Circle([f32; 2], f32), // centre and radius
+}
+
+/// The outline of each kind of piece, bottom part first. They all stand on the same base.
+#[rustfmt::skip]
+pub fn shape(piece_type: PieceType) -> Vec<Part> {
+    use Part::{Circle, Polygon};
+
+    let base = Polygon(vec![[0.25, 0.78], [0.75, 0.78], [0.75, 0.86], [0.25, 0.86]]);
+    let parts = match piece_type {
+        PieceType::Pawn => vec![
+            Polygon(vec![[0.36, 0.78], [0.44, 0.46], [0.56, 0.46], [0.64, 0.78]]),
+            Circle([0.5, 0.36], 0.11),
+        ],
+        PieceType::Rook => vec![
+            Polygon(vec![[0.34, 0.78], [0.38, 0.38], [0.62, 0.38], [0.66, 0.78]]),
+            Polygon(vec![
+                [0.32, 0.38], [0.32, 0.2], [0.4, 0.2], [0.4, 0.27], [0.46, 0.27], [0.46, 0.2],
+                [0.54, 0.2], [0.54, 0.27], [0.6, 0.27], [0.6, 0.2], [0.68, 0.2], [0.68, 0.38],
+            ]),
+        ],
+        PieceType::Knight => vec![Polygon(vec![
+            [0.3, 0.78], [0.36, 0.5], [0.3, 0.46], [0.24, 0.5], [0.2, 0.44], [0.34, 0.26],
+            [0.42, 0.16], [0.46, 0.24], [0.6, 0.24], [0.7, 0.4], [0.72, 0.78],
+        ])],
+        PieceType::Bishop => vec![
+            Polygon(vec![[0.38, 0.78], [0.44, 0.56], [0.56, 0.56], [0.62, 0.78]]),
+            Polygon(vec![[0.5, 0.22], [0.62, 0.4], [0.6, 0.56], [0.4, 0.56], [0.38, 0.4]]),
+            Circle([0.5, 0.18], 0.04),
+        ],
+        PieceType::Queen => vec![
+            Polygon(vec![
+                [0.32, 0.78], [0.26, 0.3], [0.38, 0.5], [0.44, 0.24], [0.5, 0.48],
+                [0.56, 0.24], [0.62, 0.5], [0.74, 0.3], [0.68, 0.78],
+            ]),
+            Circle([0.26, 0.3], 0.035),
+            Circle([0.44, 0.24], 0.035),
+            Circle([0.56, 0.24], 0.035),
+            Circle([0.74, 0.3], 0.035),
+        ],
+        PieceType::King => vec![
+            Polygon(vec![[0.32, 0.78], [0.3, 0.42], [0.7, 0.42], [0.68, 0.78]]),
+            Polygon(vec![
+                [0.46, 0.42], [0.46, 0.3], [0.38, 0.3], [0.38, 0.24], [0.46, 0.24], [0.46, 0.14],
+                [0.54, 0.14], [0.54, 0.24], [0.62, 0.24], [0.62, 0.3], [0.54, 0.3], [0.54, 0.42],
+            ]),
+        ],
+    };
+    std::iter::once(base).chain(parts).collect()
+}
+
+/// The game window, with pieces drawn by `pieces`.
+pub struct Screen<'a> {
+    pub ctx: &'a mut Context,
+    pub pieces: &'a dyn PieceRenderer,
+    pub markers: &'a mut MarkerMeshes,
+}
+
+impl Canvas for Screen<'_> {
+    fn rectangle(
+        &mut self,
+        mode: graphics::DrawMode,
+        rect: graphics::Rect,
+        colour: graphics::Color,
+    ) -> GameResult {
+        let mesh = graphics::Mesh::new_rectangle(self.ctx, mode, rect, colour)?;
+        graphics::draw(self.ctx, &mesh, graphics::DrawParam::default())
+    }
+
+    fn circle(&mut self, centre: [f32; 2], radius: f32, colour: graphics::Color) -> GameResult {
+        let mesh = graphics::Mesh::new_circle(
+            self.ctx,
+            graphics::DrawMode::fill(),
+            centre,
+            radius,
+            0.1,
+            colour,
+        )?;
+        graphics::draw(self.ctx, &mesh, graphics::DrawParam::default())
+    }
+
+    fn marker(&mut self, style: MarkerStyle, pos: Position, colour: graphics::Color) -> GameResult {
+        self.markers.draw(self.ctx, style, pos, colour)
+    }
+
+    fn piece(&mut self, piece: Piece, pos: Position, tint: Option<graphics::Color>) -> GameResult {
+        self.pieces.draw_piece(self.ctx, piece, pos, tint)
+    }
+}
+
+/// A call made on a `Recorder`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Call {
+    Rectangle(graphics::Rect),
+    Circle([f32; 2]),
+    Marker(usize, usize, MarkerStyle), // row, column and style
+    Piece(usize, usize),               // row and column
+}
+
+/// A canvas that notes what would have been drawn, for tests and benchmarks.
+#[derive(Default)]
+pub struct Recorder {
+    pub calls: Vec<Call>,
+}
+
+impl Canvas for Recorder {
+    fn rectangle(
+        &mut self,
+        _mode: graphics::DrawMode,
+        rect: graphics::Rect,
+        _colour: graphics::Color,
+    ) -> GameResult {
+        self.calls.push(Call::Rectangle(rect));
+        Ok(())
+    }
+
+    fn circle(&mut self, centre: [f32; 2], _radius: f32, _colour: graphics::Color) -> GameResult {
+        self.calls.push(Call::Circle(centre));
+        Ok(())
+    }
+
+    fn marker(
+        &mut self,
+        style: MarkerStyle,
+        pos: Position,
+        _colour: graphics::Color,
+    ) -> GameResult {
+        self.calls.push(Call::Marker(pos.row, pos.col, style));
+        Ok(())
+    }
+
+    fn piece(
+        &mut self,
+        _piece: Piece,
+        pos: Position,
+        _tint: Option<graphics::Color>,
+    ) -> GameResult {
+        self.calls.push(Call::Piece(pos.row, pos.col));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_template::Game;
+
+    fn start(pieces_hidden: bool, selected: Option<Position>) -> BoardState<'static> {
+        BoardState {
+            board: Game::new().get_board(),
+            pieces_hidden,
+            dots_hidden: false,
+            selected,
+            highlighted: &[],
+            attacked: &[],
+            hanging: &[],
+            dots: &[],
+            captures: &[],
+            markers: MoveMarkers::default(),
+            repetition: None,
+            check_pulse: None,
+            flipped: false,
+            cursor: None,
+            palette: Palette::Wood,
+            heat: None,
+        }
+    }
+
+    fn count(recorder: &Recorder, matches: fn(&Call) -> bool) -> usize {
+        recorder.calls.iter().filter(|call| matches(call)).count()
+    }
+
+    #[test]
+    fn the_start_position_draws_every_tile_and_piece() {
+        let mut recorder = Recorder::default();
+        draw_board(&mut recorder, &squares(&start(false, None))).unwrap();
+
+        assert_eq!(count(&recorder, |c| matches!(c, Call::Rectangle(_))), 64);
+        assert_eq!(count(&recorder, |c| matches!(c, Call::Piece(..))), 32);
+        assert_eq!(count(&recorder, |c| matches!(c, Call::Circle(_))), 0);
+    }
+
+    #[test]
+    fn the_threat_view_washes_attacked_squares_under_their_pieces() {
+        let attacked = crate::board::attacked_squares(&Game::new().get_board(), Colour::Black);
+        let mut state = start(false, None);
+        state.attacked = &attacked;
+        let mut recorder = Recorder::default();
+        draw_board(&mut recorder, &squares(&state)).unwrap();
+
+        let rectangles = count(&recorder, |c| matches!(c, Call::Rectangle(_)));
+        assert_eq!(rectangles, 64 + attacked.len());
+        // the knight on b8 is defended by the rook, and drawn over its wash
+        let knight = recorder
+            .calls
+            .iter()
+            .position(|call| *call == Call::Piece(0, 1))
+            .unwrap();
+        assert!(matches!(recorder.calls[knight - 1], Call::Rectangle(_)));
+        assert!(matches!(recorder.calls[knight - 2], Call::Rectangle(_)));
+    }
+
+    #[test]
+    fn heat_ramps_up_to_the_hottest_square() {
+        assert!(heat_colour(0, 5).is_none() && heat_colour(0, 0).is_none());
+        let hottest = heat_colour(5, 5).unwrap();
+        assert!(hottest == graphics::Color::from(HEAT_HOT));
+        // the ramp is relative, so a game's hottest square is as hot however few moves went there
+        assert!(heat_colour(1, 1).unwrap() == hottest);
+        assert!(heat_colour(9, 5).unwrap() == hottest);
+
+        let ramp: Vec<_> = (1..=5)
+            .map(|count| heat_colour(count, 5).unwrap())
+            .collect();
+        assert!(ramp
+            .windows(2)
+            .all(|pair| pair[0].a < pair[1].a && pair[0].g > pair[1].g));
+        assert!(ramp[0].a > HEAT_COOL[3] && ramp[0].a < 0.3);
+    }
+
+    #[test]
+    fn the_heatmap_washes_tiles_under_their_pieces() {
+        let mut heat = [0; 64];
+        heat[1] = 3; // b8, under the knight
+        heat[36] = 1; // e4, empty
+        let mut state = start(false, None);
+        state.heat = Some(&heat);
+        let squares = squares(&state);
+        assert!(squares[1].heat.is_some() && squares[36].heat.is_some());
+        assert!(squares[36].heat.unwrap().a < squares[1].heat.unwrap().a);
+        assert_eq!(squares.iter().filter(|s| s.heat.is_some()).count(), 2);
+
+        let mut recorder = Recorder::default();
+        draw_board(&mut recorder, &squares).unwrap();
+        let rectangles = count(&recorder, |c| matches!(c, Call::Rectangle(_)));
+        assert_eq!(rectangles, 64 + 2);
+        let knight = recorder
+            .calls
+            .iter()
+            .position(|call| *call == Call::Piece(0, 1))
+            .unwrap();
+        // the tile, then the wash, then the knight
+        assert!(matches!(recorder.calls[knight - 1], Call::Rectangle(_)));
+        assert!(matches!(recorder.calls[knight - 2], Call::Rectangle(_)));
+        assert!(matches!(recorder.calls[knight - 3], Call::Piece(0, 0)));
+    }
+
+    #[test]
+    fn arrows_point_at_the_middle_of_the_square_moved_to() {
+        let (e2, e4) = (Position::new(6, 4).unwrap(), Position::new(4, 4).unwrap());
+        let middle = |pos: Position| {
+            let (x, y) = square_origin(pos);
+            [
+                x + GRID_CELL_SIZE.0 as f32 / 2.0,
+                y + GRID_CELL_SIZE.1 as f32 / 2.0,
+            ]
+        };
+        let up = arrow(e2, e4, false);
+        assert_eq!(up.shaft[0], middle(e2));
+        assert_eq!(up.head[0], middle(e4));
+        // the shaft stops where the head starts, which is as wide either side of it
+        assert_eq!(up.shaft[1], [middle(e4)[0], middle(e4)[1] + ARROW_HEAD]);
+        assert_eq!(up.head[1][1], up.head[2][1]);
+        assert_eq!(
+            up.head[1][0] - up.shaft[1][0],
+            up.shaft[1][0] - up.head[2][0]
+        );
+
+        // turned round, it points down the board from where e2 is drawn
+        let down = arrow(e2, e4, true);
+        assert_eq!(down.shaft[0], middle(turned(e2, true)));
+        assert_eq!(down.head[0], middle(turned(e4, true)));
+        assert!(down.head[0][1] > down.shaft[0][1]);
+    }
+
+    #[test]
+    fn hanging_pieces_get_a_mark_unless_blindfolded() {
+        let hanging = [Position::new(0, 1).unwrap()];
+        let mut state = start(false, None);
+        state.hanging = &hanging;
+        let mut recorder = Recorder::default();
+        draw_board(&mut recorder, &squares(&state)).unwrap();
+
+        // drawn over the piece, in the corner of its square
+        let knight = recorder
+            .calls
+            .iter()
+            .position(|call| *call == Call::Piece(0, 1))
+            .unwrap();
+        let [x, y] = match recorder.calls[knight + 1] {
+            Call::Circle(centre) => centre,
+            ref call => panic!("expected the mark after the piece, got {:?}", call),
+        };
+        let (left, top) = square_origin(hanging[0]);
+        let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+        assert!(x > left + width / 2.0 && x < left + width);
+        assert!(y > top && y < top + height / 2.0);
+        assert_eq!(
+            count(&recorder, |c| matches!(c, Call::Rectangle(_))),
+            64 + 2
+        );
+
+        // it would give away where the piece is
+        state.pieces_hidden = true;
+        assert!(squares(&state).iter().all(|square| !square.hanging));
+    }
+
+    #[test]
+    fn only_the_king_in_check_is_tinted() {
+        // the rook on e1 checks the black king on e8
+        let board = crate::history::parse_placement("4k3/8/8/8/8/8/8/4R1K1").unwrap();
+        let mut state = start(false, None);
+        state.board = board;
+        assert!(squares(&state).iter().all(|square| square.tint.is_none()));
+
+        state.check_pulse = Some(1.0);
+        let tinted: Vec<_> = squares(&state)
+            .into_iter()
+            .filter_map(|square| square.tint.map(|tint| (square.pos, tint)))
+            .collect();
+        assert_eq!(tinted.len(), 1);
+        assert!(tinted[0].0 == Position::new(0, 4).unwrap());
+        assert!(tinted[0].1.r == 1.0 && tinted[0].1.g < 0.5);
+
+        // at the bottom of its pulse the king keeps its own colour
+        assert_eq!(check_tint(0.0), graphics::Color::WHITE);
+        state.board = Game::new().get_board();
+        assert!(squares(&state).iter().all(|square| square.tint.is_none()));
+    }
+
+    #[test]
+    fn tiles_alternate_starting_from_a_white_corner() {
+        let squares = squares(&start(false, None));
+        assert!(squares[0].white && !squares[1].white && !squares[8].white && squares[9].white);
+        assert!(squares[63].white);
+        assert!(squares[0].colour == WHITE && squares[1].colour == BLACK);
+
+        let mut state = start(false, None);
+        state.palette = Palette::HighContrast;
+        let contrast = super::squares(&state);
+        assert!(contrast[0].colour == CONTRAST_WHITE && contrast[1].colour == CONTRAST_BLACK);
+    }
+
+    #[test]
+    fn a_flipped_board_draws_each_square_turned_round() {
+        let mut state = start(false, None);
+        state.flipped = true;
+        let squares = squares(&state);
+        // a8 is first, in the bottom right corner, and still a white square
+        assert!(squares[0].pos == Position::new(7, 7).unwrap() && squares[0].white);
+        assert_eq!(squares[0].piece.unwrap().piece_type, PieceType::Rook);
+
+        // the white king is drawn on d8's square, and Black's pieces along the bottom
+        let king = squares
+            .iter()
+            .find(|square| square.pos == Position::new(0, 3).unwrap());
+        assert_eq!(king.unwrap().piece.unwrap().piece_type, PieceType::King);
+        assert!(squares[..16].iter().all(|square| square.pos.row >= 6));
+    }
+
+    #[test]
+    fn the_cursor_is_outlined_on_top_of_its_square() {
+        let mut state = start(false, Some(Position::new(6, 4).unwrap()));
+        state.cursor = Some(Position::new(6, 4).unwrap());
+        state.flipped = true;
+        let mut recorder = Recorder::default();
+        draw_board(&mut recorder, &squares(&state)).unwrap();
+
+        // inside e2's square, which is where d7's is when flipped
+        let (left, top) = square_origin(Position::new(1, 3).unwrap());
+        let (width, height) = (GRID_CELL_SIZE.0 as f32, GRID_CELL_SIZE.1 as f32);
+        let outline = graphics::Rect::new(left + 5.0, top + 5.0, width - 10.0, height - 10.0);
+        assert!(recorder.calls.contains(&Call::Rectangle(outline)));
+        assert_eq!(
+            count(&recorder, |c| matches!(c, Call::Rectangle(_))),
+            64 + 1
+        );
+    }
+
+    #[test]
+    fn blindfold_hides_pieces_but_outlines_the_selection() {
+        let selected = Position::new(6, 4).unwrap();
+        let squares = squares(&start(true, Some(selected)));
+        assert!(squares.iter().all(|square| square.piece.is_none()));
+        let outlined = squares.iter().filter(|square| square.outlined);
+        assert!(outlined.map(|square| square.pos).eq([selected]));
+    }
+
+    #[test]
+    fn dots_mark_repetitions_and_can_be_hidden() {
+        let (e3, e4) = (Position::new(5, 4).unwrap(), Position::new(4, 4).unwrap());
+        let dots = [e3, e4];
+        let mut state = start(false, Position::new(6, 4).ok());
+        state.dots = &dots;
+        state.repetition = Some(e4);
+
+        let shown = squares(&state);
+        assert_eq!(shown[5 * 8 + 4].dot, Some(Dot::Move));
+        assert_eq!(shown[4 * 8 + 4].dot, Some(Dot::Repetition));
+
+        state.dots_hidden = true;
+        assert!(squares(&state).iter().all(|square| square.dot.is_none()));
+    }
+
+    #[test]
+    fn captures_are_marked_in_their_own_style() {
+        // the knight on f3 can take on e5, or go quietly to g5
+        let board = crate::history::parse_placement("4k3/8/8/4p3/8/5N2/8/4K3").unwrap();
+        let (e5, g5) = (Position::new(3, 4).unwrap(), Position::new(3, 6).unwrap());
+        let dots = [e5, g5];
+        let captures = [e5];
+        let mut state = start(false, Position::new(5, 5).ok());
+        state.board = board;
+        state.dots = &dots;
+        state.captures = &captures;
+        state.markers = MoveMarkers {
+            quiet: MarkerStyle::Corners,
+            capture: MarkerStyle::Ring,
+        };
+        let mut recorder = Recorder::default();
+        draw_board(&mut recorder, &squares(&state)).unwrap();
+
+        let markers: Vec<_> = recorder
+            .calls
+            .iter()
+            .filter(|call| matches!(call, Call::Marker(..)))
+            .collect();
+        assert_eq!(
+            markers,
+            [
+                &Call::Marker(3, 4, MarkerStyle::Ring),
+                &Call::Marker(3, 6, MarkerStyle::Corners),
+            ]
+        );
+        // the ring goes around the pawn it would take
+        let pawn = recorder
+            .calls
+            .iter()
+            .position(|call| *call == Call::Piece(3, 4));
+        let ring = recorder.calls.iter().position(|call| *markers[0] == *call);
+        assert!(pawn < ring);
+    }
+
+    #[test]
+    fn marker_styles_cycle_both_ways() {
+        for style in MARKER_STYLES {
+            assert_eq!(style.next().previous(), style);
+        }
+        assert_eq!(MarkerStyle::Ring.next(), MarkerStyle::Dot);
+        assert_eq!(MarkerStyle::Dot.previous(), MarkerStyle::Ring);
+    }
+
+    #[test]
+    fn coordinates_are_written_on_the_frame_beside_their_squares() {
+        let labels = frame_labels(false);
+        assert_eq!(labels.len(), 16);
+        for (_, [x, y]) in &labels {
+            assert!(crate::board::square_at(*x, *y).is_none());
+            assert!((0.0..BOARD_SIZE.0).contains(x) && (0.0..BOARD_SIZE.1).contains(y));
+        }
+
+        // a is under the first column, and 8 left of the top row
+        let a = labels.iter().find(|(label, _)| label == "a").unwrap().1;
+        let (left, _) = square_origin(Position::new(7, 0).unwrap());
+        assert!(a[0] > left && a[0] < left + GRID_CELL_SIZE.0 as f32);
+        assert!(a[1] > BOARD_ORIGIN.1 + SQUARES_SIZE.1);
+        let eight = labels.iter().find(|(label, _)| label == "8").unwrap().1;
+        assert!(eight[0] < BOARD_ORIGIN.0 && eight[1] < BOARD_ORIGIN.1 + GRID_CELL_SIZE.1 as f32);
+
+        // flipped, h is under the first column and 1 left of the top row
+        let labels = frame_labels(true);
+        let h = labels.iter().find(|(label, _)| label == "h").unwrap().1;
+        assert!(h[0] > left && h[0] < left + GRID_CELL_SIZE.0 as f32);
+        let one = labels.iter().find(|(label, _)| label == "1").unwrap().1;
+        assert!(one[1] < BOARD_ORIGIN.1 + GRID_CELL_SIZE.1 as f32);
+    }
+
+    #[test]
+    fn glyphs_are_centred_in_their_square() {
+        let pos = Position::new(2, 5).unwrap();
+        let scale = GRID_CELL_SIZE.1 as f32 * GLYPH_SCALE;
+        let (width, height) = (60.0, 96.0);
+        let [x, y] = glyph_dest(pos, (width, height), scale);
+
+        let (left, top) = square_origin(pos);
+        let square_centre = [
+            left + GRID_CELL_SIZE.0 as f32 / 2.0,
+            top + GRID_CELL_SIZE.1 as f32 / 2.0,
+        ];
+        assert_eq!(x + width / 2.0, square_centre[0]);
+        assert!(y + height / 2.0 < square_centre[1]);
+        assert!(square_centre[1] - (y + height / 2.0) < 0.1 * scale);
+    }
+
+    #[test]
+    fn every_piece_has_its_own_shape_inside_its_square() {
+        let types = [
+            PieceType::King,
+            PieceType::Queen,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+            PieceType::Pawn,
+        ];
+        let shapes: Vec<_> = types.iter().map(|&piece_type| shape(piece_type)).collect();
+        for (i, parts) in shapes.iter().enumerate() {
+            assert!(shapes[..i].iter().all(|other| other != parts));
+            for part in parts {
+                let inside =
+                    |[x, y]: [f32; 2]| (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y);
+                match part {
+                    Part::Polygon(points) => {
+                        assert!(points.len() >= 3 && points.iter().all(|&p| inside(p)))
+                    }
+                    Part::Circle([x, y], r) => {
+                        assert!(inside([x - r, y - r]) && inside([x + r, y + r]))
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+/**
+ * Piece images, avatars, the font for drawing pieces as glyphs, and the window icon. Copies are built into the binary, so the game runs from any
+ * directory, but a file in `resources/` wins over the built-in copy, so a custom piece set can
+ * be dropped in without rebuilding.
+ */
+use crate::avatar;
+use crate::error::AppError;
+use chess_template::{Colour, PieceType};
+use ggez::winit::window::Icon;
+use ggez::{filesystem, graphics, Context, GameError};
+use log::warn;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Piece images, by colour and type.
+pub type Sprites = HashMap<(Colour, PieceType), graphics::Image>;
+
+/// Avatar images, by the file they're in.
+pub type AvatarImages = HashMap<&'static str, graphics::Image>;
+
+const ICON: &str = "/icon.png";
+
+/// A font with the chess symbols, which ggez's built-in one lacks.
+const GLYPH_FONT: &str = "/DejaVuSans.ttf";
+
+/// Every piece, with the file its image is in.
+#[rustfmt::skip]
+const PIECES: [((Colour, PieceType), &str); 12] = [
+    ((Colour::Black, PieceType::King), "/black_king.png"),
+    ((Colour::Black, PieceType::Queen), "/black_queen.png"),
+    ((Colour::Black, PieceType::Rook), "/black_rook.png"),
+    ((Colour::Black, PieceType::Pawn), "/black_pawn.png"),
+    ((Colour::Black, PieceType::Bishop), "/black_bishop.png"),
+    ((Colour::Black, PieceType::Knight), "/black_knight.png"),
+    ((Colour::White, PieceType::King), "/white_king.png"),
+    ((Colour::White, PieceType::Queen), "/white_queen.png"),
+    ((Colour::White, PieceType::Rook), "/white_rook.png"),
+    ((Colour::White, PieceType::Pawn), "/white_pawn.png"),
+    ((Colour::White, PieceType::Bishop), "/white_bishop.png"),
+    ((Colour::White, PieceType::Knight), "/white_knight.png"),
+];
+
+/// The images and font built into the binary.
+#[rustfmt::skip]
+const EMBEDDED: [(&str, &[u8]); 18] = [
+    ("/black_king.png", include_bytes!("../resources/black_king.png")),
+    ("/black_queen.png", include_bytes!("../resources/black_queen.png")),
+    ("/black_rook.png", include_bytes!("../resources/black_rook.png")),
+    ("/black_pawn.png", include_bytes!("../resources/black_pawn.png")),
+    ("/black_bishop.png", include_bytes!("../resources/black_bishop.png")),
+    ("/black_knight.png", include_bytes!("../resources/black_knight.png")),
+    ("/white_king.png", include_bytes!("../resources/white_king.png")),
+    ("/white_queen.png", include_bytes!("../resources/white_queen.png")),
+    ("/white_rook.png", include_bytes!("../resources/white_rook.png")),
+    ("/white_pawn.png", include_bytes!("../resources/white_pawn.png")),
+    ("/white_bishop.png", include_bytes!("../resources/white_bishop.png")),
+    ("/white_knight.png", include_bytes!("../resources/white_knight.png")),
+    ("/avatars/knight.png", include_bytes!("../resources/avatars/knight.png")),
+    ("/avatars/rook.png", include_bytes!("../resources/avatars/rook.png")),
+    ("/avatars/bishop.png", include_bytes!("../resources/avatars/bishop.png")),
+    ("/avatars/queen.png", include_bytes!("../resources/avatars/queen.png")),
+    (ICON, include_bytes!("../resources/icon.png")),
+    (GLYPH_FONT, include_bytes!("../resources/DejaVuSans.ttf")),
+];
+
+/// The built-in copy of an image.
+fn embedded(path: &str) -> Option<&'static [u8]> {
+    EMBEDDED
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, bytes)| *bytes)
+}
+
+/// Where to try loading an image from, best first: the file on disk, then the built-in copy.
+fn sources(on_disk: Option<Vec<u8>>, embedded: Option<&'static [u8]>) -> Vec<Cow<'static, [u8]>> {
+    on_disk
+        .map(Cow::Owned)
+        .into_iter()
+        .chain(embedded.map(Cow::Borrowed))
+        .collect()
+}
+
+/// Reads a file from `resources/`, if there is one.
+fn read(ctx: &mut Context, path: &str) -> Option<Vec<u8>> {
+    if !filesystem::exists(ctx, path) {
+        return None;
+    }
+
+    let mut bytes = Vec::new();
+    let result = filesystem::open(ctx, path)
+        .and_then(|mut file| file.read_to_end(&mut bytes).map_err(GameError::from));
+    match result {
+        Ok(_) => Some(bytes),
+        Err(err) => {
+            warn!("{}", AppError::MissingResource(path.to_string(), err));
+            None
+        }
+    }
+}
+
+/// The first of the sources that's a valid image, as RGBA pixels with its width and height.
+fn decode(path: &str, sources: Vec<Cow<[u8]>>) -> Option<(Vec<u8>, u32, u32)> {
+    for bytes in sources {
+        match image::load_from_memory(&bytes) {
+            Ok(image) => {
+                let image = image.to_rgba8();
+                let (width, height) = image.dimensions();
+                return Some((image.into_raw(), width, height));
+            }
+            Err(err) => {
+                let err = GameError::ResourceLoadError(err.to_string());
+                warn!("{}", AppError::MissingResource(path.to_string(), err));
+            }
+        }
+    }
+    None
+}
+
+fn load(ctx: &mut Context, path: &str) -> Option<(Vec<u8>, u32, u32)> {
+    let on_disk = read(ctx, path);
+    decode(path, sources(on_disk, embedded(path)))
+}
+
+/// Loads the piece images. A piece whose image can't be loaded is left out, and the board draws
+/// it as a shape instead.
+pub fn load_sprites(ctx: &mut Context) -> Sprites {
+    let mut sprites = Sprites::new();
+    let mut missing = Vec::new();
+    for (piece, path) in PIECES {
+        let (rgba, width, height) = match load(ctx, path) {
+            Some(image) => image,
+            None => {
+                missing.push(path);
+                continue;
+            }
+        };
+        match graphics::Image::from_rgba8(ctx, width as u16, height as u16, &rgba) {
+            Ok(image) => {
+                sprites.insert(piece, image);
+            }
+            Err(err) => {
+                warn!("{}", AppError::MissingResource(path.to_string(), err));
+                missing.push(path);
+            }
+        }
+    }
+    if !missing.is_empty() {
+        warn!(
+            "Drawing pieces as shapes, as these images are missing: {}",
+            missing.join(", ")
+        );
+    }
+    sprites
+}
+
+/// Loads the avatar images. An avatar whose image can't be loaded is drawn as the default.
+pub fn load_avatars(ctx: &mut Context) -> AvatarImages {
+    let mut images = AvatarImages::new();
+    for path in avatar::image_paths() {
+        let (rgba, width, height) = match load(ctx, path) {
+            Some(image) => image,
+            None => continue,
+        };
+        match graphics::Image::from_rgba8(ctx, width as u16, height as u16, &rgba) {
+            Ok(image) => {
+                images.insert(path, image);
+            }
+            Err(err) => warn!("{}", AppError::MissingResource(path.to_string(), err)),
+        }
+    }
+    images
+}
+
+/// Loads the font the glyph piece theme is drawn in. Without it the pieces are drawn as shapes.
+pub fn load_glyph_font(ctx: &mut Context) -> Option<graphics::Font> {
+    let on_disk = read(ctx, GLYPH_FONT);
+    for bytes in sources(on_disk, embedded(GLYPH_FONT)) {
+        match graphics::Font::new_glyph_font_bytes(ctx, &bytes) {
+            Ok(font) => return Some(font),
+            Err(err) => warn!("{}", AppError::MissingResource(GLYPH_FONT.to_string(), err)),
+        }
+    }
+    None
+}
+
+/// Sets the window icon. Without one the window just gets the platform's default icon.
+pub fn set_icon(ctx: &mut Context) {
+    let (rgba, width, height) = match load(ctx, ICON) {
+        Some(image) => image,
+        None => return,
+    };
+    match Icon::from_rgba(rgba, width, height) {
+        Ok(icon) => graphics::window(ctx).set_window_icon(Some(icon)),
+        Err(err) => warn!("Couldn't use {} as the window icon: {}", ICON, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_piece_is_built_in() {
+        for (_, path) in PIECES {
+            assert!(embedded(path).is_some(), "{} isn't built in", path);
+        }
+        for path in avatar::image_paths() {
+            assert!(embedded(path).is_some(), "{} isn't built in", path);
+        }
+        assert!(embedded(ICON).is_some());
+        assert!(embedded("/purple_king.png").is_none());
+    }
+
+    #[test]
+    fn files_on_disk_come_before_built_in_copies() {
+        let built_in: &'static [u8] = b"built in";
+        let found = sources(Some(b"on disk".to_vec()), Some(built_in));
+        assert_eq!(found, vec![Cow::from(&b"on disk"[..]), Cow::from(built_in)]);
+
+        assert_eq!(sources(None, Some(built_in)), vec![Cow::from(built_in)]);
+        assert!(sources(None, None).is_empty());
+    }
+
+    #[test]
+    fn broken_files_fall_back_to_built_in_copies() {
+        let path = "/white_king.png";
+        let garbage = Some(b"not an image".to_vec());
+        assert!(decode(path, sources(garbage.clone(), embedded(path))).is_some());
+        assert!(decode(path, sources(garbage, None)).is_none());
+    }
+}
@@ -0,0 +1,956 @@
+/**
+ * The screens the window can show, kept on a stack, and the event handler that passes events on
+ * to the one on top.
+ */
+use crate::app::AppState;
+use crate::avatar::{Avatar, Look};
+use crate::chaos;
+use crate::cli::Args;
+use crate::config::Config;
+use crate::connect::ConnectScreen;
+use crate::correspondence::{self, Correspondence};
+use crate::crash::{ResumeOffer, SavedGame};
+use crate::dialog::{DialogAction, ErrorDialog, Retry};
+use crate::editor::BoardEditor;
+use crate::layout::{self, Layout};
+use crate::matchlist::{self, MatchList};
+use crate::menu::{Menu, MenuItem};
+use crate::resources::{self, AvatarImages, Sprites};
+use crate::session::{self, Connection, Session, Setup};
+use crate::settings::SettingsScreen;
+use crate::tabs::Tabs;
+use crate::transcript;
+use crate::waiting::WaitingScreen;
+use crate::window;
+use chess_template::{Colour, PieceType};
+use ggez::event::winit_event::TouchPhase;
+use ggez::event::{self, Axis, Button, EventHandler, GamepadId, KeyCode, KeyMods, MouseButton};
+use ggez::{graphics, Context, GameError, GameResult};
+use rand::rngs::StdRng;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long after a touch mouse events are taken to be ones the system made up from it, as
+/// Windows does for programs that don't handle touch, and ignored.
+const SYNTHETIC_MOUSE: Duration = Duration::from_millis(500);
+
+/// Off the window, where the pointer is once a finger is lifted, so nothing stays hovered.
+pub const NOWHERE: (f32, f32) = (-1.0, -1.0);
+
+/// Window title, with the player's name if they gave one.
+pub fn window_title(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("Schack — {}", name),
+        None => "Schack".to_string(),
+    }
+}
+
+/// What the last pointer input came from. There's no hovering with a finger, so hover-only
+/// things aren't shown for touch, and buttons take taps a little outside them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pointer {
+    Mouse,
+    Touch,
+}
+
+/// Resources every scene can use, kept for as long as the window is open.
+pub struct Shared {
+    pub args: Args,           // command-line arguments, to fill in the connection screen
+    pub config: Config,       // settings kept between runs, saved by whichever scene changes them
+    sprites: Option<Sprites>, // piece images, loaded when the first game starts
+    avatars: Option<AvatarImages>, // loaded with the piece images
+    glyph_font: Option<graphics::Font>, // for the glyph piece theme, loaded with the images
+    pub rng: StdRng,          // every random choice, seeded from --seed if given
+    pub opening_tab: bool, // the screens on top of the games are setting up another one for a tab
+    pub new_tab: Option<AppState>, // that game, once it's ready, until the tabs take it
+    pub pointer: Pointer,  // whether the mouse or a finger was used last
+}
+
+impl Shared {
+    pub fn new(args: Args, config: Config) -> Shared {
+        let rng = session::seeded_rng(args.seed);
+        Shared {
+            args,
+            config,
+            sprites: None,
+            avatars: None,
+            glyph_font: None,
+            rng,
+            opening_tab: false,
+            new_tab: None,
+            pointer: Pointer::Mouse,
+        }
+    }
+
+    /// Loads the piece images, the avatars and the glyph font, unless they already are.
+    pub fn load_pieces(&mut self, ctx: &mut Context) {
+        if self.sprites.is_none() {
+            self.sprites = Some(resources::load_sprites(ctx));
+            self.avatars = Some(resources::load_avatars(ctx));
+            self.glyph_font = resources::load_glyph_font(ctx);
+        }
+    }
+
+    /// The image for a piece, once the images are loaded. Pieces without one are drawn as
+    /// shapes.
+    pub fn sprite(&self, colour: Colour, piece_type: PieceType) -> Option<&graphics::Image> {
+        self.sprites.as_ref()?.get(&(colour, piece_type))
+    }
+
+    /// The image for an avatar drawn as one, once the images are loaded.
+    pub fn avatar_image(&self, avatar: &Avatar) -> Option<&graphics::Image> {
+        match avatar.look {
+            Look::Image(path) => self.avatars.as_ref()?.get(path),
+            Look::Swatch(_) => None,
+        }
+    }
+
+    /// The font for the glyph piece theme, once it's loaded.
+    pub fn glyph_font(&self) -> Option<graphics::Font> {
+        self.glyph_font
+    }
+
+    /// The player's name, from the command line or the config file.
+    pub fn name(&self) -> Option<String> {
+        self.args.name.clone().or(self.config.name.clone())
+    }
+}
+
+/// What the stack should do once a scene has updated.
+pub enum Transition {
+    /// Stay on this scene.
+    None,
+    /// Show another scene on top of this one, coming back here when it's popped.
+    Push(Box<dyn Scene>),
+    /// Close this scene, and any above it, going back to the one underneath.
+    Pop,
+    /// Close this scene, and any above it, showing another in its place.
+    Replace(Box<dyn Scene>),
+    /// Close the window.
+    Quit,
+}
+
+/// A screen the window can show, like the main menu or a game.
+///
+/// Every scene on the stack is updated, so a game keeps receiving moves while something is shown
+/// over it, but only the top one is drawn and gets input.
+pub trait Scene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition>;
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult;
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, _x: f32, _y: f32) {}
+
+    fn mouse_button_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        _button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) {
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        _button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) {
+    }
+
+    /// The mouse wheel turned by `x` and `y` notches, up being positive. Where the mouse is
+    /// comes with the last `mouse_motion`.
+    fn mouse_wheel(&mut self, _ctx: &mut Context, _shared: &mut Shared, _x: f32, _y: f32) {}
+
+    fn key_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        _key: KeyCode,
+        _mods: KeyMods,
+    ) {
+    }
+
+    /// A key was let go, for the few things shown only while a key is held.
+    fn key_up(&mut self, _ctx: &mut Context, _shared: &mut Shared, _key: KeyCode) {}
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, _character: char) {}
+
+    /// A finger touched, moved on or left the screen. By default it's taken as the left mouse
+    /// button, see `touch_as_mouse`.
+    fn touch(&mut self, ctx: &mut Context, shared: &mut Shared, phase: TouchPhase, x: f32, y: f32) {
+        touch_as_mouse(self, ctx, shared, phase, x, y);
+    }
+
+    /// A button was pressed on a gamepad, any of them, including one plugged in since the game
+    /// started.
+    fn gamepad_button(&mut self, _ctx: &mut Context, _shared: &mut Shared, _button: Button) {}
+
+    /// A gamepad stick or d-pad axis moved to `value`, from -1 to 1, right and up being positive.
+    fn gamepad_axis(&mut self, _ctx: &mut Context, _shared: &mut Shared, _axis: Axis, _value: f32) {
+    }
+
+    /// The window's close button was pressed. Returns true to keep the window open, e.g. to
+    /// ask first.
+    fn quit_requested(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> bool {
+        false
+    }
+
+    /// The window is closing, however it was closed.
+    fn shut_down(&mut self, _shared: &mut Shared) {}
+
+    /// What's fitted into a window of `window` size.
+    fn layout(&self, _shared: &Shared, _window: (f32, f32)) -> Layout {
+        Layout::Standard
+    }
+}
+
+/// A touch as the left mouse button: pressed where the finger goes down and let go where it
+/// comes up, then moved off the window, since a finger doesn't hover once it's lifted. A touch
+/// the system cancels is let go off the window, so it clicks nothing.
+pub fn touch_as_mouse<S: Scene + ?Sized>(
+    scene: &mut S,
+    ctx: &mut Context,
+    shared: &mut Shared,
+    phase: TouchPhase,
+    x: f32,
+    y: f32,
+) {
+    match phase {
+        TouchPhase::Started => {
+            scene.mouse_motion(ctx, shared, x, y);
+            scene.mouse_button_down(ctx, shared, MouseButton::Left, x, y);
+        }
+        TouchPhase::Moved => scene.mouse_motion(ctx, shared, x, y),
+        TouchPhase::Ended => {
+            scene.mouse_motion(ctx, shared, x, y);
+            scene.mouse_button_up(ctx, shared, MouseButton::Left, x, y);
+            scene.mouse_motion(ctx, shared, NOWHERE.0, NOWHERE.1);
+        }
+        TouchPhase::Cancelled => {
+            scene.mouse_button_up(ctx, shared, MouseButton::Left, NOWHERE.0, NOWHERE.1);
+            scene.mouse_motion(ctx, shared, NOWHERE.0, NOWHERE.1);
+        }
+    }
+}
+
+/// The scenes open in the window, bottom first, and the resources they share.
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    shared: Shared,
+    last_touch: Option<Instant>, // for telling made up mouse events from the mouse's
+    style: window::Style,        // how the settings last framed the window
+}
+
+impl SceneStack {
+    pub fn new(scene: Box<dyn Scene>, shared: Shared) -> SceneStack {
+        SceneStack {
+            scenes: vec![scene],
+            style: window::Style::from_config(&shared.config),
+            shared,
+            last_touch: None,
+        }
+    }
+
+    /// Whether a mouse event comes from the mouse, rather than being made up from a touch. If it
+    /// does, the mouse is the pointer from now on.
+    fn takes_mouse_event(&mut self) -> bool {
+        if self
+            .last_touch
+            .is_some_and(|at| at.elapsed() < SYNTHETIC_MOUSE)
+        {
+            return false;
+        }
+        self.shared.pointer = Pointer::Mouse;
+        true
+    }
+
+    /// The screen coordinates the scene on top is drawn in, fitting its layout into the window,
+    /// and that layout.
+    fn fitted(&self, ctx: &Context) -> Option<(Layout, graphics::Rect)> {
+        let scene = self.scenes.last()?;
+        let window = graphics::drawable_size(ctx);
+        let layout = scene.layout(&self.shared, window);
+        Some((layout, layout.fit(window)))
+    }
+
+    /// A point in the window in the coordinates of the scene on top. Where part of the screen is
+    /// left out, a point beside what's shown is nowhere, so nothing left out can be clicked.
+    fn to_scene(&self, ctx: &Context, x: f32, y: f32) -> (f32, f32) {
+        let (layout, screen) = match self.fitted(ctx) {
+            Some(fitted) => fitted,
+            None => return (x, y),
+        };
+        let (x, y) = layout::to_screen(screen, graphics::drawable_size(ctx), x, y);
+        if layout != Layout::Standard && !layout.area().contains([x, y]) {
+            NOWHERE
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Saves where the window is and how big, so it opens that way next time. A position given
+    /// on the command line is only for this run, so it isn't saved over the one from the config
+    /// file, and a kiosk window is wherever its monitor is, as big as it is.
+    fn remember_window(&mut self, ctx: &Context) {
+        if self.shared.args.kiosk {
+            return;
+        }
+        let mut config = self.shared.config.clone();
+        if self.shared.args.window_pos.is_none() {
+            config.window_pos = window::position(ctx).or(config.window_pos);
+        }
+        config.window_size = Some(window::size(ctx));
+        if config != self.shared.config {
+            self.shared.config = config;
+            self.shared.config.save();
+        }
+    }
+
+    /// Lets every scene finish up, top first, before the window closes.
+    fn shut_down(&mut self, ctx: &mut Context) {
+        for scene in self.scenes.iter_mut().rev() {
+            scene.shut_down(&mut self.shared);
+        }
+        self.remember_window(ctx);
+    }
+
+    fn quit(&mut self, ctx: &mut Context) {
+        self.shut_down(ctx);
+        event::quit(ctx);
+    }
+}
+
+impl EventHandler<GameError> for SceneStack {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        let mut i = 0;
+        while i < self.scenes.len() {
+            match self.scenes[i].update(ctx, &mut self.shared)? {
+                Transition::None => i += 1,
+                Transition::Push(scene) => {
+                    self.scenes.push(scene);
+                    i += 1;
+                }
+                Transition::Pop => self.scenes.truncate(i),
+                Transition::Replace(scene) => {
+                    self.scenes.truncate(i);
+                    self.scenes.push(scene);
+                    i += 1;
+                }
+                Transition::Quit => {
+                    self.quit(ctx);
+                    return Ok(());
+                }
+            }
+        }
+
+        // popping the last scene closes the window, there's nothing left to show
+        if self.scenes.is_empty() {
+            self.quit(ctx);
+            return Ok(());
+        }
+
+        // the window's frame changes as soon as it's changed in the settings
+        let style = window::Style::from_config(&self.shared.config);
+        if style != self.style {
+            self.style = style;
+            window::set_style(ctx, style);
+        }
+        Ok(())
+    }
+
+    /// The window's close button goes through the same shutdown as quitting from a menu,
+    /// unless a scene wants to ask first.
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        let shared = &mut self.shared;
+        let ask = self
+            .scenes
+            .iter_mut()
+            .rev()
+            .any(|scene| scene.quit_requested(ctx, shared));
+        if !ask {
+            self.shut_down(ctx);
+        }
+        ask
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some((_, screen)) = self.fitted(ctx) {
+            graphics::set_screen_coordinates(ctx, screen)?;
+        }
+        match self.scenes.last_mut() {
+            Some(scene) => scene.draw(ctx, &self.shared),
+            None => Ok(()),
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if !self.takes_mouse_event() {
+            return;
+        }
+        let (x, y) = self.to_scene(ctx, x, y);
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_motion(ctx, &mut self.shared, x, y);
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if !self.takes_mouse_event() {
+            return;
+        }
+        let (x, y) = self.to_scene(ctx, x, y);
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_button_down(ctx, &mut self.shared, button, x, y);
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if !self.takes_mouse_event() {
+            return;
+        }
+        let (x, y) = self.to_scene(ctx, x, y);
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_button_up(ctx, &mut self.shared, button, x, y);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        if !self.takes_mouse_event() {
+            return;
+        }
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.mouse_wheel(ctx, &mut self.shared, x, y);
+        }
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, key: KeyCode, mods: KeyMods, _: bool) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.key_down(ctx, &mut self.shared, key, mods);
+        }
+    }
+
+    fn key_up_event(&mut self, ctx: &mut Context, key: KeyCode, _mods: KeyMods) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.key_up(ctx, &mut self.shared, key);
+        }
+    }
+
+    fn text_input_event(&mut self, ctx: &mut Context, character: char) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.text_input(ctx, &mut self.shared, character);
+        }
+    }
+
+    fn touch_event(&mut self, ctx: &mut Context, phase: TouchPhase, x: f64, y: f64) {
+        self.shared.pointer = Pointer::Touch;
+        self.last_touch = Some(Instant::now());
+        let (x, y) = self.to_scene(ctx, x as f32, y as f32);
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.touch(ctx, &mut self.shared, phase, x, y);
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, ctx: &mut Context, button: Button, _id: GamepadId) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.gamepad_button(ctx, &mut self.shared, button);
+        }
+    }
+
+    fn gamepad_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: f32, _id: GamepadId) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.gamepad_axis(ctx, &mut self.shared, axis, value);
+        }
+    }
+}
+
+/// The connection screen, filled in from the command line or config file.
+pub fn connect_screen(shared: &mut Shared) -> ConnectScreen {
+    ConnectScreen::new(
+        shared.args.server.clone().or(shared.config.server()),
+        shared.args.room.clone().or(shared.config.room()),
+        shared.name(),
+        shared.config.clone(),
+        session::split_rng(&mut shared.rng),
+    )
+}
+
+/// The game if it's ready, or the waiting screen if there's an opponent to wait for first. A
+/// correspondence game kept for the room is picked up again without waiting.
+pub fn setup_scene(ctx: &mut Context, shared: &mut Shared, setup: Setup) -> Box<dyn Scene> {
+    match resume_correspondence(setup) {
+        Setup::Ready(session) => game_scene(ctx, shared, session),
+        Setup::Waiting(connection) => Box::new(waiting_screen(shared, connection)),
+    }
+}
+
+/// The correspondence game kept for the room being joined, if there's one, ready to play.
+fn resume_correspondence(setup: Setup) -> Setup {
+    let connection = match setup {
+        Setup::Waiting(connection) => connection,
+        ready => return ready,
+    };
+    let kept = correspondence::default_path(&connection.server, &connection.room_name)
+        .and_then(|path| Correspondence::load(&path));
+    match kept {
+        Some(game) => Setup::Ready(connection.into_correspondence(game)),
+        None => Setup::Waiting(connection),
+    }
+}
+
+/// Waits in the room, where a correspondence game can be started too.
+fn waiting_screen(shared: &mut Shared, connection: Connection) -> WaitingScreen {
+    let path = correspondence::default_path(&connection.server, &connection.room_name);
+    WaitingScreen::new(connection, shared.config.avatar, path, &mut shared.rng)
+}
+
+/// A new game for the session, with its messages disturbed and recorded if the command line
+/// asked. Recording happens on the GUI's side of the chaos, so the transcript has what it saw.
+/// Online, the server, room and name are saved for filling in the connection screen next time.
+pub fn game_scene(ctx: &mut Context, shared: &mut Shared, session: Session) -> Box<dyn Scene> {
+    Box::new(Tabs::new(new_game(ctx, shared, session)))
+}
+
+/// Shows a game that's ready, in place of the screen that set it up, or in a new tab if that
+/// screen was opened from the games for another one.
+pub fn start_game(ctx: &mut Context, shared: &mut Shared, session: Session) -> Transition {
+    if !shared.opening_tab {
+        return Transition::Replace(game_scene(ctx, shared, session));
+    }
+    shared.opening_tab = false;
+    shared.new_tab = Some(new_game(ctx, shared, session));
+    Transition::Pop
+}
+
+/// The same for a game that may still have to wait for the opponent.
+fn start(ctx: &mut Context, shared: &mut Shared, setup: Setup) -> Transition {
+    match resume_correspondence(setup) {
+        Setup::Ready(session) => start_game(ctx, shared, session),
+        Setup::Waiting(connection) => {
+            Transition::Replace(Box::new(waiting_screen(shared, connection)))
+        }
+    }
+}
+
+/// Where backing out of a screen goes: back to the games if it was opened from them, otherwise
+/// to the main menu.
+fn back_out(shared: &mut Shared) -> Transition {
+    if shared.opening_tab {
+        shared.opening_tab = false;
+        return Transition::Pop;
+    }
+    Transition::Replace(Box::new(Menu::new()))
+}
+
+/// A game on this computer playing on from `saved`, e.g. after a crash, both colours played here.
+pub fn resumed_scene(ctx: &mut Context, shared: &mut Shared, saved: &SavedGame) -> Box<dyn Scene> {
+    let mut state = new_game(ctx, shared, session::offline_session());
+    state.resume(saved);
+    Box::new(Tabs::new(state))
+}
+
+/// The game itself, as `game_scene` sets it up, for a caller with more to do before showing it.
+pub fn new_game(ctx: &mut Context, shared: &mut Shared, session: Session) -> AppState {
+    if let Some(server) = &session.server {
+        let name = shared.name();
+        shared.config.remember(
+            server,
+            &session.room_name,
+            name.as_deref(),
+            SystemTime::now(),
+        );
+        shared.config.save();
+    }
+    let session = match shared.args.net_chaos {
+        Some(settings) => chaos::wrap(session, settings, &mut shared.rng),
+        None => session,
+    };
+    let session = match &shared.args.record {
+        Some(path) => transcript::record(session, path),
+        None => session,
+    };
+    shared.load_pieces(ctx);
+    let mut state = AppState::new(session, SystemTime::now(), &shared.config);
+    if let Some(path) = &shared.args.movelog {
+        state.log_moves_to(path);
+    }
+    state
+}
+
+impl Scene for Menu {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        let transition = match self.take_choice() {
+            Some(MenuItem::PlayOnline) => Transition::Replace(Box::new(connect_screen(shared))),
+            Some(MenuItem::PlayLocal) => {
+                Transition::Replace(game_scene(ctx, shared, session::offline_session()))
+            }
+            Some(MenuItem::PlayComputer) => {
+                let session = session::configured_ai_session(&shared.config, &mut shared.rng);
+                Transition::Replace(game_scene(ctx, shared, session))
+            }
+            Some(MenuItem::SetUpPosition) => {
+                shared.load_pieces(ctx);
+                Transition::Replace(Box::new(BoardEditor::new()))
+            }
+            Some(MenuItem::ReplayPgn) => {
+                self.notify("Replaying PGN files isn't supported yet");
+                Transition::None
+            }
+            Some(MenuItem::History) => Transition::Replace(Box::new(MatchList::load())),
+            Some(MenuItem::Settings) => {
+                Transition::Push(Box::new(SettingsScreen::new(shared.config.clone(), false)))
+            }
+            Some(MenuItem::Quit) => Transition::Quit,
+            None => Transition::None,
+        };
+        Ok(transition)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        Menu::draw(self, ctx)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn mouse_button_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_down_event(button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        self.key_down_event(key, mods);
+    }
+}
+
+impl Scene for BoardEditor {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> GameResult<Transition> {
+        if self.leaving() {
+            return Ok(Transition::Replace(Box::new(Menu::new())));
+        }
+        BoardEditor::update(self);
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult {
+        BoardEditor::draw(self, ctx, shared)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_down_event(button, x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        self.key_down_event(key, mods);
+    }
+}
+
+impl Scene for ConnectScreen {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        if self.leaving() {
+            return Ok(back_out(shared));
+        }
+        let transition = match ConnectScreen::update(self) {
+            Some(setup) => {
+                graphics::set_window_title(ctx, &window_title(self.name()));
+                if let Some(name) = self.name() {
+                    shared.config.name = Some(name.to_string());
+                }
+                start(ctx, shared, setup)
+            }
+            None => Transition::None,
+        };
+        Ok(transition)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        ConnectScreen::draw(self, ctx)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        self.key_down_event(key, mods);
+    }
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) {
+        self.text_input_event(character);
+    }
+}
+
+impl Scene for MatchList {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        if self.leaving() {
+            return Ok(Transition::Replace(Box::new(Menu::new())));
+        }
+        let transition = match self.take_choice() {
+            Some(game) => {
+                Transition::Replace(game_scene(ctx, shared, matchlist::replay_session(&game)))
+            }
+            None => Transition::None,
+        };
+        Ok(transition)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        MatchList::draw(self, ctx)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_down(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_down_event(button, x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn mouse_wheel(&mut self, _ctx: &mut Context, _shared: &mut Shared, _x: f32, y: f32) {
+        self.mouse_wheel_event(y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        self.key_down_event(key, mods);
+    }
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) {
+        self.text_input_event(character);
+    }
+}
+
+impl Scene for SettingsScreen {
+    fn update(&mut self, _ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        if self.leaving() {
+            shared.config = self.config().clone();
+            return Ok(Transition::Pop);
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        SettingsScreen::draw(self, ctx)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        self.key_down_event(key, mods);
+    }
+}
+
+impl Scene for WaitingScreen {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        // back to the connection screen, filled in to try another room
+        if self.leaving() {
+            let screen = ConnectScreen::new(
+                Some(self.server().to_string()),
+                Some(self.room().to_string()),
+                shared.name(),
+                shared.config.clone(),
+                session::split_rng(&mut shared.rng),
+            );
+            return Ok(Transition::Replace(Box::new(screen)));
+        }
+
+        let transition = match WaitingScreen::update(self) {
+            Ok(Some(session)) => start_game(ctx, shared, session),
+            Ok(None) => Transition::None,
+            Err(err) => {
+                let retry = Retry {
+                    server: self.server().to_string(),
+                    room: self.room().to_string(),
+                };
+                Transition::Replace(Box::new(ErrorDialog::new(err, Some(retry))))
+            }
+        };
+        Ok(transition)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        WaitingScreen::draw(self, ctx)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, _mods: KeyMods) {
+        self.key_down_event(key);
+    }
+}
+
+impl Scene for ResumeOffer {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        let transition = match self.take_answer() {
+            // the opponent is long gone, so the game goes on with both colours played here
+            Some(Some(saved)) => Transition::Replace(resumed_scene(ctx, shared, &saved)),
+            Some(None) => Transition::Replace(Box::new(Menu::new())),
+            None => Transition::None,
+        };
+        Ok(transition)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        ResumeOffer::draw(self, ctx)
+    }
+
+    fn mouse_motion(&mut self, _ctx: &mut Context, _shared: &mut Shared, x: f32, y: f32) {
+        self.mouse_motion_event(x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        _ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        let action = shared.config.keys.action(key, mods);
+        self.key_down_event(key, action);
+    }
+}
+
+impl Scene for ErrorDialog {
+    fn update(&mut self, _ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        let transition = match self.take_choice() {
+            // connect to the same room again, showing progress on the connection screen
+            Some(DialogAction::Retry) => match self.retry().cloned() {
+                Some(retry) => {
+                    let mut screen = ConnectScreen::new(
+                        Some(retry.server),
+                        Some(retry.room),
+                        shared.name(),
+                        shared.config.clone(),
+                        session::split_rng(&mut shared.rng),
+                    );
+                    screen.connect();
+                    Transition::Replace(Box::new(screen))
+                }
+                None => Transition::None,
+            },
+            Some(DialogAction::Menu) => back_out(shared),
+            Some(DialogAction::Quit) => Transition::Quit,
+            None => Transition::None,
+        };
+        Ok(transition)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _shared: &Shared) -> GameResult {
+        ErrorDialog::draw(self, ctx)
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        ctx: &mut Context,
+        _shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        self.mouse_button_up_event(ctx, button, x, y);
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, key: KeyCode, _mods: KeyMods) {
+        self.key_down_event(key);
+    }
+}
@@ -0,0 +1,189 @@
+/**
+ * Setting up the game to play: who the opponent is, and the channels to talk to them through.
+ */
+use crate::ai::{self, Ai};
+use crate::bot::{self, RandomMover};
+use crate::config::Config;
+use crate::correspondence::Correspondence;
+use crate::matches::Match;
+use crate::net::{offline_setup, online_setup, Paired};
+use chess_template::Colour;
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::time::Duration;
+
+/// Everything the GUI needs to know about the game it's joining.
+pub struct Session {
+    pub sender: SyncSender<String>, // for sending messages to server
+    pub receiver: Receiver<String>, // for messages from the network thread
+    pub room_name: String,
+    pub color: Colour, // colour we play, or the colour to start with when hotseat
+    pub difficulty: Option<u8>, // strength of the computer opponent, when playing one
+    pub hotseat: bool, // both colours are played on this computer
+    pub server: Option<String>, // address of the server, when playing online
+    pub opponent: Option<String>, // who's played, for the match history; none for games not kept
+    pub opponent_avatar: Option<u8>, // the avatar the opponent picked online, if they did
+    pub correspondence: Option<Correspondence>, // the saved game, when playing by correspondence
+    pub replay: bool,  // a finished game played back, not one being played
+    pub recorded: Option<Box<Match>>, // the game from the match history, when that's what's played back
+}
+
+/// Both colours played on this computer, with moves echoed back like the server would.
+pub fn offline_session() -> Session {
+    let (sender, receiver) = offline_setup();
+    Session {
+        sender,
+        receiver,
+        room_name: "offline".to_string(),
+        color: Colour::White,
+        difficulty: None,
+        hotseat: true,
+        server: None,
+        opponent: None,
+        opponent_avatar: None,
+        correspondence: None,
+        replay: false,
+        recorded: None,
+    }
+}
+
+/// The source of every random choice made while setting up and playing games, from `--seed` if
+/// given. Otherwise a seed is picked and logged, so a run can be repeated.
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    let seed = seed.unwrap_or_else(rand::random);
+    info!("Random seed {}", seed);
+    StdRng::seed_from_u64(seed)
+}
+
+/// A new generator seeded from `rng`, for a thread or bot to own. It gives the same numbers for
+/// the same seed, however many are drawn from `rng` afterwards.
+pub fn split_rng(rng: &mut StdRng) -> StdRng {
+    StdRng::seed_from_u64(rng.gen())
+}
+
+/// A random colour against a bot on this computer that plays random legal moves.
+pub fn random_mover_session(rng: &mut StdRng) -> Session {
+    let color = random_colour(rng);
+    let mover = RandomMover::new(split_rng(rng));
+    let (sender, receiver) = bot::bot_setup("bot", opposite(color), mover);
+    Session {
+        sender,
+        receiver,
+        room_name: "bot".to_string(),
+        color,
+        difficulty: None,
+        hotseat: false,
+        server: None,
+        opponent: Some("Random mover".to_string()),
+        opponent_avatar: None,
+        correspondence: None,
+        replay: false,
+        recorded: None,
+    }
+}
+
+/// A random colour against the computer, at the given difficulty and time limit per move.
+pub fn ai_session(difficulty: u8, time_limit: Option<Duration>, rng: &mut StdRng) -> Session {
+    let color = random_colour(rng);
+    let ai = Ai::new(difficulty, time_limit);
+    let (sender, receiver) = bot::bot_setup("ai", opposite(color), ai);
+    Session {
+        sender,
+        receiver,
+        room_name: "ai".to_string(),
+        color,
+        difficulty: Some(difficulty),
+        hotseat: false,
+        server: None,
+        opponent: Some(format!("Computer, level {}", difficulty)),
+        opponent_avatar: None,
+        correspondence: None,
+        replay: false,
+        recorded: None,
+    }
+}
+
+/// A game against the computer at the difficulty and time limit from the config file, or the
+/// defaults for anything it leaves out.
+pub fn configured_ai_session(config: &Config, rng: &mut StdRng) -> Session {
+    let difficulty = config.ai_difficulty.unwrap_or(ai::DEFAULT_DIFFICULTY);
+    let time_limit = config.ai_time_limit.unwrap_or(ai::DEFAULT_TIME_LIMIT);
+    ai_session(difficulty, ai::time_limit(time_limit), rng)
+}
+
+/// A connection to the server, before an opponent has joined the room.
+pub struct Connection {
+    pub sender: SyncSender<String>,
+    pub receiver: Receiver<String>,
+    pub server: String, // address of the server
+    pub room_name: String,
+}
+
+impl Connection {
+    /// The game, once the handshake has said which colour we play. The protocol doesn't say who
+    /// joined, so the opponent goes by the room's name.
+    pub fn into_session(self, paired: Paired) -> Session {
+        Session {
+            sender: self.sender,
+            receiver: self.receiver,
+            opponent: Some(self.room_name.clone()),
+            opponent_avatar: paired.avatar,
+            correspondence: None,
+            replay: false,
+            recorded: None,
+            room_name: self.room_name,
+            color: paired.colour,
+            difficulty: None,
+            hotseat: false,
+            server: Some(self.server),
+        }
+    }
+
+    /// A correspondence game, started now or earlier, played without waiting for the opponent.
+    pub fn into_correspondence(self, game: Correspondence) -> Session {
+        let paired = Paired {
+            colour: game.colour,
+            avatar: None,
+            correspondence: true,
+        };
+        Session {
+            correspondence: Some(game),
+            ..self.into_session(paired)
+        }
+    }
+}
+
+/// How far setting up a game has got: ready to play, or connected and waiting for an opponent.
+pub enum Setup {
+    Ready(Session),
+    Waiting(Connection),
+}
+
+/// Connects to the server, for meeting an opponent in the room. Blocks until connected, but not
+/// while waiting for the opponent; that's up to the waiting screen.
+pub fn connect(server_addr: &str, room_name: &str) -> Result<Connection, String> {
+    let (sender, receiver) = online_setup(server_addr)?;
+    Ok(Connection {
+        sender,
+        receiver,
+        server: server_addr.to_string(),
+        room_name: room_name.to_string(),
+    })
+}
+
+fn random_colour(rng: &mut StdRng) -> Colour {
+    if rng.gen() {
+        Colour::White
+    } else {
+        Colour::Black
+    }
+}
+
+fn opposite(colour: Colour) -> Colour {
+    match colour {
+        Colour::White => Colour::Black,
+        Colour::Black => Colour::White,
+    }
+}
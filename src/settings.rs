@@ -0,0 +1,759 @@
+/**
+ * The settings screen: every setting in the config file that can be changed from the GUI, with
+ * its current value. Changes apply right away and are saved to the config file.
+ */
+use crate::ai::{self, MAX_DIFFICULTY, MIN_DIFFICULTY};
+use crate::app::SCREEN_SIZE;
+use crate::avatar::{self, AVATARS};
+use crate::config::Config;
+use crate::keys::{KeyChord, Keymap, ACTIONS};
+use ggez::{event, graphics, Context, GameResult};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Setting {
+    Pieces,
+    Avatar,
+    Blindfold,
+    HangingPieces,
+    Tutor,
+    QuietMarker,
+    CaptureMarker,
+    AiDifficulty,
+    AiTimeLimit,
+    ConfirmActions,
+    AutoRematch,
+    DoubleClickMove,
+    MiddleClickFlip,
+    WheelStepsMoves,
+    HotseatFlip,
+    HotseatCover,
+    Borderless,
+    AlwaysOnTop,
+    Keys,
+}
+
+/// Which list the screen is showing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Page {
+    Settings,
+    Keys,
+}
+
+/// Settings in the order they're listed.
+const SETTINGS: [Setting; 19] = [
+    Setting::Pieces,
+    Setting::Avatar,
+    Setting::Blindfold,
+    Setting::HangingPieces,
+    Setting::Tutor,
+    Setting::QuietMarker,
+    Setting::CaptureMarker,
+    Setting::AiDifficulty,
+    Setting::AiTimeLimit,
+    Setting::ConfirmActions,
+    Setting::AutoRematch,
+    Setting::DoubleClickMove,
+    Setting::MiddleClickFlip,
+    Setting::WheelStepsMoves,
+    Setting::HotseatFlip,
+    Setting::HotseatCover,
+    Setting::Borderless,
+    Setting::AlwaysOnTop,
+    Setting::Keys,
+];
+
+/// Time limits per move the computer opponent can be given, in seconds. 0 is no limit.
+const TIME_LIMITS: [u64; 6] = [0, 1, 2, 5, 10, 30];
+
+/// Size of a row, and where the first one is drawn. The rest follow below it.
+const ROW_SIZE: (f32, f32) = (600.0, 28.0);
+const ROW_TOP: f32 = 130.0;
+const ROW_SPACING: f32 = 32.0;
+
+/// The same for the keyboard shortcuts, which need more rows: one per action, then a reset.
+const KEY_ROW_SIZE: (f32, f32) = (600.0, 24.0);
+const KEY_ROW_TOP: f32 = 120.0;
+const KEY_ROW_SPACING: f32 = 27.0;
+const KEY_ROWS: usize = ACTIONS.len() + 1;
+
+impl Setting {
+    fn label(self) -> &'static str {
+        match self {
+            Setting::Pieces => "Pieces",
+            Setting::Avatar => "Avatar shown to opponents",
+            Setting::Blindfold => "Blindfold",
+            Setting::HangingPieces => "Mark hanging pieces",
+            Setting::Tutor => "Tutor flags blunders",
+            Setting::QuietMarker => "Move markers",
+            Setting::CaptureMarker => "Capture markers",
+            Setting::AiDifficulty => "Computer difficulty",
+            Setting::AiTimeLimit => "Computer time per move",
+            Setting::ConfirmActions => "Confirm restart, resign, leave",
+            Setting::AutoRematch => "Automatic rematch online",
+            Setting::DoubleClickMove => "Double-click plays a lone move",
+            Setting::MiddleClickFlip => "Middle-click flips the board",
+            Setting::WheelStepsMoves => "Wheel on the board steps moves",
+            Setting::HotseatFlip => "One board turns to the side to move",
+            Setting::HotseatCover => "One board hidden between moves",
+            Setting::Borderless => "Window without a border",
+            Setting::AlwaysOnTop => "Window always on top",
+            Setting::Keys => "Keyboard shortcuts",
+        }
+    }
+}
+
+pub struct SettingsScreen {
+    config: Config,  // edited in place and saved after every change
+    selected: usize, // highlighted setting, moved with the arrow keys or the mouse
+    in_game: bool,   // opened during a game, so settings fixed for the game are greyed out
+    leaving: bool,   // Escape was pressed, go back to where the screen was opened from
+    page: Page,
+    key_selected: usize,    // highlighted row on the keyboard shortcuts page
+    capturing: bool,        // waiting for the key to bind to the highlighted action
+    notice: Option<String>, // what the last change to the shortcuts did
+}
+
+impl SettingsScreen {
+    pub fn new(config: Config, in_game: bool) -> SettingsScreen {
+        SettingsScreen {
+            config,
+            selected: 0,
+            in_game,
+            leaving: false,
+            page: Page::Settings,
+            key_selected: 0,
+            capturing: false,
+            notice: None,
+        }
+    }
+
+    /// The settings, with any changes made on the screen.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Whether the player asked to leave the screen.
+    pub fn leaving(&self) -> bool {
+        self.leaving
+    }
+
+    fn value(&self, setting: Setting) -> String {
+        match setting {
+            Setting::Pieces => self.config.pieces.label().to_string(),
+            Setting::Avatar => match self.config.avatar {
+                Some(index) => avatar::avatar(Some(index)).name.to_string(),
+                None => "None".to_string(),
+            },
+            Setting::Blindfold => self.config.blindfold.label().to_string(),
+            Setting::HangingPieces => self.config.hanging_pieces.label().to_string(),
+            Setting::QuietMarker => self.config.markers.quiet.label().to_string(),
+            Setting::CaptureMarker => self.config.markers.capture.label().to_string(),
+            Setting::AiDifficulty => {
+                let difficulty = self.config.ai_difficulty.unwrap_or(ai::DEFAULT_DIFFICULTY);
+                format!("{} of {}", difficulty, MAX_DIFFICULTY)
+            }
+            Setting::AiTimeLimit => {
+                match self.config.ai_time_limit.unwrap_or(ai::DEFAULT_TIME_LIMIT) {
+                    0 => "No limit".to_string(),
+                    secs => format!("{} s", secs),
+                }
+            }
+            Setting::Tutor if self.config.tutor => "On".to_string(),
+            Setting::Tutor => "Off".to_string(),
+            Setting::ConfirmActions if self.config.confirm_actions => "On".to_string(),
+            Setting::ConfirmActions => "Off".to_string(),
+            Setting::AutoRematch if self.config.auto_rematch => "On".to_string(),
+            Setting::AutoRematch => "Off".to_string(),
+            Setting::DoubleClickMove if self.config.double_click_move => "On".to_string(),
+            Setting::DoubleClickMove => "Off".to_string(),
+            Setting::MiddleClickFlip if self.config.middle_click_flip => "On".to_string(),
+            Setting::MiddleClickFlip => "Off".to_string(),
+            Setting::WheelStepsMoves if self.config.wheel_steps_moves => "On".to_string(),
+            Setting::WheelStepsMoves => "Off".to_string(),
+            Setting::HotseatFlip if self.config.hotseat_flip => "On".to_string(),
+            Setting::HotseatFlip => "Off".to_string(),
+            Setting::HotseatCover if self.config.hotseat_cover => "On".to_string(),
+            Setting::HotseatCover => "Off".to_string(),
+            Setting::Borderless if self.config.borderless => "On".to_string(),
+            Setting::Borderless => "Off".to_string(),
+            Setting::AlwaysOnTop if self.config.always_on_top => "On".to_string(),
+            Setting::AlwaysOnTop => "Off".to_string(),
+            Setting::Keys => "Change".to_string(),
+        }
+    }
+
+    /// Why a setting can't be changed right now, if it can't.
+    fn locked(&self, setting: Setting) -> Option<&'static str> {
+        match setting {
+            // the engine is given its time limit when the game starts
+            Setting::AiTimeLimit if self.in_game => Some("Can't change during a game"),
+            _ => None,
+        }
+    }
+
+    /// Moves a setting to its next value, or its previous one, and saves it.
+    fn change(&mut self, setting: Setting, forward: bool) {
+        if self.locked(setting).is_some() {
+            return;
+        }
+
+        match setting {
+            // there are only two, so both directions go to the other one
+            Setting::Pieces => self.config.pieces = self.config.pieces.next(),
+            // none comes before the first, and one from a newer version's list moves to our last
+            Setting::Avatar => {
+                let last = AVATARS.len() as u8 - 1;
+                self.config.avatar = match (self.config.avatar, forward) {
+                    (None, true) => Some(0),
+                    (None, false) | (Some(0), false) => None,
+                    (Some(index), true) => Some((index + 1).min(last)),
+                    (Some(index), false) => Some(index.min(last + 1) - 1),
+                };
+            }
+            Setting::Blindfold => {
+                let blindfold = self.config.blindfold;
+                self.config.blindfold = if forward {
+                    blindfold.next()
+                } else {
+                    blindfold.previous()
+                };
+            }
+            Setting::HangingPieces => {
+                let hanging = self.config.hanging_pieces;
+                self.config.hanging_pieces = if forward {
+                    hanging.next()
+                } else {
+                    hanging.previous()
+                };
+            }
+            Setting::QuietMarker | Setting::CaptureMarker => {
+                let markers = &mut self.config.markers;
+                let style = match setting {
+                    Setting::QuietMarker => &mut markers.quiet,
+                    _ => &mut markers.capture,
+                };
+                *style = if forward {
+                    style.next()
+                } else {
+                    style.previous()
+                };
+            }
+            Setting::AiDifficulty => {
+                let difficulty = self.config.ai_difficulty.unwrap_or(ai::DEFAULT_DIFFICULTY);
+                let difficulty = if forward {
+                    difficulty.saturating_add(1)
+                } else {
+                    difficulty.saturating_sub(1)
+                };
+                self.config.ai_difficulty = Some(difficulty.clamp(MIN_DIFFICULTY, MAX_DIFFICULTY));
+            }
+            Setting::AiTimeLimit => {
+                // limits from the config file that aren't in the list start from the closest one
+                let current = self.config.ai_time_limit.unwrap_or(ai::DEFAULT_TIME_LIMIT);
+                let index = TIME_LIMITS
+                    .iter()
+                    .position(|&limit| limit >= current)
+                    .unwrap_or(TIME_LIMITS.len() - 1);
+                let index = if forward {
+                    (index + 1).min(TIME_LIMITS.len() - 1)
+                } else {
+                    index.saturating_sub(1)
+                };
+                self.config.ai_time_limit = Some(TIME_LIMITS[index]);
+            }
+            Setting::Tutor => self.config.tutor = !self.config.tutor,
+            Setting::ConfirmActions => self.config.confirm_actions = !self.config.confirm_actions,
+            Setting::AutoRematch => self.config.auto_rematch = !self.config.auto_rematch,
+            Setting::DoubleClickMove => {
+                self.config.double_click_move = !self.config.double_click_move
+            }
+            Setting::MiddleClickFlip => {
+                self.config.middle_click_flip = !self.config.middle_click_flip
+            }
+            Setting::WheelStepsMoves => {
+                self.config.wheel_steps_moves = !self.config.wheel_steps_moves
+            }
+            Setting::HotseatFlip => self.config.hotseat_flip = !self.config.hotseat_flip,
+            Setting::HotseatCover => self.config.hotseat_cover = !self.config.hotseat_cover,
+            Setting::Borderless => self.config.borderless = !self.config.borderless,
+            Setting::AlwaysOnTop => self.config.always_on_top = !self.config.always_on_top,
+            // nothing to save until a key is changed
+            Setting::Keys => {
+                self.page = Page::Keys;
+                return;
+            }
+        }
+
+        self.config.save();
+    }
+
+    /// Starts rebinding the action on a row of the shortcuts page, or resets them all for the
+    /// last row.
+    fn activate_key_row(&mut self, row: usize) {
+        self.key_selected = row;
+        if row < ACTIONS.len() {
+            self.capturing = true;
+            self.notice = None;
+        } else {
+            self.config.keys = Keymap::default();
+            self.config.save();
+            self.notice = Some("All shortcuts are back to their defaults".to_string());
+        }
+    }
+
+    /// Binds a key pressed while capturing to the highlighted action.
+    fn capture(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        let action = ACTIONS[self.key_selected];
+        match KeyChord::new(key, mods) {
+            Some(chord) => {
+                self.notice = Some(match self.config.keys.bind(action, chord) {
+                    Some(other) => format!("{} was taken from {}", chord, other.label()),
+                    None => format!("{} now does {}", chord, action.label()),
+                });
+                self.config.save();
+                self.capturing = false;
+            }
+            None => self.notice = Some("That key can't be used for shortcuts".to_string()),
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        let title = match self.page {
+            Page::Settings => "Settings",
+            Page::Keys => "Keyboard shortcuts",
+        };
+        let title = graphics::Text::new(
+            graphics::TextFragment::from(title).scale(graphics::PxScale { x: 50.0, y: 50.0 }),
+        );
+        let title_width = title.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &title,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - title_width) / 2.0, 70.0]),
+        )?;
+
+        match self.page {
+            Page::Settings => self.draw_settings(ctx)?,
+            Page::Keys => self.draw_keys(ctx)?,
+        }
+
+        let hint = match self.page {
+            Page::Settings => "Up/Down picks a setting, Left/Right or clicking changes it, Escape goes back. Changes are saved right away.",
+            Page::Keys if self.capturing => "Press the new key, with any modifiers. Escape cancels.",
+            Page::Keys => "Enter or click changes a shortcut, Escape goes back. Changes are saved right away.",
+        };
+        let mut hint = graphics::Text::new(
+            graphics::TextFragment::from(hint).scale(graphics::PxScale { x: 18.0, y: 18.0 }),
+        );
+        hint.set_bounds(
+            [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+            graphics::Align::Center,
+        );
+        graphics::draw(
+            ctx,
+            &hint,
+            graphics::DrawParam::default()
+                .color([0.2, 0.2, 0.2, 1.0].into())
+                .dest([20.0, SCREEN_SIZE.1 - 40.0]),
+        )?;
+
+        graphics::present(ctx)
+    }
+
+    fn draw_settings(&self, ctx: &mut Context) -> GameResult {
+        for (i, setting) in SETTINGS.iter().enumerate() {
+            let rect = row_rect(i);
+            let locked = self.locked(*setting);
+
+            let background_colour = if i == self.selected {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let background = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                background_colour.into(),
+            )?;
+            graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+            // greyed out text for settings that can't be changed right now
+            let text_colour = match locked {
+                Some(_) => [0.6, 0.6, 0.6, 1.0],
+                None => [1.0, 1.0, 1.0, 1.0],
+            };
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(setting.label())
+                    .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            let label_height = label.dimensions(ctx).h;
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color(text_colour.into())
+                    .dest([rect.x + 12.0, rect.y + (rect.h - label_height) / 2.0]),
+            )?;
+
+            let value = graphics::Text::new(
+                graphics::TextFragment::from(format!("< {} >", self.value(*setting)))
+                    .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            let value_size = value.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &value,
+                graphics::DrawParam::default()
+                    .color(text_colour.into())
+                    .dest([
+                        rect.x + rect.w - value_size.w - 12.0,
+                        rect.y + (rect.h - value_size.h) / 2.0,
+                    ]),
+            )?;
+
+            if let Some(reason) = locked {
+                let reason = graphics::Text::new(
+                    graphics::TextFragment::from(reason)
+                        .scale(graphics::PxScale { x: 16.0, y: 16.0 }),
+                );
+                graphics::draw(
+                    ctx,
+                    &reason,
+                    graphics::DrawParam::default()
+                        .color([0.2, 0.2, 0.2, 1.0].into())
+                        .dest([rect.x + 12.0, rect.y + rect.h + 2.0]),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_keys(&self, ctx: &mut Context) -> GameResult {
+        for i in 0..KEY_ROWS {
+            let rect = key_row_rect(i);
+            let colour = if i == self.key_selected {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let background = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+            let (label, value) = match ACTIONS.get(i) {
+                Some(action) if self.capturing && i == self.key_selected => {
+                    (action.label(), "Press a key…".to_string())
+                }
+                Some(action) => (action.label(), self.config.keys.names(*action)),
+                None => ("Reset all to defaults", String::new()),
+            };
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(label).scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+            );
+            let label_height = label.dimensions(ctx).h;
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([rect.x + 12.0, rect.y + (rect.h - label_height) / 2.0]),
+            )?;
+
+            let value = graphics::Text::new(
+                graphics::TextFragment::from(value).scale(graphics::PxScale { x: 22.0, y: 22.0 }),
+            );
+            let value_size = value.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &value,
+                graphics::DrawParam::default()
+                    .color([1.0, 0.85, 0.4, 1.0].into())
+                    .dest([
+                        rect.x + rect.w - value_size.w - 12.0,
+                        rect.y + (rect.h - value_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+
+        if let Some(notice) = &self.notice {
+            let text = graphics::Text::new(
+                graphics::TextFragment::from(notice.as_str())
+                    .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            let below = key_row_rect(KEY_ROWS - 1);
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.1, 0.1, 0.4, 1.0].into())
+                    .dest([below.x, below.y + below.h + 12.0]),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        match self.page {
+            Page::Settings => {
+                if let Some(i) = setting_at(x, y) {
+                    self.selected = i;
+                }
+            }
+            Page::Keys if !self.capturing => {
+                if let Some(i) = key_row_at(x, y) {
+                    self.key_selected = i;
+                }
+            }
+            Page::Keys => (),
+        }
+    }
+
+    /// Left-click moves a setting to its next value, right-click to its previous one.
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        if self.page == Page::Keys {
+            if button == event::MouseButton::Left && !self.capturing {
+                if let Some(i) = key_row_at(x, y) {
+                    self.activate_key_row(i);
+                }
+            }
+            return;
+        }
+
+        let forward = match button {
+            event::MouseButton::Left => true,
+            event::MouseButton::Right => false,
+            _ => return,
+        };
+        if let Some(i) = setting_at(x, y) {
+            self.selected = i;
+            self.change(SETTINGS[i], forward);
+        }
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        if self.page == Page::Keys {
+            self.keys_key_down_event(key, mods);
+            return;
+        }
+
+        let setting = SETTINGS[self.selected];
+        match key {
+            event::KeyCode::Up => {
+                self.selected = (self.selected + SETTINGS.len() - 1) % SETTINGS.len()
+            }
+            event::KeyCode::Down | event::KeyCode::Tab => {
+                self.selected = (self.selected + 1) % SETTINGS.len()
+            }
+            event::KeyCode::Left => self.change(setting, false),
+            event::KeyCode::Right | event::KeyCode::Return | event::KeyCode::Space => {
+                self.change(setting, true)
+            }
+            event::KeyCode::Escape => self.leaving = true,
+            _ => (),
+        }
+    }
+}
+
+impl SettingsScreen {
+    fn keys_key_down_event(&mut self, key: event::KeyCode, mods: event::KeyMods) {
+        if self.capturing {
+            match key {
+                event::KeyCode::Escape => {
+                    self.capturing = false;
+                    self.notice = None;
+                }
+                // wait for the key that goes with the modifiers
+                event::KeyCode::LShift
+                | event::KeyCode::RShift
+                | event::KeyCode::LControl
+                | event::KeyCode::RControl
+                | event::KeyCode::LAlt
+                | event::KeyCode::RAlt
+                | event::KeyCode::LWin
+                | event::KeyCode::RWin => (),
+                _ => self.capture(key, mods),
+            }
+            return;
+        }
+
+        match key {
+            event::KeyCode::Up => self.key_selected = (self.key_selected + KEY_ROWS - 1) % KEY_ROWS,
+            event::KeyCode::Down | event::KeyCode::Tab => {
+                self.key_selected = (self.key_selected + 1) % KEY_ROWS
+            }
+            event::KeyCode::Return
+            | event::KeyCode::NumpadEnter
+            | event::KeyCode::Space
+            | event::KeyCode::Right => self.activate_key_row(self.key_selected),
+            event::KeyCode::Escape => {
+                self.page = Page::Settings;
+                self.notice = None;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Where a setting's row is on the screen.
+fn row_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - ROW_SIZE.0) / 2.0,
+        ROW_TOP + i as f32 * ROW_SPACING,
+        ROW_SIZE.0,
+        ROW_SIZE.1,
+    )
+}
+
+/// Index of the setting at a point on the screen, if there is one.
+fn setting_at(x: f32, y: f32) -> Option<usize> {
+    (0..SETTINGS.len()).find(|&i| row_rect(i).contains([x, y]))
+}
+
+/// Where a row of the shortcuts page is on the screen.
+fn key_row_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - KEY_ROW_SIZE.0) / 2.0,
+        KEY_ROW_TOP + i as f32 * KEY_ROW_SPACING,
+        KEY_ROW_SIZE.0,
+        KEY_ROW_SIZE.1,
+    )
+}
+
+/// Index of the shortcuts page row at a point on the screen, if there is one.
+fn key_row_at(x: f32, y: f32) -> Option<usize> {
+    (0..KEY_ROWS).find(|&i| key_row_rect(i).contains([x, y]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Blindfold;
+    use crate::keys::Action;
+    use crate::render::{MarkerStyle, PieceTheme};
+
+    #[test]
+    fn values_cycle_and_stop_at_the_ends() {
+        let mut screen = SettingsScreen::new(Config::default(), false);
+
+        screen.change(Setting::Blindfold, false);
+        assert_eq!(screen.config().blindfold, Blindfold::PiecesAndDots);
+
+        for _ in 0..20 {
+            screen.change(Setting::AiDifficulty, true);
+        }
+        assert_eq!(screen.config().ai_difficulty, Some(MAX_DIFFICULTY));
+        // one not loaded from a file isn't clamped, and still stops at the top
+        let mut unclamped = Config::default();
+        unclamped.ai_difficulty = Some(u8::MAX);
+        let mut high = SettingsScreen::new(unclamped, false);
+        high.change(Setting::AiDifficulty, true);
+        assert_eq!(high.config().ai_difficulty, Some(MAX_DIFFICULTY));
+
+        for _ in 0..20 {
+            screen.change(Setting::AiTimeLimit, false);
+        }
+        assert_eq!(screen.config().ai_time_limit, Some(0));
+    }
+
+    #[test]
+    fn avatars_step_through_the_list() {
+        let mut screen = SettingsScreen::new(Config::default(), true);
+        screen.change(Setting::Avatar, false);
+        assert_eq!(screen.config().avatar, None);
+        screen.change(Setting::Avatar, true);
+        screen.change(Setting::Avatar, true);
+        assert_eq!(screen.value(Setting::Avatar), "Knight");
+
+        let mut config = Config::default();
+        config.avatar = Some(200);
+        let mut screen = SettingsScreen::new(config, true);
+        screen.change(Setting::Avatar, false);
+        assert_eq!(screen.config().avatar, Some(AVATARS.len() as u8 - 1));
+        screen.change(Setting::Avatar, true);
+        assert_eq!(screen.config().avatar, Some(AVATARS.len() as u8 - 1));
+    }
+
+    #[test]
+    fn piece_theme_toggles() {
+        let mut screen = SettingsScreen::new(Config::default(), true);
+        screen.change(Setting::Pieces, true);
+        assert_eq!(screen.config().pieces, PieceTheme::Glyphs);
+        screen.change(Setting::Pieces, false);
+        assert_eq!(screen.config().pieces, PieceTheme::Images);
+    }
+
+    #[test]
+    fn move_and_capture_markers_change_separately() {
+        let mut screen = SettingsScreen::new(Config::default(), true);
+        screen.change(Setting::CaptureMarker, false);
+        assert_eq!(screen.config().markers.capture, MarkerStyle::Ring);
+        assert_eq!(screen.config().markers.quiet, MarkerStyle::Dot);
+        screen.change(Setting::QuietMarker, true);
+        assert_eq!(screen.config().markers.quiet, MarkerStyle::Fill);
+    }
+
+    #[test]
+    fn every_setting_fits_above_the_hint() {
+        let last = row_rect(SETTINGS.len() - 1);
+        assert!(last.y + last.h + 20.0 < SCREEN_SIZE.1 - 40.0);
+        let last = key_row_rect(KEY_ROWS - 1);
+        assert!(last.y + last.h + 20.0 < SCREEN_SIZE.1 - 40.0);
+    }
+
+    #[test]
+    fn unlisted_time_limits_move_to_a_listed_one() {
+        let mut config = Config::default();
+        config.ai_time_limit = Some(7);
+        let mut screen = SettingsScreen::new(config, false);
+        screen.change(Setting::AiTimeLimit, true);
+        assert_eq!(screen.config().ai_time_limit, Some(30));
+    }
+
+    #[test]
+    fn shortcuts_are_rebound_and_reset() {
+        let mut screen = SettingsScreen::new(Config::default(), false);
+        screen.change(Setting::Keys, true);
+        assert_eq!(screen.page, Page::Keys);
+
+        // Restart is the fourth action
+        screen.activate_key_row(3);
+        screen.keys_key_down_event(event::KeyCode::LControl, event::KeyMods::CTRL);
+        assert!(screen.capturing);
+        screen.keys_key_down_event(event::KeyCode::R, event::KeyMods::CTRL);
+        assert!(!screen.capturing);
+        assert_eq!(screen.config().keys.names(Action::Restart), "Ctrl+R");
+
+        screen.activate_key_row(KEY_ROWS - 1);
+        assert_eq!(screen.config().keys, Keymap::default());
+
+        screen.keys_key_down_event(event::KeyCode::Escape, event::KeyMods::NONE);
+        assert_eq!(screen.page, Page::Settings);
+        assert!(!screen.leaving());
+    }
+
+    #[test]
+    fn time_limit_is_locked_during_a_game() {
+        let mut screen = SettingsScreen::new(Config::default(), true);
+        screen.change(Setting::AiTimeLimit, true);
+        assert_eq!(screen.config().ai_time_limit, None);
+
+        screen.change(Setting::AiDifficulty, true);
+        assert_eq!(
+            screen.config().ai_difficulty,
+            Some(ai::DEFAULT_DIFFICULTY + 1)
+        );
+    }
+}
@@ -0,0 +1,280 @@
+/**
+ * Snapshots of everything the game knows, for bug reports. F12 writes one to
+ * `snapshot-<seconds>.json` in the working directory: the settings, the moves with the key of
+ * every position reached, the counters, the state of the connection and the last 50 messages
+ * either way. `--load-snapshot` plays its moves back into a game on this computer, so whoever
+ * reads the report can see the board as the player saw it.
+ *
+ * Nothing in a snapshot should let someone else act as the player, so the tokens spectators
+ * join with and a user name or password in a server address are blanked out before it's written.
+ * The settings and connection are only there to be read; loading one leaves them alone.
+ */
+use crate::config::Config;
+use crate::crash::SavedGame;
+use crate::protocol;
+use crate::transcript::Direction;
+use chess_template::Colour;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many of the last messages are kept, both ways together.
+pub const MESSAGES: usize = 50;
+
+/// What a secret is replaced with.
+const REDACTED: &str = "redacted";
+
+/// The game and everything around it, as written to the file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StateSnapshot {
+    pub version: String, // of the program that wrote it
+    pub taken: u64,      // when, in seconds since the Unix epoch
+    pub config: Config,
+    pub game: GameState,
+    pub connection: Connection,
+    pub messages: Vec<LoggedMessage>, // oldest first
+}
+
+/// The game on the board.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameState {
+    pub room: String,
+    pub colour: String, // the colour played here, "white" or "black"
+    pub hotseat: bool,
+    pub counter: u32,
+    pub moves: Vec<String>,      // every move in coordinate notation
+    pub fen: String,             // the position after them
+    pub position_keys: Vec<u64>, // of every position reached, the start included
+    pub ending: Option<String>,  // how it ended, if not on the board, e.g. "Resigned(Black)"
+    pub draw_offered_by: Option<String>,
+}
+
+/// The state of the connection, and the counts from the network info overlay.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Connection {
+    pub server: Option<String>,
+    pub connected: bool,
+    pub correspondence: bool,
+    pub desync: bool, // the games fell out of sync
+    pub received: u64,
+    pub ignored: u64,
+    pub echoes: u64, // our own messages sent back, and dropped
+    pub repeated: u64,
+    pub flooded: u64,
+    pub unasked: u64, // resets and rematch messages that weren't asked for
+    pub sent: u64,
+    pub waited: u64,
+    pub deferred: u64,
+    pub outbox: usize, // messages still waiting to go to the network thread
+}
+
+/// A message through the connection, `ms` milliseconds after the game started.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LoggedMessage {
+    pub ms: u64,
+    pub incoming: bool,
+    pub text: String,
+}
+
+impl LoggedMessage {
+    pub fn new(ms: u64, direction: Direction, text: &str) -> LoggedMessage {
+        LoggedMessage {
+            ms,
+            incoming: direction == Direction::In,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl StateSnapshot {
+    /// The game as the crash saves keep it, to play on from.
+    pub fn saved_game(&self) -> SavedGame {
+        SavedGame {
+            room: self.game.room.clone(),
+            fen: self.game.fen.clone(),
+            moves: self.game.moves.clone(),
+        }
+    }
+
+    /// The colour played when it was taken, if it says.
+    pub fn colour(&self) -> Option<Colour> {
+        protocol::parse_colour(&self.game.colour).ok()
+    }
+
+    /// Blanks out anything secret: a user name and password in the server addresses, and
+    /// spectator tokens in the messages.
+    pub fn redact(&mut self) {
+        let config = &mut self.config;
+        for server in [&mut config.server, &mut config.last_server]
+            .into_iter()
+            .chain([&mut self.connection.server])
+            .flatten()
+        {
+            *server = redact_server(server);
+        }
+        for server in &mut config.recent_servers {
+            *server = redact_server(server);
+        }
+        for room in &mut config.recent_rooms {
+            room.server = redact_server(&room.server);
+        }
+        for message in &mut self.messages {
+            message.text = redact_message(&message.text);
+        }
+    }
+
+    /// Writes it to `dir`, named after when it was taken, and says where.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        let path = dir.join(format!("snapshot-{}.json", self.taken));
+        let text = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(&path, text + "\n").map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(path)
+    }
+}
+
+/// Reads a snapshot back from its file.
+pub fn load(path: &Path) -> Result<StateSnapshot, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Couldn't read the snapshot {}: {}", path.display(), err))?;
+    serde_json::from_str(&text)
+        .map_err(|err| format!("{} isn't a snapshot: {}", path.display(), err))
+}
+
+/// Seconds since the Unix epoch, to name a snapshot by.
+pub fn seconds(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// A server address without the user name and password in front of it, if it has them.
+fn redact_server(server: &str) -> String {
+    match server.rsplit_once('@') {
+        Some((_, host)) => format!("{}@{}", REDACTED, host),
+        None => server.to_string(),
+    }
+}
+
+/// A message with its spectator token blanked out, if it has one. It's found by where it stands
+/// rather than by parsing the message, so one that's cut off or garbled loses it too.
+fn redact_message(text: &str) -> String {
+    let mut fields: Vec<&str> = text.split_whitespace().collect();
+    match fields.as_mut_slice() {
+        [_, "spectate", token, ..] => {
+            *token = REDACTED;
+            fields.join(" ")
+        }
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecentRoom;
+
+    fn populated() -> StateSnapshot {
+        let mut config = Config::default();
+        config.name = Some("Alice".to_string());
+        config.server = Some("alice:hunter2@10.0.0.2:6000".to_string());
+        config.recent_servers = vec!["alice:hunter2@10.0.0.2:6000".to_string()];
+        config.recent_rooms = vec![RecentRoom {
+            server: "alice:hunter2@10.0.0.2:6000".to_string(),
+            room: "fredagsschack".to_string(),
+            played: 1_700_000_000,
+            finished: false,
+        }];
+        StateSnapshot {
+            version: "0.1.0".to_string(),
+            taken: 1_700_000_123,
+            config,
+            game: GameState {
+                room: "fredagsschack".to_string(),
+                colour: "black".to_string(),
+                hotseat: false,
+                counter: 3,
+                moves: vec!["e2e4".to_string(), "e7e5".to_string()],
+                fen: "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2".to_string(),
+                position_keys: vec![1, u64::MAX, 42],
+                ending: None,
+                draw_offered_by: Some("white".to_string()),
+            },
+            connection: Connection {
+                server: Some("alice:hunter2@10.0.0.2:6000".to_string()),
+                connected: true,
+                correspondence: false,
+                desync: false,
+                received: 12,
+                ignored: 1,
+                echoes: 9,
+                repeated: 1,
+                flooded: 0,
+                unasked: 0,
+                sent: 9,
+                waited: 0,
+                deferred: 2,
+                outbox: 0,
+            },
+            messages: vec![
+                LoggedMessage {
+                    ms: 5230,
+                    incoming: false,
+                    text: "fredagsschack mv 2 6 4 4 4".to_string(),
+                },
+                LoggedMessage {
+                    ms: 6001,
+                    incoming: true,
+                    text: "fredagsschack spectate s3cr3t Bob".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn snapshots_round_trip_through_json() {
+        let snapshot = populated();
+        let text = serde_json::to_string_pretty(&snapshot).unwrap();
+        let read: StateSnapshot = serde_json::from_str(&text).unwrap();
+        assert_eq!(read, snapshot);
+        assert!(read.colour() == Some(Colour::Black));
+        assert_eq!(read.saved_game().moves, ["e2e4", "e7e5"]);
+
+        // and through a file
+        let dir = std::env::temp_dir().join(format!("snapshot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = snapshot.save(&dir).unwrap();
+        assert_eq!(path.file_name().unwrap(), "snapshot-1700000123.json");
+        assert_eq!(load(&path).unwrap(), snapshot);
+        fs::write(&path, "{}").unwrap();
+        assert!(load(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn secrets_are_blanked_out() {
+        let mut snapshot = populated();
+        snapshot.redact();
+        let text = serde_json::to_string(&snapshot).unwrap();
+        assert!(!text.contains("hunter2") && !text.contains("s3cr3t"));
+        assert_eq!(
+            snapshot.connection.server.as_deref(),
+            Some("redacted@10.0.0.2:6000")
+        );
+        assert_eq!(
+            snapshot.config.recent_rooms[0].server,
+            "redacted@10.0.0.2:6000"
+        );
+        assert_eq!(
+            snapshot.messages[1].text,
+            "fredagsschack spectate redacted Bob"
+        );
+        // the rest is left as it was
+        assert_eq!(snapshot.messages[0], populated().messages[0]);
+        assert_eq!(snapshot.config.name.as_deref(), Some("Alice"));
+        assert_eq!(redact_server("10.0.0.2:6000"), "10.0.0.2:6000");
+        assert_eq!(
+            redact_message("lobby spectate s3cr3t"),
+            "lobby spectate redacted"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+/**
+ * Who's watching a game. This client can't watch games itself, but the server relays every
+ * message to every client in a room, so anyone else in it sees the moves. Spectators announce
+ * themselves with a `spectate` message and announce themselves again every few seconds, as the
+ * server doesn't say when a client leaves. A spectator not heard from for a while is taken to
+ * have gone.
+ */
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long after their last announcement a spectator is still counted. Spectators should
+/// announce themselves about every 10 seconds, so a missed announcement or two doesn't drop
+/// them.
+pub const SPECTATOR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The spectators heard from recently, by the token each picked to tell them apart.
+#[derive(Default)]
+pub struct Spectators {
+    seen: HashMap<String, (String, Instant)>, // name and the last announcement
+}
+
+impl Spectators {
+    /// Notes an announcement from a spectator at `now`. A spectator that changes its name is
+    /// listed by the new one.
+    pub fn saw(&mut self, token: &str, name: &str, now: Instant) {
+        self.seen.insert(token.to_string(), (name.to_string(), now));
+    }
+
+    /// Forgets spectators that haven't announced themselves within the timeout before `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.seen
+            .retain(|_, (_, at)| now.saturating_duration_since(*at) < SPECTATOR_TIMEOUT);
+    }
+
+    /// How many spectators there are.
+    pub fn count(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// The spectators' names in alphabetical order, with those who didn't give one as
+    /// "Anonymous".
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .seen
+            .values()
+            .map(|(name, _)| match name.as_str() {
+                "" => "Anonymous".to_string(),
+                _ => name.clone(),
+            })
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spectators_are_forgotten_unless_they_announce_themselves_again() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut spectators = Spectators::default();
+        spectators.saw("a1", "Bertil", at(0));
+        spectators.saw("b2", "", at(5));
+        spectators.saw("c3", "alva", at(10));
+        // the same spectator again is still one
+        spectators.saw("a1", "Bertil", at(12));
+        spectators.expire(at(20));
+        assert_eq!(spectators.count(), 3);
+        assert_eq!(spectators.names(), ["alva", "Anonymous", "Bertil"]);
+
+        // b2 was last heard from 30 seconds ago, and the others since
+        spectators.expire(at(35));
+        assert_eq!(spectators.names(), ["alva", "Bertil"]);
+        spectators.saw("c3", "Alva", at(39));
+        spectators.expire(at(42));
+        assert_eq!(spectators.names(), ["Alva"]);
+        spectators.expire(at(69));
+        assert_eq!(spectators.count(), 0);
+    }
+}
@@ -0,0 +1,199 @@
+/**
+ * Suggested moves on the analysis board: the computer opponent's best few moves in the position
+ * shown, each with its score. They're searched for on a thread of its own, one after another, and
+ * each is shown as soon as it's found, so the panel fills in while the board stays responsive.
+ * Moving on the analysis board starts a search for the new position and cancels the old one.
+ */
+use crate::ai;
+use crate::board::position_key;
+use chess_template::{Colour, Game, Position};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+/// How many moves are suggested, and how many plies deep they're searched. Deeper than the
+/// tutor looks, but quick enough that the first comes within a second or so.
+pub const SUGGESTIONS: usize = 3;
+pub const SUGGESTION_DEPTH: u32 = 3;
+
+/// A suggested move.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Suggestion {
+    pub from: Position,
+    pub to: Position,
+    pub score: i32, // in centipawns, from White's side
+}
+
+/// The search for one position's suggestions, and what it's found so far.
+pub struct Suggestions {
+    key: u64, // the position searched, as in the game's history
+    found: Vec<Suggestion>,
+    done: bool, // the search has ended, with all it could find
+    results: Receiver<Suggestion>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Suggestions {
+    /// Starts searching `game`, the position with key `key`.
+    pub fn start(game: Game, key: u64) -> Suggestions {
+        let (sender, results) = mpsc::sync_channel(SUGGESTIONS);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            // scores come for the side to move
+            let sign = match game.get_active_colour() {
+                Colour::White => 1,
+                Colour::Black => -1,
+            };
+            ai::best_moves(
+                &game,
+                SUGGESTION_DEPTH,
+                SUGGESTIONS,
+                &stop,
+                &mut |from, to, score| {
+                    let _ = sender.send(Suggestion {
+                        from,
+                        to,
+                        score: sign * score,
+                    });
+                },
+            );
+        });
+        Suggestions {
+            key,
+            found: Vec::new(),
+            done: false,
+            results,
+            cancelled,
+        }
+    }
+
+    /// The key of the position searched.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Takes in the suggestions found since the last call. Returns whether there were any.
+    pub fn poll(&mut self) -> bool {
+        let before = self.found.len();
+        loop {
+            match self.results.try_recv() {
+                Ok(suggestion) => self.found.push(suggestion),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        self.found.len() > before
+    }
+
+    /// The suggestions found so far, best first.
+    pub fn found(&self) -> &[Suggestion] {
+        &self.found
+    }
+
+    /// Whether the search has ended, whether or not it found as many as it looked for.
+    pub fn done(&self) -> bool {
+        self.done
+    }
+}
+
+/// The suggestions for the position on the analysis board, searched again whenever it changes.
+#[derive(Default)]
+pub struct Suggester {
+    search: Option<Suggestions>, // for the position last shown, while there's an analysis board
+}
+
+impl Suggester {
+    /// Follows `shown`, the position on the analysis board while it's open: a new search whenever
+    /// it changes, which cancels the last one, taking in what's been found since the last call.
+    /// Returns the search, while there's one.
+    pub fn follow(&mut self, shown: Option<&Game>) -> Option<&Suggestions> {
+        let game = match shown {
+            Some(game) => game,
+            None => {
+                self.search = None;
+                return None;
+            }
+        };
+        let key = position_key(game);
+        if self.key() != Some(key) {
+            self.search = Some(Suggestions::start(game.clone(), key));
+        }
+        let search = self.search.as_mut()?;
+        search.poll();
+        Some(search)
+    }
+
+    /// The key of the position searched, while there's a search.
+    pub fn key(&self) -> Option<u64> {
+        self.search.as_ref().map(Suggestions::key)
+    }
+
+    /// The suggested move numbered `i` in `game`, if it's been found.
+    pub fn get(&self, game: &Game, i: usize) -> Option<(Position, Position)> {
+        let search = self
+            .search
+            .as_ref()
+            .filter(|search| search.key() == position_key(game))?;
+        let found = search.found().get(i)?;
+        Some((found.from, found.to))
+    }
+}
+
+impl Drop for Suggestions {
+    /// Stops the search, which is for a position no longer shown.
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A score in pawns from White's side, e.g. "+0.35", or which side mates.
+pub fn score_text(score: i32) -> String {
+    match score {
+        score if ai::is_mate(score) && score > 0 => "+mate".to_string(),
+        score if ai::is_mate(score) => "-mate".to_string(),
+        score => format!("{:+.2}", f64::from(score) / 100.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::parse_move;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn scores_read_from_whites_side() {
+        assert_eq!(score_text(35), "+0.35");
+        assert_eq!(score_text(-120), "-1.20");
+        assert_eq!(score_text(0), "+0.00");
+        assert_eq!(score_text(99_990), "+mate");
+        assert_eq!(score_text(-99_990), "-mate");
+    }
+
+    #[test]
+    fn black_taking_a_queen_is_good_for_black() {
+        let mut game = Game::new();
+        for text in ["e2e4", "d7d5", "d1g4"] {
+            let (from, to, _) = parse_move(text).unwrap();
+            game.make_move_pos(from, to).unwrap();
+        }
+        let mut suggestions = Suggestions::start(game, 7);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !suggestions.done() {
+            assert!(Instant::now() < deadline, "the search never ended");
+            suggestions.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let found = suggestions.found();
+        assert_eq!(found.len(), SUGGESTIONS);
+        assert!(found[0].to == parse_move("g4g4").unwrap().0);
+        assert!(found[0].score < -700);
+        assert!(found.windows(2).all(|pair| pair[0].score <= pair[1].score));
+        assert_eq!(suggestions.key(), 7);
+    }
+}
@@ -0,0 +1,334 @@
+/**
+ * Several games open at once, e.g. one to play and another to keep an eye on, each in a tab above
+ * the board. With one game open there are no tabs and the game fills the window as always; with
+ * more, the game is drawn slightly smaller to make room for them.
+ */
+use crate::app::{AppState, SCREEN_SIZE};
+use crate::keys::Action;
+use crate::layout::{self, Layout};
+use crate::scene::{connect_screen, window_title, Scene, Shared, Transition};
+use ggez::event::winit_event::TouchPhase;
+use ggez::event::{Axis, Button, KeyCode, KeyMods, MouseButton};
+use ggez::{graphics, Context, GameResult};
+
+/// How many games can be open at once.
+pub const MAX_GAMES: usize = 3;
+
+/// Size of a tab, in the game's coordinates. The tabs sit just above the game.
+const TAB_SIZE: (f32, f32) = (220.0, 32.0);
+
+/// How much the game is shrunk by while there are tabs, so they fit above it.
+const SCALE: f32 = (SCREEN_SIZE.1 + TAB_SIZE.1) / SCREEN_SIZE.1;
+
+/// The open games, and which of them is shown.
+pub struct Tabs {
+    games: Vec<AppState>,
+    active: usize,
+    opening: bool, // another game was asked for, and the connection screen is about to open
+    title: String, // what the window title was last set to
+}
+
+impl Tabs {
+    pub fn new(game: AppState) -> Tabs {
+        Tabs {
+            games: vec![game],
+            active: 0,
+            opening: false,
+            title: String::new(),
+        }
+    }
+
+    /// Whether the tabs are shown, which they are once there's more than one game.
+    fn shown(&self) -> bool {
+        self.games.len() > 1
+    }
+
+    /// A point in the window in the game's coordinates, which are shifted down and shrunk while
+    /// the tabs are shown.
+    fn to_game(&self, x: f32, y: f32) -> (f32, f32) {
+        if self.shown() {
+            (x * SCALE, y * SCALE - TAB_SIZE.1)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Closes a game's tab, keeping the same game shown if it's another one.
+    fn close(&mut self, i: usize) {
+        self.games.remove(i);
+        if self.active > i || self.active == self.games.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+    }
+
+    fn draw_tabs(&self, ctx: &mut Context) -> GameResult {
+        let strip = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, -TAB_SIZE.1, SCREEN_SIZE.0 * SCALE, TAB_SIZE.1),
+            [0.3, 0.3, 0.3, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &strip, graphics::DrawParam::default())?;
+
+        for (i, game) in self.games.iter().enumerate() {
+            let rect = tab_rect(i);
+            let colour = if i == self.active {
+                [0.5, 0.5, 0.5, 1.0]
+            } else {
+                [0.4, 0.4, 0.4, 1.0]
+            };
+            let tab = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &tab, graphics::DrawParam::default())?;
+
+            // the games that aren't shown say when they're waiting for us
+            let mut label = graphics::Text::new(
+                graphics::TextFragment::from(game.room())
+                    .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            if i != self.active && game.awaiting_move() {
+                label.add(
+                    graphics::TextFragment::from("  your move")
+                        .color(graphics::Color::new(1.0, 0.85, 0.4, 1.0))
+                        .scale(graphics::PxScale { x: 16.0, y: 16.0 }),
+                );
+            }
+            label.set_bounds([rect.w - 16.0, rect.h], graphics::Align::Left);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([rect.x + 8.0, rect.y + 6.0]),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a tab is, in the game's coordinates.
+fn tab_rect(i: usize) -> graphics::Rect {
+    graphics::Rect::new(
+        i as f32 * (TAB_SIZE.0 + 4.0),
+        -TAB_SIZE.1,
+        TAB_SIZE.0,
+        TAB_SIZE.1,
+    )
+}
+
+/// Which of `count` tabs is at a point in the game's coordinates, if any.
+fn tab_at(count: usize, x: f32, y: f32) -> Option<usize> {
+    (0..count).find(|&i| tab_rect(i).contains([x, y]))
+}
+
+impl Scene for Tabs {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> GameResult<Transition> {
+        if let Some(game) = shared.new_tab.take() {
+            self.games.push(game);
+            self.active = self.games.len() - 1;
+        }
+
+        let mut i = 0;
+        while i < self.games.len() {
+            match self.games[i].update(ctx, shared)? {
+                Transition::None => i += 1,
+                Transition::Quit => return Ok(Transition::Quit),
+                // the last game decides where to go, as if there never were tabs. Away from it,
+                // the title goes back to saying who's playing
+                transition if self.games.len() == 1 => {
+                    if matches!(transition, Transition::Replace(_) | Transition::Pop) {
+                        graphics::set_window_title(ctx, &window_title(shared.name().as_deref()));
+                    }
+                    return Ok(transition);
+                }
+                // otherwise the others carry on. A game that stopped shows why over them
+                Transition::Replace(scene) | Transition::Push(scene) => {
+                    let left = self.games[i].left();
+                    self.close(i);
+                    if !left {
+                        shared.opening_tab = true;
+                        return Ok(Transition::Push(scene));
+                    }
+                }
+                Transition::Pop => self.close(i),
+            }
+        }
+
+        // the title follows the game shown, and is only set when it changes
+        let title = self.games[self.active].title();
+        if title != self.title {
+            graphics::set_window_title(ctx, &title);
+            self.title = title;
+        }
+
+        // the connection screen goes on top, and hands the game over once it's ready
+        if self.opening {
+            self.opening = false;
+            shared.opening_tab = true;
+            return Ok(Transition::Push(Box::new(connect_screen(shared))));
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &Shared) -> GameResult {
+        let shown = self.shown();
+        let game = &mut self.games[self.active];
+        if !shown || game.showing_settings() {
+            return game.draw(ctx, shared);
+        }
+
+        let screen = graphics::screen_coordinates(ctx);
+        graphics::set_screen_coordinates(
+            ctx,
+            layout::within(
+                screen,
+                graphics::Rect::new(
+                    0.0,
+                    -TAB_SIZE.1,
+                    SCREEN_SIZE.0 * SCALE,
+                    SCREEN_SIZE.1 + TAB_SIZE.1,
+                ),
+            ),
+        )?;
+        game.draw_game(ctx, shared, Layout::Standard)?;
+        self.draw_tabs(ctx)?;
+        graphics::set_screen_coordinates(ctx, screen)?;
+        graphics::present(ctx)
+    }
+
+    fn mouse_motion(&mut self, ctx: &mut Context, shared: &mut Shared, x: f32, y: f32) {
+        let (x, y) = self.to_game(x, y);
+        self.games[self.active].mouse_motion(ctx, shared, x, y);
+    }
+
+    fn mouse_button_down(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        let (x, y) = self.to_game(x, y);
+        self.games[self.active].mouse_button_down(ctx, shared, button, x, y);
+    }
+
+    fn mouse_button_up(
+        &mut self,
+        ctx: &mut Context,
+        shared: &mut Shared,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) {
+        let (x, y) = self.to_game(x, y);
+        if self.shown() && y < 0.0 {
+            if let Some(i) = tab_at(self.games.len(), x, y) {
+                self.active = i;
+            }
+            return;
+        }
+        self.games[self.active].mouse_button_up(ctx, shared, button, x, y);
+    }
+
+    fn mouse_wheel(&mut self, ctx: &mut Context, shared: &mut Shared, x: f32, y: f32) {
+        self.games[self.active].mouse_wheel(ctx, shared, x, y);
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, shared: &mut Shared, key: KeyCode, mods: KeyMods) {
+        match shared.config.keys.action(key, mods) {
+            Some(Action::NextGame) => self.active = (self.active + 1) % self.games.len(),
+            Some(Action::AnotherGame) if self.games.len() < MAX_GAMES => self.opening = true,
+            Some(Action::AnotherGame) => self.games[self.active]
+                .notify(format!("At most {} games can be open at once", MAX_GAMES)),
+            _ => self.games[self.active].key_down(ctx, shared, key, mods),
+        }
+    }
+
+    /// Every game hears about it, in case the key was pressed before switching tabs.
+    fn key_up(&mut self, ctx: &mut Context, shared: &mut Shared, key: KeyCode) {
+        for game in &mut self.games {
+            game.key_up(ctx, shared, key);
+        }
+    }
+
+    fn text_input(&mut self, ctx: &mut Context, shared: &mut Shared, character: char) {
+        self.games[self.active].text_input(ctx, shared, character);
+    }
+
+    /// A tap on a tab switches to it; the game gets the rest, for its long presses.
+    fn touch(&mut self, ctx: &mut Context, shared: &mut Shared, phase: TouchPhase, x: f32, y: f32) {
+        let (x, y) = self.to_game(x, y);
+        if self.shown() && y < 0.0 {
+            if phase == TouchPhase::Ended {
+                // in case the finger went down on the game
+                self.games[self.active].touch(ctx, shared, TouchPhase::Cancelled, x, y);
+                if let Some(i) = tab_at(self.games.len(), x, y) {
+                    self.active = i;
+                }
+            }
+            return;
+        }
+        self.games[self.active].touch(ctx, shared, phase, x, y);
+    }
+
+    fn gamepad_button(&mut self, ctx: &mut Context, shared: &mut Shared, button: Button) {
+        self.games[self.active].gamepad_button(ctx, shared, button);
+    }
+
+    fn gamepad_axis(&mut self, ctx: &mut Context, shared: &mut Shared, axis: Axis, value: f32) {
+        self.games[self.active].gamepad_axis(ctx, shared, axis, value);
+    }
+
+    /// Shows the first game that wants to ask before the window closes.
+    fn quit_requested(&mut self, ctx: &mut Context, shared: &mut Shared) -> bool {
+        match (0..self.games.len()).find(|&i| self.games[i].quit_requested(ctx, shared)) {
+            Some(i) => {
+                self.active = i;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn shut_down(&mut self, shared: &mut Shared) {
+        for game in &mut self.games {
+            game.shut_down(shared);
+        }
+    }
+
+    /// The tabs go round the whole screen, so the board is only shown alone with one game open.
+    fn layout(&self, shared: &Shared, window: (f32, f32)) -> Layout {
+        if self.shown() {
+            Layout::Standard
+        } else {
+            self.games[self.active].layout(shared, window)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabs_are_found_above_the_game() {
+        assert_eq!(tab_at(2, 10.0, -10.0), Some(0));
+        assert_eq!(tab_at(2, TAB_SIZE.0 + 10.0, -10.0), Some(1));
+        // past the last tab, in the gap between two, and on the board underneath
+        assert_eq!(tab_at(2, 2.0 * TAB_SIZE.0 + 20.0, -10.0), None);
+        assert_eq!(tab_at(2, TAB_SIZE.0 + 2.0, -10.0), None);
+        assert_eq!(tab_at(2, 10.0, 10.0), None);
+    }
+
+    #[test]
+    fn the_game_still_fits_the_window_under_the_tabs() {
+        let bottom = SCREEN_SIZE.1 * SCALE - TAB_SIZE.1;
+        assert!((bottom - SCREEN_SIZE.1).abs() < 0.01);
+        assert!(tab_rect(MAX_GAMES - 1).right() <= SCREEN_SIZE.0 * SCALE);
+    }
+}
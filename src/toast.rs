@@ -0,0 +1,367 @@
+/**
+ * Short messages about the game, e.g. a move that was refused or a draw that was offered, stacked
+ * in the top right corner of the window. At most three are shown at once; the rest wait their
+ * turn, and each fades out at the end of its time.
+ *
+ * The opponent's quick-chat reactions go through a queue of their own, drawn beside their name
+ * in a style of their own.
+ */
+use ggez::{graphics, Context, GameResult};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How many toasts are shown at once.
+pub const MAX_VISIBLE: usize = 3;
+
+/// How long a toast takes to fade out, at the end of its time on screen.
+const FADE: Duration = Duration::from_millis(400);
+
+/// Width of a toast, which its text wraps to, and the gap around them.
+const WIDTH: f32 = 320.0;
+const MARGIN: f32 = 8.0;
+
+/// Size of the speech bubble before a reaction.
+const ICON: f32 = 18.0;
+
+/// How much a toast matters, which sets its colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+    /// A quick-chat reaction from the opponent.
+    Reaction,
+}
+
+impl Severity {
+    pub fn colour(self) -> [f32; 3] {
+        match self {
+            Severity::Info => [0.1, 0.1, 0.1],
+            Severity::Warn => [0.55, 0.35, 0.0],
+            Severity::Error => [0.6, 0.1, 0.1],
+            Severity::Reaction => [0.2, 0.35, 0.6],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub text: String,
+    pub severity: Severity,
+    duration: Duration,
+    expires: Option<Instant>, // set once the toast is shown, so waiting doesn't use up its time
+}
+
+impl Toast {
+    /// How opaque the toast is, fading from 1 to 0 over the end of its time.
+    fn alpha(&self, now: Instant) -> f32 {
+        match self.expires {
+            Some(expires) => {
+                let left = expires.saturating_duration_since(now);
+                (left.as_secs_f32() / FADE.as_secs_f32()).min(1.0)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// The toasts on screen, oldest first, and the ones waiting for room.
+#[derive(Default)]
+pub struct ToastQueue {
+    shown: Vec<Toast>,
+    waiting: VecDeque<Toast>,
+}
+
+impl ToastQueue {
+    /// Adds a toast, shown right away if there's room. The same message already on screen or
+    /// waiting starts its time over instead of being shown twice, so clicking again and again
+    /// doesn't fill the queue.
+    pub fn push(
+        &mut self,
+        text: impl Into<String>,
+        severity: Severity,
+        duration: Duration,
+        now: Instant,
+    ) {
+        let text = text.into();
+        if let Some(toast) = self.shown.iter_mut().find(|toast| toast.text == text) {
+            toast.expires = Some(now + duration);
+            return;
+        }
+        if self.waiting.iter().any(|toast| toast.text == text) {
+            return;
+        }
+        self.waiting.push_back(Toast {
+            text,
+            severity,
+            duration,
+            expires: None,
+        });
+        self.update(now);
+    }
+
+    /// Changes what a toast says, e.g. how far something has got, starting its time over. If it's
+    /// gone, the new text is pushed as a new toast.
+    pub fn retext(
+        &mut self,
+        old: &str,
+        text: impl Into<String>,
+        severity: Severity,
+        duration: Duration,
+        now: Instant,
+    ) {
+        let text = text.into();
+        let shown = self.shown.iter_mut().find(|toast| toast.text == old);
+        match shown {
+            Some(toast) => {
+                toast.text = text;
+                toast.severity = severity;
+                toast.expires = Some(now + duration);
+            }
+            None => {
+                self.waiting.retain(|toast| toast.text != old);
+                self.push(text, severity, duration, now);
+            }
+        }
+    }
+
+    /// Drops the toasts whose time is up, and shows waiting ones in their place.
+    pub fn update(&mut self, now: Instant) {
+        self.shown
+            .retain(|toast| toast.expires.is_some_and(|expires| expires > now));
+        while self.shown.len() < MAX_VISIBLE {
+            match self.waiting.pop_front() {
+                Some(mut toast) => {
+                    toast.expires = Some(now + toast.duration);
+                    self.shown.push(toast);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// The toasts on screen, oldest first.
+    pub fn shown(&self) -> &[Toast] {
+        &self.shown
+    }
+
+    /// How many toasts are waiting for room.
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Draws the toasts as rounded boxes down from the top right corner of what's shown, which
+    /// ends at `right`, the oldest at the top.
+    pub fn draw(&self, ctx: &mut Context, now: Instant, right: f32) -> GameResult {
+        let mut y = MARGIN;
+        for toast in &self.shown {
+            let bubble = Bubble::new(ctx, &toast.text, WIDTH, false);
+            let x = right - WIDTH - MARGIN;
+            bubble.draw(ctx, [x, y], toast.severity.colour(), toast.alpha(now))?;
+            y += bubble.size().1 + MARGIN;
+        }
+        Ok(())
+    }
+
+    /// Draws the toasts as reactions, each only as wide as its text with a speech bubble before
+    /// it, down from `top` and ending at `right`, the oldest at the top.
+    pub fn draw_reactions(
+        &self,
+        ctx: &mut Context,
+        now: Instant,
+        right: f32,
+        top: f32,
+    ) -> GameResult {
+        let mut y = top;
+        for toast in &self.shown {
+            let bubble = Bubble::new(ctx, &toast.text, WIDTH, true);
+            let (w, h) = bubble.size();
+            let x = right - w;
+            let [r, g, b] = toast.severity.colour();
+            let alpha = toast.alpha(now);
+            let colour = graphics::Color::new(r, g, b, alpha);
+
+            // a round bubble with its tail towards the text
+            let icon = [x - ICON - 4.0, y + (h - ICON) / 2.0];
+            let round = ICON / 2.0;
+            let speech = graphics::MeshBuilder::new()
+                .circle(
+                    graphics::DrawMode::fill(),
+                    [icon[0] + round, icon[1] + round - 2.0],
+                    round - 2.0,
+                    0.2,
+                    colour,
+                )?
+                .triangles(
+                    &[
+                        [icon[0] + round, icon[1] + round],
+                        [icon[0] + ICON, icon[1] + ICON],
+                        [icon[0] + ICON - 2.0, icon[1] + round - 4.0],
+                    ],
+                    colour,
+                )?
+                .build(ctx)?;
+            graphics::draw(ctx, &speech, graphics::DrawParam::default())?;
+            bubble.draw(ctx, [x, y], [r, g, b], alpha)?;
+            y += h + MARGIN / 2.0;
+        }
+        Ok(())
+    }
+}
+
+/// White text in a rounded box, the way toasts are drawn, and anything else shown briefly over
+/// the game, like a piece's tooltip.
+pub struct Bubble {
+    text: graphics::Text,
+    size: (f32, f32),
+}
+
+impl Bubble {
+    /// `text` wrapped to fit `width`, in a box that wide, or only as wide as the text if `fit`.
+    pub fn new(ctx: &mut Context, text: &str, width: f32, fit: bool) -> Bubble {
+        let mut text = graphics::Text::new(
+            graphics::TextFragment::from(text).scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+        );
+        text.set_bounds([width - 20.0, f32::INFINITY], graphics::Align::Left);
+        let measured = text.dimensions(ctx);
+        let width = if fit { measured.w + 20.0 } else { width };
+        Bubble {
+            text,
+            size: (width, measured.h + 16.0),
+        }
+    }
+
+    pub fn size(&self) -> (f32, f32) {
+        self.size
+    }
+
+    /// Draws it with its top left corner at `at`, in `colour` and as opaque as `alpha`.
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        at: [f32; 2],
+        colour: [f32; 3],
+        alpha: f32,
+    ) -> GameResult {
+        let [r, g, b] = colour;
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(at[0], at[1], self.size.0, self.size.1),
+            8.0,
+            graphics::Color::new(r, g, b, 0.9 * alpha),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(
+            ctx,
+            &self.text,
+            graphics::DrawParam::default()
+                .color(graphics::Color::new(1.0, 1.0, 1.0, alpha))
+                .dest([at[0] + 10.0, at[1] + 8.0]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn texts(queue: &ToastQueue) -> Vec<&str> {
+        queue
+            .shown()
+            .iter()
+            .map(|toast| toast.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn toasts_are_shown_in_order_until_they_expire() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push("Draw offered", Severity::Info, 2 * SECOND, start);
+        queue.push("Connection lost", Severity::Error, 4 * SECOND, start);
+        assert_eq!(texts(&queue), ["Draw offered", "Connection lost"]);
+        assert_eq!(queue.shown()[1].severity, Severity::Error);
+
+        queue.update(start + 3 * SECOND);
+        assert_eq!(texts(&queue), ["Connection lost"]);
+        queue.update(start + 4 * SECOND);
+        assert!(queue.shown().is_empty());
+    }
+
+    #[test]
+    fn toasts_past_the_third_wait_for_room() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        for (i, text) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+            queue.push(text, Severity::Info, (i as u32 + 1) * SECOND, start);
+        }
+        assert_eq!(texts(&queue), ["a", "b", "c"]);
+        assert_eq!(queue.waiting(), 2);
+
+        // the fourth takes the first one's place, with all of its own time still to go
+        queue.update(start + SECOND);
+        assert_eq!(texts(&queue), ["b", "c", "d"]);
+        queue.update(start + 4 * SECOND);
+        assert_eq!(texts(&queue), ["d", "e"]);
+        queue.update(start + 5 * SECOND);
+        assert_eq!(texts(&queue), ["e"]);
+        assert_eq!(queue.waiting(), 0);
+    }
+
+    #[test]
+    fn repeated_toasts_start_their_time_over() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push("It's not your turn", Severity::Warn, 2 * SECOND, start);
+        queue.push(
+            "It's not your turn",
+            Severity::Warn,
+            2 * SECOND,
+            start + SECOND,
+        );
+        assert_eq!(texts(&queue), ["It's not your turn"]);
+        queue.update(start + 2 * SECOND);
+        assert_eq!(texts(&queue), ["It's not your turn"]);
+    }
+
+    #[test]
+    fn progress_is_shown_in_the_same_toast() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push("Exporting, 0%", Severity::Info, 2 * SECOND, start);
+        queue.push("Draw offered", Severity::Info, 2 * SECOND, start);
+        queue.retext(
+            "Exporting, 0%",
+            "Exporting, 50%",
+            Severity::Info,
+            2 * SECOND,
+            start + SECOND,
+        );
+        assert_eq!(texts(&queue), ["Exporting, 50%", "Draw offered"]);
+
+        // once it's gone, the next step comes back as a new toast
+        queue.update(start + 3 * SECOND + SECOND / 2);
+        queue.retext(
+            "Exporting, 50%",
+            "Exported",
+            Severity::Info,
+            2 * SECOND,
+            start + 4 * SECOND,
+        );
+        assert_eq!(texts(&queue), ["Exported"]);
+    }
+
+    #[test]
+    fn toasts_fade_out_at_the_end() {
+        let start = Instant::now();
+        let mut queue = ToastQueue::default();
+        queue.push("Draw agreed", Severity::Info, 2 * SECOND, start);
+        let toast = &queue.shown()[0];
+        assert_eq!(toast.alpha(start + SECOND), 1.0);
+        let half = toast.alpha(start + 2 * SECOND - FADE / 2);
+        assert!((half - 0.5).abs() < 0.01);
+    }
+}
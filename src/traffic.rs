@@ -0,0 +1,129 @@
+/**
+ * The last few messages each way through the connection, timed from when the game started.
+ * The game keeps one for the network info overlay and the desync report, and the transcript
+ * recorder times what it writes down with another. Pushing a message copies it into a line
+ * the buffer already had once it's full, so keeping it costs nothing while nobody looks.
+ */
+use crate::transcript::{Direction, Entry};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// A message, `ms` milliseconds after the buffer was made.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Logged {
+    pub ms: u64,
+    pub direction: Direction,
+    pub text: String,
+}
+
+impl Logged {
+    /// The message as a transcript line.
+    pub fn entry(&self) -> Entry {
+        Entry::Message {
+            ms: self.ms,
+            direction: self.direction,
+            text: self.text.clone(),
+        }
+    }
+}
+
+pub struct Traffic {
+    started: Instant,
+    capacity: usize,
+    messages: VecDeque<Logged>, // oldest first
+    last_in: Option<Instant>,   // when the last message arrived, even if it's since been dropped
+}
+
+impl Traffic {
+    /// An empty buffer keeping the last `capacity` messages, timed from `started`.
+    pub fn new(capacity: usize, started: Instant) -> Traffic {
+        Traffic {
+            started,
+            capacity: capacity.max(1),
+            messages: VecDeque::with_capacity(capacity.max(1)),
+            last_in: None,
+        }
+    }
+
+    /// Notes a message going one way, dropping the oldest once the buffer is full. Trailing
+    /// whitespace is left off.
+    pub fn push(&mut self, direction: Direction, text: &str) -> &Logged {
+        let now = Instant::now();
+        if direction == Direction::In {
+            self.last_in = Some(now);
+        }
+        let mut line = if self.messages.len() == self.capacity {
+            self.messages
+                .pop_front()
+                .map(|old| old.text)
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        line.clear();
+        line.push_str(text.trim_end());
+
+        self.messages.push_back(Logged {
+            ms: now.saturating_duration_since(self.started).as_millis() as u64,
+            direction,
+            text: line,
+        });
+        &self.messages[self.messages.len() - 1]
+    }
+
+    /// Every message kept, oldest first.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Logged> {
+        self.messages.iter()
+    }
+
+    /// The last `count` messages that went one way, oldest first.
+    pub fn last(&self, direction: Direction, count: usize) -> Vec<String> {
+        let mut texts: Vec<String> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|logged| logged.direction == direction)
+            .take(count)
+            .map(|logged| logged.text.clone())
+            .collect();
+        texts.reverse();
+        texts
+    }
+
+    /// How long it's been since the last message arrived, if one has.
+    pub fn since_last_in(&self, now: Instant) -> Option<Duration> {
+        self.last_in.map(|then| now.saturating_duration_since(then))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_last_messages_are_kept() {
+        let mut traffic = Traffic::new(4, Instant::now());
+        assert!(traffic.since_last_in(Instant::now()).is_none());
+        for i in 0..6 {
+            traffic.push(Direction::Out, &format!("room mv {} 6 4 4 4\n", i));
+            traffic.push(Direction::In, &format!("room mv {} 6 4 4 4\n", i));
+        }
+
+        let kept: Vec<&str> = traffic.iter().map(|logged| logged.text.as_str()).collect();
+        assert_eq!(
+            kept,
+            [
+                "room mv 4 6 4 4 4",
+                "room mv 4 6 4 4 4",
+                "room mv 5 6 4 4 4",
+                "room mv 5 6 4 4 4"
+            ]
+        );
+        assert_eq!(
+            traffic.last(Direction::In, 5),
+            ["room mv 4 6 4 4 4", "room mv 5 6 4 4 4"]
+        );
+        assert_eq!(traffic.last(Direction::Out, 1), ["room mv 5 6 4 4 4"]);
+        assert!(traffic.since_last_in(Instant::now()).is_some());
+    }
+}
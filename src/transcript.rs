@@ -0,0 +1,406 @@
+/**
+ * Network transcripts, for reproducing desyncs. With `--record`, every message between the GUI
+ * and whoever it's playing is appended to a file, and `--replay-net` plays a file's incoming
+ * messages back into a game instead of connecting anywhere.
+ *
+ * The recorder sits on the channels every session talks through, so it covers the server, the
+ * bots and hotseat games alike. A transcript is JSON lines: one line describing the session,
+ * then one per message, e.g.
+ *
+ * ```text
+ * {"ms":0,"dir":"session","room":"myroom","colour":"white","hotseat":false}
+ * {"ms":5230,"dir":"out","msg":"myroom mv 2 6 4 4 4"}
+ * {"ms":5231,"dir":"in","msg":"myroom mv 2 6 4 4 4"}
+ * ```
+ */
+use crate::net::CHANNEL_CAPACITY;
+use crate::protocol;
+use crate::session::Session;
+use crate::traffic::Traffic;
+use chess_template::Colour;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Which way a message went.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    In,  // from the opponent or server to the GUI
+    Out, // from the GUI to the opponent or server
+}
+
+/// A line of a transcript.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Entry {
+    /// Starts a recording: the game the messages belong to.
+    Session {
+        room: String,
+        colour: Colour,
+        hotseat: bool,
+    },
+    /// A message, `ms` milliseconds after the recording started.
+    Message {
+        ms: u64,
+        direction: Direction,
+        text: String,
+    },
+}
+
+/// A transcript line as it's written: the fields of both kinds of entry, with those the entry
+/// doesn't have left out.
+#[derive(Serialize, Deserialize)]
+struct Line {
+    ms: u64,
+    dir: Dir,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    room: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    colour: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hotseat: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msg: Option<String>,
+}
+
+/// What a line is: the session, or a message going one way.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Dir {
+    Session,
+    In,
+    Out,
+}
+
+impl Entry {
+    /// The entry as a line of JSON, without the newline.
+    pub fn to_line(&self) -> String {
+        let line = match self {
+            Entry::Session {
+                room,
+                colour,
+                hotseat,
+            } => Line {
+                ms: 0,
+                dir: Dir::Session,
+                room: Some(room.clone()),
+                colour: Some(protocol::colour_name(*colour).to_string()),
+                hotseat: Some(*hotseat),
+                msg: None,
+            },
+            Entry::Message {
+                ms,
+                direction,
+                text,
+            } => Line {
+                ms: *ms,
+                dir: match direction {
+                    Direction::In => Dir::In,
+                    Direction::Out => Dir::Out,
+                },
+                room: None,
+                colour: None,
+                hotseat: None,
+                msg: Some(text.trim_end().to_string()),
+            },
+        };
+        serde_json::to_string(&line).expect("a line is only strings, numbers and booleans")
+    }
+
+    /// Parses a line written by `to_line`.
+    pub fn parse(line: &str) -> Result<Entry, String> {
+        let line: Line = serde_json::from_str(line).map_err(|err| err.to_string())?;
+        let missing = |key: &str| format!("missing field {:?}", key);
+
+        let direction = match line.dir {
+            Dir::Session => {
+                let colour = line.colour.ok_or_else(|| missing("colour"))?;
+                return Ok(Entry::Session {
+                    room: line.room.ok_or_else(|| missing("room"))?,
+                    colour: protocol::parse_colour(&colour).map_err(|err| err.to_string())?,
+                    hotseat: line.hotseat.unwrap_or(false),
+                });
+            }
+            Dir::In => Direction::In,
+            Dir::Out => Direction::Out,
+        };
+        Ok(Entry::Message {
+            ms: line.ms,
+            direction,
+            text: line.msg.ok_or_else(|| missing("msg"))?,
+        })
+    }
+}
+
+/// Appends entries to a transcript file, timing messages from when it was created.
+struct Recorder {
+    file: File,
+    traffic: Traffic,
+}
+
+impl Recorder {
+    fn write(&mut self, entry: &Entry) {
+        if let Err(err) = writeln!(self.file, "{}", entry.to_line()) {
+            warn!("Couldn't write to the network transcript: {}", err);
+        }
+    }
+
+    fn message(&mut self, direction: Direction, text: &str) {
+        let entry = self.traffic.push(direction, text).entry();
+        self.write(&entry);
+    }
+}
+
+/// Records every message of a session to the end of a transcript file. The returned session
+/// talks to the same opponent, through threads that write each message down as it passes.
+/// If the file can't be opened, the game goes on unrecorded.
+pub fn record(session: Session, path: &Path) -> Session {
+    let file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            warn!("Couldn't open {} to record to: {}", path.display(), err);
+            return session;
+        }
+    };
+    let mut recorder = Recorder {
+        file,
+        traffic: Traffic::new(1, Instant::now()),
+    };
+    recorder.write(&Entry::Session {
+        room: session.room_name.clone(),
+        colour: session.color,
+        hotseat: session.hotseat,
+    });
+    info!("Recording network messages to {}", path.display());
+    let recorder = Arc::new(Mutex::new(recorder));
+
+    // from the GUI, through the recorder, to the opponent
+    let (sender, from_gui) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+    let to_opponent = session.sender;
+    let outgoing = Arc::clone(&recorder);
+    thread::spawn(move || {
+        for msg in from_gui {
+            if let Ok(mut recorder) = outgoing.lock() {
+                recorder.message(Direction::Out, &msg);
+            }
+            if to_opponent.send(msg).is_err() {
+                break;
+            }
+        }
+    });
+
+    // from the opponent, through the recorder, to the GUI
+    let (to_gui, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+    let from_opponent = session.receiver;
+    thread::spawn(move || {
+        for msg in from_opponent {
+            if let Ok(mut recorder) = recorder.lock() {
+                recorder.message(Direction::In, &msg);
+            }
+            if to_gui.send(msg).is_err() {
+                break;
+            }
+        }
+    });
+
+    Session {
+        sender,
+        receiver,
+        ..session
+    }
+}
+
+/// Reads a transcript. Only the first recording in the file is kept, if several were appended.
+pub fn load(path: &Path) -> Result<(Entry, Vec<Entry>), String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Couldn't read transcript {}: {}", path.display(), err))?;
+    parse(&text).map_err(|err| format!("Bad transcript {}: {}", path.display(), err))
+}
+
+/// The session line and the messages after it, up to the next session line.
+fn parse(text: &str) -> Result<(Entry, Vec<Entry>), String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Entry::parse(line).map_err(|err| format!("line {}: {}", i + 1, err)));
+
+    let session = match lines.next() {
+        Some(Ok(session @ Entry::Session { .. })) => session,
+        Some(Ok(_)) => return Err("doesn't start with a session line".to_string()),
+        Some(Err(err)) => return Err(err),
+        None => return Err("it's empty".to_string()),
+    };
+
+    let mut messages = Vec::new();
+    for entry in lines {
+        match entry? {
+            Entry::Session { .. } => break,
+            message => messages.push(message),
+        }
+    }
+    Ok((session, messages))
+}
+
+/// Plays a transcript back: a session whose incoming messages are the recorded ones, at the
+/// pace they were recorded or, with `fast`, all at once. What the GUI sends goes nowhere.
+pub fn replay_session(path: &Path, fast: bool) -> Result<Session, String> {
+    let (session, messages) = load(path)?;
+    let (room_name, color, hotseat) = match session {
+        Entry::Session {
+            room,
+            colour,
+            hotseat,
+        } => (room, colour, hotseat),
+        Entry::Message { .. } => unreachable!("load starts with a session line"),
+    };
+    let incoming = messages
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Entry::Message {
+                ms,
+                direction: Direction::In,
+                text,
+            } => Some((ms, text)),
+            _ => None,
+        })
+        .collect::<Vec<(u64, String)>>();
+    info!(
+        "Replaying {} messages from {}",
+        incoming.len(),
+        path.display()
+    );
+    Ok(playback(room_name, color, hotseat, incoming, fast))
+}
+
+/// A session that receives `incoming`, each message `ms` milliseconds after it starts or, with
+/// `fast`, all at once. What the GUI sends goes nowhere.
+pub fn playback(
+    room_name: String,
+    color: Colour,
+    hotseat: bool,
+    incoming: Vec<(u64, String)>,
+    fast: bool,
+) -> Session {
+    // the GUI's own messages are dropped; once it stops sending, the replay is over
+    let (sender, from_gui) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+    let (done, gui_gone) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        for msg in from_gui {
+            debug!("not sending {:?} while replaying", msg.trim_end());
+        }
+        drop(done);
+    });
+
+    let (to_gui, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+    thread::spawn(move || {
+        let started = Instant::now();
+        for (ms, text) in incoming {
+            if !fast {
+                let due = started + Duration::from_millis(ms);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+            if to_gui.send(text).is_err() {
+                return;
+            }
+        }
+        // keep the channel open, so the end of the transcript doesn't look like a lost connection
+        let _ = gui_gone.recv();
+    });
+
+    Session {
+        sender,
+        receiver,
+        room_name,
+        color,
+        difficulty: None,
+        hotseat,
+        server: None,
+        opponent: None,
+        opponent_avatar: None,
+        correspondence: None,
+        replay: true,
+        recorded: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip() {
+        let entries = [
+            Entry::Session {
+                room: "my \"room\"".to_string(),
+                colour: Colour::Black,
+                hotseat: false,
+            },
+            Entry::Message {
+                ms: 5230,
+                direction: Direction::Out,
+                text: "room mv 2 6 4 4 4".to_string(),
+            },
+            Entry::Message {
+                ms: 0,
+                direction: Direction::In,
+                text: "odd \\ text\twith\u{1}control".to_string(),
+            },
+        ];
+        for entry in entries {
+            assert_eq!(Entry::parse(&entry.to_line()), Ok(entry));
+        }
+    }
+
+    #[test]
+    fn lines_are_json() {
+        let entry = Entry::Message {
+            ms: 12,
+            direction: Direction::In,
+            text: "room reset ".to_string(),
+        };
+        assert_eq!(
+            entry.to_line(),
+            r#"{"ms":12,"dir":"in","msg":"room reset"}"#
+        );
+        assert!(Entry::parse(r#" { "dir" : "out", "msg" : "a", "ms" : 3 } "#).is_ok());
+        assert!(Entry::parse(r#"{"ms":12,"dir":"sideways","msg":"a"}"#).is_err());
+        assert!(Entry::parse(r#"{"ms":12,"dir":"in","msg":"a"} trailing"#).is_err());
+        assert!(Entry::parse("not json").is_err());
+    }
+
+    #[test]
+    fn only_the_first_recording_is_replayed() {
+        let session = |room: &str| Entry::Session {
+            room: room.to_string(),
+            colour: Colour::White,
+            hotseat: false,
+        };
+        let message = |text: &str| Entry::Message {
+            ms: 1,
+            direction: Direction::In,
+            text: text.to_string(),
+        };
+        let text = [
+            session("first"),
+            message("first reset"),
+            session("second"),
+            message("second reset"),
+        ]
+        .iter()
+        .map(|entry| entry.to_line() + "\n")
+        .collect::<String>();
+
+        let (first, messages) = parse(&text).unwrap();
+        assert_eq!(first, session("first"));
+        assert_eq!(messages, vec![message("first reset")]);
+
+        assert!(parse("").is_err());
+        assert!(parse(&(message("no session").to_line() + "\n")).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+/**
+ * The tutor, which flags moves that might lose material in games against the computer or across
+ * the desk. Each move is checked on a thread of its own with the computer opponent's evaluation,
+ * so a slow check never holds up drawing, and a move that loses too much is offered back. It's
+ * off unless turned on in the settings, and never checks online games.
+ */
+use crate::ai;
+use chess_template::{Game, Position};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How many centipawns a move has to lose to be flagged: more than a pawn and a half, so the
+/// piece-square bonuses alone never do.
+pub const BLUNDER_SWING: i32 = 150;
+
+/// How long a flagged move is offered back.
+pub const TAKEBACK_OFFER: Duration = Duration::from_secs(6);
+
+/// Whether the tutor is on, the move it's checking, and the one it offers back.
+pub struct Tutor {
+    pub on: bool,
+    check: Option<Check>,          // our last move, while it's checked
+    offer: Option<(u32, Instant)>, // the counter to go back to, and until when
+}
+
+/// A move being checked.
+pub struct Check {
+    pub counter: u32, // the move counter after the move
+    pub from: Position,
+    pub to: Position,
+    result: Receiver<i32>,
+}
+
+impl Check {
+    /// Starts checking the move from `from` to `to` in `game`, the position before it.
+    pub fn start(game: Game, from: Position, to: Position, counter: u32) -> Check {
+        let (sender, result) = mpsc::sync_channel(1);
+        thread::spawn(move || {
+            if let Some(swing) = ai::swing(&game, from, to) {
+                let _ = sender.send(swing);
+            }
+        });
+        Check {
+            counter,
+            from,
+            to,
+            result,
+        }
+    }
+
+    /// How much the move loses once it's worked out, or None until then. A move that couldn't be
+    /// checked never gets an answer.
+    pub fn poll(&self) -> Option<i32> {
+        self.result.try_recv().ok()
+    }
+}
+
+impl Tutor {
+    pub fn new(on: bool) -> Tutor {
+        Tutor {
+            on,
+            check: None,
+            offer: None,
+        }
+    }
+
+    /// Notes our move from `from` to `to`, taking the game to `counter`, and checks it in
+    /// `before`, the position before it, if that's given. The last move is ours now, so only
+    /// this one can be offered back.
+    pub fn played(&mut self, before: Option<Game>, from: Position, to: Position, counter: u32) {
+        self.offer = None;
+        if let Some(game) = before {
+            self.check = Some(Check::start(game, from, to, counter));
+        }
+    }
+
+    /// Offers the move checked back if it's found `now` to lose material and it's still the one
+    /// played, going by the game's `moves`, and withdraws the offer once its time is up. Returns
+    /// how much it loses, when it's offered.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        moves: &[(Position, Position)],
+        game_over: bool,
+    ) -> Option<i32> {
+        if self.offer.is_some_and(|(_, until)| now >= until) {
+            self.offer = None;
+        }
+        let swing = self.check.as_ref().and_then(Check::poll)?;
+        let check = self.check.take()?;
+
+        // it may have been taken back, or the game restarted, while it was checked
+        let played = moves
+            .get((check.counter as usize).saturating_sub(2))
+            .is_some_and(|&played| played == (check.from, check.to));
+        if swing < BLUNDER_SWING || !played || game_over {
+            return None;
+        }
+        self.offer = Some((check.counter - 1, now + TAKEBACK_OFFER));
+        Some(swing)
+    }
+
+    /// Whether a move is offered back.
+    pub fn offering(&self) -> bool {
+        self.offer.is_some()
+    }
+
+    /// Whether a move is being checked.
+    pub fn checking(&self) -> bool {
+        self.check.is_some()
+    }
+
+    /// The counter to go back to, taking the move offered back, if there's one.
+    pub fn take_offer(&mut self) -> Option<u32> {
+        self.offer.take().map(|(counter, _)| counter)
+    }
+
+    /// Forgets the move checked and any offer, e.g. for a new game.
+    pub fn reset(&mut self) {
+        self.check = None;
+        self.offer = None;
+    }
+}
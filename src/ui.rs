@@ -0,0 +1,966 @@
+/**
+ * Widgets shared by the screens. A `Buttons` holds a screen's buttons and works out which one is
+ * hovered, pressed, focused or picked from the input the scene passes on; the scene only asks
+ * which action was picked. A `TextInput` is a single-line field to type in, and a `ScrollPanel`
+ * shows content taller than itself. Layout helpers place widgets in rows and columns.
+ */
+use ggez::event::{KeyCode, KeyMods, MouseButton};
+use ggez::{graphics, Context, GameResult};
+use log::warn;
+use std::time::{Duration, Instant};
+
+/// Colours every button is drawn in, so the screens look alike.
+const NORMAL: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const HIGHLIGHTED: [f32; 4] = [0.3, 0.6, 0.3, 1.0]; // hovered or focused
+const PRESSED: [f32; 4] = [0.2, 0.45, 0.2, 1.0];
+const DISABLED: [f32; 4] = [0.42, 0.42, 0.42, 1.0];
+const LABEL: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const DISABLED_LABEL: [f32; 4] = [0.65, 0.65, 0.65, 1.0];
+
+/// Size of the text on buttons.
+const LABEL_SIZE: f32 = 26.0;
+
+/// Size of the text in text inputs, and how far it's inset from the edges.
+const INPUT_TEXT_SIZE: f32 = 24.0;
+const INPUT_PADDING: f32 = 8.0;
+
+/// How long the caret is shown, then hidden, while it blinks.
+const CARET_BLINK: Duration = Duration::from_millis(530);
+
+/// Width of a scroll panel's scrollbar, along its right edge.
+const SCROLLBAR_WIDTH: f32 = 8.0;
+
+/// The thumb never gets shorter than this, so it can still be grabbed.
+const MIN_THUMB: f32 = 24.0;
+
+/// How far one notch of the mouse wheel scrolls.
+const WHEEL_STEP: f32 = 40.0;
+
+/// How far outside a button a tap still picks it, when buttons are set up for touch.
+const TOUCH_MARGIN: f32 = 16.0;
+
+/// A button that does something when clicked, or when focused and Enter is pressed.
+pub struct Button<A> {
+    pub rect: graphics::Rect,
+    pub label: String,
+    pub action: A,
+    pub enabled: bool, // disabled buttons are drawn greyed out and can't be picked
+}
+
+impl<A> Button<A> {
+    pub fn new(rect: graphics::Rect, label: impl Into<String>, action: A) -> Button<A> {
+        Button {
+            rect,
+            label: label.into(),
+            action,
+            enabled: true,
+        }
+    }
+}
+
+/// The buttons on a screen. Buttons added later are drawn over earlier ones, and take the clicks
+/// where they overlap.
+pub struct Buttons<A> {
+    buttons: Vec<Button<A>>,
+    focused: Option<usize>, // picked by Enter, moved with Tab
+    hovered: Option<usize>, // under the mouse
+    pressed: Option<usize>, // the mouse went down on it and hasn't come up yet
+    chosen: Option<A>,      // picked, until the scene acts on it
+    touch: bool,            // taps just outside a button pick it too
+    focusable: bool,        // the keyboard can pick them, rather than having shortcuts of its own
+}
+
+impl<A: Copy> Buttons<A> {
+    /// The first enabled button starts out focused.
+    pub fn new(buttons: Vec<Button<A>>) -> Buttons<A> {
+        let focused = buttons.iter().position(|button| button.enabled);
+        Buttons {
+            buttons,
+            focused,
+            hovered: None,
+            pressed: None,
+            chosen: None,
+            touch: false,
+            focusable: true,
+        }
+    }
+
+    /// Buttons only the mouse or a finger picks, none of them ever focused, e.g. ones standing in
+    /// for keyboard shortcuts.
+    pub fn unfocusable(buttons: Vec<Button<A>>) -> Buttons<A> {
+        Buttons {
+            focused: None,
+            focusable: false,
+            ..Buttons::new(buttons)
+        }
+    }
+
+    /// Makes the buttons easier to hit with a finger, taking taps up to `TOUCH_MARGIN` outside
+    /// them, or back to just their rectangles for the mouse.
+    pub fn set_touch(&mut self, touch: bool) {
+        self.touch = touch;
+    }
+
+    /// Whether a point is on a button, or near enough to one for touch.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.at(x, y).is_some()
+    }
+
+    /// The action picked since the last call, if any.
+    pub fn take_choice(&mut self) -> Option<A> {
+        self.chosen.take()
+    }
+
+    /// The action of the focused button.
+    pub fn focused(&self) -> Option<A> {
+        self.focused.map(|i| self.buttons[i].action)
+    }
+
+    /// The action of the enabled button under the mouse, if any.
+    pub fn hovered(&self) -> Option<A> {
+        self.hovered.map(|i| self.buttons[i].action)
+    }
+
+    /// Enables or disables every button with an action. A disabled button loses the focus.
+    pub fn set_enabled(&mut self, action: A, enabled: bool)
+    where
+        A: PartialEq,
+    {
+        for button in self.buttons.iter_mut().filter(|b| b.action == action) {
+            button.enabled = enabled;
+        }
+        if self.focused.is_some_and(|i| !self.buttons[i].enabled) {
+            self.focus_next();
+        }
+    }
+
+    /// Relabels every button with an action, e.g. one that toggles a setting.
+    pub fn set_label(&mut self, action: A, label: &str)
+    where
+        A: PartialEq,
+    {
+        for button in self.buttons.iter_mut().filter(|b| b.action == action) {
+            button.label = label.to_string();
+        }
+    }
+
+    /// Picks an action as if its button was clicked, unless it's disabled.
+    pub fn pick(&mut self, action: A)
+    where
+        A: PartialEq,
+    {
+        if self.buttons.iter().any(|b| b.enabled && b.action == action) {
+            self.chosen = Some(action);
+        }
+    }
+
+    /// The button at a point, if any: the topmost one, even if it's disabled. For touch, a point
+    /// on no button goes to the topmost one it's near.
+    fn at(&self, x: f32, y: f32) -> Option<usize> {
+        let on = |margin: f32| {
+            (0..self.buttons.len()).rev().find(|&i| {
+                let rect = self.buttons[i].rect;
+                x >= rect.x - margin
+                    && x <= rect.x + rect.w + margin
+                    && y >= rect.y - margin
+                    && y <= rect.y + rect.h + margin
+            })
+        };
+        match on(0.0) {
+            None if self.touch => on(TOUCH_MARGIN),
+            found => found,
+        }
+    }
+
+    /// The enabled button at a point, if the topmost one there is enabled.
+    fn enabled_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.at(x, y).filter(|&i| self.buttons[i].enabled)
+    }
+
+    /// Moves the focus to the next enabled button, wrapping around.
+    pub fn focus_next(&mut self) {
+        self.move_focus(true);
+    }
+
+    /// Moves the focus to the previous enabled button, wrapping around.
+    pub fn focus_previous(&mut self) {
+        self.move_focus(false);
+    }
+
+    fn move_focus(&mut self, forward: bool) {
+        let count = self.buttons.len();
+        if count == 0 || !self.focusable {
+            return;
+        }
+        let start = self.focused.unwrap_or(if forward { count - 1 } else { 0 });
+        let step = if forward { 1 } else { count - 1 };
+        self.focused = (1..=count)
+            .map(|n| (start + n * step) % count)
+            .find(|&i| self.buttons[i].enabled);
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        for (i, button) in self.buttons.iter().enumerate() {
+            let (colour, label_colour) = if !button.enabled {
+                (DISABLED, DISABLED_LABEL)
+            } else if self.pressed == Some(i) && self.hovered == Some(i) {
+                (PRESSED, LABEL)
+            } else if self.hovered == Some(i) || self.focused == Some(i) {
+                (HIGHLIGHTED, LABEL)
+            } else {
+                (NORMAL, LABEL)
+            };
+            let background = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                button.rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(button.label.as_str()).scale(graphics::PxScale {
+                    x: LABEL_SIZE,
+                    y: LABEL_SIZE,
+                }),
+            );
+            let label_size = label.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color(label_colour.into())
+                    .dest([
+                        button.rect.x + (button.rect.w - label_size.w) / 2.0,
+                        button.rect.y + (button.rect.h - label_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.hovered = self.enabled_at(x, y);
+    }
+
+    pub fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        if button == MouseButton::Left {
+            self.pressed = self.enabled_at(x, y);
+        }
+    }
+
+    /// A click picks a button if the mouse went down and came up on it.
+    pub fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        if button != MouseButton::Left {
+            return;
+        }
+        if let Some(i) = self
+            .pressed
+            .take()
+            .filter(|&i| self.enabled_at(x, y) == Some(i))
+        {
+            if self.focusable {
+                self.focused = Some(i);
+            }
+            self.chosen = Some(self.buttons[i].action);
+        }
+    }
+
+    /// Tab and Shift+Tab move the focus, and Enter or Space picks the focused button. Returns
+    /// whether the key was used.
+    pub fn key_down_event(&mut self, key: KeyCode, mods: KeyMods) -> bool {
+        match key {
+            KeyCode::Tab if mods.contains(KeyMods::SHIFT) => self.focus_previous(),
+            KeyCode::Tab => self.focus_next(),
+            KeyCode::Return | KeyCode::NumpadEnter | KeyCode::Space => match self.focused() {
+                Some(action) => self.chosen = Some(action),
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// A change to the text in a text input.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edit<'a> {
+    Type(&'a str), // typed or pasted at the caret
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+/// Applies an edit to `text` with the caret `cursor` characters in, giving the new text and
+/// caret. Typed characters that aren't `allowed`, and those past `max_length`, are dropped.
+pub fn edit(
+    text: &str,
+    cursor: usize,
+    change: Edit,
+    max_length: usize,
+    allowed: fn(char) -> bool,
+) -> (String, usize) {
+    let mut chars: Vec<char> = text.chars().collect();
+    let cursor = cursor.min(chars.len());
+    match change {
+        Edit::Type(typed) => {
+            let room = max_length.saturating_sub(chars.len());
+            let typed: Vec<char> = typed
+                .chars()
+                .filter(|&c| !c.is_control() && allowed(c))
+                .take(room)
+                .collect();
+            let count = typed.len();
+            chars.splice(cursor..cursor, typed);
+            (chars.into_iter().collect(), cursor + count)
+        }
+        Edit::Backspace if cursor > 0 => {
+            chars.remove(cursor - 1);
+            (chars.into_iter().collect(), cursor - 1)
+        }
+        Edit::Delete if cursor < chars.len() => {
+            chars.remove(cursor);
+            (chars.into_iter().collect(), cursor)
+        }
+        Edit::Left => (text.to_string(), cursor.saturating_sub(1)),
+        Edit::Right => (text.to_string(), (cursor + 1).min(chars.len())),
+        Edit::Home => (text.to_string(), 0),
+        Edit::End => (text.to_string(), chars.len()),
+        Edit::Backspace | Edit::Delete => (text.to_string(), cursor),
+    }
+}
+
+/// Any character may be typed.
+pub fn any_char(_: char) -> bool {
+    true
+}
+
+/// Anything but control characters, like the carriage return Enter types.
+pub fn printable(c: char) -> bool {
+    !c.is_control()
+}
+
+/// Anything but spaces, e.g. for room names, since the protocol separates words with spaces.
+pub fn no_spaces(c: char) -> bool {
+    !c.is_whitespace()
+}
+
+/// A single-line text field. Whether it has the focus is up to the screen it's on, which passes
+/// on key presses and typed characters only while it does.
+pub struct TextInput {
+    text: String,
+    cursor: usize,             // caret position, in characters from the start
+    max_length: usize,         // in characters
+    allowed: fn(char) -> bool, // characters that may be typed
+    edited_at: Instant,        // the caret stays lit for a moment after an edit
+}
+
+impl TextInput {
+    pub fn new(text: impl Into<String>, max_length: usize, allowed: fn(char) -> bool) -> TextInput {
+        let mut input = TextInput {
+            text: String::new(),
+            cursor: 0,
+            max_length,
+            allowed,
+            edited_at: Instant::now(),
+        };
+        input.set_text(&text.into());
+        input
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text, filtered like typing would, with the caret at its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.cursor = 0;
+        self.apply(Edit::Type(text));
+    }
+
+    fn apply(&mut self, change: Edit) {
+        (self.text, self.cursor) = edit(
+            &self.text,
+            self.cursor,
+            change,
+            self.max_length,
+            self.allowed,
+        );
+        self.edited_at = Instant::now();
+    }
+
+    /// Pastes the first line of the clipboard at the caret.
+    fn paste(&mut self) {
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => self.apply(Edit::Type(text.lines().next().unwrap_or(""))),
+            Err(err) => warn!("Couldn't paste from the clipboard: {}", err),
+        }
+    }
+
+    /// Draws the field in `rect`, with the caret if it has the focus. While it's empty, the
+    /// placeholder is shown in grey.
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        rect: graphics::Rect,
+        focused: bool,
+        placeholder: &str,
+    ) -> GameResult {
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            rect,
+            [1.0, 1.0, 1.0, 1.0].into(),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+        if focused {
+            let outline = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::stroke(3.0),
+                rect,
+                [0.2, 0.4, 1.0, 1.0].into(),
+            )?;
+            graphics::draw(ctx, &outline, graphics::DrawParam::default())?;
+        }
+
+        let (shown, colour) = match self.text.as_str() {
+            "" => (placeholder, [0.6, 0.6, 0.6, 1.0]),
+            text => (text, [0.0, 0.0, 0.0, 1.0]),
+        };
+        let scale = graphics::PxScale {
+            x: INPUT_TEXT_SIZE,
+            y: INPUT_TEXT_SIZE,
+        };
+        let text = graphics::Text::new(graphics::TextFragment::from(shown).scale(scale));
+        let dest = [rect.x + INPUT_PADDING, rect.y + INPUT_PADDING];
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .color(colour.into())
+                .dest(dest),
+        )?;
+
+        let blink = CARET_BLINK.as_millis();
+        let lit = self.edited_at.elapsed().as_millis() % (2 * blink) < blink;
+        if focused && lit {
+            let before: String = self.text.chars().take(self.cursor).collect();
+            let x = graphics::Text::new(graphics::TextFragment::from(before).scale(scale))
+                .dimensions(ctx)
+                .w;
+            let caret = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(dest[0] + x, dest[1], 2.0, INPUT_TEXT_SIZE),
+                [0.0, 0.0, 0.0, 1.0].into(),
+            )?;
+            graphics::draw(ctx, &caret, graphics::DrawParam::default())?;
+        }
+        Ok(())
+    }
+
+    /// Moves the caret and deletes, and Ctrl+V pastes. Returns whether the key was used.
+    pub fn key_down_event(&mut self, key: KeyCode, mods: KeyMods) -> bool {
+        let change = match key {
+            KeyCode::V if mods.contains(KeyMods::CTRL) => {
+                self.paste();
+                return true;
+            }
+            KeyCode::Back => Edit::Backspace,
+            KeyCode::Delete => Edit::Delete,
+            KeyCode::Left => Edit::Left,
+            KeyCode::Right => Edit::Right,
+            KeyCode::Home => Edit::Home,
+            KeyCode::End => Edit::End,
+            _ => return false,
+        };
+        self.apply(change);
+        true
+    }
+
+    pub fn text_input_event(&mut self, character: char) {
+        self.apply(Edit::Type(character.encode_utf8(&mut [0; 4])));
+    }
+}
+
+/// A panel that scrolls content taller than itself, with the mouse wheel or by dragging the
+/// thumb of the scrollbar along its right edge. The screen says how tall the content is and draws
+/// it as if the panel were tall enough; the panel shifts and clips it.
+///
+/// A panel that sticks to the bottom keeps the newest content in view as more is added, until
+/// it's scrolled up.
+pub struct ScrollPanel {
+    rect: graphics::Rect,
+    content_height: f32,
+    offset: f32, // how far the content is scrolled up
+    stick_to_bottom: bool,
+    pinned: bool,      // at the bottom, and staying there as the content grows
+    hovered: bool,     // the wheel only scrolls the panel under the mouse
+    grab: Option<f32>, // while the thumb is dragged, how far down it was grabbed
+    canvas: Option<graphics::Canvas>, // the content is drawn here to clip it to the panel
+}
+
+impl ScrollPanel {
+    pub fn new(rect: graphics::Rect, stick_to_bottom: bool) -> ScrollPanel {
+        ScrollPanel {
+            rect,
+            content_height: 0.0,
+            offset: 0.0,
+            stick_to_bottom,
+            pinned: stick_to_bottom,
+            hovered: false,
+            grab: None,
+            canvas: None,
+        }
+    }
+
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    /// Whether the thumb is being dragged.
+    pub fn dragging(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    /// How far the content can be scrolled, which is nowhere if it fits.
+    fn max_offset(&self) -> f32 {
+        (self.content_height - self.rect.h).max(0.0)
+    }
+
+    /// Says how tall the content is now. A pinned panel follows it to the bottom.
+    pub fn set_content_height(&mut self, height: f32) {
+        self.content_height = height;
+        if self.pinned {
+            self.offset = self.max_offset();
+        } else {
+            self.scroll_to(self.offset);
+        }
+    }
+
+    /// Scrolls to `offset`, as far as the content goes. Scrolling to the bottom pins a panel that
+    /// sticks to it, and scrolling away unpins it.
+    pub fn scroll_to(&mut self, offset: f32) {
+        self.offset = offset.clamp(0.0, self.max_offset());
+        self.pinned = self.stick_to_bottom && self.offset >= self.max_offset() - 0.5;
+    }
+
+    pub fn scroll_by(&mut self, distance: f32) {
+        self.scroll_to(self.offset + distance);
+    }
+
+    /// The scrollbar along the right edge.
+    fn track(&self) -> graphics::Rect {
+        graphics::Rect::new(
+            self.rect.right() - SCROLLBAR_WIDTH,
+            self.rect.y,
+            SCROLLBAR_WIDTH,
+            self.rect.h,
+        )
+    }
+
+    /// The thumb, as long as the track in proportion to how much of the content is in view,
+    /// and as far down it as the view is. There's none while everything fits.
+    fn thumb(&self) -> Option<graphics::Rect> {
+        let max = self.max_offset();
+        if max <= 0.0 {
+            return None;
+        }
+        let track = self.track();
+        let length =
+            (track.h * self.rect.h / self.content_height).clamp(MIN_THUMB.min(track.h), track.h);
+        let top = track.y + (track.h - length) * self.offset / max;
+        Some(graphics::Rect::new(track.x, top, track.w, length))
+    }
+
+    /// The offset that puts the top of the thumb at `top`.
+    fn offset_for_thumb(&self, top: f32) -> f32 {
+        match self.thumb() {
+            Some(thumb) if thumb.h < self.rect.h => {
+                (top - self.rect.y) / (self.rect.h - thumb.h) * self.max_offset()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Draws the content clipped to the panel, and the scrollbar over it. `content` draws at
+    /// the content's own coordinates, with its top at the top of the panel.
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        content: impl FnOnce(&mut Context) -> GameResult,
+    ) -> GameResult {
+        if self.canvas.is_none() {
+            let format = graphics::get_window_color_format(ctx);
+            self.canvas = Some(graphics::Canvas::new(
+                ctx,
+                self.rect.w as u16,
+                self.rect.h as u16,
+                ggez::conf::NumSamples::One,
+                format,
+            )?);
+        }
+        let canvas = self.canvas.as_ref().expect("created above");
+
+        // ggez has no scissor rects, so the content goes through a canvas the panel's size
+        let screen = graphics::screen_coordinates(ctx);
+        graphics::set_canvas(ctx, Some(canvas));
+        graphics::set_screen_coordinates(
+            ctx,
+            graphics::Rect::new(
+                self.rect.x,
+                self.rect.y + self.offset,
+                self.rect.w,
+                self.rect.h,
+            ),
+        )?;
+        graphics::clear(ctx, graphics::Color::new(0.0, 0.0, 0.0, 0.0));
+        let drawn = content(ctx);
+        graphics::set_canvas(ctx, None);
+        graphics::set_screen_coordinates(ctx, screen)?;
+        drawn?;
+        graphics::draw(
+            ctx,
+            canvas,
+            graphics::DrawParam::default().dest([self.rect.x, self.rect.y]),
+        )?;
+
+        if let Some(thumb) = self.thumb() {
+            let track = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                self.track(),
+                [0.0, 0.0, 0.0, 0.1].into(),
+            )?;
+            graphics::draw(ctx, &track, graphics::DrawParam::default())?;
+
+            let colour = if self.grab.is_some() {
+                [0.2, 0.2, 0.2, 0.8]
+            } else {
+                [0.4, 0.4, 0.4, 0.6]
+            };
+            let thumb = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                thumb,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &thumb, graphics::DrawParam::default())?;
+        }
+        Ok(())
+    }
+
+    /// Notes whether the mouse is over the panel, and moves the thumb while it's dragged.
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.hovered = self.rect.contains([x, y]);
+        if let Some(grab) = self.grab {
+            let offset = self.offset_for_thumb(y - grab);
+            self.scroll_to(offset);
+        }
+    }
+
+    /// Grabs the thumb, or pages towards the click on the track. Returns whether the click was
+    /// on the scrollbar.
+    pub fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) -> bool {
+        let thumb = match self.thumb() {
+            Some(thumb) if button == MouseButton::Left && self.track().contains([x, y]) => thumb,
+            _ => return false,
+        };
+        if thumb.contains([x, y]) {
+            self.grab = Some(y - thumb.y);
+        } else if y < thumb.y {
+            self.scroll_by(-self.rect.h);
+        } else {
+            self.scroll_by(self.rect.h);
+        }
+        true
+    }
+
+    /// Lets go of the thumb. Returns whether it was being dragged, so the release isn't taken
+    /// for a click on whatever is underneath.
+    pub fn mouse_button_up_event(&mut self, button: MouseButton) -> bool {
+        button == MouseButton::Left && self.grab.take().is_some()
+    }
+
+    /// Scrolls if the mouse is over the panel. `y` is how many notches the wheel turned, up
+    /// being positive. Returns whether the panel scrolled.
+    pub fn mouse_wheel_event(&mut self, y: f32) -> bool {
+        if self.hovered {
+            self.scroll_by(-y * WHEEL_STEP);
+        }
+        self.hovered
+    }
+}
+
+/// `count` rectangles of the same size side by side, with `gap` between them, centred on `x`.
+pub fn row(count: usize, size: (f32, f32), gap: f32, x: f32, y: f32) -> Vec<graphics::Rect> {
+    let width = count as f32 * size.0 + count.saturating_sub(1) as f32 * gap;
+    let left = x - width / 2.0;
+    (0..count)
+        .map(|i| graphics::Rect::new(left + i as f32 * (size.0 + gap), y, size.0, size.1))
+        .collect()
+}
+
+/// `count` rectangles of the same size stacked from `top` down, with `gap` between them,
+/// centred on `x`.
+pub fn column(count: usize, size: (f32, f32), gap: f32, x: f32, top: f32) -> Vec<graphics::Rect> {
+    (0..count)
+        .map(|i| {
+            graphics::Rect::new(
+                x - size.0 / 2.0,
+                top + i as f32 * (size.1 + gap),
+                size.0,
+                size.1,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32) -> graphics::Rect {
+        graphics::Rect::new(x, y, 100.0, 50.0)
+    }
+
+    fn click<A: Copy>(buttons: &mut Buttons<A>, x: f32, y: f32) -> Option<A> {
+        buttons.mouse_button_down_event(MouseButton::Left, x, y);
+        buttons.mouse_button_up_event(MouseButton::Left, x, y);
+        buttons.take_choice()
+    }
+
+    #[test]
+    fn clicks_go_to_the_topmost_button() {
+        let mut buttons = Buttons::new(vec![
+            Button::new(rect(0.0, 0.0), "Below", 1),
+            Button::new(rect(50.0, 0.0), "Above", 2),
+        ]);
+        assert_eq!(click(&mut buttons, 10.0, 10.0), Some(1));
+        assert_eq!(click(&mut buttons, 75.0, 10.0), Some(2));
+        assert_eq!(click(&mut buttons, 125.0, 10.0), Some(2));
+        assert_eq!(click(&mut buttons, 200.0, 10.0), None);
+    }
+
+    #[test]
+    fn disabled_buttons_block_clicks_without_taking_them() {
+        let mut buttons = Buttons::new(vec![
+            Button::new(rect(0.0, 0.0), "Below", 1),
+            Button::new(rect(50.0, 0.0), "Above", 2),
+        ]);
+        buttons.set_enabled(2, false);
+        assert_eq!(click(&mut buttons, 75.0, 10.0), None);
+        assert_eq!(click(&mut buttons, 10.0, 10.0), Some(1));
+        buttons.mouse_motion_event(75.0, 10.0);
+        assert_eq!(buttons.hovered, None);
+    }
+
+    #[test]
+    fn a_click_needs_the_press_and_release_on_the_same_button() {
+        let mut buttons = Buttons::new(vec![
+            Button::new(rect(0.0, 0.0), "One", 1),
+            Button::new(rect(0.0, 100.0), "Two", 2),
+        ]);
+        buttons.mouse_button_down_event(MouseButton::Left, 10.0, 10.0);
+        buttons.mouse_button_up_event(MouseButton::Left, 10.0, 110.0);
+        assert_eq!(buttons.take_choice(), None);
+
+        buttons.mouse_button_up_event(MouseButton::Left, 10.0, 10.0);
+        assert_eq!(buttons.take_choice(), None);
+        buttons.mouse_button_down_event(MouseButton::Right, 10.0, 10.0);
+        buttons.mouse_button_up_event(MouseButton::Right, 10.0, 10.0);
+        assert_eq!(buttons.take_choice(), None);
+    }
+
+    #[test]
+    fn taps_near_a_button_pick_it() {
+        let mut buttons = Buttons::new(vec![
+            Button::new(rect(0.0, 0.0), "One", 1),
+            Button::new(rect(0.0, 60.0), "Two", 2),
+        ]);
+        assert_eq!(click(&mut buttons, 110.0, 20.0), None);
+
+        buttons.set_touch(true);
+        assert_eq!(click(&mut buttons, 110.0, 20.0), Some(1));
+        // a tap on a button goes to it, even if it's near one drawn over it
+        assert_eq!(click(&mut buttons, 50.0, 48.0), Some(1));
+        assert_eq!(click(&mut buttons, 50.0, 55.0), Some(2));
+        assert!(!buttons.contains(150.0, 20.0));
+    }
+
+    #[test]
+    fn tab_skips_disabled_buttons() {
+        let mut buttons = Buttons::new(vec![
+            Button::new(rect(0.0, 0.0), "One", 1),
+            Button::new(rect(0.0, 100.0), "Two", 2),
+            Button::new(rect(0.0, 200.0), "Three", 3),
+        ]);
+        buttons.set_enabled(2, false);
+        assert_eq!(buttons.focused(), Some(1));
+        assert!(buttons.key_down_event(KeyCode::Tab, KeyMods::NONE));
+        assert_eq!(buttons.focused(), Some(3));
+        buttons.key_down_event(KeyCode::Tab, KeyMods::NONE);
+        assert_eq!(buttons.focused(), Some(1));
+        buttons.key_down_event(KeyCode::Tab, KeyMods::SHIFT);
+        assert_eq!(buttons.focused(), Some(3));
+
+        assert!(buttons.key_down_event(KeyCode::Return, KeyMods::NONE));
+        assert_eq!(buttons.take_choice(), Some(3));
+        assert!(!buttons.key_down_event(KeyCode::A, KeyMods::NONE));
+
+        // disabling the focused button moves the focus on
+        buttons.set_enabled(3, false);
+        assert_eq!(buttons.focused(), Some(1));
+        buttons.set_enabled(1, false);
+        assert_eq!(buttons.focused(), None);
+        assert!(!buttons.key_down_event(KeyCode::Return, KeyMods::NONE));
+    }
+
+    /// Applies edits one after the other, starting from an empty field.
+    fn type_in(edits: &[Edit], max_length: usize, allowed: fn(char) -> bool) -> (String, usize) {
+        edits
+            .iter()
+            .fold((String::new(), 0), |(text, cursor), &change| {
+                edit(&text, cursor, change, max_length, allowed)
+            })
+    }
+
+    #[test]
+    fn typing_goes_in_at_the_caret() {
+        let edits = [
+            Edit::Type("lunh"),
+            Edit::Left,
+            Edit::Type("c"),
+            Edit::End,
+            Edit::Type("!"),
+        ];
+        assert_eq!(type_in(&edits, 32, any_char), ("lunch!".to_string(), 6));
+
+        let edits = [Edit::Type("åäö"), Edit::Home, Edit::Type("x"), Edit::Right];
+        assert_eq!(type_in(&edits, 32, any_char), ("xåäö".to_string(), 2));
+    }
+
+    #[test]
+    fn deleting_on_either_side_of_the_caret() {
+        let edits = [Edit::Type("lunch"), Edit::Left, Edit::Left, Edit::Backspace];
+        assert_eq!(type_in(&edits, 32, any_char), ("luch".to_string(), 2));
+
+        let edits = [
+            Edit::Type("lunch"),
+            Edit::Home,
+            Edit::Delete,
+            Edit::Backspace,
+        ];
+        assert_eq!(type_in(&edits, 32, any_char), ("unch".to_string(), 0));
+
+        let edits = [Edit::Type("ab"), Edit::Delete, Edit::Right, Edit::Right];
+        assert_eq!(type_in(&edits, 32, any_char), ("ab".to_string(), 2));
+    }
+
+    #[test]
+    fn typing_respects_the_length_and_filter() {
+        let edits = [Edit::Type("fredags schack"), Edit::Home, Edit::Type("ab")];
+        assert_eq!(
+            type_in(&edits, 10, no_spaces),
+            ("fredagssch".to_string(), 0)
+        );
+
+        // pasted text with a line break or tab in it keeps the rest
+        let edits = [Edit::Type("a\tb\nc")];
+        assert_eq!(type_in(&edits, 32, any_char), ("abc".to_string(), 3));
+    }
+
+    fn panel() -> ScrollPanel {
+        let mut panel = ScrollPanel::new(graphics::Rect::new(0.0, 100.0, 200.0, 400.0), true);
+        panel.set_content_height(1000.0);
+        panel
+    }
+
+    #[test]
+    fn scrolling_stops_at_either_end_of_the_content() {
+        let mut panel = panel();
+        assert_eq!(panel.offset(), 600.0);
+        panel.scroll_by(100.0);
+        assert_eq!(panel.offset(), 600.0);
+        panel.scroll_to(-50.0);
+        assert_eq!(panel.offset(), 0.0);
+
+        // content that fits doesn't scroll at all, and neither does content that shrank
+        panel.scroll_to(500.0);
+        panel.set_content_height(300.0);
+        assert_eq!(panel.offset(), 0.0);
+        panel.scroll_by(100.0);
+        assert_eq!(panel.offset(), 0.0);
+    }
+
+    #[test]
+    fn a_pinned_panel_follows_new_content_until_scrolled_up() {
+        let mut panel = panel();
+        panel.set_content_height(1200.0);
+        assert_eq!(panel.offset(), 800.0);
+
+        panel.scroll_by(-100.0);
+        panel.set_content_height(1400.0);
+        assert_eq!(panel.offset(), 700.0);
+
+        // scrolling back down pins it again
+        panel.scroll_to(f32::MAX);
+        panel.set_content_height(1500.0);
+        assert_eq!(panel.offset(), 1100.0);
+
+        let mut loose = ScrollPanel::new(graphics::Rect::new(0.0, 0.0, 200.0, 400.0), false);
+        loose.set_content_height(1000.0);
+        assert_eq!(loose.offset(), 0.0);
+    }
+
+    #[test]
+    fn the_thumb_shows_how_much_is_in_view_and_where() {
+        let mut panel = panel();
+        let thumb = panel.thumb().unwrap();
+        // 400 of 1000 in view, scrolled to the bottom
+        assert_eq!((thumb.x, thumb.w), (192.0, SCROLLBAR_WIDTH));
+        assert_eq!((thumb.y, thumb.h), (340.0, 160.0));
+
+        panel.scroll_to(0.0);
+        assert_eq!(panel.thumb().unwrap().y, 100.0);
+
+        // a long content still leaves a thumb that can be grabbed, and none is needed if it fits
+        panel.set_content_height(100_000.0);
+        assert_eq!(panel.thumb().unwrap().h, MIN_THUMB);
+        panel.set_content_height(400.0);
+        assert_eq!(panel.thumb(), None);
+    }
+
+    #[test]
+    fn dragging_the_thumb_scrolls_the_content() {
+        let mut panel = panel();
+        panel.scroll_to(0.0);
+        assert!(panel.mouse_button_down_event(MouseButton::Left, 196.0, 110.0));
+        // halfway down the 240 pixels the thumb can move is halfway through the content
+        panel.mouse_motion_event(196.0, 230.0);
+        assert_eq!(panel.offset(), 300.0);
+        // and it stops at the end however far the mouse goes
+        panel.mouse_motion_event(196.0, 900.0);
+        assert_eq!(panel.offset(), 600.0);
+        assert!(panel.mouse_button_up_event(MouseButton::Left));
+
+        // clicks beside the scrollbar are left for the content
+        assert!(!panel.mouse_button_down_event(MouseButton::Left, 100.0, 200.0));
+        assert!(!panel.mouse_button_up_event(MouseButton::Left));
+    }
+
+    #[test]
+    fn rows_and_columns_are_centred() {
+        let rects = row(2, (100.0, 50.0), 20.0, 500.0, 10.0);
+        assert_eq!(rects[0], graphics::Rect::new(390.0, 10.0, 100.0, 50.0));
+        assert_eq!(rects[1], graphics::Rect::new(510.0, 10.0, 100.0, 50.0));
+
+        let rects = column(3, (100.0, 50.0), 20.0, 500.0, 10.0);
+        assert_eq!(rects[2], graphics::Rect::new(450.0, 150.0, 100.0, 50.0));
+    }
+}
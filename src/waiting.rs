@@ -0,0 +1,447 @@
+/**
+ * The screen shown once connected to the server, while waiting for an opponent to join the room.
+ * The lobby handshake is polled each frame, so the window keeps drawing, and the wait can be
+ * cancelled to go back to the connection screen. Instead of waiting, the game can be started by
+ * correspondence, for the opponent to pick up whenever they connect.
+ */
+use crate::app::SCREEN_SIZE;
+use crate::correspondence::Correspondence;
+use crate::history::format_duration;
+use crate::invite::Invite;
+use crate::net::Handshake;
+use crate::session::{Connection, Session};
+use chess_template::Colour;
+use ggez::{event, graphics, Context, GameResult};
+use log::{info, warn};
+use rand::rngs::StdRng;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// What the player can do while waiting.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WaitingAction {
+    CopyInvite,
+    PlayAsync,
+    Cancel,
+}
+
+const ACTIONS: [WaitingAction; 3] = [
+    WaitingAction::CopyInvite,
+    WaitingAction::PlayAsync,
+    WaitingAction::Cancel,
+];
+
+impl WaitingAction {
+    fn label(self) -> &'static str {
+        match self {
+            WaitingAction::CopyInvite => "Copy invite",
+            WaitingAction::PlayAsync => "Play asynchronously",
+            WaitingAction::Cancel => "Cancel",
+        }
+    }
+}
+
+/// Size of a button, the gap between them, and the top edge of the row.
+const BUTTON_SIZE: (f32, f32) = (240.0, 50.0);
+const BUTTON_GAP: f32 = 20.0;
+const BUTTON_TOP: f32 = 480.0;
+
+/// Number of dots in the spinner, and how long each stays lit.
+const SPINNER_DOTS: u128 = 8;
+const SPINNER_STEP_MS: u128 = 125;
+
+pub struct WaitingScreen {
+    connection: Option<Connection>, // until the opponent joins and it becomes the game's
+    handshake: Result<Handshake, String>, // or why announcing ourselves failed
+    started: Instant,               // for the time spent waiting
+    selected: usize,                // highlighted button
+    notice: Option<String>,         // how copying the invite went
+    leaving: bool,                  // Cancel was picked, go back to the connection screen
+    record: Option<PathBuf>,        // where a correspondence game in the room is kept, if anywhere
+    asynchronous: bool,             // the game is to start by correspondence, on the next update
+}
+
+impl WaitingScreen {
+    /// Announces us in the room, with our avatar if we picked one, and starts waiting for an
+    /// opponent. A correspondence game started or joined here is kept in `record`.
+    pub fn new(
+        connection: Connection,
+        avatar: Option<u8>,
+        record: Option<PathBuf>,
+        rng: &mut StdRng,
+    ) -> WaitingScreen {
+        let handshake = Handshake::start(&connection.room_name, avatar, &connection.sender, rng);
+        WaitingScreen {
+            connection: Some(connection),
+            handshake,
+            started: Instant::now(),
+            selected: 0,
+            notice: None,
+            leaving: false,
+            record,
+            asynchronous: false,
+        }
+    }
+
+    /// Server and room being waited in.
+    pub fn server(&self) -> &str {
+        self.connection.as_ref().map_or("", |c| c.server.as_str())
+    }
+
+    pub fn room(&self) -> &str {
+        self.connection
+            .as_ref()
+            .map_or("", |c| c.room_name.as_str())
+    }
+
+    /// Whether the player gave up waiting. Dropping the screen closes the connection.
+    pub fn leaving(&self) -> bool {
+        self.leaving
+    }
+
+    /// Checks for the opponent. Returns the game once they've joined, or once it's started by
+    /// correspondence, or why waiting failed.
+    pub fn update(&mut self) -> Result<Option<Session>, String> {
+        if self.asynchronous {
+            self.asynchronous = false;
+            return Ok(self.correspondence(Colour::White));
+        }
+        let connection = match &self.connection {
+            Some(connection) => connection,
+            None => return Ok(None),
+        };
+        let handshake = self.handshake.as_ref().map_err(Clone::clone)?;
+
+        match handshake.poll(&connection.sender, &connection.receiver)? {
+            Some(paired) if paired.correspondence && self.record.is_some() => {
+                info!("The opponent started a correspondence game here");
+                Ok(self.correspondence(paired.colour))
+            }
+            Some(paired) => Ok(self.connection.take().map(|c| c.into_session(paired))),
+            None => Ok(None),
+        }
+    }
+
+    /// The game by correspondence, playing `colour`, with no moves yet.
+    fn correspondence(&mut self, colour: Colour) -> Option<Session> {
+        let game = Correspondence::new(self.record.clone()?, colour);
+        let connection = self.connection.take()?;
+        Some(connection.into_correspondence(game))
+    }
+
+    /// What to send the opponent so they can find the game.
+    fn invite(&self) -> Invite {
+        Invite {
+            server: self.server().to_string(),
+            room: self.room().to_string(),
+        }
+    }
+
+    fn copy_invite(&mut self) {
+        let invite = self.invite().to_string();
+        let copied = arboard::Clipboard::new().and_then(|mut c| c.set_text(invite.clone()));
+        self.notice = Some(match copied {
+            Ok(()) => "Copied, paste it to your opponent".to_string(),
+            Err(err) => {
+                // it's still in the log for copying by hand
+                warn!("Couldn't copy the invite: {}", err);
+                info!("{}", invite);
+                "Couldn't copy the invite, it's in the log instead".to_string()
+            }
+        });
+    }
+
+    fn choose(&mut self, i: usize) {
+        self.selected = i;
+        match ACTIONS[i] {
+            WaitingAction::CopyInvite => self.copy_invite(),
+            WaitingAction::PlayAsync if self.record.is_none() => {
+                self.notice = Some(
+                    "There's nowhere to keep a correspondence game on this computer".to_string(),
+                )
+            }
+            WaitingAction::PlayAsync => self.asynchronous = true,
+            WaitingAction::Cancel => self.leaving = true,
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
+
+        let waited = format_duration(self.started.elapsed());
+        let lines = [
+            (format!("Room {}", self.room()), 40.0),
+            (format!("on {}", self.server()), 22.0),
+            ("Waiting for an opponent to join".to_string(), 26.0),
+            (waited, 26.0),
+        ];
+        let mut top = 100.0;
+        for (line, size) in lines {
+            let text = graphics::Text::new(
+                graphics::TextFragment::from(line).scale(graphics::PxScale { x: size, y: size }),
+            );
+            let width = text.dimensions(ctx).w;
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([(SCREEN_SIZE.0 - width) / 2.0, top]),
+            )?;
+            top += size + 16.0;
+        }
+
+        let hint = graphics::Text::new(
+            graphics::TextFragment::from(
+                "Or play asynchronously: they'll get your move when they connect",
+            )
+            .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+        );
+        let width = hint.dimensions(ctx).w;
+        graphics::draw(
+            ctx,
+            &hint,
+            graphics::DrawParam::default()
+                .color([0.15, 0.15, 0.15, 1.0].into())
+                .dest([(SCREEN_SIZE.0 - width) / 2.0, BUTTON_TOP - 40.0]),
+        )?;
+
+        // a ring of dots with one lit, going round
+        let lit = self.started.elapsed().as_millis() / SPINNER_STEP_MS % SPINNER_DOTS;
+        for i in 0..SPINNER_DOTS {
+            let angle = i as f32 / SPINNER_DOTS as f32 * std::f32::consts::TAU;
+            let centre = [
+                SCREEN_SIZE.0 / 2.0 + 40.0 * angle.sin(),
+                360.0 - 40.0 * angle.cos(),
+            ];
+            let colour = if i == lit {
+                [0.2, 0.4, 1.0, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let dot = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                centre,
+                8.0,
+                0.1,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &dot, graphics::DrawParam::default())?;
+        }
+
+        for (i, action) in ACTIONS.iter().enumerate() {
+            let rect = button_rect(i);
+            let colour = if i == self.selected {
+                [0.3, 0.6, 0.3, 1.0]
+            } else {
+                [0.3, 0.3, 0.3, 1.0]
+            };
+            let button = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                colour.into(),
+            )?;
+            graphics::draw(ctx, &button, graphics::DrawParam::default())?;
+
+            let label = graphics::Text::new(
+                graphics::TextFragment::from(action.label())
+                    .scale(graphics::PxScale { x: 24.0, y: 24.0 }),
+            );
+            let label_size = label.dimensions(ctx);
+            graphics::draw(
+                ctx,
+                &label,
+                graphics::DrawParam::default()
+                    .color([1.0, 1.0, 1.0, 1.0].into())
+                    .dest([
+                        rect.x + (rect.w - label_size.w) / 2.0,
+                        rect.y + (rect.h - label_size.h) / 2.0,
+                    ]),
+            )?;
+        }
+
+        if let Some(notice) = &self.notice {
+            let mut text = graphics::Text::new(
+                graphics::TextFragment::from(notice.as_str())
+                    .scale(graphics::PxScale { x: 20.0, y: 20.0 }),
+            );
+            text.set_bounds(
+                [SCREEN_SIZE.0 - 40.0, f32::INFINITY],
+                graphics::Align::Center,
+            );
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([20.0, BUTTON_TOP + BUTTON_SIZE.1 + 20.0]),
+            )?;
+        }
+
+        graphics::present(ctx)
+    }
+
+    pub fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        if let Some(i) = (0..ACTIONS.len()).find(|&i| button_rect(i).contains([x, y])) {
+            self.selected = i;
+        }
+    }
+
+    pub fn mouse_button_up_event(&mut self, button: event::MouseButton, x: f32, y: f32) {
+        if button != event::MouseButton::Left {
+            return;
+        }
+        if let Some(i) = (0..ACTIONS.len()).find(|&i| button_rect(i).contains([x, y])) {
+            self.choose(i);
+        }
+    }
+
+    pub fn key_down_event(&mut self, key: event::KeyCode) {
+        let count = ACTIONS.len();
+        match key {
+            event::KeyCode::Left => self.selected = (self.selected + count - 1) % count,
+            event::KeyCode::Right | event::KeyCode::Tab => {
+                self.selected = (self.selected + 1) % count
+            }
+            event::KeyCode::Return | event::KeyCode::NumpadEnter | event::KeyCode::Space => {
+                self.choose(self.selected)
+            }
+            event::KeyCode::Escape => self.leaving = true,
+            _ => (),
+        }
+    }
+}
+
+/// Where a button is on the screen.
+fn button_rect(i: usize) -> graphics::Rect {
+    let count = ACTIONS.len() as f32;
+    let width = count * BUTTON_SIZE.0 + (count - 1.0) * BUTTON_GAP;
+    graphics::Rect::new(
+        (SCREEN_SIZE.0 - width) / 2.0 + i as f32 * (BUTTON_SIZE.0 + BUTTON_GAP),
+        BUTTON_TOP,
+        BUTTON_SIZE.0,
+        BUTTON_SIZE.1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::CHANNEL_CAPACITY;
+    use crate::protocol::Message;
+    use chess_template::Colour;
+    use rand::SeedableRng;
+    use std::sync::mpsc;
+
+    #[test]
+    fn the_game_starts_once_an_opponent_joins() {
+        let (sender, sent) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (inbox, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let connection = Connection {
+            sender,
+            receiver,
+            server: "127.0.0.1:6000".to_string(),
+            room_name: "lobby".to_string(),
+        };
+        let mut screen =
+            WaitingScreen::new(connection, Some(4), None, &mut StdRng::seed_from_u64(1));
+
+        // our own announcement comes back first, and doesn't count as an opponent
+        let ours = sent.try_recv().unwrap();
+        inbox.send(ours.clone()).unwrap();
+        assert!(screen.update().unwrap().is_none());
+
+        let id = match Message::parse(&ours) {
+            Ok(Message::Join {
+                id,
+                avatar: Some(4),
+                ..
+            }) => id,
+            _ => panic!("expected a join with our avatar, got {:?}", ours),
+        };
+        let theirs = Message::Join {
+            room: "lobby".to_string(),
+            id: id.wrapping_add(1),
+            avatar: Some(6),
+        };
+        inbox.send(theirs.to_string()).unwrap();
+        let session = screen.update().unwrap().unwrap();
+        let expected = if id < id.wrapping_add(1) {
+            Colour::White
+        } else {
+            Colour::Black
+        };
+        assert_eq!(session.color, expected);
+        assert_eq!(session.server.as_deref(), Some("127.0.0.1:6000"));
+        assert_eq!(session.opponent_avatar, Some(6));
+        // the opponent is told we're here too
+        assert_eq!(sent.try_recv().unwrap(), ours);
+    }
+
+    #[test]
+    fn losing_the_connection_while_waiting_fails() {
+        let (sender, _sent) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (inbox, receiver) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let connection = Connection {
+            sender,
+            receiver,
+            server: "127.0.0.1:6000".to_string(),
+            room_name: "lobby".to_string(),
+        };
+        let mut screen = WaitingScreen::new(connection, None, None, &mut StdRng::seed_from_u64(1));
+        drop(inbox);
+        assert!(screen.update().is_err());
+    }
+
+    #[test]
+    fn a_correspondence_game_starts_as_white_or_is_joined_as_black() {
+        let waiting = |record: Option<PathBuf>| {
+            let (sender, sent) = mpsc::sync_channel(CHANNEL_CAPACITY);
+            let (inbox, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+            let connection = Connection {
+                sender,
+                receiver,
+                server: "127.0.0.1:6000".to_string(),
+                room_name: "lobby".to_string(),
+            };
+            let screen =
+                WaitingScreen::new(connection, None, record, &mut StdRng::seed_from_u64(1));
+            (screen, inbox, sent)
+        };
+        let path = PathBuf::from("lobby.txt");
+        let play_async = ACTIONS
+            .iter()
+            .position(|&action| action == WaitingAction::PlayAsync)
+            .unwrap();
+
+        // starting one plays white, with nothing to wait for
+        let (mut screen, _inbox, _sent) = waiting(Some(path.clone()));
+        screen.choose(play_async);
+        let session = screen.update().unwrap().unwrap();
+        assert_eq!(session.color, Colour::White);
+        assert_eq!(
+            session.correspondence,
+            Some(Correspondence::new(path.clone(), Colour::White))
+        );
+
+        // someone waiting when its sync offer comes joins it as black
+        let (mut screen, inbox, _sent) = waiting(Some(path.clone()));
+        let offer = Message::SyncOffer {
+            room: "lobby".to_string(),
+            counter: 2,
+            hash: 7,
+        };
+        inbox.send(offer.to_string()).unwrap();
+        let session = screen.update().unwrap().unwrap();
+        assert_eq!(session.color, Colour::Black);
+        assert_eq!(session.correspondence.unwrap().colour, Colour::Black);
+
+        // with nowhere to keep it, it's not started
+        let (mut screen, _inbox, _sent) = waiting(None);
+        screen.choose(play_async);
+        assert!(screen.update().unwrap().is_none());
+        assert!(screen.notice.is_some());
+    }
+}
@@ -0,0 +1,182 @@
+/**
+ * Where the window is on the desktop and how big it is, remembered between runs so it opens as
+ * it was left, and how it's framed: without a title bar and border, or kept above other windows, e.g. to pin
+ * a small game over other work.
+ */
+use crate::config::Config;
+use crate::layout::MINI_WINDOW;
+use ggez::winit::dpi::{PhysicalPosition, PhysicalSize};
+use ggez::winit::monitor::MonitorHandle;
+use ggez::{graphics, Context};
+
+/// How much of the window has to be on a monitor for it to be dragged back, in pixels.
+const MIN_VISIBLE: i32 = 100;
+
+/// A monitor's left and top edges and its width and height, in physical pixels.
+type Area = (i32, i32, i32, i32);
+
+/// How the window is framed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub borderless: bool,    // no title bar or border
+    pub always_on_top: bool, // kept above other windows
+}
+
+impl Style {
+    /// The style the settings ask for.
+    pub fn from_config(config: &Config) -> Style {
+        Style {
+            borderless: config.borderless,
+            always_on_top: config.always_on_top,
+        }
+    }
+}
+
+/// Frames the window in a style. ggez only sets these when the window is made, so they're
+/// changed on the window itself.
+pub fn set_style(ctx: &Context, style: Style) {
+    let window = graphics::window(ctx);
+    window.set_decorations(!style.borderless);
+    window.set_always_on_top(style.always_on_top);
+}
+
+/// Where the window's top-left corner is, if the platform says.
+pub fn position(ctx: &Context) -> Option<(i32, i32)> {
+    let pos = graphics::window(ctx).outer_position().ok()?;
+    Some((pos.x, pos.y))
+}
+
+/// Moves the window to a saved position. A position that would leave the window out of reach,
+/// e.g. one saved on an external monitor that's since been unplugged, is pulled onto the monitor
+/// the window opened on.
+pub fn restore(ctx: &Context, pos: (i32, i32)) {
+    let window = graphics::window(ctx);
+    let size = window.inner_size();
+    let size = (size.width as i32, size.height as i32);
+    let monitors = window
+        .available_monitors()
+        .map(|monitor| area(&monitor))
+        .collect::<Vec<Area>>();
+    let current = window.current_monitor().map(|monitor| area(&monitor));
+
+    let (x, y) = clamp(pos, size, &monitors, current);
+    window.set_outer_position(PhysicalPosition::new(x, y));
+}
+
+/// How big the window is inside its frame, in physical pixels.
+pub fn size(ctx: &Context) -> (u32, u32) {
+    let size = graphics::window(ctx).inner_size();
+    (size.width, size.height)
+}
+
+/// Makes the window a saved size, cut down to fit the monitor it opened on, e.g. one saved on a
+/// big external monitor and restored on a laptop, and never smaller than the mini layout.
+pub fn restore_size(ctx: &Context, size: (u32, u32)) {
+    let window = graphics::window(ctx);
+    let monitor = window.current_monitor().map(|monitor| area(&monitor));
+    let (width, height) = fit_size(size, monitor);
+    window.set_inner_size(PhysicalSize::new(width, height));
+}
+
+fn area(monitor: &MonitorHandle) -> Area {
+    let pos = monitor.position();
+    let size = monitor.size();
+    (pos.x, pos.y, size.width as i32, size.height as i32)
+}
+
+/// `pos` if a window of `size` placed there can be reached on one of the monitors, otherwise the
+/// nearest position that keeps it on `fallback`, or on the first monitor. With nothing known
+/// about the monitors, `pos` is trusted.
+fn clamp(
+    pos: (i32, i32),
+    size: (i32, i32),
+    monitors: &[Area],
+    fallback: Option<Area>,
+) -> (i32, i32) {
+    let (x, y) = pos;
+    let reachable = monitors.iter().any(|&(left, top, width, height)| {
+        let overlap = (x + size.0).min(left + width) - x.max(left);
+        // the title bar is along the top, so that's the edge that has to be on screen
+        overlap >= MIN_VISIBLE.min(size.0)
+            && y >= top
+            && y <= top + height - MIN_VISIBLE.min(size.1)
+    });
+    if reachable {
+        return pos;
+    }
+
+    match fallback.or(monitors.first().copied()) {
+        Some((left, top, width, height)) => (
+            x.clamp(left, (left + width - size.0).max(left)),
+            y.clamp(top, (top + height - size.1).max(top)),
+        ),
+        None => pos,
+    }
+}
+
+/// `size` cut down to `monitor`, if anything's known about it, and made up to the mini layout.
+fn fit_size(size: (u32, u32), monitor: Option<Area>) -> (u32, u32) {
+    let (mut width, mut height) = size;
+    if let Some((_, _, monitor_width, monitor_height)) = monitor {
+        width = width.min(monitor_width.max(0) as u32);
+        height = height.min(monitor_height.max(0) as u32);
+    }
+    (
+        width.max(MINI_WINDOW.0 as u32),
+        height.max(MINI_WINDOW.1 as u32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: (i32, i32) = (1000, 700);
+    const LAPTOP: Area = (0, 0, 1920, 1080);
+    const EXTERNAL: Area = (1920, 0, 2560, 1440);
+
+    #[test]
+    fn reachable_positions_are_kept() {
+        assert_eq!(clamp((100, 50), WINDOW, &[LAPTOP], Some(LAPTOP)), (100, 50));
+        assert_eq!(
+            clamp((2500, 300), WINDOW, &[LAPTOP, EXTERNAL], Some(LAPTOP)),
+            (2500, 300)
+        );
+        // mostly off the right edge, but the title bar can still be grabbed
+        assert_eq!(
+            clamp((1700, 50), WINDOW, &[LAPTOP], Some(LAPTOP)),
+            (1700, 50)
+        );
+    }
+
+    #[test]
+    fn positions_on_unplugged_monitors_are_pulled_back() {
+        assert_eq!(
+            clamp((2500, 300), WINDOW, &[LAPTOP], Some(LAPTOP)),
+            (920, 300)
+        );
+        assert_eq!(clamp((-3000, -20), WINDOW, &[LAPTOP], Some(LAPTOP)), (0, 0));
+        assert_eq!(clamp((2500, 300), WINDOW, &[LAPTOP], None), (920, 300));
+    }
+
+    #[test]
+    fn windows_larger_than_the_monitor_go_in_the_corner() {
+        let small = (0, 0, 800, 600);
+        assert_eq!(clamp((5000, 5000), WINDOW, &[small], Some(small)), (0, 0));
+    }
+
+    #[test]
+    fn positions_are_trusted_without_monitor_information() {
+        assert_eq!(clamp((2500, 300), WINDOW, &[], None), (2500, 300));
+    }
+
+    #[test]
+    fn sizes_saved_on_a_bigger_monitor_are_cut_down() {
+        assert_eq!(fit_size((1000, 700), Some(LAPTOP)), (1000, 700));
+        assert_eq!(fit_size((2400, 1400), Some(LAPTOP)), (1920, 1080));
+        assert_eq!(fit_size((2400, 1400), None), (2400, 1400));
+        // never too small to play on, whatever was saved
+        assert_eq!(fit_size((0, 0), Some(LAPTOP)), (360, 400));
+        assert_eq!(fit_size((5000, 10), Some((0, 0, 0, 0))), (360, 400));
+    }
+}